@@ -1289,6 +1289,7 @@ impl<'a> OpEmitter<'a> {
                 self.push_immediate(imm, span);
                 self.shl_u64(span);
             }
+            Type::U128 | Type::I128 => self.shl_imm_int128(imm.as_u32().unwrap(), span),
             Type::U32 => self.shl_imm_u32(imm.as_u32().unwrap(), span),
             Type::I32 => self.shl_imm_u32(imm.as_u32().unwrap(), span),
             ty @ (Type::U16 | Type::I16 | Type::U8 | Type::I8) => {
@@ -1333,6 +1334,7 @@ impl<'a> OpEmitter<'a> {
                 self.shr_u64(span);
             }
             Type::I64 => self.shr_imm_i64(imm.as_u32().unwrap(), span),
+            Type::U128 => self.shr_imm_u128(imm.as_u32().unwrap(), span),
             Type::U32 | Type::U16 | Type::U8 => self.shr_imm_u32(imm.as_u32().unwrap(), span),
             Type::I32 => self.shr_imm_i32(imm.as_u32().unwrap(), span),
             ty if !ty.is_integer() => {
@@ -22,30 +22,19 @@ impl<'a> OpEmitter<'a> {
     ///
     /// An u128 value consists of 4 32-bit limbs
     pub fn push_u128(&mut self, value: u128, span: SourceSpan) {
-        let bytes = value.to_le_bytes();
-        let hi = u64::from_le_bytes([
-            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
-        ]);
-        let lo = u64::from_le_bytes([
-            bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15],
-        ]);
-        self.push_u64(lo, span);
-        self.push_u64(hi, span);
+        // The most significant limb is on top of the stack, so push the low 64 bits first
+        self.push_u64(value as u64, span);
+        self.push_u64((value >> 64) as u64, span);
     }
 
     /// Push an i128 value on the operand stack
     ///
     /// An i128 value consists of 4 32-bit limbs
     pub fn push_i128(&mut self, value: i128, span: SourceSpan) {
-        let bytes = value.to_le_bytes();
-        let hi = u64::from_le_bytes([
-            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
-        ]);
-        let lo = u64::from_le_bytes([
-            bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15],
-        ]);
-        self.push_u64(lo, span);
-        self.push_u64(hi, span);
+        // The most significant limb is on top of the stack, so push the low 64 bits first
+        let value = value as u128;
+        self.push_u64(value as u64, span);
+        self.push_u64((value >> 64) as u64, span);
     }
 
     /// Convert an i128 value to a field element value.
@@ -200,6 +189,109 @@ impl<'a> OpEmitter<'a> {
         }
     }
 
+    /// Pops a 128-bit value off the stack, `a`, and performs `a << <shift>`, discarding any bits
+    /// shifted out of the high limb.
+    ///
+    /// NOTE: This function does not validate the 128-bit value, that is left up to the caller.
+    pub fn shl_imm_int128(&mut self, shift: u32, span: SourceSpan) {
+        assert!(shift < 128, "invalid shift value: must be < 128, got {shift}");
+        let limbs = (shift / 32) as u8;
+        let bits = shift % 32;
+        // Shift whole limbs by dropping the high limbs, and zero-filling the low limbs
+        //
+        // [a3, a2, a1, a0] => [a2, a1, a0, 0] when shifting by 32
+        self.emit_n(limbs as usize, Op::Drop, span);
+        for _ in 0..limbs {
+            self.emit(Op::PushU32(0), span);
+            self.movdn_or_swap(4 - limbs, span);
+        }
+        if bits == 0 {
+            return;
+        }
+        // Shift the remaining bits, carrying the high bits of each limb into the limb above it,
+        // starting from the low limb.
+        //
+        // [a3, a2, a1, a0] => [a0, a3, a2, a1] => [c1, a3, a2, a1, b0]
+        self.emit_all(
+            &[
+                Op::Movup(3),
+                Op::Dup(0),
+                Op::U32ShlImm(bits),
+                Op::Movdn(4),
+                Op::U32ShrImm(32 - bits),
+            ],
+            span,
+        );
+        // [c1, a3, a2, a1, b0] => [c2, a3, a2, b1, b0] => [c3, a3, b2, b1, b0]
+        for n in [3u8, 2] {
+            self.emit_all(
+                &[
+                    Op::Movup(n),
+                    Op::Dup(0),
+                    Op::U32ShlImm(bits),
+                    Op::Movup(2),
+                    Op::U32Or,
+                    Op::Movdn(n),
+                    Op::U32ShrImm(32 - bits),
+                ],
+                span,
+            );
+        }
+        // [c3, a3, b2, b1, b0] => [b3, b2, b1, b0]
+        self.emit_all(&[Op::Swap(1), Op::U32ShlImm(bits), Op::U32Or], span);
+    }
+
+    /// Pops a 128-bit value off the stack, `a`, and performs a logical `a >> <shift>`, i.e. the
+    /// high bits of the result are zero-filled.
+    ///
+    /// NOTE: This function does not validate the 128-bit value, that is left up to the caller.
+    pub fn shr_imm_u128(&mut self, shift: u32, span: SourceSpan) {
+        assert!(shift < 128, "invalid shift value: must be < 128, got {shift}");
+        let limbs = (shift / 32) as u8;
+        let bits = shift % 32;
+        // Shift whole limbs by dropping the low limbs, and zero-filling the high limbs
+        //
+        // [a3, a2, a1, a0] => [0, a3, a2, a1] when shifting by 32
+        for n in 0..limbs {
+            self.movup_or_swap(3 - n, span);
+            self.emit(Op::Drop, span);
+        }
+        self.emit_n(limbs as usize, Op::PushU32(0), span);
+        if bits == 0 {
+            return;
+        }
+        // Shift the remaining bits, carrying the low bits of each limb into the limb below it,
+        // starting from the low limb.
+        //
+        // [a3, a2, a1, a0] => [a3, a2, a1, b0] => [a3, a2, b1, b0] => [a3, b2, b1, b0]
+        for n in [3u8, 2, 1] {
+            self.movup_or_swap(n, span);
+            self.emit_all(
+                &[Op::U32ShrImm(bits), Op::Dup(n), Op::U32ShlImm(32 - bits), Op::U32Or],
+                span,
+            );
+            self.movdn_or_swap(n, span);
+        }
+        // [a3, b2, b1, b0] => [b3, b2, b1, b0]
+        self.emit(Op::U32ShrImm(bits), span);
+    }
+
+    /// Move the `n`th element of the stack to the top, where `n` is at least 1
+    fn movup_or_swap(&mut self, n: u8, span: SourceSpan) {
+        match n {
+            1 => self.emit(Op::Swap(1), span),
+            n => self.emit(Op::Movup(n), span),
+        }
+    }
+
+    /// Move the top of the stack down to the `n`th position, where `n` is at least 1
+    fn movdn_or_swap(&mut self, n: u8, span: SourceSpan) {
+        match n {
+            1 => self.emit(Op::Swap(1), span),
+            n => self.emit(Op::Movdn(n), span),
+        }
+    }
+
     /// Pop two i128 values, `b` and `a`, off the operand stack, and place the result of `a == b` on
     /// the stack.
     #[inline]
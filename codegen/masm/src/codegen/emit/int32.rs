@@ -588,7 +588,10 @@ impl<'a> OpEmitter<'a> {
                 Overflow::Unchecked => Op::Mul,
                 Overflow::Checked => return self.emit_all(&[Op::Mul, Op::U32Assert], span),
                 Overflow::Wrapping => Op::U32WrappingMul,
-                Overflow::Overflowing => Op::U32OverflowingMul,
+                // The overflow is given as the high 32 bits of the product, not a boolean
+                Overflow::Overflowing => {
+                    return self.emit_all(&[Op::U32OverflowingMul, Op::NeqImm(Felt::ZERO)], span)
+                }
             },
             span,
         );
@@ -637,7 +640,12 @@ impl<'a> OpEmitter<'a> {
                             )
                         }
                         Overflow::Wrapping => Op::U32WrappingMulImm(imm),
-                        Overflow::Overflowing => Op::U32OverflowingMulImm(imm),
+                        Overflow::Overflowing => {
+                            return self.emit_all(
+                                &[Op::U32OverflowingMulImm(imm), Op::NeqImm(Felt::ZERO)],
+                                span,
+                            )
+                        }
                     },
                     span,
                 );
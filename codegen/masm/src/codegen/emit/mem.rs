@@ -52,16 +52,37 @@ impl<'a> OpEmitter<'a> {
 
 /// Loads
 impl<'a> OpEmitter<'a> {
-    /// Load a value corresponding to the type of the given local, from the memory allocated for
-    /// that local.
+    /// Load a value of type `ty` from the memory allocated for the given local.
     ///
-    /// Internally, this pushes the address of the local on the stack, then delegates to
-    /// [OpEmitter::load]
-    pub fn load_local(&mut self, local: hir::LocalId, span: SourceSpan) {
-        let ty = self.function.local(local).ty.clone();
-        self.emit(Op::LocAddr(local), span);
-        self.push(Type::Ptr(Box::new(ty.clone())));
-        self.load(ty, span)
+    /// The type is given explicitly, as the local may be shared by several local variables, and
+    /// so be allocated with a larger type than the one being loaded.
+    ///
+    /// Locals are addressed natively, i.e. each local occupies one or more whole words of the
+    /// procedure's local memory, so no translation from the IR's byte-addressable address space
+    /// is required. Values of more than one element are loaded a word at a time.
+    pub fn load_local(&mut self, local: hir::LocalId, ty: Type, span: SourceSpan) {
+        assert!(!ty.is_zst(), "cannot load a zero-sized type from a local");
+        let size = ty.size_in_felts();
+        if size == 1 {
+            self.emit(Op::LocLoad(local), span);
+        } else {
+            // Load the words in reverse, so that the first word ends up on top of the stack
+            let num_words = size.div_ceil(4);
+            for i in (0..num_words).rev() {
+                self.emit_all(&[Op::Padw, Op::LocLoadw(local_word(local, i))], span);
+                // Drop the padding below the elements of a partial word
+                let len = core::cmp::min(4, size - (i * 4));
+                let movup = if len == 1 {
+                    Op::Swap(1)
+                } else {
+                    Op::Movup(len as u8)
+                };
+                for _ in len..4 {
+                    self.emit_all(&[movup, Op::Drop], span);
+                }
+            }
+        }
+        self.push(ty);
     }
 
     /// Load a value corresponding to the pointee type of a pointer operand on the stack.
@@ -77,9 +98,10 @@ impl<'a> OpEmitter<'a> {
                 self.emit_native_ptr(span);
                 match &ty {
                     Type::I128 => self.load_quad_word(None, span),
+                    ty if is_word(ty) => self.load_quad_word(None, span),
                     Type::I64 | Type::U64 => self.load_double_word(None, span),
                     Type::Felt => self.load_felt(None, span),
-                    Type::I32 | Type::U32 => self.load_word(None, span),
+                    Type::I32 | Type::U32 | Type::Ptr(_) => self.load_word(None, span),
                     ty @ (Type::I16 | Type::U16 | Type::U8 | Type::I8 | Type::I1) => {
                         self.load_word(None, span);
                         self.trunc_int32(ty.size_in_bits() as u32, span);
@@ -102,9 +124,10 @@ impl<'a> OpEmitter<'a> {
         let ptr = NativePtr::from_ptr(addr);
         match &ty {
             Type::I128 => self.load_quad_word(Some(ptr), span),
+            ty if is_word(ty) => self.load_quad_word(Some(ptr), span),
             Type::I64 | Type::U64 => self.load_double_word(Some(ptr), span),
             Type::Felt => self.load_felt(Some(ptr), span),
-            Type::I32 | Type::U32 => self.load_word(Some(ptr), span),
+            Type::I32 | Type::U32 | Type::Ptr(_) => self.load_word(Some(ptr), span),
             Type::I16 | Type::U16 | Type::U8 | Type::I8 | Type::I1 => {
                 self.load_word(Some(ptr), span);
                 self.trunc_int32(ty.size_in_bits() as u32, span);
@@ -155,6 +178,21 @@ impl<'a> OpEmitter<'a> {
         );
     }
 
+    /// Given a native pointer triple on the stack, as produced by [OpEmitter::emit_native_ptr],
+    /// assert that it refers to the first element of a word, leaving just the word address on
+    /// the stack.
+    fn emit_word_aligned_native_ptr(&mut self, span: SourceSpan) {
+        self.emit_all(
+            &[
+                // [offset, index, waddr]
+                Op::Swap(2),
+                Op::Assertz,
+                Op::Assertz,
+            ],
+            span,
+        );
+    }
+
     /// Load a field element from a naturally aligned address, either immediate or dynamic
     ///
     /// A native pointer triplet is expected on the stack if an immediate is not given.
@@ -529,7 +567,27 @@ impl<'a> OpEmitter<'a> {
         if let Some(imm) = ptr {
             return self.load_quad_word_imm(imm, span);
         }
-        self.emit(Op::Exec("intrinsics::mem::load_qw".parse().unwrap()), span);
+        // Only word-aligned quad-word loads are supported for dynamic addresses
+        self.emit_word_aligned_native_ptr(span);
+        self.emit_all(
+            &[
+                // [waddr, 0, 0, 0, 0]
+                Op::Padw,
+                Op::Movup(4),
+                // [w3, w2, w1, w0]
+                Op::MemLoadw,
+                // Swap the element order to lowest-address-first
+                // [w2, w3, w1, w0]
+                Op::Swap(1),
+                // [w1, w3, w2, w0]
+                Op::Swap(2),
+                // [w3, w1, w2, w0]
+                Op::Swap(1),
+                // [w0, w1, w2, w3]
+                Op::Swap(3),
+            ],
+            span,
+        );
     }
 
     fn load_quad_word_imm(&mut self, ptr: NativePtr, span: SourceSpan) {
@@ -926,16 +984,41 @@ impl<'a> OpEmitter<'a> {
 
 /// Stores
 impl<'a> OpEmitter<'a> {
-    /// Store a value of the type given by the specified [hir::LocalId], using the memory allocated
-    /// for that local.
+    /// Store a value of type `ty`, using the memory allocated for the specified [hir::LocalId].
     ///
-    /// Internally, this pushes the address of the given local on the stack, and delegates to
-    /// [OpEmitter::store] to perform the actual store.
-    pub fn store_local(&mut self, local: hir::LocalId, span: SourceSpan) {
-        let ty = self.function.local(local).ty.clone();
-        self.emit(Op::LocAddr(local), span);
-        self.push(Type::Ptr(Box::new(ty)));
-        self.store(span)
+    /// As with [OpEmitter::load_local], the local may be shared, and so the type of the value is
+    /// given explicitly, rather than derived from the local.
+    ///
+    /// See [OpEmitter::load_local] for details on how values are laid out in local memory.
+    pub fn store_local(&mut self, local: hir::LocalId, ty: Type, span: SourceSpan) {
+        let value = self.stack.pop().expect("operand stack is empty");
+        assert!(!ty.is_zst(), "cannot store a zero-sized type in a local");
+        assert_eq!(
+            value.ty().size_in_felts(),
+            ty.size_in_felts(),
+            "invalid store to local: value of type {} cannot be stored as {ty}",
+            value.ty()
+        );
+        let size = ty.size_in_felts();
+        if size == 1 {
+            self.emit(Op::LocStore(local), span);
+            return;
+        }
+        // Store the value a word at a time, starting with the elements on top of the stack
+        let num_words = size.div_ceil(4);
+        for i in 0..num_words {
+            // Pad a partial word with zeroes beneath the elements being stored
+            let len = core::cmp::min(4, size - (i * 4));
+            let movdn = if len == 1 {
+                Op::Swap(1)
+            } else {
+                Op::Movdn(len as u8)
+            };
+            for _ in len..4 {
+                self.emit_all(&[Op::PushU8(0), movdn], span);
+            }
+            self.emit_all(&[Op::LocStorew(local_word(local, i)), Op::Dropw], span);
+        }
     }
 
     /// Store a value of type `value` to the address in the Miden address space
@@ -956,6 +1039,7 @@ impl<'a> OpEmitter<'a> {
                 self.emit_native_ptr(span);
                 match value_ty {
                     Type::I128 => self.store_quad_word(None, span),
+                    ref ty if is_word(ty) => self.store_quad_word(None, span),
                     Type::I64 | Type::U64 => self.store_double_word(None, span),
                     Type::Felt => self.store_felt(None, span),
                     Type::I32 | Type::U32 => self.store_word(None, span),
@@ -984,6 +1068,7 @@ impl<'a> OpEmitter<'a> {
         let ptr = NativePtr::from_ptr(addr);
         match value_ty {
             Type::I128 => self.store_quad_word(Some(ptr), span),
+            ref ty if is_word(ty) => self.store_quad_word(Some(ptr), span),
             Type::I64 | Type::U64 => self.store_double_word(Some(ptr), span),
             Type::Felt => self.store_felt(Some(ptr), span),
             Type::I32 | Type::U32 => self.store_word(Some(ptr), span),
@@ -1130,7 +1215,7 @@ impl<'a> OpEmitter<'a> {
                 // [src, dst, count]
                 Op::PushU32(0),         // [i, src, dst, count]
                 Op::Dup(3),             // [count, i, src, dst, count]
-                Op::GteImm(Felt::ZERO), // [count > 0, i, src, dst, count]
+                Op::NeqImm(Felt::ZERO), // [count > 0, i, src, dst, count]
                 Op::While(body),
             ],
             span,
@@ -1176,7 +1261,7 @@ impl<'a> OpEmitter<'a> {
                 Op::U32WrappingAddImm(1),
                 Op::Dup(0), // [i++, i++, src, dst, count]
                 Op::Dup(4), // [count, i++, i++, src, dst, count]
-                Op::U32Gte, // [i++ >= count, i++, src, dst, count]
+                Op::U32Lt,  // [i++ < count, i++, src, dst, count]
             ],
             span,
         );
@@ -1191,7 +1276,27 @@ impl<'a> OpEmitter<'a> {
         if let Some(imm) = ptr {
             return self.store_quad_word_imm(imm, span);
         }
-        self.emit(Op::Exec("intrinsics::mem::store_qw".parse().unwrap()), span);
+        // Only word-aligned quad-word stores are supported for dynamic addresses
+        self.emit_word_aligned_native_ptr(span);
+        self.emit_all(
+            &[
+                // [a, b, c, d, waddr]
+                Op::Movdn(4),
+                // Swap to highest-address-first order
+                // [d, b, c, a, waddr]
+                Op::Swap(3),
+                // [c, d, b, a, waddr]
+                Op::Movup(2),
+                // [d, c, b, a, waddr]
+                Op::Swap(1),
+                // [waddr, d, c, b, a]
+                Op::Movup(4),
+                // Write to heap
+                Op::MemStorew,
+                Op::Dropw,
+            ],
+            span,
+        );
     }
 
     fn store_quad_word_imm(&mut self, ptr: NativePtr, span: SourceSpan) {
@@ -1634,3 +1739,14 @@ impl<'a> OpEmitter<'a> {
         todo!()
     }
 }
+
+/// Get the id of the `index`th word of the memory allocated for `local`
+fn local_word(local: hir::LocalId, index: usize) -> hir::LocalId {
+    hir::LocalId::from_u16(u16::try_from(local.as_usize() + index).expect("invalid local index"))
+}
+
+/// Returns true if `ty` is a Miden word, i.e. `[felt; 4]`, which is loaded and stored in the same
+/// way as any other quad-word value
+fn is_word(ty: &Type) -> bool {
+    matches!(ty, Type::Array(elem, 4) if elem.as_ref() == &Type::Felt)
+}
@@ -125,6 +125,11 @@ impl<'a> InstOpEmitter<'a> {
         self.emitter.exec(import, span);
     }
 
+    pub fn call(&mut self, callee: hir::FunctionIdent, span: SourceSpan) {
+        let import = self.dfg.get_import(&callee).unwrap();
+        self.emitter.call(import, span);
+    }
+
     pub fn syscall(&mut self, callee: hir::FunctionIdent, span: SourceSpan) {
         let import = self.dfg.get_import(&callee).unwrap();
         self.emitter.syscall(import, span);
@@ -250,23 +255,6 @@ impl<'a> OpEmitter<'a> {
         self.current_block().push_repeat(ops, count);
     }
 
-    /// Emit `n` copies of the sequence `ops` to the current block
-    #[inline]
-    pub fn emit_template<const N: usize, F>(&mut self, count: usize, template: F)
-    where
-        F: Fn(usize) -> [Span<Op>; N],
-    {
-        for op in template(0) {
-            self.maybe_register_invoke(&op);
-        }
-
-        let block = self.current_block();
-        for n in 0..count {
-            let ops = template(n);
-            block.extend_from_slice(&ops);
-        }
-    }
-
     /// Push an immediate value on the operand stack
     ///
     /// This has no effect on the state of the emulated operand stack
@@ -285,7 +273,8 @@ impl<'a> OpEmitter<'a> {
             Immediate::U128(i) => self.push_u128(i, span),
             Immediate::I128(i) => self.push_i128(i, span),
             Immediate::Felt(i) => self.emit(Op::Push(i), span),
-            Immediate::F64(_) => unimplemented!("floating-point immediates are not supported"),
+            // An f64 is represented by its bit pattern, as expected by `intrinsics::f64`
+            Immediate::F64(f) => self.push_u64(f.to_bits(), span),
         }
     }
 
@@ -463,7 +452,7 @@ impl<'a> OpEmitter<'a> {
             let raw_size = self.stack.raw_len();
             self.stack.dropn(num_to_drop);
             self.emit_n(raw_size / 4, Op::Dropw, span);
-            self.emit_n(raw_size % 4, Op::Dropw, span);
+            self.emit_n(raw_size % 4, Op::Drop, span);
             return;
         }
 
@@ -641,8 +630,8 @@ mod tests {
             assert_eq!(ops[0].into_inner(), Op::PushU32(1));
             assert_eq!(ops[1].into_inner(), Op::PushU32(2));
             assert_eq!(ops[2].into_inner(), Op::PushU8(3));
-            assert_eq!(ops[3].into_inner(), Op::Push2([Felt::new(1), Felt::ZERO]));
-            assert_eq!(ops[4].into_inner(), Op::Push2([Felt::new(3), Felt::new(u32::MAX as u64)]));
+            assert_eq!(ops[3].into_inner(), Op::Push2([Felt::ZERO, Felt::new(1)]));
+            assert_eq!(ops[4].into_inner(), Op::Push2([Felt::new(u32::MAX as u64), Felt::new(3)]));
         }
 
         assert_eq!(emitter.stack()[0], five);
@@ -1722,6 +1711,124 @@ mod tests {
         assert_eq!(emitter.stack()[0], Type::U32);
     }
 
+    #[test]
+    fn op_emitter_u128_literal_test() {
+        let mut function = setup();
+        let entry = function.body.id();
+        let mut stack = OperandStack::default();
+        let mut emitter = OpEmitter::new(&mut function, entry, &mut stack);
+
+        let num = Immediate::U128((4u128 << 96) | (3u128 << 64) | (2u128 << 32) | 1);
+        let neg = Immediate::I128(-2);
+
+        emitter.literal(num, SourceSpan::default());
+        emitter.literal(neg, SourceSpan::default());
+
+        // The most significant limb must end up on top of the stack
+        let block = emitter.current_block();
+        let ops = block.ops.as_slice();
+        assert_eq!(ops.len(), 4);
+        assert_eq!(ops[0].into_inner(), Op::Push2([Felt::new(1), Felt::new(2)]));
+        assert_eq!(ops[1].into_inner(), Op::Push2([Felt::new(3), Felt::new(4)]));
+        let max = Felt::new(u32::MAX as u64);
+        assert_eq!(ops[2].into_inner(), Op::Push2([Felt::new(u32::MAX as u64 - 1), max]));
+        assert_eq!(ops[3].into_inner(), Op::Push2([max, max]));
+    }
+
+    #[test]
+    fn op_emitter_u128_bnot_test() {
+        let mut function = setup();
+        let entry = function.body.id();
+        let mut stack = OperandStack::default();
+        let mut emitter = OpEmitter::new(&mut function, entry, &mut stack);
+
+        let num = Immediate::U128(128);
+
+        emitter.literal(num, SourceSpan::default());
+
+        emitter.bnot(SourceSpan::default());
+        assert_eq!(emitter.stack_len(), 1);
+        assert_eq!(emitter.stack()[0], Type::U128);
+
+        // Each limb is rotated to the top and inverted in turn, preserving the limb order
+        let block = emitter.current_block();
+        let ops = block.ops.as_slice();
+        assert_eq!(ops.len(), 10);
+        for pair in ops[2..].chunks(2) {
+            assert_eq!(pair[0].into_inner(), Op::Movup(3));
+            assert_eq!(pair[1].into_inner(), Op::U32Not);
+        }
+    }
+
+    #[test]
+    fn op_emitter_u128_assert_test() {
+        let context = midenc_hir::testing::TestContext::default();
+        let span = SourceSpan::default();
+
+        // Assemble and execute `assert` of the given value on the VM, returning true if the
+        // assertion held
+        let assert_holds = |value: u128| -> bool {
+            let mut function =
+                Function::new("test::probe".parse().unwrap(), Signature::new(vec![], vec![]));
+            function.signature.linkage = midenc_hir::Linkage::External;
+            {
+                let entry = function.body.id();
+                let mut stack = OperandStack::default();
+                let mut emitter = OpEmitter::new(&mut function, entry, &mut stack);
+                emitter.literal(Immediate::U128(value), span);
+                emitter.assert(None, span);
+                assert_eq!(emitter.stack_len(), 0);
+            }
+            let mut module = Box::new(crate::masm::Module::new(
+                miden_assembly::LibraryPath::new("test").unwrap(),
+                miden_assembly::ast::ModuleKind::Library,
+            ));
+            module.push_back(Box::new(function));
+            let program = miden_assembly::Assembler::new(context.session.source_manager.clone())
+                .with_module(module.to_ast(false).unwrap())
+                .unwrap()
+                .assemble_program("begin exec.::test::probe end")
+                .unwrap();
+            miden_processor::execute(
+                &program,
+                Default::default(),
+                miden_processor::DefaultHost::default(),
+                Default::default(),
+            )
+            .is_ok()
+        };
+
+        assert!(assert_holds(1));
+        assert!(!assert_holds(0));
+        assert!(!assert_holds(1 << 96));
+    }
+
+    #[test]
+    fn op_emitter_truncate_stack_test() {
+        let mut function = setup();
+        let entry = function.body.id();
+        let mut stack = OperandStack::default();
+        let mut emitter = OpEmitter::new(&mut function, entry, &mut stack);
+
+        // 6 elements in total: a u64, and four u32s
+        emitter.literal(Immediate::U64(1), SourceSpan::default());
+        emitter.literal(Immediate::U32(2), SourceSpan::default());
+        emitter.literal(Immediate::U32(3), SourceSpan::default());
+        emitter.literal(Immediate::U32(4), SourceSpan::default());
+        emitter.literal(Immediate::U32(5), SourceSpan::default());
+
+        emitter.truncate_stack(0, SourceSpan::default());
+        assert_eq!(emitter.stack_len(), 0);
+
+        // A word is dropped at a time, and the remaining elements are dropped individually
+        let block = emitter.current_block();
+        let ops = block.ops.as_slice();
+        assert_eq!(ops.len(), 8);
+        assert_eq!(ops[5].into_inner(), Op::Dropw);
+        assert_eq!(ops[6].into_inner(), Op::Drop);
+        assert_eq!(ops[7].into_inner(), Op::Drop);
+    }
+
     #[test]
     fn op_emitter_u32_pow2_test() {
         let mut function = setup();
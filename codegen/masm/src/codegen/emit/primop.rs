@@ -1,3 +1,5 @@
+use core::alloc::Layout;
+
 use midenc_hir::{
     self as hir, diagnostics::SourceSpan, ArgumentExtension, ArgumentPurpose, Felt, FieldElement,
     Immediate, Type,
@@ -6,6 +8,35 @@ use midenc_hir::{
 use super::{int64, OpEmitter};
 use crate::masm::Op;
 
+/// The byte address of the memory in a callee's context to which the arguments of a cross-context
+/// call are copied, when they are passed in memory.
+///
+/// This is the first address beyond the end of the dynamic heap, and so is never used otherwise.
+pub(crate) const CANONICAL_ABI_PARAMS_ADDR: u32 = 0x1000_0000;
+
+/// The byte address of the memory in a caller's context to which the results of a cross-context
+/// call are copied, when they are returned in memory, before they are written to the `sret`
+/// pointer.
+pub(crate) const CANONICAL_ABI_RESULTS_ADDR: u32 = 0x1800_0000;
+
+/// The maximum size in bytes of the arguments or results of a cross-context call which are passed
+/// in memory
+const MAX_CANONICAL_ABI_AREA_SIZE: usize =
+    (CANONICAL_ABI_RESULTS_ADDR - CANONICAL_ABI_PARAMS_ADDR) as usize;
+
+/// Returns the layout of the memory through which values of type `ty` are passed across contexts
+/// using the canonical ABI
+fn canonical_abi_area_layout(ty: &Type) -> Layout {
+    let layout = hir::canonical_abi_layout(ty)
+        .expect("invalid cross-context call: arguments or results cannot be passed in memory");
+    assert!(
+        layout.size() > 0 && layout.size() <= MAX_CANONICAL_ABI_AREA_SIZE,
+        "invalid cross-context call: arguments or results passed in memory must be between 1 and \
+         {MAX_CANONICAL_ABI_AREA_SIZE} bytes"
+    );
+    layout
+}
+
 impl<'a> OpEmitter<'a> {
     /// Assert that an integer value on the stack has the value 1
     ///
@@ -27,7 +58,7 @@ impl<'a> OpEmitter<'a> {
             Type::I128 | Type::U128 => {
                 self.emit_all(
                     &[
-                        Op::Pushw([Felt::ZERO, Felt::ZERO, Felt::ZERO, Felt::ONE]),
+                        Op::Pushw([Felt::ONE, Felt::ZERO, Felt::ZERO, Felt::ZERO]),
                         Op::AssertEqwWithError(code),
                     ],
                     span,
@@ -218,6 +249,156 @@ impl<'a> OpEmitter<'a> {
     ///
     /// A function called using this operation is invoked in the same memory context as the caller.
     pub fn exec(&mut self, callee: &hir::ExternalFunction, span: SourceSpan) {
+        self.prepare_call_args(callee, span);
+
+        for result in callee.signature.results.iter().rev() {
+            self.push(result.ty.clone());
+        }
+
+        self.emit(Op::Exec(callee.id), span);
+    }
+
+    /// Call the given procedure.
+    ///
+    /// A function called using this operation is invoked in a new memory context, and is only
+    /// able to observe the top 16 elements of the operand stack. The arguments are padded to
+    /// 16 elements before the call, and on return, everything below the results is dropped, so
+    /// that the rest of the operand stack is as it would be after an `exec`.
+    ///
+    /// The callee must use the [hir::CallConv::Wasm] calling convention, and must belong to another
+    /// module, as calls to such functions from within their own module are rejected before
+    /// codegen. When its flattened parameters or results do not fit on the operand stack, they are
+    /// passed in memory, see [hir::canonical_abi_params_area] and [hir::canonical_abi_results_area].
+    /// As the callee cannot observe the memory of the caller, that memory is copied through the
+    /// advice map: the caller passes a commitment to the memory containing the arguments, which the
+    /// callee copies to [CANONICAL_ABI_PARAMS_ADDR] in its own memory on entry, and the callee
+    /// returns the results the same way, which are copied to [CANONICAL_ABI_RESULTS_ADDR], and
+    /// from there to the memory given by the `sret` parameter.
+    pub fn call(&mut self, callee: &hir::ExternalFunction, span: SourceSpan) {
+        assert_ne!(
+            callee.id.module, self.function.name.module,
+            "invalid call to {}: a function using the canonical ABI may not be called from within \
+             its own module",
+            callee.id
+        );
+        let signature = &callee.signature;
+        let params_area = hir::canonical_abi_params_area(signature).map(canonical_abi_area_layout);
+        let results_area =
+            hir::canonical_abi_results_area(signature).map(canonical_abi_area_layout);
+        let num_args = match params_area {
+            Some(_) => hir::CANONICAL_ABI_AREA_SIZE_IN_FELTS,
+            None => signature
+                .params
+                .iter()
+                .filter(|p| p.purpose != ArgumentPurpose::StructReturn)
+                .map(|p| p.ty.size_in_felts())
+                .sum::<usize>(),
+        };
+        let num_results = match results_area {
+            Some(_) => hir::CANONICAL_ABI_AREA_SIZE_IN_FELTS,
+            None => signature.results.iter().map(|r| r.ty.size_in_felts()).sum::<usize>(),
+        };
+        assert!(
+            num_args <= 16 && num_results <= 16,
+            "invalid call to {}: the arguments and results of a cross-context call must each fit \
+             in 16 elements",
+            callee.id
+        );
+
+        self.prepare_call_args(callee, span);
+
+        // Replace the pointer to the arguments with a commitment to the memory containing them,
+        // and the offset of the arguments in the first word of that memory
+        if let Some(layout) = params_area {
+            self.emit_all(
+                &[
+                    // [ptr, ..]
+                    Op::PushU32(layout.size() as u32),
+                    Op::Swap(1),
+                    // [COM, offset, ..]
+                    Op::Exec("intrinsics::mem::copy_to_advice_map".parse().unwrap()),
+                ],
+                span,
+            );
+        }
+
+        // Pad the arguments with zeroes so that the callee sees exactly its arguments. The `sret`
+        // parameter, if present, is not passed to the callee, and so ends up below the padding
+        match num_args {
+            0 => {
+                self.emit_n(4, Op::Padw, span);
+            }
+            16 => (),
+            n => {
+                for _ in n..16 {
+                    self.emit(Op::PushU32(0), span);
+                    if n == 1 {
+                        self.emit(Op::Swap(1), span);
+                    } else {
+                        self.emit(Op::Movdn(n as u8), span);
+                    }
+                }
+            }
+        }
+
+        self.emit(Op::Call(callee.id), span);
+
+        // Drop everything the callee left below its results
+        match num_results {
+            0 => {
+                self.emit_n(4, Op::Dropw, span);
+            }
+            16 => (),
+            n => {
+                for _ in n..16 {
+                    if n == 1 {
+                        self.emit_all(&[Op::Swap(1), Op::Drop], span);
+                    } else {
+                        self.emit_all(&[Op::Movup(n as u8), Op::Drop], span);
+                    }
+                }
+            }
+        }
+
+        // Copy the results from the memory of the callee to the memory given by the `sret`
+        // parameter, by way of the scratch memory reserved for this purpose
+        if let Some(layout) = results_area {
+            let size = layout.size() as u32;
+            self.emit_all(
+                &[
+                    // [COM, offset, retptr]
+                    Op::PushU32(size),
+                    Op::Movdn(5),
+                    Op::PushU32(CANONICAL_ABI_RESULTS_ADDR),
+                    Op::Movdn(6),
+                    // [ptr, retptr]
+                    Op::Exec("intrinsics::mem::copy_from_advice_map".parse().unwrap()),
+                ],
+                span,
+            );
+            // Copy the results using the largest element type permitted by their alignment
+            let element_ty = match layout.align() {
+                1 => Type::U8,
+                2 => Type::U16,
+                _ => Type::U32,
+            };
+            let count = size / element_ty.size_in_bytes() as u32;
+            self.emit_all(&[Op::PushU32(count), Op::Movdn(2)], span);
+            let ptr_ty = Type::Ptr(Box::new(element_ty));
+            self.push(Type::U32);
+            self.push(ptr_ty.clone());
+            self.push(ptr_ty);
+            self.memcpy(span);
+        }
+
+        for result in callee.signature.results.iter().rev() {
+            self.push(result.ty.clone());
+        }
+    }
+
+    /// Pop the arguments to `callee` from the operand stack, validating them against its signature,
+    /// and extending them to the parameter types where the signature requires it
+    fn prepare_call_args(&mut self, callee: &hir::ExternalFunction, span: SourceSpan) {
         let import = callee;
         let callee = import.id;
         let signature = &import.signature;
@@ -228,10 +409,17 @@ impl<'a> OpEmitter<'a> {
             // Validate the purpose matches
             match param.purpose {
                 ArgumentPurpose::StructReturn => {
+                    // Functions using the canonical ABI take the pointer to which their results
+                    // are written as their last parameter
+                    let expected = match signature.cc {
+                        hir::CallConv::Wasm => signature.arity() - 1,
+                        _ => 0,
+                    };
                     assert_eq!(
-                        i, 0,
-                        "invalid function signature: sret parameters must be the first parameter, \
-                         and only one sret parameter is allowed"
+                        i, expected,
+                        "invalid function signature: sret parameters must be the first parameter \
+                         (or the last, when using the canonical ABI), and only one sret parameter \
+                         is allowed"
                     );
                     assert_eq!(
                         signature.results.len(),
@@ -326,12 +514,6 @@ impl<'a> OpEmitter<'a> {
                 ArgumentExtension::Zext | ArgumentExtension::Sext => (),
             }
         }
-
-        for result in signature.results.iter().rev() {
-            self.push(result.ty.clone());
-        }
-
-        self.emit(Op::Exec(callee), span);
     }
 
     /// Execute the given procedure as a syscall.
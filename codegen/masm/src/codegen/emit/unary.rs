@@ -852,9 +852,12 @@ impl<'a> OpEmitter<'a> {
                         );
                     }
                     n => {
-                        self.emit_template(n, |n| {
-                            [Span::new(span, Op::Movup(n as u8)), Span::new(span, Op::U32Not)]
-                        });
+                        // Rotate each limb to the top in turn, so that the limbs are back in
+                        // their original order once all of them have been inverted
+                        self.emit_repeat(
+                            n,
+                            &[Span::new(span, Op::Movup(n as u8 - 1)), Span::new(span, Op::U32Not)],
+                        );
                     }
                 }
             }
@@ -8,12 +8,13 @@ use midenc_hir::{
     diagnostics::{SourceSpan, Span},
 };
 use midenc_hir_analysis::{
-    DominatorTree, GlobalVariableLayout, LivenessAnalysis, Loop, LoopAnalysis,
+    DominatorTree, GlobalVariableLayout, LivenessAnalysis, LocalSlotAnalysis, Loop, LoopAnalysis,
 };
 use smallvec::SmallVec;
 
 use super::{
     emit::{InstOpEmitter, OpEmitter},
+    layout::StackLayout,
     opt::{OperandMovementConstraintSolver, SolverError},
     scheduler::{BlockInfo, InstInfo, Schedule, ScheduleOp},
     Constraint, OperandStack,
@@ -34,6 +35,7 @@ pub struct FunctionEmitter<'a> {
 struct BlockEmitter<'b, 'f: 'b> {
     function: &'b mut FunctionEmitter<'f>,
     block_infos: &'b SparseMap<hir::Block, Rc<BlockInfo>>,
+    stack_layout: &'b StackLayout,
     block_info: Rc<BlockInfo>,
     /// The "controlling" loop corresponds to the current maximum loop depth
     /// reached along the control flow path reaching this block. When we reach
@@ -99,12 +101,15 @@ impl<'a> FunctionEmitter<'a> {
         domtree: &'a DominatorTree,
         loops: &'a LoopAnalysis,
         liveness: &'a LivenessAnalysis,
+        local_slots: &LocalSlotAnalysis,
         globals: &'a GlobalVariableLayout,
     ) -> Self {
-        // Allocate procedure locals for each local variable
-        let locals = BTreeMap::from_iter(
-            f.dfg.locals().map(|local| (local.id, f_prime.alloc_local(local.ty.clone()))),
-        );
+        // Allocate a procedure local for each slot, shared by the local variables assigned to it
+        let mut locals = BTreeMap::default();
+        for slot in local_slots.slots() {
+            let id = f_prime.alloc_local(slot.ty.clone());
+            locals.extend(slot.locals.iter().map(|local| (*local, id)));
+        }
 
         Self {
             f,
@@ -138,6 +143,7 @@ impl<'a> FunctionEmitter<'a> {
                     let emitter = BlockEmitter {
                         function: &mut self,
                         block_infos: &schedule.block_infos,
+                        stack_layout: &schedule.stack_layout,
                         block_info,
                         controlling_loop,
                         target,
@@ -158,6 +164,7 @@ impl<'a> FunctionEmitter<'a> {
                     let emitter = BlockEmitter {
                         function: &mut self,
                         block_infos: &schedule.block_infos,
+                        stack_layout: &schedule.stack_layout,
                         block_info,
                         controlling_loop,
                         target,
@@ -189,6 +196,10 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
         // up these unused values is pushed into the successor on entry.
         self.drop_unused_operands();
 
+        // If this is a loop header, the remaining operands must be placed in the canonical order
+        // planned for it, so that they are found in the same place on every iteration
+        self.place_live_through_operands();
+
         // Continue normally, by emitting the contents of the block based on the given schedule
         for op in block_schedule.iter() {
             match op {
@@ -248,15 +259,16 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
             Instruction::LocalVar(ref op) => {
                 let span = self.function.f.dfg.inst_span(inst_info.inst);
                 let local = self.function.locals[&op.local];
+                let ty = self.function.f.dfg.local_type(op.local).clone();
                 let args = op.args.as_slice(&self.function.f.dfg.value_lists);
                 let mut emitter = self.inst_emitter(inst_info.inst);
                 match op.op {
                     hir::Opcode::Store => {
                         assert_eq!(args.len(), 1);
-                        emitter.store_local(local, span);
+                        emitter.store_local(local, ty, span);
                     }
                     hir::Opcode::Load => {
-                        emitter.load_local(local, span);
+                        emitter.load_local(local, ty, span);
                     }
                     opcode => unimplemented!("unrecognized local variable op '{opcode}'"),
                 }
@@ -275,6 +287,7 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
         let span = self.function.f.dfg.inst_span(inst_info.inst);
         let num_args = self.function.f.dfg.inst_args(inst_info.inst).len();
         let level = self.controlling_loop_level().unwrap_or(0);
+        let function = self.function.f;
 
         let mut emitter = self.emitter();
         // Upon return, the operand stack should only contain the function result(s),
@@ -286,6 +299,22 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
             emitter.literal(*arg, span);
         }
 
+        // Functions using the canonical ABI are invoked with `call`, and must return with exactly
+        // 16 elements on the operand stack. The arguments were padded to 16 elements by the
+        // caller, so if there are more results than arguments, we must drop the excess padding
+        let signature = &function.signature;
+        if signature.cc == hir::CallConv::Wasm {
+            let num_params = signature.params.iter().map(|p| p.ty.size_in_felts()).sum::<usize>();
+            let num_results = signature.results.iter().map(|r| r.ty.size_in_felts()).sum::<usize>();
+            for _ in num_params..num_results {
+                if num_results == 1 {
+                    emitter.emit_all(&[Op::Swap(1), Op::Drop], span);
+                } else {
+                    emitter.emit_all(&[Op::Movup(num_results as u8), Op::Drop], span);
+                }
+            }
+        }
+
         // If we're in a loop, push N zeroes on the stack, where N is the current loop depth
         for _ in 0..level {
             emitter.literal(false, span);
@@ -315,12 +344,16 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
         // Move block arguments into position
         let span = self.function.f.dfg.inst_span(inst_info.inst);
         let args = op.successor.args.as_slice(&self.function.f.dfg.value_lists);
-        self.schedule_operands(args, inst_info.block_arguments(destination), span)
-            .unwrap_or_else(|err| {
-                panic!("failed to schedule operands for {}: {err:?}", inst_info.inst)
-            });
+        let args = self.stack_layout.permute(destination, args);
+        let constraints =
+            self.stack_layout.permute(destination, inst_info.block_arguments(destination));
+        self.schedule_operands(&args, &constraints, span).unwrap_or_else(|err| {
+            panic!("failed to schedule operands for {}: {err:?}", inst_info.inst)
+        });
         // Rename operands on stack to destination block parameters
-        let params = self.function.f.dfg.block_params(destination);
+        let params = self
+            .stack_layout
+            .permute(destination, self.function.f.dfg.block_params(destination));
         for (idx, param) in params.iter().enumerate() {
             self.stack.rename(idx, *param);
         }
@@ -406,23 +439,21 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
                 // the control flow edge to that successor, i.e. we do not emit
                 // these stack ops in the current block, but in the successor block
                 let args = args.as_slice(&self.function.f.dfg.value_lists);
-                self.schedule_operands_in_block(
-                    args,
-                    inst_info.block_arguments(block),
-                    masm_block,
-                    &mut stack,
-                    span,
-                )
-                .unwrap_or_else(|err| {
-                    panic!(
-                        "failed to schedule operands for successor {block} of {}: {err:?}",
-                        inst_info.inst
-                    )
-                });
+                let args = self.stack_layout.permute(block, args);
+                let constraints =
+                    self.stack_layout.permute(block, inst_info.block_arguments(block));
+                self.schedule_operands_in_block(&args, &constraints, masm_block, &mut stack, span)
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "failed to schedule operands for successor {block} of {}: {err:?}",
+                            inst_info.inst
+                        )
+                    });
 
                 // Now that the block arguments are in place, we need to rename
                 // the stack operands to use the value names the successor expects
-                let params = self.function.f.dfg.block_params(block);
+                let params =
+                    self.stack_layout.permute(block, self.function.f.dfg.block_params(block));
                 for (idx, param) in params.iter().enumerate() {
                     stack.rename(idx, *param);
                 }
@@ -551,6 +582,14 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
                 assert_matches!(op.imm, Immediate::U64(_));
                 emitter.literal(op.imm, span);
             }
+            hir::Opcode::ImmI128 => {
+                assert_matches!(op.imm, Immediate::I128(_));
+                emitter.literal(op.imm, span);
+            }
+            hir::Opcode::ImmU128 => {
+                assert_matches!(op.imm, Immediate::U128(_));
+                emitter.literal(op.imm, span);
+            }
             hir::Opcode::ImmFelt => {
                 assert_matches!(op.imm, Immediate::Felt(_));
                 emitter.literal(op.imm, span);
@@ -815,9 +854,18 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
         assert_ne!(op.callee, self.function.f.id, "unexpected recursive call");
 
         let span = self.function.f.dfg.inst_span(inst_info.inst);
+        // Functions using the canonical ABI belong to another component, and so must be invoked
+        // in their own context
+        let is_cross_context = self
+            .function
+            .f
+            .dfg
+            .get_import(&op.callee)
+            .is_some_and(|import| import.signature.cc == hir::CallConv::Wasm);
         let mut emitter = self.inst_emitter(inst_info.inst);
         match op.op {
             hir::Opcode::Syscall => emitter.syscall(op.callee, span),
+            hir::Opcode::Call if is_cross_context => emitter.call(op.callee, span),
             hir::Opcode::Call => emitter.exec(op.callee, span),
             opcode => unimplemented!("unrecognized procedure call opcode: '{opcode}'"),
         }
@@ -1056,6 +1104,31 @@ impl<'b, 'f: 'b> BlockEmitter<'b, 'f> {
         }
     }
 
+    /// Move the parameters of the current block, and the values which are live through it, into
+    /// the order planned for them by the [StackLayout], if the block has a canonical layout.
+    ///
+    /// This is intended to be called after [Self::drop_unused_operands], at which point the only
+    /// operands on the stack are those which are live on entry to the block.
+    fn place_live_through_operands(&mut self) {
+        let block = self.block_info.source;
+        let Some(live_through) = self.stack_layout.live_through(block) else {
+            return;
+        };
+        let pp = hir::ProgramPoint::Block(block);
+        let params = self.stack_layout.permute(block, self.function.f.dfg.block_params(block));
+        let expected = params
+            .iter()
+            .copied()
+            .filter(|param| self.function.liveness.is_live_at(param, pp))
+            .chain(live_through.iter().copied())
+            .collect::<SmallVec<[hir::Value; 8]>>();
+        let constraints = SmallVec::<[Constraint; 8]>::from_elem(Constraint::Move, expected.len());
+        self.schedule_operands(&expected, &constraints, SourceSpan::default())
+            .unwrap_or_else(|err| {
+                panic!("failed to place live-through operands for {block}: {err:?}")
+            });
+    }
+
     fn schedule_operands(
         &mut self,
         expected: &[hir::Value],
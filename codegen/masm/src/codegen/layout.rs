@@ -0,0 +1,183 @@
+use cranelift_entity::SecondaryMap;
+use midenc_hir::{self as hir, BranchInfo, ValueData};
+use midenc_hir_analysis::{LivenessAnalysis, LoopAnalysis};
+use smallvec::SmallVec;
+
+use super::scheduler::{Schedule, ScheduleOp};
+
+/// [StackLayout] describes the order in which the parameters of each block in a function, and the
+/// values which are live through each loop header, are expected to be found on the operand stack on
+/// entry to that block.
+///
+/// By default, the parameters of a block are expected in parameter order, i.e. the first
+/// parameter on top of the stack. However, every predecessor of a block must place the block
+/// arguments in the same order, so whenever that order differs from the order in which the
+/// arguments are naturally produced, shuffling is required along the control flow edge. This is
+/// particularly costly along the loopback edges of a loop, which are executed on every iteration,
+/// and which must agree with the order established on entry to the loop.
+///
+/// The layout is chosen once for the whole function by [StackLayoutPlanner], and then honored by
+/// every branch to the block, so the [OperandMovementConstraintSolver] is asked to produce the
+/// planned order, rather than parameter order.
+///
+/// The same is true of the values which are live through a loop header, i.e. which are live on
+/// entry to the header, but are not parameters of it. The code for the header is emitted once on
+/// entry to the loop, and again along each loopback edge, and must find those values in the same
+/// place each time, so they are given a canonical order below the block parameters, which is
+/// established on entry to the header, after any dead values are dropped.
+///
+/// [OperandMovementConstraintSolver]: super::opt::OperandMovementConstraintSolver
+#[derive(Debug, Default)]
+pub struct StackLayout {
+    /// For each block whose parameters are not in parameter order, the index of the parameter
+    /// which is expected at each position of the operand stack, starting from the top.
+    params: SecondaryMap<hir::Block, Option<SmallVec<[u16; 4]>>>,
+    /// For each loop header with values which are live through it, those values, in the order
+    /// they are expected on the operand stack below the parameters of the block.
+    live_through: SecondaryMap<hir::Block, Option<SmallVec<[hir::Value; 4]>>>,
+}
+impl StackLayout {
+    /// Returns the parameter indices of `block`, in the order they are expected on the operand
+    /// stack on entry to `block`, or `None` if they are expected in parameter order.
+    pub fn get(&self, block: hir::Block) -> Option<&[u16]> {
+        self.params[block].as_deref()
+    }
+
+    /// Returns the values which are live through `block`, in the order they are expected on the
+    /// operand stack below the parameters of `block`, or `None` if they have no canonical order.
+    pub fn live_through(&self, block: hir::Block) -> Option<&[hir::Value]> {
+        self.live_through[block].as_deref()
+    }
+
+    /// Permute `items`, given in parameter order for `block`, into the order they are expected on
+    /// the operand stack on entry to `block`
+    pub fn permute<T: Copy>(&self, block: hir::Block, items: &[T]) -> SmallVec<[T; 4]> {
+        match self.get(block) {
+            None => SmallVec::from_slice(items),
+            Some(order) => order.iter().map(|index| items[*index as usize]).collect(),
+        }
+    }
+}
+
+/// The [StackLayoutPlanner] picks a [StackLayout] for a function, by choosing, for each block
+/// with more than one parameter, the order in which its arguments are most likely to already be
+/// on the operand stack along the hottest incoming control flow edge.
+///
+/// The hottest edge is estimated to be the one originating in the most deeply nested loop, which
+/// for a loop header is a loopback edge. Along that edge, the arguments produced latest in the
+/// predecessor's schedule are expected to be nearest to the top of the stack, as each instruction
+/// places its results on top of the stack. Arguments which are not produced by the predecessor
+/// are expected deeper in the stack than those which are.
+///
+/// The values which are live through a loop header are ordered by the distance to their next use,
+/// so that the value needed soonest is nearest the top of the stack.
+pub struct StackLayoutPlanner<'a> {
+    f: &'a hir::Function,
+    loops: &'a LoopAnalysis,
+    liveness: &'a LivenessAnalysis,
+    schedule: &'a Schedule,
+}
+impl<'a> StackLayoutPlanner<'a> {
+    /// Only the top 16 elements of the operand stack are directly accessible, so we only plan the
+    /// placement of live-through values when they fit in that window along with the parameters
+    const MAX_PLANNED_OPERANDS: usize = 16;
+
+    pub fn new(
+        f: &'a hir::Function,
+        loops: &'a LoopAnalysis,
+        liveness: &'a LivenessAnalysis,
+        schedule: &'a Schedule,
+    ) -> Self {
+        Self {
+            f,
+            loops,
+            liveness,
+            schedule,
+        }
+    }
+
+    pub fn plan(self) -> StackLayout {
+        let mut layout = StackLayout::default();
+        for (block, hottest) in self.hottest_edges().iter() {
+            let Some((predecessor, args)) = *hottest else {
+                continue;
+            };
+            // Rank each argument by its position in the schedule of the predecessor, so that the
+            // latest argument to be produced is placed on top
+            let mut order = (0..args.len() as u16).collect::<SmallVec<[u16; 4]>>();
+            let ranks = args
+                .iter()
+                .map(|arg| self.schedule_position(predecessor, *arg))
+                .collect::<SmallVec<[Option<usize>; 4]>>();
+            order.sort_by(|a, b| ranks[*b as usize].cmp(&ranks[*a as usize]));
+            if order.iter().enumerate().any(|(pos, index)| pos != *index as usize) {
+                layout.params[block] = Some(order);
+            }
+        }
+        for lp in self.loops.loops() {
+            let header = self.loops.loop_header(lp);
+            layout.live_through[header] = self.live_through(header);
+        }
+        layout
+    }
+
+    /// Get the values which are live through `header`, in order of their next use
+    fn live_through(&self, header: hir::Block) -> Option<SmallVec<[hir::Value; 4]>> {
+        let pp = hir::ProgramPoint::Block(header);
+        let params = self.f.dfg.block_params(header);
+        let mut live_through = self
+            .liveness
+            .live_at(pp)
+            .filter(|value| !params.contains(value))
+            .collect::<SmallVec<[hir::Value; 4]>>();
+        if live_through.is_empty() || live_through.len() + params.len() > Self::MAX_PLANNED_OPERANDS
+        {
+            return None;
+        }
+        live_through.sort_by_key(|value| (self.liveness.next_use(value, pp), *value));
+        Some(live_through)
+    }
+
+    /// Get the hottest incoming edge of each block with more than one parameter, as the
+    /// predecessor block, and the arguments passed along that edge.
+    fn hottest_edges(&self) -> SecondaryMap<hir::Block, Option<(hir::Block, &'a [hir::Value])>> {
+        let dfg = &self.f.dfg;
+        let mut hottest = SecondaryMap::<hir::Block, Option<(hir::Block, &'a [hir::Value])>>::new();
+        for (predecessor, _) in dfg.blocks() {
+            let Some(terminator) = dfg.last_inst(predecessor) else {
+                continue;
+            };
+            let successors = match dfg.analyze_branch(terminator) {
+                BranchInfo::NotABranch => continue,
+                BranchInfo::SingleDest(successor) => SmallVec::<[_; 2]>::from_iter([successor]),
+                BranchInfo::MultiDest(successors) => SmallVec::from_vec(successors),
+            };
+            let level = self.loops.loop_level(predecessor);
+            for successor in successors {
+                if successor.args.len() < 2 {
+                    continue;
+                }
+                let is_hotter = match hottest[successor.destination] {
+                    None => true,
+                    Some((current, _)) => level > self.loops.loop_level(current),
+                };
+                if is_hotter {
+                    hottest[successor.destination] = Some((predecessor, successor.args));
+                }
+            }
+        }
+        hottest
+    }
+
+    /// Get the position in the schedule of `block` of the instruction which produces `value`, or
+    /// `None` if `value` is not produced in `block`.
+    fn schedule_position(&self, block: hir::Block, value: hir::Value) -> Option<usize> {
+        let ValueData::Inst { inst, .. } = self.f.dfg.value_data(value) else {
+            return None;
+        };
+        self.schedule
+            .get(block)
+            .iter()
+            .position(|op| matches!(op, ScheduleOp::Inst(inst_info) if inst_info.inst == *inst))
+    }
+}
@@ -1,11 +1,14 @@
 mod emit;
 mod emitter;
+mod layout;
 mod opt;
 mod scheduler;
 mod stack;
 
+pub(crate) use self::emit::primop::CANONICAL_ABI_PARAMS_ADDR;
 pub use self::{
     emitter::FunctionEmitter,
+    opt::{Outliner, Peephole, PeepholeStats},
     scheduler::Scheduler,
     stack::{Constraint, Operand, OperandStack, TypedValue},
 };
@@ -1,3 +1,9 @@
 pub mod operands;
+mod outline;
+mod peephole;
 
-pub use self::operands::{OperandMovementConstraintSolver, SolverError};
+pub use self::{
+    operands::{OperandMovementConstraintSolver, SolverError},
+    outline::Outliner,
+    peephole::{Peephole, PeepholeStats},
+};
@@ -0,0 +1,456 @@
+use alloc::collections::BTreeMap;
+
+use miden_assembly::{
+    ast::{InvokeKind, ModuleKind},
+    LibraryPath,
+};
+use miden_core::crypto::hash::RpoDigest;
+use midenc_hir::{
+    diagnostics::{Report, Span},
+    FunctionIdent, Ident, Linkage, Signature, Symbol,
+};
+use midenc_session::Session;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    masm::{self, BlockId, Op},
+    mast::{MastLowering, PROCEDURE_INLINING_THRESHOLD},
+};
+
+/// The number of instructions a sequence must share with another before it is considered for
+/// outlining
+const SEED_LEN: usize = 4;
+
+/// The maximum number of operations in a group of a basic block, and of groups in an operation
+/// batch. An immediate value always occupies a group of its own.
+const OPS_PER_GROUP: usize = 9;
+const GROUPS_PER_BATCH: usize = 8;
+
+/// The approximate size, in bytes, of a MAST node in a serialized MAST forest, i.e. its digest,
+/// and the header describing it
+const MAST_NODE_SIZE: usize = 40;
+
+/// The approximate number of bytes added to a procedure by replacing a sequence of instructions
+/// with an `exec` of an outlined procedure: the basic block containing the sequence is split in
+/// two, and the three parts are then joined together.
+const CALL_SITE_SIZE: usize = 3 * MAST_NODE_SIZE;
+
+/// The [Outliner] extracts sequences of instructions which occur repeatedly in the functions of a
+/// program, into new internal procedures, replacing each occurrence with an `exec` of the new
+/// procedure, in order to reduce the size of the resulting MAST.
+///
+/// Sequences are only outlined when doing so is estimated to reduce the size of the serialized
+/// MAST, i.e. when the size of the copies removed, derived from [Op::cost], exceeds the overhead of
+/// the new procedure, and of each call site. Invoking the outlined procedure adds a few cycles at
+/// each call site, so this is only worth doing when optimizing for size.
+///
+/// A procedure whose body is a basic block of fewer than [PROCEDURE_INLINING_THRESHOLD] operation
+/// batches is merged back into the code surrounding each of its call sites when the MAST is built,
+/// so sequences are only outlined if they are assembled to at least that many batches. How many
+/// operations that takes depends on the sequence: a batch holds up to 72 operations, but as few as
+/// 4 `push` instructions, as each immediate value occupies a group of the batch on its own.
+///
+/// Outlined procedures are deduplicated by the digest of their MAST, so sequences which differ
+/// syntactically, but assemble to the same code, e.g. `add.1` and `incr`, share a single
+/// procedure. A sequence which occurs in several modules is outlined into a procedure in each of
+/// them, all of which have the same MAST root, and so are only stored once in the MAST forest.
+///
+/// Only straight-line code is outlined, and only if it does not depend on the frame of the
+/// function it is in, i.e. it does not reference locals. Invocations and debugging instructions
+/// are also left in place, so that outlined procedures are self-contained.
+pub struct Outliner<'a> {
+    session: &'a Session,
+    /// The outlined procedures created so far, with the digest of their body
+    outlined: Vec<(RpoDigest, masm::Function)>,
+    /// The sequences which were found to be inlined when lowered, and so are not outlined
+    inlined: Vec<Vec<Op>>,
+}
+impl<'a> Outliner<'a> {
+    pub fn new(session: &'a Session) -> Self {
+        Self {
+            session,
+            outlined: vec![],
+            inlined: vec![],
+        }
+    }
+
+    /// Treat `ops` as a sequence which would be inlined into its call sites, regardless of its size
+    #[cfg(test)]
+    pub(crate) fn assume_inlined(mut self, ops: Vec<Op>) -> Self {
+        self.inlined.push(ops);
+        self
+    }
+
+    /// Outline repeated sequences in the functions of `modules`, adding the outlined procedures to
+    /// the modules which invoke them.
+    pub fn run(mut self, modules: &mut [Box<masm::Module>]) -> Result<(), Report> {
+        let mut functions = vec![];
+        for module in modules.iter_mut() {
+            while let Some(function) = module.pop_front() {
+                functions.push(*function);
+            }
+        }
+
+        while let Some(candidate) = Candidate::find(&functions, &self.inlined) {
+            let occurrence = &candidate.occurrences[0];
+            let ops = functions[occurrence.function].body.block(occurrence.block).ops
+                [occurrence.start..(occurrence.start + candidate.len)]
+                .to_vec();
+            let Some(digest) = self.lower(&ops)? else {
+                // Our estimate of the number of batches is a lower bound, so this is not expected,
+                // but other candidates may still be worth outlining
+                log::debug!(
+                    "not outlining a sequence of {} instructions, as it would be inlined",
+                    candidate.len
+                );
+                self.inlined.push(ops.iter().map(|op| op.into_inner()).collect());
+                continue;
+            };
+            log::debug!(
+                "outlining {} occurrences of a sequence of {} instructions",
+                candidate.occurrences.len(),
+                candidate.len
+            );
+            // Replace the occurrences in reverse, so that the indices of earlier occurrences in the
+            // same block remain valid
+            for occurrence in candidate.occurrences.iter().rev() {
+                let module = functions[occurrence.function].name.module;
+                let callee = self.procedure(digest, module, &ops, &functions);
+                let function = &mut functions[occurrence.function];
+                let ops = &mut function.body.block_mut(occurrence.block).ops;
+                let span = ops[occurrence.start].span();
+                ops.drain(occurrence.start..(occurrence.start + candidate.len));
+                ops.insert(occurrence.start, Span::new(span, Op::Exec(callee)));
+                function.register_absolute_invocation_target(InvokeKind::Exec, callee);
+            }
+        }
+
+        let outlined = self.outlined.into_iter().map(|(_, function)| function);
+        for function in functions.into_iter().chain(outlined) {
+            let module = modules
+                .iter_mut()
+                .find(|module| module.id == function.name.module)
+                .expect("function does not belong to any module");
+            module.push_back(Box::new(function));
+        }
+
+        Ok(())
+    }
+
+    /// Get the procedure of `module` with MAST root `digest`, creating it with body `ops` if it
+    /// does not exist yet
+    fn procedure(
+        &mut self,
+        digest: RpoDigest,
+        module: Ident,
+        ops: &[Span<Op>],
+        functions: &[masm::Function],
+    ) -> FunctionIdent {
+        let existing = self
+            .outlined
+            .iter()
+            .find(|(d, function)| d == &digest && function.name.module == module);
+        if let Some((_, function)) = existing {
+            return function.name;
+        }
+
+        // Choose a name which is not yet used in this module
+        let name = (0..)
+            .map(|n| FunctionIdent {
+                module,
+                function: Ident::with_empty_span(Symbol::intern(format!("outlined_{n}"))),
+            })
+            .find(|name| {
+                functions.iter().all(|f| f.name != *name)
+                    && self.outlined.iter().all(|(_, f)| f.name != *name)
+            })
+            .unwrap();
+        let mut signature = Signature::new([], []);
+        signature.linkage = Linkage::Internal;
+        let mut function = masm::Function::new(name, signature);
+        function.body_mut().ops.extend_from_slice(ops);
+        self.outlined.push((digest, function));
+        name
+    }
+
+    /// Lower `ops` to MAST as the body of a procedure, returning its MAST root, or `None` if the
+    /// procedure would be inlined into its call sites
+    fn lower(&self, ops: &[Span<Op>]) -> Result<Option<RpoDigest>, Report> {
+        let mut module =
+            masm::Module::new(LibraryPath::new("outlined").unwrap(), ModuleKind::Library);
+        let name = FunctionIdent {
+            module: module.id,
+            function: Ident::with_empty_span(Symbol::intern("outlined")),
+        };
+        let mut function = masm::Function::new(name, Signature::new([], []));
+        function.body_mut().ops.extend_from_slice(ops);
+        module.push_back(Box::new(function));
+
+        let mut lowering = MastLowering::new(self.session, &[], None);
+        let function = module.functions().next().unwrap();
+        let digest = lowering.lower_function(&module, function)?;
+        Ok((!lowering.is_inlined_procedure(digest)).then_some(digest))
+    }
+}
+
+/// A sequence of instructions, and the non-overlapping places where it occurs
+struct Candidate {
+    len: usize,
+    occurrences: Vec<Occurrence>,
+}
+
+#[derive(Copy, Clone)]
+struct Occurrence {
+    function: usize,
+    block: BlockId,
+    start: usize,
+}
+
+/// A maximal sequence of consecutive instructions in a block, all of which can be outlined, with
+/// each instruction replaced by an identifier which is equal for equal instructions.
+struct Run {
+    function: usize,
+    block: BlockId,
+    start: usize,
+    ops: Vec<u32>,
+    /// The number of VM operations, the number of immediate values, and the encoded size, of the
+    /// first `n` instructions of the run
+    prefix: Vec<(usize, usize, usize)>,
+}
+impl Run {
+    fn new(function: usize, block: BlockId, start: usize) -> Self {
+        Self {
+            function,
+            block,
+            start,
+            ops: vec![],
+            prefix: vec![(0, 0, 0)],
+        }
+    }
+
+    fn push(&mut self, id: u32, op: &Op) {
+        let (ops, immediates, size) = self.prefix.last().copied().unwrap();
+        self.ops.push(id);
+        self.prefix.push((
+            ops + op.cost(),
+            immediates + num_immediates(op),
+            size + encoded_size(op),
+        ));
+    }
+
+    /// Returns true if this run is long enough to contain a sequence worth outlining
+    fn is_long_enough(&self) -> bool {
+        self.ops.len() >= SEED_LEN && !self.is_inlined(0, self.ops.len())
+    }
+
+    /// Returns true if a procedure consisting of the instructions in `start..end` may be inlined
+    /// into its call sites, based on a lower bound of the number of batches it is assembled to
+    fn is_inlined(&self, start: usize, end: usize) -> bool {
+        let ops = self.prefix[end].0 - self.prefix[start].0;
+        let immediates = self.prefix[end].1 - self.prefix[start].1;
+        let groups = ops.div_ceil(OPS_PER_GROUP) + immediates;
+        groups.div_ceil(GROUPS_PER_BATCH) < PROCEDURE_INLINING_THRESHOLD
+    }
+
+    /// The estimated encoded size of the instructions in `start..end`
+    fn size_between(&self, start: usize, end: usize) -> usize {
+        self.prefix[end].2 - self.prefix[start].2
+    }
+}
+
+impl Candidate {
+    /// Find the candidate whose outlining is estimated to save the most space, if any would,
+    /// ignoring the sequences in `excluded`, and any part of them
+    fn find(functions: &[masm::Function], excluded: &[Vec<Op>]) -> Option<Self> {
+        let mut interner = Interner::default();
+        let mut runs = vec![];
+        for (index, function) in functions.iter().enumerate() {
+            for (block, data) in function.body.blocks.iter() {
+                let mut run = None::<Run>;
+                for (op_index, op) in data.ops.iter().enumerate() {
+                    if !can_outline(op) {
+                        runs.extend(run.take().filter(Run::is_long_enough));
+                        continue;
+                    }
+                    let op = op.into_inner();
+                    run.get_or_insert_with(|| Run::new(index, block, op_index))
+                        .push(interner.intern(op), &op);
+                }
+                runs.extend(run.filter(Run::is_long_enough));
+            }
+        }
+
+        // Group the positions at which each sequence of SEED_LEN instructions starts. The groups
+        // are visited in a deterministic order, so that ties between candidates are always broken
+        // the same way
+        let mut seeds = BTreeMap::<&[u32], Vec<(usize, usize)>>::default();
+        for (run_index, run) in runs.iter().enumerate() {
+            for offset in 0..=(run.ops.len() - SEED_LEN) {
+                seeds
+                    .entry(&run.ops[offset..(offset + SEED_LEN)])
+                    .or_default()
+                    .push((run_index, offset));
+            }
+        }
+
+        // A sequence which occurs within an excluded one is excluded as well, as it is assembled to
+        // no more batches, and so would be inlined too
+        let is_excluded = |run: &Run, start: usize, end: usize| {
+            let sequence = &run.ops[start..end];
+            excluded
+                .iter()
+                .flat_map(|ops| ops.windows(sequence.len()))
+                .any(|window| window.iter().zip(sequence).all(|(op, id)| interner.get(*id) == op))
+        };
+
+        // For each group, extend the match between the first position and the others as far as
+        // possible, and evaluate each prefix of the matches, ordered by length
+        let mut best = None::<(usize, usize, Vec<(usize, usize)>)>;
+        for positions in seeds.values().filter(|positions| positions.len() > 1) {
+            let (first_run, first_offset) = positions[0];
+            let run = &runs[first_run];
+            if run.is_inlined(first_offset, run.ops.len()) {
+                continue;
+            }
+            let first = &run.ops[first_offset..];
+            let mut matches = positions[1..]
+                .iter()
+                .map(|&(run, offset)| {
+                    let other = &runs[run].ops[offset..];
+                    let mut len = first.iter().zip(other).take_while(|(a, b)| a == b).count();
+                    if run == first_run {
+                        len = len.min(offset - first_offset);
+                    }
+                    (len, run, offset)
+                })
+                .filter(|(len, ..)| *len >= SEED_LEN)
+                .collect::<Vec<_>>();
+            matches.sort_by(|a, b| b.0.cmp(&a.0));
+
+            let mut chosen = vec![(first_run, first_offset)];
+            let mut len = usize::MAX;
+            for (match_len, run, offset) in matches {
+                let overlaps = chosen
+                    .iter()
+                    .any(|&(r, o)| r == run && o.max(offset) - o.min(offset) < match_len.min(len));
+                if overlaps {
+                    continue;
+                }
+                len = len.min(match_len);
+                if runs[first_run].is_inlined(first_offset, first_offset + len) {
+                    break;
+                }
+                chosen.push((run, offset));
+                if is_excluded(&runs[first_run], first_offset, first_offset + len) {
+                    continue;
+                }
+                let size = runs[first_run].size_between(first_offset, first_offset + len);
+                let savings = savings(size, chosen.len());
+                if savings > best.as_ref().map(|(savings, ..)| *savings).unwrap_or(0) {
+                    best = Some((savings, len, chosen.clone()));
+                }
+            }
+        }
+
+        best.map(|(_, len, chosen)| {
+            let mut occurrences = chosen
+                .into_iter()
+                .map(|(run, offset)| {
+                    let run = &runs[run];
+                    Occurrence {
+                        function: run.function,
+                        block: run.block,
+                        start: run.start + offset,
+                    }
+                })
+                .collect::<Vec<_>>();
+            occurrences.sort_by_key(|o| (o.function, o.block, o.start));
+            Self { len, occurrences }
+        })
+    }
+}
+
+/// The estimated number of bytes saved by outlining `count` occurrences of a sequence of `size`
+/// bytes into a single procedure, if any
+fn savings(size: usize, count: usize) -> usize {
+    (count * size).saturating_sub(size + MAST_NODE_SIZE + count * CALL_SITE_SIZE)
+}
+
+/// The estimated number of bytes `op` occupies in a serialized MAST forest
+fn encoded_size(op: &Op) -> usize {
+    // Operations are encoded in a single byte, except for `push`, which is followed by its
+    // 8-byte immediate
+    op.cost() + 8 * num_immediates(op)
+}
+
+/// The number of immediate values pushed by `op`, i.e. excluding 0 and 1, which are pushed using
+/// `pad` and `incr`
+fn num_immediates(op: &Op) -> usize {
+    let immediate = |value: u64| usize::from(value > 1);
+    match op {
+        Op::Push(value) => immediate(value.as_int()),
+        Op::PushU8(value) => immediate(*value as u64),
+        Op::PushU16(value) => immediate(*value as u64),
+        Op::PushU32(value) => immediate(*value as u64),
+        Op::Push2(values) => values.iter().map(|value| immediate(value.as_int())).sum(),
+        Op::Pushw(values) => values.iter().map(|value| immediate(value.as_int())).sum(),
+        _ => 0,
+    }
+}
+
+/// Returns true if `op` may be moved into an outlined procedure
+fn can_outline(op: &Span<Op>) -> bool {
+    !matches!(
+        op.into_inner(),
+        Op::If(..)
+            | Op::While(_)
+            | Op::Repeat(..)
+            | Op::Exec(_)
+            | Op::Call(_)
+            | Op::Syscall(_)
+            | Op::DynExec
+            | Op::DynCall
+            | Op::ProcRef(_)
+            | Op::LocAddr(_)
+            | Op::LocStore(_)
+            | Op::LocStorew(_)
+            | Op::LocLoad(_)
+            | Op::LocLoadw(_)
+            | Op::Caller
+            | Op::Emit(_)
+            | Op::Trace(_)
+            | Op::Breakpoint
+            | Op::DebugStack
+            | Op::DebugStackN(_)
+            | Op::DebugMemory
+            | Op::DebugMemoryAt(_)
+            | Op::DebugMemoryRange(..)
+            | Op::DebugFrame
+            | Op::DebugFrameAt(_)
+            | Op::DebugFrameRange(..)
+    )
+}
+
+/// Assigns each distinct instruction a unique identifier, so that sequences of instructions can
+/// be hashed.
+#[derive(Default)]
+struct Interner {
+    ops: Vec<Op>,
+    by_kind: FxHashMap<core::mem::Discriminant<Op>, Vec<u32>>,
+}
+impl Interner {
+    fn intern(&mut self, op: Op) -> u32 {
+        let ids = self.by_kind.entry(core::mem::discriminant(&op)).or_default();
+        if let Some(id) = ids.iter().copied().find(|id| self.ops[*id as usize] == op) {
+            return id;
+        }
+        let id = self.ops.len() as u32;
+        self.ops.push(op);
+        ids.push(id);
+        id
+    }
+
+    fn get(&self, id: u32) -> &Op {
+        &self.ops[id as usize]
+    }
+}
@@ -0,0 +1,340 @@
+use core::fmt;
+
+use midenc_hir::{diagnostics::Span, Felt, FieldElement};
+use smallvec::{smallvec, SmallVec};
+
+use crate::masm::{self, Op};
+
+/// A peephole rewrite rule, which replaces a short sequence of instructions with a cheaper,
+/// equivalent sequence.
+pub struct Rule {
+    /// The name of this rule, as reported in [PeepholeStats]
+    pub name: &'static str,
+    /// The number of consecutive instructions this rule matches against
+    pub window: usize,
+    /// Returns the replacement for `ops`, if this rule applies to them
+    ///
+    /// The replacement must be strictly shorter than `ops`, or, if of the same length, must not
+    /// be matched again by this rule, so that the rewrite terminates. A replacement which is
+    /// estimated to take more cycles than `ops` is never applied.
+    pub rewrite: fn(&[Op]) -> Option<SmallVec<[Op; 2]>>,
+}
+
+/// The rules applied by the [Peephole] optimizer, in the order they are tried at each position.
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "swap-swap",
+        window: 2,
+        rewrite: |ops| match ops {
+            [Op::Swap(a), Op::Swap(b)] | [Op::Swapw(a), Op::Swapw(b)] if a == b => {
+                Some(smallvec![])
+            }
+            [Op::Swapdw, Op::Swapdw] => Some(smallvec![]),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "movup-movdn",
+        window: 2,
+        rewrite: |ops| match ops {
+            [Op::Movup(a), Op::Movdn(b)]
+            | [Op::Movdn(a), Op::Movup(b)]
+            | [Op::Movupw(a), Op::Movdnw(b)]
+            | [Op::Movdnw(a), Op::Movupw(b)]
+                if a == b =>
+            {
+                Some(smallvec![])
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "move-one-to-swap",
+        window: 1,
+        rewrite: |ops| match ops {
+            [Op::Movup(1) | Op::Movdn(1)] => Some(smallvec![Op::Swap(1)]),
+            [Op::Movupw(1) | Op::Movdnw(1)] => Some(smallvec![Op::Swapw(1)]),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "push-drop",
+        window: 2,
+        rewrite: |ops| match ops {
+            [op, Op::Drop] if push_value(op).is_some() || matches!(op, Op::Dup(_)) => {
+                Some(smallvec![])
+            }
+            [Op::Padw | Op::Pushw(_) | Op::Dupw(_), Op::Dropw] => Some(smallvec![]),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "push-swap",
+        window: 3,
+        rewrite: |ops| match ops {
+            [a, b, Op::Swap(1)] if push_value(a).is_some() && push_value(b).is_some() => {
+                Some(smallvec![*b, *a])
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "push-dup-swap",
+        window: 3,
+        rewrite: |ops| match ops {
+            [push, Op::Dup(n), Op::Swap(1)] if *n > 0 && push_value(push).is_some() => {
+                Some(smallvec![Op::Dup(*n - 1), *push])
+            }
+            _ => None,
+        },
+    },
+    Rule {
+        name: "identity-arith",
+        window: 1,
+        rewrite: |ops| match ops {
+            [Op::AddImm(imm) | Op::SubImm(imm)] if *imm == Felt::ZERO => Some(smallvec![]),
+            [Op::MulImm(imm) | Op::DivImm(imm)] if *imm == Felt::ONE => Some(smallvec![]),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "push-identity-arith",
+        window: 2,
+        rewrite: |ops| match ops {
+            [op, Op::Add | Op::Sub] if push_value(op) == Some(0) => Some(smallvec![]),
+            [op, Op::Mul | Op::Div] if push_value(op) == Some(1) => Some(smallvec![]),
+            _ => None,
+        },
+    },
+    Rule {
+        name: "redundant-u32assert",
+        window: 2,
+        rewrite: |ops| match ops {
+            [op, Op::U32Assert | Op::U32AssertWithError(_)] if produces_u32(op) => {
+                Some(smallvec![*op])
+            }
+            _ => None,
+        },
+    },
+];
+
+/// Returns the value pushed on the operand stack by `op`, if `op` pushes a constant
+fn push_value(op: &Op) -> Option<u64> {
+    match op {
+        Op::Push(value) => Some(value.as_int()),
+        Op::PushU8(value) => Some(*value as u64),
+        Op::PushU16(value) => Some(*value as u64),
+        Op::PushU32(value) => Some(*value as u64),
+        _ => None,
+    }
+}
+
+/// Returns true if `op`, when it executes successfully, is guaranteed to leave a valid u32 value
+/// on top of the operand stack.
+fn produces_u32(op: &Op) -> bool {
+    if push_value(op).is_some_and(|value| value <= u32::MAX as u64) {
+        return true;
+    }
+    matches!(
+        op,
+        Op::U32Assert
+            | Op::U32AssertWithError(_)
+            | Op::U32Assert2
+            | Op::U32Assert2WithError(_)
+            | Op::U32Assertw
+            | Op::U32AssertwWithError(_)
+            | Op::U32WrappingAdd
+            | Op::U32WrappingAddImm(_)
+            | Op::U32WrappingSub
+            | Op::U32WrappingSubImm(_)
+            | Op::U32WrappingMul
+            | Op::U32WrappingMulImm(_)
+            | Op::U32Div
+            | Op::U32DivImm(_)
+            | Op::U32Mod
+            | Op::U32ModImm(_)
+            | Op::U32And
+            | Op::U32Or
+            | Op::U32Xor
+            | Op::U32Not
+            | Op::U32Shl
+            | Op::U32ShlImm(_)
+            | Op::U32Shr
+            | Op::U32ShrImm(_)
+            | Op::U32Rotl
+            | Op::U32RotlImm(_)
+            | Op::U32Rotr
+            | Op::U32RotrImm(_)
+            | Op::U32Popcnt
+            | Op::U32Clz
+            | Op::U32Ctz
+            | Op::U32Clo
+            | Op::U32Cto
+            | Op::U32Min
+            | Op::U32MinImm(_)
+            | Op::U32Max
+            | Op::U32MaxImm(_)
+            | Op::U32Lt
+            | Op::U32LtImm(_)
+            | Op::U32Lte
+            | Op::U32LteImm(_)
+            | Op::U32Gt
+            | Op::U32GtImm(_)
+            | Op::U32Gte
+            | Op::U32GteImm(_)
+            | Op::U32Test
+            | Op::U32Testw
+            | Op::Eq
+            | Op::EqImm(_)
+            | Op::Neq
+            | Op::NeqImm(_)
+            | Op::Lt
+            | Op::LtImm(_)
+            | Op::Lte
+            | Op::LteImm(_)
+            | Op::Gt
+            | Op::GtImm(_)
+            | Op::Gte
+            | Op::GteImm(_)
+            | Op::Eqw
+            | Op::IsOdd
+            | Op::Not
+            | Op::And
+            | Op::Or
+            | Op::Xor
+    )
+}
+
+/// The number of times each of the [RULES] was applied by a [Peephole] optimizer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeepholeStats {
+    counts: [usize; RULES.len()],
+}
+impl Default for PeepholeStats {
+    fn default() -> Self {
+        Self {
+            counts: [0; RULES.len()],
+        }
+    }
+}
+impl PeepholeStats {
+    /// Get the number of times the rule named `name` was applied
+    pub fn get(&self, name: &str) -> usize {
+        RULES
+            .iter()
+            .position(|rule| rule.name == name)
+            .map(|index| self.counts[index])
+            .unwrap_or_else(|| panic!("unknown peephole rule '{name}'"))
+    }
+
+    /// Get the total number of rewrites applied
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Iterate over the rules which were applied at least once, with their counts
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        RULES
+            .iter()
+            .zip(self.counts.iter().copied())
+            .filter(|(_, count)| *count > 0)
+            .map(|(rule, count)| (rule.name, count))
+    }
+}
+impl fmt::Display for PeepholeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_map();
+        for (name, count) in self.iter() {
+            list.entry(&format_args!("{name}"), &count);
+        }
+        list.finish()
+    }
+}
+
+/// The [Peephole] optimizer rewrites the blocks of a [masm::Function] using [RULES], until none
+/// of them apply anymore.
+///
+/// This cleans up redundant sequences which are produced by the emitter when composing the code
+/// for individual instructions, e.g. `swap.1 swap.1`, or `dup.0 drop`, which cannot be avoided
+/// locally during emission.
+///
+/// Each rule only inspects the instructions of a single block, and treats control flow
+/// instructions, e.g. `if.true`, as opaque, so no rewrite spans the boundary of a block.
+#[derive(Default)]
+pub struct Peephole {
+    stats: PeepholeStats,
+}
+impl Peephole {
+    /// Get the number of rewrites applied so far, by rule
+    pub fn stats(&self) -> &PeepholeStats {
+        &self.stats
+    }
+
+    /// Optimize every block of `function`, returning true if any changes were made
+    pub fn run(&mut self, function: &mut masm::Function) -> bool {
+        let mut changed = false;
+        for block in function.body.blocks.values_mut() {
+            changed |= self.run_block(&mut block.ops);
+        }
+        changed
+    }
+
+    /// Optimize `ops`, returning true if any changes were made
+    pub fn run_block(&mut self, ops: &mut SmallVec<[Span<Op>; 4]>) -> bool {
+        let mut changed = false;
+        loop {
+            let mut changed_this_pass = false;
+            let mut index = 0;
+            while index < ops.len() {
+                if self.rewrite_at(ops, index) {
+                    changed_this_pass = true;
+                    // A rewrite may enable rules which match starting before `index`, so back up
+                    // by the largest window any rule could have, less the instruction at `index`
+                    index = index.saturating_sub(MAX_WINDOW - 1);
+                } else {
+                    index += 1;
+                }
+            }
+            if !changed_this_pass {
+                break;
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Try each rule at `index`, applying the first one that matches
+    fn rewrite_at(&mut self, ops: &mut SmallVec<[Span<Op>; 4]>, index: usize) -> bool {
+        for (rule_index, rule) in RULES.iter().enumerate() {
+            let Some(window) = ops.get(index..(index + rule.window)) else {
+                continue;
+            };
+            let span = window[0].span();
+            let window = window.iter().map(|op| op.into_inner()).collect::<SmallVec<[Op; 4]>>();
+            let Some(replacement) = (rule.rewrite)(&window) else {
+                continue;
+            };
+            let cost = |ops: &[Op]| ops.iter().map(Op::cost).sum::<usize>();
+            if cost(&replacement) <= cost(&window) {
+                ops.drain(index..(index + rule.window));
+                ops.insert_many(index, replacement.into_iter().map(|op| Span::new(span, op)));
+                self.stats.counts[rule_index] += 1;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// The largest window of any of the [RULES]
+const MAX_WINDOW: usize = {
+    let mut max = 0;
+    let mut index = 0;
+    while index < RULES.len() {
+        if RULES[index].window > max {
+            max = RULES[index].window;
+        }
+        index += 1;
+    }
+    max
+};
@@ -12,6 +12,7 @@ use midenc_hir_analysis::{
 };
 use smallvec::SmallVec;
 
+use super::layout::{StackLayout, StackLayoutPlanner};
 use crate::{codegen::Constraint, masm};
 
 /// Information about a block's successor
@@ -169,17 +170,21 @@ impl SparseMapValue<hir::Block> for BlockInfo {
 /// [Schedule] describes an instruction scheduling plan for a single IR function.
 ///
 /// The plan describes the order in which blocks will be scheduled,
-/// and the schedule for instructions in each block.
+/// the schedule for instructions in each block, and the layout of
+/// block arguments and live-through values on the operand stack at
+/// block boundaries.
 #[derive(Debug)]
 pub struct Schedule {
     pub block_infos: SparseMap<hir::Block, Rc<BlockInfo>>,
     pub block_schedules: SecondaryMap<hir::Block, Vec<ScheduleOp>>,
+    pub stack_layout: StackLayout,
 }
 impl Schedule {
     pub fn new() -> Self {
         Self {
             block_infos: Default::default(),
             block_schedules: SecondaryMap::new(),
+            stack_layout: Default::default(),
         }
     }
 
@@ -282,6 +287,12 @@ impl<'a> Scheduler<'a> {
             block_scheduler.schedule(schedule);
         }
 
+        // Now that every block is scheduled, plan the layout of block arguments, and of values
+        // which are live through loop headers, across the control flow edges of the function
+        let stack_layout =
+            StackLayoutPlanner::new(self.f, self.loops, self.liveness, &self.schedule).plan();
+        self.schedule.stack_layout = stack_layout;
+
         self.schedule
     }
 
@@ -1186,11 +1197,11 @@ fn assign_control_dependencies(
         // store. In effect, this makes the read dependent on the most recent write, even if there
         // is no direct connection between the two instructions otherwise.
         //
-        // If it writes memory, ensure that there is an edge in the graph from the last observed
-        // load. In effect, this makes the write dependent on the most recent read, even if there
-        // is no direct connection between the two instructions otherwise.
+        // If it writes memory, ensure that there is an edge in the graph from every load observed
+        // since the last store. In effect, this makes the write dependent on all of the reads it
+        // could clobber, even if there is no direct connection between the instructions otherwise.
         //
-        // If it both reads and writes, ensure there are edges to both the last load and store.
+        // If it both reads and writes, ensure there are edges to both the last store and loads.
         let mem_read = opcode.reads_memory();
         let mem_write = opcode.writes_memory();
         // Ensure store-store ordering as well as load-store ordering
@@ -1202,16 +1213,17 @@ fn assign_control_dependencies(
                     graph.add_dependency(node_id, last_store);
                 }
             }
-            reads.push(node_id);
         }
         if mem_write {
             // Have there been any loads observed?
-            if let Some(last_load) = reads.last().copied() {
+            for last_load in reads.drain(..) {
                 if !graph.is_reachable_from(node_id, last_load) {
                     graph.add_dependency(node_id, last_load);
                 }
             }
             writes.push(node_id);
+        } else if mem_read {
+            reads.push(node_id);
         }
 
         // At this point, we want to handle adding a control dependency from the terminator to this
@@ -1,5 +1,8 @@
+use std::collections::BTreeMap;
+
 use miden_assembly::Library as CompiledLibrary;
-use midenc_hir::Symbol;
+use miden_core::crypto::hash::RpoDigest;
+use midenc_hir::{FunctionIdent, Symbol};
 use midenc_session::{diagnostics::Report, Emit, OutputMode, OutputType, Session};
 
 use crate::{Library, MastArtifact, Module, Program};
@@ -30,6 +33,14 @@ impl MasmArtifact {
         }
     }
 
+    /// Compute the MAST root of every function in this artifact, as it is lowered to MAST
+    pub fn procedure_digests(
+        &self,
+        session: &Session,
+    ) -> Result<BTreeMap<FunctionIdent, RpoDigest>, Report> {
+        self.library().procedure_digests(session)
+    }
+
     pub fn insert(&mut self, module: Box<Module>) {
         match self {
             Self::Executable(ref mut program) => program.insert(module),
@@ -37,6 +48,13 @@ impl MasmArtifact {
         }
     }
 
+    pub(crate) fn library(&self) -> &Library {
+        match self {
+            Self::Executable(ref program) => program.library(),
+            Self::Library(ref lib) => lib,
+        }
+    }
+
     pub fn link_library(&mut self, lib: CompiledLibrary) {
         match self {
             Self::Executable(ref mut program) => program.link_library(lib),
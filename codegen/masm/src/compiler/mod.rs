@@ -42,6 +42,20 @@ impl<'a> MasmCompiler<'a> {
         let modules = input.modules_mut().take();
         for mut module in modules.into_iter() {
             rewrites.apply(&mut module, &mut self.analyses, self.session)?;
+            // The program has already been linked, so any globals declared by the rewrites must
+            // be added to the program's global variable table here. Such globals are always
+            // declared with `odr` linkage, as the same global may be declared by several modules.
+            for global in module.globals().iter() {
+                if global.linkage != hir::Linkage::Odr || input.globals().exists(global.name) {
+                    continue;
+                }
+                let mut global = global.clone();
+                if let Some(init) = global.init.take() {
+                    let data = module.globals().get_constant(init).clone();
+                    global.init = Some(input.globals_mut().insert_refcounted_constant(data));
+                }
+                input.globals_mut().try_insert(global)?;
+            }
             input.modules_mut().insert(module);
         }
 
@@ -123,6 +137,7 @@ where
     let mut rewrites = RewriteSet::default();
     if registered.len() == 0 {
         if session.should_codegen() {
+            rewrites.push(midenc_hir_transform::LowerWideSignatures);
             let fn_rewrites = default_function_rewrites(session);
             for rewrite in fn_rewrites {
                 rewrites.push(ModuleRewritePassAdapter::new(rewrite));
@@ -144,6 +159,7 @@ pub fn default_function_rewrites(session: &Session) -> RewriteSet<hir::Function>
     // Otherwise, assume that the intent was to skip those rewrites and do not add them
     let mut rewrites = RewriteSet::default();
     if session.should_codegen() {
+        rewrites.push(transforms::LowerSwitch);
         rewrites.push(transforms::SplitCriticalEdges);
         rewrites.push(transforms::Treeify);
         rewrites.push(transforms::InlineBlocks);
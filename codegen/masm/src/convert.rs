@@ -1,15 +1,20 @@
 use miden_assembly::LibraryPath;
 use midenc_hir::{
     self as hir,
+    diagnostics::{Report, Severity},
     pass::{AnalysisManager, ConversionPass, ConversionResult},
     ConversionPassRegistration, PassInfo,
 };
-use midenc_hir_analysis as analysis;
-use midenc_session::Session;
+use midenc_hir_analysis::{self as analysis, Rule};
+use midenc_session::{OptLevel, Session};
 
 use crate::{
-    codegen::{FunctionEmitter, OperandStack, Scheduler, TypedValue},
-    masm, MasmArtifact,
+    codegen::{
+        FunctionEmitter, OperandStack, Outliner, Peephole, Scheduler, TypedValue,
+        CANONICAL_ABI_PARAMS_ADDR,
+    },
+    masm::{self, Op},
+    MasmArtifact,
 };
 
 type ProgramGlobalVariableAnalysis = analysis::GlobalVariableAnalysis<hir::Program>;
@@ -24,6 +29,7 @@ type ModuleGlobalVariableAnalysis = analysis::GlobalVariableAnalysis<hir::Module
 /// * If converting multiple modules, they must be linked into a [Program], in order to ensure that
 ///   there are no undefined symbols, and that the placement of global variables in linear memory
 ///   has been fixed.
+/// * There are no `switch` instructions, or the [LowerSwitch] rewrite has been applied.
 /// * There are no critical edges in the control flow graph, or the [SplitCriticalEdges] rewrite has
 ///   been applied.
 /// * The control flow graph is a tree, with the exception of loop header blocks. This means that
@@ -31,6 +37,10 @@ type ModuleGlobalVariableAnalysis = analysis::GlobalVariableAnalysis<hir::Module
 ///   more information.
 ///
 /// Any further optimizations or rewrites are considered optional.
+///
+/// Unless optimizations are disabled, the emitted code is cleaned up by the [Peephole] optimizer.
+/// When optimizing for size, code which is repeated across the functions of a program is extracted
+/// into shared procedures by the [Outliner].
 #[derive(ConversionPassRegistration)]
 pub struct ConvertHirToMasm<T>(core::marker::PhantomData<T>);
 impl<T> Default for ConvertHirToMasm<T> {
@@ -77,12 +87,53 @@ impl ConversionPass for ConvertHirToMasm<hir::Program> {
         // Remove the set of modules to compile from the program
         let modules = program.modules_mut().take();
 
+        let mut masm_modules = vec![];
         for module in modules.into_iter() {
             // Convert the module
             let mut convert_to_masm = ConvertHirToMasm::<hir::Module>::default();
-            let masm_module = convert_to_masm.convert(module, analyses, session)?;
+            let mut masm_module = convert_to_masm.convert(module, analyses, session)?;
+
+            // Functions using the canonical ABI are invoked with `call`, and so execute in a fresh
+            // memory context, which must be initialized on entry
+            if masm_module.functions().any(is_cross_context_entry) {
+                let mut functions = vec![];
+                while let Some(mut function) = masm_module.pop_front() {
+                    if is_cross_context_entry(&function) {
+                        let mut prologue = masm::Block {
+                            id: function.body.body,
+                            ops: Default::default(),
+                        };
+                        artifact.library().emit_context_initialization(&mut prologue);
+                        if let Some(signature) =
+                            masm_module.export_signature(function.name.function)
+                        {
+                            emit_canonical_abi_marshalling(
+                                &mut function,
+                                &mut prologue,
+                                signature,
+                                session,
+                            )?;
+                        }
+                        function.body_mut().ops.insert_many(0, prologue.ops);
+                    }
+                    functions.push(function);
+                }
+                for function in functions.into_iter() {
+                    masm_module.push_back(function);
+                }
+            }
+
+            masm_modules.push(masm_module);
+        }
+
+        // Extract code which is repeated across the functions of the program into shared
+        // procedures, when optimizing for size
+        if matches!(session.options.optimize, OptLevel::Size | OptLevel::SizeMin) {
+            Outliner::new(session).run(&mut masm_modules)?;
+        }
 
-            // Add to the final Miden Assembly program
+        // Add to the final Miden Assembly program
+        for masm_module in masm_modules {
             artifact.insert(masm_module);
         }
 
@@ -90,6 +141,79 @@ impl ConversionPass for ConvertHirToMasm<hir::Program> {
     }
 }
 
+/// Returns true if `function` may be invoked from another context using `call`
+fn is_cross_context_entry(function: &masm::Function) -> bool {
+    function.signature.cc == hir::CallConv::Wasm && function.signature.is_public()
+}
+
+/// Emit the code by which `function`, exported using the canonical ABI with the component-level
+/// `signature`, receives its arguments, or returns its results, through memory, when they do not
+/// fit on the operand stack.
+///
+/// Such arguments are passed by the caller as a commitment to the memory containing them, and the
+/// offset of the data in the first word of that memory, which `prologue` uses to copy the data
+/// into the memory of the callee, replacing them with a pointer to it. Likewise, the pointer to
+/// the results returned by `function` is replaced with a commitment to them. See the lowering of
+/// `call` for the caller side of this.
+fn emit_canonical_abi_marshalling(
+    function: &mut masm::Function,
+    prologue: &mut masm::Block,
+    signature: &hir::Signature,
+    session: &Session,
+) -> Result<(), Report> {
+    let span = function.span;
+    let area_layout = |tys: &mut dyn Iterator<Item = &hir::Type>| {
+        hir::canonical_abi_record_layout(tys).ok_or_else(|| {
+            session
+                .diagnostics
+                .diagnostic(Severity::Error)
+                .with_message("invalid export")
+                .with_primary_label(
+                    span,
+                    format!(
+                        "the signature of '{}' requires lifting or lowering values which refer to \
+                         other memory, which is not yet supported",
+                        &function.name
+                    ),
+                )
+                .into_report()
+        })
+    };
+    let flat_count = |params: &[hir::AbiParam]| {
+        params
+            .iter()
+            .map(|param| hir::canonical_abi_flat_count(&param.ty))
+            .sum::<usize>()
+    };
+
+    if flat_count(&signature.params) > hir::MAX_FLAT_PARAMS {
+        let layout = area_layout(&mut signature.params.iter().map(|param| &param.ty))?;
+        // [COM, offset, pad..] -> [ptr, pad..]
+        prologue.push(Op::PushU32(layout.size() as u32), span);
+        prologue.push(Op::Movdn(5), span);
+        prologue.push(Op::PushU32(CANONICAL_ABI_PARAMS_ADDR), span);
+        prologue.push(Op::Movdn(6), span);
+        // The operand stack is never less than 16 elements deep, so it is padded with zeroes here
+        prologue.push(Op::Exec("intrinsics::mem::copy_from_advice_map".parse().unwrap()), span);
+    }
+
+    if flat_count(&signature.results) > hir::MAX_FLAT_RESULTS {
+        let layout = area_layout(&mut signature.results.iter().map(|result| &result.ty))?;
+        let body = function.body_mut();
+        // [ptr, pad..] -> [COM, offset, pad..]
+        body.push(Op::PushU32(layout.size() as u32), span);
+        body.push(Op::Swap(1), span);
+        body.push(Op::Exec("intrinsics::mem::copy_to_advice_map".parse().unwrap()), span);
+        // Restore the operand stack to a depth of 16
+        for _ in 0..4 {
+            body.push(Op::Movup(5), span);
+            body.push(Op::Drop, span);
+        }
+    }
+
+    Ok(())
+}
+
 impl ConversionPass for ConvertHirToMasm<hir::Module> {
     type From = Box<hir::Module>;
     type To = Box<masm::Module>;
@@ -122,11 +246,23 @@ impl ConversionPass for ConvertHirToMasm<hir::Module> {
             analyses.get_or_compute::<ModuleGlobalVariableAnalysis>(&module, session)?;
         }
 
+        // Calls using the canonical ABI are lowered to `call`, which we can only do for calls to
+        // other modules, with arguments and results that are passed entirely on the operand stack
+        let mut canonical_abi_calls = analysis::CanonicalAbiCalls::new(&module);
+
         // Removing a function via this cursor will move the cursor to
         // the next function in the module. Once the end of the module
         // is reached, the cursor will point to the null object, and
         // `remove` will return `None`.
         while let Some(function) = module.pop_front() {
+            if function.signature.is_public() {
+                let signature = module
+                    .export_signature(function.id.function)
+                    .cloned()
+                    .unwrap_or_else(|| function.signature.clone());
+                masm_module.set_export_signature(function.id.function, signature);
+            }
+            canonical_abi_calls.validate(&function, &session.diagnostics)?;
             let mut convert_to_masm = ConvertHirToMasm::<&hir::Function>::default();
             let masm_function = convert_to_masm.convert(&function, analyses, session)?;
             masm_module.push_back(Box::new(masm_function));
@@ -168,6 +304,7 @@ impl<'a> ConversionPass for ConvertHirToMasm<&'a hir::Function> {
             let domtree = analyses.get_or_compute::<analysis::DominatorTree>(f, session)?;
             let loops = analyses.get_or_compute::<analysis::LoopAnalysis>(f, session)?;
             let liveness = analyses.get_or_compute::<analysis::LivenessAnalysis>(f, session)?;
+            let local_slots = analyses.get_or_compute::<analysis::LocalSlotAnalysis>(f, session)?;
 
             let mut stack = OperandStack::default();
             for arg in f.dfg.block_args(entry).iter().rev().copied() {
@@ -183,11 +320,26 @@ impl<'a> ConversionPass for ConvertHirToMasm<&'a hir::Function> {
                            dbg!(&schedule);
                        }
             */
-            let emitter =
-                FunctionEmitter::new(f, &mut f_prime, &domtree, &loops, &liveness, &globals);
+            let emitter = FunctionEmitter::new(
+                f,
+                &mut f_prime,
+                &domtree,
+                &loops,
+                &liveness,
+                &local_slots,
+                &globals,
+            );
             emitter.emit(schedule, stack);
         }
 
+        // Clean up redundant instruction sequences left behind by the emitter
+        if !matches!(session.options.optimize, OptLevel::None) {
+            let mut peephole = Peephole::default();
+            if peephole.run(&mut f_prime) {
+                log::debug!("peephole optimizations applied to '{}': {}", f.id, peephole.stats());
+            }
+        }
+
         Ok(f_prime)
     }
 }
@@ -0,0 +1,373 @@
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use midenc_hir::{FunctionIdent, Symbol};
+use midenc_session::{Emit, OutputMode, OutputType, Session};
+use serde::Serialize;
+
+use crate::masm::{BlockId, Function, Module, Op};
+
+/// A cycle count which may depend on the number of iterations of one or more `while.true` loops.
+///
+/// This is represented as a constant number of cycles, plus a term for every loop, each of which
+/// is the product of the number of iterations of that loop, `nN`, and the cost of its body.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct SymbolicCycles {
+    /// The number of cycles which does not depend on any loop
+    pub constant: usize,
+    /// The cycles spent in loops, with an unknown number of iterations
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub loops: Vec<LoopCycles>,
+}
+
+/// The cycles spent in a `while.true` loop, whose number of iterations is given by `nN`, where `N`
+/// is the loop variable, and which may be executed `factor` times, e.g. when nested in a `repeat`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoopCycles {
+    pub var: usize,
+    pub factor: usize,
+    pub body: SymbolicCycles,
+}
+
+impl SymbolicCycles {
+    pub const fn new(constant: usize) -> Self {
+        Self {
+            constant,
+            loops: vec![],
+        }
+    }
+
+    /// Returns true if this count does not depend on any loop
+    pub fn is_constant(&self) -> bool {
+        self.loops.is_empty()
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.constant += other.constant;
+        self.loops.extend(other.loops.iter().cloned());
+    }
+
+    fn scale(&mut self, n: usize) {
+        self.constant *= n;
+        for term in self.loops.iter_mut() {
+            term.factor *= n;
+        }
+        self.loops.retain(|term| term.factor > 0);
+    }
+
+    /// Renumber the loop variables of `self` so they follow on from `next_var`
+    fn renumber(&mut self, next_var: &mut usize) {
+        for term in self.loops.iter_mut() {
+            term.var = *next_var;
+            *next_var += 1;
+            term.body.renumber(next_var);
+        }
+    }
+}
+impl fmt::Display for SymbolicCycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.constant)?;
+        for term in self.loops.iter() {
+            f.write_str(" + ")?;
+            if term.factor > 1 {
+                write!(f, "{} * ", term.factor)?;
+            }
+            write!(f, "n{} * ({})", term.var, &term.body)?;
+        }
+        Ok(())
+    }
+}
+
+/// The estimated number of cycles it takes to execute a procedure.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct CycleEstimate {
+    /// The fewest cycles any execution of the procedure takes, assuming every `while.true` loop
+    /// exits without executing its body
+    pub min: usize,
+    /// The most cycles any execution of the procedure takes, in terms of the number of iterations
+    /// of each `while.true` loop
+    pub max: SymbolicCycles,
+    /// Procedures which are invoked, but whose cost is not known, e.g. because they are not part
+    /// of the program being analyzed, or are recursive. Only the overhead of invoking them is
+    /// counted.
+    #[serde(
+        skip_serializing_if = "BTreeSet::is_empty",
+        serialize_with = "serialize_callees"
+    )]
+    pub unknown_callees: BTreeSet<FunctionIdent>,
+    /// True if the procedure contains `dynexec` or `dyncall`, whose callees cannot be known
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub dynamic_calls: bool,
+}
+impl CycleEstimate {
+    fn new(cycles: usize) -> Self {
+        Self {
+            min: cycles,
+            max: SymbolicCycles::new(cycles),
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if the estimate does not depend on any unknown quantity
+    pub fn is_exact(&self) -> bool {
+        self.max.is_constant()
+            && self.min == self.max.constant
+            && self.unknown_callees.is_empty()
+            && !self.dynamic_calls
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.min += other.min;
+        self.max.add(&other.max);
+        self.unknown_callees.extend(other.unknown_callees.iter().copied());
+        self.dynamic_calls |= other.dynamic_calls;
+    }
+}
+impl fmt::Display for CycleEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max.constant && self.max.is_constant() {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}..={}", self.min, &self.max)
+        }
+    }
+}
+
+/// Statically estimates the number of cycles it takes to execute the procedures of a set of
+/// modules, using [Op::cost].
+///
+/// Estimates are computed on demand, and cached, as the estimate of a procedure includes the
+/// estimates of the procedures it invokes.
+pub struct CycleAnalysis<'a> {
+    functions: BTreeMap<FunctionIdent, &'a Function>,
+    estimates: BTreeMap<FunctionIdent, CycleEstimate>,
+    in_progress: BTreeSet<FunctionIdent>,
+}
+impl<'a> CycleAnalysis<'a> {
+    pub fn new(modules: impl IntoIterator<Item = &'a Module>) -> Self {
+        let functions = modules
+            .into_iter()
+            .flat_map(|module| module.functions())
+            .map(|function| (function.name, function))
+            .collect();
+        Self {
+            functions,
+            estimates: Default::default(),
+            in_progress: Default::default(),
+        }
+    }
+
+    /// Get the estimate for the procedure `id`, if it is defined in the modules being analyzed
+    pub fn estimate(&mut self, id: FunctionIdent) -> Option<&CycleEstimate> {
+        if !self.estimates.contains_key(&id) {
+            let function = *self.functions.get(&id)?;
+            self.in_progress.insert(id);
+            let mut next_var = 0;
+            let estimate = self.estimate_block(function, function.body.body, &mut next_var);
+            self.in_progress.remove(&id);
+            return Some(self.estimates.entry(id).or_insert(estimate));
+        }
+        self.estimates.get(&id)
+    }
+
+    fn estimate_block(
+        &mut self,
+        function: &Function,
+        block: BlockId,
+        next_var: &mut usize,
+    ) -> CycleEstimate {
+        let mut estimate = CycleEstimate::default();
+        for op in function.body.block(block).ops.iter() {
+            let op = op.into_inner();
+            estimate.add(&CycleEstimate::new(op.cost()));
+            match op {
+                Op::If(then_blk, else_blk) => {
+                    let then_blk = self.estimate_block(function, then_blk, next_var);
+                    let else_blk = self.estimate_block(function, else_blk, next_var);
+                    // The maximum of two symbolic counts cannot be expressed in general, so we
+                    // take the larger constant, and the loops of both branches as an upper bound
+                    let mut branch = CycleEstimate {
+                        min: then_blk.min.min(else_blk.min),
+                        max: SymbolicCycles::new(then_blk.max.constant.max(else_blk.max.constant)),
+                        ..Default::default()
+                    };
+                    for arm in [then_blk, else_blk] {
+                        branch.max.loops.extend(arm.max.loops);
+                        branch.unknown_callees.extend(arm.unknown_callees);
+                        branch.dynamic_calls |= arm.dynamic_calls;
+                    }
+                    estimate.add(&branch);
+                }
+                Op::While(body) => {
+                    let var = *next_var;
+                    *next_var += 1;
+                    let body = self.estimate_block(function, body, next_var);
+                    estimate.max.loops.push(LoopCycles {
+                        var,
+                        factor: 1,
+                        body: body.max,
+                    });
+                    estimate.unknown_callees.extend(body.unknown_callees);
+                    estimate.dynamic_calls |= body.dynamic_calls;
+                }
+                Op::Repeat(n, body) => {
+                    let mut body = self.estimate_block(function, body, next_var);
+                    body.min *= n as usize;
+                    body.max.scale(n as usize);
+                    estimate.add(&body);
+                }
+                Op::Exec(callee) | Op::Call(callee) | Op::Syscall(callee) => {
+                    if self.in_progress.contains(&callee) {
+                        estimate.unknown_callees.insert(callee);
+                        continue;
+                    }
+                    match self.estimate(callee) {
+                        Some(callee) => {
+                            let mut callee = callee.clone();
+                            callee.max.renumber(next_var);
+                            estimate.add(&callee);
+                        }
+                        None => {
+                            estimate.unknown_callees.insert(callee);
+                        }
+                    }
+                }
+                Op::DynExec | Op::DynCall => {
+                    estimate.dynamic_calls = true;
+                }
+                _ => (),
+            }
+        }
+        estimate
+    }
+}
+
+/// A report of the estimated cycle counts of the exported procedures of a program or library,
+/// as emitted for [OutputType::Cycles], with one procedure per line.
+///
+/// See [CycleReport::as_json] for the same report as JSON.
+#[derive(Debug, Default, Serialize)]
+pub struct CycleReport {
+    pub procedures: Vec<ProcedureCycles>,
+}
+
+/// The estimated cycle count of a single procedure in a [CycleReport]
+#[derive(Debug, Serialize)]
+pub struct ProcedureCycles {
+    #[serde(serialize_with = "serialize_function_ident")]
+    pub name: FunctionIdent,
+    #[serde(flatten)]
+    pub estimate: CycleEstimate,
+}
+
+fn serialize_function_ident<S>(id: &FunctionIdent, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&procedure_path(id))
+}
+
+fn serialize_callees<S>(callees: &BTreeSet<FunctionIdent>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_seq(callees.iter().map(procedure_path))
+}
+
+/// Format `id` as the fully-qualified path of a procedure, e.g. `std::math::u64::add`
+fn procedure_path(id: &FunctionIdent) -> String {
+    format!("{}::{}", id.module.as_str(), id.function.as_str())
+}
+
+impl CycleReport {
+    /// Estimate the cycle counts of the exported procedures of every module in `modules` for which
+    /// `report` returns true.
+    ///
+    /// Modules which are not reported on are still used to resolve the costs of the procedures
+    /// they define, e.g. intrinsics.
+    pub fn new<'a>(
+        modules: impl IntoIterator<Item = &'a Module>,
+        report: impl Fn(&Module) -> bool,
+    ) -> Self {
+        let modules = modules.into_iter().collect::<Vec<_>>();
+        let mut analysis = CycleAnalysis::new(modules.iter().copied());
+        let procedures = modules
+            .into_iter()
+            .filter(|module| report(module))
+            .flat_map(|module| module.functions())
+            .filter(|function| function.signature.is_public() || function.is_entrypoint())
+            .map(|function| ProcedureCycles {
+                name: function.name,
+                estimate: analysis.estimate(function.name).cloned().unwrap_or_default(),
+            })
+            .collect();
+        Self { procedures }
+    }
+
+    /// Get this report in JSON form, for emitting as [OutputType::CyclesJson]
+    pub fn as_json(&self) -> CycleReportJson<'_> {
+        CycleReportJson(self)
+    }
+}
+impl fmt::Display for CycleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for procedure in self.procedures.iter() {
+            writeln!(f, "{}: {}", procedure_path(&procedure.name), &procedure.estimate)?;
+            if !procedure.estimate.unknown_callees.is_empty() {
+                let callees = procedure
+                    .estimate
+                    .unknown_callees
+                    .iter()
+                    .map(procedure_path)
+                    .collect::<Vec<_>>();
+                writeln!(f, "  excluding calls to: {}", callees.join(", "))?;
+            }
+            if procedure.estimate.dynamic_calls {
+                writeln!(f, "  excluding dynamic calls")?;
+            }
+        }
+        Ok(())
+    }
+}
+impl Emit for CycleReport {
+    fn name(&self) -> Option<Symbol> {
+        None
+    }
+
+    fn output_type(&self, _mode: OutputMode) -> OutputType {
+        OutputType::Cycles
+    }
+
+    fn write_to<W: std::io::Write>(
+        &self,
+        mut writer: W,
+        mode: OutputMode,
+        _session: &Session,
+    ) -> std::io::Result<()> {
+        assert_eq!(mode, OutputMode::Text, "binary mode is not supported for cycle reports");
+        write!(writer, "{self}")
+    }
+}
+
+/// A [CycleReport] in JSON form, as emitted for [OutputType::CyclesJson]
+pub struct CycleReportJson<'a>(&'a CycleReport);
+impl Emit for CycleReportJson<'_> {
+    fn name(&self) -> Option<Symbol> {
+        None
+    }
+
+    fn output_type(&self, _mode: OutputMode) -> OutputType {
+        OutputType::CyclesJson
+    }
+
+    fn write_to<W: std::io::Write>(
+        &self,
+        mut writer: W,
+        mode: OutputMode,
+        _session: &Session,
+    ) -> std::io::Result<()> {
+        assert_eq!(mode, OutputMode::Text, "binary mode is not supported for cycle reports");
+        serde_json::to_writer_pretty(&mut writer, self.0).map_err(std::io::Error::other)?;
+        writeln!(writer)
+    }
+}
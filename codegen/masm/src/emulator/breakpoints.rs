@@ -81,7 +81,7 @@ pub enum WatchMode {
 /// The [BreakpointManager] is responsible for tracking what break-
 /// and watchpoints have been created, activated/deactivated, and for
 /// informing the emulator when a breakpoint was hit.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct BreakpointManager {
     /// True if we should break every cycle
     break_every_cycle: bool,
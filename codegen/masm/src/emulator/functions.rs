@@ -7,6 +7,9 @@ use super::{Addr, ControlEffect, EmulationError, Emulator, InstructionPointer};
 use crate::{BlockId, Function, Op};
 
 /// The type signature for native Rust functions callable from MASM IR
+///
+/// When a native function is invoked from MASM IR, its arguments are on the operand stack, and
+/// the slice of arguments it is given is empty.
 pub type NativeFn = dyn FnMut(&mut Emulator, &[Felt]) -> Result<(), EmulationError>;
 
 /// We allow functions in the emulator to be defined in either MASM IR, or native Rust.
@@ -89,7 +92,7 @@ pub struct RepeatState {
     pub iterations: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ControlStack {
     /// The control frame for the current instruction being executed
     current: ControlFrame,
@@ -368,6 +371,7 @@ pub struct InstructionWithOp {
 /// The activation record contains state about the execution of that function of interest
 /// to the emulator, in particular, the instruction pointer, the frame pointer for locals,
 /// and the function-local control stack
+#[derive(Clone)]
 pub struct Activation {
     function: Arc<Function>,
     fp: Addr,
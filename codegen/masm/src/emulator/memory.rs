@@ -9,6 +9,7 @@ use midenc_hir::Felt;
 
 const EMPTY_WORD: [Felt; 4] = [Felt::ZERO; 4];
 
+#[derive(Clone)]
 pub struct Memory {
     memory: Vec<[Felt; 4]>,
     set_memory_addrs: BTreeSet<usize>,
@@ -32,6 +33,11 @@ impl Memory {
         }
         self.set_memory_addrs = Default::default();
     }
+
+    /// Returns the words which have been written since the last reset, in address order
+    pub fn written(&self) -> impl Iterator<Item = (usize, [Felt; 4])> + '_ {
+        self.set_memory_addrs.iter().map(|addr| (*addr, self.memory[*addr]))
+    }
 }
 
 impl Index<usize> for Memory {
@@ -3,21 +3,33 @@ mod debug;
 mod events;
 mod functions;
 mod memory;
+mod snapshot;
 
-use std::{cell::RefCell, cmp, rc::Rc, sync::Arc};
+use std::{cell::RefCell, cmp, collections::BTreeMap, rc::Rc, sync::Arc};
 
 use memory::Memory;
 use miden_assembly::{ast::ProcedureName, LibraryNamespace};
+use miden_core::{
+    crypto::{
+        hash::{Rpo256, RpoDigest},
+        merkle::{MerkleStore, NodeIndex},
+    },
+    ExtensionOf, QuadExtension, StarkField,
+};
+use miden_processor::{AdviceInputs, AdviceProvider, MemAdviceProvider};
 use midenc_hir::{assert_matches, Felt, FieldElement, FunctionIdent, Ident, OperandStack, Stack};
 use rustc_hash::{FxHashMap, FxHashSet};
 
-use self::functions::{Activation, Stub};
 pub use self::{
     breakpoints::*,
     debug::{CallFrame, DebugInfo, DebugInfoWithStack},
     events::{BreakpointEvent, ControlEffect, EmulatorEvent},
     functions::{Instruction, InstructionWithOp, NativeFn},
 };
+use self::{
+    functions::{Activation, Stub},
+    snapshot::Snapshot,
+};
 use crate::{BlockId, Function, Module, Op, Program};
 
 /// This type represents the various sorts of errors which can occur when
@@ -46,6 +58,13 @@ pub enum EmulationError {
     /// An attempt was made to run the emulator without specifying an entrypoint
     #[error("unable to start the emulator without an entrypoint")]
     NoEntrypoint,
+    /// A procedure invoked with `call` returned with more than 16 elements on the operand stack
+    #[error("unable to return from '{0}': operand stack depth of {1} exceeds the maximum of 16")]
+    InvalidStackDepthOnReturn(FunctionIdent, usize),
+    /// A dynamic invocation referenced a procedure digest which does not correspond to any
+    /// function loaded in the emulator
+    #[error("unable to invoke procedure with digest '{0}': no such procedure is defined")]
+    UndefinedProcedure(RpoDigest),
 }
 
 /// The size/type of pointers in the emulator
@@ -101,6 +120,31 @@ enum Status {
     Faulted(EmulationError),
 }
 
+/// The state of the caller saved when a procedure is invoked with `call` or `syscall`, which
+/// execute the callee in a new context, with only the top 16 elements of the operand stack visible
+/// to it.
+#[derive(Clone)]
+struct Context {
+    /// The depth of the call stack at which the activation record of the callee resides
+    depth: usize,
+    /// The linear memory of the caller, or `None` if the callee shares it with the caller, i.e. a
+    /// `syscall` made from the root context
+    memory: Option<Memory>,
+    /// The elements of the operand stack below the top 16, at the time of the call
+    overflow: Vec<Felt>,
+    /// How the context was entered
+    kind: ContextKind,
+}
+
+#[derive(Copy, Clone)]
+enum ContextKind {
+    /// The callee was invoked with `call`, and executes with its own linear memory. The digest is
+    /// that of the callee, and is what `caller` produces for syscalls made from this context.
+    Call(RpoDigest),
+    /// The callee was invoked with `syscall`, and executes with the memory of the root context
+    Syscall,
+}
+
 /// [Emulator] provides us with a means to execute our MASM IR directly
 /// without having to emit "real" MASM and run it via the Miden VM.
 /// In other words, it's a convenient way to run tests to verify the
@@ -110,20 +154,29 @@ enum Status {
 /// [Emulator] is necessarily a more limited execution environment:
 ///
 /// * It only handles instructions which are defined in the [Op] enum
-/// * Anything related to proving, calling contracts, etc. is not supported
+/// * Anything related to proving is not supported
 /// * The default environment is empty, i.e. there are no Miden VM standard
 ///   library functions available. Users must emit Miden IR for all functions
 ///   they wish to call, or alternatively, provide native stubs.
+/// * Code is never lowered to MAST, so procedures are identified by a digest derived from their
+///   name, rather than their MAST root. These digests are used consistently by `procref`,
+///   `dynexec`, `dyncall` and `caller`, but differ from the ones the VM would use.
 pub struct Emulator {
     status: Status,
     functions: FxHashMap<FunctionIdent, Stub>,
     locals: FxHashMap<FunctionIdent, Addr>,
     modules_loaded: FxHashMap<Ident, Arc<Module>>,
     modules_pending: FxHashSet<Ident>,
+    procedures: BTreeMap<RpoDigest, FunctionIdent>,
     memory: Memory,
     stack: OperandStack<Felt>,
     advice_stack: OperandStack<Felt>,
+    /// The advice map and Merkle store are shared with the snapshots taken while they are
+    /// unchanged, and copied on write
+    advice_map: Arc<BTreeMap<RpoDigest, Vec<Felt>>>,
+    merkle_store: Arc<MerkleStore>,
     callstack: Vec<Activation>,
+    contexts: Vec<Context>,
     hp_start: u32,
     hp: u32,
     lp_start: u32,
@@ -132,6 +185,9 @@ pub struct Emulator {
     step_over: Option<InstructionPointer>,
     clk: usize,
     clk_limit: usize,
+    cycles: usize,
+    snapshots: BTreeMap<usize, Snapshot>,
+    snapshot_interval: usize,
     entrypoint: Option<FunctionIdent>,
     print_trace: bool,
 }
@@ -164,10 +220,14 @@ impl Emulator {
             locals: Default::default(),
             modules_loaded: Default::default(),
             modules_pending: Default::default(),
+            procedures: Default::default(),
             memory,
             stack: Default::default(),
             advice_stack: Default::default(),
+            advice_map: Default::default(),
+            merkle_store: Default::default(),
             callstack: vec![],
+            contexts: vec![],
             hp_start: hp,
             hp,
             lp_start: lp,
@@ -176,6 +236,9 @@ impl Emulator {
             step_over: None,
             clk: 0,
             clk_limit: usize::MAX,
+            cycles: 0,
+            snapshots: Default::default(),
+            snapshot_interval: Self::DEFAULT_SNAPSHOT_INTERVAL,
             entrypoint: None,
             print_trace: print_stack,
         }
@@ -186,6 +249,15 @@ impl Emulator {
         self.clk_limit = max;
     }
 
+    /// Returns the number of cycles the Miden VM would have taken to execute the instructions
+    /// emulated since the emulator was last stopped.
+    ///
+    /// This is the sum of [Op::cost] over all executed instructions, so it is exact for code that
+    /// does not use control flow, aside from any `noop` padding the assembler would insert.
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
     /// Returns all watchpoints that are currently managed by this [BreakpointManager]
     pub fn watchpoints(&self) -> impl Iterator<Item = Watchpoint> + '_ {
         self.breakpoints.watchpoints()
@@ -285,6 +357,29 @@ impl Emulator {
         &mut self.stack
     }
 
+    /// Get mutable access to the advice stack
+    pub fn advice_stack_mut(&mut self) -> &mut OperandStack<Felt> {
+        &mut self.advice_stack
+    }
+
+    /// Get mutable access to the advice map
+    pub fn advice_map_mut(&mut self) -> &mut BTreeMap<RpoDigest, Vec<Felt>> {
+        Arc::make_mut(&mut self.advice_map)
+    }
+
+    /// Get mutable access to the Merkle store used by the `mtree_*` instructions
+    pub fn merkle_store_mut(&mut self) -> &mut MerkleStore {
+        Arc::make_mut(&mut self.merkle_store)
+    }
+
+    /// Read the word at `addr`, a word address, in the linear memory of the current context
+    ///
+    /// The elements are in the order they are stored by the VM, i.e. the reverse of the order in
+    /// which they appear on the operand stack after a `mem_loadw`.
+    pub fn load_word(&self, addr: u32) -> [Felt; 4] {
+        self.memory[addr as usize]
+    }
+
     /// Load `program` into this emulator
     ///
     /// This resets the emulator state, as only one program may be loaded at a time.
@@ -380,6 +475,7 @@ impl Emulator {
         for f in prev.functions() {
             self.functions.remove(&f.name);
             self.locals.remove(&f.name);
+            self.procedures.remove(&procedure_digest(f.name));
         }
 
         // Determine if we need to add `name` to `modules_pending` if there are dependents still
@@ -400,6 +496,7 @@ impl Emulator {
         }
         let fp = self.lp;
         self.lp += function.locals().len() as u32;
+        self.procedures.insert(procedure_digest(id), id);
         self.functions.insert(id, Stub::Asm(function));
         self.locals.insert(id, fp);
 
@@ -425,6 +522,7 @@ impl Emulator {
         if self.functions.contains_key(&id) {
             return Err(EmulationError::DuplicateFunction(id));
         }
+        self.procedures.insert(procedure_digest(id), id);
         self.functions.insert(id, Stub::Native(Rc::new(RefCell::new(function))));
 
         Ok(())
@@ -578,19 +676,24 @@ impl Emulator {
     pub fn stop(&mut self) {
         self.callstack.clear();
         self.stack.clear();
+        if let Some(memory) = self.contexts.drain(..).next().and_then(|context| context.memory) {
+            self.memory = memory;
+        }
         self.memory.reset();
         self.hp = self.hp_start;
         self.lp = self.lp_start;
         self.step_over = None;
         self.clk = 0;
+        self.cycles = 0;
+        self.snapshots.clear();
         self.status = Status::Loaded;
     }
 
     /// Reset the emulator state to its initial state at creation.
     ///
     /// In addition to resetting the cycle counter, operand stack, and linear memory,
-    /// this function also unloads all code, and clears all breakpoints. Only the
-    /// configuration used to initialize the emulator is preserved.
+    /// this function also unloads all code, clears the advice provider, and clears all
+    /// breakpoints. Only the configuration used to initialize the emulator is preserved.
     ///
     /// To use the emulator after calling this function, you must load a program or module again.
     pub fn reset(&mut self) {
@@ -599,6 +702,10 @@ impl Emulator {
         self.locals.clear();
         self.modules_loaded.clear();
         self.modules_pending.clear();
+        self.procedures.clear();
+        self.advice_stack.clear();
+        self.advice_map = Default::default();
+        self.merkle_store = Default::default();
         self.breakpoints.clear();
         self.status = Status::Init;
     }
@@ -1218,10 +1325,9 @@ impl Emulator {
             return Ok(EmulatorEvent::Stopped);
         }
 
-        // Terminate execution early if we reach a predetermined number of cycles
-        self.clk += 1;
-        if self.clk > self.clk_limit {
-            return Err(EmulationError::CycleBudgetExceeded);
+        // Periodically record the state at the start of the cycle, so we can travel back to it
+        if self.clk % self.snapshot_interval == 0 {
+            self.take_snapshot();
         }
 
         let mut state = self.callstack.pop().unwrap();
@@ -1249,11 +1355,21 @@ impl Emulator {
             }
         }
 
+        // Terminate execution early if we reach a predetermined number of cycles. A cycle is only
+        // counted once we know we aren't breaking before it, so that a given cycle count always
+        // identifies the same point in the execution, regardless of what breakpoints are set
+        self.clk += 1;
+        if self.clk > self.clk_limit {
+            self.callstack.push(state);
+            return Err(EmulationError::CycleBudgetExceeded);
+        }
+
         // Advance the instruction pointer, returning the instruction
         // that it previously pointed to, along with what, if any,
         // control flow effect occurred to reach it
         let ix_with_op = state.next();
         if let Some(ix_with_op) = ix_with_op {
+            self.cycles += ix_with_op.op.cost();
             if self.print_trace {
                 eprintln!("mem: {:?}", self.memory);
                 eprintln!("stk: {}", self.stack.debug());
@@ -1271,7 +1387,7 @@ impl Emulator {
                     self.stack.push(b);
                 }
                 Op::Pushw(word) => {
-                    self.stack.pushw(word);
+                    self.push_word(word);
                 }
                 Op::PushU8(i) => {
                     self.stack.push_u8(i);
@@ -1359,7 +1475,7 @@ impl Emulator {
                 Op::AdvLoadw => {
                     let word = adv_popw!(self);
                     self.stack.dropw();
-                    self.stack.pushw(word);
+                    self.push_word(word);
                 }
                 Op::AdvPipe => {
                     // We're overwriting the first two words, C and B, so drop them
@@ -1369,16 +1485,17 @@ impl Emulator {
                     let a = popw!(self);
                     // The memory address to write to is the first element of the fourth word
                     let addr = pop_addr!(self);
+                    assert!(addr + 1 < self.memory.len(), "out of bounds memory access");
                     // We update the original address += 2, and restore A
                     self.stack.push_u32(addr as u32 + 2);
                     self.stack.pushw(a);
                     // We then move words D and E from the advice stack to the operand stack,
                     // while also writing those words to memory starting at `addr`
                     let d = adv_popw!(self);
-                    self.stack.pushw(d);
+                    self.push_word(d);
                     self.memory[addr] = d;
                     let e = adv_popw!(self);
-                    self.stack.pushw(e);
+                    self.push_word(e);
                     self.memory[addr + 1] = e;
                     // Lastly, since we performed a memory write here, suspend like we do for other
                     // memory-modifying ops
@@ -1413,32 +1530,101 @@ impl Emulator {
                     self.stack.push_u32(b_lo as u32);
                     self.stack.push_u32(b_hi as u32);
                 }
-                Op::AdvInjectInsertMem
-                | Op::AdvInjectInsertHperm
-                | Op::AdvInjectInsertHdword
-                | Op::AdvInjectInsertHdwordImm(_)
-                | Op::AdvInjectPushMTreeNode
-                | Op::AdvInjectPushMapVal
-                | Op::AdvInjectPushMapValImm(_)
-                | Op::AdvInjectPushMapValN
-                | Op::AdvInjectPushMapValNImm(_) => unimplemented!(),
+                Op::AdvInjectPushMapVal => self.push_map_value(0, false),
+                Op::AdvInjectPushMapValImm(offset) => self.push_map_value(offset as usize, false),
+                Op::AdvInjectPushMapValN => self.push_map_value(0, true),
+                Op::AdvInjectPushMapValNImm(offset) => self.push_map_value(offset as usize, true),
+                Op::AdvInjectPushMTreeNode => {
+                    let depth = self.stack[0];
+                    let index = self.stack[1];
+                    let root = [self.stack[5], self.stack[4], self.stack[3], self.stack[2]];
+                    let node = self.get_merkle_node(root, depth, index);
+                    for elem in node.into_iter().rev() {
+                        self.advice_stack.push(elem);
+                    }
+                }
+                Op::AdvInjectInsertMem => {
+                    let key = self.peek_word(0);
+                    let start = self.stack[4].as_int();
+                    let end = self.stack[5].as_int();
+                    assert!(
+                        start <= end && end <= self.memory.len() as u64,
+                        "invalid memory range: {start}..{end}"
+                    );
+                    let values = (start..end)
+                        .flat_map(|addr| self.memory[addr as usize])
+                        .collect::<Vec<_>>();
+                    Arc::make_mut(&mut self.advice_map).insert(RpoDigest::new(key), values);
+                }
+                Op::AdvInjectInsertHdword => self.insert_hdword(Felt::ZERO),
+                Op::AdvInjectInsertHdwordImm(domain) => self.insert_hdword(Felt::from(domain)),
+                Op::AdvInjectInsertHperm => {
+                    let mut state = [Felt::ZERO; 12];
+                    for (i, elem) in state.iter_mut().enumerate() {
+                        *elem = self.stack[11 - i];
+                    }
+                    let values = state[Rpo256::RATE_RANGE].to_vec();
+                    Rpo256::apply_permutation(&mut state);
+                    let key = RpoDigest::new(state[Rpo256::DIGEST_RANGE].try_into().unwrap());
+                    Arc::make_mut(&mut self.advice_map).insert(key, values);
+                }
+                Op::AdvInjectPushSignature(kind) => {
+                    let pub_key = self.peek_word(0);
+                    let msg = self.peek_word(1);
+                    let key_pair =
+                        self.advice_map.get(&RpoDigest::new(pub_key)).cloned().unwrap_or_else(
+                            || panic!("no secret key found for public key {pub_key:?}"),
+                        );
+                    let provider = MemAdviceProvider::from(
+                        AdviceInputs::default().with_map([(RpoDigest::new(pub_key), key_pair)]),
+                    );
+                    let signature = provider
+                        .get_signature(kind.into(), pub_key, msg)
+                        .unwrap_or_else(|err| panic!("failed to sign message: {err}"));
+                    for elem in signature {
+                        self.advice_stack.push(elem);
+                    }
+                }
                 Op::Assert => {
                     let cond = pop_bool!(self);
                     assert!(cond, "assertion failed: expected true, got false");
                 }
+                Op::AssertWithError(code) => {
+                    let cond = pop_bool!(self);
+                    assert!(
+                        cond,
+                        "assertion failed with error code {code}: expected true, got false"
+                    );
+                }
                 Op::Assertz => {
                     let cond = pop_bool!(self);
                     assert!(!cond, "assertion failed: expected false, got true");
                 }
+                Op::AssertzWithError(code) => {
+                    let cond = pop_bool!(self);
+                    assert!(
+                        !cond,
+                        "assertion failed with error code {code}: expected false, got true"
+                    );
+                }
                 Op::AssertEq => {
                     let (b, a) = pop2!(self);
                     assert_eq!(a, b, "equality assertion failed");
                 }
+                Op::AssertEqWithError(code) => {
+                    let (b, a) = pop2!(self);
+                    assert_eq!(a, b, "equality assertion failed with error code {code}");
+                }
                 Op::AssertEqw => {
                     let b = popw!(self);
                     let a = popw!(self);
                     assert_eq!(a, b, "equality assertion failed");
                 }
+                Op::AssertEqwWithError(code) => {
+                    let b = popw!(self);
+                    let a = popw!(self);
+                    assert_eq!(a, b, "equality assertion failed with error code {code}");
+                }
                 Op::LocAddr(id) => {
                     let addr = state.fp() + id.as_usize() as u32;
                     debug_assert!(addr < self.memory.len() as u32);
@@ -1449,6 +1635,7 @@ impl Emulator {
                     debug_assert!(addr < self.memory.len());
                     let value = pop!(self);
                     self.memory[addr][0] = value;
+                    self.callstack.push(state);
                     return Ok(EmulatorEvent::MemoryWrite {
                         addr: addr as u32,
                         size: 4,
@@ -1457,9 +1644,11 @@ impl Emulator {
                 Op::LocStorew(id) => {
                     let addr = (state.fp() + id.as_usize() as u32) as usize;
                     assert!(addr < self.memory.len() - 4, "out of bounds memory access");
-                    let word =
+                    let mut word =
                         self.stack.peekw().expect("operand stack does not contain a full word");
+                    word.reverse();
                     self.memory[addr] = word;
+                    self.callstack.push(state);
                     return Ok(EmulatorEvent::MemoryWrite {
                         addr: addr as u32,
                         size: 16,
@@ -1474,7 +1663,7 @@ impl Emulator {
                     let addr = (state.fp() + id.as_usize() as u32) as usize;
                     debug_assert!(addr < self.memory.len());
                     self.stack.dropw();
-                    self.stack.pushw(self.memory[addr]);
+                    self.push_word(self.memory[addr]);
                 }
                 Op::MemLoad => {
                     let addr = pop_addr!(self);
@@ -1546,6 +1735,22 @@ impl Emulator {
                         size: 16,
                     });
                 }
+                Op::MemStream => {
+                    // We're overwriting the first two words, C and B, so drop them
+                    self.stack.dropw();
+                    self.stack.dropw();
+                    // The third word, A, is saved, but unused
+                    let a = popw!(self);
+                    // The memory address to read from is the first element of the fourth word
+                    let addr = pop_addr!(self);
+                    assert!(addr + 1 < self.memory.len(), "out of bounds memory access");
+                    // We update the original address += 2, and restore A
+                    self.stack.push_u32(addr as u32 + 2);
+                    self.stack.pushw(a);
+                    // We then place the words at `addr` and `addr + 1` on the operand stack
+                    self.push_word(self.memory[addr]);
+                    self.push_word(self.memory[addr + 1]);
+                }
                 Op::If(then_blk, else_blk) => {
                     self.step_over = Some(state.ip());
                     let cond = pop_bool!(self);
@@ -1575,46 +1780,76 @@ impl Emulator {
                     return Ok(EmulatorEvent::EnterLoop(body_blk));
                 }
                 Op::Exec(callee) => {
-                    let fun = self
-                        .functions
-                        .get(&callee)
-                        .cloned()
-                        .ok_or(EmulationError::UndefinedFunction(callee))?;
                     self.step_over = Some(state.ip());
-                    match fun {
-                        Stub::Asm(ref function) => {
-                            let fp = self.locals[&function.name];
-                            let callee_state = Activation::new(function.clone(), fp);
-                            // Suspend caller and scheduled callee next
-                            self.callstack.push(state);
-                            self.callstack.push(callee_state);
-                            return Ok(EmulatorEvent::EnterFunction(function.name));
-                        }
-                        Stub::Native(_function) => unimplemented!(),
+                    match self.invoke_from(state, callee, None)? {
+                        Some(caller) => state = caller,
+                        None => return Ok(EmulatorEvent::EnterFunction(callee)),
+                    }
+                }
+                Op::Call(callee) => {
+                    self.step_over = Some(state.ip());
+                    let context = ContextKind::Call(procedure_digest(callee));
+                    match self.invoke_from(state, callee, Some(context))? {
+                        Some(caller) => state = caller,
+                        None => return Ok(EmulatorEvent::EnterFunction(callee)),
+                    }
+                }
+                Op::Syscall(callee) => {
+                    self.step_over = Some(state.ip());
+                    match self.invoke_from(state, callee, Some(ContextKind::Syscall))? {
+                        Some(caller) => state = caller,
+                        None => return Ok(EmulatorEvent::EnterFunction(callee)),
+                    }
+                }
+                Op::DynExec => {
+                    self.step_over = Some(state.ip());
+                    let callee = self.resolve_digest(self.peek_word(0))?;
+                    match self.invoke_from(state, callee, None)? {
+                        Some(caller) => state = caller,
+                        None => return Ok(EmulatorEvent::EnterFunction(callee)),
+                    }
+                }
+                Op::DynCall => {
+                    self.step_over = Some(state.ip());
+                    let digest = self.peek_word(0);
+                    let callee = self.resolve_digest(digest)?;
+                    let context = ContextKind::Call(RpoDigest::new(digest));
+                    match self.invoke_from(state, callee, Some(context))? {
+                        Some(caller) => state = caller,
+                        None => return Ok(EmulatorEvent::EnterFunction(callee)),
                     }
                 }
-                Op::Call(_callee) | Op::Syscall(_callee) => unimplemented!(),
+                Op::ProcRef(callee) => {
+                    self.push_word(procedure_digest(callee).into());
+                }
                 Op::Add => binop!(self, add),
                 Op::AddImm(imm) => binop!(self, add, imm),
                 Op::Sub => binop!(self, sub),
                 Op::SubImm(imm) => binop!(self, sub, imm),
                 Op::Mul => binop!(self, mul),
                 Op::MulImm(imm) => binop!(self, mul, imm),
-                Op::Div => binop!(self, div),
-                Op::DivImm(imm) => binop!(self, div, imm),
+                Op::Div => {
+                    assert_ne!(peek!(self), Felt::ZERO, "division by zero");
+                    binop!(self, div)
+                }
+                Op::DivImm(imm) => {
+                    assert_ne!(imm, Felt::ZERO, "division by zero");
+                    binop!(self, div, imm)
+                }
                 Op::Neg => {
                     let a = self.stack.pop().expect("operand stack is empty");
                     self.stack.push(-a);
                 }
                 Op::Inv => {
                     let a = self.stack.pop().expect("operand stack is empty");
+                    assert_ne!(a, Felt::ZERO, "division by zero");
                     self.stack.push(a.inv());
                 }
                 Op::Incr => binop!(self, add, Felt::ONE),
                 Op::Ilog2 => {
-                    let a = peek!(self).as_int();
+                    let a = pop!(self).as_int();
                     assert!(a > 0, "invalid ilog2 argument: expected {a} to be > 0");
-                    self.advice_stack.push_u32(a.ilog2());
+                    self.stack.push_u32(a.ilog2());
                 }
                 Op::Pow2 => {
                     let a = pop!(self).as_int();
@@ -1627,21 +1862,20 @@ impl Emulator {
                 }
                 Op::Exp => {
                     let (b, a) = pop2!(self);
-                    let b = b.as_int();
-                    assert!(
-                        b < 64,
-                        "invalid power of two: expected {b} to be a value less than 64"
-                    );
-                    self.stack.push(a.exp(b));
+                    self.stack.push(a.exp(b.as_int()));
                 }
-                Op::ExpImm(pow) | Op::ExpBitLength(pow) => {
-                    let pow = pow as u64;
+                Op::ExpImm(pow) => {
                     let a = pop!(self);
+                    self.stack.push(a.exp(pow as u64));
+                }
+                Op::ExpBitLength(bits) => {
+                    let (b, a) = pop2!(self);
+                    let b = b.as_int();
                     assert!(
-                        pow < 64,
-                        "invalid power of two: expected {pow} to be a value less than 64"
+                        bits >= 64 || b < (1 << bits),
+                        "invalid exponent: expected {b} to be a value of at most {bits} bits"
                     );
-                    self.stack.push(a.exp(pow));
+                    self.stack.push(a.exp(b));
                 }
                 Op::Not => {
                     let a = pop_bool!(self);
@@ -1688,11 +1922,11 @@ impl Emulator {
                 Op::LteImm(imm) => comparison!(self, le, imm.as_int()),
                 Op::IsOdd => {
                     let a = pop!(self).as_int();
-                    self.stack.push_u8((a % 2 == 0) as u8);
+                    self.stack.push_u8((a % 2 == 1) as u8);
                 }
                 Op::Eqw => {
-                    let b = popw!(self);
-                    let a = popw!(self);
+                    let b = self.peek_word(0);
+                    let a = self.peek_word(1);
                     self.stack.push_u8((a == b) as u8);
                 }
                 Op::Clk => {
@@ -1701,6 +1935,25 @@ impl Emulator {
                 Op::Sdepth => {
                     self.stack.push(Felt::new(self.stack.len() as u64));
                 }
+                Op::Caller => {
+                    assert!(
+                        self.in_syscall(),
+                        "invalid use of 'caller': only valid when executing a syscall"
+                    );
+                    // The caller is the procedure which created the context the syscall was made
+                    // from, or the empty word when made from the root context
+                    let caller = self
+                        .contexts
+                        .iter()
+                        .rev()
+                        .find_map(|context| match context.kind {
+                            ContextKind::Call(digest) => Some(digest),
+                            ContextKind::Syscall => None,
+                        })
+                        .unwrap_or_default();
+                    self.stack.dropw();
+                    self.push_word(caller.into());
+                }
                 Op::U32Test => {
                     let top = self.stack.peek().expect("operand stack is empty").as_int();
                     self.stack.push_u8((top < U32_P) as u8);
@@ -1714,12 +1967,31 @@ impl Emulator {
                     let top = self.stack.peek().expect("operand stack is empty").as_int();
                     assert!(top < U32_P, "assertion failed: {top} is larger than 2^32");
                 }
+                Op::U32AssertWithError(code) => {
+                    let top = self.stack.peek().expect("operand stack is empty").as_int();
+                    assert!(
+                        top < U32_P,
+                        "assertion failed with error code {code}: {top} is larger than 2^32"
+                    );
+                }
                 Op::U32Assert2 => {
-                    let a = self.stack.peek().expect("operand stack is empty").as_int();
-                    let b = self.stack.peek().expect("operand stack is empty").as_int();
+                    let a = self.stack[0].as_int();
+                    let b = self.stack[1].as_int();
                     assert!(a < U32_P, "assertion failed: {a} is larger than 2^32");
                     assert!(b < U32_P, "assertion failed: {b} is larger than 2^32");
                 }
+                Op::U32Assert2WithError(code) => {
+                    let a = self.stack[0].as_int();
+                    let b = self.stack[1].as_int();
+                    assert!(
+                        a < U32_P,
+                        "assertion failed with error code {code}: {a} is larger than 2^32"
+                    );
+                    assert!(
+                        b < U32_P,
+                        "assertion failed with error code {code}: {b} is larger than 2^32"
+                    );
+                }
                 Op::U32Assertw => {
                     let word = self.stack.peekw().expect("operand stack is empty");
                     for elem in word.into_iter() {
@@ -1729,6 +2001,15 @@ impl Emulator {
                         );
                     }
                 }
+                Op::U32AssertwWithError(code) => {
+                    let word = self.stack.peekw().expect("operand stack is empty");
+                    for elem in word.into_iter() {
+                        assert!(
+                            elem.as_int() < U32_P,
+                            "assertion failed with error code {code}: {elem} is larger than 2^32"
+                        );
+                    }
+                }
                 Op::U32Cast => {
                     let a = pop!(self).as_int();
                     self.stack.push(Felt::new(a % U32_P));
@@ -1744,14 +2025,37 @@ impl Emulator {
                 Op::U32OverflowingAddImm(imm) => binop_overflowing_u32!(self, add, imm),
                 Op::U32WrappingAdd => binop_wrapping_u32!(self, add),
                 Op::U32WrappingAddImm(imm) => binop_wrapping_u32!(self, add, imm),
-                Op::U32OverflowingAdd3 => todo!(),
-                Op::U32WrappingAdd3 => todo!(),
+                Op::U32OverflowingAdd3 => {
+                    let c = pop_u32!(self) as u64;
+                    let b = pop_u32!(self) as u64;
+                    let a = pop_u32!(self) as u64;
+                    let sum = a + b + c;
+                    self.stack.push(Felt::new(sum % U32_P));
+                    self.stack.push(Felt::new(sum / U32_P));
+                }
+                Op::U32WrappingAdd3 => {
+                    let c = pop_u32!(self) as u64;
+                    let b = pop_u32!(self) as u64;
+                    let a = pop_u32!(self) as u64;
+                    self.stack.push(Felt::new((a + b + c) % U32_P));
+                }
                 Op::U32OverflowingSub => binop_overflowing_u32!(self, sub),
                 Op::U32OverflowingSubImm(imm) => binop_overflowing_u32!(self, sub, imm),
                 Op::U32WrappingSub => binop_wrapping_u32!(self, sub),
                 Op::U32WrappingSubImm(imm) => binop_wrapping_u32!(self, sub, imm),
-                Op::U32OverflowingMul => binop_overflowing_u32!(self, mul),
-                Op::U32OverflowingMulImm(imm) => binop_overflowing_u32!(self, mul, imm),
+                Op::U32OverflowingMul => {
+                    let b = pop_u32!(self) as u64;
+                    let a = pop_u32!(self) as u64;
+                    let result = a * b;
+                    self.stack.push(Felt::new(result % U32_P));
+                    self.stack.push(Felt::new(result / U32_P));
+                }
+                Op::U32OverflowingMulImm(b) => {
+                    let a = pop_u32!(self) as u64;
+                    let result = a * b as u64;
+                    self.stack.push(Felt::new(result % U32_P));
+                    self.stack.push(Felt::new(result / U32_P));
+                }
                 Op::U32WrappingMul => binop_wrapping_u32!(self, mul),
                 Op::U32WrappingMulImm(imm) => binop_wrapping_u32!(self, mul, imm),
                 Op::U32OverflowingMadd => {
@@ -1844,19 +2148,31 @@ impl Emulator {
                     self.stack.push_u32(a.trailing_ones());
                 }
                 Op::U32Gt => comparison!(self, gt),
+                Op::U32GtImm(imm) => comparison!(self, gt, imm as u64),
                 Op::U32Gte => comparison!(self, ge),
+                Op::U32GteImm(imm) => comparison!(self, ge, imm as u64),
                 Op::U32Lt => comparison!(self, lt),
+                Op::U32LtImm(imm) => comparison!(self, lt, imm as u64),
                 Op::U32Lte => comparison!(self, le),
+                Op::U32LteImm(imm) => comparison!(self, le, imm as u64),
                 Op::U32Min => {
                     let b = pop!(self).as_int();
                     let a = pop!(self).as_int();
                     self.stack.push(Felt::new(cmp::min(a, b)));
                 }
+                Op::U32MinImm(b) => {
+                    let a = pop!(self).as_int();
+                    self.stack.push(Felt::new(cmp::min(a, b as u64)));
+                }
                 Op::U32Max => {
                     let b = pop!(self).as_int();
                     let a = pop!(self).as_int();
                     self.stack.push(Felt::new(cmp::max(a, b)));
                 }
+                Op::U32MaxImm(b) => {
+                    let a = pop!(self).as_int();
+                    self.stack.push(Felt::new(cmp::max(a, b as u64)));
+                }
                 Op::Breakpoint => {
                     self.callstack.push(state);
                     return Ok(EmulatorEvent::Breakpoint(BreakpointEvent::Step));
@@ -1873,8 +2189,94 @@ impl Emulator {
                 | Op::DebugFrame
                 | Op::DebugFrameAt(_)
                 | Op::DebugFrameRange(..) => (),
-                Op::Emit(_) | Op::Trace(_) => (),
-                op => unimplemented!("missing opcode implementation for {op:?}"),
+                Op::Hash => {
+                    let a = self.pop_word();
+                    let mut state = [Felt::ZERO; 12];
+                    state[0] = Felt::ONE;
+                    state[8] = Felt::ONE;
+                    state[Rpo256::RATE_RANGE][..4].copy_from_slice(&a);
+                    Rpo256::apply_permutation(&mut state);
+                    self.push_word(state[Rpo256::DIGEST_RANGE].try_into().unwrap());
+                }
+                Op::Hmerge => {
+                    let b = self.pop_word();
+                    let a = self.pop_word();
+                    let digest = Rpo256::merge(&[a.into(), b.into()]);
+                    self.push_word(digest.into());
+                }
+                Op::Hperm => {
+                    let mut state = [Felt::ZERO; 12];
+                    for elem in state.iter_mut().rev() {
+                        *elem = pop!(self);
+                    }
+                    Rpo256::apply_permutation(&mut state);
+                    for elem in state.into_iter() {
+                        self.stack.push(elem);
+                    }
+                }
+                Op::MtreeGet => {
+                    let depth = pop!(self);
+                    let index = pop!(self);
+                    let root = self.peek_word(0);
+                    let node = self.get_merkle_node(root, depth, index);
+                    self.push_word(node);
+                }
+                Op::MtreeSet => {
+                    let depth = pop!(self);
+                    let index = pop!(self);
+                    let root = self.pop_word();
+                    let value = self.pop_word();
+                    let old_value = self.get_merkle_node(root, depth, index);
+                    let index = self.merkle_node_index(depth, index);
+                    let path = Arc::make_mut(&mut self.merkle_store)
+                        .set_node(root.into(), index, value.into())
+                        .unwrap_or_else(|err| panic!("failed to update merkle tree: {err}"));
+                    self.push_word(path.root.into());
+                    self.push_word(old_value);
+                }
+                Op::MtreeMerge => {
+                    let right = self.pop_word();
+                    let left = self.pop_word();
+                    let root = Arc::make_mut(&mut self.merkle_store)
+                        .merge_roots(left.into(), right.into())
+                        .unwrap_or_else(|err| panic!("failed to merge merkle roots: {err}"));
+                    self.push_word(root.into());
+                }
+                Op::MtreeVerify => self.verify_merkle_node(0),
+                Op::MtreeVerifyWithError(code) => self.verify_merkle_node(code),
+                Op::FriExt2Fold4 => self.fri_ext2fold4(),
+                Op::RCombBase => self.rcomb_base(),
+                Op::Ext2add => {
+                    let b = self.pop_ext2();
+                    let a = self.pop_ext2();
+                    self.push_ext2(a + b);
+                }
+                Op::Ext2sub => {
+                    let b = self.pop_ext2();
+                    let a = self.pop_ext2();
+                    self.push_ext2(a - b);
+                }
+                Op::Ext2mul => {
+                    let b = self.pop_ext2();
+                    let a = self.pop_ext2();
+                    self.push_ext2(a * b);
+                }
+                Op::Ext2neg => {
+                    let a = self.pop_ext2();
+                    self.push_ext2(-a);
+                }
+                Op::Ext2inv => {
+                    let a = self.pop_ext2();
+                    assert_ne!(a, QuadFelt::ZERO, "division by zero");
+                    self.push_ext2(a.inv());
+                }
+                Op::Ext2div => {
+                    let b = self.pop_ext2();
+                    let a = self.pop_ext2();
+                    assert_ne!(b, QuadFelt::ZERO, "division by zero");
+                    self.push_ext2(a / b);
+                }
+                Op::Emit(_) | Op::Trace(_) | Op::Nop => (),
             }
 
             match ix_with_op.effect {
@@ -1898,8 +2300,374 @@ impl Emulator {
 
             Ok(EmulatorEvent::Suspended)
         } else {
-            // No more code left in the current function
+            // No more code left in the current function, if it was invoked with `call`, we must
+            // restore the context of the caller before returning to it
+            if self
+                .contexts
+                .last()
+                .is_some_and(|context| context.depth == self.callstack.len())
+            {
+                self.exit_context(current_function)?;
+            }
             Ok(EmulatorEvent::ExitFunction(current_function))
         }
     }
 }
+
+/// A quadratic extension field element, as operated on by the `ext2*` instructions
+type QuadFelt = QuadExtension<Felt>;
+
+/// Returns the digest used to identify the procedure `id` in the emulator
+///
+/// This is derived from the fully-qualified name of the procedure, rather than its MAST root, as
+/// the emulator does not compile the code it executes. It is only meaningful within the emulator.
+fn procedure_digest(id: FunctionIdent) -> RpoDigest {
+    Rpo256::hash(format!("{}::{}", id.module.as_str(), id.function.as_str()).as_bytes())
+}
+
+impl Emulator {
+    /// Returns true if the emulator is currently executing a syscall
+    fn in_syscall(&self) -> bool {
+        self.contexts.iter().any(|context| matches!(context.kind, ContextKind::Syscall))
+    }
+
+    /// Invoke `callee` from the activation record `caller`, optionally in a new execution context.
+    ///
+    /// If `callee` is implemented in MASM, the caller is suspended, the callee is scheduled, and
+    /// `None` is returned. If `callee` is a native function, it is run to completion immediately,
+    /// and the caller is handed back so that execution can continue from where it left off.
+    fn invoke_from(
+        &mut self,
+        caller: Activation,
+        callee: FunctionIdent,
+        context: Option<ContextKind>,
+    ) -> Result<Option<Activation>, EmulationError> {
+        let fun = self
+            .functions
+            .get(&callee)
+            .cloned()
+            .ok_or(EmulationError::UndefinedFunction(callee))?;
+        match fun {
+            Stub::Asm(ref function) => {
+                let fp = self.locals[&function.name];
+                let callee_state = Activation::new(function.clone(), fp);
+                // Suspend caller and schedule callee next
+                self.callstack.push(caller);
+                if let Some(kind) = context {
+                    self.enter_context(kind);
+                }
+                self.callstack.push(callee_state);
+                Ok(None)
+            }
+            Stub::Native(function) => {
+                // Native functions have no activation record, so they are treated as if they
+                // are called from the caller's frame
+                self.callstack.push(caller);
+                if let Some(kind) = context {
+                    self.enter_context(kind);
+                }
+                let result = {
+                    let mut function = function.borrow_mut();
+                    function(self, &[])
+                };
+                result?;
+                if context.is_some() {
+                    self.exit_context(callee)?;
+                }
+                Ok(self.callstack.pop())
+            }
+        }
+    }
+
+    /// Switch to a new execution context of the given kind, on behalf of the most recently
+    /// suspended activation record.
+    ///
+    /// Only the top 16 elements of the operand stack are visible to the new context, and it is
+    /// given fresh memory, unless it is a syscall, in which case it operates on the memory of the
+    /// root context.
+    fn enter_context(&mut self, kind: ContextKind) {
+        let memory = match kind {
+            ContextKind::Call(_) => {
+                assert!(
+                    !self.in_syscall(),
+                    "invalid call: cannot create a new context while executing a syscall"
+                );
+                let memory_size = self.memory.len();
+                Some(core::mem::replace(&mut self.memory, Memory::new(memory_size)))
+            }
+            ContextKind::Syscall => {
+                assert!(!self.in_syscall(), "invalid syscall: cannot nest syscalls");
+                // If we're not in the root context, the root context memory was saved when the
+                // first context was entered, so we swap it in for the duration of the syscall
+                self.contexts
+                    .first_mut()
+                    .and_then(|context| context.memory.take())
+                    .map(|root| core::mem::replace(&mut self.memory, root))
+            }
+        };
+        let depth = self.stack.len();
+        let overflow = self.stack.stack_mut().drain(..depth.saturating_sub(16)).collect();
+        self.contexts.push(Context {
+            depth: self.callstack.len(),
+            memory,
+            overflow,
+            kind,
+        });
+    }
+
+    /// Return from the innermost execution context to that of the caller, restoring the state
+    /// of the operand stack and memory that the caller had before entering it.
+    fn exit_context(&mut self, callee: FunctionIdent) -> Result<(), EmulationError> {
+        let context = self.contexts.pop().expect("expected an execution context to exit");
+        let depth = self.stack.len();
+        if depth > 16 {
+            return Err(EmulationError::InvalidStackDepthOnReturn(callee, depth));
+        }
+        let stack = self.stack.stack_mut();
+        stack.splice(
+            0..0,
+            context
+                .overflow
+                .into_iter()
+                .chain(core::iter::repeat(Felt::ZERO).take(16 - depth)),
+        );
+        if let Some(memory) = context.memory {
+            let memory = core::mem::replace(&mut self.memory, memory);
+            // The memory we just swapped out belongs to the root context if this was a syscall
+            if let ContextKind::Syscall = context.kind {
+                self.contexts[0].memory = Some(memory);
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the procedure with the given digest, as pushed on the stack by `procref`
+    fn resolve_digest(&self, digest: [Felt; 4]) -> Result<FunctionIdent, EmulationError> {
+        let digest = RpoDigest::new(digest);
+        self.procedures
+            .get(&digest)
+            .copied()
+            .ok_or(EmulationError::UndefinedProcedure(digest))
+    }
+
+    /// Pop a word from the operand stack, in the order it is represented by the VM
+    fn pop_word(&mut self) -> [Felt; 4] {
+        let mut word = self.stack.popw().expect("operand stack does not contain a full word");
+        word.reverse();
+        word
+    }
+
+    /// Push a word on the operand stack, given in the order it is represented by the VM
+    fn push_word(&mut self, mut word: [Felt; 4]) {
+        word.reverse();
+        self.stack.pushw(word);
+    }
+
+    /// Get the `index`th word from the top of the operand stack, without removing it
+    fn peek_word(&self, index: usize) -> [Felt; 4] {
+        let offset = index * 4;
+        assert!(self.stack.len() >= offset + 4, "operand stack does not contain a full word");
+        [
+            self.stack[offset + 3],
+            self.stack[offset + 2],
+            self.stack[offset + 1],
+            self.stack[offset],
+        ]
+    }
+
+    /// Pop a quadratic extension field element from the operand stack
+    fn pop_ext2(&mut self) -> QuadFelt {
+        let a1 = self.stack.pop().expect("operand stack is empty");
+        let a0 = self.stack.pop().expect("operand stack is empty");
+        QuadFelt::new(a0, a1)
+    }
+
+    /// Push a quadratic extension field element on the operand stack
+    fn push_ext2(&mut self, value: QuadFelt) {
+        let [a0, a1] = value.to_base_elements();
+        self.stack.push(a0);
+        self.stack.push(a1);
+    }
+
+    /// Push the values associated in the advice map with the word at `offset` on the operand
+    /// stack, on to the advice stack, optionally preceded by the number of values.
+    fn push_map_value(&mut self, offset: usize, include_len: bool) {
+        assert!(offset <= 12, "invalid advice map key offset: {offset}");
+        let key = RpoDigest::new([
+            self.stack[offset + 3],
+            self.stack[offset + 2],
+            self.stack[offset + 1],
+            self.stack[offset],
+        ]);
+        let values = self
+            .advice_map
+            .get(&key)
+            .unwrap_or_else(|| panic!("no value found in advice map for key {key}"));
+        for value in values.iter().rev() {
+            self.advice_stack.push(*value);
+        }
+        if include_len {
+            self.advice_stack.push(Felt::new(values.len() as u64));
+        }
+    }
+
+    /// Insert the hash of the top two words of the operand stack in the given domain into the
+    /// advice map, mapped to the concatenation of those words.
+    fn insert_hdword(&mut self, domain: Felt) {
+        let word0 = self.peek_word(0);
+        let word1 = self.peek_word(1);
+        let key = Rpo256::merge_in_domain(&[word1.into(), word0.into()], domain);
+        let values = word1.into_iter().chain(word0).collect();
+        Arc::make_mut(&mut self.advice_map).insert(key, values);
+    }
+
+    fn merkle_node_index(&self, depth: Felt, index: Felt) -> NodeIndex {
+        let depth = u8::try_from(depth.as_int())
+            .unwrap_or_else(|_| panic!("invalid merkle tree depth: {depth}"));
+        NodeIndex::new(depth, index.as_int())
+            .unwrap_or_else(|err| panic!("invalid merkle tree node index: {err}"))
+    }
+
+    /// Get the node at `index` and `depth` in the merkle tree with root `root`
+    fn get_merkle_node(&self, root: [Felt; 4], depth: Felt, index: Felt) -> [Felt; 4] {
+        let index = self.merkle_node_index(depth, index);
+        self.merkle_store
+            .get_node(root.into(), index)
+            .unwrap_or_else(|err| panic!("failed to get merkle tree node: {err}"))
+            .into()
+    }
+
+    /// Verify that the merkle tree whose root is on the operand stack contains the node on top
+    /// of the stack, at the depth and index given by the elements in between.
+    fn verify_merkle_node(&self, code: u32) {
+        let value = self.peek_word(0);
+        let depth = self.stack[4];
+        let index = self.stack[5];
+        let root = [self.stack[9], self.stack[8], self.stack[7], self.stack[6]];
+        let node = self.get_merkle_node(root, depth, index);
+        assert_eq!(
+            node, value,
+            "merkle path verification failed with error code {code}: value not found at index \
+             {index} of tree at depth {depth}"
+        );
+    }
+
+    /// Perform a single layer of FRI folding by a factor of four, for the `fri_ext2fold4`
+    /// instruction.
+    fn fri_ext2fold4(&mut self) {
+        const TWO_INV: Felt = Felt::new(9223372034707292161);
+        const TAU_INV: Felt = Felt::new(18446462594437873665);
+        const TAU2_INV: Felt = Felt::new(18446744069414584320);
+        const TAU3_INV: Felt = Felt::new(281474976710656);
+
+        let fold2 =
+            |f: QuadFelt, g: QuadFelt, ep: QuadFelt| (f + g + (f - g) * ep).mul_base(TWO_INV);
+
+        let s = |n: usize| self.stack[n];
+        let query_values = [
+            QuadFelt::new(s(7), s(6)),
+            QuadFelt::new(s(5), s(4)),
+            QuadFelt::new(s(3), s(2)),
+            QuadFelt::new(s(1), s(0)),
+        ];
+        let folded_pos = s(8);
+        let domain_segment = s(9).as_int();
+        let poe = s(10);
+        let prev_value = QuadFelt::new(s(12), s(11));
+        let alpha = QuadFelt::new(s(14), s(13));
+        let layer_ptr = s(15);
+
+        assert!(domain_segment <= 3, "invalid fri domain segment: {domain_segment}");
+        assert_eq!(
+            prev_value, query_values[domain_segment as usize],
+            "fri folding failed: previous value does not match the query value"
+        );
+
+        let f_tau = [Felt::ONE, TAU_INV, TAU2_INV, TAU3_INV][domain_segment as usize];
+        let x = poe * f_tau * Felt::GENERATOR;
+        let ev = alpha.mul_base(x.inv());
+        let es = ev.square();
+        let tmp0 = fold2(query_values[0], query_values[2], ev);
+        let tmp1 = fold2(query_values[1], query_values[3], ev.mul_base(TAU_INV));
+        let folded = fold2(tmp0, tmp1, es);
+        let mut flags = [Felt::ZERO; 4];
+        flags[domain_segment as usize] = Felt::ONE;
+        let poe2 = poe.square();
+
+        let [tmp0_0, tmp0_1] = tmp0.to_base_elements();
+        let [tmp1_0, tmp1_1] = tmp1.to_base_elements();
+        let [folded_0, folded_1] = folded.to_base_elements();
+        // Listed in order from the top of the stack down
+        let outputs = [
+            tmp0_1,
+            tmp0_0,
+            tmp1_1,
+            tmp1_0,
+            flags[3],
+            flags[2],
+            flags[1],
+            flags[0],
+            poe2,
+            f_tau,
+            layer_ptr + Felt::new(2),
+            poe2.square(),
+            folded_pos,
+            folded_1,
+            folded_0,
+        ];
+        for _ in 0..16 {
+            self.stack.drop();
+        }
+        for elem in outputs.into_iter().rev() {
+            self.stack.push(elem);
+        }
+    }
+
+    /// Compute a single term of the random linear combination used in DEEP queries, for the
+    /// `rcomb_base` instruction.
+    fn rcomb_base(&mut self) {
+        let s = |n: usize| self.stack[n];
+        let alpha_ptr = s(14).as_int() as usize;
+        let z_ptr = s(13).as_int() as usize;
+        assert!(
+            alpha_ptr < self.memory.len() && z_ptr < self.memory.len(),
+            "out of bounds memory access"
+        );
+        let alpha_word = self.memory[alpha_ptr];
+        let z_word = self.memory[z_ptr];
+        let alpha = QuadFelt::new(alpha_word[0], alpha_word[1]);
+        let tz = QuadFelt::new(z_word[0], z_word[1]);
+        let tgz = QuadFelt::new(z_word[2], z_word[3]);
+        let p = QuadFelt::new(s(9), s(8));
+        let r = QuadFelt::new(s(11), s(10));
+        let tx = QuadFelt::new(s(7), Felt::ZERO);
+
+        let [p0, p1] = (p + alpha * (tx - tz)).to_base_elements();
+        let [r0, r1] = (r + alpha * (tx - tgz)).to_base_elements();
+        // Listed in order from the top of the stack down
+        let outputs = [
+            s(7),
+            s(0),
+            s(1),
+            s(2),
+            s(3),
+            s(4),
+            s(5),
+            s(6),
+            p1,
+            p0,
+            r1,
+            r0,
+            s(12),
+            s(13) + Felt::ONE,
+            s(14) + Felt::ONE,
+            s(15),
+        ];
+        for _ in 0..16 {
+            self.stack.drop();
+        }
+        for elem in outputs.into_iter().rev() {
+            self.stack.push(elem);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use miden_core::crypto::{hash::RpoDigest, merkle::MerkleStore};
+use midenc_hir::{assert_matches, Felt, OperandStack};
+
+use super::{
+    functions::Activation, BreakpointEvent, BreakpointManager, Context, EmulationError, Emulator,
+    EmulatorEvent, Instruction, InstructionPointer, Status,
+};
+
+/// A copy of the execution state of the [Emulator] at the start of a given cycle.
+///
+/// Snapshots are taken periodically while the emulator runs, and are used to travel back in time
+/// by restoring the nearest snapshot preceding the desired cycle, and replaying forward from there.
+///
+/// The advice map and Merkle store may be large, and rarely change, so rather than being copied,
+/// they are shared with the emulator, and any other snapshots taken while they are unchanged.
+#[derive(Clone)]
+pub(super) struct Snapshot {
+    stack: OperandStack<Felt>,
+    /// The words written to the linear memory of the current context
+    memory: Vec<(usize, [Felt; 4])>,
+    callstack: Vec<Activation>,
+    contexts: Vec<Context>,
+    advice_stack: OperandStack<Felt>,
+    advice_map: Arc<BTreeMap<RpoDigest, Vec<Felt>>>,
+    merkle_store: Arc<MerkleStore>,
+    hp: u32,
+    lp: u32,
+    step_over: Option<InstructionPointer>,
+    clk: usize,
+    cycles: usize,
+}
+
+impl Emulator {
+    /// The default number of cycles between snapshots of the emulator state
+    pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 1000;
+
+    /// Set the number of cycles between snapshots of the emulator state.
+    ///
+    /// Smaller intervals make [Emulator::goto_cycle] and friends faster, at the expense of memory.
+    pub fn set_snapshot_interval(&mut self, interval: usize) {
+        assert_ne!(interval, 0, "the snapshot interval must be at least one cycle");
+        self.snapshot_interval = interval;
+    }
+
+    /// Get the current cycle count, i.e. the number of cycles executed so far
+    pub fn clk(&self) -> usize {
+        self.clk
+    }
+
+    /// Move the emulator to the state it was in after `cycle` cycles were executed.
+    ///
+    /// This works in both directions: if `cycle` is in the past, the nearest snapshot preceding it
+    /// is restored, and execution is replayed from there. Breakpoints and watchpoints are ignored
+    /// while replaying. If the program stops, or faults, before `cycle` is reached, the emulator
+    /// is left in that state instead.
+    ///
+    /// Replay assumes execution is deterministic, so changes made to the emulator state by the
+    /// caller while suspended, e.g. via [Emulator::stack_mut], are not preserved when travelling
+    /// back past them.
+    pub fn goto_cycle(&mut self, cycle: usize) -> Result<EmulatorEvent, EmulationError> {
+        assert_matches!(
+            self.status,
+            Status::Suspended | Status::Stopped | Status::Faulted(_),
+            "cannot travel in time unless the emulator has started executing"
+        );
+
+        let replay_from_current = cycle >= self.clk && matches!(self.status, Status::Suspended);
+        if !replay_from_current {
+            let start = self.snapshot_at_or_before(cycle);
+            self.restore_snapshot(start);
+        }
+
+        let breakpoints = core::mem::take(&mut self.breakpoints);
+        let result = loop {
+            if self.clk >= cycle {
+                break Ok(EmulatorEvent::Suspended);
+            }
+            match self.run_once() {
+                Ok(EmulatorEvent::Stopped) => break Ok(EmulatorEvent::Stopped),
+                Ok(_) => continue,
+                Err(err) => break Err(err),
+            }
+        };
+        self.breakpoints = breakpoints;
+
+        match result {
+            Ok(EmulatorEvent::Stopped) => {
+                self.status = Status::Stopped;
+                Ok(EmulatorEvent::Stopped)
+            }
+            Ok(event) => {
+                self.status = Status::Suspended;
+                Ok(event)
+            }
+            Err(err) => {
+                self.status = Status::Faulted(err.clone());
+                Err(err)
+            }
+        }
+    }
+
+    /// Step the emulator backward one cycle, undoing the effects of the last instruction executed.
+    ///
+    /// If the emulator faulted, this moves it to the state just before the faulting instruction.
+    pub fn step_back(&mut self) -> Result<EmulatorEvent, EmulationError> {
+        self.goto_cycle(self.clk.saturating_sub(1))
+    }
+
+    /// Run the emulator backward until the most recent point at which a breakpoint or watchpoint
+    /// would have suspended it when running forward.
+    ///
+    /// Returns the [BreakpointEvent] that was hit, or `Suspended` if no breakpoint was found, in
+    /// which case the emulator is left at the start of execution.
+    pub fn reverse_continue(&mut self) -> Result<EmulatorEvent, EmulationError> {
+        assert_matches!(
+            self.status,
+            Status::Suspended | Status::Stopped | Status::Faulted(_),
+            "cannot travel in time unless the emulator has started executing"
+        );
+
+        // Search the history one snapshot interval at a time, starting with the most recent
+        let target = self.clk;
+        let starts = self.snapshots.range(..target).map(|(clk, _)| *clk).collect::<Vec<_>>();
+        let breakpoints = core::mem::take(&mut self.breakpoints);
+        let mut hit = None;
+        for (i, start) in starts.iter().copied().enumerate().rev() {
+            let end = starts.get(i + 1).copied().unwrap_or(target);
+            self.restore_snapshot(start);
+            hit = self.find_last_breakpoint(breakpoints.clone(), end, target);
+            if hit.is_some() {
+                break;
+            }
+        }
+        self.breakpoints = breakpoints;
+
+        match hit {
+            Some((cycle, bp)) => {
+                self.goto_cycle(cycle)?;
+                Ok(EmulatorEvent::Breakpoint(bp))
+            }
+            None => self.goto_cycle(starts.first().copied().unwrap_or(0)),
+        }
+    }
+
+    /// Take a snapshot of the current state, replacing any previous snapshot for this cycle
+    pub(super) fn take_snapshot(&mut self) {
+        let snapshot = Snapshot {
+            stack: self.stack.clone(),
+            memory: self.memory.written().collect(),
+            callstack: self.callstack.clone(),
+            contexts: self.contexts.clone(),
+            advice_stack: self.advice_stack.clone(),
+            advice_map: Arc::clone(&self.advice_map),
+            merkle_store: Arc::clone(&self.merkle_store),
+            hp: self.hp,
+            lp: self.lp,
+            step_over: self.step_over,
+            clk: self.clk,
+            cycles: self.cycles,
+        };
+        self.snapshots.insert(self.clk, snapshot);
+    }
+
+    /// Find the cycle of the most recent snapshot which is not after `cycle`
+    fn snapshot_at_or_before(&self, cycle: usize) -> usize {
+        self.snapshots
+            .range(..=cycle)
+            .next_back()
+            .map(|(clk, _)| *clk)
+            .expect("no snapshot precedes the requested cycle")
+    }
+
+    /// Restore the snapshot taken at the start of cycle `clk`
+    fn restore_snapshot(&mut self, clk: usize) {
+        let snapshot = self.snapshots[&clk].clone();
+        self.stack = snapshot.stack;
+        self.memory.reset();
+        for (addr, word) in snapshot.memory {
+            self.memory[addr] = word;
+        }
+        self.callstack = snapshot.callstack;
+        self.contexts = snapshot.contexts;
+        self.advice_stack = snapshot.advice_stack;
+        self.advice_map = snapshot.advice_map;
+        self.merkle_store = snapshot.merkle_store;
+        self.hp = snapshot.hp;
+        self.lp = snapshot.lp;
+        self.step_over = snapshot.step_over;
+        self.clk = snapshot.clk;
+        self.cycles = snapshot.cycles;
+    }
+
+    /// Replay execution from the current state until cycle `end`, returning the last point prior
+    /// to cycle `limit` at which one of `breakpoints` would have suspended the emulator.
+    ///
+    /// Breakpoints which are checked at the start of a cycle are attributed to the cycle they
+    /// precede, while those triggered by the effects of an instruction, e.g. watchpoints, are
+    /// attributed to the cycle following that instruction.
+    fn find_last_breakpoint(
+        &mut self,
+        mut breakpoints: BreakpointManager,
+        end: usize,
+        limit: usize,
+    ) -> Option<(usize, BreakpointEvent)> {
+        let mut hit = None;
+        while self.clk < end {
+            let clk = self.clk;
+            let ip = self.current_ip();
+            if let Some(bp) = breakpoints.handle_event(EmulatorEvent::CycleStart(clk), ip) {
+                hit = Some((clk, bp));
+            }
+            if let Some(Instruction { ip, .. }) = ip {
+                if breakpoints.should_break_at(ip.block, ip.index) {
+                    hit = Some((clk, BreakpointEvent::Reached(ip)));
+                }
+            }
+            match self.run_once() {
+                Ok(EmulatorEvent::Stopped) | Err(_) => break,
+                Ok(event) if self.clk < limit => {
+                    if let Some(bp) = breakpoints.handle_event(event, self.current_ip()) {
+                        hit = Some((self.clk, bp));
+                    }
+                }
+                Ok(_) => (),
+            }
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midenc_hir::{FunctionIdent, Signature, SourceSpan};
+
+    use super::*;
+    use crate::{Function, Module, Op};
+
+    #[test]
+    fn snapshots_share_advice_state() {
+        let span = SourceSpan::default();
+        let main: FunctionIdent = "test::main".parse().unwrap();
+        let mut module = Box::new(Module::new(
+            miden_assembly::LibraryPath::new("test").unwrap(),
+            miden_assembly::ast::ModuleKind::Library,
+        ));
+        let mut function = Function::new(main, Signature::new(vec![], vec![]));
+        let word = |n: u64| Op::Pushw([n, n + 1, n + 2, n + 3].map(Felt::new));
+        for op in [word(1), word(5), Op::AdvInjectInsertHdword, Op::Dropw, Op::Dropw, word(9)] {
+            function.body_mut().push(op, span);
+        }
+        module.push_back(Box::new(function));
+
+        let mut emulator = Emulator::default();
+        emulator.set_snapshot_interval(1);
+        emulator
+            .advice_map_mut()
+            .extend((0..1000u64).map(|n| (RpoDigest::new([Felt::new(n); 4]), vec![Felt::new(n)])));
+        emulator.load_module(module.freeze()).expect("failed to load module");
+        emulator.invoke(main, &[]).expect("execution failed");
+        let end = emulator.clk();
+
+        // Every snapshot taken before the advice map was modified shares the original, and every
+        // one taken after shares the modified map with the emulator
+        let (before, after) = emulator
+            .snapshots
+            .values()
+            .partition::<Vec<_>, _>(|snapshot| snapshot.advice_map.len() == 1000);
+        assert!(!before.is_empty() && !after.is_empty());
+        assert!(before.iter().all(|s| Arc::ptr_eq(&s.advice_map, &before[0].advice_map)));
+        assert!(after.iter().all(|s| Arc::ptr_eq(&s.advice_map, &emulator.advice_map)));
+        let merkle_store = &emulator.merkle_store;
+        assert!(emulator.snapshots.values().all(|s| Arc::ptr_eq(&s.merkle_store, merkle_store)));
+
+        // Modifying the advice map does not affect the snapshots, so travelling back restores it
+        emulator.advice_map_mut().clear();
+        assert_eq!(emulator.snapshots[&0].advice_map.len(), 1000);
+        emulator.goto_cycle(0).expect("reverse execution failed");
+        assert_eq!(emulator.advice_map.len(), 1000);
+        emulator.goto_cycle(end).expect("execution failed");
+        assert_eq!(emulator.advice_map.len(), 1001);
+    }
+}
@@ -7,18 +7,25 @@ extern crate alloc;
 mod codegen;
 mod compiler;
 mod convert;
+mod cycles;
 mod emulator;
 mod masm;
+mod mast;
 mod packaging;
 #[cfg(test)]
 mod tests;
 
 pub use self::{
+    codegen::{Outliner, Peephole, PeepholeStats},
     compiler::{
         default_function_rewrites, default_rewrites, CompilerResult, MasmArtifact, MasmCompiler,
         MastArtifact,
     },
     convert::ConvertHirToMasm,
+    cycles::{
+        CycleAnalysis, CycleEstimate, CycleReport, CycleReportJson, LoopCycles, ProcedureCycles,
+        SymbolicCycles,
+    },
     emulator::{
         Breakpoint, BreakpointEvent, CallFrame, DebugInfo, DebugInfoWithStack, EmulationError,
         Emulator, EmulatorEvent, InstructionPointer, WatchMode, Watchpoint, WatchpointId,
@@ -7,7 +7,7 @@ use miden_assembly::{
     LibraryNamespace, LibraryPath,
 };
 use midenc_hir::{
-    diagnostics::{SourceSpan, Span, Spanned},
+    diagnostics::{Report, SourceSpan, Span, Spanned, WrapErr},
     formatter::PrettyPrint,
     AttributeSet, FunctionIdent, Ident, Signature, Type,
 };
@@ -186,7 +186,26 @@ impl Function {
         }
     }
 
-    pub fn from_ast(module: Ident, proc: &ast::Procedure) -> Box<Self> {
+    /// Convert a [miden_assembly::ast::Procedure] to a [Function] of `module`
+    ///
+    /// Miden Assembly has no syntax for type signatures, so the signature of the function is
+    /// taken from an `@signature` annotation in the documentation of the procedure, if present:
+    ///
+    /// ```masm
+    /// #! Adds `b` to `a`
+    /// #!
+    /// #! @signature (func (param i32) (param i32) (result i32))
+    /// export.add
+    ///     ...
+    /// end
+    /// ```
+    ///
+    /// Note that a documentation comment at the start of a module documents the module itself, not
+    /// the procedure which follows it.
+    ///
+    /// Otherwise the function is given an empty signature. Returns `Err` if the annotation is not
+    /// a valid signature.
+    pub fn from_ast(module: Ident, proc: &ast::Procedure) -> Result<Box<Self>, Report> {
         use midenc_hir::{Linkage, Symbol};
 
         let proc_span = proc.name().span();
@@ -196,7 +215,10 @@ impl Function {
             function: Ident::new(proc_name, proc_span),
         };
 
-        let mut signature = Signature::new(vec![], vec![]);
+        let docs = proc.docs().map(|docs| docs.inner().as_str());
+        let mut signature = parse_signature_annotation(docs)
+            .wrap_err_with(|| format!("invalid signature annotation for '{}'", id.display()))?
+            .unwrap_or_else(|| Signature::new(vec![], vec![]));
         let visibility = proc.visibility();
         if !visibility.is_exported() {
             signature.linkage = Linkage::Internal;
@@ -214,7 +236,7 @@ impl Function {
         function.invoked.extend(proc.invoked().cloned());
         function.body = Region::from_block(module, proc.body());
 
-        function
+        Ok(function)
     }
 
     pub fn to_ast(
@@ -251,6 +273,19 @@ impl Function {
     }
 }
 
+/// The prefix of a line in the documentation of a procedure which declares its type signature
+const SIGNATURE_ANNOTATION: &str = "@signature";
+
+/// Get the text of the `@signature` annotation in `docs`, if present
+pub(super) fn signature_annotation(docs: Option<&str>) -> Option<&str> {
+    docs?.lines().find_map(|line| line.trim().strip_prefix(SIGNATURE_ANNOTATION))
+}
+
+/// Parse the signature declared by the `@signature` annotation in `docs`, if present
+pub(super) fn parse_signature_annotation(docs: Option<&str>) -> Result<Option<Signature>, Report> {
+    signature_annotation(docs).map(|signature| signature.trim().parse()).transpose()
+}
+
 fn emit_trace_frame_events(span: SourceSpan, body: &mut ast::Block) {
     use midenc_hir::{TRACE_FRAME_END, TRACE_FRAME_START};
 
@@ -395,6 +430,13 @@ impl Functions {
         }
     }
 
+    pub fn pop_front(&mut self) -> Option<Box<Function>> {
+        match self {
+            Self::Open(ref mut list) => list.pop_front(),
+            Self::Frozen(_) => panic!("cannot remove function from frozen module"),
+        }
+    }
+
     pub fn freeze(&mut self) {
         if let Self::Open(ref mut functions) = self {
             let mut frozen = FrozenFunctionList::default();
@@ -9,24 +9,20 @@ const I64_INTRINSICS: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/intrinsics/i64.masm"));
 const MEM_INTRINSICS: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/intrinsics/mem.masm"));
+const F64_INTRINSICS: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/intrinsics/f64.masm"));
 
-/// This is a mapping of intrinsics module name to the raw MASM source for that module
-const INTRINSICS: [(&str, &str, &str); 3] = [
-    (
-        "intrinsics::i32",
-        I32_INTRINSICS,
-        concat!(env!("CARGO_MANIFEST_DIR"), "/intrinsics/i32.masm"),
-    ),
-    (
-        "intrinsics::i64",
-        I64_INTRINSICS,
-        concat!(env!("CARGO_MANIFEST_DIR"), "/intrinsics/i64.masm"),
-    ),
-    (
-        "intrinsics::mem",
-        MEM_INTRINSICS,
-        concat!(env!("CARGO_MANIFEST_DIR"), "/intrinsics/mem.masm"),
-    ),
+/// This is a mapping of intrinsics module name to the raw MASM source for that module, and the
+/// path by which that source is known.
+///
+/// The path is relative to this crate, rather than the absolute path at which the compiler was
+/// built, as it is recorded in the debug info of compiled artifacts, which must not depend on
+/// where the compiler was built.
+const INTRINSICS: [(&str, &str, &str); 4] = [
+    ("intrinsics::i32", I32_INTRINSICS, "intrinsics/i32.masm"),
+    ("intrinsics::i64", I64_INTRINSICS, "intrinsics/i64.masm"),
+    ("intrinsics::mem", MEM_INTRINSICS, "intrinsics/mem.masm"),
+    ("intrinsics::f64", F64_INTRINSICS, "intrinsics/f64.masm"),
 ];
 
 /// This helper loads the named module from the set of intrinsics modules defined in this crate.
@@ -1,4 +1,9 @@
-use std::{collections::BTreeSet, fmt, path::Path, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+    sync::Arc,
+};
 
 use intrusive_collections::{intrusive_adapter, RBTree, RBTreeAtomicLink};
 use miden_assembly::{
@@ -6,12 +11,15 @@ use miden_assembly::{
     LibraryPath,
 };
 use midenc_hir::{
-    diagnostics::{Report, SourceFile, SourceSpan, Span, Spanned},
+    diagnostics::{Report, SourceFile, SourceSpan, Span, Spanned, WrapErr},
     formatter::PrettyPrint,
-    FunctionIdent, Ident, Symbol,
+    FunctionIdent, Ident, Signature, Symbol,
 };
 
-use super::{function::Functions, FrozenFunctionList, Function, ModuleImportInfo};
+use super::{
+    function::{parse_signature_annotation, signature_annotation, Functions},
+    FrozenFunctionList, Function, ModuleImportInfo,
+};
 
 /// This represents a single compiled Miden Assembly module in a form that is
 /// designed to integrate well with the rest of our IR. You can think of this
@@ -39,6 +47,9 @@ pub struct Module {
     functions: Functions,
     /// The set of re-exported functions declared in this module
     reexports: Vec<ast::ProcedureAlias>,
+    /// The type signatures of the procedures exported from this module, as seen by callers, where
+    /// they are known
+    export_signatures: BTreeMap<Ident, Signature>,
 }
 impl Module {
     /// Create a new, empty [Module] with the given name and kind.
@@ -54,6 +65,7 @@ impl Module {
             imports: Default::default(),
             functions: Default::default(),
             reexports: Default::default(),
+            export_signatures: Default::default(),
         }
     }
 
@@ -66,7 +78,7 @@ impl Module {
         let span = source.source_span();
         let mut parser = ast::Module::parser(kind);
         let ast = parser.parse(path, source)?;
-        Ok(Self::from_ast(&ast, span))
+        Self::from_ast(&ast, span)
     }
 
     /// Returns true if this module is a kernel module
@@ -100,7 +112,11 @@ impl Module {
         self.functions.iter().any(|f| f.name.function == name)
     }
 
-    pub fn from_ast(ast: &ast::Module, span: SourceSpan) -> Self {
+    /// Convert a [miden_assembly::ast::Module] to a [Module]
+    ///
+    /// The type signatures of exported procedures are taken from their `@signature` annotations,
+    /// see [Function::from_ast]. Returns `Err` if any such annotation is invalid.
+    pub fn from_ast(ast: &ast::Module, span: SourceSpan) -> Result<Self, Report> {
         let mut module = Self::new(ast.path().clone(), ast.kind());
         module.span = span;
         module.docs = ast.docs().map(|s| s.to_string());
@@ -120,16 +136,31 @@ impl Module {
         for export in ast.procedures() {
             match export {
                 ast::Export::Alias(ref alias) => {
+                    let docs = alias.docs().map(|docs| docs.inner().as_str());
+                    let name =
+                        Ident::new(Symbol::intern(alias.name().as_str()), alias.name().span());
+                    let signature = parse_signature_annotation(docs).wrap_err_with(|| {
+                        format!("invalid signature annotation for '{}::{name}'", &module.id)
+                    })?;
+                    if let Some(signature) = signature {
+                        module.export_signatures.insert(name, signature);
+                    }
                     module.reexports.push(alias.clone());
                 }
                 ast::Export::Procedure(ref proc) => {
-                    let function = Function::from_ast(module.id, proc);
+                    let function = Function::from_ast(module.id, proc)?;
+                    let docs = proc.docs().map(|docs| docs.inner().as_str());
+                    if function.signature.is_public() && signature_annotation(docs).is_some() {
+                        module
+                            .export_signatures
+                            .insert(function.name.function, function.signature.clone());
+                    }
                     module.functions.push_back(function);
                 }
             }
         }
 
-        module
+        Ok(module)
     }
 
     /// Freezes this program, preventing further modifications
@@ -143,6 +174,21 @@ impl Module {
         self.functions.iter()
     }
 
+    /// Get the type signature of the procedure `name` exported from this module, if known
+    pub fn export_signature(&self, name: Ident) -> Option<&Signature> {
+        self.export_signatures.get(&name)
+    }
+
+    /// Set the type signature of the procedure `name` exported from this module
+    pub fn set_export_signature(&mut self, name: Ident, signature: Signature) {
+        self.export_signatures.insert(name, signature);
+    }
+
+    /// Get the set of procedures re-exported from this module
+    pub fn reexports(&self) -> &[ast::ProcedureAlias] {
+        self.reexports.as_slice()
+    }
+
     /// Access the frozen functions list of this module, and panic if not frozen
     pub fn unwrap_frozen_functions(&self) -> &FrozenFunctionList {
         match self.functions {
@@ -158,6 +204,13 @@ impl Module {
         self.functions.push_back(function);
     }
 
+    /// Remove the first function in this module, if there is one
+    ///
+    /// NOTE: This function will panic if the module has been frozen
+    pub fn pop_front(&mut self) -> Option<Box<Function>> {
+        self.functions.pop_front()
+    }
+
     /// Convert this module into its [miden_assembly::ast::Module] representation.
     pub fn to_ast(&self, tracing_enabled: bool) -> Result<ast::Module, Report> {
         let mut ast = ast::Module::new(self.kind, self.name.clone()).with_span(self.span);
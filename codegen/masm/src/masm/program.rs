@@ -1,13 +1,13 @@
-use std::{fmt, path::Path, sync::Arc};
+use std::{collections::BTreeMap, fmt, path::Path, sync::Arc};
 
 use hir::{Signature, Symbol};
 use miden_assembly::{
     ast::{ModuleKind, ProcedureName},
     KernelLibrary, Library as CompiledLibrary, LibraryNamespace,
 };
-use miden_core::crypto::hash::Rpo256;
+use miden_core::crypto::hash::RpoDigest;
 use midenc_hir::{
-    self as hir, diagnostics::Report, DataSegmentTable, Felt, FieldElement, FunctionIdent,
+    self as hir, diagnostics::Report, DataSegmentTable, ErrorCodeTable, FunctionIdent,
     GlobalVariableTable, Ident, SourceSpan,
 };
 use midenc_hir_analysis::GlobalVariableAnalysis;
@@ -24,6 +24,17 @@ inventory::submit! {
         .help_heading("Testing")
 }
 
+inventory::submit! {
+    midenc_session::CompileFlag::new("assemble_from_ast")
+        .long("assemble-from-ast")
+        .action(midenc_session::FlagAction::SetTrue)
+        .help(
+            "If present, MASM is assembled by converting it to Miden Assembly syntax and running \
+             it through the assembler, rather than lowering it directly to MAST"
+        )
+        .help_heading("Codegen")
+}
+
 /// A [Program] represents a complete set of modules which are intended to be shipped and executed
 /// together.
 #[derive(Clone)]
@@ -32,12 +43,6 @@ pub struct Program {
     library: Library,
     /// The function identifier for the program entrypoint, if applicable
     entrypoint: FunctionIdent,
-    /// The base address of the dynamic heap, as computed by the codegen backend
-    ///
-    /// Defaults to an offset which is two 64k pages from the start of linear memory,
-    /// or, if available, the next byte following the both the reserved linear memory region as
-    /// declared in HIR, and the global variables of the program.
-    heap_base: u32,
 }
 impl Program {
     /// Create a new [Program] initialized from an [hir::Program].
@@ -59,17 +64,9 @@ impl Program {
         };
         let library = Library::from_hir(program, globals);
 
-        // Compute the first page boundary after the end of the globals table to use as the start
-        // of the dynamic heap when the program is executed
-        let heap_base = program.reserved_memory_bytes()
-            + u32::try_from(
-                program.globals().size_in_bytes().next_multiple_of(program.page_size() as usize),
-            )
-            .expect("unable to allocate dynamic heap: global table too large");
         Ok(Self {
             library,
             entrypoint,
-            heap_base,
         })
     }
 
@@ -78,6 +75,16 @@ impl Program {
         self.library.rodata.as_slice()
     }
 
+    /// Get the table of messages associated with the assertion error codes of this program
+    pub fn error_codes(&self) -> &ErrorCodeTable {
+        &self.library.error_codes
+    }
+
+    /// Get the account-component metadata declared in the source of this program, as TOML
+    pub fn account_component(&self) -> Option<&str> {
+        self.library.account_component()
+    }
+
     /// Link this [Program] against the given kernel during assembly
     pub fn link_kernel(&mut self, kernel: KernelLibrary) {
         self.library.link_kernel(kernel);
@@ -106,14 +113,8 @@ impl Program {
         let mut start = Box::new(Function::new(start_id, start_sig));
         {
             let body = start.body_mut();
-            // Initialize dynamic heap
-            body.push(Op::PushU32(self.heap_base), SourceSpan::default());
-            body.push(
-                Op::Exec("intrinsics::mem::heap_init".parse().unwrap()),
-                SourceSpan::default(),
-            );
-            // Initialize data segments from advice stack
-            self.emit_data_segment_initialization(body);
+            // Initialize dynamic heap and data segments
+            self.library.emit_context_initialization(body);
             // Possibly initialize test harness
             if emit_test_harness {
                 self.emit_test_harness(body);
@@ -137,38 +138,6 @@ impl Program {
         block.push(Op::Drop, span);
     }
 
-    /// Emit the sequence of instructions necessary to consume rodata from the advice stack and
-    /// populate the global heap with the data segments of this program, verifying that the
-    /// commitments match.
-    fn emit_data_segment_initialization(&self, block: &mut Block) {
-        // Emit data segment initialization code
-        //
-        // NOTE: This depends on the program being executed with the data for all data
-        // segments having been placed in the advice map with the same commitment and
-        // encoding used here. The program will fail to execute if this is not set up
-        // correctly.
-        //
-        // TODO(pauls): To facilitate automation of this, we should emit an inputs file to
-        // disk that maps each segment to a commitment and its data encoded as binary. This
-        // can then be loaded into the advice provider during VM init.
-        let pipe_preimage_to_memory = "std::mem::pipe_preimage_to_memory".parse().unwrap();
-        for rodata in self.library.rodata.iter() {
-            let span = SourceSpan::default();
-
-            // Move rodata from advice map to advice stack
-            block.push(Op::Pushw(rodata.digest.into()), span); // COM
-            block.push(Op::AdvInjectPushMapVal, span);
-            // write_ptr
-            block.push(Op::PushU32(rodata.start.waddr), span);
-            // num_words
-            block.push(Op::PushU32(rodata.size_in_words() as u32), span);
-            // [num_words, write_ptr, COM, ..] -> [write_ptr']
-            block.push(Op::Exec(pipe_preimage_to_memory), span);
-            // drop write_ptr'
-            block.push(Op::Drop, span);
-        }
-    }
-
     #[inline(always)]
     pub fn entrypoint(&self) -> FunctionIdent {
         self.entrypoint
@@ -238,8 +207,40 @@ impl Program {
         Ok(())
     }
 
-    // Assemble this program to MAST
+    /// Assemble this program to MAST
+    ///
+    /// Unless `--assemble-from-ast` was given, the program is lowered directly to MAST, rather
+    /// than via the Miden Assembly assembler, see [Self::assemble_from_ast].
     pub fn assemble(&self, session: &Session) -> Result<Arc<miden_core::Program>, Report> {
+        if session.get_flag("assemble_from_ast") {
+            return self.assemble_from_ast(session);
+        }
+
+        log::debug!(
+            "lowering executable with entrypoint '{}' to MAST (debug_mode={})",
+            self.entrypoint,
+            session.options.emit_debug_decorators()
+        );
+        let emit_test_harness = session.get_flag("test_harness");
+        let main = self.generate_main(self.entrypoint, emit_test_harness);
+        let main_function = main.functions().next().unwrap();
+
+        let mut lowering = crate::mast::MastLowering::new(
+            session,
+            &self.library.libraries,
+            self.library.kernel.as_ref(),
+        );
+        for module in self.library.modules.iter() {
+            lowering.add_module(module);
+        }
+        lowering.add_module(&main);
+        let entrypoint = lowering.lower_function(&main, main_function)?;
+        lowering.into_program(entrypoint).map(Arc::new)
+    }
+
+    /// Assemble this program to MAST by converting it to Miden Assembly syntax, and assembling
+    /// the result with the Miden Assembly assembler.
+    pub fn assemble_from_ast(&self, session: &Session) -> Result<Arc<miden_core::Program>, Report> {
         use miden_assembly::{Assembler, CompileOptions};
 
         let debug_mode = session.options.emit_debug_decorators();
@@ -250,7 +251,7 @@ impl Program {
             debug_mode
         );
         let mut assembler =
-            Assembler::new(session.source_manager.clone()).with_debug_mode(debug_mode);
+            Assembler::new(session.artifact_source_manager()).with_debug_mode(debug_mode);
 
         // Link extra libraries
         for library in self.library.libraries.iter() {
@@ -335,8 +336,17 @@ pub struct Library {
     kernel: Option<KernelLibrary>,
     /// The rodata segments of this program keyed by the offset of the segment
     rodata: Vec<Rodata>,
+    /// The messages associated with the assertion error codes used in this library
+    error_codes: ErrorCodeTable,
+    /// The account-component metadata declared in the source of this library, as TOML
+    account_component: Option<String>,
     /// The address of the `__stack_pointer` global, if such a global has been defined
     stack_pointer: Option<u32>,
+    /// The base address of the dynamic heap, as computed by the codegen backend
+    ///
+    /// This is the next page boundary following both the reserved linear memory region as
+    /// declared in HIR, and the global variables of the program.
+    heap_base: u32,
 }
 impl Library {
     /// Create a new, empty [Library]
@@ -367,12 +377,63 @@ impl Library {
             program.globals(),
             program.segments(),
         );
+
+        // Compute the first page boundary after the end of the globals table to use as the start
+        // of the dynamic heap when the program is executed
+        let heap_base = program.heap_base();
         Self {
             modules: Modules::default(),
             libraries: vec![],
             kernel: None,
             rodata,
+            error_codes: program.error_codes().clone(),
+            account_component: program.account_component().map(String::from),
             stack_pointer,
+            heap_base,
+        }
+    }
+
+    /// Emit the sequence of instructions necessary to initialize a fresh memory context for the
+    /// code in this library, i.e. the dynamic heap, and the data segments.
+    ///
+    /// This is done on startup of a program, and on entry to any function invoked with `call`.
+    pub(crate) fn emit_context_initialization(&self, block: &mut Block) {
+        // Initialize dynamic heap
+        block.push(Op::PushU32(self.heap_base), SourceSpan::default());
+        block.push(Op::Exec("intrinsics::mem::heap_init".parse().unwrap()), SourceSpan::default());
+        // Initialize data segments from advice stack
+        self.emit_data_segment_initialization(block);
+    }
+
+    /// Emit the sequence of instructions necessary to consume rodata from the advice stack and
+    /// populate the global heap with the data segments of this library, verifying that the
+    /// commitments match.
+    fn emit_data_segment_initialization(&self, block: &mut Block) {
+        // Emit data segment initialization code
+        //
+        // NOTE: This depends on the program being executed with the data for all data
+        // segments having been placed in the advice map with the same commitment and
+        // encoding used here. The program will fail to execute if this is not set up
+        // correctly.
+        //
+        // TODO(pauls): To facilitate automation of this, we should emit an inputs file to
+        // disk that maps each segment to a commitment and its data encoded as binary. This
+        // can then be loaded into the advice provider during VM init.
+        let pipe_preimage_to_memory = "std::mem::pipe_preimage_to_memory".parse().unwrap();
+        for rodata in self.rodata.iter() {
+            let span = SourceSpan::default();
+
+            // Move rodata from advice map to advice stack
+            block.push(Op::Pushw(rodata.digest.into()), span); // COM
+            block.push(Op::AdvInjectPushMapVal, span);
+            // write_ptr
+            block.push(Op::PushU32(rodata.start.waddr), span);
+            // num_words
+            block.push(Op::PushU32(rodata.size_in_words() as u32), span);
+            // [num_words, write_ptr, COM, ..] -> [write_ptr']
+            block.push(Op::Exec(pipe_preimage_to_memory), span);
+            // drop write_ptr'
+            block.push(Op::Drop, span);
         }
     }
 
@@ -380,6 +441,16 @@ impl Library {
         self.rodata.as_slice()
     }
 
+    /// Get the table of messages associated with the assertion error codes of this library
+    pub fn error_codes(&self) -> &ErrorCodeTable {
+        &self.error_codes
+    }
+
+    /// Get the account-component metadata declared in the source of this library, as TOML
+    pub fn account_component(&self) -> Option<&str> {
+        self.account_component.as_deref()
+    }
+
     /// Link this [Library] against the given kernel during assembly
     pub fn link_kernel(&mut self, kernel: KernelLibrary) {
         self.kernel = Some(kernel);
@@ -456,8 +527,73 @@ impl Library {
         Ok(())
     }
 
-    // Assemble this library to MAST
+    /// Assemble this library to MAST
+    ///
+    /// Unless `--assemble-from-ast` was given, the library is lowered directly to MAST, rather
+    /// than via the Miden Assembly assembler, see [Self::assemble_from_ast].
+    ///
+    /// The resulting library exports all public and kernel procedures of its modules.
     pub fn assemble(&self, session: &Session) -> Result<Arc<CompiledLibrary>, Report> {
+        if session.get_flag("assemble_from_ast") {
+            return self.assemble_from_ast(session);
+        }
+
+        log::debug!(
+            "lowering library of {} modules to MAST (debug_mode={})",
+            self.modules().count(),
+            session.options.emit_debug_decorators()
+        );
+        let mut lowering =
+            crate::mast::MastLowering::new(session, &self.libraries, self.kernel.as_ref());
+        for module in self.modules.iter() {
+            lowering.add_module(module);
+        }
+
+        let mut exports = std::collections::BTreeMap::default();
+        for module in self.modules.iter() {
+            for alias in module.reexports() {
+                let digest = lowering.lower_alias(module, alias)?;
+                exports.insert(crate::mast::export_name(module, alias.name()), digest);
+            }
+            for function in module.functions() {
+                if !(function.signature.is_public() || function.signature.is_kernel()) {
+                    continue;
+                }
+                let digest = lowering.lower_function(module, function)?;
+                exports.insert(crate::mast::export_name(module, function.name.function), digest);
+            }
+        }
+
+        Ok(Arc::new(lowering.into_library(exports)))
+    }
+
+    /// Compute the MAST root of every function in this library, as it is lowered to MAST
+    ///
+    /// Unlike [Self::assemble], this includes functions which are neither exported, nor reachable
+    /// from an export.
+    pub fn procedure_digests(
+        &self,
+        session: &Session,
+    ) -> Result<BTreeMap<FunctionIdent, RpoDigest>, Report> {
+        let mut lowering =
+            crate::mast::MastLowering::new(session, &self.libraries, self.kernel.as_ref());
+        for module in self.modules.iter() {
+            lowering.add_module(module);
+        }
+
+        let mut digests = BTreeMap::default();
+        for module in self.modules.iter() {
+            for function in module.functions() {
+                digests.insert(function.name, lowering.lower_function(module, function)?);
+            }
+        }
+
+        Ok(digests)
+    }
+
+    /// Assemble this library to MAST by converting it to Miden Assembly syntax, and assembling
+    /// the result with the Miden Assembly assembler.
+    pub fn assemble_from_ast(&self, session: &Session) -> Result<Arc<CompiledLibrary>, Report> {
         use miden_assembly::Assembler;
 
         let debug_mode = session.options.emit_debug_decorators();
@@ -468,7 +604,7 @@ impl Library {
         );
 
         let mut assembler =
-            Assembler::new(session.source_manager.clone()).with_debug_mode(debug_mode);
+            Assembler::new(session.artifact_source_manager()).with_debug_mode(debug_mode);
 
         // Link extra libraries
         for library in self.libraries.iter() {
@@ -541,13 +677,16 @@ impl Emit for Library {
 ///
 /// This consists of the data itself, as well as a content digest, which will be used to place
 /// that data in the advice map when the program starts.
+///
+/// Segments which are adjacent in memory, i.e. where one segment begins no later than the start of
+/// the word following the end of the previous segment, are coalesced into a single segment before
+/// the digests are computed, so that they are initialized with a single commitment. Segments with
+/// identical contents have the same digest, and so share a single entry in the advice map.
 fn compute_rodata(
     global_table_offset: u32,
     globals: &GlobalVariableTable,
     segments: &DataSegmentTable,
 ) -> Vec<Rodata> {
-    let mut rodatas = Vec::with_capacity(segments.iter().count() + 1);
-
     // Convert global variable initializers to a data segment, and place it at the computed
     // global table offset in linear memory.
     let extra = if !globals.is_empty() {
@@ -566,7 +705,7 @@ fn compute_rodata(
         }
         // Don't bother emitting anything for zeroed segments
         if data.iter().any(|&b| b != 0) {
-            Some((size as u32, offset, Arc::new(midenc_hir::ConstantData::from(data))))
+            Some((offset, Arc::new(midenc_hir::ConstantData::from(data))))
         } else {
             None
         }
@@ -574,68 +713,67 @@ fn compute_rodata(
         None
     };
 
-    // Process all segments, ignoring zeroed segments (as Miden's memory is always zeroed)
-    for (size, offset, segment_data) in segments
+    // Gather all segments, ignoring zeroed segments (as Miden's memory is always zeroed), in
+    // order of their offset in linear memory
+    let mut segments = segments
         .iter()
-        .filter_map(|segment| {
-            if segment.is_zeroed() {
-                None
-            } else {
-                Some((segment.size(), segment.offset(), segment.init()))
-            }
-        })
+        .filter(|segment| !segment.is_zeroed())
+        .map(|segment| (segment.offset(), segment.init()))
         .chain(extra)
-    {
-        let base = NativePtr::from_ptr(offset);
-
-        // TODO(pauls): Do we ever have a need for data segments which are not aligned
-        // to an word boundary? If so, we need to implement that
-        // support when emitting the entry for a program
-        assert_eq!(
-            base.offset,
-            0,
-            "unsupported data segment alignment {}: must be aligned to a 32 byte boundary",
-            base.alignment()
-        );
-        assert_eq!(
-            base.index,
-            0,
-            "unsupported data segment alignment {}: must be aligned to a 32 byte boundary",
-            base.alignment()
-        );
-
-        // Compute the commitment for the data
-        let num_elements = (size.next_multiple_of(4) / 4) as usize;
-        let num_words = num_elements.next_multiple_of(4) / 4;
-        let padding = (num_words * 4).abs_diff(num_elements);
-        let mut elements = Vec::with_capacity(num_elements + padding);
-        // TODO(pauls): If the word containing the first element overlaps with the
-        // previous segment, then ensure the overlapping elements
-        // are mixed together, so that the data is preserved, and
-        // the commitment is correct
-        let mut iter = segment_data.as_slice().iter().copied().array_chunks::<4>();
-        elements.extend(iter.by_ref().map(|bytes| Felt::new(u32::from_le_bytes(bytes) as u64)));
-        if let Some(remainder) = iter.into_remainder() {
-            let mut chunk = [0u8; 4];
-            for (i, byte) in remainder.into_iter().enumerate() {
-                chunk[i] = byte;
+        .collect::<Vec<_>>();
+    segments.sort_by_key(|(offset, _)| *offset);
+
+    // Coalesce adjacent segments, filling any gap between them with zeroes
+    let mut coalesced = Vec::<(u32, Arc<midenc_hir::ConstantData>)>::with_capacity(segments.len());
+    for (offset, segment_data) in segments {
+        if let Some((prev_offset, prev_data)) = coalesced.last_mut() {
+            let prev_end = *prev_offset + prev_data.len() as u32;
+            if (prev_end..=prev_end.next_multiple_of(16)).contains(&offset) {
+                let len = (offset - *prev_offset) as usize + segment_data.len();
+                let mut data = Vec::with_capacity(len);
+                data.extend_from_slice(prev_data.as_slice());
+                data.resize((offset - *prev_offset) as usize, 0);
+                data.extend_from_slice(segment_data.as_slice());
+                *prev_data = Arc::new(midenc_hir::ConstantData::from(data));
+                continue;
             }
-            elements.push(Felt::new(u32::from_le_bytes(chunk) as u64));
         }
-        elements.resize(num_elements + padding, Felt::ZERO);
-        let digest = Rpo256::hash_elements(&elements);
+        coalesced.push((offset, segment_data));
+    }
+
+    coalesced
+        .into_iter()
+        .map(|(offset, segment_data)| {
+            let base = NativePtr::from_ptr(offset);
+
+            // TODO(pauls): Do we ever have a need for data segments which are not aligned
+            // to an word boundary? If so, we need to implement that
+            // support when emitting the entry for a program
+            assert_eq!(
+                base.offset,
+                0,
+                "unsupported data segment alignment {}: must be aligned to a 32 byte boundary",
+                base.alignment()
+            );
+            assert_eq!(
+                base.index,
+                0,
+                "unsupported data segment alignment {}: must be aligned to a 32 byte boundary",
+                base.alignment()
+            );
 
-        log::debug!(
-            "computed commitment for data segment at offset {offset} ({size} bytes, \
-             {num_elements} elements): '{digest}'"
-        );
+            // Compute the commitment for the data
+            let rodata = Rodata::new(base, segment_data);
 
-        rodatas.push(Rodata {
-            digest,
-            start: base,
-            data: segment_data,
-        });
-    }
+            log::debug!(
+                "computed commitment for data segment at offset {offset} ({} bytes, {} elements): \
+                 '{}'",
+                rodata.size_in_bytes(),
+                rodata.size_in_felts(),
+                rodata.digest
+            );
 
-    rodatas
+            rodata
+        })
+        .collect()
 }
@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use miden_core::{
+    crypto::hash::RpoDigest,
+    mast::{MastForest, MastForestError, MastNode, MastNodeId},
+    DecoratorList, Operation,
+};
+use midenc_hir::diagnostics::Report;
+
+/// Basic blocks which are procedure roots are only merged into their parent if they consist of
+/// fewer than this many operation batches. This mirrors the heuristic used by the assembler.
+pub(crate) const PROCEDURE_INLINING_THRESHOLD: usize = 32;
+
+/// Constructs a [MastForest], deduplicating nodes by digest as they are added.
+///
+/// The node construction rules (merging of contiguous basic blocks, balanced joins, removal of
+/// merged nodes) follow those of the Miden Assembly assembler exactly, so that a procedure lowered
+/// via this builder has the same MAST root as it would have if assembled from text.
+#[derive(Default)]
+pub(super) struct MastForestBuilder {
+    forest: MastForest,
+    node_id_by_hash: BTreeMap<RpoDigest, MastNodeId>,
+    merged_node_ids: BTreeSet<MastNodeId>,
+}
+impl MastForestBuilder {
+    /// Finish building the forest, removing any merged nodes which are no longer referenced.
+    ///
+    /// Returns the forest, and the remapping of node ids applied to it, if nodes were removed.
+    pub fn build(self) -> (MastForest, Option<BTreeMap<MastNodeId, MastNodeId>>) {
+        let Self {
+            mut forest,
+            merged_node_ids,
+            ..
+        } = self;
+
+        let mut nodes_to_remove = merged_node_ids
+            .into_iter()
+            .filter(|id| !forest.is_procedure_root(*id))
+            .collect::<BTreeSet<_>>();
+        for node in forest.nodes() {
+            match node {
+                MastNode::Join(node) => {
+                    nodes_to_remove.remove(&node.first());
+                    nodes_to_remove.remove(&node.second());
+                }
+                MastNode::Split(node) => {
+                    nodes_to_remove.remove(&node.on_true());
+                    nodes_to_remove.remove(&node.on_false());
+                }
+                MastNode::Loop(node) => {
+                    nodes_to_remove.remove(&node.body());
+                }
+                MastNode::Call(node) => {
+                    nodes_to_remove.remove(&node.callee());
+                }
+                MastNode::Block(_) | MastNode::Dyn | MastNode::External(_) => (),
+            }
+        }
+
+        let remapping = forest.remove_nodes(&nodes_to_remove);
+        (forest, remapping)
+    }
+
+    #[inline]
+    pub fn digest(&self, id: MastNodeId) -> RpoDigest {
+        self.forest[id].digest()
+    }
+
+    #[inline]
+    pub fn find_procedure_root(&self, digest: RpoDigest) -> Option<MastNodeId> {
+        self.forest.find_procedure_root(digest)
+    }
+
+    #[inline]
+    pub fn make_root(&mut self, id: MastNodeId) {
+        self.forest.make_root(id)
+    }
+
+    /// Returns true if the procedure rooted at `id` is merged into the basic blocks adjacent to
+    /// each of its call sites, rather than being invoked, see [PROCEDURE_INLINING_THRESHOLD].
+    pub fn is_inlined_procedure(&self, id: MastNodeId) -> bool {
+        self.forest[id]
+            .get_basic_block()
+            .is_some_and(|block| block.num_op_batches() < PROCEDURE_INLINING_THRESHOLD)
+    }
+
+    /// Combine `node_ids` into a single node, as if they were executed in sequence
+    pub fn join_nodes(&mut self, node_ids: Vec<MastNodeId>) -> Result<MastNodeId, Report> {
+        assert!(!node_ids.is_empty(), "cannot combine empty MAST node id list");
+
+        let mut node_ids = self.merge_contiguous_basic_blocks(node_ids)?;
+        while node_ids.len() > 1 {
+            let odd = if node_ids.len() % 2 == 0 {
+                None
+            } else {
+                node_ids.pop()
+            };
+            let pairs = core::mem::take(&mut node_ids);
+            for [left, right] in pairs.into_iter().array_chunks::<2>() {
+                node_ids.push(self.ensure_join(left, right)?);
+            }
+            node_ids.extend(odd);
+        }
+
+        Ok(node_ids[0])
+    }
+
+    fn merge_contiguous_basic_blocks(
+        &mut self,
+        node_ids: Vec<MastNodeId>,
+    ) -> Result<Vec<MastNodeId>, Report> {
+        let mut merged = Vec::with_capacity(node_ids.len());
+        let mut contiguous = Vec::new();
+        for id in node_ids {
+            if self.forest[id].is_basic_block() {
+                contiguous.push(id);
+            } else {
+                merged.extend(self.merge_basic_blocks(&contiguous)?);
+                contiguous.clear();
+                merged.push(id);
+            }
+        }
+        merged.extend(self.merge_basic_blocks(&contiguous)?);
+
+        Ok(merged)
+    }
+
+    fn merge_basic_blocks(&mut self, ids: &[MastNodeId]) -> Result<Vec<MastNodeId>, Report> {
+        if ids.len() < 2 {
+            return Ok(ids.to_vec());
+        }
+
+        let mut ops = Vec::<Operation>::new();
+        let mut decorators = DecoratorList::new();
+        let mut merged = Vec::new();
+        for &id in ids {
+            let block = self.forest[id].get_basic_block().unwrap().clone();
+            if !self.forest.is_procedure_root(id) || self.is_inlined_procedure(id) {
+                for (index, decorator) in block.decorators() {
+                    decorators.push((index + ops.len(), decorator.clone()));
+                }
+                for batch in block.op_batches() {
+                    ops.extend_from_slice(batch.ops());
+                }
+            } else {
+                if !ops.is_empty() {
+                    let ops = core::mem::take(&mut ops);
+                    let decorators = core::mem::take(&mut decorators);
+                    merged.push(self.ensure_block(ops, decorators)?);
+                }
+                merged.push(id);
+            }
+        }
+        self.merged_node_ids.extend(ids.iter().copied());
+        if !ops.is_empty() {
+            merged.push(self.ensure_block(ops, decorators)?);
+        }
+
+        Ok(merged)
+    }
+
+    fn ensure_node(&mut self, node: MastNode) -> Result<MastNodeId, Report> {
+        let digest = node.digest();
+        if let Some(id) = self.node_id_by_hash.get(&digest) {
+            return Ok(*id);
+        }
+        let id = self.forest.add_node(node).map_err(forest_error)?;
+        self.node_id_by_hash.insert(digest, id);
+        Ok(id)
+    }
+
+    pub fn ensure_block(
+        &mut self,
+        ops: Vec<Operation>,
+        decorators: DecoratorList,
+    ) -> Result<MastNodeId, Report> {
+        let block = MastNode::new_basic_block(ops, Some(decorators)).map_err(forest_error)?;
+        self.ensure_node(block)
+    }
+
+    pub fn ensure_join(
+        &mut self,
+        left: MastNodeId,
+        right: MastNodeId,
+    ) -> Result<MastNodeId, Report> {
+        let node = MastNode::new_join(left, right, &self.forest).map_err(forest_error)?;
+        self.ensure_node(node)
+    }
+
+    pub fn ensure_split(
+        &mut self,
+        on_true: MastNodeId,
+        on_false: MastNodeId,
+    ) -> Result<MastNodeId, Report> {
+        let node = MastNode::new_split(on_true, on_false, &self.forest).map_err(forest_error)?;
+        self.ensure_node(node)
+    }
+
+    pub fn ensure_loop(&mut self, body: MastNodeId) -> Result<MastNodeId, Report> {
+        let node = MastNode::new_loop(body, &self.forest).map_err(forest_error)?;
+        self.ensure_node(node)
+    }
+
+    pub fn ensure_call(&mut self, callee: MastNodeId) -> Result<MastNodeId, Report> {
+        let node = MastNode::new_call(callee, &self.forest).map_err(forest_error)?;
+        self.ensure_node(node)
+    }
+
+    pub fn ensure_syscall(&mut self, callee: MastNodeId) -> Result<MastNodeId, Report> {
+        let node = MastNode::new_syscall(callee, &self.forest).map_err(forest_error)?;
+        self.ensure_node(node)
+    }
+
+    pub fn ensure_dyn(&mut self) -> Result<MastNodeId, Report> {
+        self.ensure_node(MastNode::new_dyn())
+    }
+
+    pub fn ensure_external(&mut self, digest: RpoDigest) -> Result<MastNodeId, Report> {
+        self.ensure_node(MastNode::new_external(digest))
+    }
+}
+
+fn forest_error(err: MastForestError) -> Report {
+    Report::msg(format!("failed to construct MAST: {err}"))
+}
@@ -0,0 +1,512 @@
+//! This module implements the direct lowering of [masm::Function] bodies to MAST.
+//!
+//! Historically, assembling a [masm::Program] or [masm::Library] required converting every module
+//! to its [miden_assembly::ast] representation, and handing the result to the Miden Assembly
+//! assembler. Besides the cost of the round-trip, this loses track of the source spans we have
+//! for the code we generate. Here, we instead build the [miden_core::mast::MastForest] ourselves,
+//! following the same rules as the assembler, so that the resulting procedure roots are identical
+//! regardless of the path taken.
+//!
+//! The assembler-based path is still available via `--assemble-from-ast`, and is what is used to
+//! emit textual MASM.
+mod builder;
+mod ops;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Arc,
+};
+
+use miden_assembly::{
+    ast::{self, ProcedureName, QualifiedProcedureName},
+    KernelLibrary, Library as CompiledLibrary,
+};
+use miden_core::{crypto::hash::RpoDigest, mast::MastNodeId, AssemblyOp, Felt, Operation};
+use midenc_hir::{
+    diagnostics::{Report, Span, Spanned},
+    FunctionIdent, Ident, Symbol, TRACE_FRAME_END, TRACE_FRAME_START,
+};
+use midenc_session::Session;
+
+pub(crate) use self::builder::PROCEDURE_INLINING_THRESHOLD;
+use self::{builder::MastForestBuilder, ops::BasicBlockBuilder};
+use crate::masm::{self, Op};
+
+/// The state needed to lower the body of a single procedure
+pub(crate) struct ProcedureContext {
+    /// The fully-qualified name of the procedure, e.g. `std::mem::memcopy`
+    name: String,
+    /// The number of procedure locals allocated on entry to the procedure
+    num_locals: u16,
+    /// Whether the procedure is defined in a kernel module
+    is_kernel: bool,
+    /// Whether to emit debug decorators
+    debug_mode: bool,
+}
+
+/// A procedure that is defined in a library we are linking against
+#[derive(Copy, Clone)]
+struct ExternalProcedure {
+    digest: RpoDigest,
+    /// True if this procedure is exported from the kernel, i.e. it is a valid syscall target
+    is_kernel: bool,
+}
+
+/// The result of resolving an invocation target
+enum Callee<'a> {
+    Local(&'a masm::Module, &'a masm::Function),
+    External(ExternalProcedure),
+}
+
+#[derive(Copy, Clone)]
+enum InvokeKind {
+    Exec,
+    Call,
+    SysCall,
+}
+
+/// Lowers a set of [masm::Module] directly to a [miden_core::mast::MastForest].
+///
+/// Procedures are lowered on demand, callees first, starting from the set of roots requested via
+/// [MastLowering::lower_function], so only code reachable from those roots ends up in the forest.
+pub(crate) struct MastLowering<'a> {
+    session: &'a Session,
+    debug_mode: bool,
+    kernel: Option<&'a KernelLibrary>,
+    modules: BTreeMap<Ident, &'a masm::Module>,
+    /// The set of functions defined in each module, needed to render instructions for debug info
+    locals: BTreeMap<Ident, BTreeSet<FunctionIdent>>,
+    externals: BTreeMap<String, ExternalProcedure>,
+    forest: MastForestBuilder,
+    compiled: BTreeMap<FunctionIdent, RpoDigest>,
+    in_progress: BTreeSet<FunctionIdent>,
+}
+impl<'a> MastLowering<'a> {
+    pub fn new(
+        session: &'a Session,
+        libraries: &'a [CompiledLibrary],
+        kernel: Option<&'a KernelLibrary>,
+    ) -> Self {
+        let mut externals = BTreeMap::default();
+        let linked = libraries.iter().map(|lib| (lib, false));
+        let kernel_lib = kernel.map(|k| (AsRef::<CompiledLibrary>::as_ref(k), true));
+        for (library, is_kernel) in linked.chain(kernel_lib) {
+            for module in library.module_infos() {
+                log::debug!("registering '{}' for MAST lowering", module.path());
+                for (_, procedure) in module.procedures() {
+                    let name = format!("{}::{}", module.path(), &procedure.name);
+                    let digest = procedure.digest;
+                    externals.insert(name, ExternalProcedure { digest, is_kernel });
+                }
+            }
+        }
+
+        Self {
+            session,
+            debug_mode: session.options.emit_debug_decorators(),
+            kernel,
+            modules: Default::default(),
+            locals: Default::default(),
+            externals,
+            forest: Default::default(),
+            compiled: Default::default(),
+            in_progress: Default::default(),
+        }
+    }
+
+    /// Make the functions of `module` available for lowering
+    pub fn add_module(&mut self, module: &'a masm::Module) {
+        let locals = BTreeSet::from_iter(module.functions().map(|f| f.name));
+        self.locals.insert(module.id, locals);
+        self.modules.insert(module.id, module);
+    }
+
+    /// Lower `function`, and all of the procedures it depends on, returning its MAST root
+    pub fn lower_function(
+        &mut self,
+        module: &'a masm::Module,
+        function: &'a masm::Function,
+    ) -> Result<RpoDigest, Report> {
+        if let Some(digest) = self.compiled.get(&function.name) {
+            return Ok(*digest);
+        }
+        if !self.in_progress.insert(function.name) {
+            return Err(Report::msg(format!(
+                "unable to lower '{}' to MAST: found a recursive call cycle involving this \
+                 function",
+                qualified_name(function.name)
+            )));
+        }
+
+        log::trace!("lowering '{}' to MAST", qualified_name(function.name));
+        let num_locals = u16::try_from(function.locals().len()).expect("too many locals");
+        let ctx = ProcedureContext {
+            name: format!("{}::{}", &module.name, function.name.function.as_str()),
+            num_locals,
+            is_kernel: module.is_kernel(),
+            debug_mode: self.debug_mode,
+        };
+
+        let mut body = function.body().ops.to_vec();
+        if self.debug_mode {
+            emit_trace_frame_events(function.span, &mut body);
+        }
+
+        let mut builder = if num_locals > 0 {
+            let num_locals = Felt::from(num_locals);
+            BasicBlockBuilder::with_wrapper(
+                vec![Operation::Push(num_locals), Operation::FmpUpdate],
+                vec![Operation::Push(-num_locals), Operation::FmpUpdate],
+            )
+        } else {
+            BasicBlockBuilder::default()
+        };
+        let root = self.lower_body(module, function, &body, &ctx, &mut builder)?;
+        let root = self.finish_body(builder, root)?;
+        self.forest.make_root(root);
+
+        let digest = self.forest.digest(root);
+        self.in_progress.remove(&function.name);
+        self.compiled.insert(function.name, digest);
+
+        Ok(digest)
+    }
+
+    /// Lower the re-exported procedure `alias`, defined in `module`, returning its MAST root
+    pub fn lower_alias(
+        &mut self,
+        module: &'a masm::Module,
+        alias: &ast::ProcedureAlias,
+    ) -> Result<RpoDigest, Report> {
+        let target = match alias.target() {
+            ast::AliasTarget::MastRoot(digest) => return Ok(digest.into_inner()),
+            ast::AliasTarget::ProcedurePath(name)
+            | ast::AliasTarget::AbsoluteProcedurePath(name) => FunctionIdent {
+                module: Ident::new(Symbol::intern(name.module.path()), name.span()),
+                function: Ident::new(Symbol::intern(name.name.as_str()), name.span()),
+            },
+        };
+        self.resolve_digest(module, target)
+    }
+
+    /// Returns true if the procedure with MAST root `digest`, which must have been lowered already,
+    /// is merged into the code surrounding each of its call sites, rather than being invoked.
+    pub fn is_inlined_procedure(&self, digest: RpoDigest) -> bool {
+        let root = self.forest.find_procedure_root(digest).expect("procedure was not lowered");
+        self.forest.is_inlined_procedure(root)
+    }
+
+    /// Finish lowering, producing an executable [miden_core::Program] whose entrypoint is the
+    /// procedure with the given MAST root.
+    pub fn into_program(self, entrypoint: RpoDigest) -> Result<miden_core::Program, Report> {
+        let kernel = self.kernel.map(|k| k.kernel().clone()).unwrap_or_default();
+        let entry = self
+            .forest
+            .find_procedure_root(entrypoint)
+            .expect("entrypoint was not lowered to MAST");
+        let (forest, remapping) = self.forest.build();
+        let entry = remapping.map(|remapping| remapping[&entry]).unwrap_or(entry);
+
+        Ok(miden_core::Program::with_kernel(forest, entry, kernel))
+    }
+
+    /// Finish lowering, producing a [CompiledLibrary] with the given exports
+    pub fn into_library(
+        self,
+        exports: BTreeMap<QualifiedProcedureName, RpoDigest>,
+    ) -> CompiledLibrary {
+        let (forest, _) = self.forest.build();
+        CompiledLibrary::new(forest, exports)
+    }
+
+    /// Lower a sequence of ops to MAST, appending straight-line code to `builder`, and returning
+    /// the nodes produced so far.
+    ///
+    /// The caller is expected to pass the result to [Self::finish_body].
+    fn lower_body(
+        &mut self,
+        module: &'a masm::Module,
+        function: &'a masm::Function,
+        body: &[Span<Op>],
+        ctx: &ProcedureContext,
+        builder: &mut BasicBlockBuilder,
+    ) -> Result<Vec<MastNodeId>, Report> {
+        let mut nodes = vec![];
+        for op in body.iter().copied() {
+            let span = op.span();
+            let node = match op.into_inner() {
+                Op::If(then_blk, else_blk) => {
+                    let then_blk = self.lower_block(module, function, then_blk, ctx)?;
+                    let else_blk = self.lower_block(module, function, else_blk, ctx)?;
+                    self.forest.ensure_split(then_blk, else_blk)?
+                }
+                Op::While(blk) => {
+                    let body = self.lower_block(module, function, blk, ctx)?;
+                    self.forest.ensure_loop(body)?
+                }
+                Op::Repeat(count, blk) => {
+                    let body = self.lower_block(module, function, blk, ctx)?;
+                    self.flush(builder, &mut nodes)?;
+                    nodes.extend(core::iter::repeat(body).take(count as usize));
+                    continue;
+                }
+                Op::Exec(callee) => self.invoke(InvokeKind::Exec, module, callee)?,
+                Op::Call(callee) => self.invoke(InvokeKind::Call, module, callee)?,
+                Op::Syscall(callee) => self.invoke(InvokeKind::SysCall, module, callee)?,
+                Op::DynExec => self.forest.ensure_dyn()?,
+                Op::DynCall => {
+                    let callee = self.forest.ensure_dyn()?;
+                    self.forest.ensure_call(callee)?
+                }
+                Op::ProcRef(callee) => {
+                    let digest = self.resolve_digest(module, callee)?;
+                    self.track_instruction(builder, module, ctx, span, op.into_inner());
+                    builder.push_digest(digest);
+                    builder.set_instruction_cycle_count();
+                    continue;
+                }
+                op => {
+                    self.track_instruction(builder, module, ctx, span, op);
+                    builder.lower(op, ctx)?;
+                    builder.set_instruction_cycle_count();
+                    continue;
+                }
+            };
+            self.flush(builder, &mut nodes)?;
+            nodes.push(node);
+        }
+
+        Ok(nodes)
+    }
+
+    /// Lower the nested block `id` of `function` to a single MAST node
+    fn lower_block(
+        &mut self,
+        module: &'a masm::Module,
+        function: &'a masm::Function,
+        id: masm::BlockId,
+        ctx: &ProcedureContext,
+    ) -> Result<MastNodeId, Report> {
+        let mut builder = BasicBlockBuilder::default();
+        let nodes =
+            self.lower_body(module, function, &function.block(id).ops, ctx, &mut builder)?;
+        self.finish_body(builder, nodes)
+    }
+
+    /// Combine the nodes of a body, and any remaining straight-line code, into a single node
+    fn finish_body(
+        &mut self,
+        builder: BasicBlockBuilder,
+        mut nodes: Vec<MastNodeId>,
+    ) -> Result<MastNodeId, Report> {
+        if let Some((ops, decorators)) = builder.finish() {
+            nodes.push(self.forest.ensure_block(ops, decorators)?);
+        }
+        if nodes.is_empty() {
+            self.forest.ensure_block(vec![Operation::Noop], vec![])
+        } else {
+            self.forest.join_nodes(nodes)
+        }
+    }
+
+    /// Terminate the current basic block, if it is non-empty
+    fn flush(
+        &mut self,
+        builder: &mut BasicBlockBuilder,
+        nodes: &mut Vec<MastNodeId>,
+    ) -> Result<(), Report> {
+        if let Some((ops, decorators)) = builder.take() {
+            nodes.push(self.forest.ensure_block(ops, decorators)?);
+        }
+        Ok(())
+    }
+
+    fn track_instruction(
+        &self,
+        builder: &mut BasicBlockBuilder,
+        module: &masm::Module,
+        ctx: &ProcedureContext,
+        span: midenc_hir::SourceSpan,
+        op: Op,
+    ) {
+        if !self.debug_mode {
+            return;
+        }
+        let locals = &self.locals[&module.id];
+        let instruction = op
+            .into_masm(&module.imports, locals)
+            .iter()
+            .map(|ix| ix.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let location = self.session.source_manager.location(span).ok().map(|mut location| {
+            let path = self.session.artifact_path(Path::new(location.path.as_ref()));
+            location.path = Arc::from(path.to_string_lossy().as_ref());
+            location
+        });
+        let should_break = matches!(op, Op::Breakpoint);
+        builder.track_instruction(AssemblyOp::new(
+            location,
+            ctx.name.clone(),
+            0,
+            instruction,
+            should_break,
+        ));
+    }
+
+    fn invoke(
+        &mut self,
+        kind: InvokeKind,
+        module: &'a masm::Module,
+        callee: FunctionIdent,
+    ) -> Result<MastNodeId, Report> {
+        let digest = match self.resolve(module, callee)? {
+            Callee::Local(callee_module, function) => {
+                if matches!(kind, InvokeKind::SysCall)
+                    && !(callee_module.is_kernel() && function.signature.is_kernel())
+                {
+                    return Err(invalid_syscall(callee));
+                }
+                self.lower_function(callee_module, function)?
+            }
+            Callee::External(procedure) => {
+                if matches!(kind, InvokeKind::SysCall) && !procedure.is_kernel {
+                    return Err(invalid_syscall(callee));
+                }
+                procedure.digest
+            }
+        };
+
+        let root = match self.forest.find_procedure_root(digest) {
+            Some(root) => root,
+            None => self.forest.ensure_external(digest)?,
+        };
+        match kind {
+            InvokeKind::Exec => Ok(root),
+            InvokeKind::Call => self.forest.ensure_call(root),
+            InvokeKind::SysCall => self.forest.ensure_syscall(root),
+        }
+    }
+
+    /// Resolve `callee`, lowering it first if it is defined locally, and return its MAST root
+    fn resolve_digest(
+        &mut self,
+        module: &'a masm::Module,
+        callee: FunctionIdent,
+    ) -> Result<RpoDigest, Report> {
+        match self.resolve(module, callee)? {
+            Callee::Local(module, function) => self.lower_function(module, function),
+            Callee::External(procedure) => Ok(procedure.digest),
+        }
+    }
+
+    /// Resolve `callee`, as referenced from `module`
+    fn resolve(
+        &mut self,
+        module: &'a masm::Module,
+        callee: FunctionIdent,
+    ) -> Result<Callee<'a>, Report> {
+        if let Some(callee) = self.resolve_absolute(callee)? {
+            return Ok(callee);
+        }
+
+        // The callee may be referenced via an import alias, or for code parsed from textual MASM,
+        // by the last component of the module path.
+        let aliased = module.imports.unalias(&callee.module).map(|module| FunctionIdent {
+            module,
+            function: callee.function,
+        });
+        if let Some(callee) = aliased {
+            if let Some(callee) = self.resolve_absolute(callee)? {
+                return Ok(callee);
+            }
+        }
+        let short_name = callee.module.as_str();
+        if !short_name.contains("::") {
+            let suffix = format!("::{short_name}");
+            let candidates = self
+                .modules
+                .keys()
+                .map(|id| id.as_str())
+                .chain(self.externals.keys().filter_map(|name| Some(name.rsplit_once("::")?.0)))
+                .filter(|name| name.ends_with(&suffix))
+                .collect::<BTreeSet<_>>();
+            if let [name] = candidates.into_iter().collect::<Vec<_>>().as_slice() {
+                let resolved = FunctionIdent {
+                    module: Ident::new(Symbol::intern(*name), callee.module.span()),
+                    function: callee.function,
+                };
+                if let Some(callee) = self.resolve_absolute(resolved)? {
+                    return Ok(callee);
+                }
+            }
+        }
+
+        Err(Report::msg(format!(
+            "undefined procedure '{}': referenced from '{}', but no such procedure is defined or \
+             linked",
+            qualified_name(callee),
+            &module.name
+        )))
+    }
+
+    /// Resolve `callee`, assuming it is a fully-qualified name
+    fn resolve_absolute(&mut self, callee: FunctionIdent) -> Result<Option<Callee<'a>>, Report> {
+        if let Some(module) = self.modules.get(&callee.module).copied() {
+            if let Some(function) = module.functions().find(|f| f.name.function == callee.function)
+            {
+                return Ok(Some(Callee::Local(module, function)));
+            }
+            if let Some(alias) = module
+                .reexports()
+                .iter()
+                .find(|a| a.name().as_str() == callee.function.as_str())
+            {
+                let digest = self.lower_alias(module, alias)?;
+                let is_kernel = module.is_kernel();
+                return Ok(Some(Callee::External(ExternalProcedure { digest, is_kernel })));
+            }
+            return Ok(None);
+        }
+
+        let name = qualified_name(callee);
+        Ok(self.externals.get(&name).copied().map(Callee::External))
+    }
+}
+
+/// Wrap the body of a procedure with trace events marking entry and exit of its frame, unless the
+/// body already starts with such an event.
+///
+/// This mirrors what is done when converting a [masm::Function] to its AST representation.
+fn emit_trace_frame_events(span: midenc_hir::SourceSpan, body: &mut Vec<Span<Op>>) {
+    if let Some(Op::Trace(TRACE_FRAME_START)) = body.get(1).map(|op| op.into_inner()) {
+        return;
+    }
+    body.splice(0..0, [Span::new(span, Op::Nop), Span::new(span, Op::Trace(TRACE_FRAME_START))]);
+    body.extend([Span::new(span, Op::Nop), Span::new(span, Op::Trace(TRACE_FRAME_END))]);
+}
+
+fn invalid_syscall(callee: FunctionIdent) -> Report {
+    Report::msg(format!(
+        "invalid syscall: '{}' is not a kernel procedure, and cannot be the target of a syscall",
+        qualified_name(callee)
+    ))
+}
+
+/// Render `id` as a fully-qualified Miden Assembly procedure path
+fn qualified_name(id: FunctionIdent) -> String {
+    format!("{}::{}", id.module.as_str(), id.function.as_str())
+}
+
+/// Get the name under which `function` is exported from a library
+pub(crate) fn export_name(
+    module: &masm::Module,
+    function: impl AsRef<str>,
+) -> QualifiedProcedureName {
+    let name = ast::Ident::new_unchecked(Span::new(
+        midenc_hir::SourceSpan::UNKNOWN,
+        Arc::from(function.as_ref().to_string().into_boxed_str()),
+    ));
+    QualifiedProcedureName::new(module.name.clone(), ProcedureName::new_unchecked(name))
+}
@@ -0,0 +1,1056 @@
+use miden_core::{
+    AdviceInjector, AssemblyOp, Decorator, DecoratorList, Felt, FieldElement, Operation, ONE, ZERO,
+};
+use midenc_hir::diagnostics::Report;
+
+use super::ProcedureContext;
+use crate::masm::Op;
+
+/// The largest number of elements which may be read from the advice stack by `adv_push`
+const ADVICE_READ_LIMIT: u8 = 16;
+/// The largest immediate shift amount accepted by `u32shl`/`u32shr`
+const MAX_U32_SHIFT_VALUE: u8 = 31;
+/// The largest immediate rotation amount accepted by `u32rotl`/`u32rotr`
+const MAX_U32_ROTATE_VALUE: u8 = 31;
+/// The largest number of exponent bits accepted by `exp.uN`
+const MAX_EXP_BITS: u8 = 64;
+
+const TWO: Felt = Felt::new(2);
+
+/// Accumulates the operations and decorators of a basic block.
+///
+/// The expansion of each [Op] into VM operations must match that of the Miden Assembly assembler
+/// exactly, as any difference changes the MAST root of the procedure containing it. Each expansion
+/// below therefore mirrors its counterpart in `miden_assembly::assembler::instruction`.
+#[derive(Default)]
+pub(super) struct BasicBlockBuilder {
+    ops: Vec<Operation>,
+    decorators: DecoratorList,
+    epilogue: Vec<Operation>,
+    last_asmop: Option<usize>,
+}
+impl BasicBlockBuilder {
+    /// Create a builder whose first block begins with `prologue`, and whose last block ends with
+    /// `epilogue`, as is done to allocate and free procedure locals.
+    pub fn with_wrapper(prologue: Vec<Operation>, epilogue: Vec<Operation>) -> Self {
+        Self {
+            ops: prologue,
+            epilogue,
+            ..Default::default()
+        }
+    }
+
+    /// Take the operations and decorators accumulated so far, if there are any
+    pub fn take(&mut self) -> Option<(Vec<Operation>, DecoratorList)> {
+        if self.ops.is_empty() && !self.decorators.is_empty() {
+            // Decorators must be attached to an operation, so pad the block if all we have are
+            // decorators, e.g. a block consisting solely of `trace` or `emit`
+            self.ops.push(Operation::Noop);
+        }
+        if self.ops.is_empty() {
+            return None;
+        }
+        self.last_asmop = None;
+        Some((core::mem::take(&mut self.ops), core::mem::take(&mut self.decorators)))
+    }
+
+    /// Take the remaining operations and decorators, including the epilogue, if any
+    pub fn finish(mut self) -> Option<(Vec<Operation>, DecoratorList)> {
+        let mut epilogue = core::mem::take(&mut self.epilogue);
+        self.ops.append(&mut epilogue);
+        self.take()
+    }
+
+    fn push_op(&mut self, op: Operation) {
+        self.ops.push(op);
+    }
+
+    fn push_ops<const N: usize>(&mut self, ops: [Operation; N]) {
+        self.ops.extend(ops);
+    }
+
+    fn push_op_many(&mut self, op: Operation, n: usize) {
+        self.ops.resize(self.ops.len() + n, op);
+    }
+
+    fn push_decorator(&mut self, decorator: Decorator) {
+        self.decorators.push((self.ops.len(), decorator));
+    }
+
+    fn push_advice_injector(&mut self, injector: AdviceInjector) {
+        self.push_decorator(Decorator::Advice(injector));
+    }
+
+    /// Attach an [AssemblyOp] decorator describing the instruction about to be lowered
+    pub fn track_instruction(&mut self, asmop: AssemblyOp) {
+        self.push_decorator(Decorator::AsmOp(asmop));
+        self.last_asmop = Some(self.decorators.len() - 1);
+    }
+
+    /// Record the number of cycles taken by the instruction last passed to
+    /// [Self::track_instruction], removing its decorator if it produced no operations.
+    pub fn set_instruction_cycle_count(&mut self) {
+        let Some(index) = self.last_asmop.take() else {
+            return;
+        };
+        let (op_start, decorator) = &mut self.decorators[index];
+        let cycles = self.ops.len() - *op_start;
+        if cycles == 0 {
+            self.decorators.remove(index);
+        } else if let Decorator::AsmOp(asmop) = decorator {
+            asmop.set_num_cycles(cycles.try_into().unwrap_or(u8::MAX));
+        }
+    }
+
+    /// Append the VM operations implementing `op`.
+    ///
+    /// Control flow and procedure invocations are not handled here, as they produce MAST nodes
+    /// other than basic blocks, see [super::MastLowering].
+    pub fn lower(&mut self, op: Op, ctx: &ProcedureContext) -> Result<(), Report> {
+        use Operation::*;
+
+        match op {
+            Op::Padw => self.push_ops([Pad; 4]),
+            Op::Push(imm) => self.push_felt(imm),
+            Op::Push2(imms) => imms.into_iter().for_each(|imm| self.push_felt(imm)),
+            Op::Pushw(imms) => imms.into_iter().for_each(|imm| self.push_felt(imm)),
+            Op::PushU8(imm) => self.push_felt(imm.into()),
+            Op::PushU16(imm) => self.push_felt(imm.into()),
+            Op::PushU32(imm) => self.push_felt(imm.into()),
+            Op::Drop => self.push_op(Drop),
+            Op::Dropw => self.push_ops([Drop; 4]),
+            Op::Dup(n) => self.dup(n),
+            Op::Dupw(0) => self.push_ops([Dup3; 4]),
+            Op::Dupw(1) => self.push_ops([Dup7; 4]),
+            Op::Dupw(2) => self.push_ops([Dup11; 4]),
+            Op::Dupw(3) => self.push_ops([Dup15; 4]),
+            Op::Dupw(n) => return Err(invalid_index("dupw", n, 0..=3)),
+            Op::Swap(n) => self.swap(n),
+            Op::Swapw(1) => self.push_op(SwapW),
+            Op::Swapw(2) => self.push_op(SwapW2),
+            Op::Swapw(3) => self.push_op(SwapW3),
+            Op::Swapw(n) => return Err(invalid_index("swapw", n, 1..=3)),
+            Op::Swapdw => self.push_op(SwapDW),
+            Op::Movup(n) => self.movup(n),
+            Op::Movupw(2) => self.push_ops([SwapW, SwapW2]),
+            Op::Movupw(3) => self.push_ops([SwapW, SwapW2, SwapW3]),
+            Op::Movupw(n) => return Err(invalid_index("movupw", n, 2..=3)),
+            Op::Movdn(n) => self.movdn(n),
+            Op::Movdnw(2) => self.push_ops([SwapW2, SwapW]),
+            Op::Movdnw(3) => self.push_ops([SwapW3, SwapW2, SwapW]),
+            Op::Movdnw(n) => return Err(invalid_index("movdnw", n, 2..=3)),
+            Op::Cswap => self.push_op(CSwap),
+            Op::Cswapw => self.push_op(CSwapW),
+            Op::Cdrop => self.push_ops([CSwap, Drop]),
+            Op::Cdropw => self.push_ops([CSwapW, Drop, Drop, Drop, Drop]),
+            Op::Assert => self.push_op(Assert(0)),
+            Op::AssertWithError(code) => self.push_op(Assert(code)),
+            Op::Assertz => self.push_ops([Eqz, Assert(0)]),
+            Op::AssertzWithError(code) => self.push_ops([Eqz, Assert(code)]),
+            Op::AssertEq => self.push_ops([Eq, Assert(0)]),
+            Op::AssertEqWithError(code) => self.push_ops([Eq, Assert(code)]),
+            Op::AssertEqw => self.assertw(0),
+            Op::AssertEqwWithError(code) => self.assertw(code),
+            Op::LocAddr(id) => self.local_to_absolute_addr(id.into(), ctx)?,
+            Op::LocLoad(id) => {
+                self.local_to_absolute_addr(id.into(), ctx)?;
+                self.push_op(MLoad);
+            }
+            Op::LocLoadw(id) => {
+                self.local_to_absolute_addr(id.into(), ctx)?;
+                self.push_op(MLoadW);
+            }
+            Op::LocStore(id) => {
+                self.local_to_absolute_addr(id.into(), ctx)?;
+                self.push_ops([MStore, Drop]);
+            }
+            Op::LocStorew(id) => {
+                self.local_to_absolute_addr(id.into(), ctx)?;
+                self.push_op(MStoreW);
+            }
+            Op::MemLoad => self.push_op(MLoad),
+            Op::MemLoadImm(addr) => {
+                self.push_u32(addr);
+                self.push_op(MLoad);
+            }
+            Op::MemLoadw => self.push_op(MLoadW),
+            Op::MemLoadwImm(addr) => {
+                self.push_u32(addr);
+                self.push_op(MLoadW);
+            }
+            Op::MemStore => self.push_ops([MStore, Drop]),
+            Op::MemStoreImm(addr) => {
+                self.push_u32(addr);
+                self.push_ops([MStore, Drop]);
+            }
+            Op::MemStorew => self.push_op(MStoreW),
+            Op::MemStorewImm(addr) => {
+                self.push_u32(addr);
+                self.push_op(MStoreW);
+            }
+            Op::MemStream => self.push_op(MStream),
+            Op::AdvPipe => self.push_op(Pipe),
+            Op::AdvPush(n) => {
+                validate_param("adv_push", n, 1..=ADVICE_READ_LIMIT)?;
+                self.push_op_many(AdvPop, n as usize);
+            }
+            Op::AdvLoadw => self.push_op(AdvPopW),
+            Op::AdvInjectPushU64Div => self.push_advice_injector(AdviceInjector::U64Div),
+            Op::AdvInjectPushMapVal => self.push_advice_injector(AdviceInjector::MapValueToStack {
+                include_len: false,
+                key_offset: 0,
+            }),
+            Op::AdvInjectPushMapValImm(offset) => {
+                self.push_advice_injector(AdviceInjector::MapValueToStack {
+                    include_len: false,
+                    key_offset: offset as usize,
+                })
+            }
+            Op::AdvInjectPushMapValN => {
+                self.push_advice_injector(AdviceInjector::MapValueToStack {
+                    include_len: true,
+                    key_offset: 0,
+                })
+            }
+            Op::AdvInjectPushMapValNImm(offset) => {
+                self.push_advice_injector(AdviceInjector::MapValueToStack {
+                    include_len: true,
+                    key_offset: offset as usize,
+                })
+            }
+            Op::AdvInjectPushMTreeNode => {
+                self.push_advice_injector(AdviceInjector::MerkleNodeToStack)
+            }
+            Op::AdvInjectInsertMem => self.push_advice_injector(AdviceInjector::MemToMap),
+            Op::AdvInjectInsertHdword => {
+                self.push_advice_injector(AdviceInjector::HdwordToMap { domain: ZERO })
+            }
+            Op::AdvInjectInsertHdwordImm(domain) => {
+                self.push_advice_injector(AdviceInjector::HdwordToMap {
+                    domain: domain.into(),
+                })
+            }
+            Op::AdvInjectInsertHperm => self.push_advice_injector(AdviceInjector::HpermToMap),
+            Op::AdvInjectPushSignature(kind) => {
+                self.push_advice_injector(AdviceInjector::SigToStack { kind: kind.into() })
+            }
+            Op::Hash => self.push_ops([
+                Pad, Incr, Pad, Pad, Pad, SwapW, Dup7, Dup7, Dup7, Dup7, HPerm, Drop, Drop, Drop,
+                Drop, SwapW, Drop, Drop, Drop, Drop,
+            ]),
+            Op::Hmerge => self.hmerge(),
+            Op::Hperm => self.push_op(HPerm),
+            Op::MtreeGet => {
+                self.read_mtree_node();
+                self.push_ops([MpVerify(0), MovUp4, Drop, MovUp4, Drop]);
+            }
+            Op::MtreeSet => {
+                self.read_mtree_node();
+                self.push_ops([
+                    MovUp5, MovUp5, Dup5, Dup5, SwapDW, SwapW, SwapW2, Dup13, Dup13, MrUpdate,
+                    MovUp4, Drop, MovUp4, Drop, SwapW, Drop, Drop, Drop, Drop, SwapW, Drop, Drop,
+                    Drop, Drop, SwapW,
+                ]);
+            }
+            Op::MtreeMerge => {
+                self.push_advice_injector(AdviceInjector::MerkleNodeMerge);
+                self.hmerge();
+            }
+            Op::MtreeVerify => self.push_op(MpVerify(0)),
+            Op::MtreeVerifyWithError(code) => self.push_op(MpVerify(code)),
+            Op::FriExt2Fold4 => self.push_op(FriE2F4),
+            Op::RCombBase => self.push_op(RCombBase),
+            Op::Ext2add => self.push_ops([Swap, MovUp3, Add, MovDn2, Add]),
+            Op::Ext2sub => self.push_ops([Neg, Swap, Neg, MovUp3, Add, MovDn2, Add]),
+            Op::Ext2mul => self.push_ops([Ext2Mul, Drop, Drop]),
+            Op::Ext2neg => self.push_ops([Neg, Swap, Neg, Swap]),
+            Op::Ext2inv => self.ext2_inv(),
+            Op::Ext2div => {
+                self.ext2_inv();
+                self.push_ops([Ext2Mul, Drop, Drop]);
+            }
+            Op::If(..)
+            | Op::While(_)
+            | Op::Repeat(..)
+            | Op::Exec(_)
+            | Op::Call(_)
+            | Op::Syscall(_)
+            | Op::ProcRef(_)
+            | Op::DynExec
+            | Op::DynCall => {
+                unreachable!("control flow and invocations must be handled by the caller")
+            }
+            Op::Add => self.push_op(Add),
+            Op::AddImm(imm) => {
+                if imm == ZERO {
+                    self.push_op(Noop);
+                } else if imm == ONE {
+                    self.push_op(Incr);
+                } else if imm == TWO {
+                    self.push_ops([Incr, Incr]);
+                } else {
+                    self.push_ops([Push(imm), Add]);
+                }
+            }
+            Op::Sub => self.push_ops([Neg, Add]),
+            Op::SubImm(imm) => {
+                if imm == ZERO {
+                    self.push_op(Noop);
+                } else {
+                    self.push_ops([Push(-imm), Add]);
+                }
+            }
+            Op::Mul => self.push_op(Mul),
+            Op::MulImm(imm) => {
+                if imm == ZERO {
+                    self.push_ops([Drop, Pad]);
+                } else if imm == ONE {
+                    self.push_op(Noop);
+                } else {
+                    self.push_ops([Push(imm), Mul]);
+                }
+            }
+            Op::Div => self.push_ops([Inv, Mul]),
+            Op::DivImm(imm) => {
+                if imm == ZERO {
+                    return Err(Report::msg("invalid div immediate: division by zero"));
+                } else if imm == ONE {
+                    self.push_op(Noop);
+                } else {
+                    self.push_ops([Push(imm.inv()), Mul]);
+                }
+            }
+            Op::Neg => self.push_op(Neg),
+            Op::Inv => self.push_op(Inv),
+            Op::Incr => self.push_op(Incr),
+            Op::Ilog2 => self.ilog2(),
+            Op::Pow2 => self.pow2(),
+            Op::Exp => self.exp(MAX_EXP_BITS)?,
+            Op::ExpImm(pow) => self.exp_imm(pow as u64)?,
+            Op::ExpBitLength(bits) => self.exp(bits)?,
+            Op::Not => self.push_op(Not),
+            Op::And => self.push_op(And),
+            Op::AndImm(imm) => {
+                self.push_felt(Felt::from(imm as u8));
+                self.push_op(And);
+            }
+            Op::Or => self.push_op(Or),
+            Op::OrImm(imm) => {
+                self.push_felt(Felt::from(imm as u8));
+                self.push_op(Or);
+            }
+            Op::Xor => self.xor(),
+            Op::XorImm(imm) => {
+                self.push_felt(Felt::from(imm as u8));
+                self.xor();
+            }
+            Op::Eq => self.push_op(Eq),
+            Op::EqImm(imm) => {
+                if imm == ZERO {
+                    self.push_op(Eqz);
+                } else {
+                    self.push_ops([Push(imm), Eq]);
+                }
+            }
+            Op::Neq => self.push_ops([Eq, Not]),
+            Op::NeqImm(imm) => {
+                if imm == ZERO {
+                    self.push_ops([Eqz, Not]);
+                } else {
+                    self.push_ops([Push(imm), Eq, Not]);
+                }
+            }
+            Op::Gt => self.gt(),
+            Op::GtImm(imm) => {
+                self.push_felt(imm);
+                self.gt();
+            }
+            Op::Gte => self.gte(),
+            Op::GteImm(imm) => {
+                self.push_felt(imm);
+                self.gte();
+            }
+            Op::Lt => self.lt(),
+            Op::LtImm(imm) => {
+                self.push_felt(imm);
+                self.lt();
+            }
+            Op::Lte => self.lte(),
+            Op::LteImm(imm) => {
+                self.push_felt(imm);
+                self.lte();
+            }
+            Op::IsOdd => self.push_ops([U32split, Drop, Pad, Incr, U32and]),
+            Op::Eqw => self.push_ops([
+                Dup7, Dup4, Eq, Dup7, Dup4, Eq, And, Dup6, Dup3, Eq, And, Dup5, Dup2, Eq, And,
+            ]),
+            Op::Sdepth => self.push_op(SDepth),
+            Op::Caller => {
+                if !ctx.is_kernel {
+                    return Err(Report::msg(format!(
+                        "invalid use of 'caller' in '{}': only kernel procedures may use it",
+                        &ctx.name
+                    )));
+                }
+                self.push_op(Caller);
+            }
+            Op::Clk => self.push_op(Clk),
+            Op::U32Test => self.push_ops([Dup0, U32split, Swap, Drop, Eqz]),
+            Op::U32Testw => self.push_ops([
+                Dup3, U32split, Swap, Drop, Eqz, Dup3, U32split, Swap, Drop, Eqz, And, Dup2,
+                U32split, Swap, Drop, Eqz, And, Dup1, U32split, Swap, Drop, Eqz, And,
+            ]),
+            Op::U32Assert => self.push_ops([Pad, U32assert2(0), Drop]),
+            Op::U32AssertWithError(code) => self.push_ops([Pad, U32assert2(code), Drop]),
+            Op::U32Assert2 => self.push_op(U32assert2(0)),
+            Op::U32Assert2WithError(code) => self.push_op(U32assert2(code)),
+            Op::U32Assertw => self.u32assertw(0),
+            Op::U32AssertwWithError(code) => self.u32assertw(code),
+            Op::U32Cast => self.push_ops([U32split, Drop]),
+            Op::U32Split => self.push_op(U32split),
+            Op::U32OverflowingAdd => self.u32_arithmetic(U32add, false, None),
+            Op::U32OverflowingAddImm(imm) => self.u32_arithmetic(U32add, false, Some(imm)),
+            Op::U32WrappingAdd => self.u32_arithmetic(U32add, true, None),
+            Op::U32WrappingAddImm(imm) => self.u32_arithmetic(U32add, true, Some(imm)),
+            Op::U32OverflowingAdd3 => self.push_op(U32add3),
+            Op::U32WrappingAdd3 => self.push_ops([U32add3, Drop]),
+            Op::U32OverflowingSub => self.u32_arithmetic(U32sub, false, None),
+            Op::U32OverflowingSubImm(imm) => self.u32_arithmetic(U32sub, false, Some(imm)),
+            Op::U32WrappingSub => self.u32_arithmetic(U32sub, true, None),
+            Op::U32WrappingSubImm(imm) => self.u32_arithmetic(U32sub, true, Some(imm)),
+            Op::U32OverflowingMul => self.u32_arithmetic(U32mul, false, None),
+            Op::U32OverflowingMulImm(imm) => self.u32_arithmetic(U32mul, false, Some(imm)),
+            Op::U32WrappingMul => self.u32_arithmetic(U32mul, true, None),
+            Op::U32WrappingMulImm(imm) => self.u32_arithmetic(U32mul, true, Some(imm)),
+            Op::U32OverflowingMadd => self.push_op(U32madd),
+            Op::U32WrappingMadd => self.push_ops([U32madd, Drop]),
+            Op::U32Div => self.push_ops([U32div, Drop]),
+            Op::U32DivImm(imm) => {
+                self.u32_division(imm)?;
+                self.push_op(Drop);
+            }
+            Op::U32Mod => self.push_ops([U32div, Swap, Drop]),
+            Op::U32ModImm(imm) => {
+                self.u32_division(imm)?;
+                self.push_ops([Swap, Drop]);
+            }
+            Op::U32DivMod => self.push_op(U32div),
+            Op::U32DivModImm(imm) => self.u32_division(imm)?,
+            Op::U32And => self.push_op(U32and),
+            Op::U32Or => self.push_ops([Dup1, Dup1, U32and, Neg, Add, Add]),
+            Op::U32Xor => self.push_op(U32xor),
+            Op::U32Not => {
+                self.push_ops([Push(Felt::from(u32::MAX)), U32assert2(0), Swap, U32sub, Drop])
+            }
+            Op::U32Shl => {
+                self.pow2();
+                self.push_ops([U32mul, Drop]);
+            }
+            Op::U32ShlImm(imm) => {
+                if self.prepare_bitwise("u32shl", imm, MAX_U32_SHIFT_VALUE)? {
+                    self.push_ops([U32mul, Drop]);
+                }
+            }
+            Op::U32Shr => {
+                self.pow2();
+                self.push_ops([U32div, Drop]);
+            }
+            Op::U32ShrImm(imm) => {
+                if self.prepare_bitwise("u32shr", imm, MAX_U32_SHIFT_VALUE)? {
+                    self.push_ops([U32div, Drop]);
+                }
+            }
+            Op::U32Rotl => {
+                self.pow2();
+                self.push_ops([U32mul, Add]);
+            }
+            Op::U32RotlImm(imm) => {
+                if self.prepare_bitwise("u32rotl", imm, MAX_U32_ROTATE_VALUE)? {
+                    self.push_ops([U32mul, Add]);
+                }
+            }
+            Op::U32Rotr => {
+                self.push_ops([Push(Felt::new(32)), Swap, U32sub, Drop]);
+                self.pow2();
+                self.push_ops([U32mul, Add]);
+            }
+            Op::U32RotrImm(0) => self.push_op(Noop),
+            Op::U32RotrImm(imm) => {
+                let imm = validate_param("u32rotr", imm, 1..=(MAX_U32_ROTATE_VALUE as u32))?;
+                self.push_op(Push(Felt::new(1 << (32 - imm))));
+                self.push_ops([U32mul, Add]);
+            }
+            Op::U32Popcnt => self.u32popcnt(),
+            Op::U32Clz => {
+                self.push_advice_injector(AdviceInjector::U32Clz);
+                self.push_op(AdvPop);
+                self.push_ops([Swap, Push(32u8.into()), Dup2, Neg, Add]);
+                self.pow2();
+                self.push_ops([
+                    Push(Felt::new(u32::MAX as u64 + 1)),
+                    Dup1,
+                    Neg,
+                    Add,
+                    Swap,
+                    Push(2u8.into()),
+                    U32div,
+                    Drop,
+                    Swap,
+                    Dup1,
+                    Add,
+                    MovUp2,
+                    U32and,
+                    Eq,
+                    Assert(0),
+                ]);
+            }
+            Op::U32Ctz => {
+                self.push_advice_injector(AdviceInjector::U32Ctz);
+                self.push_op(AdvPop);
+                self.push_ops([Swap, Dup1]);
+                self.pow2();
+                self.push_ops([
+                    Dup0,
+                    Pad,
+                    Incr,
+                    Neg,
+                    Add,
+                    Swap,
+                    U32split,
+                    Drop,
+                    Dup0,
+                    MovUp2,
+                    Add,
+                    MovUp2,
+                    U32and,
+                    Eq,
+                    Assert(0),
+                ]);
+            }
+            Op::U32Clo => {
+                self.push_advice_injector(AdviceInjector::U32Clo);
+                self.push_op(AdvPop);
+                self.push_ops([Swap, Push(32u8.into()), Dup2, Neg, Add]);
+                self.pow2();
+                self.push_ops([
+                    Push(Felt::new(u32::MAX as u64 + 1)),
+                    Dup1,
+                    Neg,
+                    Add,
+                    Swap,
+                    Push(2u8.into()),
+                    U32div,
+                    Drop,
+                    Dup1,
+                    Add,
+                    MovUp2,
+                    U32and,
+                    Eq,
+                    Assert(0),
+                ]);
+            }
+            Op::U32Cto => {
+                self.push_advice_injector(AdviceInjector::U32Cto);
+                self.push_op(AdvPop);
+                self.push_ops([Swap, Dup1]);
+                self.pow2();
+                self.push_ops([
+                    Dup0,
+                    Pad,
+                    Incr,
+                    Neg,
+                    Add,
+                    Swap,
+                    U32split,
+                    Drop,
+                    Dup1,
+                    Add,
+                    MovUp2,
+                    U32and,
+                    Eq,
+                    Assert(0),
+                ]);
+            }
+            Op::U32Lt => self.push_ops([U32sub, Swap, Drop]),
+            Op::U32LtImm(imm) => {
+                self.push_felt(imm.into());
+                self.push_ops([U32sub, Swap, Drop]);
+            }
+            Op::U32Lte => self.push_ops([Swap, U32sub, Swap, Drop, Not]),
+            Op::U32LteImm(imm) => {
+                self.push_felt(imm.into());
+                self.push_ops([Swap, U32sub, Swap, Drop, Not]);
+            }
+            Op::U32Gt => self.push_ops([Swap, U32sub, Swap, Drop]),
+            Op::U32GtImm(imm) => {
+                self.push_felt(imm.into());
+                self.push_ops([Swap, U32sub, Swap, Drop]);
+            }
+            Op::U32Gte => self.push_ops([U32sub, Swap, Drop, Not]),
+            Op::U32GteImm(imm) => {
+                self.push_felt(imm.into());
+                self.push_ops([U32sub, Swap, Drop, Not]);
+            }
+            Op::U32Min => self.push_ops([Dup1, Dup1, U32sub, Swap, Drop, Eqz, CSwap, Drop]),
+            Op::U32MinImm(imm) => {
+                self.push_felt(imm.into());
+                self.push_ops([Dup1, Dup1, U32sub, Swap, Drop, Eqz, CSwap, Drop]);
+            }
+            Op::U32Max => self.push_ops([Dup1, Dup1, U32sub, Swap, Drop, Eqz, CSwap, Swap, Drop]),
+            Op::U32MaxImm(imm) => {
+                self.push_felt(imm.into());
+                self.push_ops([Dup1, Dup1, U32sub, Swap, Drop, Eqz, CSwap, Swap, Drop]);
+            }
+            Op::Breakpoint => {
+                if ctx.debug_mode {
+                    self.push_op(Noop);
+                }
+            }
+            Op::DebugStack => self.push_debug(ctx, miden_core::DebugOptions::StackAll),
+            Op::DebugStackN(n) => self.push_debug(ctx, miden_core::DebugOptions::StackTop(n)),
+            Op::DebugMemory => self.push_debug(ctx, miden_core::DebugOptions::MemAll),
+            Op::DebugMemoryAt(start) => {
+                self.push_debug(ctx, miden_core::DebugOptions::MemInterval(start, u32::MAX))
+            }
+            Op::DebugMemoryRange(start, end) => {
+                self.push_debug(ctx, miden_core::DebugOptions::MemInterval(start, end))
+            }
+            Op::DebugFrame => {
+                let end = ctx.num_locals.saturating_sub(1);
+                self.push_debug(
+                    ctx,
+                    miden_core::DebugOptions::LocalInterval(0, end, ctx.num_locals),
+                )
+            }
+            Op::DebugFrameAt(start) => {
+                let end = ctx.num_locals.saturating_sub(1);
+                self.push_debug(
+                    ctx,
+                    miden_core::DebugOptions::LocalInterval(start, end, ctx.num_locals),
+                )
+            }
+            Op::DebugFrameRange(start, end) => self.push_debug(
+                ctx,
+                miden_core::DebugOptions::LocalInterval(start, end, ctx.num_locals),
+            ),
+            Op::Emit(event) => self.push_decorator(Decorator::Event(event)),
+            Op::Trace(event) => self.push_decorator(Decorator::Trace(event)),
+            Op::Nop => self.push_op(Noop),
+        }
+
+        Ok(())
+    }
+
+    /// Push the 4 elements of `digest` on the operand stack, as done by `procref`
+    pub fn push_digest(&mut self, digest: miden_core::crypto::hash::RpoDigest) {
+        for elem in digest.iter() {
+            self.push_op(Operation::Push(*elem));
+        }
+    }
+
+    fn push_debug(&mut self, ctx: &ProcedureContext, options: miden_core::DebugOptions) {
+        if ctx.debug_mode {
+            self.push_decorator(Decorator::Debug(options));
+        }
+    }
+
+    /// Push `value`, using `pad` or `pad incr` rather than `push` for 0 and 1
+    fn push_felt(&mut self, value: Felt) {
+        use Operation::*;
+
+        if value == ZERO {
+            self.push_op(Pad);
+        } else if value == ONE {
+            self.push_ops([Pad, Incr]);
+        } else {
+            self.push_op(Push(value));
+        }
+    }
+
+    fn push_u32(&mut self, value: u32) {
+        self.push_felt(Felt::from(value));
+    }
+
+    fn dup(&mut self, n: u8) {
+        use Operation::*;
+
+        match n {
+            0 => self.push_op(Dup0),
+            1 => self.push_op(Dup1),
+            2 => self.push_op(Dup2),
+            3 => self.push_op(Dup3),
+            4 => self.push_op(Dup4),
+            5 => self.push_op(Dup5),
+            6 => self.push_op(Dup6),
+            7 => self.push_op(Dup7),
+            8 => self.push_ops([Pad, Dup9, Add]),
+            9 => self.push_op(Dup9),
+            10 => self.push_ops([Pad, Dup11, Add]),
+            11 => self.push_op(Dup11),
+            12 => self.push_ops([Pad, Dup13, Add]),
+            13 => self.push_op(Dup13),
+            14 => self.push_ops([Pad, Dup15, Add]),
+            15 => self.push_op(Dup15),
+            n => panic!("invalid dup instruction, valid index range is 0..=15, got {n}"),
+        }
+    }
+
+    fn swap(&mut self, n: u8) {
+        use Operation::*;
+
+        match n {
+            1 => self.push_op(Swap),
+            2 => self.push_ops([Swap, MovUp2]),
+            3 => self.push_ops([MovDn2, MovUp3]),
+            4 => self.push_ops([MovDn3, MovUp4]),
+            5 => self.push_ops([MovDn4, MovUp5]),
+            6 => self.push_ops([MovDn5, MovUp6]),
+            7 => self.push_ops([MovDn6, MovUp7]),
+            8 => self.push_ops([MovDn7, MovUp8]),
+            9 => self.push_ops([MovDn8, SwapDW, Swap, SwapDW, MovUp8]),
+            10 => self.push_ops([MovDn8, SwapDW, Swap, MovUp2, SwapDW, MovUp8]),
+            11 => self.push_ops([MovDn8, SwapDW, MovDn2, MovUp3, SwapDW, MovUp8]),
+            12 => self.push_ops([MovDn8, SwapDW, MovDn3, MovUp4, SwapDW, MovUp8]),
+            13 => self.push_ops([MovDn8, SwapDW, MovDn4, MovUp5, SwapDW, MovUp8]),
+            14 => self.push_ops([MovDn8, SwapDW, MovDn5, MovUp6, SwapDW, MovUp8]),
+            15 => self.push_ops([MovDn8, SwapDW, MovDn6, MovUp7, SwapDW, MovUp8]),
+            n => panic!("invalid swap instruction, valid index range is 1..=15, got {n}"),
+        }
+    }
+
+    fn movup(&mut self, n: u8) {
+        use Operation::*;
+
+        match n {
+            2 => self.push_op(MovUp2),
+            3 => self.push_op(MovUp3),
+            4 => self.push_op(MovUp4),
+            5 => self.push_op(MovUp5),
+            6 => self.push_op(MovUp6),
+            7 => self.push_op(MovUp7),
+            8 => self.push_op(MovUp8),
+            9 => self.push_ops([SwapDW, Swap, SwapDW, MovUp8]),
+            10 => self.push_ops([SwapDW, MovUp2, SwapDW, MovUp8]),
+            11 => self.push_ops([SwapDW, MovUp3, SwapDW, MovUp8]),
+            12 => self.push_ops([SwapDW, MovUp4, SwapDW, MovUp8]),
+            13 => self.push_ops([SwapDW, MovUp5, SwapDW, MovUp8]),
+            14 => self.push_ops([SwapDW, MovUp6, SwapDW, MovUp8]),
+            15 => self.push_ops([SwapDW, MovUp7, SwapDW, MovUp8]),
+            n => panic!("invalid movup instruction, valid index range is 2..=15, got {n}"),
+        }
+    }
+
+    fn movdn(&mut self, n: u8) {
+        use Operation::*;
+
+        match n {
+            2 => self.push_op(MovDn2),
+            3 => self.push_op(MovDn3),
+            4 => self.push_op(MovDn4),
+            5 => self.push_op(MovDn5),
+            6 => self.push_op(MovDn6),
+            7 => self.push_op(MovDn7),
+            8 => self.push_op(MovDn8),
+            9 => self.push_ops([MovDn8, SwapDW, Swap, SwapDW]),
+            10 => self.push_ops([MovDn8, SwapDW, MovDn2, SwapDW]),
+            11 => self.push_ops([MovDn8, SwapDW, MovDn3, SwapDW]),
+            12 => self.push_ops([MovDn8, SwapDW, MovDn4, SwapDW]),
+            13 => self.push_ops([MovDn8, SwapDW, MovDn5, SwapDW]),
+            14 => self.push_ops([MovDn8, SwapDW, MovDn6, SwapDW]),
+            15 => self.push_ops([MovDn8, SwapDW, MovDn7, SwapDW]),
+            n => panic!("invalid movdn instruction, valid index range is 2..=15, got {n}"),
+        }
+    }
+
+    fn assertw(&mut self, code: u32) {
+        use Operation::*;
+
+        self.push_ops([
+            MovUp4,
+            Eq,
+            Assert(code),
+            MovUp3,
+            Eq,
+            Assert(code),
+            MovUp2,
+            Eq,
+            Assert(code),
+            Eq,
+            Assert(code),
+        ]);
+    }
+
+    fn u32assertw(&mut self, code: u32) {
+        use Operation::*;
+
+        self.push_ops([U32assert2(code), MovUp3, MovUp3, U32assert2(code), MovUp3, MovUp3]);
+    }
+
+    /// Convert the index of a procedure local to its absolute address, relative to `fmp`
+    fn local_to_absolute_addr(&mut self, index: u16, ctx: &ProcedureContext) -> Result<(), Report> {
+        if ctx.num_locals == 0 {
+            return Err(Report::msg(format!(
+                "invalid reference to local {index} in '{}': the procedure has no locals",
+                &ctx.name
+            )));
+        }
+        let max = ctx.num_locals - 1;
+        validate_param("local index", index, 0..=max)?;
+        self.push_felt(-Felt::from(max - index));
+        self.push_op(Operation::FmpAdd);
+        Ok(())
+    }
+
+    fn hmerge(&mut self) {
+        use Operation::*;
+
+        self.push_ops([
+            Pad, Pad, Pad, Pad, SwapW2, SwapW, HPerm, Drop, Drop, Drop, Drop, SwapW, Drop, Drop,
+            Drop, Drop,
+        ]);
+    }
+
+    fn read_mtree_node(&mut self) {
+        self.push_advice_injector(AdviceInjector::MerkleNodeToStack);
+        self.push_op_many(Operation::AdvPop, 4);
+    }
+
+    fn ext2_inv(&mut self) {
+        use Operation::*;
+
+        self.push_advice_injector(AdviceInjector::Ext2Inv);
+        self.push_ops([AdvPop, AdvPop, Ext2Mul, MovUp2, Eqz, Assert(0), MovUp2, Assert(0)]);
+    }
+
+    fn xor(&mut self) {
+        use Operation::*;
+
+        self.push_ops([Dup0, Dup2, Or, MovDn2, And, Not, And]);
+    }
+
+    fn pow2(&mut self) {
+        use Operation::*;
+
+        self.push_ops([
+            Push(TWO),
+            Pad,
+            Incr,
+            Swap,
+            Pad,
+            Expacc,
+            Expacc,
+            Expacc,
+            Expacc,
+            Expacc,
+            Expacc,
+            Drop,
+            Drop,
+            Swap,
+            Eqz,
+            Assert(0),
+        ]);
+    }
+
+    fn exp(&mut self, num_pow_bits: u8) -> Result<(), Report> {
+        use Operation::*;
+
+        validate_param("exp.u", num_pow_bits, 0..=MAX_EXP_BITS)?;
+        self.push_ops([Pad, Incr, MovUp2, Pad]);
+        self.push_op_many(Expacc, num_pow_bits as usize);
+        self.push_ops([Drop, Drop, Swap, Eqz, Assert(0)]);
+        Ok(())
+    }
+
+    fn exp_imm(&mut self, pow: u64) -> Result<(), Report> {
+        use Operation::*;
+
+        match pow {
+            0 => self.push_ops([Drop, Pad, Incr]),
+            1 => self.push_op(Noop),
+            2..=7 => {
+                let n = (pow - 1) as usize;
+                self.push_op_many(Dup0, n);
+                self.push_op_many(Mul, n);
+            }
+            _ => {
+                let num_pow_bits = (64 - pow.leading_zeros()) as u8;
+                self.push_op(Push(Felt::new(pow)));
+                self.exp(num_pow_bits)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ilog2(&mut self) {
+        use Operation::*;
+
+        self.push_advice_injector(AdviceInjector::ILog2);
+        self.push_ops([AdvPop, Dup0]);
+        self.pow2();
+        self.push_ops([
+            MovUp2,
+            U32split,
+            MovUp2,
+            U32split,
+            Dup1,
+            Eqz,
+            Dup0,
+            MovDn3,
+            CSwap,
+            Drop,
+            MovDn3,
+            CSwap,
+            Drop,
+            Swap,
+            Pad,
+            Incr,
+            Incr,
+            Mul,
+            Pad,
+            Incr,
+            Neg,
+            Add,
+            Dup1,
+            U32and,
+            Eq,
+            Assert(0),
+        ]);
+    }
+
+    /// Split both field elements into their high and low 32-bit limbs, for comparison
+    fn split_elements(&mut self) {
+        use Operation::*;
+
+        self.push_ops([U32split, MovUp2, U32split]);
+    }
+
+    fn lt(&mut self) {
+        use Operation::*;
+
+        self.split_elements();
+        self.push_ops([MovUp2, U32sub, Swap, Eqz, MovUp2, MovUp3]);
+        self.push_ops([U32sub, Swap, Drop]);
+        self.push_ops([And, Or]);
+    }
+
+    fn lte(&mut self) {
+        use Operation::*;
+
+        self.split_elements();
+        self.push_ops([MovUp2, U32sub, Swap, Eqz, MovUp2, MovUp3]);
+        self.push_ops([U32sub, Swap, Eqz, Or]);
+        self.push_ops([And, Or]);
+    }
+
+    fn gt(&mut self) {
+        use Operation::*;
+
+        self.split_elements();
+        self.push_ops([Swap, MovDn2, U32sub, Swap, Eqz, MovUp3, MovUp3]);
+        self.push_ops([U32sub, Swap, Drop]);
+        self.push_ops([And, Or]);
+    }
+
+    fn gte(&mut self) {
+        use Operation::*;
+
+        self.split_elements();
+        self.push_ops([Swap, MovDn2, U32sub, Swap, Eqz, MovUp3, MovUp3]);
+        self.push_ops([U32sub, Swap, Eqz, Or]);
+        self.push_ops([And, Or]);
+    }
+
+    fn u32_arithmetic(&mut self, op: Operation, wrapping: bool, imm: Option<u32>) {
+        if let Some(imm) = imm {
+            self.push_u32(imm);
+        }
+        self.push_op(op);
+        if wrapping {
+            self.push_op(Operation::Drop);
+        }
+    }
+
+    fn u32_division(&mut self, imm: u32) -> Result<(), Report> {
+        if imm == 0 {
+            return Err(Report::msg("invalid u32 division immediate: division by zero"));
+        }
+        self.push_u32(imm);
+        self.push_op(Operation::U32div);
+        Ok(())
+    }
+
+    /// Push `2^imm` for a shift or rotation by `imm`, returning false if `imm` is 0, in which case
+    /// the operation is a no-op.
+    fn prepare_bitwise(&mut self, opcode: &str, imm: u32, max: u8) -> Result<bool, Report> {
+        if imm == 0 {
+            self.push_op(Operation::Noop);
+            return Ok(false);
+        }
+        let imm = validate_param(opcode, imm, 1..=(max as u32))?;
+        self.push_op(Operation::Push(Felt::new(1 << imm)));
+        Ok(true)
+    }
+
+    fn u32popcnt(&mut self) {
+        use Operation::*;
+
+        self.push_ops([
+            Dup0,
+            Push(Felt::new(1 << 1)),
+            U32div,
+            Drop,
+            Push(Felt::new(0x55555555)),
+            U32and,
+            U32sub,
+            Drop,
+            Dup0,
+            Push(Felt::new(1 << 2)),
+            U32div,
+            Drop,
+            Push(Felt::new(0x33333333)),
+            U32and,
+            Swap,
+            Push(Felt::new(0x33333333)),
+            U32and,
+            U32add,
+            Drop,
+            Dup0,
+            Push(Felt::new(1 << 4)),
+            U32div,
+            Drop,
+            U32add,
+            Drop,
+            Push(Felt::new(0x0f0f0f0f)),
+            U32and,
+            Push(Felt::new(0x01010101)),
+            U32mul,
+            Drop,
+            Push(Felt::new(1 << 24)),
+            U32div,
+            Drop,
+        ]);
+    }
+}
+
+fn validate_param<T>(what: &str, value: T, range: core::ops::RangeInclusive<T>) -> Result<T, Report>
+where
+    T: PartialOrd + core::fmt::Display + Copy,
+{
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(Report::msg(format!(
+            "invalid {what} immediate: expected a value in the range {}..={}, got {value}",
+            range.start(),
+            range.end()
+        )))
+    }
+}
+
+fn invalid_index(opcode: &str, index: u8, range: core::ops::RangeInclusive<u8>) -> Report {
+    Report::msg(format!(
+        "invalid {opcode} instruction: valid index range is {}..={}, got {index}",
+        range.start(),
+        range.end()
+    ))
+}
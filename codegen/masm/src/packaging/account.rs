@@ -0,0 +1,237 @@
+use alloc::{collections::BTreeSet, fmt};
+
+use midenc_session::{
+    diagnostics::{Report, WrapErr},
+    Session,
+};
+use serde::{Deserialize, Serialize};
+
+use super::PackageExport;
+use crate::MasmArtifact;
+
+inventory::submit! {
+    midenc_session::CompileFlag::new("account_component")
+        .long("account-component")
+        .action(midenc_session::FlagAction::Set)
+        .help(
+            "Path to a TOML file describing the package as an account component, i.e. its \
+             storage layout and account interface. It is validated and stored in the package \
+             manifest"
+        )
+        .help_heading("Codegen")
+}
+
+/// The number of storage slots available to an account
+pub const MAX_STORAGE_SLOTS: usize = 255;
+
+/// Describes a package which implements a component of a Miden account: the storage slots it
+/// uses, and which of its exported procedures are account interface methods, rather than
+/// internal procedures.
+///
+/// This is declared in TOML, either in a file passed via `--account-component`, or embedded in
+/// the source program (e.g. in the `miden:account-component` custom section of a Wasm module):
+///
+/// ```toml
+/// name = "basic_wallet"
+/// description = "A wallet which can send and receive assets"
+/// interface = ["receive_asset", "send_asset"]
+///
+/// [[storage]]
+/// name = "owner_public_key"
+/// slot = 0
+///
+/// [[storage]]
+/// name = "allowances"
+/// slot = 1
+/// kind = "map"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountComponentMetadata {
+    /// The name of the component
+    pub name: String,
+    /// A human-readable description of the component
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The storage slots used by the component
+    #[serde(default)]
+    pub storage: Vec<StorageSlot>,
+    /// The exported procedures which are account interface methods, by name.
+    ///
+    /// All other exports of the package are internal to the component.
+    #[serde(default)]
+    pub interface: BTreeSet<String>,
+}
+
+/// A named range of account storage slots used by an account component
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageSlot {
+    /// The name of the slot
+    pub name: String,
+    /// The index of the first storage slot in the range
+    pub slot: u8,
+    /// The number of consecutive storage slots in the range
+    #[serde(default = "StorageSlot::default_size")]
+    pub size: u8,
+    /// What kind of value is stored in the slot
+    #[serde(default)]
+    pub kind: StorageKind,
+    /// A human-readable description of what is stored in the slot
+    #[serde(default)]
+    pub description: Option<String>,
+}
+impl StorageSlot {
+    const fn default_size() -> u8 {
+        1
+    }
+
+    /// The range of storage slot indices used by this slot
+    pub fn indices(&self) -> core::ops::Range<usize> {
+        let start = self.slot as usize;
+        start..(start + self.size as usize)
+    }
+}
+impl fmt::Display for StorageSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.size {
+            1 => write!(f, "'{}' (slot {})", &self.name, self.slot),
+            _ => write!(
+                f,
+                "'{}' (slots {}..{})",
+                &self.name,
+                self.indices().start,
+                self.indices().end
+            ),
+        }
+    }
+}
+
+/// The kind of value stored in an account storage slot
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// The slot stores a single word
+    #[default]
+    Value,
+    /// The slot stores the root of a storage map
+    Map,
+}
+impl fmt::Display for StorageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value => f.pad("value"),
+            Self::Map => f.pad("map"),
+        }
+    }
+}
+
+impl AccountComponentMetadata {
+    /// Parse account-component metadata from `toml`
+    pub fn parse(toml: &str) -> Result<Self, Report> {
+        toml::from_str(toml).map_err(|err| Report::msg(err.to_string()))
+    }
+
+    /// Get the account-component metadata for the package being built from `masm`, if any.
+    ///
+    /// The metadata is taken from the file given by `--account-component`, or if that flag is
+    /// not present, from the metadata declared in the source program. It is an error for both
+    /// to be present, as it is ambiguous which one is intended.
+    pub fn from_session(masm: &MasmArtifact, session: &Session) -> Result<Option<Self>, Report> {
+        let declared = match masm {
+            MasmArtifact::Executable(ref prog) => prog.account_component(),
+            MasmArtifact::Library(ref lib) => lib.account_component(),
+        };
+        match (session.get_flag_value::<String>("account_component"), declared) {
+            (Some(path), None) => {
+                let toml = std::fs::read_to_string(path).map_err(|err| {
+                    Report::msg(format!(
+                        "unable to read account-component metadata from '{path}': {err}"
+                    ))
+                })?;
+                Self::parse(&toml)
+                    .map(Some)
+                    .wrap_err_with(|| format!("invalid account-component metadata in '{path}'"))
+            }
+            (None, Some(toml)) => Self::parse(toml)
+                .map(Some)
+                .wrap_err("invalid account-component metadata declared in the source program"),
+            (None, None) => Ok(None),
+            (Some(path), Some(_)) => Err(Report::msg(format!(
+                "account-component metadata was declared in the source program, and also given by \
+                 '{path}': only one of them may be used"
+            ))),
+        }
+    }
+
+    /// Validate this metadata against the `exports` of the package it describes.
+    ///
+    /// Returns an error if:
+    ///
+    /// * A storage slot is empty, or extends past the last storage slot of an account
+    /// * A storage map occupies more than one slot
+    /// * Two storage slots have the same name, or overlap
+    /// * An interface method is not exported by the package
+    pub fn validate(&self, exports: &BTreeSet<PackageExport>) -> Result<(), Report> {
+        let mut names = BTreeSet::default();
+        for slot in self.storage.iter() {
+            if slot.size == 0 {
+                return Err(Report::msg(format!("storage slot {slot} must have a non-zero size")));
+            }
+            if slot.indices().end > MAX_STORAGE_SLOTS {
+                return Err(Report::msg(format!(
+                    "storage slot {slot} is out of bounds: accounts have {MAX_STORAGE_SLOTS} \
+                     storage slots"
+                )));
+            }
+            if slot.kind == StorageKind::Map && slot.size != 1 {
+                return Err(Report::msg(format!(
+                    "storage slot {slot} is a map, so it must occupy exactly one slot"
+                )));
+            }
+            if !names.insert(slot.name.as_str()) {
+                return Err(Report::msg(format!(
+                    "storage slot '{}' is declared more than once",
+                    &slot.name
+                )));
+            }
+        }
+
+        let mut slots = self.storage.iter().collect::<Vec<_>>();
+        slots.sort_by_key(|slot| slot.slot);
+        for pair in slots.windows(2) {
+            if pair[0].indices().end > pair[1].indices().start {
+                return Err(Report::msg(format!(
+                    "storage slot {} overlaps storage slot {}",
+                    pair[0], pair[1]
+                )));
+            }
+        }
+
+        for method in self.interface.iter() {
+            if !exports.iter().any(|export| Self::is_export_named(export, method)) {
+                return Err(Report::msg(format!(
+                    "interface method '{method}' is not exported by the package"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if `export` is an interface method of this component
+    pub fn is_interface_method(&self, export: &PackageExport) -> bool {
+        self.interface.iter().any(|method| Self::is_export_named(export, method))
+    }
+
+    /// Interface methods may be named either by their fully-qualified name, e.g.
+    /// `wallet::receive_asset`, or just the procedure name, e.g. `receive_asset`.
+    fn is_export_named(export: &PackageExport, name: &str) -> bool {
+        match name.rsplit_once("::") {
+            Some((module, function)) => {
+                export.id.module.as_str() == module && export.id.function.as_str() == function
+            }
+            None => export.id.function.as_str() == name,
+        }
+    }
+}
@@ -4,6 +4,7 @@ use miden_assembly::Library as CompiledLibrary;
 use miden_core::{utils::Deserializable, Program};
 use miden_processor::Digest;
 
+use super::{rodata::RodataTable, Rodata};
 use crate::MastArtifact;
 
 pub fn deserialize_digest<'de, D>(deserializer: D) -> Result<Digest, D::Error>
@@ -54,3 +55,14 @@ where
     }
     deserializer.deserialize_bytes(MastArtifactVisitor)
 }
+
+pub fn deserialize_rodata<'de, D>(deserializer: D) -> Result<Vec<Rodata>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    RodataTable::deserialize(deserializer)?
+        .into_rodata()
+        .map_err(serde::de::Error::custom)
+}
@@ -1,7 +1,13 @@
+mod account;
 mod de;
 mod package;
+mod rodata;
 mod se;
 #[cfg(test)]
 mod tests;
 
-pub use self::package::{Package, PackageExport, PackageManifest, Rodata};
+pub use self::{
+    account::{AccountComponentMetadata, StorageKind, StorageSlot, MAX_STORAGE_SLOTS},
+    package::{load_link_library, Package, PackageDependency, PackageExport, PackageManifest},
+    rodata::Rodata,
+};
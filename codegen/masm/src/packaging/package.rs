@@ -1,17 +1,28 @@
-use alloc::{collections::BTreeSet, fmt, sync::Arc};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::Arc,
+};
 
+use miden_assembly::Library as CompiledLibrary;
 use miden_processor::Digest;
-use midenc_hir::{formatter::DisplayHex, ConstantData, FunctionIdent, Ident, Signature, Symbol};
-use midenc_session::{diagnostics::Report, Emit, LinkLibrary, Session};
+use midenc_hir::{formatter::DisplayHex, FunctionIdent, Ident, Signature, Symbol};
+use midenc_session::{
+    diagnostics::{Report, Severity, WrapErr},
+    Emit, LibraryKind, LinkLibrary, Session,
+};
 use serde::{Deserialize, Serialize};
 
-use super::{de, se};
+use super::{de, rodata::RodataTable, se, AccountComponentMetadata, Rodata};
 use crate::*;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Deserialize, Clone)]
 pub struct Package {
     /// Name of the package
     pub name: Symbol,
+    /// The version of the package, if one was specified when it was compiled
+    #[serde(default)]
+    pub version: Option<semver::Version>,
     /// Content digest of the package
     #[serde(
         serialize_with = "se::serialize_digest",
@@ -25,6 +36,7 @@ pub struct Package {
     )]
     pub mast: MastArtifact,
     /// The rodata segments required by the code in this package
+    #[serde(deserialize_with = "de::deserialize_rodata")]
     pub rodata: Vec<Rodata>,
     /// The package manifest, containing the set of exported procedures and their signatures,
     /// if known.
@@ -34,6 +46,7 @@ impl fmt::Debug for Package {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Package")
             .field("name", &self.name)
+            .field("version", &self.version)
             .field("digest", &format_args!("{}", DisplayHex::new(&self.digest.as_bytes())))
             .field_with("rodata", |f| f.debug_list().entries(self.rodata.iter()).finish())
             .field("manifest", &self.manifest)
@@ -64,136 +77,274 @@ impl Emit for Package {
             OutputMode::Text => self.mast.write_to(writer, mode, session),
             OutputMode::Binary => {
                 // Write magic
-                writer.write_all(b"MASP\0")?;
+                writer.write_all(Self::MAGIC)?;
                 // Write format version
-                writer.write_all(b"1.0\0")?;
-                let data = bitcode::serialize(self).map_err(std::io::Error::other)?;
+                writer.write_all(Self::FORMAT_VERSION.as_bytes())?;
+                writer.write_all(b"\0")?;
+                let package = SerializePackage {
+                    package: self,
+                    compress_rodata: session.get_flag("compress_rodata"),
+                };
+                let data = bitcode::serialize(&package).map_err(std::io::Error::other)?;
                 writer.write_all(data.as_slice())
             }
         }
     }
 }
 
+impl Serialize for Package {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        SerializePackage {
+            package: self,
+            compress_rodata: false,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Serializes a [Package], optionally compressing its rodata.
+///
+/// The rodata segments of a package are always serialized as a [RodataTable], in which segments
+/// with identical contents share a single copy of those contents.
+struct SerializePackage<'a> {
+    package: &'a Package,
+    compress_rodata: bool,
+}
+impl Serialize for SerializePackage<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let package = self.package;
+        let mut state = serializer.serialize_struct("Package", 6)?;
+        state.serialize_field("name", &package.name)?;
+        state.serialize_field("version", &package.version)?;
+        state.serialize_field("digest", &se::DigestBytes(&package.digest))?;
+        state.serialize_field("mast", &se::MastBytes(&package.mast))?;
+        state
+            .serialize_field("rodata", &RodataTable::new(&package.rodata, self.compress_rodata))?;
+        state.serialize_field("manifest", &package.manifest)?;
+        state.end()
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct PackageManifest {
     /// The set of exports in this package.
     pub exports: BTreeSet<PackageExport>,
     /// The libraries linked against by this package, which must be provided when executing the
-    /// program.
-    pub link_libraries: Vec<LinkLibrary>,
+    /// program, pinned to the exact build of each that the package was compiled against.
+    pub dependencies: Vec<PackageDependency>,
+    /// The messages associated with the error codes of assertions in this package, e.g. the
+    /// message and location of a Rust panic, used to explain a failed assertion to the user.
+    pub error_codes: BTreeMap<u32, String>,
+    /// The account-component metadata of this package, i.e. its storage layout, and which of its
+    /// exports are account interface methods, if it implements a component of an account.
+    pub account_component: Option<AccountComponentMetadata>,
 }
 
+/// A library that a package was linked against
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct PackageExport {
-    pub id: FunctionIdent,
+pub struct PackageDependency {
+    /// The library, i.e. how to locate and load the dependency
+    pub library: LinkLibrary,
+    /// The version of the dependency, if it is a package with a known version
+    #[serde(default)]
+    pub version: Option<semver::Version>,
+    /// The MAST digest of the dependency
     #[serde(
         serialize_with = "se::serialize_digest",
         deserialize_with = "de::deserialize_digest"
     )]
     pub digest: Digest,
-    /// We don't always have a type signature for an export
-    #[serde(default)]
-    pub signature: Option<Signature>,
 }
-impl fmt::Debug for PackageExport {
+impl fmt::Debug for PackageDependency {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("PackageExport")
-            .field("id", &format_args!("{}", self.id.display()))
+        f.debug_struct("PackageDependency")
+            .field("library", &self.library)
+            .field("version", &self.version)
             .field("digest", &format_args!("{}", DisplayHex::new(&self.digest.as_bytes())))
-            .field("signature", &self.signature)
             .finish()
     }
 }
-impl PartialOrd for PackageExport {
-    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        Some(self.cmp(other))
+impl PackageDependency {
+    /// Resolve `library` to the build of it that will be linked against in `session`
+    pub fn resolve(library: &LinkLibrary, session: &Session) -> Result<Self, Report> {
+        let (version, digest) = match library.kind {
+            LibraryKind::Package => {
+                let package = Package::load(library, session)?;
+                (package.version, package.digest)
+            }
+            LibraryKind::Mast | LibraryKind::Masm => (None, *library.load(session)?.digest()),
+        };
+
+        Ok(Self {
+            library: library.clone(),
+            version,
+            digest,
+        })
     }
-}
-impl Ord for PackageExport {
-    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
-        self.id.cmp(&other.id).then_with(|| self.digest.cmp(&other.digest))
+
+    /// The name of this dependency
+    pub fn name(&self) -> &str {
+        self.library.name.as_ref()
+    }
+
+    /// Load this dependency, which must be a package, verifying that it is the same build that
+    /// was originally linked against.
+    ///
+    /// A dependency which was resolved using the local package registry is loaded from the exact
+    /// build that was linked against, if it is still in the registry, rather than the latest
+    /// version matching the original requirement.
+    pub fn load_package(&self, session: &Session) -> Result<Package, Report> {
+        let pinned = match (self.library.version.as_ref(), self.version.as_ref()) {
+            (Some(_), Some(version)) => session
+                .registry()
+                .map(|registry| registry.package_path(self.name(), version, &self.digest))
+                .ok()
+                .filter(|path| path.is_file()),
+            _ => None,
+        };
+        let package = match pinned {
+            Some(path) => Package::read_from_file(&path).map_err(|err| {
+                Report::msg(format!("failed to load package from '{}': {err}", path.display()))
+            })?,
+            None => Package::load(&self.library, session)?,
+        };
+        self.verify(&package.digest, package.version.as_ref(), session)?;
+        Ok(package)
+    }
+
+    /// Load this dependency, which must not be a package, verifying that it is the same build
+    /// that was originally linked against.
+    pub fn load_library(&self, session: &Session) -> Result<CompiledLibrary, Report> {
+        assert_ne!(self.library.kind, LibraryKind::Package, "expected a non-package dependency");
+
+        let library = self.library.load(session)?;
+        self.verify(library.digest(), None, session)?;
+        Ok(library)
+    }
+
+    /// Load the [CompiledLibrary] for this dependency, along with those of its own dependencies,
+    /// if it is a package, verifying each of them along the way.
+    pub fn load(&self, session: &Session) -> Result<Vec<CompiledLibrary>, Report> {
+        match self.library.kind {
+            LibraryKind::Package => self.load_package(session)?.load_link_libraries(session),
+            LibraryKind::Mast | LibraryKind::Masm => Ok(vec![self.load_library(session)?]),
+        }
+    }
+
+    fn verify(
+        &self,
+        digest: &Digest,
+        version: Option<&semver::Version>,
+        session: &Session,
+    ) -> Result<(), Report> {
+        if &self.digest == digest && self.version.as_ref() == version {
+            return Ok(());
+        }
+
+        let describe = |digest: &Digest, version: Option<&semver::Version>| match version {
+            Some(version) => {
+                format!("version {version} (digest {})", DisplayHex::new(&digest.as_bytes()))
+            }
+            None => format!("digest {}", DisplayHex::new(&digest.as_bytes())),
+        };
+        Err(session
+            .diagnostics
+            .diagnostic(Severity::Error)
+            .with_message(format!(
+                "dependency mismatch: '{}' is not the build that was linked against",
+                self.name()
+            ))
+            .with_help(format!(
+                "expected {}, but found {}. Either provide the original build of '{}', or \
+                 recompile against the current one",
+                describe(&self.digest, self.version.as_ref()),
+                describe(digest, version),
+                self.name()
+            ))
+            .into_report())
     }
 }
 
-/// Represents a read-only data segment, combined with its content digest
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Rodata {
-    /// The content digest computed for `data`
+pub struct PackageExport {
+    pub id: FunctionIdent,
     #[serde(
         serialize_with = "se::serialize_digest",
         deserialize_with = "de::deserialize_digest"
     )]
     pub digest: Digest,
-    /// The address at which the data for this segment begins
-    pub start: NativePtr,
-    /// The raw binary data for this segment
-    pub data: Arc<ConstantData>,
+    /// The type signature of the export, as seen by its callers.
+    ///
+    /// This is derived from HIR for compiled code, from the component-level types for functions
+    /// lifted by a component export, and from `@signature` annotations for hand-written Miden
+    /// Assembly. It is `None` for procedures which are not annotated.
+    #[serde(default)]
+    pub signature: Option<Signature>,
 }
-impl fmt::Debug for Rodata {
+impl fmt::Debug for PackageExport {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Rodata")
+        f.debug_struct("PackageExport")
+            .field("id", &format_args!("{}", self.id.display()))
             .field("digest", &format_args!("{}", DisplayHex::new(&self.digest.as_bytes())))
-            .field("start", &self.start)
-            .field_with("data", |f| {
-                f.debug_struct("ConstantData")
-                    .field("len", &self.data.len())
-                    .finish_non_exhaustive()
-            })
+            .field("signature", &self.signature)
             .finish()
     }
 }
-impl Rodata {
-    pub fn size_in_bytes(&self) -> usize {
-        self.data.len()
-    }
-
-    pub fn size_in_felts(&self) -> usize {
-        self.data.len().next_multiple_of(4) / 4
-    }
-
-    pub fn size_in_words(&self) -> usize {
-        self.size_in_felts().next_multiple_of(4) / 4
+impl PartialOrd for PackageExport {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
     }
-
-    /// Attempt to convert this rodata object to its equivalent representation in felts
-    ///
-    /// The resulting felts will be in padded out to the nearest number of words, i.e. if the data
-    /// only takes up 3 felts worth of bytes, then the resulting `Vec` will contain 4 felts, so that
-    /// the total size is a valid number of words.
-    pub fn to_elements(&self) -> Result<Vec<miden_processor::Felt>, String> {
-        use miden_core::FieldElement;
-        use miden_processor::Felt;
-
-        let data = self.data.as_slice();
-        let mut felts = Vec::with_capacity(data.len() / 4);
-        let mut iter = data.iter().copied().array_chunks::<4>();
-        felts.extend(iter.by_ref().map(|bytes| Felt::new(u32::from_le_bytes(bytes) as u64)));
-        if let Some(remainder) = iter.into_remainder() {
-            let mut chunk = [0u8; 4];
-            for (i, byte) in remainder.into_iter().enumerate() {
-                chunk[i] = byte;
-            }
-            felts.push(Felt::new(u32::from_le_bytes(chunk) as u64));
-        }
-
-        let padding = (self.size_in_words() * 4).abs_diff(felts.len());
-        felts.resize(felts.len() + padding, Felt::ZERO);
-
-        Ok(felts)
+}
+impl Ord for PackageExport {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.id.cmp(&other.id).then_with(|| self.digest.cmp(&other.digest))
     }
 }
 
 impl Package {
+    /// The version of the serialized package format, which follows [Package::MAGIC].
+    ///
+    /// This must be changed whenever the serialized form of a package changes.
+    const FORMAT_VERSION: &'static str = "5.0";
+    /// The magic bytes with which a serialized package begins
+    const MAGIC: &'static [u8] = b"MASP\0";
+
     /// Create a [Package] for a [MastArtifact], using the [MasmArtifact] from which it was
     /// assembled, and the [Session] that was used to compile it.
-    pub fn new(mast: MastArtifact, masm: &MasmArtifact, session: &Session) -> Self {
+    ///
+    /// Returns an error if any of the libraries linked against cannot be loaded, or if the
+    /// account-component metadata of the package is invalid.
+    pub fn new(mast: MastArtifact, masm: &MasmArtifact, session: &Session) -> Result<Self, Report> {
         let name = Symbol::intern(session.name());
+        let version = session.options.version.clone();
         let digest = mast.digest();
-        let link_libraries = session.options.link_libraries.clone();
+        let dependencies = session
+            .options
+            .link_libraries
+            .iter()
+            .map(|library| PackageDependency::resolve(library, session))
+            .collect::<Result<Vec<_>, _>>()?;
+        let error_codes = match masm {
+            MasmArtifact::Executable(ref prog) => prog.error_codes(),
+            MasmArtifact::Library(ref lib) => lib.error_codes(),
+        };
         let mut manifest = PackageManifest {
             exports: Default::default(),
-            link_libraries,
+            dependencies,
+            error_codes: error_codes
+                .iter()
+                .map(|(code, message)| (code, message.to_string()))
+                .collect(),
+            account_component: AccountComponentMetadata::from_session(masm, session)?,
         };
 
         // Gater all of the rodata segments for this package
@@ -222,7 +373,9 @@ impl Package {
                         function: Ident::new(Symbol::intern(proc_name), proc_span),
                     };
                     let digest = proc_info.digest;
-                    let signature = masm_function.map(|f| f.signature.clone());
+                    let signature = masm_module
+                        .and_then(|module| module.export_signature(id.function))
+                        .cloned();
                     manifest.exports.insert(PackageExport {
                         id,
                         digest,
@@ -232,13 +385,30 @@ impl Package {
             }
         }
 
-        Self {
+        if let Some(ref account_component) = manifest.account_component {
+            account_component
+                .validate(&manifest.exports)
+                .wrap_err("invalid account-component metadata")?;
+        }
+
+        Ok(Self {
             name,
+            version,
             digest,
             mast,
             rodata,
             manifest,
-        }
+        })
+    }
+
+    /// Load the package requested by `library`, which must be a [LibraryKind::Package]
+    pub fn load(library: &LinkLibrary, session: &Session) -> Result<Self, Report> {
+        assert_eq!(library.kind, LibraryKind::Package, "expected a package link library");
+
+        let path = library.locate(session)?;
+        Self::read_from_file(&path).map_err(|err| {
+            Report::msg(format!("failed to load package from '{}': {err}", path.display()))
+        })
     }
 
     pub fn read_from_file<P>(path: P) -> std::io::Result<Self>
@@ -255,19 +425,23 @@ impl Package {
     where
         B: AsRef<[u8]>,
     {
-        use alloc::borrow::Cow;
-
         let bytes = bytes.as_ref();
 
         let bytes = bytes
-            .strip_prefix(b"MASP\0")
+            .strip_prefix(Self::MAGIC)
             .ok_or_else(|| Report::msg("invalid package: missing header"))?;
-        let bytes = bytes.strip_prefix(b"1.0\0").ok_or_else(|| {
-            Report::msg(format!(
-                "invalid package: incorrect version, expected '1.0', got '{}'",
-                bytes.get(0..4).map(String::from_utf8_lossy).unwrap_or(Cow::Borrowed("")),
-            ))
-        })?;
+        let (version, bytes) = bytes
+            .iter()
+            .position(|b| *b == b'\0')
+            .map(|len| (&bytes[..len], &bytes[(len + 1)..]))
+            .ok_or_else(|| Report::msg("invalid package: missing format version"))?;
+        if version != Self::FORMAT_VERSION.as_bytes() {
+            return Err(Report::msg(format!(
+                "invalid package: unsupported format version '{}', expected '{}'",
+                String::from_utf8_lossy(version),
+                Self::FORMAT_VERSION,
+            )));
+        }
 
         bitcode::deserialize(bytes).map_err(Report::msg)
     }
@@ -294,6 +468,27 @@ impl Package {
         }
     }
 
+    /// Get the [CompiledLibrary] for this package, followed by those of its dependencies,
+    /// transitively, verifying that each of them is the build this package was linked against.
+    pub fn load_link_libraries(&self, session: &Session) -> Result<Vec<CompiledLibrary>, Report> {
+        if !self.is_library() {
+            return Err(Report::msg(format!(
+                "unable to link against package '{}': it is an executable, not a library",
+                &self.name
+            )));
+        }
+
+        let mut libraries = vec![self.unwrap_library().as_ref().clone()];
+        for dependency in self.manifest.dependencies.iter() {
+            let loaded = dependency
+                .load(session)
+                .wrap_err_with(|| format!("invalid dependency of package '{}'", &self.name))?;
+            libraries.extend(loaded);
+        }
+
+        Ok(libraries)
+    }
+
     pub fn make_executable(&self, entrypoint: &FunctionIdent) -> Result<Self, Report> {
         use midenc_session::diagnostics::{SourceSpan, Span};
 
@@ -334,6 +529,7 @@ impl Package {
 
             Ok(Self {
                 name: self.name,
+                version: self.version.clone(),
                 digest,
                 mast: MastArtifact::Executable(Arc::new(miden_core::Program::new(
                     library.mast_forest().clone(),
@@ -342,7 +538,9 @@ impl Package {
                 rodata: self.rodata.clone(),
                 manifest: PackageManifest {
                     exports,
-                    link_libraries: self.manifest.link_libraries.clone(),
+                    dependencies: self.manifest.dependencies.clone(),
+                    error_codes: self.manifest.error_codes.clone(),
+                    account_component: self.manifest.account_component.clone(),
                 },
             })
         } else {
@@ -353,3 +551,17 @@ impl Package {
         }
     }
 }
+
+/// Load the [CompiledLibrary] for `library`.
+///
+/// If `library` is a package, the libraries it was linked against, transitively, are loaded along
+/// with it, as they must also be provided in order to execute code which calls into the package.
+pub fn load_link_library(
+    library: &LinkLibrary,
+    session: &Session,
+) -> Result<Vec<CompiledLibrary>, Report> {
+    match library.kind {
+        LibraryKind::Package => Package::load(library, session)?.load_link_libraries(session),
+        LibraryKind::Mast | LibraryKind::Masm => library.load(session).map(|lib| vec![lib]),
+    }
+}
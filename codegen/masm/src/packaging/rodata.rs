@@ -0,0 +1,222 @@
+use alloc::{collections::BTreeMap, fmt, sync::Arc};
+use std::io::{Read, Write};
+
+use miden_core::crypto::hash::Rpo256;
+use miden_processor::{Digest, Felt};
+use midenc_hir::{formatter::DisplayHex, ConstantData, FieldElement};
+use serde::{Deserialize, Serialize};
+
+use super::{de, se};
+use crate::NativePtr;
+
+inventory::submit! {
+    midenc_session::CompileFlag::new("compress_rodata")
+        .long("compress-rodata")
+        .action(midenc_session::FlagAction::SetTrue)
+        .help(
+            "If present, the contents of rodata segments are stored deflate-compressed in .masp \
+             packages"
+        )
+        .help_heading("Codegen")
+}
+
+/// Represents a read-only data segment, combined with its content digest
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rodata {
+    /// The content digest computed for `data`
+    #[serde(
+        serialize_with = "se::serialize_digest",
+        deserialize_with = "de::deserialize_digest"
+    )]
+    pub digest: Digest,
+    /// The address at which the data for this segment begins
+    pub start: NativePtr,
+    /// The raw binary data for this segment
+    pub data: Arc<ConstantData>,
+}
+impl fmt::Debug for Rodata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rodata")
+            .field("digest", &format_args!("{}", DisplayHex::new(&self.digest.as_bytes())))
+            .field("start", &self.start)
+            .field_with("data", |f| {
+                f.debug_struct("ConstantData")
+                    .field("len", &self.data.len())
+                    .finish_non_exhaustive()
+            })
+            .finish()
+    }
+}
+impl Rodata {
+    /// Create a new [Rodata] segment for `data`, placed at `start`, computing its digest.
+    pub fn new(start: NativePtr, data: Arc<ConstantData>) -> Self {
+        let digest = Rpo256::hash_elements(&bytes_to_elements(data.as_slice()));
+        Self {
+            digest,
+            start,
+            data,
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn size_in_felts(&self) -> usize {
+        self.data.len().next_multiple_of(4) / 4
+    }
+
+    pub fn size_in_words(&self) -> usize {
+        self.size_in_felts().next_multiple_of(4) / 4
+    }
+
+    /// Attempt to convert this rodata object to its equivalent representation in felts
+    ///
+    /// The resulting felts will be in padded out to the nearest number of words, i.e. if the data
+    /// only takes up 3 felts worth of bytes, then the resulting `Vec` will contain 4 felts, so that
+    /// the total size is a valid number of words.
+    pub fn to_elements(&self) -> Result<Vec<Felt>, String> {
+        Ok(bytes_to_elements(self.data.as_slice()))
+    }
+}
+
+/// Convert `data` to field elements, one per 32-bit little-endian chunk, zero-padded to a whole
+/// number of words.
+fn bytes_to_elements(data: &[u8]) -> Vec<Felt> {
+    let num_elements = data.len().next_multiple_of(4) / 4;
+    let mut felts = Vec::with_capacity(num_elements.next_multiple_of(4));
+    let mut iter = data.iter().copied().array_chunks::<4>();
+    felts.extend(iter.by_ref().map(|bytes| Felt::new(u32::from_le_bytes(bytes) as u64)));
+    if let Some(remainder) = iter.into_remainder() {
+        let mut chunk = [0u8; 4];
+        for (i, byte) in remainder.into_iter().enumerate() {
+            chunk[i] = byte;
+        }
+        felts.push(Felt::new(u32::from_le_bytes(chunk) as u64));
+    }
+    felts.resize(num_elements.next_multiple_of(4), Felt::ZERO);
+    felts
+}
+
+/// The serialized form of the rodata segments of a package.
+///
+/// The contents of each segment are stored once in `data`, no matter how many segments share them,
+/// and may be compressed.
+#[derive(Serialize, Deserialize)]
+pub(super) struct RodataTable {
+    /// The distinct contents of the segments in this table
+    data: Vec<RodataContents>,
+    /// The segments in this table, in address order
+    segments: Vec<RodataSegment>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RodataSegment {
+    #[serde(
+        serialize_with = "se::serialize_digest",
+        deserialize_with = "de::deserialize_digest"
+    )]
+    digest: Digest,
+    start: NativePtr,
+    /// The index of the contents of this segment in [RodataTable::data]
+    data: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum RodataContents {
+    /// The raw bytes of the segment
+    Raw(#[serde(with = "serde_bytes")] Vec<u8>),
+    /// The bytes of the segment, compressed using deflate
+    Deflate {
+        /// The size, in bytes, of the decompressed contents
+        size: u32,
+        #[serde(with = "serde_bytes")]
+        bytes: Vec<u8>,
+    },
+}
+impl RodataContents {
+    fn new(data: &[u8], compress: bool) -> Self {
+        if compress {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+            let compressed = encoder.write_all(data).and_then(|_| encoder.finish());
+            // Only store the compressed form if it actually saves space
+            if let Some(bytes) = compressed.ok().filter(|bytes| bytes.len() < data.len()) {
+                return Self::Deflate {
+                    size: data.len() as u32,
+                    bytes,
+                };
+            }
+        }
+        Self::Raw(data.to_vec())
+    }
+
+    fn decode(self) -> Result<ConstantData, String> {
+        match self {
+            Self::Raw(bytes) => Ok(ConstantData::from(bytes)),
+            Self::Deflate { size, bytes } => {
+                let mut data = Vec::with_capacity(size as usize);
+                flate2::read::DeflateDecoder::new(bytes.as_slice())
+                    .take(size as u64 + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|err| format!("failed to decompress rodata: {err}"))?;
+                if data.len() != size as usize {
+                    return Err(format!(
+                        "expected {size} bytes of decompressed rodata, got {}",
+                        data.len()
+                    ));
+                }
+                Ok(ConstantData::from(data))
+            }
+        }
+    }
+}
+
+impl RodataTable {
+    /// Build the table for `rodata`, compressing the contents of each segment if `compress` is set
+    pub fn new(rodata: &[Rodata], compress: bool) -> Self {
+        let mut data = vec![];
+        let mut indices = BTreeMap::<&[u8], u32>::default();
+        let segments = rodata
+            .iter()
+            .map(|rodata| {
+                let index = *indices.entry(rodata.data.as_slice()).or_insert_with(|| {
+                    data.push(RodataContents::new(rodata.data.as_slice(), compress));
+                    (data.len() - 1) as u32
+                });
+                RodataSegment {
+                    digest: rodata.digest,
+                    start: rodata.start,
+                    data: index,
+                }
+            })
+            .collect();
+
+        Self { data, segments }
+    }
+
+    /// Decode the segments of this table, verifying that the contents match their digests
+    pub fn into_rodata(self) -> Result<Vec<Rodata>, String> {
+        let data = self
+            .data
+            .into_iter()
+            .map(|contents| contents.decode().map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.segments
+            .into_iter()
+            .map(|segment| {
+                let contents = data.get(segment.data as usize).ok_or_else(|| {
+                    format!("rodata segment refers to undefined contents {}", segment.data)
+                })?;
+                let rodata = Rodata::new(segment.start, Arc::clone(contents));
+                if rodata.digest != segment.digest {
+                    return Err(format!(
+                        "rodata segment at {:#x} does not match its digest",
+                        segment.start.as_ptr()
+                    ));
+                }
+                Ok(rodata)
+            })
+            .collect()
+    }
+}
@@ -28,3 +28,26 @@ where
 
     serde_bytes::serialize(&buffer, serializer)
 }
+
+/// Serializes a [Digest] as [serialize_digest] does, for use with manual [serde::Serialize] impls
+pub struct DigestBytes<'a>(pub &'a Digest);
+impl serde::Serialize for DigestBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_digest(self.0, serializer)
+    }
+}
+
+/// Serializes a [MastArtifact] as [serialize_mast] does, for use with manual [serde::Serialize]
+/// impls
+pub struct MastBytes<'a>(pub &'a MastArtifact);
+impl serde::Serialize for MastBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_mast(self.0, serializer)
+    }
+}
@@ -5,12 +5,12 @@ use midenc_hir::{diagnostics::IntoDiagnostic, testing::TestContext, StructType,
 use midenc_session::{diagnostics::Report, Emit};
 
 use super::*;
-use crate::{MasmArtifact, NativePtr};
+use crate::{Module, NativePtr};
 
 #[test]
 fn packaging_serialization() -> Result<(), Report> {
     let context = TestContext::default_with_emitter(None);
-    let package = example_package(&context)?;
+    let package = example_package(&context, true)?;
 
     bitcode::serialize(package.as_ref()).map_err(Report::msg)?;
 
@@ -20,7 +20,7 @@ fn packaging_serialization() -> Result<(), Report> {
 #[test]
 fn packaging_deserialization() -> Result<(), Report> {
     let context = TestContext::default_with_emitter(None);
-    let expected = example_package(&context)?;
+    let expected = example_package(&context, true)?;
 
     let mut bytes = vec![];
     expected
@@ -30,6 +30,7 @@ fn packaging_deserialization() -> Result<(), Report> {
     let package = Package::read_from_bytes(bytes)?;
 
     assert_eq!(package.name, expected.name);
+    assert_eq!(package.version, expected.version);
     assert_eq!(package.digest, expected.digest);
     assert_eq!(package.rodata, expected.rodata);
     assert_eq!(package.manifest, expected.manifest);
@@ -87,7 +88,467 @@ fn packaging_deserialization() -> Result<(), Report> {
     Ok(())
 }
 
-fn example_package(context: &TestContext) -> Result<Arc<Package>, Report> {
+#[test]
+fn packaging_load_link_library() -> Result<(), Report> {
+    use midenc_session::{LibraryKind, LinkLibrary};
+
+    let context = TestContext::default_with_emitter(None);
+    let expected = example_package(&context, false)?;
+    assert!(expected.is_library());
+
+    let path = std::env::temp_dir()
+        .join(format!("midenc-packaging-load-link-library-{}.masp", std::process::id()));
+    let mut bytes = vec![];
+    expected
+        .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+        .into_diagnostic()?;
+    std::fs::write(&path, bytes).into_diagnostic()?;
+
+    let link_library = LinkLibrary {
+        name: "test".into(),
+        path: Some(path.clone()),
+        kind: LibraryKind::Package,
+        version: None,
+    };
+    let package = Package::load(&link_library, &context.session);
+    let libraries = load_link_library(&link_library, &context.session);
+    std::fs::remove_file(&path).into_diagnostic()?;
+
+    // The exports of the package, and their signatures, are available to the linker
+    let package = package?;
+    assert_eq!(package.manifest, expected.manifest);
+    let fib = package
+        .manifest
+        .exports
+        .iter()
+        .find(|export| export.id == "test::fib".parse().unwrap())
+        .expect("expected 'test::fib' to be exported");
+    assert!(fib.signature.is_some());
+
+    // The package was not linked against anything, so it is the only library that is loaded
+    let libraries = libraries?;
+    assert_eq!(libraries.len(), 1);
+    assert_eq!(libraries[0].digest(), expected.unwrap_library().digest());
+
+    Ok(())
+}
+
+#[test]
+fn packaging_dependency_verification() -> Result<(), Report> {
+    use midenc_session::{LibraryKind, LinkLibrary};
+
+    let context = TestContext::default_with_emitter(None);
+    let mut dependency = example_package(&context, false)?.as_ref().clone();
+    dependency.version = Some(semver::Version::new(1, 2, 3));
+
+    let mut bytes = vec![];
+    dependency
+        .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+        .into_diagnostic()?;
+
+    // Packages written in a different format are rejected
+    let mut old_format = bytes.clone();
+    old_format[5..8].copy_from_slice(b"1.0");
+    let Err(err) = Package::read_from_bytes(old_format) else {
+        panic!("expected package in an unsupported format to be rejected");
+    };
+    assert_eq!(
+        err.to_string(),
+        "invalid package: unsupported format version '1.0', expected '5.0'"
+    );
+
+    let path = std::env::temp_dir()
+        .join(format!("midenc-packaging-dependency-verification-{}.masp", std::process::id()));
+    std::fs::write(&path, bytes).into_diagnostic()?;
+
+    let link_library = LinkLibrary {
+        name: "dependency".into(),
+        path: Some(path.clone()),
+        kind: LibraryKind::Package,
+        version: None,
+    };
+    let result = verify_dependency(&link_library, &dependency, &context);
+    std::fs::remove_file(&path).into_diagnostic()?;
+    result
+}
+
+#[test]
+fn packaging_export_signatures() -> Result<(), Report> {
+    use miden_assembly::{ast::ModuleKind, LibraryPath};
+    use midenc_hir::{AbiParam, FunctionIdent, ProgramBuilder, Signature};
+
+    let context = TestContext::default_with_emitter(None);
+
+    let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+    let mut mb = builder.module("test");
+    midenc_hir::testing::fib1(mb.as_mut(), &context);
+    mb.build().expect("unexpected error constructing test module");
+    let mut program = builder.link().expect("failed to link program");
+    program.add_library(StdLibrary::default().into());
+
+    let mut compiler = crate::MasmCompiler::new(&context.session);
+    let mut masm_artifact = compiler.compile(program).expect("compilation failed");
+
+    // Hand-written Miden Assembly, where only some of the procedures are annotated
+    let source = context.session.source_manager.load(
+        "test/text.masm",
+        "#! A test module\n\n#! Adds `b` to `a`\n#!\n#! @signature (func (param u32) (param u32) \
+         (result u32))\nexport.add\n    u32wrapping_add\nend\n\n#! Not \
+         annotated\nexport.unannotated\n    drop\nend\n"
+            .to_string(),
+    );
+    let path = LibraryPath::new("test::text").unwrap();
+    let module = Module::parse(ModuleKind::Library, path, source)?;
+    masm_artifact.insert(Box::new(module));
+
+    let mast_artifact = masm_artifact.assemble(&context.session)?;
+    let package = Package::new(mast_artifact, &masm_artifact, &context.session)?;
+
+    let signature = |name: &str| {
+        let id = name.parse::<FunctionIdent>().unwrap();
+        let export = package.manifest.exports.iter().find(|export| export.id == id);
+        export.unwrap_or_else(|| panic!("missing export '{name}'")).signature.clone()
+    };
+    let fib = Signature::new([AbiParam::new(Type::U32)], [AbiParam::new(Type::U32)]);
+    assert_eq!(signature("test::fib"), Some(fib));
+    let u32_add = Signature::new(
+        [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+        [AbiParam::new(Type::U32)],
+    );
+    assert_eq!(signature("test::text::add"), Some(u32_add));
+    assert_eq!(signature("test::text::unannotated"), None);
+    // The intrinsics we ship are annotated
+    let i32_add = Signature::new(
+        [AbiParam::new(Type::I32), AbiParam::new(Type::I32)],
+        [AbiParam::new(Type::I32)],
+    );
+    assert_eq!(signature("intrinsics::i32::checked_add"), Some(i32_add));
+
+    Ok(())
+}
+
+#[test]
+fn packaging_invalid_signature_annotation() {
+    use miden_assembly::{ast::ModuleKind, LibraryPath};
+
+    let context = TestContext::default_with_emitter(None);
+    let source = context.session.source_manager.load(
+        "test/text.masm",
+        "#! A test module\n\n#! @signature (func (result u32) (param u32))\nexport.bad\n    \
+         drop\nend\n"
+            .to_string(),
+    );
+    let path = LibraryPath::new("test::text").unwrap();
+    let Err(err) = Module::parse(ModuleKind::Library, path, source) else {
+        panic!("expected invalid signature annotation to be rejected");
+    };
+    assert!(err.to_string().contains("invalid signature annotation for 'test::text::bad'"));
+}
+
+#[test]
+fn packaging_rodata_coalescing() -> Result<(), Report> {
+    use midenc_hir::ProgramBuilder;
+
+    let context = TestContext::default_with_emitter(None);
+
+    let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+    let mut mb = builder.module("test");
+    midenc_hir::testing::fib1(mb.as_mut(), &context);
+    let base = 65536 * 4;
+    let string = b"hello".to_vec();
+    mb.declare_data_segment(base, 5, string.clone(), true)?;
+    // Starts in the word following the end of the previous segment
+    mb.declare_data_segment(base + 16, 4, vec![1u8, 2, 3, 4], true)?;
+    // Not adjacent to any other segment, but with identical contents
+    mb.declare_data_segment(base + 64, 5, string.clone(), true)?;
+    mb.declare_data_segment(base + 128, 5, string.clone(), true)?;
+    mb.build().expect("unexpected error constructing test module");
+    let mut program = builder.link().expect("failed to link program");
+    program.add_library(StdLibrary::default().into());
+
+    let mut compiler = crate::MasmCompiler::new(&context.session);
+    let masm_artifact = compiler.compile(program).expect("compilation failed");
+    let mast_artifact = masm_artifact.assemble(&context.session)?;
+    let package = Package::new(mast_artifact, &masm_artifact, &context.session)?;
+
+    let starts = package.rodata.iter().map(|rodata| rodata.start).collect::<Vec<_>>();
+    assert_eq!(
+        starts,
+        vec![
+            NativePtr::from_ptr(base),
+            NativePtr::from_ptr(base + 64),
+            NativePtr::from_ptr(base + 128)
+        ]
+    );
+    let mut coalesced = string.clone();
+    coalesced.resize(16, 0);
+    coalesced.extend_from_slice(&[1, 2, 3, 4]);
+    assert_eq!(package.rodata[0].data.as_slice(), coalesced.as_slice());
+    assert_eq!(package.rodata[0].size_in_words(), 2);
+    assert_eq!(package.rodata[1].data.as_slice(), string.as_slice());
+    assert_eq!(package.rodata[1].digest, package.rodata[2].digest);
+
+    // Identical segments share their contents once loaded
+    let mut bytes = vec![];
+    package
+        .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+        .into_diagnostic()?;
+    let loaded = Package::read_from_bytes(bytes)?;
+    assert_eq!(loaded.rodata, package.rodata);
+    assert!(Arc::ptr_eq(&loaded.rodata[1].data, &loaded.rodata[2].data));
+
+    Ok(())
+}
+
+#[test]
+fn packaging_rodata_compression() -> Result<(), Report> {
+    use midenc_hir::ConstantData;
+    use midenc_session::CompileFlags;
+
+    let mut context = TestContext::default_with_emitter(None);
+    let mut package = example_package(&context, true)?.as_ref().clone();
+    let data = Arc::new(ConstantData::from(b"abcdefgh".repeat(512)));
+    package
+        .rodata
+        .push(Rodata::new(NativePtr::from_ptr(65536 * 8), Arc::clone(&data)));
+
+    let write = |package: &Package, context: &TestContext| -> Result<Vec<u8>, Report> {
+        let mut bytes = vec![];
+        package
+            .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+            .into_diagnostic()?;
+        Ok(bytes)
+    };
+
+    // Duplicate segments are only stored once
+    let raw = write(&package, &context)?;
+    let mut duplicated = package.clone();
+    duplicated
+        .rodata
+        .push(Rodata::new(NativePtr::from_ptr(65536 * 12), Arc::clone(&data)));
+    let raw_duplicated = write(&duplicated, &context)?;
+    assert!(raw_duplicated.len() < raw.len() + data.len());
+
+    context
+        .session
+        .options
+        .set_extra_flags(CompileFlags::new(["--compress-rodata"])?);
+    let compressed = write(&duplicated, &context)?;
+    assert!(compressed.len() + data.len() / 2 < raw_duplicated.len());
+    let loaded = Package::read_from_bytes(compressed)?;
+    assert_eq!(loaded.rodata, duplicated.rodata);
+
+    // Segments whose contents do not match their digest are rejected
+    let mut corrupted = package;
+    corrupted.rodata.last_mut().unwrap().digest = corrupted.digest;
+    let Err(err) = Package::read_from_bytes(write(&corrupted, &context)?) else {
+        panic!("expected rodata with an invalid digest to be rejected");
+    };
+    assert!(err.to_string().contains("does not match its digest"), "unexpected error: {err}");
+
+    Ok(())
+}
+
+#[test]
+fn packaging_local_registry() -> Result<(), Report> {
+    use midenc_session::{LibraryKind, LinkLibrary};
+
+    let root = std::env::temp_dir()
+        .join(format!("midenc-packaging-local-registry-{}", std::process::id()));
+    let mut context = TestContext::default_with_emitter(None);
+    context.session.options.registry = Some(root.clone());
+    let result = (|| {
+        let registry = context.session.registry()?;
+        let package = example_package(&context, false)?;
+        let name = package.name.as_str();
+
+        // Publish two versions of the package
+        let mut paths = vec![];
+        for version in [semver::Version::new(1, 2, 3), semver::Version::new(1, 3, 0)] {
+            let mut package = package.as_ref().clone();
+            package.version = Some(version.clone());
+            let mut bytes = vec![];
+            package
+                .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+                .into_diagnostic()?;
+            paths.push(registry.publish(name, &version, &package.digest, &bytes)?);
+
+            // Publishing the same build again has no effect, but a different build is rejected
+            assert_eq!(
+                registry.publish(name, &version, &package.digest, &bytes)?,
+                *paths.last().unwrap()
+            );
+            let Err(err) = registry.publish(name, &version, &Default::default(), &bytes) else {
+                panic!("expected a different build of a published version to be rejected");
+            };
+            assert!(
+                err.to_string().contains("has already been published"),
+                "unexpected error: {err}"
+            );
+        }
+
+        // Packages are resolved to the latest version matching the requirement
+        let link_library = |requirement: &str| LinkLibrary {
+            name: name.to_string().into(),
+            path: None,
+            kind: LibraryKind::Package,
+            version: Some(requirement.parse().unwrap()),
+        };
+        assert_eq!(link_library("~1.2").locate(&context.session)?, paths[0]);
+        assert_eq!(link_library("^1.2").locate(&context.session)?, paths[1]);
+        assert!(link_library("^2").locate(&context.session).is_err());
+
+        // Dependencies are pinned to the version they were resolved to
+        let dependency = PackageDependency::resolve(&link_library("~1.2"), &context.session)?;
+        assert_eq!(dependency.version, Some(semver::Version::new(1, 2, 3)));
+        assert_eq!(dependency.load_package(&context.session)?.version, dependency.version);
+
+        Ok(())
+    })();
+    std::fs::remove_dir_all(&root).into_diagnostic()?;
+    result
+}
+
+#[test]
+fn packaging_reproducible() -> Result<(), Report> {
+    let write = |package: &Package, context: &TestContext| -> Result<Vec<u8>, Report> {
+        let mut bytes = vec![];
+        package
+            .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+            .into_diagnostic()?;
+        Ok(bytes)
+    };
+
+    // Building the same program in two separate sessions produces an identical package
+    let context = TestContext::default_with_emitter(None);
+    let package = example_package(&context, true)?;
+    let rebuilt_context = TestContext::default_with_emitter(None);
+    let rebuilt = example_package(&rebuilt_context, true)?;
+    assert_eq!(package.digest, rebuilt.digest);
+    assert_eq!(write(&package, &context)?, write(&rebuilt, &rebuilt_context)?);
+
+    Ok(())
+}
+
+#[test]
+fn packaging_account_component() -> Result<(), Report> {
+    use midenc_session::CompileFlags;
+
+    let path = std::env::temp_dir()
+        .join(format!("midenc-packaging-account-component-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+name = "counter"
+interface = ["fib"]
+
+[[storage]]
+name = "count"
+slot = 0
+size = 2
+
+[[storage]]
+name = "history"
+slot = 2
+kind = "map"
+"#,
+    )
+    .into_diagnostic()?;
+    let mut context = TestContext::default_with_emitter(None);
+    context
+        .session
+        .options
+        .set_extra_flags(CompileFlags::new(["--account-component", path.to_str().unwrap()])?);
+    let result = example_package(&context, false);
+    std::fs::remove_file(&path).into_diagnostic()?;
+    let package = result?;
+
+    // The metadata is stored in the manifest, and survives serialization
+    let metadata = package.manifest.account_component.as_ref().expect("expected metadata");
+    assert_eq!(metadata.name, "counter");
+    assert_eq!(metadata.storage[0].indices(), 0..2);
+    assert_eq!(metadata.storage[1].kind, StorageKind::Map);
+    let mut bytes = vec![];
+    package
+        .write_to(&mut bytes, midenc_session::OutputMode::Binary, &context.session)
+        .into_diagnostic()?;
+    assert_eq!(Package::read_from_bytes(bytes)?.manifest, package.manifest);
+
+    // Interface methods may be named with or without their module
+    let fib = package
+        .manifest
+        .exports
+        .iter()
+        .find(|export| export.id.function.as_str() == "fib")
+        .unwrap();
+    assert!(metadata.is_interface_method(fib));
+    let qualified = AccountComponentMetadata::parse("name = 'counter'\ninterface = ['test::fib']")?;
+    assert!(qualified.is_interface_method(fib));
+    qualified.validate(&package.manifest.exports)?;
+
+    // Invalid metadata is rejected
+    let validate = |toml: &str| {
+        AccountComponentMetadata::parse(&format!("name = 'counter'\n{toml}"))?
+            .validate(&package.manifest.exports)
+    };
+    for (toml, expected) in [
+        (
+            "storage = [{ name = 'a', slot = 1, size = 2 }, { name = 'b', slot = 2 }]",
+            "storage slot 'a' (slots 1..3) overlaps storage slot 'b' (slot 2)",
+        ),
+        (
+            "storage = [{ name = 'a', slot = 1 }, { name = 'a', slot = 2 }]",
+            "storage slot 'a' is declared more than once",
+        ),
+        (
+            "storage = [{ name = 'a', slot = 254, size = 2 }]",
+            "storage slot 'a' (slots 254..256) is out of bounds: accounts have 255 storage slots",
+        ),
+        (
+            "storage = [{ name = 'a', slot = 0, size = 2, kind = 'map' }]",
+            "storage slot 'a' (slots 0..2) is a map, so it must occupy exactly one slot",
+        ),
+        (
+            "interface = ['other::fib']",
+            "interface method 'other::fib' is not exported by the package",
+        ),
+    ] {
+        let Err(err) = validate(toml) else {
+            panic!("expected invalid metadata to be rejected: {toml}");
+        };
+        assert_eq!(err.to_string(), expected);
+    }
+
+    Ok(())
+}
+
+fn verify_dependency(
+    link_library: &midenc_session::LinkLibrary,
+    dependency: &Package,
+    context: &TestContext,
+) -> Result<(), Report> {
+    // The dependency is pinned to the version and digest of the package that was loaded
+    let resolved = PackageDependency::resolve(link_library, &context.session)?;
+    assert_eq!(resolved.name(), "dependency");
+    assert_eq!(resolved.version, dependency.version);
+    assert_eq!(resolved.digest, dependency.digest);
+    assert_eq!(resolved.load(&context.session)?.len(), 1);
+
+    // A dependency that does not match the package that was originally linked against is rejected
+    let mut stale = resolved;
+    stale.version = Some(semver::Version::new(1, 2, 2));
+    let Err(err) = stale.load_package(&context.session) else {
+        panic!("expected mismatched dependency to be rejected");
+    };
+    assert_eq!(
+        err.to_string(),
+        "dependency mismatch: 'dependency' is not the build that was linked against"
+    );
+
+    Ok(())
+}
+
+fn example_package(context: &TestContext, executable: bool) -> Result<Arc<Package>, Report> {
     use midenc_hir::ProgramBuilder;
 
     // Build a simple program
@@ -118,21 +579,20 @@ fn example_package(context: &TestContext) -> Result<Arc<Package>, Report> {
     mb.build().expect("unexpected error constructing test module");
 
     // Link the program
-    let mut program = builder
-        .with_entrypoint("test::fib".parse().unwrap())
-        .link()
-        .expect("failed to link program");
+    if executable {
+        builder = builder.with_entrypoint("test::fib".parse().unwrap());
+    }
+    let mut program = builder.link().expect("failed to link program");
 
     program.add_library(StdLibrary::default().into());
 
     // Compile the program
     let mut compiler = crate::MasmCompiler::new(&context.session);
-    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+    let masm_artifact = compiler.compile(program).expect("compilation failed");
 
     // Assemble the program
-    let masm_artifact = MasmArtifact::Executable(program);
     let mast_artifact = masm_artifact.assemble(&context.session)?;
 
     // Package the program
-    Ok(Arc::new(Package::new(mast_artifact, &masm_artifact, &context.session)))
+    Package::new(mast_artifact, &masm_artifact, &context.session).map(Arc::new)
 }
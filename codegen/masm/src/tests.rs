@@ -1,12 +1,16 @@
 #![allow(unused_imports)]
 use std::sync::Arc;
 
+use miden_core::crypto::{
+    hash::{Rpo256, RpoDigest},
+    merkle::{MerkleStore, MerkleTree, NodeIndex},
+};
 use midenc_hir::{
     self as hir,
     pass::{AnalysisManager, ConversionPass},
     testing::{self, TestContext},
     AbiParam, CallConv, Felt, FieldElement, FunctionIdent, Immediate, InstBuilder, Linkage,
-    OperandStack, ProgramBuilder, Signature, SourceSpan, Stack, Type,
+    OperandStack, ProgramBuilder, Signature, SourceSpan, Stack, StructType, Type,
 };
 use prop::test_runner::{Config, TestRunner};
 use proptest::prelude::*;
@@ -355,7 +359,7 @@ fn codegen_fundamental_if() {
 
     let mut stack = harness.execute_program(program.freeze(), &[a, b]).expect("execution failed");
     assert_eq!(stack.len(), 1);
-    assert_eq!(stack.pop().map(|e| e.as_int()), Some(12));
+    assert_eq!(stack.pop().map(|e| e.as_int()), Some(7));
 }
 
 /// Test the code generator on a very simple program with a loop as a sanity check
@@ -424,6 +428,615 @@ fn codegen_fundamental_loops() {
     assert_eq!(stack.pop().map(|e| e.as_int()), Some(7));
 }
 
+/// Build a function which computes `b * n` by repeated addition, using a loop whose block
+/// parameters are not all updated on each iteration
+fn mul_by_addition(mb: &mut hir::ModuleBuilder, context: &TestContext) -> FunctionIdent {
+    let mut fb = mb
+        .function(
+            "mul_by_addition",
+            Signature::new(
+                [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                [AbiParam::new(Type::U32)],
+            ),
+        )
+        .expect("unexpected symbol conflict");
+    let entry = fb.current_block();
+    let (b, n) = {
+        let args = fb.block_params(entry);
+        (args[0], args[1])
+    };
+    let loop_header_blk = fb.create_block();
+    let n1 = fb.append_block_param(loop_header_blk, Type::U32, SourceSpan::UNKNOWN);
+    let b1 = fb.append_block_param(loop_header_blk, Type::U32, SourceSpan::UNKNOWN);
+    let sum1 = fb.append_block_param(loop_header_blk, Type::U32, SourceSpan::UNKNOWN);
+    let loop_body_blk = fb.create_block();
+    let loop_exit_blk = fb.create_block();
+    let result0 = fb.append_block_param(loop_exit_blk, Type::U32, SourceSpan::UNKNOWN);
+    let zero = fb.ins().u32(0, SourceSpan::UNKNOWN);
+    fb.ins().br(loop_header_blk, &[n, b, zero], SourceSpan::UNKNOWN);
+
+    fb.switch_to_block(loop_header_blk);
+    let is_zero = fb.ins().eq_imm(n1, Immediate::U32(0), SourceSpan::UNKNOWN);
+    fb.ins()
+        .cond_br(is_zero, loop_exit_blk, &[sum1], loop_body_blk, &[], SourceSpan::UNKNOWN);
+
+    fb.switch_to_block(loop_body_blk);
+    let n2 = fb.ins().sub_imm_checked(n1, Immediate::U32(1), SourceSpan::UNKNOWN);
+    let sum2 = fb.ins().add_checked(sum1, b1, SourceSpan::UNKNOWN);
+    fb.ins().br(loop_header_blk, &[n2, b1, sum2], SourceSpan::UNKNOWN);
+
+    fb.switch_to_block(loop_exit_blk);
+    fb.ins().ret(Some(result0), SourceSpan::UNKNOWN);
+
+    fb.build(&context.session.diagnostics)
+        .expect("unexpected error building function")
+}
+
+/// Test that the block arguments of a loop header are laid out on the operand stack in the order
+/// they are produced along the loopback edge, and that the resulting code is still correct
+#[test]
+fn codegen_loop_stack_layout() {
+    use midenc_hir_analysis::{DominatorTree, LivenessAnalysis, LoopAnalysis};
+
+    let mut harness = TestByEmulationHarness::default();
+
+    // Check the layout planned for the loop header
+    let mut mb = hir::ModuleBuilder::new("test");
+    let id = mul_by_addition(&mut mb, &harness.context);
+    let module = mb.build();
+    let f = module.function(id.function).unwrap();
+    let session = &harness.context.session;
+    let mut analyses = AnalysisManager::new();
+    let domtree = analyses.get_or_compute::<DominatorTree>(f, session).unwrap();
+    let loops = analyses.get_or_compute::<LoopAnalysis>(f, session).unwrap();
+    let liveness = analyses.get_or_compute::<LivenessAnalysis>(f, session).unwrap();
+    let mut f_prime = masm::Function::new(f.id, f.signature.clone());
+    let schedule = codegen::Scheduler::new(f, &mut f_prime, &domtree, &loops, &liveness).build();
+    let loop_header_blk = loops.loop_header(loops.loops().next().unwrap());
+    // The loop-invariant `b` is expected at the bottom, below the updated sum and counter
+    assert_eq!(schedule.stack_layout.get(loop_header_blk), Some([0, 2, 1].as_slice()));
+
+    // Check that the program computes the expected result
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+    let id = mul_by_addition(mb.as_mut(), &harness.context);
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+
+    let b = Felt::new(3);
+    let n = Felt::new(4);
+    let mut stack = harness.execute_program(program.freeze(), &[b, n]).expect("execution failed");
+    assert_eq!(stack.len(), 1);
+    assert_eq!(stack.pop().map(|e| e.as_int()), Some(3 * 4));
+}
+
+/// Test the code generator on a `switch` with a mix of single-value and range cases, which is
+/// lowered to a decision tree
+#[test]
+fn codegen_switch() {
+    let mut harness = TestByEmulationHarness::default();
+
+    // Build a simple program
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function(
+                "classify",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let entry = fb.current_block();
+        let (selector, y) = {
+            let args = fb.block_params(entry);
+            (args[0], args[1])
+        };
+        let identity_blk = fb.create_block();
+        let y1 = fb.append_block_param(identity_blk, Type::U32, SourceSpan::UNKNOWN);
+        let double_blk = fb.create_block();
+        let y2 = fb.append_block_param(double_blk, Type::U32, SourceSpan::UNKNOWN);
+        let seven_blk = fb.create_block();
+        let default_blk = fb.create_block();
+        fb.ins()
+            .switch(selector, SourceSpan::UNKNOWN)
+            .case(0, identity_blk, &[y])
+            .case(1, identity_blk, &[y])
+            .case(2, identity_blk, &[y])
+            .case(5, double_blk, &[y])
+            .case(7, seven_blk, &[])
+            .case(10, default_blk, &[])
+            .case(100, double_blk, &[y])
+            .case(101, double_blk, &[y])
+            .case(102, double_blk, &[y])
+            .case(103, double_blk, &[y])
+            .case(104, identity_blk, &[y])
+            .case(u32::MAX, seven_blk, &[])
+            .or_else(default_blk, &[]);
+
+        fb.switch_to_block(identity_blk);
+        fb.ins().ret(Some(y1), SourceSpan::UNKNOWN);
+        fb.switch_to_block(double_blk);
+        let doubled = fb.ins().mul_imm_checked(y2, Immediate::U32(2), SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(doubled), SourceSpan::UNKNOWN);
+        fb.switch_to_block(seven_blk);
+        fb.ins().ret_imm(Immediate::U32(7), SourceSpan::UNKNOWN);
+        fb.switch_to_block(default_blk);
+        fb.ins().ret_imm(Immediate::U32(0), SourceSpan::UNKNOWN);
+
+        fb.build().expect("unexpected error building function")
+    };
+
+    mb.build().expect("unexpected error constructing test module");
+
+    // Link the program
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+    let program = program.freeze();
+
+    let classify = |selector: u32, y: u32| match selector {
+        0..=2 | 104 => y,
+        5 | 100..=103 => y * 2,
+        7 | u32::MAX => 7,
+        _ => 0,
+    };
+    let y = 3;
+    for selector in (0..=12).chain(98..=106).chain([u32::MAX - 1, u32::MAX]) {
+        let args = [Felt::new(selector as u64), Felt::new(y as u64)];
+        let mut stack = harness.execute_program(program.clone(), &args).expect("execution failed");
+        assert_eq!(stack.len(), 1);
+        assert_eq!(
+            stack.pop().map(|e| e.as_int()),
+            Some(classify(selector, y) as u64),
+            "unexpected result for selector {selector}"
+        );
+        harness.reset();
+    }
+}
+
+/// Test that calls to functions whose parameters require more than 16 elements of the operand
+/// stack are lowered by passing the excess arguments in memory
+#[test]
+fn codegen_wide_signature() {
+    let mut harness = TestByEmulationHarness::default();
+    let span = SourceSpan::UNKNOWN;
+    let word = Type::Array(Box::new(Type::Felt), 4);
+
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+
+    // Sum 20 integers, the last 4 of which are passed in memory
+    let sum_sig = Signature {
+        linkage: Linkage::Internal,
+        ..Signature::new((0..20).map(|_| AbiParam::new(Type::U32)), [AbiParam::new(Type::U32)])
+    };
+    let sum = {
+        let mut fb = mb.function("sum", sum_sig.clone()).expect("unexpected symbol conflict");
+        let entry = fb.current_block();
+        let params = fb.block_params(entry).to_vec();
+        let mut acc = params[0];
+        for param in params[1..].iter().copied() {
+            acc = fb.ins().add_checked(acc, param, span);
+        }
+        fb.ins().ret(Some(acc), span);
+        fb.build().expect("unexpected error building function")
+    };
+
+    // Return the second of two words, where the second word, and the integer following it, are
+    // passed in memory
+    let pick_sig = Signature {
+        linkage: Linkage::Internal,
+        ..Signature::new(
+            (0..10)
+                .map(|_| AbiParam::new(Type::U32))
+                .chain([AbiParam::new(word.clone()), AbiParam::new(word.clone())])
+                .chain([AbiParam::new(Type::U32)]),
+            [AbiParam::new(word.clone())],
+        )
+    };
+    let pick = {
+        let mut fb = mb.function("pick", pick_sig.clone()).expect("unexpected symbol conflict");
+        let entry = fb.current_block();
+        let params = fb.block_params(entry).to_vec();
+        fb.ins().assert_eq_imm(Immediate::U32(11), params[12], span);
+        fb.ins().ret(Some(params[11]), span);
+        fb.build().expect("unexpected error building function")
+    };
+
+    let main = {
+        let mut fb = mb
+            .function(
+                "main",
+                Signature::new(
+                    [AbiParam::new(word.clone()), AbiParam::new(word.clone())],
+                    [AbiParam::new(word.clone())],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let entry = fb.current_block();
+        let (w0, w1) = {
+            let args = fb.block_params(entry);
+            (args[0], args[1])
+        };
+        let sum = fb.import_function(sum.module, sum.function, sum_sig).unwrap();
+        let pick = fb.import_function(pick.module, pick.function, pick_sig).unwrap();
+        let args = (1..=20).map(|n| fb.ins().u32(n, span)).collect::<Vec<_>>();
+        let call = fb.ins().call(sum, &args, span);
+        let total = fb.first_result(call);
+        fb.ins().assert_eq_imm(Immediate::U32(210), total, span);
+        let mut args = (1..=10).map(|n| fb.ins().u32(n, span)).collect::<Vec<_>>();
+        let eleven = fb.ins().u32(11, span);
+        args.extend([w0, w1, eleven]);
+        let call = fb.ins().call(pick, &args, span);
+        let picked = fb.first_result(call);
+        fb.ins().ret(Some(picked), span);
+        fb.build().expect("unexpected error building function")
+    };
+
+    mb.build().expect("unexpected error constructing test module");
+
+    let program = builder.with_entrypoint(main).link().expect("failed to link program");
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+
+    let args = (1..=8).map(|n| Felt::new(n * 100)).collect::<Vec<_>>();
+    let mut stack = harness.execute_program(program.freeze(), &args).expect("execution failed");
+    assert_eq!(stack.len(), 4);
+    let picked = (0..4).map(|_| stack.pop().unwrap().as_int()).collect::<Vec<_>>();
+    assert_eq!(picked, vec![500, 600, 700, 800]);
+}
+
+/// Test that a call which cannot be lowered to fit on the operand stack is rejected with a
+/// diagnostic, rather than a panic
+#[test]
+fn codegen_wide_signature_unsupported_cc() {
+    let harness = TestByEmulationHarness::default();
+    let span = SourceSpan::UNKNOWN;
+
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+
+    let sig = Signature {
+        cc: CallConv::Wasm,
+        ..Signature::new((0..20).map(|_| AbiParam::new(Type::U32)), [AbiParam::new(Type::U32)])
+    };
+    let callee = {
+        let mut fb = mb.function("callee", sig.clone()).expect("unexpected symbol conflict");
+        let entry = fb.current_block();
+        let first = fb.block_params(entry)[0];
+        fb.ins().ret(Some(first), span);
+        fb.build().expect("unexpected error building function")
+    };
+    let main = {
+        let mut fb = mb
+            .function("main", Signature::new([], [AbiParam::new(Type::U32)]))
+            .expect("unexpected symbol conflict");
+        let callee = fb.import_function(callee.module, callee.function, sig).unwrap();
+        let args = (1..=20).map(|n| fb.ins().u32(n, span)).collect::<Vec<_>>();
+        let call = fb.ins().call(callee, &args, span);
+        let result = fb.first_result(call);
+        fb.ins().ret(Some(result), span);
+        fb.build().expect("unexpected error building function")
+    };
+
+    mb.build().expect("unexpected error constructing test module");
+
+    let program = builder.with_entrypoint(main).link().expect("failed to link program");
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let Err(err) = compiler.compile(program) else {
+        panic!("expected compilation to fail");
+    };
+    assert_eq!(err.to_string(), "operand stack depth exceeded");
+}
+
+/// Test that the peephole optimizer removes the redundant sequences it is designed to handle
+#[test]
+fn peephole_rules() {
+    let span = SourceSpan::UNKNOWN;
+    let mut function =
+        Function::new("test::peephole".parse().unwrap(), Signature::new(vec![], vec![]));
+    let body = function.body_mut();
+    for op in [
+        Op::Swap(1),
+        Op::Swap(1),
+        Op::Dup(0),
+        Op::Drop,
+        Op::PushU8(0),
+        Op::Add,
+        Op::Movup(2),
+        Op::Movdn(2),
+        Op::U32WrappingAdd,
+        Op::U32Assert,
+        Op::Movup(1),
+        Op::PushU8(1),
+        Op::PushU8(2),
+        Op::Swap(1),
+        Op::Swap(1),
+        Op::Add,
+    ] {
+        body.push(op, span);
+    }
+
+    let mut peephole = Peephole::default();
+    assert!(peephole.run(&mut function));
+    let ops = function.body().ops().iter().map(|op| op.into_inner()).collect::<Vec<_>>();
+    assert_eq!(ops, [Op::U32WrappingAdd, Op::Swap(1), Op::PushU8(1), Op::PushU8(2), Op::Add]);
+
+    let stats = peephole.stats();
+    assert_eq!(stats.get("swap-swap"), 1);
+    assert_eq!(stats.get("push-drop"), 1);
+    assert_eq!(stats.get("push-identity-arith"), 1);
+    assert_eq!(stats.get("movup-movdn"), 1);
+    assert_eq!(stats.get("redundant-u32assert"), 1);
+    assert_eq!(stats.get("move-one-to-swap"), 1);
+    assert_eq!(stats.get("push-swap"), 2);
+    assert_eq!(stats.total(), 8);
+
+    // Running again is a no-op, as the rules have been applied to a fixpoint
+    assert!(!peephole.run(&mut function));
+}
+
+/// Sequences of instructions which are valid when the operand stack holds only u32 values, and
+/// which preserve that property, used to check the peephole optimizer against the emulator.
+fn peephole_snippet() -> impl Strategy<Value = Vec<Op>> {
+    prop_oneof![
+        (1u8..4).prop_map(|n| vec![Op::Swap(n)]),
+        (1u8..4).prop_map(|n| vec![Op::Movup(n)]),
+        (1u8..4).prop_map(|n| vec![Op::Movdn(n)]),
+        (0u8..4).prop_map(|n| vec![Op::Dup(n)]),
+        Just(vec![Op::Drop]),
+        (0u8..3).prop_map(|n| vec![Op::PushU8(n)]),
+        (0u32..3).prop_map(|n| vec![Op::Push(Felt::new(n as u64))]),
+        Just(vec![Op::PushU8(0), Op::Add]),
+        Just(vec![Op::PushU8(1), Op::Mul]),
+        Just(vec![Op::AddImm(Felt::ZERO)]),
+        Just(vec![Op::MulImm(Felt::ONE)]),
+        Just(vec![Op::U32WrappingAdd]),
+        Just(vec![Op::U32WrappingMul]),
+        Just(vec![Op::U32Lt]),
+        Just(vec![Op::Eq]),
+        Just(vec![Op::U32Assert]),
+        Just(vec![Op::Padw, Op::Dropw]),
+        (1u8..4).prop_map(|n| vec![Op::PushU8(3), Op::Dup(n), Op::Swap(1)]),
+    ]
+}
+
+/// Returns the minimum depth of the operand stack required by `op`, and its effect on the depth
+fn peephole_stack_effect(op: &Op) -> (usize, isize) {
+    match op {
+        Op::Swap(n) | Op::Movup(n) | Op::Movdn(n) => (*n as usize + 1, 0),
+        Op::Dup(n) => (*n as usize + 1, 1),
+        Op::Drop => (1, -1),
+        Op::PushU8(_) | Op::Push(_) => (0, 1),
+        Op::Padw => (0, 4),
+        Op::Dropw => (4, -4),
+        Op::AddImm(_) | Op::MulImm(_) | Op::U32Assert => (1, 0),
+        Op::Add | Op::Mul | Op::U32WrappingAdd | Op::U32WrappingMul | Op::U32Lt | Op::Eq => (2, -1),
+        op => unimplemented!("unexpected op: {op}"),
+    }
+}
+
+/// Test that the peephole optimizer preserves the semantics of random instruction sequences, by
+/// comparing the results of the original and optimized sequences in the emulator.
+#[test]
+fn peephole_preserves_semantics() {
+    const NUM_INPUTS: usize = 8;
+    const MAX_DEPTH: usize = 16;
+
+    let sequences = prop::collection::vec(peephole_snippet(), 0..32).prop_map(|snippets| {
+        // Discard snippets which would underflow or overflow the operand stack
+        let mut depth = NUM_INPUTS as isize;
+        let mut ops = vec![];
+        for snippet in snippets {
+            let mut snippet_depth = depth;
+            let valid = snippet.iter().all(|op| {
+                let (required, effect) = peephole_stack_effect(op);
+                let valid = snippet_depth >= required as isize;
+                snippet_depth += effect;
+                valid && snippet_depth <= MAX_DEPTH as isize
+            });
+            if valid {
+                depth = snippet_depth;
+                ops.extend(snippet);
+            }
+        }
+        ops
+    });
+    let inputs = prop::collection::vec(any::<u32>(), NUM_INPUTS);
+
+    let run = |ops: &[Op], inputs: &[u32]| -> (Vec<u64>, usize) {
+        let id: FunctionIdent = "test::peephole".parse().unwrap();
+        let mut function = Function::new(id, Signature::new(vec![], vec![]));
+        for op in ops.iter().copied() {
+            function.body_mut().push(op, SourceSpan::UNKNOWN);
+        }
+        let num_ops = function.body().ops().len();
+        let mut module = Box::new(Module::new(
+            miden_assembly::LibraryPath::new("test").unwrap(),
+            miden_assembly::ast::ModuleKind::Library,
+        ));
+        module.push_back(Box::new(function));
+        let mut emulator = Emulator::default();
+        emulator.load_module(module.freeze()).expect("failed to load module");
+        let args = inputs.iter().map(|input| Felt::new(*input as u64)).collect::<Vec<_>>();
+        let stack = emulator.invoke(id, &args).expect("execution failed");
+        let stack = stack.stack().iter().map(|felt| felt.as_int()).collect();
+        (stack, num_ops)
+    };
+
+    TestRunner::new(Config::with_cases(256))
+        .run(&(sequences, inputs), move |(ops, inputs)| {
+            let mut block = ops
+                .iter()
+                .map(|op| hir::diagnostics::Span::new(SourceSpan::UNKNOWN, *op))
+                .collect::<SmallVec<[_; 4]>>();
+            Peephole::default().run_block(&mut block);
+            let optimized = block.iter().map(|op| op.into_inner()).collect::<Vec<_>>();
+
+            let (expected, _) = run(&ops, &inputs);
+            let (actual, _) = run(&optimized, &inputs);
+            prop_assert_eq!(actual, expected, "{:?} was optimized to {:?}", ops, optimized);
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Test that the peephole optimizer is applied when optimizations are enabled, and that the
+/// optimized program behaves the same as the unoptimized one
+#[test]
+fn codegen_peephole() {
+    let compile = |optimize: midenc_session::OptLevel| {
+        let mut harness = TestByEmulationHarness::default();
+        harness.context.session.options.optimize = optimize;
+        let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+        let mut mb = builder.module("test");
+        // (x - 32) * x, which requires `x` to be copied from beneath the constant operand
+        let id = {
+            let mut fb = mb
+                .function(
+                    "sub_mul",
+                    Signature::new([AbiParam::new(Type::U32)], [AbiParam::new(Type::U32)]),
+                )
+                .expect("unexpected symbol conflict");
+            let x = fb.block_params(fb.current_block())[0];
+            let c = fb.ins().u32(32, SourceSpan::UNKNOWN);
+            let diff = fb.ins().sub_wrapping(x, c, SourceSpan::UNKNOWN);
+            let product = fb.ins().mul_wrapping(diff, x, SourceSpan::UNKNOWN);
+            fb.ins().ret(Some(product), SourceSpan::UNKNOWN);
+            fb.build().expect("unexpected error building function")
+        };
+        mb.build().expect("unexpected error constructing test module");
+        let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+        let mut compiler = MasmCompiler::new(&harness.context.session);
+        let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+        let num_ops = program
+            .get("test")
+            .unwrap()
+            .functions()
+            .flat_map(|function| function.body.blocks.values())
+            .map(|block| block.ops.len())
+            .sum::<usize>();
+
+        let x = 100u32;
+        let mut stack = harness
+            .execute_program(program.freeze(), &[Felt::new(x as u64)])
+            .expect("execution failed");
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.pop().map(|e| e.as_int()), Some(((x - 32) * x) as u64));
+        num_ops
+    };
+
+    let unoptimized = compile(midenc_session::OptLevel::None);
+    let optimized = compile(midenc_session::OptLevel::Balanced);
+    assert!(
+        optimized < unoptimized,
+        "expected optimized program ({optimized} ops) to be smaller than the unoptimized program \
+         ({unoptimized} ops)"
+    );
+}
+
+/// Build a function which computes `(y + n * 2) - x`, where `x` and `y` are defined before a loop
+/// which does not use them, but they are live through the loop, as they are used after it
+fn live_through_loop(mb: &mut hir::ModuleBuilder, context: &TestContext) -> FunctionIdent {
+    let mut fb = mb
+        .function(
+            "live_through_loop",
+            Signature::new(
+                [AbiParam::new(Type::U32), AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                [AbiParam::new(Type::U32)],
+            ),
+        )
+        .expect("unexpected symbol conflict");
+    let entry = fb.current_block();
+    let (x, y, n) = {
+        let args = fb.block_params(entry);
+        (args[0], args[1], args[2])
+    };
+    let loop_header_blk = fb.create_block();
+    let n1 = fb.append_block_param(loop_header_blk, Type::U32, SourceSpan::UNKNOWN);
+    let sum1 = fb.append_block_param(loop_header_blk, Type::U32, SourceSpan::UNKNOWN);
+    let loop_body_blk = fb.create_block();
+    let loop_exit_blk = fb.create_block();
+    let result0 = fb.append_block_param(loop_exit_blk, Type::U32, SourceSpan::UNKNOWN);
+    let zero = fb.ins().u32(0, SourceSpan::UNKNOWN);
+    fb.ins().br(loop_header_blk, &[n, zero], SourceSpan::UNKNOWN);
+
+    fb.switch_to_block(loop_header_blk);
+    let is_zero = fb.ins().eq_imm(n1, Immediate::U32(0), SourceSpan::UNKNOWN);
+    fb.ins()
+        .cond_br(is_zero, loop_exit_blk, &[sum1], loop_body_blk, &[], SourceSpan::UNKNOWN);
+
+    fb.switch_to_block(loop_body_blk);
+    let n2 = fb.ins().sub_imm_checked(n1, Immediate::U32(1), SourceSpan::UNKNOWN);
+    let sum2 = fb.ins().add_imm_checked(sum1, Immediate::U32(2), SourceSpan::UNKNOWN);
+    fb.ins().br(loop_header_blk, &[n2, sum2], SourceSpan::UNKNOWN);
+
+    fb.switch_to_block(loop_exit_blk);
+    let sum = fb.ins().add_checked(y, result0, SourceSpan::UNKNOWN);
+    let result = fb.ins().sub_checked(sum, x, SourceSpan::UNKNOWN);
+    fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+
+    fb.build(&context.session.diagnostics)
+        .expect("unexpected error building function")
+}
+
+/// Test that values which are live through a loop, but not used in it, are given a canonical
+/// place on the operand stack on entry to the loop header, so that they are found in the same
+/// place on every iteration of the loop
+#[test]
+fn codegen_loop_live_through_values() {
+    use midenc_hir_analysis::{DominatorTree, LivenessAnalysis, LoopAnalysis};
+
+    let mut harness = TestByEmulationHarness::default();
+
+    // Check the layout planned for the loop header
+    let mut mb = hir::ModuleBuilder::new("test");
+    let id = live_through_loop(&mut mb, &harness.context);
+    let module = mb.build();
+    let f = module.function(id.function).unwrap();
+    let (x, y) = {
+        let params = f.dfg.block_params(f.dfg.entry_block());
+        (params[0], params[1])
+    };
+    let session = &harness.context.session;
+    let mut analyses = AnalysisManager::new();
+    let domtree = analyses.get_or_compute::<DominatorTree>(f, session).unwrap();
+    let loops = analyses.get_or_compute::<LoopAnalysis>(f, session).unwrap();
+    let liveness = analyses.get_or_compute::<LivenessAnalysis>(f, session).unwrap();
+    let mut f_prime = masm::Function::new(f.id, f.signature.clone());
+    let schedule = codegen::Scheduler::new(f, &mut f_prime, &domtree, &loops, &liveness).build();
+    let loop_header_blk = loops.loop_header(loops.loops().next().unwrap());
+    // `y` is used before `x` after the loop, so it is expected nearer the top of the stack
+    assert_eq!(schedule.stack_layout.live_through(loop_header_blk), Some([y, x].as_slice()));
+
+    // Check that the program computes the expected result
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+    let id = live_through_loop(mb.as_mut(), &harness.context);
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+
+    let x = Felt::new(3);
+    let y = Felt::new(10);
+    let n = Felt::new(4);
+    let mut stack =
+        harness.execute_program(program.freeze(), &[x, y, n]).expect("execution failed");
+    assert_eq!(stack.len(), 1);
+    assert_eq!(stack.pop().map(|e| e.as_int()), Some(10 + 4 * 2 - 3));
+}
+
 /// Test the code generator on a simple program containing [testing::sum_matrix].
 #[test]
 fn codegen_sum_matrix() {
@@ -500,6 +1113,103 @@ fn i32_checked_neg() {
     harness.invoke(neg, &[min]).expect("execution failed");
 }
 
+/// Test that a store is not scheduled before any of the loads of the same memory preceding it,
+/// not just the most recent one
+#[test]
+fn codegen_store_after_loads() {
+    let mut harness = TestByEmulationHarness::default();
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+    // (x * 100) + (x * 10) + y, where `x` and `y` are written to the same local in turn
+    let id = {
+        let mut fb = mb
+            .function(
+                "overwrite",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let (x, y) = {
+            let args = fb.block_params(fb.current_block());
+            (args[0], args[1])
+        };
+        let local = fb.data_flow_graph_mut().alloc_local(Type::U32);
+        fb.ins().store_local(local, x, SourceSpan::UNKNOWN);
+        let x1 = fb.ins().load_local(local, SourceSpan::UNKNOWN);
+        let x2 = fb.ins().load_local(local, SourceSpan::UNKNOWN);
+        fb.ins().store_local(local, y, SourceSpan::UNKNOWN);
+        let y1 = fb.ins().load_local(local, SourceSpan::UNKNOWN);
+        let x2 = fb.ins().mul_imm_wrapping(x2, Immediate::U32(10), SourceSpan::UNKNOWN);
+        let sum = fb.ins().add_wrapping(x2, y1, SourceSpan::UNKNOWN);
+        let x1 = fb.ins().mul_imm_wrapping(x1, Immediate::U32(100), SourceSpan::UNKNOWN);
+        let result = fb.ins().add_wrapping(x1, sum, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+
+    let mut stack = harness
+        .execute_program(program.freeze(), &[Felt::new(5), Felt::new(7)])
+        .expect("execution failed");
+    assert_eq!(stack.len(), 1);
+    assert_eq!(stack.pop().map(|e| e.as_int()), Some(557));
+}
+
+/// Test that the overflow flag of an overflowing u32 multiplication is a boolean, as
+/// `u32overflowing_mul` produces the high 32 bits of the product instead
+#[test]
+fn codegen_u32_overflowing_mul_flag() {
+    let harness = TestByEmulationHarness::default();
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function(
+                "overflows",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let (a, b) = {
+            let args = fb.block_params(fb.current_block());
+            (args[0], args[1])
+        };
+        let inst = fb.ins().mul_overflowing(a, b, SourceSpan::UNKNOWN);
+        let overflowed = fb.first_result(inst);
+        let result = fb.ins().zext(overflowed, Type::U32, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let mut program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+
+    // The emulator does not model the high bits produced by `u32overflowing_mul`, so we execute
+    // the program on the VM
+    let stdlib = miden_stdlib::StdLibrary::default();
+    let stdlib = AsRef::<miden_assembly::Library>::as_ref(&stdlib);
+    program.link_library(stdlib.clone());
+    let assembled = program.assemble(&harness.context.session).expect("failed to assemble");
+    let mut host = miden_processor::DefaultHost::default();
+    host.load_mast_forest(stdlib.mast_forest().clone());
+    // 2^17 * 2^17 = 2^34, i.e. the high 32 bits of the product are 4
+    let stack_inputs =
+        miden_core::StackInputs::new(vec![Felt::new(1 << 17), Felt::new(1 << 17)]).unwrap();
+    let trace = miden_processor::execute(&assembled, stack_inputs, host, Default::default())
+        .expect("execution failed");
+    assert_eq!(trace.stack_outputs().stack()[0].as_int(), 1);
+}
+
 #[test]
 fn codegen_mem_store_sw_load_sw() {
     let context = TestContext::default();
@@ -641,48 +1351,507 @@ fn codegen_mem_store_dw_load_dw() {
         .unwrap();
 }
 
-#[test]
-fn codegen_mem_store_felt_load_felt() {
+/// Compile a program which applies `op` to its u128 argument, and returns the result
+fn compile_u128_unary_op<F>(op: F) -> Arc<Program>
+where
+    F: FnOnce(&mut hir::ModuleFunctionBuilder, hir::Value) -> hir::Value,
+{
     let context = TestContext::default();
     let mut builder = ProgramBuilder::new(&context.session.diagnostics);
     let mut mb = builder.module("test");
     let id = {
         let mut fb = mb
             .function(
-                "store_load_felt",
-                Signature::new(
-                    [AbiParam::new(Type::U32), AbiParam::new(Type::Felt)],
-                    [AbiParam::new(Type::Felt)],
-                ),
+                "unary",
+                Signature::new([AbiParam::new(Type::U128)], [AbiParam::new(Type::U128)]),
             )
             .expect("unexpected symbol conflict");
         let entry = fb.current_block();
-        let (ptr_u32, value) = {
-            let args = fb.block_params(entry);
-            (args[0], args[1])
-        };
-        let ptr = fb.ins().inttoptr(ptr_u32, Type::Ptr(Type::Felt.into()), SourceSpan::UNKNOWN);
-        fb.ins().store(ptr, value, SourceSpan::UNKNOWN);
-        let loaded_value = fb.ins().load(ptr, SourceSpan::UNKNOWN);
-        fb.ins().ret(Some(loaded_value), SourceSpan::UNKNOWN);
+        let value = fb.block_params(entry)[0];
+        let result = op(&mut fb, value);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
         fb.build().expect("unexpected error building function")
     };
-
     mb.build().expect("unexpected error constructing test module");
-
     let program = builder.with_entrypoint(id).link().expect("failed to link program");
-
-    let ir_module = program
-        .modules()
-        .iter()
-        .take(1)
-        .collect::<Vec<&midenc_hir::Module>>()
-        .first()
-        .expect("no module in IR program")
-        .to_string();
-
-    eprintln!("{}", ir_module.as_str());
-
+    let mut compiler = MasmCompiler::new(&context.session);
+    compiler
+        .compile(program)
+        .expect("compilation failed")
+        .unwrap_executable()
+        .freeze()
+}
+
+/// Check that `program`, compiled by [compile_u128_unary_op], computes `expected`
+fn check_u128_unary_op<F>(program: Arc<Program>, expected: F)
+where
+    F: Fn(u128) -> u128,
+{
+    TestRunner::new(Config::with_cases(64))
+        .run(&any::<u128>(), move |value| {
+            let mut harness = TestByEmulationHarness::default();
+            let mut stack = harness
+                .execute_program(program.clone(), &value.canonicalize())
+                .expect("execution failed");
+            prop_assert_eq!(u128::from_stack(&mut stack), expected(value));
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn codegen_u128_shift_imm() {
+    for shift in [0, 1, 17, 31, 32, 45, 64, 96, 127] {
+        let program =
+            compile_u128_unary_op(|fb, value| fb.ins().shl_imm(value, shift, SourceSpan::UNKNOWN));
+        check_u128_unary_op(program, |value| value << shift);
+
+        let program =
+            compile_u128_unary_op(|fb, value| fb.ins().shr_imm(value, shift, SourceSpan::UNKNOWN));
+        check_u128_unary_op(program, |value| value >> shift);
+    }
+}
+
+#[test]
+fn codegen_u128_bnot() {
+    let program = compile_u128_unary_op(|fb, value| fb.ins().bnot(value, SourceSpan::UNKNOWN));
+    check_u128_unary_op(program, |value| !value);
+}
+
+#[test]
+fn codegen_push_u64() {
+    let context = TestContext::default();
+    let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function("constant", Signature::new([], [AbiParam::new(Type::U64)]))
+            .expect("unexpected symbol conflict");
+        let value = fb.ins().u64(0x0011_2233_4455_6677, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(value), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+    let mut compiler = MasmCompiler::new(&context.session);
+    let program = compiler
+        .compile(program)
+        .expect("compilation failed")
+        .unwrap_executable()
+        .freeze();
+
+    let mut harness = TestByEmulationHarness::default();
+    let mut stack = harness.execute_program(program, &[]).expect("execution failed");
+    assert_eq!(u64::from_stack(&mut stack), 0x0011_2233_4455_6677);
+}
+
+#[test]
+fn codegen_push_u128() {
+    let context = TestContext::default();
+    let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function("constant", Signature::new([], [AbiParam::new(Type::U128)]))
+            .expect("unexpected symbol conflict");
+        let value = fb.ins().u128(0x0011_2233_4455_6677_8899_aabb_ccdd_eeff, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(value), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+    let mut compiler = MasmCompiler::new(&context.session);
+    let program = compiler
+        .compile(program)
+        .expect("compilation failed")
+        .unwrap_executable()
+        .freeze();
+
+    let mut harness = TestByEmulationHarness::default();
+    let mut stack = harness.execute_program(program, &[]).expect("execution failed");
+    assert_eq!(u128::from_stack(&mut stack), 0x0011_2233_4455_6677_8899_aabb_ccdd_eeff);
+}
+
+/// Executes procedures of `intrinsics::f64` using the Miden VM, as the emulator does not implement
+/// all of the standard library procedures they depend on.
+struct SoftFloatHarness {
+    context: TestContext,
+    host: miden_processor::DefaultHost<miden_processor::MemAdviceProvider>,
+}
+
+impl SoftFloatHarness {
+    fn new() -> Self {
+        let stdlib = miden_stdlib::StdLibrary::default();
+        let mut host = miden_processor::DefaultHost::default();
+        host.load_mast_forest(
+            AsRef::<miden_assembly::Library>::as_ref(&stdlib).mast_forest().clone(),
+        );
+        Self {
+            context: TestContext::default(),
+            host,
+        }
+    }
+
+    /// Assemble a program which calls `intrinsics::f64::<name>` with the operand stack as-is
+    fn compile(&self, name: &str) -> miden_core::Program {
+        let source_manager = self.context.session.source_manager.clone();
+        let module = intrinsics::load("intrinsics::f64", &source_manager)
+            .expect("undefined intrinsic module")
+            .to_ast(false)
+            .expect("invalid intrinsic module");
+        miden_assembly::Assembler::new(source_manager)
+            .with_library(miden_stdlib::StdLibrary::default())
+            .and_then(|assembler| assembler.with_module(module))
+            .and_then(|assembler| {
+                assembler.assemble_program(format!("begin exec.::intrinsics::f64::{name} end"))
+            })
+            .expect("failed to assemble program")
+    }
+
+    /// Execute `program` with `inputs` on the operand stack, the first input on top, and return the
+    /// first `arity` elements of the resulting operand stack, or `None` if execution trapped.
+    fn run(&self, program: &miden_core::Program, inputs: &[u64], arity: usize) -> Option<Vec<u64>> {
+        let inputs = inputs.iter().rev().copied().map(miden_core::Felt::new).collect();
+        let stack_inputs = miden_core::StackInputs::new(inputs).expect("invalid stack inputs");
+        let mut process = miden_processor::Process::new(
+            program.kernel().clone(),
+            stack_inputs,
+            self.host.clone(),
+            miden_processor::ExecutionOptions::default(),
+        );
+        let outputs = process.execute(program).ok()?;
+        Some(outputs.stack()[..arity].iter().map(|felt| felt.as_int()).collect())
+    }
+}
+
+/// The number of random cases to check each soft-float intrinsic against
+///
+/// Each case runs on the VM, so this is kept fairly low to keep the test suite fast
+const SOFT_FLOAT_CASES: u32 = 128;
+
+/// The canonical NaN produced by the soft-float library
+const CANONICAL_NAN: u64 = 0x7ff8_0000_0000_0000;
+
+fn f64_limbs(bits: u64) -> [u64; 2] {
+    [bits >> 32, bits & (u32::MAX as u64)]
+}
+
+fn f64_from_limbs(limbs: &[u64]) -> u64 {
+    (limbs[0] << 32) | limbs[1]
+}
+
+/// Bit patterns of f64 values which exercise edge cases of the soft-float library
+fn f64_bits() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        any::<u64>(),
+        any::<f64>().prop_map(f64::to_bits),
+        (-1.0e6f64..1.0e6).prop_map(f64::to_bits),
+        (any::<bool>(), 0u64..(1 << 52)).prop_map(|(sign, frac)| ((sign as u64) << 63) | frac),
+        prop::sample::select(vec![
+            0.0f64,
+            -0.0,
+            1.0,
+            -1.0,
+            0.5,
+            -0.5,
+            1.5,
+            2.5,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            f64::MIN,
+            f64::EPSILON,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            4503599627370496.0,
+            9007199254740993.0,
+            9.223372036854776e18,
+            -9.223372036854776e18,
+            1.8446744073709552e19,
+            2147483648.0,
+            -2147483648.0,
+            4294967296.0,
+        ])
+        .prop_map(f64::to_bits),
+    ]
+}
+
+/// Check that `intrinsics::f64::<name>` computes `expected` for random pairs of operands
+fn check_f64_binary_op<F>(name: &str, expected: F)
+where
+    F: Fn(f64, f64) -> f64,
+{
+    let harness = SoftFloatHarness::new();
+    let program = harness.compile(name);
+    TestRunner::new(Config::with_cases(SOFT_FLOAT_CASES))
+        .run(&(f64_bits(), f64_bits()), move |(a, b)| {
+            let [a_hi, a_lo] = f64_limbs(a);
+            let [b_hi, b_lo] = f64_limbs(b);
+            let output =
+                harness.run(&program, &[a_hi, a_lo, b_hi, b_lo], 2).expect("execution failed");
+            let expected = expected(f64::from_bits(a), f64::from_bits(b));
+            let expected = if expected.is_nan() {
+                CANONICAL_NAN
+            } else {
+                expected.to_bits()
+            };
+            let actual = f64_from_limbs(&output);
+            prop_assert_eq!(
+                actual,
+                expected,
+                "{}({:e}, {:e}) = {:e}",
+                name,
+                f64::from_bits(a),
+                f64::from_bits(b),
+                f64::from_bits(actual)
+            );
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Check that `intrinsics::f64::<name>` computes `expected` for random operands
+fn check_f64_unary_op<F>(name: &str, expected: F)
+where
+    F: Fn(f64) -> f64,
+{
+    let harness = SoftFloatHarness::new();
+    let program = harness.compile(name);
+    TestRunner::new(Config::with_cases(SOFT_FLOAT_CASES))
+        .run(&f64_bits(), move |x| {
+            let output = harness.run(&program, &f64_limbs(x), 2).expect("execution failed");
+            let expected = expected(f64::from_bits(x));
+            let expected = if expected.is_nan() {
+                CANONICAL_NAN
+            } else {
+                expected.to_bits()
+            };
+            let actual = f64_from_limbs(&output);
+            prop_assert_eq!(
+                actual,
+                expected,
+                "{}({:e}) = {:e}",
+                name,
+                f64::from_bits(x),
+                f64::from_bits(actual)
+            );
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Check that the comparison `intrinsics::f64::<name>` computes `expected` for random operands
+fn check_f64_comparison<F>(name: &str, expected: F)
+where
+    F: Fn(f64, f64) -> bool,
+{
+    let harness = SoftFloatHarness::new();
+    let program = harness.compile(name);
+    TestRunner::new(Config::with_cases(SOFT_FLOAT_CASES))
+        .run(&(f64_bits(), f64_bits()), move |(a, b)| {
+            // Equal operands are unlikely to be generated otherwise
+            let b = if b % 4 == 0 { a } else { b };
+            let [a_hi, a_lo] = f64_limbs(a);
+            let [b_hi, b_lo] = f64_limbs(b);
+            let output =
+                harness.run(&program, &[a_hi, a_lo, b_hi, b_lo], 1).expect("execution failed");
+            let (a, b) = (f64::from_bits(a), f64::from_bits(b));
+            prop_assert_eq!(output[0], expected(a, b) as u64, "{}({:e}, {:e})", name, a, b);
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Check that the conversion `intrinsics::f64::<name>` of an integer to f64 computes `expected`
+fn check_f64_from_int<F>(name: &str, arity: usize, expected: F)
+where
+    F: Fn(u64) -> f64,
+{
+    let harness = SoftFloatHarness::new();
+    let program = harness.compile(name);
+    let inputs = prop_oneof![
+        any::<u64>(),
+        any::<u64>().prop_map(|x| x >> (x % 64)),
+        prop::sample::select(vec![0, 1, u32::MAX as u64, 1 << 63, u64::MAX]),
+    ];
+    TestRunner::new(Config::with_cases(SOFT_FLOAT_CASES))
+        .run(&inputs, move |x| {
+            let inputs = if arity == 1 {
+                vec![x & (u32::MAX as u64)]
+            } else {
+                f64_limbs(x).to_vec()
+            };
+            let output = harness.run(&program, &inputs, 2).expect("execution failed");
+            let expected = expected(x);
+            let actual = f64_from_limbs(&output);
+            prop_assert_eq!(
+                actual,
+                expected.to_bits(),
+                "{}({}) = {:e}",
+                name,
+                x,
+                f64::from_bits(actual)
+            );
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Check that the conversion `intrinsics::f64::<name>` of an f64 to an integer computes
+/// `expected`, where `None` indicates that the conversion must trap
+fn check_f64_to_int<F>(name: &str, arity: usize, expected: F)
+where
+    F: Fn(f64) -> Option<u64>,
+{
+    let harness = SoftFloatHarness::new();
+    let program = harness.compile(name);
+    TestRunner::new(Config::with_cases(SOFT_FLOAT_CASES))
+        .run(&f64_bits(), move |x| {
+            let output = harness.run(&program, &f64_limbs(x), arity);
+            let actual = output.map(|output| {
+                if arity == 1 {
+                    output[0]
+                } else {
+                    f64_from_limbs(&output)
+                }
+            });
+            prop_assert_eq!(
+                actual,
+                expected(f64::from_bits(x)),
+                "{}({:e})",
+                name,
+                f64::from_bits(x)
+            );
+            Ok(())
+        })
+        .unwrap();
+}
+
+/// Returns `x`, truncated towards zero, if the result is within `min..=max`
+fn trunc_in_range(x: f64, min: f64, max: f64) -> Option<f64> {
+    let x = x.trunc();
+    (x >= min && x <= max).then_some(x)
+}
+
+#[test]
+fn soft_float_arithmetic() {
+    check_f64_binary_op("add", |a, b| a + b);
+    check_f64_binary_op("sub", |a, b| a - b);
+    check_f64_binary_op("mul", |a, b| a * b);
+    check_f64_binary_op("div", |a, b| a / b);
+}
+
+#[test]
+fn soft_float_min_max() {
+    check_f64_binary_op("min", |a, b| {
+        if a == 0.0 && b == 0.0 {
+            f64::from_bits(a.to_bits() | b.to_bits())
+        } else if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else {
+            a.min(b)
+        }
+    });
+    check_f64_binary_op("max", |a, b| {
+        if a == 0.0 && b == 0.0 {
+            f64::from_bits(a.to_bits() & b.to_bits())
+        } else if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else {
+            a.max(b)
+        }
+    });
+}
+
+#[test]
+fn soft_float_comparisons() {
+    check_f64_comparison("eq", |a, b| a == b);
+    check_f64_comparison("lt", |a, b| a < b);
+    check_f64_comparison("le", |a, b| a <= b);
+}
+
+#[test]
+fn soft_float_rounding() {
+    check_f64_unary_op("trunc", f64::trunc);
+    check_f64_unary_op("floor", f64::floor);
+    check_f64_unary_op("ceil", f64::ceil);
+    check_f64_unary_op("nearest", f64::round_ties_even);
+}
+
+#[test]
+fn soft_float_from_int() {
+    check_f64_from_int("from_u64", 2, |x| x as f64);
+    check_f64_from_int("from_i64", 2, |x| x as i64 as f64);
+    check_f64_from_int("from_u32", 1, |x| x as u32 as f64);
+    check_f64_from_int("from_i32", 1, |x| x as u32 as i32 as f64);
+}
+
+#[test]
+fn soft_float_to_int() {
+    check_f64_to_int("trunc_u64", 2, |x| {
+        trunc_in_range(x, 0.0, u64::MAX as f64)
+            .filter(|x| *x < 18446744073709551616.0)
+            .map(|x| x as u64)
+    });
+    check_f64_to_int("trunc_i64", 2, |x| {
+        trunc_in_range(x, i64::MIN as f64, 9223372036854775807.0)
+            .filter(|x| *x < 9223372036854775808.0)
+            .map(|x| x as i64 as u64)
+    });
+    check_f64_to_int("trunc_u32", 1, |x| {
+        trunc_in_range(x, 0.0, u32::MAX as f64).map(|x| x as u32 as u64)
+    });
+    check_f64_to_int("trunc_i32", 1, |x| {
+        trunc_in_range(x, i32::MIN as f64, i32::MAX as f64).map(|x| x as i32 as u32 as u64)
+    });
+    check_f64_to_int("trunc_sat_u64", 2, |x| Some(x as u64));
+    check_f64_to_int("trunc_sat_i64", 2, |x| Some(x as i64 as u64));
+    check_f64_to_int("trunc_sat_u32", 1, |x| Some(x as u32 as u64));
+    check_f64_to_int("trunc_sat_i32", 1, |x| Some(x as i32 as u32 as u64));
+}
+
+#[test]
+fn codegen_mem_store_felt_load_felt() {
+    let context = TestContext::default();
+    let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function(
+                "store_load_felt",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::Felt)],
+                    [AbiParam::new(Type::Felt)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let entry = fb.current_block();
+        let (ptr_u32, value) = {
+            let args = fb.block_params(entry);
+            (args[0], args[1])
+        };
+        let ptr = fb.ins().inttoptr(ptr_u32, Type::Ptr(Type::Felt.into()), SourceSpan::UNKNOWN);
+        fb.ins().store(ptr, value, SourceSpan::UNKNOWN);
+        let loaded_value = fb.ins().load(ptr, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(loaded_value), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+
+    mb.build().expect("unexpected error constructing test module");
+
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let ir_module = program
+        .modules()
+        .iter()
+        .take(1)
+        .collect::<Vec<&midenc_hir::Module>>()
+        .first()
+        .expect("no module in IR program")
+        .to_string();
+
+    eprintln!("{}", ir_module.as_str());
+
     let mut compiler = MasmCompiler::new(&context.session);
     let program = compiler
         .compile(program)
@@ -965,6 +2134,24 @@ impl ToCanonicalRepr for i128 {
     }
 }
 
+impl ToCanonicalRepr for u128 {
+    fn ir_type() -> Type {
+        Type::U128
+    }
+
+    fn canonicalize(self) -> SmallVec<[Felt; 4]> {
+        let mut out = ((self >> 64) as u64).canonicalize();
+        out.extend_from_slice(&(self as u64).canonicalize());
+        out
+    }
+
+    fn from_stack(stack: &mut OperandStack<Felt>) -> Self {
+        let hi = (<u64 as ToCanonicalRepr>::from_stack(stack) as u128) << 64;
+        let lo = <u64 as ToCanonicalRepr>::from_stack(stack) as u128;
+        hi | lo
+    }
+}
+
 proptest! {
     #![proptest_config(ProptestConfig { cases: 1000, failure_persistence: None, ..Default::default() })]
 
@@ -1280,3 +2467,1943 @@ proptest! {
         prop_assert_eq!(result, Ok(a >> b));
     }
 }
+
+/// Assemble a program which executes each of `entrypoints` in turn, from the given modules
+fn assemble_modules(
+    context: &TestContext,
+    modules: &[Box<Module>],
+    entrypoints: &[&str],
+) -> miden_core::Program {
+    let mut assembler = miden_assembly::Assembler::new(context.session.source_manager.clone());
+    for module in modules {
+        assembler = assembler.with_module(module.to_ast(false).unwrap()).unwrap();
+    }
+    let body = entrypoints.iter().map(|name| format!("exec.::{name}")).collect::<Vec<_>>();
+    assembler.assemble_program(format!("begin {} end", body.join(" "))).unwrap()
+}
+
+/// Execute `program`, returning the contents of the operand stack on exit
+fn execute_assembled(program: &miden_core::Program) -> Vec<Felt> {
+    let trace = miden_processor::execute(
+        program,
+        Default::default(),
+        miden_processor::DefaultHost::default(),
+        Default::default(),
+    )
+    .unwrap();
+    trace.stack_outputs().stack().to_vec()
+}
+
+/// Build a module named `name` with the given functions, in order
+fn module_with(name: &str, functions: impl IntoIterator<Item = Function>) -> Box<Module> {
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new(name).unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+    for function in functions {
+        module.push_back(Box::new(function));
+    }
+    module
+}
+
+/// Test that the outliner extracts repeated sequences into a shared procedure, deduplicating
+/// sequences which assemble to the same MAST, and that doing so preserves semantics while
+/// reducing the size of the MAST.
+#[test]
+fn outliner() {
+    let context = TestContext::default();
+    let span = SourceSpan::UNKNOWN;
+
+    // `a`, `b` and `c` share a sequence, while `d` and `e` share one which differs only in how the
+    // constants are pushed, which assembles to the same code
+    let sequence = |push: fn(u32) -> Op| {
+        (0..1200).flat_map(move |i| [push(1000 + i), Op::Add]).collect::<Vec<_>>()
+    };
+    let functions = ["a", "b", "c", "d", "e"].into_iter().enumerate().map(|(i, name)| {
+        let name = format!("test::{name}").parse().unwrap();
+        let mut function = Function::new(name, Signature::new(vec![], vec![]));
+        function.signature.linkage = Linkage::External;
+        let body = function.body_mut();
+        body.push(Op::PushU8(i as u8), span);
+        let push: fn(u32) -> Op = if i < 3 {
+            |n| Op::Push(Felt::new(n as u64))
+        } else {
+            Op::PushU32
+        };
+        for op in sequence(push) {
+            body.push(op, span);
+        }
+        body.push(Op::MulImm(Felt::new(i as u64 + 2)), span);
+        function
+    });
+    let mut modules = vec![module_with("test", functions)];
+    let entrypoints = ["test::a", "test::b", "test::c", "test::d", "test::e"];
+
+    let original = assemble_modules(&context, &modules, &entrypoints);
+    Outliner::new(&context.session).run(&mut modules).unwrap();
+    let outlined = modules[0]
+        .functions()
+        .filter(|f| f.name.function.as_str().starts_with("outlined_"))
+        .collect::<Vec<_>>();
+    assert_eq!(outlined.len(), 1);
+    let callee = outlined[0].name;
+    assert_eq!(callee.function.as_str(), "outlined_0");
+    for function in modules[0].functions().filter(|f| f.name != callee) {
+        let ops = function.body.block(function.body.body).ops.iter().map(|op| op.into_inner());
+        assert_eq!(
+            ops.collect::<Vec<_>>(),
+            vec![
+                Op::PushU8(function.name.function.as_str().as_bytes()[0] - b'a'),
+                Op::Exec(callee),
+                Op::MulImm(Felt::new(
+                    (function.name.function.as_str().as_bytes()[0] - b'a') as u64 + 2
+                )),
+            ]
+        );
+    }
+
+    let optimized = assemble_modules(&context, &modules, &entrypoints);
+    assert_eq!(execute_assembled(&optimized), execute_assembled(&original));
+
+    use miden_core::utils::Serializable;
+    let original_size = original.mast_forest().to_bytes().len();
+    let optimized_size = optimized.mast_forest().to_bytes().len();
+    assert!(
+        optimized_size < original_size,
+        "expected outlining to reduce the size of the MAST, but it went from {original_size} to \
+         {optimized_size} bytes"
+    );
+}
+
+/// Test that when the sequence estimated to save the most space would be inlined when lowered, the
+/// outliner moves on to the next best candidate, rather than giving up.
+#[test]
+fn outliner_skips_inlined_candidate() {
+    let context = TestContext::default();
+    let span = SourceSpan::UNKNOWN;
+
+    // `a`, `b` and `c` share a long sequence, while `d` and `e` share a shorter one, so the former
+    // is the best candidate for outlining
+    let sequence = |base: u64, len: u64| {
+        (0..len)
+            .flat_map(move |i| [Op::Push(Felt::new(base + i)), Op::Add])
+            .collect::<Vec<_>>()
+    };
+    let long = sequence(1000, 1200);
+    let short = sequence(5000, 600);
+    let functions = || {
+        ["a", "b", "c", "d", "e"].into_iter().enumerate().map(|(i, name)| {
+            let name = format!("test::{name}").parse().unwrap();
+            let mut function = Function::new(name, Signature::new(vec![], vec![]));
+            function.signature.linkage = Linkage::External;
+            let body = function.body_mut();
+            body.push(Op::PushU8(i as u8), span);
+            for op in if i < 3 { &long } else { &short } {
+                body.push(*op, span);
+            }
+            function
+        })
+    };
+    let outlined_bodies = |modules: &[Box<Module>]| {
+        modules[0]
+            .functions()
+            .filter(|f| f.name.function.as_str().starts_with("outlined_"))
+            .map(|f| f.body.block(f.body.body).ops.iter().map(|op| op.into_inner()).collect())
+            .collect::<Vec<Vec<_>>>()
+    };
+    let entrypoints = ["test::a", "test::b", "test::c", "test::d", "test::e"];
+
+    let mut modules = vec![module_with("test", functions())];
+    Outliner::new(&context.session).run(&mut modules).unwrap();
+    assert_eq!(outlined_bodies(&modules), vec![long.clone(), short.clone()]);
+
+    let mut modules = vec![module_with("test", functions())];
+    let original = assemble_modules(&context, &modules, &entrypoints);
+    Outliner::new(&context.session)
+        .assume_inlined(long.clone())
+        .run(&mut modules)
+        .unwrap();
+    assert_eq!(outlined_bodies(&modules), vec![short.clone()]);
+    for function in modules[0].functions().filter(|f| f.name.function.as_str() < "d") {
+        let ops = function.body.block(function.body.body).ops.iter().map(|op| op.into_inner());
+        assert_eq!(ops.skip(1).collect::<Vec<_>>(), long);
+    }
+
+    let optimized = assemble_modules(&context, &modules, &entrypoints);
+    assert_eq!(execute_assembled(&optimized), execute_assembled(&original));
+}
+
+/// Test that a realistic helper sequence, the initialization of a table of constants in memory,
+/// is outlined when it is repeated in functions of different modules, and that the procedures
+/// outlined into each module share their MAST.
+#[test]
+fn outliner_across_modules() {
+    let context = TestContext::default();
+    let span = SourceSpan::UNKNOWN;
+
+    // Write a table of 48 words of constants to memory, starting at address 1000. This is only a
+    // few hundred operations, but with most of them pushing an immediate, it is assembled to more
+    // batches than a procedure may have before it is no longer inlined
+    let init_table = (0..48u32).flat_map(|i| {
+        let word = [4 * i + 2, 4 * i + 3, 4 * i + 4, 4 * i + 5].map(|n| Felt::new(n as u64));
+        [Op::Pushw(word), Op::PushU32(1000 + i), Op::MemStorew, Op::Dropw]
+    });
+    let function = |name: &str, entry: u32| {
+        let mut function = Function::new(name.parse().unwrap(), Signature::new(vec![], vec![]));
+        function.signature.linkage = Linkage::External;
+        let body = function.body_mut();
+        for op in init_table.clone() {
+            body.push(op, span);
+        }
+        body.push(Op::PushU32(1000 + entry), span);
+        body.push(Op::MemLoad, span);
+        function
+    };
+    let mut modules = vec![
+        module_with("a", [function("a::f", 1), function("a::g", 2)]),
+        module_with("b", [function("b::h", 3)]),
+    ];
+    let entrypoints = ["a::f", "a::g", "b::h"];
+
+    let original = assemble_modules(&context, &modules, &entrypoints);
+    Outliner::new(&context.session).run(&mut modules).unwrap();
+    for module in modules.iter() {
+        let callee = FunctionIdent {
+            module: module.id,
+            function: hir::Ident::with_empty_span(hir::Symbol::intern("outlined_0")),
+        };
+        assert!(
+            module.functions().any(|f| f.name == callee),
+            "expected {callee} in {}",
+            module.id
+        );
+        for function in module.functions().filter(|f| f.name != callee) {
+            let ops = function.body.block(function.body.body).ops.iter().map(|op| op.into_inner());
+            assert_eq!(ops.take(1).collect::<Vec<_>>(), vec![Op::Exec(callee)]);
+        }
+    }
+
+    let optimized = assemble_modules(&context, &modules, &entrypoints);
+    let outputs = execute_assembled(&optimized);
+    assert_eq!(outputs, execute_assembled(&original));
+    assert_eq!(outputs[..3], [Felt::new(14), Felt::new(10), Felt::new(6)]);
+
+    use miden_core::utils::Serializable;
+    let original_size = original.mast_forest().to_bytes().len();
+    let optimized_size = optimized.mast_forest().to_bytes().len();
+    assert!(
+        optimized_size < original_size,
+        "expected outlining to reduce the size of the MAST, but it went from {original_size} to \
+         {optimized_size} bytes"
+    );
+}
+
+/// Test the static cycle estimates computed for procedures with control flow and calls
+#[test]
+fn cycle_estimate() {
+    use midenc_session::{Emit, OutputMode, OutputType};
+
+    let span = SourceSpan::UNKNOWN;
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new("test").unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+
+    // 2 cycles
+    let mut helper = Function::new("test::helper".parse().unwrap(), Signature::new(vec![], vec![]));
+    helper.signature.linkage = Linkage::Internal;
+    helper.body_mut().push(Op::PushU8(2), span);
+    helper.body_mut().push(Op::Add, span);
+    module.push_back(Box::new(helper));
+
+    let mut main = Function::new("test::main".parse().unwrap(), Signature::new(vec![], vec![]));
+    main.signature.linkage = Linkage::External;
+    let then_blk = main.create_block();
+    main.block_mut(then_blk).push(Op::Add, span);
+    let else_blk = main.create_block();
+    main.block_mut(else_blk).push(Op::Sub, span);
+    main.block_mut(else_blk).push(Op::Sub, span);
+    let repeat_blk = main.create_block();
+    main.block_mut(repeat_blk).push(Op::Drop, span);
+    let loop_blk = main.create_block();
+    main.block_mut(loop_blk).push(Op::Exec("std::foo::bar".parse().unwrap()), span);
+    main.block_mut(loop_blk).push(Op::Dup(0), span);
+    let body = main.body_mut();
+    body.push(Op::Dup(0), span);
+    body.push(Op::If(then_blk, else_blk), span);
+    body.push(Op::Exec("test::helper".parse().unwrap()), span);
+    body.push(Op::Repeat(3, repeat_blk), span);
+    body.push(Op::While(loop_blk), span);
+    module.push_back(Box::new(main));
+
+    let mut recursive =
+        Function::new("test::recursive".parse().unwrap(), Signature::new(vec![], vec![]));
+    recursive.signature.linkage = Linkage::External;
+    recursive.body_mut().push(Op::Exec("test::recursive".parse().unwrap()), span);
+    module.push_back(Box::new(recursive));
+
+    let mut analysis = CycleAnalysis::new([module.as_ref()]);
+    let estimate = analysis.estimate("test::main".parse().unwrap()).unwrap();
+    // dup + if + cheapest branch + exec + helper + 3 * drop + while
+    assert_eq!(estimate.min, 1 + 12 + 1 + 2 + 2 + 3 + 12);
+    assert_eq!(estimate.max.constant, 1 + 12 + 4 + 2 + 2 + 3 + 12);
+    assert_eq!(estimate.max.loops.len(), 1);
+    assert_eq!(estimate.max.loops[0].body, SymbolicCycles::new(3));
+    assert!(!estimate.is_exact());
+    assert_eq!(estimate.to_string(), "33..=36 + n0 * (3)");
+
+    let report = CycleReport::new([module.as_ref()], |_| true);
+    assert_eq!(
+        report.to_string(),
+        "test::main: 33..=36 + n0 * (3)\n  excluding calls to: std::foo::bar\ntest::recursive: \
+         2\n  excluding calls to: test::recursive\n"
+    );
+
+    // The same report is emitted as JSON for `--emit=cycles-json`
+    let context = TestContext::default();
+    let json = report.as_json();
+    assert_eq!(json.output_type(OutputMode::Text), OutputType::CyclesJson);
+    let mut buf = vec![];
+    json.write_to(&mut buf, OutputMode::Text, &context.session).unwrap();
+    let json = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+    assert_eq!(json["procedures"][0]["name"], "test::main");
+    assert_eq!(json["procedures"][1]["name"], "test::recursive");
+}
+
+/// A sample of each instruction, with representative immediates, for tests which must cover
+/// every instruction
+fn sample_instructions() -> Vec<Op> {
+    use cranelift_entity::EntityRef;
+    use midenc_hir::LocalId;
+
+    let felt = Felt::new;
+    let local = LocalId::new;
+    let mut ops = vec![];
+    ops.extend((0..16).map(Op::Dup));
+    ops.extend((1..16).map(Op::Swap));
+    ops.extend((2..16).flat_map(|n| [Op::Movup(n), Op::Movdn(n)]));
+    ops.extend((0..4).map(Op::Dupw));
+    ops.extend((1..4).map(Op::Swapw));
+    ops.extend((2..4).flat_map(|n| [Op::Movupw(n), Op::Movdnw(n)]));
+    ops.extend([0, 1, 2, 1 << 40].map(|v| Op::Push(felt(v))));
+    ops.extend([0, 1, 300].map(Op::PushU16));
+    ops.extend([Op::Push2([felt(0), felt(1)]), Op::Pushw([felt(0), felt(1), felt(2), felt(3)])]);
+    ops.extend((0..4).flat_map(|id| {
+        [
+            Op::LocAddr(local(id)),
+            Op::LocStore(local(id)),
+            Op::LocStorew(local(id)),
+            Op::LocLoad(local(id)),
+            Op::LocLoadw(local(id)),
+        ]
+    }));
+    ops.extend([0, 1, 5].into_iter().flat_map(|addr| {
+        [
+            Op::MemLoadImm(addr),
+            Op::MemLoadwImm(addr),
+            Op::MemStoreImm(addr),
+            Op::MemStorewImm(addr),
+        ]
+    }));
+    ops.extend([0, 1, 5].into_iter().flat_map(|v| {
+        [
+            Op::AddImm(felt(v)),
+            Op::SubImm(felt(v)),
+            Op::MulImm(felt(v)),
+            Op::EqImm(felt(v)),
+            Op::NeqImm(felt(v)),
+            Op::GtImm(felt(v)),
+            Op::GteImm(felt(v)),
+            Op::LtImm(felt(v)),
+            Op::LteImm(felt(v)),
+        ]
+    }));
+    ops.extend(
+        [false, true]
+            .into_iter()
+            .flat_map(|b| [Op::AndImm(b), Op::OrImm(b), Op::XorImm(b)]),
+    );
+    ops.extend((0..=64).map(Op::ExpImm));
+    ops.extend([0, 1, 16, 64].map(Op::ExpBitLength));
+    ops.extend([0, 1, 5].into_iter().flat_map(|v| {
+        [
+            Op::U32OverflowingAddImm(v),
+            Op::U32WrappingAddImm(v),
+            Op::U32OverflowingSubImm(v),
+            Op::U32WrappingSubImm(v),
+            Op::U32OverflowingMulImm(v),
+            Op::U32WrappingMulImm(v),
+            Op::U32LtImm(v),
+            Op::U32LteImm(v),
+            Op::U32GtImm(v),
+            Op::U32GteImm(v),
+            Op::U32MinImm(v),
+            Op::U32MaxImm(v),
+        ]
+    }));
+    ops.extend(
+        [1, 5]
+            .into_iter()
+            .flat_map(|v| [Op::U32DivImm(v), Op::U32ModImm(v), Op::U32DivModImm(v)]),
+    );
+    ops.extend(
+        [0, 1, 31].into_iter().flat_map(|v| {
+            [Op::U32ShlImm(v), Op::U32ShrImm(v), Op::U32RotlImm(v), Op::U32RotrImm(v)]
+        }),
+    );
+    ops.extend((1..4).map(Op::AdvPush));
+    ops.extend([
+        Op::Add,
+        Op::Sub,
+        Op::Mul,
+        Op::Div,
+        Op::Neg,
+        Op::Inv,
+        Op::Incr,
+        Op::Exp,
+        Op::Ilog2,
+        Op::Pow2,
+        Op::Eq,
+        Op::Neq,
+        Op::Gt,
+        Op::Gte,
+        Op::Lt,
+        Op::Lte,
+        Op::IsOdd,
+        Op::Not,
+        Op::And,
+        Op::Or,
+        Op::Xor,
+        Op::Assertz,
+        Op::AssertzWithError(1),
+        Op::Sdepth,
+        Op::Clk,
+        Op::Nop,
+        Op::Emit(1),
+        Op::Trace(1),
+        Op::Breakpoint,
+        Op::DebugStack,
+        Op::AdvInjectPushU64Div,
+        Op::AdvPipe,
+        Op::AdvLoadw,
+        Op::MemStream,
+        Op::Hash,
+        Op::Hmerge,
+        Op::Hperm,
+        Op::MtreeGet,
+        Op::MtreeSet,
+        Op::MtreeMerge,
+        Op::MtreeVerify,
+        Op::FriExt2Fold4,
+        Op::RCombBase,
+        Op::U32OverflowingAdd3,
+        Op::U32WrappingAdd3,
+        Op::U32OverflowingMadd,
+        Op::U32WrappingMadd,
+        Op::U32Lt,
+        Op::U32Div,
+        Op::U32Mod,
+        Op::U32Shl,
+        Op::U32Rotr,
+        Op::U32Popcnt,
+        Op::U32Clz,
+    ]);
+
+    ops
+}
+
+/// Verify that [Op::cost] matches the number of VM operations each instruction assembles to
+#[test]
+fn cycle_costs_match_vm() {
+    let ops = sample_instructions();
+
+    // Assemble `op` as the body of a procedure, and count the operations it produced, ignoring
+    // the padding inserted by the assembler, which makes `nop` itself invisible
+    let context = TestContext::default();
+    let measure = |op: Option<Op>| -> usize {
+        let mut function =
+            Function::new("test::probe".parse().unwrap(), Signature::new(vec![], vec![]));
+        function.signature.linkage = Linkage::External;
+        function.alloc_n_locals(4);
+        if let Some(op) = op {
+            function.body_mut().push(op, SourceSpan::UNKNOWN);
+        }
+        let mut module = Box::new(Module::new(
+            miden_assembly::LibraryPath::new("test").unwrap(),
+            miden_assembly::ast::ModuleKind::Library,
+        ));
+        module.push_back(Box::new(function));
+        let program = miden_assembly::Assembler::new(context.session.source_manager.clone())
+            .with_module(module.to_ast(false).unwrap())
+            .unwrap()
+            .assemble_program("begin exec.::test::probe end")
+            .unwrap();
+        let ops = program
+            .mast_forest()
+            .nodes()
+            .iter()
+            .map(|node| match node {
+                miden_core::mast::MastNode::Block(block) => block
+                    .op_batches()
+                    .iter()
+                    .flat_map(|batch| batch.ops())
+                    .filter(|op| !matches!(op, miden_core::Operation::Noop))
+                    .count(),
+                _ => 0,
+            })
+            .sum::<usize>();
+        ops + usize::from(matches!(op, Some(Op::Nop)))
+    };
+
+    let base = measure(None);
+    let mismatches = ops
+        .into_iter()
+        .filter_map(|op| {
+            let measured = measure(Some(op)) - base;
+            (op.cost() != measured)
+                .then(|| format!("{op:?}: expected {measured}, got {}", op.cost()))
+        })
+        .collect::<Vec<_>>();
+    assert!(mismatches.is_empty(), "incorrect instruction costs:\n{}", mismatches.join("\n"));
+}
+
+/// Test that locals whose lifetimes do not overlap share a procedure local, and that locals which
+/// are live at the same time do not
+#[test]
+fn codegen_local_slots() {
+    let mut harness = TestByEmulationHarness::default();
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("test");
+    // a + b + a, where `a` is kept in a local across the use of three short-lived locals
+    let id = {
+        let mut fb = mb
+            .function(
+                "add_twice",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let (a, b) = {
+            let args = fb.block_params(fb.current_block());
+            (args[0], args[1])
+        };
+        let saved = fb.data_flow_graph_mut().alloc_local(Type::U32);
+        let x = fb.data_flow_graph_mut().alloc_local(Type::U32);
+        let y = fb.data_flow_graph_mut().alloc_local(Type::U32);
+        let wide = fb.data_flow_graph_mut().alloc_local(Type::U64);
+        fb.ins().store_local(saved, a, SourceSpan::UNKNOWN);
+        fb.ins().store_local(x, a, SourceSpan::UNKNOWN);
+        let a1 = fb.ins().load_local(x, SourceSpan::UNKNOWN);
+        fb.ins().store_local(y, b, SourceSpan::UNKNOWN);
+        let b1 = fb.ins().load_local(y, SourceSpan::UNKNOWN);
+        let sum = fb.ins().add_wrapping(a1, b1, SourceSpan::UNKNOWN);
+        let sum = fb.ins().zext(sum, Type::U64, SourceSpan::UNKNOWN);
+        fb.ins().store_local(wide, sum, SourceSpan::UNKNOWN);
+        let sum = fb.ins().load_local(wide, SourceSpan::UNKNOWN);
+        let sum = fb.ins().trunc(sum, Type::U32, SourceSpan::UNKNOWN);
+        let a2 = fb.ins().load_local(saved, SourceSpan::UNKNOWN);
+        let result = fb.ins().add_wrapping(sum, a2, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+    let locals = program
+        .get("test")
+        .unwrap()
+        .functions()
+        .find(|function| function.name == id)
+        .unwrap()
+        .locals()
+        .iter()
+        .map(|local| local.ty.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(locals, vec![Type::U32, Type::U64]);
+
+    let mut stack = harness
+        .execute_program(program.freeze(), &[Felt::new(5), Felt::new(7)])
+        .expect("execution failed");
+    assert_eq!(stack.len(), 1);
+    assert_eq!(stack.pop().map(|e| e.as_int()), Some(17));
+}
+
+/// Test that `call` executes the callee in a fresh memory context, with only the top 16 elements
+/// of the operand stack visible to it, and restores the context of the caller on return
+#[test]
+fn emulator_call_context_switch() {
+    let span = SourceSpan::UNKNOWN;
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new("test").unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+
+    // Adds the contents of address 100 to its argument, which is always zero in a new context,
+    // then overwrites that address
+    let mut callee = Function::new("test::callee".parse().unwrap(), Signature::new(vec![], vec![]));
+    callee.signature.linkage = Linkage::External;
+    let body = callee.body_mut();
+    body.push(Op::MemLoadImm(100), span);
+    body.push(Op::Add, span);
+    body.push(Op::PushU32(11), span);
+    body.push(Op::MemStoreImm(100), span);
+    module.push_back(Box::new(callee));
+
+    let mut main = Function::new("test::main".parse().unwrap(), Signature::new(vec![], vec![]));
+    main.signature.linkage = Linkage::External;
+    let body = main.body_mut();
+    body.push(Op::PushU32(7), span);
+    body.push(Op::MemStoreImm(100), span);
+    // An element below the frame of the callee, which must not be visible to it
+    body.push(Op::PushU32(99), span);
+    body.push_n(15, Op::PushU32(0), span);
+    body.push(Op::PushU32(5), span);
+    body.push(Op::Call("test::callee".parse().unwrap()), span);
+    body.push(Op::MemLoadImm(100), span);
+    module.push_back(Box::new(main));
+
+    let mut harness = TestByEmulationHarness::default();
+    harness.emulator.load_module(module.freeze()).expect("failed to load module");
+    let stack = harness
+        .invoke("test::main".parse().unwrap(), &[])
+        .expect("execution failed")
+        .stack()
+        .iter()
+        .rev()
+        .map(|felt| felt.as_int())
+        .collect::<Vec<_>>();
+    let mut expected = vec![7, 5];
+    expected.extend([0; 15]);
+    expected.push(99);
+    assert_eq!(stack, expected);
+
+    // A callee may not return with more than 16 elements on the operand stack
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new("test").unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+    let mut callee = Function::new("test::callee".parse().unwrap(), Signature::new(vec![], vec![]));
+    callee.signature.linkage = Linkage::External;
+    callee.body_mut().push(Op::PushU32(1), span);
+    module.push_back(Box::new(callee));
+    let mut main = Function::new("test::main".parse().unwrap(), Signature::new(vec![], vec![]));
+    main.signature.linkage = Linkage::External;
+    main.body_mut().push_n(4, Op::Padw, span);
+    main.body_mut().push(Op::Call("test::callee".parse().unwrap()), span);
+    module.push_back(Box::new(main));
+
+    harness.reset();
+    harness.emulator.load_module(module.freeze()).expect("failed to load module");
+    assert_eq!(
+        harness.invoke("test::main".parse().unwrap(), &[]).err(),
+        Some(EmulationError::InvalidStackDepthOnReturn("test::callee".parse().unwrap(), 17))
+    );
+}
+
+/// Test that calls to functions using the canonical ABI are lowered to `call`, with the arguments
+/// and results marshalled through a 16-element frame on the operand stack
+#[test]
+fn codegen_cross_context_call() {
+    let harness = TestByEmulationHarness::default();
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+
+    let mut add_sig = Signature::new(
+        [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+        [AbiParam::new(Type::U32)],
+    );
+    add_sig.cc = CallConv::Wasm;
+    let mut seven_sig = Signature::new([], [AbiParam::new(Type::U32)]);
+    seven_sig.cc = CallConv::Wasm;
+
+    // The callees belong to another component
+    let mut mb = builder.module("adder");
+    {
+        let mut fb = mb.function("add", add_sig.clone()).expect("unexpected symbol conflict");
+        let (a, b) = {
+            let args = fb.block_params(fb.current_block());
+            (args[0], args[1])
+        };
+        let sum = fb.ins().add_wrapping(a, b, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(sum), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function");
+    }
+    {
+        let mut fb = mb.function("seven", seven_sig.clone()).expect("unexpected symbol conflict");
+        let seven = fb.ins().u32(7, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(seven), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function");
+    }
+    mb.build().expect("unexpected error constructing test module");
+
+    // main(a, b) = add(a, b) + seven()
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function(
+                "main",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let add = fb.import_function("adder", "add", add_sig).unwrap();
+        let seven = fb.import_function("adder", "seven", seven_sig).unwrap();
+        let (a, b) = {
+            let args = fb.block_params(fb.current_block());
+            (args[0], args[1])
+        };
+        let call = fb.ins().call(add, &[a, b], SourceSpan::UNKNOWN);
+        let sum = fb.first_result(call);
+        let call = fb.ins().call(seven, &[], SourceSpan::UNKNOWN);
+        let seven = fb.first_result(call);
+        let result = fb.ins().add_wrapping(sum, seven, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+    let main = program.get("test").unwrap().functions().next().unwrap();
+    let invoked = main
+        .body
+        .block(main.body.body)
+        .ops
+        .iter()
+        .map(|op| op.into_inner())
+        .filter(|op| matches!(op, Op::Exec(_) | Op::Call(_)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        invoked,
+        vec![
+            Op::Call("adder::add".parse().unwrap()),
+            Op::Call("adder::seven".parse().unwrap())
+        ]
+    );
+
+    // The callees initialize the heap of their context on entry, which the emulator does not
+    // support, so we execute the program on the VM
+    let stdlib = miden_stdlib::StdLibrary::default();
+    let stdlib = AsRef::<miden_assembly::Library>::as_ref(&stdlib);
+    let mut program = program;
+    program.link_library(stdlib.clone());
+    let assembled = program.assemble(&harness.context.session).expect("failed to assemble");
+    let mut host = miden_processor::DefaultHost::default();
+    host.load_mast_forest(stdlib.mast_forest().clone());
+    let stack_inputs = miden_core::StackInputs::new(vec![Felt::new(4), Felt::new(5)]).unwrap();
+    let trace = miden_processor::execute(&assembled, stack_inputs, host, Default::default())
+        .expect("execution failed");
+    assert_eq!(trace.stack_outputs().stack()[0].as_int(), 16);
+}
+
+/// Test that the arguments and results of a cross-context call which do not fit on the operand
+/// stack are passed through memory, and copied between the memories of the caller and callee
+#[test]
+fn codegen_cross_context_call_through_memory() {
+    let harness = TestByEmulationHarness::default();
+    let span = SourceSpan::UNKNOWN;
+    let stdlib = miden_stdlib::StdLibrary::default();
+    let stdlib = AsRef::<miden_assembly::Library>::as_ref(&stdlib);
+
+    // sum(a0, .., a16) -> (a0 + .. + a16, a0), as seen by callers in other components
+    let lifted_sig = Signature::new(
+        (0..17).map(|_| AbiParam::new(Type::U32)),
+        [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+    );
+    // The core function takes a pointer to its arguments, and returns a pointer to its results
+    let mut core_sig = Signature::new([AbiParam::new(Type::U32)], [AbiParam::new(Type::U32)]);
+    core_sig.cc = CallConv::Wasm;
+    // The caller passes a pointer to its arguments, and a pointer to which the results are written
+    let args_ty = Type::Struct(StructType::new((0..17).map(|_| Type::U32)));
+    let results_ty = Type::Struct(StructType::new([Type::U32, Type::U32]));
+    let mut import_sig = Signature::new(
+        [
+            AbiParam::new(Type::Ptr(Box::new(args_ty))),
+            AbiParam::sret(Type::Ptr(Box::new(results_ty))),
+        ],
+        [],
+    );
+    import_sig.cc = CallConv::Wasm;
+
+    // The callee belongs to another component, which is compiled separately, as the signature
+    // of its core function differs from the one seen by callers
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    let mut mb = builder.module("adder");
+    mb.with_export_signature("sum", lifted_sig);
+    {
+        let mut fb = mb.function("sum", core_sig).expect("unexpected symbol conflict");
+        let args = fb.block_params(fb.current_block())[0];
+        let mut sum = fb.ins().u32(0, span);
+        let mut first = None;
+        for i in 0..17 {
+            let offset = fb.ins().u32(i * 4, span);
+            let addr = fb.ins().add_unchecked(args, offset, span);
+            let ptr = fb.ins().inttoptr(addr, Type::Ptr(Box::new(Type::U32)), span);
+            let arg = fb.ins().load(ptr, span);
+            first.get_or_insert(arg);
+            sum = fb.ins().add_wrapping(sum, arg, span);
+        }
+        let results = fb.ins().u32(1024, span);
+        let ptr = fb.ins().inttoptr(results, Type::Ptr(Box::new(Type::U32)), span);
+        fb.ins().store(ptr, sum, span);
+        let addr = fb.ins().u32(1028, span);
+        let ptr = fb.ins().inttoptr(addr, Type::Ptr(Box::new(Type::U32)), span);
+        fb.ins().store(ptr, first.unwrap(), span);
+        fb.ins().ret(Some(results), span);
+        fb.build().expect("unexpected error building function");
+    }
+    mb.build().expect("unexpected error constructing test module");
+    let adder = builder.link().expect("failed to link library");
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let MasmArtifact::Library(mut adder) = compiler.compile(adder).expect("compilation failed")
+    else {
+        panic!("expected a library");
+    };
+    adder.link_library(stdlib.clone());
+    let adder = adder.assemble(&harness.context.session).expect("failed to assemble");
+
+    // main(a) = sum(a, a + 1, .., a + 16), with the arguments and results at addresses which are
+    // not word-aligned
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+    builder.add_library((*adder).clone());
+    builder.add_extern_module("adder".into(), ["sum".into()]).unwrap();
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function(
+                "main",
+                Signature::new([AbiParam::new(Type::U32)], [AbiParam::new(Type::U32)]),
+            )
+            .expect("unexpected symbol conflict");
+        let sum = fb.import_function("adder", "sum", import_sig.clone()).unwrap();
+        let a = fb.block_params(fb.current_block())[0];
+        for i in 0..17 {
+            let addr = fb.ins().u32(132 + i * 4, span);
+            let ptr = fb.ins().inttoptr(addr, Type::Ptr(Box::new(Type::U32)), span);
+            let value = fb.ins().add_imm_wrapping(a, Immediate::U32(i), span);
+            fb.ins().store(ptr, value, span);
+        }
+        let args = fb.ins().u32(132, span);
+        let args = fb.ins().inttoptr(args, import_sig.params[0].ty.clone(), span);
+        let retptr = fb.ins().u32(1004, span);
+        let retptr = fb.ins().inttoptr(retptr, import_sig.params[1].ty.clone(), span);
+        fb.ins().call(sum, &[args, retptr], span);
+        let mut results = vec![];
+        for addr in [1004, 1008] {
+            let addr = fb.ins().u32(addr, span);
+            let ptr = fb.ins().inttoptr(addr, Type::Ptr(Box::new(Type::U32)), span);
+            results.push(fb.ins().load(ptr, span));
+        }
+        // Return `sum * 100 + first`, to check that both results arrived in order
+        let scaled = fb.ins().mul_imm_wrapping(results[0], Immediate::U32(100), span);
+        let result = fb.ins().add_wrapping(scaled, results[1], span);
+        fb.ins().ret(Some(result), span);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let program = compiler.compile(program).expect("compilation failed").unwrap_executable();
+
+    // The callee initializes the heap of its context on entry, which the emulator does not
+    // support, so we execute the program on the VM
+    let mut program = program;
+    program.link_library(stdlib.clone());
+    let assembled = program.assemble(&harness.context.session).expect("failed to assemble");
+    let mut host = miden_processor::DefaultHost::default();
+    host.load_mast_forest(stdlib.mast_forest().clone());
+    host.load_mast_forest(adder.mast_forest().clone());
+    let stack_inputs = miden_core::StackInputs::new(vec![Felt::new(1)]).unwrap();
+    let trace = miden_processor::execute(&assembled, stack_inputs, host, Default::default())
+        .expect("execution failed");
+    // 1 + 2 + .. + 17 = 153
+    assert_eq!(trace.stack_outputs().stack()[0].as_int(), 15301);
+}
+
+/// Test that a call to a function exported using the canonical ABI, from within its own module, is
+/// rejected, rather than being lowered to `exec`
+#[test]
+fn codegen_internal_call_to_canonical_abi_export() {
+    let harness = TestByEmulationHarness::default();
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+
+    let mut add_sig = Signature::new(
+        [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+        [AbiParam::new(Type::U32)],
+    );
+    add_sig.cc = CallConv::Wasm;
+
+    let mut mb = builder.module("adder");
+    {
+        let mut fb = mb.function("add", add_sig.clone()).expect("unexpected symbol conflict");
+        let (a, b) = {
+            let args = fb.block_params(fb.current_block());
+            (args[0], args[1])
+        };
+        let sum = fb.ins().add_wrapping(a, b, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(sum), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function");
+    }
+    // double(a) = add(a, a), where the call is made as it would be by the frontend, i.e. using
+    // the calling convention of the core function
+    let id = {
+        let mut fb = mb
+            .function(
+                "double",
+                Signature::new([AbiParam::new(Type::U32)], [AbiParam::new(Type::U32)]),
+            )
+            .expect("unexpected symbol conflict");
+        let add = fb
+            .import_function(
+                "adder",
+                "add",
+                Signature::new(
+                    [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .unwrap();
+        let a = fb.block_params(fb.current_block())[0];
+        let call = fb.ins().call(add, &[a, a], SourceSpan::UNKNOWN);
+        let result = fb.first_result(call);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let err = compiler.compile(program).err().expect("expected compilation to fail");
+    assert_eq!(err.to_string(), "invalid call");
+}
+
+/// Test that a call using the canonical ABI which would require passing values through memory is
+/// rejected, as only the flat subset of the canonical ABI is supported
+#[test]
+fn codegen_cross_context_call_by_reference() {
+    let harness = TestByEmulationHarness::default();
+    let mut builder = ProgramBuilder::new(&harness.context.session.diagnostics);
+
+    let mut len_sig =
+        Signature::new([AbiParam::new(Type::Ptr(Box::new(Type::U8)))], [AbiParam::new(Type::U32)]);
+    len_sig.cc = CallConv::Wasm;
+
+    let mut mb = builder.module("strings");
+    {
+        let mut fb = mb.function("len", len_sig.clone()).expect("unexpected symbol conflict");
+        let len = fb.ins().u32(0, SourceSpan::UNKNOWN);
+        fb.ins().ret(Some(len), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function");
+    }
+    mb.build().expect("unexpected error constructing test module");
+
+    let mut mb = builder.module("test");
+    let id = {
+        let mut fb = mb
+            .function(
+                "main",
+                Signature::new(
+                    [AbiParam::new(Type::Ptr(Box::new(Type::U8)))],
+                    [AbiParam::new(Type::U32)],
+                ),
+            )
+            .expect("unexpected symbol conflict");
+        let len = fb.import_function("strings", "len", len_sig).unwrap();
+        let s = fb.block_params(fb.current_block())[0];
+        let call = fb.ins().call(len, &[s], SourceSpan::UNKNOWN);
+        let result = fb.first_result(call);
+        fb.ins().ret(Some(result), SourceSpan::UNKNOWN);
+        fb.build().expect("unexpected error building function")
+    };
+    mb.build().expect("unexpected error constructing test module");
+    let program = builder.with_entrypoint(id).link().expect("failed to link program");
+
+    let mut compiler = MasmCompiler::new(&harness.context.session);
+    let err = compiler.compile(program).err().expect("expected compilation to fail");
+    assert_eq!(err.to_string(), "invalid call");
+}
+
+/// Test that lowering MASM directly to MAST produces the same procedure roots as assembling it
+/// from Miden Assembly syntax
+#[test]
+fn mast_lowering_matches_assembler() {
+    use cranelift_entity::EntityRef;
+    use midenc_hir::LocalId;
+
+    let mut context = TestContext::default();
+    let span = SourceSpan::UNKNOWN;
+    let stdlib = miden_stdlib::StdLibrary::default();
+    let stdlib = AsRef::<miden_assembly::Library>::as_ref(&stdlib);
+    let mut library = Library::empty();
+    library.link_library(stdlib.clone());
+
+    // One procedure per instruction
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new("test").unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+    for (i, op) in sample_instructions().into_iter().enumerate() {
+        let name = format!("test::probe{i}").parse().unwrap();
+        let mut function = Function::new(name, Signature::new(vec![], vec![]));
+        function.signature.linkage = Linkage::External;
+        function.alloc_n_locals(4);
+        function.body_mut().push(op, span);
+        module.push_back(Box::new(function));
+    }
+
+    // A private helper which is large enough not to be merged into its callers
+    let mut helper = Function::new("test::helper".parse().unwrap(), Signature::new([], []));
+    helper.body_mut().push_n(400, Op::U32Popcnt, span);
+    module.push_back(Box::new(helper));
+
+    // Control flow and invocations of local and external procedures
+    let helper = "test::helper".parse().unwrap();
+    let mut function = Function::new("test::control".parse().unwrap(), Signature::new([], []));
+    function.signature.linkage = Linkage::External;
+    function.alloc_n_locals(1);
+    let then_blk = function.create_block();
+    let else_blk = function.create_block();
+    let loop_blk = function.create_block();
+    let repeat_blk = function.create_block();
+    function.block_mut(then_blk).push(Op::Exec(helper), span);
+    function.block_mut(loop_blk).push_n(3, Op::Drop, span);
+    function.block_mut(loop_blk).push(Op::Call(helper), span);
+    function.block_mut(repeat_blk).push(Op::U32WrappingAddImm(5), span);
+    let body = function.body_mut();
+    body.push(Op::LocStore(LocalId::new(0)), span);
+    body.push(Op::If(then_blk, else_blk), span);
+    body.push(Op::While(loop_blk), span);
+    body.push(Op::Repeat(3, repeat_blk), span);
+    body.push(Op::ProcRef(helper), span);
+    body.push(Op::DynExec, span);
+    body.push(Op::DynCall, span);
+    body.push(Op::Exec("std::math::u64::wrapping_add".parse().unwrap()), span);
+    body.push_n(200, Op::Add, span);
+    body.push(Op::Exec(helper), span);
+    module.push_back(Box::new(function));
+    library.insert(module);
+
+    // Textual MASM, using re-exports
+    let source = context.session.source_manager.load(
+        "test/text.masm",
+        "export.::std::math::u64::overflowing_add\nexport.::test::control->reexported\n\nexport.\
+         add\n    exec.::std::math::u64::wrapping_add\nend\n"
+            .to_string(),
+    );
+    let path = miden_assembly::LibraryPath::new("test::text").unwrap();
+    let module = Module::parse(miden_assembly::ast::ModuleKind::Library, path, source).unwrap();
+    library.insert(Box::new(module));
+
+    // The intrinsics we ship
+    for name in ["intrinsics::mem", "intrinsics::i32", "intrinsics::i64", "intrinsics::f64"] {
+        let module = intrinsics::load(name, &context.session.source_manager).unwrap();
+        library.insert(Box::new(module));
+    }
+
+    let digests = |library: &miden_assembly::Library| {
+        library
+            .module_infos()
+            .flat_map(|module| {
+                let path = module.path().clone();
+                module
+                    .procedures()
+                    .map(move |(_, proc)| (format!("{path}::{}", &proc.name), proc.digest))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<std::collections::BTreeMap<_, _>>()
+    };
+
+    // Debug decorators must not affect the result
+    for debug in [midenc_session::DebugInfo::None, midenc_session::DebugInfo::Full] {
+        context.session.options.debug = debug;
+        let lowered = library.assemble(&context.session).expect("failed to lower library");
+        let assembled = library.assemble_from_ast(&context.session).expect("failed to assemble");
+        let lowered = digests(&lowered);
+        let assembled = digests(&assembled);
+        assert!(lowered.len() > 300);
+        assert_eq!(lowered.keys().collect::<Vec<_>>(), assembled.keys().collect::<Vec<_>>());
+        let mismatches = lowered
+            .iter()
+            .filter(|(name, digest)| assembled[*name] != **digest)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert!(
+            mismatches.is_empty(),
+            "MAST roots differ ({debug:?}) for:\n{}",
+            mismatches.join("\n")
+        );
+    }
+}
+
+/// A sequence of instructions used to check the emulator against the VM, along with the minimum
+/// depth of the operand stack it requires, its effect on that depth, and the number of elements it
+/// consumes from the advice stack provided as input.
+#[derive(Debug, Clone)]
+struct DifferentialSnippet {
+    ops: Vec<Op>,
+    required: usize,
+    effect: isize,
+    advice: usize,
+}
+
+impl DifferentialSnippet {
+    fn new(ops: Vec<Op>, required: usize, effect: isize) -> Self {
+        Self {
+            ops,
+            required,
+            effect,
+            advice: 0,
+        }
+    }
+
+    fn with_advice(mut self, advice: usize) -> Self {
+        self.advice = advice;
+        self
+    }
+}
+
+/// The number of words of memory the differential tests read from and write to
+const DIFFERENTIAL_MEMORY_WORDS: u32 = 16;
+
+/// The keys of the advice map entries available to the differential tests
+fn differential_map_keys() -> [[Felt; 4]; 4] {
+    core::array::from_fn(|i| Rpo256::hash_elements(&[Felt::new(i as u64)]).into())
+}
+
+/// The merkle tree available to the differential tests
+fn differential_merkle_tree() -> MerkleTree {
+    let leaves = (0..8u64)
+        .map(|i| Rpo256::hash_elements(&[Felt::new(i), Felt::ONE]).into())
+        .collect::<Vec<[Felt; 4]>>();
+    MerkleTree::new(leaves).unwrap()
+}
+
+fn push_felts(values: impl IntoIterator<Item = Felt>) -> Vec<Op> {
+    values.into_iter().map(Op::Push).collect()
+}
+
+fn push_word(word: [Felt; 4]) -> Op {
+    Op::Pushw(word)
+}
+
+/// Instructions which only permute, duplicate or discard elements of the operand stack, or which
+/// are valid for any field element operands
+fn differential_stack_snippet() -> impl Strategy<Value = DifferentialSnippet> {
+    let felt = any::<u64>().prop_map(Felt::new);
+    prop_oneof![
+        (0u8..16).prop_map(|n| DifferentialSnippet::new(vec![Op::Dup(n)], n as usize + 1, 1)),
+        (1u8..16).prop_map(|n| DifferentialSnippet::new(vec![Op::Swap(n)], n as usize + 1, 0)),
+        (2u8..16).prop_map(|n| DifferentialSnippet::new(vec![Op::Movup(n)], n as usize + 1, 0)),
+        (2u8..16).prop_map(|n| DifferentialSnippet::new(vec![Op::Movdn(n)], n as usize + 1, 0)),
+        (0u8..4).prop_map(|n| DifferentialSnippet::new(vec![Op::Dupw(n)], 4 * n as usize + 4, 4)),
+        (1u8..4).prop_map(|n| DifferentialSnippet::new(vec![Op::Swapw(n)], 4 * n as usize + 4, 0)),
+        (2u8..4).prop_map(|n| DifferentialSnippet::new(vec![Op::Movupw(n)], 4 * n as usize + 4, 0)),
+        (2u8..4).prop_map(|n| DifferentialSnippet::new(vec![Op::Movdnw(n)], 4 * n as usize + 4, 0)),
+        Just(DifferentialSnippet::new(vec![Op::Swapdw], 16, 0)),
+        Just(DifferentialSnippet::new(vec![Op::Drop], 1, -1)),
+        Just(DifferentialSnippet::new(vec![Op::Dropw], 4, -4)),
+        Just(DifferentialSnippet::new(vec![Op::Dropw, Op::Dropw], 8, -8)),
+        Just(DifferentialSnippet::new(vec![Op::Padw], 0, 4)),
+        felt.clone().prop_map(|a| DifferentialSnippet::new(vec![Op::Push(a)], 0, 1)),
+        (felt.clone(), felt.clone()).prop_map(|(a, b)| DifferentialSnippet::new(
+            vec![Op::Push2([a, b])],
+            0,
+            2
+        )),
+        prop::array::uniform4(felt.clone()).prop_map(|word| DifferentialSnippet::new(
+            vec![Op::Pushw(word)],
+            0,
+            4
+        )),
+        any::<u8>().prop_map(|a| DifferentialSnippet::new(vec![Op::PushU8(a)], 0, 1)),
+        any::<u16>().prop_map(|a| DifferentialSnippet::new(vec![Op::PushU16(a)], 0, 1)),
+        any::<u32>().prop_map(|a| DifferentialSnippet::new(vec![Op::PushU32(a)], 0, 1)),
+        any::<bool>().prop_map(|c| DifferentialSnippet::new(
+            vec![Op::PushU8(c as u8), Op::Cswap],
+            2,
+            0
+        )),
+        any::<bool>().prop_map(|c| DifferentialSnippet::new(
+            vec![Op::PushU8(c as u8), Op::Cdrop],
+            2,
+            -1
+        )),
+        any::<bool>().prop_map(|c| DifferentialSnippet::new(
+            vec![Op::PushU8(c as u8), Op::Cswapw],
+            8,
+            0
+        )),
+        any::<bool>().prop_map(|c| DifferentialSnippet::new(
+            vec![Op::PushU8(c as u8), Op::Cdropw],
+            8,
+            -4
+        )),
+        Just(DifferentialSnippet::new(vec![Op::Sdepth], 0, 1)),
+        Just(DifferentialSnippet::new(vec![Op::Dup(0), Op::Dup(0), Op::AssertEq], 1, 0)),
+        (0u32..3).prop_map(|code| DifferentialSnippet::new(
+            vec![Op::Dup(0), Op::Dup(0), Op::AssertEqWithError(code)],
+            1,
+            0
+        )),
+        Just(DifferentialSnippet::new(vec![Op::Dupw(0), Op::Dupw(0), Op::AssertEqw], 4, 0)),
+        (0u32..3).prop_map(|code| {
+            DifferentialSnippet::new(
+                vec![Op::Dupw(0), Op::Dupw(0), Op::AssertEqwWithError(code)],
+                4,
+                0,
+            )
+        }),
+        Just(DifferentialSnippet::new(vec![Op::PushU8(1), Op::Assert], 0, 0)),
+        Just(DifferentialSnippet::new(vec![Op::PushU8(0), Op::Assertz], 0, 0)),
+        (0u32..3).prop_map(|code| {
+            DifferentialSnippet::new(vec![Op::PushU8(1), Op::AssertWithError(code)], 0, 0)
+        }),
+        (0u32..3).prop_map(|code| {
+            DifferentialSnippet::new(vec![Op::PushU8(0), Op::AssertzWithError(code)], 0, 0)
+        }),
+    ]
+}
+
+/// Field arithmetic and comparison instructions, with operands chosen to be valid for them
+fn differential_field_snippet() -> impl Strategy<Value = DifferentialSnippet> {
+    let felt = any::<u64>().prop_map(Felt::new);
+    let nonzero = (1..=u64::MAX).prop_map(Felt::new).prop_filter("nonzero", |f| *f != Felt::ZERO);
+    let unary = prop::sample::select(vec![Op::Neg, Op::Incr, Op::IsOdd, Op::U32Cast]);
+    let binary = prop::sample::select(vec![
+        Op::Add,
+        Op::Sub,
+        Op::Mul,
+        Op::Eq,
+        Op::Neq,
+        Op::Gt,
+        Op::Gte,
+        Op::Lt,
+        Op::Lte,
+    ]);
+    let with_imm: fn(Felt) -> Vec<Op> = |imm| {
+        vec![
+            Op::AddImm(imm),
+            Op::SubImm(imm),
+            Op::MulImm(imm),
+            Op::EqImm(imm),
+            Op::NeqImm(imm),
+            Op::GtImm(imm),
+            Op::GteImm(imm),
+            Op::LtImm(imm),
+            Op::LteImm(imm),
+        ]
+    };
+    let boolean = prop::sample::select(vec![Op::And, Op::Or, Op::Xor]);
+    let boolean_imm: fn(bool) -> Vec<Op> =
+        |imm| vec![Op::AndImm(imm), Op::OrImm(imm), Op::XorImm(imm)];
+    prop_oneof![
+        unary.prop_map(|op| DifferentialSnippet::new(vec![op], 1, 0)),
+        binary.prop_map(|op| DifferentialSnippet::new(vec![op], 2, -1)),
+        (felt.clone(), 0usize..9).prop_map(move |(imm, i)| DifferentialSnippet::new(
+            vec![with_imm(imm)[i]],
+            1,
+            0
+        )),
+        Just(DifferentialSnippet::new(vec![Op::Eqw], 8, 1)),
+        Just(DifferentialSnippet::new(vec![Op::U32Split], 1, 1)),
+        Just(DifferentialSnippet::new(vec![Op::U32Test], 1, 1)),
+        Just(DifferentialSnippet::new(vec![Op::U32Testw], 4, 1)),
+        nonzero
+            .clone()
+            .prop_map(|b| DifferentialSnippet::new(vec![Op::Push(b), Op::Div], 1, 0)),
+        nonzero
+            .clone()
+            .prop_map(|b| DifferentialSnippet::new(vec![Op::DivImm(b)], 1, 0)),
+        nonzero
+            .clone()
+            .prop_map(|a| DifferentialSnippet::new(vec![Op::Push(a), Op::Inv], 0, 1)),
+        nonzero
+            .clone()
+            .prop_map(|a| DifferentialSnippet::new(vec![Op::Push(a), Op::Ilog2], 0, 1)),
+        (0u8..64).prop_map(|a| DifferentialSnippet::new(vec![Op::PushU8(a), Op::Pow2], 0, 1)),
+        // The VM does not handle exponentiation of zero in debug builds, so the base is nonzero
+        (nonzero.clone(), felt.clone()).prop_map(|(a, b)| {
+            DifferentialSnippet::new(vec![Op::Push(a), Op::Push(b), Op::Exp], 0, 1)
+        }),
+        (nonzero.clone(), 0u8..=64).prop_map(|(a, b)| {
+            DifferentialSnippet::new(vec![Op::Push(a), Op::ExpImm(b)], 0, 1)
+        }),
+        (nonzero, 1u8..=64, any::<u64>()).prop_map(|(a, bits, b)| {
+            let b = if bits == 64 { b } else { b & ((1 << bits) - 1) };
+            let ops = vec![Op::Push(a), Op::Push(Felt::new(b)), Op::ExpBitLength(bits)];
+            DifferentialSnippet::new(ops, 0, 1)
+        }),
+        any::<bool>().prop_map(|a| DifferentialSnippet::new(
+            vec![Op::PushU8(a as u8), Op::Not],
+            0,
+            1
+        )),
+        (any::<bool>(), any::<bool>(), boolean).prop_map(|(a, b, op)| {
+            DifferentialSnippet::new(vec![Op::PushU8(a as u8), Op::PushU8(b as u8), op], 0, 1)
+        }),
+        (any::<bool>(), any::<bool>(), 0usize..3).prop_map(move |(a, imm, i)| {
+            DifferentialSnippet::new(vec![Op::PushU8(a as u8), boolean_imm(imm)[i]], 0, 1)
+        }),
+    ]
+}
+
+/// 32-bit integer instructions, with operands chosen to be valid for them
+fn differential_u32_snippet() -> impl Strategy<Value = DifferentialSnippet> {
+    // Binary operations, along with the number of results they produce
+    let binary = prop::sample::select(vec![
+        (Op::U32OverflowingAdd, 2),
+        (Op::U32WrappingAdd, 1),
+        (Op::U32OverflowingSub, 2),
+        (Op::U32WrappingSub, 1),
+        (Op::U32OverflowingMul, 2),
+        (Op::U32WrappingMul, 1),
+        (Op::U32And, 1),
+        (Op::U32Or, 1),
+        (Op::U32Xor, 1),
+        (Op::U32Lt, 1),
+        (Op::U32Lte, 1),
+        (Op::U32Gt, 1),
+        (Op::U32Gte, 1),
+        (Op::U32Min, 1),
+        (Op::U32Max, 1),
+    ]);
+    let with_imm: fn(u32) -> Vec<(Op, isize)> = |imm| {
+        vec![
+            (Op::U32OverflowingAddImm(imm), 2),
+            (Op::U32WrappingAddImm(imm), 1),
+            (Op::U32OverflowingSubImm(imm), 2),
+            (Op::U32WrappingSubImm(imm), 1),
+            (Op::U32OverflowingMulImm(imm), 2),
+            (Op::U32WrappingMulImm(imm), 1),
+            (Op::U32LtImm(imm), 1),
+            (Op::U32LteImm(imm), 1),
+            (Op::U32GtImm(imm), 1),
+            (Op::U32GteImm(imm), 1),
+            (Op::U32MinImm(imm), 1),
+            (Op::U32MaxImm(imm), 1),
+        ]
+    };
+    let division = prop::sample::select(vec![(Op::U32Div, 1), (Op::U32Mod, 1), (Op::U32DivMod, 2)]);
+    let division_imm: fn(u32) -> Vec<(Op, isize)> =
+        |imm| vec![(Op::U32DivImm(imm), 1), (Op::U32ModImm(imm), 1), (Op::U32DivModImm(imm), 2)];
+    let unary = prop::sample::select(vec![
+        Op::U32Not,
+        Op::U32Popcnt,
+        Op::U32Clz,
+        Op::U32Ctz,
+        Op::U32Clo,
+        Op::U32Cto,
+    ]);
+    let shift = prop::sample::select(vec![Op::U32Shl, Op::U32Shr, Op::U32Rotl, Op::U32Rotr]);
+    let shift_imm: fn(u32) -> Vec<Op> = |imm| {
+        vec![Op::U32ShlImm(imm), Op::U32ShrImm(imm), Op::U32RotlImm(imm), Op::U32RotrImm(imm)]
+    };
+    let ternary = prop::sample::select(vec![
+        (Op::U32OverflowingAdd3, 2),
+        (Op::U32WrappingAdd3, 1),
+        (Op::U32OverflowingMadd, 2),
+        (Op::U32WrappingMadd, 1),
+    ]);
+    let assertion = prop::sample::select(vec![
+        (Op::U32Assert, 1),
+        (Op::U32AssertWithError(1), 1),
+        (Op::U32Assert2, 2),
+        (Op::U32Assert2WithError(1), 2),
+        (Op::U32Assertw, 4),
+        (Op::U32AssertwWithError(1), 4),
+    ]);
+    prop_oneof![
+        (any::<u32>(), any::<u32>(), binary).prop_map(|(a, b, (op, results))| {
+            DifferentialSnippet::new(vec![Op::PushU32(a), Op::PushU32(b), op], 0, results)
+        }),
+        (any::<u32>(), any::<u32>(), 0usize..12).prop_map(move |(a, imm, i)| {
+            let (op, results) = with_imm(imm)[i];
+            DifferentialSnippet::new(vec![Op::PushU32(a), op], 0, results)
+        }),
+        (any::<u32>(), 1..=u32::MAX, division).prop_map(|(a, b, (op, results))| {
+            DifferentialSnippet::new(vec![Op::PushU32(a), Op::PushU32(b), op], 0, results)
+        }),
+        (any::<u32>(), 1..=u32::MAX, 0usize..3).prop_map(move |(a, imm, i)| {
+            let (op, results) = division_imm(imm)[i];
+            DifferentialSnippet::new(vec![Op::PushU32(a), op], 0, results)
+        }),
+        (any::<u32>(), unary).prop_map(|(a, op)| DifferentialSnippet::new(
+            vec![Op::PushU32(a), op],
+            0,
+            1
+        )),
+        (any::<u32>(), 0u8..32, shift).prop_map(|(a, b, op)| {
+            DifferentialSnippet::new(vec![Op::PushU32(a), Op::PushU8(b), op], 0, 1)
+        }),
+        (any::<u32>(), 0u32..32, 0usize..4).prop_map(move |(a, imm, i)| {
+            DifferentialSnippet::new(vec![Op::PushU32(a), shift_imm(imm)[i]], 0, 1)
+        }),
+        (prop::array::uniform3(any::<u32>()), ternary).prop_map(|(operands, (op, results))| {
+            let mut ops = operands.map(Op::PushU32).to_vec();
+            ops.push(op);
+            DifferentialSnippet::new(ops, 0, results)
+        }),
+        (prop::array::uniform4(any::<u32>()), assertion).prop_map(|(operands, (op, n))| {
+            let mut ops =
+                operands[..n as usize].iter().copied().map(Op::PushU32).collect::<Vec<_>>();
+            ops.push(op);
+            DifferentialSnippet::new(ops, 0, n)
+        }),
+    ]
+}
+
+/// Instructions which read from or write to memory, procedure locals, or the advice provider
+fn differential_memory_snippet() -> impl Strategy<Value = DifferentialSnippet> {
+    use cranelift_entity::EntityRef;
+    use midenc_hir::LocalId;
+
+    let addr = 0..DIFFERENTIAL_MEMORY_WORDS;
+    let local = (0usize..4).prop_map(LocalId::new);
+    prop_oneof![
+        addr.clone().prop_map(|a| DifferentialSnippet::new(
+            vec![Op::PushU32(a), Op::MemLoad],
+            0,
+            1
+        )),
+        addr.clone()
+            .prop_map(|a| DifferentialSnippet::new(vec![Op::MemLoadImm(a)], 0, 1)),
+        addr.clone().prop_map(|a| DifferentialSnippet::new(
+            vec![Op::PushU32(a), Op::MemLoadw],
+            4,
+            0
+        )),
+        addr.clone()
+            .prop_map(|a| DifferentialSnippet::new(vec![Op::MemLoadwImm(a)], 4, 0)),
+        addr.clone().prop_map(|a| DifferentialSnippet::new(
+            vec![Op::PushU32(a), Op::MemStore],
+            1,
+            -1
+        )),
+        addr.clone()
+            .prop_map(|a| DifferentialSnippet::new(vec![Op::MemStoreImm(a)], 1, -1)),
+        addr.clone().prop_map(|a| DifferentialSnippet::new(
+            vec![Op::PushU32(a), Op::MemStorew],
+            4,
+            0
+        )),
+        addr.clone()
+            .prop_map(|a| DifferentialSnippet::new(vec![Op::MemStorewImm(a)], 4, 0)),
+        (0..DIFFERENTIAL_MEMORY_WORDS - 1).prop_map(|a| {
+            DifferentialSnippet::new(
+                vec![Op::PushU32(a), Op::Padw, Op::Padw, Op::Padw, Op::MemStream],
+                0,
+                13,
+            )
+        }),
+        (0..DIFFERENTIAL_MEMORY_WORDS - 1).prop_map(|a| {
+            DifferentialSnippet::new(
+                vec![Op::PushU32(a), Op::Padw, Op::Padw, Op::Padw, Op::AdvPipe],
+                0,
+                13,
+            )
+            .with_advice(8)
+        }),
+        local
+            .clone()
+            .prop_map(|id| DifferentialSnippet::new(vec![Op::LocLoad(id)], 0, 1)),
+        local
+            .clone()
+            .prop_map(|id| DifferentialSnippet::new(vec![Op::LocLoadw(id)], 4, 0)),
+        local
+            .clone()
+            .prop_map(|id| DifferentialSnippet::new(vec![Op::LocStore(id)], 1, -1)),
+        local.prop_map(|id| DifferentialSnippet::new(vec![Op::LocStorew(id)], 4, 0)),
+        (1u8..=16).prop_map(|n| {
+            DifferentialSnippet::new(vec![Op::AdvPush(n)], 0, n as isize).with_advice(n as usize)
+        }),
+        Just(DifferentialSnippet::new(vec![Op::AdvLoadw], 4, 0).with_advice(4)),
+    ]
+}
+
+/// Advice injectors, each followed by the instructions needed to move what they injected on to
+/// the operand stack, so that their effects are observable.
+fn differential_advice_snippet() -> impl Strategy<Value = DifferentialSnippet> {
+    let felt = any::<u64>().prop_map(Felt::new);
+    let word = prop::array::uniform4(felt.clone());
+    prop_oneof![
+        (any::<u64>(), 1..=u64::MAX).prop_map(|(a, b)| {
+            let ops = vec![
+                Op::PushU32(a as u32),
+                Op::PushU32((a >> 32) as u32),
+                Op::PushU32(b as u32),
+                Op::PushU32((b >> 32) as u32),
+                Op::AdvInjectPushU64Div,
+                Op::AdvPush(4),
+            ];
+            DifferentialSnippet::new(ops, 0, 8)
+        }),
+        (0usize..4, any::<bool>()).prop_map(|(i, include_len)| {
+            let key = differential_map_keys()[i];
+            let (inject, len) = if include_len {
+                (Op::AdvInjectPushMapValN, 5)
+            } else {
+                (Op::AdvInjectPushMapVal, 4)
+            };
+            let ops = vec![push_word(key), inject, Op::Dropw, Op::AdvPush(len)];
+            DifferentialSnippet::new(ops, 0, len as isize)
+        }),
+        (0usize..4, any::<bool>()).prop_map(|(i, include_len)| {
+            let key = differential_map_keys()[i];
+            let (inject, len) = if include_len {
+                (Op::AdvInjectPushMapValNImm(4), 5)
+            } else {
+                (Op::AdvInjectPushMapValImm(4), 4)
+            };
+            let ops =
+                vec![push_word(key), Op::Padw, inject, Op::Dropw, Op::Dropw, Op::AdvPush(len)];
+            DifferentialSnippet::new(ops, 0, len as isize)
+        }),
+        (0u64..8).prop_map(|index| {
+            let root = differential_merkle_tree().root().into();
+            let ops = vec![
+                push_word(root),
+                Op::PushU8(index as u8),
+                Op::PushU8(3),
+                Op::AdvInjectPushMTreeNode,
+                Op::AdvPush(4),
+            ];
+            DifferentialSnippet::new(ops, 0, 10)
+        }),
+        (word.clone(), 0..DIFFERENTIAL_MEMORY_WORDS - 4, 1u32..=4).prop_map(|(key, start, len)| {
+            let mut ops = vec![Op::PushU32(start + len), Op::PushU32(start), push_word(key)];
+            ops.extend([
+                Op::AdvInjectInsertMem,
+                Op::AdvInjectPushMapVal,
+                Op::AdvPush(4 * len as u8),
+            ]);
+            DifferentialSnippet::new(ops, 0, 6 + 4 * len as isize)
+        }),
+        (word.clone(), word, prop::option::of(any::<u8>())).prop_map(|(word1, word0, domain)| {
+            let key = Rpo256::merge_in_domain(
+                &[word1.into(), word0.into()],
+                Felt::from(domain.unwrap_or(0)),
+            );
+            let inject =
+                domain.map(Op::AdvInjectInsertHdwordImm).unwrap_or(Op::AdvInjectInsertHdword);
+            let ops = vec![
+                push_word(word1),
+                push_word(word0),
+                inject,
+                Op::Dropw,
+                Op::Dropw,
+                push_word(key.into()),
+                Op::AdvInjectPushMapVal,
+                Op::Dropw,
+                Op::AdvPush(8),
+            ];
+            DifferentialSnippet::new(ops, 0, 8)
+        }),
+        prop::array::uniform12(felt).prop_map(|state| {
+            // The elements are pushed so that `state[0]` is deepest on the stack
+            let mut permuted = state;
+            Rpo256::apply_permutation(&mut permuted);
+            let key: [Felt; 4] = permuted[Rpo256::DIGEST_RANGE].try_into().unwrap();
+            let mut ops = push_felts(state);
+            ops.extend([Op::AdvInjectInsertHperm, Op::Dropw, Op::Dropw, Op::Dropw]);
+            ops.extend([push_word(key), Op::AdvInjectPushMapVal, Op::Dropw, Op::AdvPush(8)]);
+            DifferentialSnippet::new(ops, 0, 8)
+        }),
+    ]
+}
+
+/// Hashing, merkle tree, and extension field instructions, with operands chosen to be valid for
+/// them
+fn differential_crypto_snippet() -> impl Strategy<Value = DifferentialSnippet> {
+    let felt = any::<u64>().prop_map(Felt::new);
+    let nonzero = (1..=u64::MAX).prop_map(Felt::new).prop_filter("nonzero", |f| *f != Felt::ZERO);
+    let word = prop::array::uniform4(felt.clone());
+    let ext2 = prop::sample::select(vec![Op::Ext2add, Op::Ext2sub, Op::Ext2mul]);
+    prop_oneof![
+        Just(DifferentialSnippet::new(vec![Op::Hash], 4, 0)),
+        Just(DifferentialSnippet::new(vec![Op::Hmerge], 8, -4)),
+        Just(DifferentialSnippet::new(vec![Op::Hperm], 12, 0)),
+        (0u8..8).prop_map(|index| {
+            let root = differential_merkle_tree().root().into();
+            let ops = vec![push_word(root), Op::PushU8(index), Op::PushU8(3), Op::MtreeGet];
+            DifferentialSnippet::new(ops, 0, 8)
+        }),
+        (0u8..8, word.clone()).prop_map(|(index, value)| {
+            let root = differential_merkle_tree().root().into();
+            let ops = vec![
+                push_word(value),
+                push_word(root),
+                Op::PushU8(index),
+                Op::PushU8(3),
+                Op::MtreeSet,
+            ];
+            DifferentialSnippet::new(ops, 0, 8)
+        }),
+        Just(DifferentialSnippet::new(
+            {
+                let root = differential_merkle_tree().root().into();
+                vec![push_word(root), push_word(root), Op::MtreeMerge]
+            },
+            0,
+            4
+        )),
+        (0u8..8, prop::option::of(0u32..3)).prop_map(|(index, code)| {
+            let tree = differential_merkle_tree();
+            let node = tree.get_node(NodeIndex::new(3, index as u64).unwrap()).unwrap();
+            let verify = code.map(Op::MtreeVerifyWithError).unwrap_or(Op::MtreeVerify);
+            let ops = vec![
+                push_word(tree.root().into()),
+                Op::PushU8(index),
+                Op::PushU8(3),
+                push_word(node.into()),
+                verify,
+            ];
+            DifferentialSnippet::new(ops, 0, 10)
+        }),
+        (prop::array::uniform4(felt.clone()), ext2).prop_map(|(operands, op)| {
+            let mut ops = push_felts(operands);
+            ops.push(op);
+            DifferentialSnippet::new(ops, 0, 2)
+        }),
+        (felt.clone(), felt.clone()).prop_map(|(a0, a1)| {
+            DifferentialSnippet::new(vec![Op::Push(a0), Op::Push(a1), Op::Ext2neg], 0, 2)
+        }),
+        (felt.clone(), nonzero.clone()).prop_map(|(a0, a1)| {
+            DifferentialSnippet::new(vec![Op::Push(a0), Op::Push(a1), Op::Ext2inv], 0, 2)
+        }),
+        (felt.clone(), felt.clone(), felt.clone(), nonzero.clone()).prop_map(|(a0, a1, b0, b1)| {
+            let ops = vec![Op::Push(a0), Op::Push(a1), Op::Push(b0), Op::Push(b1), Op::Ext2div];
+            DifferentialSnippet::new(ops, 0, 2)
+        }),
+        (
+            prop::array::uniform8(felt.clone()),
+            0usize..4,
+            nonzero,
+            prop::array::uniform4(felt.clone())
+        )
+            .prop_map(
+                |(query_values, segment, poe, [f_pos, alpha0, alpha1, layer_ptr])| {
+                    // The previous layer value must match the query value in the domain segment
+                    let prev = [query_values[2 * segment], query_values[2 * segment + 1]];
+                    let mut ops = push_felts([layer_ptr, alpha0, alpha1, prev[0], prev[1]]);
+                    ops.extend(push_felts([poe, Felt::new(segment as u64), f_pos]));
+                    ops.extend(push_felts(query_values));
+                    ops.push(Op::FriExt2Fold4);
+                    DifferentialSnippet::new(ops, 0, 15)
+                }
+            ),
+        (
+            prop::array::uniform14(felt),
+            // The VM cannot build a trace when both pointers refer to the same address, as it
+            // is accessed twice in a single cycle
+            0..DIFFERENTIAL_MEMORY_WORDS / 2,
+            DIFFERENTIAL_MEMORY_WORDS / 2..DIFFERENTIAL_MEMORY_WORDS
+        )
+            .prop_map(|(values, z_ptr, alpha_ptr)| {
+                let mut ops =
+                    vec![Op::Push(values[13]), Op::PushU32(alpha_ptr), Op::PushU32(z_ptr)];
+                ops.extend(push_felts(values[..13].iter().copied()));
+                ops.push(Op::RCombBase);
+                DifferentialSnippet::new(ops, 0, 16)
+            }),
+    ]
+}
+
+/// Test that the emulator and the VM agree on the effects of random instruction sequences, by
+/// comparing the resulting operand stack, memory, and cycle count.
+///
+/// Instructions whose results depend on details of the environment the emulator does not model,
+/// i.e. control flow, procedure digests, `clk`, `locaddr`, and signature generation, which is
+/// randomized, are not covered.
+#[test]
+fn emulator_matches_vm() {
+    const NUM_INPUTS: usize = 16;
+    const MAX_DEPTH: usize = 64;
+    const ADVICE_LEN: usize = 64;
+
+    let snippets = prop_oneof![
+        3 => differential_stack_snippet(),
+        2 => differential_field_snippet(),
+        2 => differential_u32_snippet(),
+        2 => differential_memory_snippet(),
+        1 => differential_advice_snippet(),
+        1 => differential_crypto_snippet(),
+    ];
+    let sequences = prop::collection::vec(snippets, 1..24).prop_map(|snippets| {
+        // Discard snippets which would underflow the inputs, exceed the maximum depth, or
+        // exhaust the advice stack
+        let mut depth = NUM_INPUTS as isize;
+        let mut advice = ADVICE_LEN;
+        let mut ops = vec![];
+        for snippet in snippets {
+            let new_depth = depth + snippet.effect;
+            if depth - (snippet.required as isize) < 0
+                || new_depth < NUM_INPUTS as isize
+                || new_depth > MAX_DEPTH as isize
+                || snippet.advice > advice
+            {
+                continue;
+            }
+            depth = new_depth;
+            advice -= snippet.advice;
+            ops.extend(snippet.ops);
+        }
+        ops
+    });
+    let inputs = prop::collection::vec(any::<u64>().prop_map(Felt::new), NUM_INPUTS);
+    let advice_stack = prop::collection::vec(any::<u64>().prop_map(Felt::new), ADVICE_LEN);
+    let map_values = prop::array::uniform4(prop::array::uniform4(any::<u64>().prop_map(Felt::new)));
+
+    // The resulting operand stack, memory, and number of cycles executed
+    type Outcome = (Vec<Felt>, Vec<[Felt; 4]>, usize);
+
+    let context = TestContext::default();
+    let probe = |ops: &[Op]| -> Box<Module> {
+        let mut function =
+            Function::new("test::probe".parse().unwrap(), Signature::new(vec![], vec![]));
+        function.signature.linkage = Linkage::External;
+        function.alloc_n_locals(4);
+        for op in ops.iter().copied() {
+            function.body_mut().push(op, SourceSpan::UNKNOWN);
+        }
+        let mut module = Box::new(Module::new(
+            miden_assembly::LibraryPath::new("test").unwrap(),
+            miden_assembly::ast::ModuleKind::Library,
+        ));
+        module.push_back(Box::new(function));
+        module
+    };
+    let execute = |ops: &[Op],
+                   inputs: &[Felt],
+                   advice_inputs: miden_processor::AdviceInputs|
+     -> Result<Outcome, String> {
+        use miden_core::Operation;
+
+        let program = miden_assembly::Assembler::new(context.session.source_manager.clone())
+            .with_module(probe(ops).to_ast(false).unwrap())
+            .unwrap()
+            .assemble_program("begin exec.::test::probe end")
+            .map_err(|err| format!("failed to assemble: {err}"))?;
+        let stack_inputs =
+            miden_core::StackInputs::new(inputs.iter().rev().copied().collect()).unwrap();
+        let host = miden_processor::DefaultHost::new(miden_processor::MemAdviceProvider::from(
+            advice_inputs,
+        ));
+        let mut cycles = 0;
+        let mut last = None;
+        for state in miden_processor::execute_iter(&program, stack_inputs, host) {
+            let state = state.map_err(|err| format!("execution failed: {err}"))?;
+            let counted = state.op.is_some_and(|op| {
+                !matches!(
+                    op,
+                    Operation::Noop
+                        | Operation::Span
+                        | Operation::Respan
+                        | Operation::End
+                        | Operation::Join
+                        | Operation::Split
+                        | Operation::Loop
+                        | Operation::Call
+                        | Operation::SysCall
+                        | Operation::Dyn
+                        | Operation::Halt
+                )
+            });
+            cycles += counted as usize;
+            last = Some(state);
+        }
+        let last = last.expect("expected at least one cycle of execution");
+        let memory = (0..DIFFERENTIAL_MEMORY_WORDS as u64)
+            .map(|addr| {
+                last.memory
+                    .iter()
+                    .find_map(|(a, word)| (*a == addr).then_some(*word))
+                    .unwrap_or_default()
+            })
+            .collect();
+        Ok((last.stack, memory, cycles))
+    };
+
+    let merkle_tree = differential_merkle_tree();
+    let merkle_store = MerkleStore::from(&merkle_tree);
+    let empty = miden_processor::AdviceInputs::default();
+    let (_, _, base_cycles) = execute(&[], &[Felt::ZERO; NUM_INPUTS], empty).unwrap();
+
+    TestRunner::new(Config::with_cases(128))
+        .run(
+            &(sequences, inputs, advice_stack, map_values),
+            move |(ops, inputs, advice_stack, map_values)| {
+                let map = differential_map_keys()
+                    .into_iter()
+                    .zip(map_values)
+                    .map(|(key, values)| (RpoDigest::new(key), values.to_vec()))
+                    .collect::<Vec<_>>();
+                let advice_inputs = miden_processor::AdviceInputs::default()
+                    .with_stack_values(advice_stack.iter().map(|felt| felt.as_int()))
+                    .unwrap()
+                    .with_map(map.clone())
+                    .with_merkle_store(merkle_store.clone());
+                let (expected_stack, expected_memory, cycles) =
+                    execute(&ops, &inputs, advice_inputs).map_err(TestCaseError::fail)?;
+
+                let mut emulator = Emulator::default();
+                emulator.load_module(probe(&ops).freeze()).expect("failed to load module");
+                for felt in advice_stack.iter().rev() {
+                    emulator.advice_stack_mut().push(*felt);
+                }
+                emulator.advice_map_mut().extend(map);
+                *emulator.merkle_store_mut() = merkle_store.clone();
+                let stack = emulator
+                    .invoke("test::probe".parse().unwrap(), &inputs)
+                    .map_err(|err| TestCaseError::fail(format!("emulation failed: {err}")))?;
+                let stack = stack.stack().iter().rev().copied().collect::<Vec<_>>();
+                let memory = (0..DIFFERENTIAL_MEMORY_WORDS)
+                    .map(|addr| emulator.load_word(addr))
+                    .collect::<Vec<_>>();
+
+                prop_assert_eq!(stack, expected_stack, "operand stack mismatch for {:?}", ops);
+                prop_assert_eq!(memory, expected_memory, "memory mismatch for {:?}", ops);
+                prop_assert_eq!(
+                    emulator.cycles(),
+                    cycles - base_cycles,
+                    "cycle count mismatch for {:?}",
+                    ops
+                );
+                Ok(())
+            },
+        )
+        .unwrap();
+}
+
+/// Test that the emulator can invoke procedures by digest, and call native functions from MASM
+#[test]
+fn emulator_dynamic_and_native_calls() {
+    let span = SourceSpan::UNKNOWN;
+    let incr: FunctionIdent = "test::incr_below_digest".parse().unwrap();
+    let double: FunctionIdent = "test::double".parse().unwrap();
+    let main: FunctionIdent = "test::main".parse().unwrap();
+    let unknown: FunctionIdent = "test::unknown".parse().unwrap();
+
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new("test").unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+    // Dynamically invoked procedures find the digest they were invoked with on top of the stack
+    let mut function = Function::new(incr, Signature::new(vec![], vec![]));
+    for op in [Op::Movup(4), Op::Incr, Op::Movdn(4)] {
+        function.body_mut().push(op, span);
+    }
+    module.push_back(Box::new(function));
+    let mut function = Function::new(main, Signature::new(vec![], vec![]));
+    for op in [
+        Op::ProcRef(incr),
+        Op::DynExec,
+        Op::Dropw,
+        Op::Exec(double),
+        Op::ProcRef(incr),
+        Op::DynCall,
+        Op::Dropw,
+    ] {
+        function.body_mut().push(op, span);
+    }
+    module.push_back(Box::new(function));
+    let mut function = Function::new(unknown, Signature::new(vec![], vec![]));
+    for op in [Op::Padw, Op::DynExec] {
+        function.body_mut().push(op, span);
+    }
+    module.push_back(Box::new(function));
+
+    let mut emulator = Emulator::default();
+    emulator.load_module(module.freeze()).expect("failed to load module");
+    emulator
+        .load_nif(
+            double,
+            Box::new(|emulator: &mut Emulator, _args: &[Felt]| {
+                let stack = emulator.stack_mut();
+                let value = stack.pop().expect("operand stack is empty");
+                stack.push(value + value);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    let stack = emulator.invoke(main, &[Felt::new(3)]).expect("execution failed");
+    assert_eq!(stack.peek(), Some(Felt::new(9)));
+
+    emulator.stop();
+    let result = emulator.invoke(unknown, &[]).map(|_| ());
+    assert_eq!(result, Err(EmulationError::UndefinedProcedure(RpoDigest::default())));
+}
+
+#[test]
+fn emulator_time_travel() {
+    let span = SourceSpan::UNKNOWN;
+    let main: FunctionIdent = "test::main".parse().unwrap();
+
+    let mut module = Box::new(Module::new(
+        miden_assembly::LibraryPath::new("test").unwrap(),
+        miden_assembly::ast::ModuleKind::Library,
+    ));
+    let mut function = Function::new(main, Signature::new(vec![], vec![]));
+    for op in [
+        Op::PushU8(1),
+        Op::MemStoreImm(100),
+        Op::PushU8(2),
+        Op::MemStoreImm(200),
+        // The faulty store we want to find
+        Op::PushU8(99),
+        Op::MemStoreImm(100),
+        Op::PushU8(3),
+        Op::MemStoreImm(200),
+        Op::MemLoadImm(100),
+    ] {
+        function.body_mut().push(op, span);
+    }
+    module.push_back(Box::new(function));
+
+    let mut emulator = Emulator::default();
+    emulator.set_snapshot_interval(3);
+    emulator.load_module(module.freeze()).expect("failed to load module");
+    let stack = emulator.invoke(main, &[]).expect("execution failed");
+    assert_eq!(stack.peek(), Some(Felt::new(99)));
+    let end = emulator.clk();
+
+    // Walk backwards to the store which clobbered address 100
+    emulator.set_watchpoint(100, 1, WatchMode::Break);
+    let event = emulator.reverse_continue().expect("reverse execution failed");
+    assert!(matches!(event, EmulatorEvent::Breakpoint(BreakpointEvent::Watch(_))));
+    assert_eq!(emulator.load_word(100)[0], Felt::new(99));
+    assert_eq!(emulator.load_word(200)[0], Felt::new(2));
+    let faulty_store = emulator.clk();
+
+    // Undoing the store restores the previous value, and the operand to be stored
+    emulator.step_back().expect("reverse execution failed");
+    assert_eq!(emulator.clk(), faulty_store - 1);
+    assert_eq!(emulator.load_word(100)[0], Felt::new(1));
+    assert_eq!(emulator.stack().peek(), Some(Felt::new(99)));
+
+    // The previous write to the watched address is the first store
+    let event = emulator.reverse_continue().expect("reverse execution failed");
+    assert!(matches!(event, EmulatorEvent::Breakpoint(BreakpointEvent::Watch(_))));
+    assert_eq!(emulator.load_word(100)[0], Felt::new(1));
+    assert_eq!(emulator.load_word(200)[0], Felt::ZERO);
+
+    // There are no more writes to find, so we end up at the start of execution
+    let event = emulator.reverse_continue().expect("reverse execution failed");
+    assert!(matches!(event, EmulatorEvent::Suspended));
+    assert_eq!(emulator.clk(), 0);
+    assert_eq!(emulator.load_word(100)[0], Felt::ZERO);
+
+    // Travelling forward again ignores the watchpoint, and reproduces the original result
+    assert!(matches!(emulator.goto_cycle(faulty_store), Ok(EmulatorEvent::Suspended)));
+    assert_eq!(emulator.load_word(100)[0], Felt::new(99));
+    assert!(matches!(emulator.goto_cycle(end), Ok(EmulatorEvent::Suspended)));
+    assert_eq!(emulator.stack().peek(), Some(Felt::new(99)));
+    assert!(matches!(emulator.goto_cycle(end + 1), Ok(EmulatorEvent::Stopped)));
+}
@@ -0,0 +1,307 @@
+//! Lowering of the WebAssembly exception-handling proposal (`try_table`, `throw`, `throw_ref`).
+//!
+//! Miden has no native notion of unwinding, so exceptions are lowered to ordinary control flow
+//! using a per-activation landing-pad protocol:
+//!
+//! * Each module which defines exception tags gets a pair of internal globals per tag parameter,
+//!   holding the payload of the exception in flight, plus a single [EXCEPTION_TAG_GLOBAL], which
+//!   holds `tag index + 1` while an exception is propagating, and `0` otherwise.
+//! * `throw` stores the payload and tag into those globals, and then branches to the landing pad
+//!   of the innermost enclosing `try_table` of the current function. If there is no such
+//!   `try_table`, the function returns immediately (with zeroed results), leaving the exception
+//!   pending for its caller. Exported functions trap instead, as their callers may be outside of
+//!   the module, and so unaware of the pending exception.
+//! * After every call to a function which may unwind, the caller checks whether an exception is
+//!   pending, and if so, branches to its own landing pad, or returns to its caller in turn.
+//! * The landing pad of a `try_table` tests its catch clauses in order. The first matching
+//!   clause clears the pending exception, loads the payload, and branches to the clause label.
+//!   If no clause matches, the exception continues unwinding to the next landing pad.
+//!
+//! An `exnref` is represented as the (non-zero) tag identifier of the exception it refers to.
+//! Rethrowing it with `throw_ref` reuses the payload globals, so this representation is only
+//! sound as long as no other exception with the same tag is thrown between catching the
+//! exception and rethrowing it, which holds for the cleanup pads generated by rustc.
+//!
+//! Since the unwinding state is module-local, exception tags cannot be imported or exported.
+
+use midenc_hir::{
+    diagnostics::{DiagnosticsHandler, Severity, SourceSpan},
+    Immediate, InstBuilder, Type, Value,
+};
+use wasmparser::Catch;
+
+use crate::{
+    error::WasmResult,
+    module::{
+        func_translation_state::{ControlStackFrame, FuncTranslationState},
+        function_builder_ext::FunctionBuilderExt,
+        types::{ir_type, BlockType, FuncIndex, ModuleTypes, TagIndex},
+        Module,
+    },
+    translation_utils::emit_zero,
+    unsupported_diag,
+};
+
+/// The name of the global holding the identifier of the exception currently being propagated,
+/// or zero if there is no such exception.
+pub const EXCEPTION_TAG_GLOBAL: &str = "__miden_exn_tag";
+
+/// Get the name of the global holding the `index`-th payload value of exceptions with `tag`
+pub fn exception_payload_global(tag: TagIndex, index: usize) -> String {
+    format!("__miden_exn_payload{}_{index}", tag.as_u32())
+}
+
+/// Get the value used to identify exceptions with `tag` in [EXCEPTION_TAG_GLOBAL]
+fn exception_id(tag: TagIndex) -> u32 {
+    tag.as_u32() + 1
+}
+
+/// Get the Miden IR types of the payload carried by exceptions with `tag`
+pub fn tag_payload_types(
+    tag: TagIndex,
+    module: &Module,
+    mod_types: &ModuleTypes,
+    diagnostics: &DiagnosticsHandler,
+) -> WasmResult<Vec<Type>> {
+    let sig_index = module.types[module.tags[tag].ty].unwrap_function();
+    mod_types[sig_index]
+        .params()
+        .iter()
+        .map(|ty| ir_type(*ty, diagnostics))
+        .collect()
+}
+
+pub fn translate_try_table(
+    try_table: &wasmparser::TryTable,
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    mod_types: &ModuleTypes,
+    diagnostics: &DiagnosticsHandler,
+    span: SourceSpan,
+) -> WasmResult<()> {
+    let blockty = BlockType::from_wasm(&try_table.ty, mod_types, diagnostics)?;
+    let next = builder.create_block_with_params(blockty.results.clone(), span);
+    state.push_try_table(
+        next,
+        try_table.catches.clone(),
+        blockty.params.len(),
+        blockty.results.len(),
+    );
+    Ok(())
+}
+
+pub fn translate_throw(
+    tag_index: u32,
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    module: &Module,
+    mod_types: &ModuleTypes,
+    diagnostics: &DiagnosticsHandler,
+    span: SourceSpan,
+) -> WasmResult<()> {
+    let tag = TagIndex::from_u32(tag_index);
+    let payload_types = tag_payload_types(tag, module, mod_types, diagnostics)?;
+    let num_payload_values = payload_types.len();
+    let payload = state.peekn(num_payload_values).to_vec();
+    for (index, (value, ty)) in payload.into_iter().zip(payload_types).enumerate() {
+        let name = exception_payload_global(tag, index);
+        let ptr = builder.ins().symbol_addr(&name, Type::Ptr(ty.into()), span);
+        builder.ins().store(ptr, value, span);
+    }
+    state.popn(num_payload_values);
+    let id = builder.ins().u32(exception_id(tag), span);
+    raise(id, builder, state, span);
+    Ok(())
+}
+
+pub fn translate_throw_ref(
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    span: SourceSpan,
+) {
+    let exnref = state.pop1();
+    // Rethrowing a null exception reference traps
+    let is_valid = builder.ins().neq_imm(exnref, Immediate::U32(0), span);
+    builder.ins().assert(is_valid, span);
+    raise(exnref, builder, state, span);
+}
+
+/// Mark the exception identified by `id` as pending, and start unwinding from the current point
+fn raise(
+    id: Value,
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    span: SourceSpan,
+) {
+    let ptr = builder
+        .ins()
+        .symbol_addr(EXCEPTION_TAG_GLOBAL, Type::Ptr(Type::U32.into()), span);
+    builder.ins().store(ptr, id, span);
+    let destination = state.unwind_destination(builder);
+    builder.ins().br(destination, &[], span);
+    state.reachable = false;
+}
+
+/// Emit a check, following a call, for an exception raised by the callee, which unwinds to the
+/// landing pad of the current activation if one is found.
+pub fn emit_unwind_check(
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    span: SourceSpan,
+) {
+    let pending = builder.ins().load_symbol(EXCEPTION_TAG_GLOBAL, Type::U32, span);
+    let is_pending = builder.ins().neq_imm(pending, Immediate::U32(0), span);
+    let destination = state.unwind_destination(builder);
+    let next_block = builder.create_block();
+    builder.ins().cond_br(is_pending, destination, &[], next_block, &[], span);
+    builder.seal_block(next_block); // The only predecessor is the current block.
+    builder.switch_to_block(next_block);
+}
+
+/// Emit the landing pad of a `try_table` frame which has just been popped from the control
+/// stack, if anything in its body could unwind.
+///
+/// The landing pad tests each catch clause in order, and either branches to the label of the
+/// first clause which matches the pending exception, or continues unwinding.
+pub fn emit_landing_pad(
+    frame: &ControlStackFrame,
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    module: &Module,
+    mod_types: &ModuleTypes,
+    diagnostics: &DiagnosticsHandler,
+    span: SourceSpan,
+) -> WasmResult<()> {
+    let ControlStackFrame::TryTable {
+        ref catches,
+        landing_pad: Some(landing_pad),
+        ..
+    } = *frame
+    else {
+        return Ok(());
+    };
+
+    builder.switch_to_block(landing_pad);
+    // All of the instructions which unwind to this landing pad are contained in the body of the
+    // `try_table`, which has been fully translated by now.
+    builder.seal_block(landing_pad);
+    let pending = builder.ins().load_symbol(EXCEPTION_TAG_GLOBAL, Type::U32, span);
+    for catch in catches.iter() {
+        let (tag, label, with_ref) = match *catch {
+            Catch::One { tag, label } => (Some(TagIndex::from_u32(tag)), label, false),
+            Catch::OneRef { tag, label } => (Some(TagIndex::from_u32(tag)), label, true),
+            Catch::All { label } => (None, label, false),
+            Catch::AllRef { label } => (None, label, true),
+        };
+
+        // Catch-all clauses always match, so only test for a specific tag
+        let handler = match tag {
+            Some(tag) => {
+                let is_match =
+                    builder.ins().eq_imm(pending, Immediate::U32(exception_id(tag)), span);
+                let handler = builder.create_block();
+                let next_clause = builder.create_block();
+                builder.ins().cond_br(is_match, handler, &[], next_clause, &[], span);
+                builder.seal_block(handler);
+                builder.seal_block(next_clause);
+                builder.switch_to_block(handler);
+                Some(next_clause)
+            }
+            None => None,
+        };
+
+        // The exception is handled, so clear it before branching to the handler
+        let ptr =
+            builder
+                .ins()
+                .symbol_addr(EXCEPTION_TAG_GLOBAL, Type::Ptr(Type::U32.into()), span);
+        let zero = builder.ins().u32(0, span);
+        builder.ins().store(ptr, zero, span);
+
+        let mut args = vec![];
+        if let Some(tag) = tag {
+            let payload_types = tag_payload_types(tag, module, mod_types, diagnostics)?;
+            for (index, ty) in payload_types.into_iter().enumerate() {
+                let name = exception_payload_global(tag, index);
+                args.push(builder.ins().load_symbol(&name, ty, span));
+            }
+        }
+        if with_ref {
+            args.push(pending);
+        }
+
+        // Catch labels are relative to the block enclosing the `try_table`, which is now the
+        // innermost frame of the control stack.
+        let i = state.control_stack.len() - 1 - (label as usize);
+        let frame = &mut state.control_stack[i];
+        frame.set_branched_to_exit();
+        builder.ins().br(frame.br_destination(), &args, span);
+
+        match handler {
+            Some(next_clause) => builder.switch_to_block(next_clause),
+            // Any clauses following a catch-all are dead
+            None => return Ok(()),
+        }
+    }
+
+    // None of the clauses matched, so keep unwinding
+    let destination = state.unwind_destination(builder);
+    builder.ins().br(destination, &[], span);
+    Ok(())
+}
+
+/// Emit the block which returns from the current function when an exception raised in it was not
+/// caught, if anything in the function could unwind.
+///
+/// The exception remains pending, so that the caller can resume unwinding from the call site.
+///
+/// If the function is exported, it may be called from outside of the module, where nothing checks
+/// for a pending exception, so returning would make it look as if the function succeeded. Instead,
+/// an uncaught exception traps with [midenc_hir::ASSERT_FAILED_UNCAUGHT_EXCEPTION].
+pub fn emit_unwind_return(
+    builder: &mut FunctionBuilderExt,
+    state: &mut FuncTranslationState,
+    is_exported: bool,
+    diagnostics: &DiagnosticsHandler,
+    span: SourceSpan,
+) -> WasmResult<()> {
+    let Some(unwind_block) = state.unwind_block.take() else {
+        return Ok(());
+    };
+
+    builder.switch_to_block(unwind_block);
+    builder.seal_block(unwind_block);
+    if is_exported {
+        let zero = builder.ins().u32(0, span);
+        builder
+            .ins()
+            .assert_with_error(zero, midenc_hir::ASSERT_FAILED_UNCAUGHT_EXCEPTION, span);
+        builder.ins().unreachable(span);
+        return Ok(());
+    }
+    let result_types =
+        builder.signature().results().iter().map(|p| p.ty.clone()).collect::<Vec<_>>();
+    let return_value = match result_types.as_slice() {
+        [] => None,
+        [ty] => Some(emit_zero(ty, builder, diagnostics)?),
+        _ => {
+            unsupported_diag!(diagnostics, "Multiple values are not supported");
+        }
+    };
+    builder.ins().ret(return_value, span);
+    Ok(())
+}
+
+/// Returns true if a call to `callee` must be followed by a check for a pending exception.
+///
+/// Only functions defined in `module` can raise exceptions, see the module docs for details.
+pub fn may_unwind(module: &Module, callee: FuncIndex) -> bool {
+    module.uses_exceptions() && !module.is_imported_function(callee)
+}
+
+/// Returns true if an indirect call must be followed by a check for a pending exception.
+///
+/// The callee of an indirect call is not known statically, so it may be any function of `module`.
+pub fn may_unwind_indirect(module: &Module) -> bool {
+    module.uses_exceptions()
+}
@@ -22,6 +22,7 @@ use midenc_hir::{
 };
 use wasmparser::{MemArg, Operator};
 
+use self::simd::{Lanes, Shift};
 use crate::{
     error::WasmResult,
     intrinsics::{convert_intrinsics_call, is_miden_intrinsics_module},
@@ -37,6 +38,11 @@ use crate::{
     unsupported_diag,
 };
 
+pub(crate) mod exceptions;
+mod panics;
+mod simd;
+pub(crate) mod soft_float;
+
 #[cfg(test)]
 mod tests;
 
@@ -56,7 +62,7 @@ pub fn translate_operator(
     span: SourceSpan,
 ) -> WasmResult<()> {
     if !state.reachable {
-        translate_unreachable_operator(op, builder, state, mod_types, diagnostics, span)?;
+        translate_unreachable_operator(op, builder, state, module, mod_types, diagnostics, span)?;
         return Ok(());
     }
 
@@ -136,6 +142,10 @@ pub fn translate_operator(
                     let cond = builder.ins().neq_imm(cond, Immediate::I64(0), span);
                     state.push1(builder.ins().select(cond, arg1, arg2, span));
                 }
+                wasmparser::ValType::F64 | wasmparser::ValType::V128 => {
+                    let cond = builder.ins().neq_imm(cond, Immediate::I32(0), span);
+                    state.push1(builder.ins().select(cond, arg1, arg2, span));
+                }
                 ty => panic!("unsupported value type for 'select': {ty}"),
             }
         }
@@ -154,8 +164,18 @@ pub fn translate_operator(
         Operator::If { blockty } => {
             translate_if(blockty, state, builder, mod_types, diagnostics, span)?
         }
+        Operator::TryTable { try_table } => {
+            exceptions::translate_try_table(
+                try_table,
+                builder,
+                state,
+                mod_types,
+                diagnostics,
+                span,
+            )?;
+        }
         Operator::Else => translate_else(state, builder, span)?,
-        Operator::End => translate_end(state, builder, span),
+        Operator::End => translate_end(state, builder, module, mod_types, diagnostics, span)?,
 
         /**************************** Branch instructions *********************************/
         Operator::Br { relative_depth } => translate_br(state, relative_depth, builder, span),
@@ -164,22 +184,45 @@ pub fn translate_operator(
         }
         Operator::BrTable { targets } => translate_br_table(targets, state, builder, span)?,
         Operator::Return => translate_return(state, builder, diagnostics, span)?,
+        /********************************** Exceptions *************************************/
+        Operator::Throw { tag_index } => {
+            exceptions::translate_throw(
+                *tag_index,
+                builder,
+                state,
+                module,
+                mod_types,
+                diagnostics,
+                span,
+            )?;
+        }
+        Operator::ThrowRef => exceptions::translate_throw_ref(builder, state, span),
         /************************************ Calls ****************************************/
         Operator::Call { function_index } => {
-            translate_call(
+            let function_index = FuncIndex::from_u32(*function_index);
+            if panics::translate_panic_call(
+                function_index,
                 state,
                 module_state,
+                module,
                 builder,
-                FuncIndex::from_u32(*function_index),
                 span,
-                diagnostics,
-            )?;
+            ) {
+                return Ok(());
+            }
+            translate_call(state, module_state, builder, function_index, span, diagnostics)?;
+            if exceptions::may_unwind(module, function_index) {
+                exceptions::emit_unwind_check(builder, state, span);
+            }
         }
         Operator::CallIndirect {
             type_index: _,
             table_index: _,
         } => {
             // TODO:
+            if exceptions::may_unwind_indirect(module) {
+                exceptions::emit_unwind_check(builder, state, span);
+            }
         }
         /******************************* Memory management *********************************/
         Operator::MemoryGrow { .. } => {
@@ -540,6 +583,120 @@ pub fn translate_operator(
             let val = builder.ins().neq(arg0, arg1, span);
             state.push1(builder.ins().zext(val, I32, span));
         }
+        /******************************* SIMD Operators ***************************************/
+        Operator::V128Const { value } => simd::translate_const(value, state, builder, span),
+        Operator::V128Load { memarg } => simd::translate_load(memarg, state, builder, span),
+        Operator::V128Store { memarg } => simd::translate_store(memarg, state, builder, span),
+        Operator::V128Load8Splat { memarg } => {
+            let value = load(U8, memarg, state, builder, span);
+            simd::translate_load_splat(Lanes::I8x16, value, state, builder, span);
+        }
+        Operator::V128Load16Splat { memarg } => {
+            let value = load(U16, memarg, state, builder, span);
+            simd::translate_load_splat(Lanes::I16x8, value, state, builder, span);
+        }
+        Operator::V128Load32Splat { memarg } => {
+            let value = load(U32, memarg, state, builder, span);
+            simd::translate_load_splat(Lanes::I32x4, value, state, builder, span);
+        }
+        Operator::V128Load64Splat { memarg } => {
+            let value = load(U64, memarg, state, builder, span);
+            simd::translate_load_splat(Lanes::I64x2, value, state, builder, span);
+        }
+        Operator::V128Load32Zero { memarg } => {
+            let value = load(U32, memarg, state, builder, span);
+            simd::translate_load_zero(value, state, builder, span);
+        }
+        Operator::V128Load64Zero { memarg } => {
+            let value = load(U64, memarg, state, builder, span);
+            simd::translate_load_zero(value, state, builder, span);
+        }
+        Operator::V128Not => {
+            let arg = state.pop1();
+            state.push1(builder.ins().bnot(arg, span));
+        }
+        Operator::V128And => {
+            let (arg1, arg2) = state.pop2();
+            state.push1(builder.ins().band(arg1, arg2, span));
+        }
+        Operator::V128Or => {
+            let (arg1, arg2) = state.pop2();
+            state.push1(builder.ins().bor(arg1, arg2, span));
+        }
+        Operator::V128Xor => {
+            let (arg1, arg2) = state.pop2();
+            state.push1(builder.ins().bxor(arg1, arg2, span));
+        }
+        Operator::V128AndNot => simd::translate_andnot(state, builder, span),
+        Operator::V128Bitselect => simd::translate_bitselect(state, builder, span),
+        Operator::V128AnyTrue => simd::translate_any_true(state, builder, span),
+        Operator::I32x4AllTrue => simd::translate_all_true(state, builder, span),
+        Operator::I8x16Splat => simd::translate_splat(Lanes::I8x16, state, builder, span),
+        Operator::I16x8Splat => simd::translate_splat(Lanes::I16x8, state, builder, span),
+        Operator::I32x4Splat => simd::translate_splat(Lanes::I32x4, state, builder, span),
+        Operator::I64x2Splat => simd::translate_splat(Lanes::I64x2, state, builder, span),
+        Operator::I8x16ExtractLaneS { lane } => {
+            simd::translate_extract_lane(Lanes::I8x16, *lane, true, state, builder, span)
+        }
+        Operator::I8x16ExtractLaneU { lane } => {
+            simd::translate_extract_lane(Lanes::I8x16, *lane, false, state, builder, span)
+        }
+        Operator::I16x8ExtractLaneS { lane } => {
+            simd::translate_extract_lane(Lanes::I16x8, *lane, true, state, builder, span)
+        }
+        Operator::I16x8ExtractLaneU { lane } => {
+            simd::translate_extract_lane(Lanes::I16x8, *lane, false, state, builder, span)
+        }
+        Operator::I32x4ExtractLane { lane } => {
+            simd::translate_extract_lane(Lanes::I32x4, *lane, false, state, builder, span)
+        }
+        Operator::I64x2ExtractLane { lane } => {
+            simd::translate_extract_lane(Lanes::I64x2, *lane, false, state, builder, span)
+        }
+        Operator::I8x16ReplaceLane { lane } => {
+            simd::translate_replace_lane(Lanes::I8x16, *lane, state, builder, span)
+        }
+        Operator::I16x8ReplaceLane { lane } => {
+            simd::translate_replace_lane(Lanes::I16x8, *lane, state, builder, span)
+        }
+        Operator::I32x4ReplaceLane { lane } => {
+            simd::translate_replace_lane(Lanes::I32x4, *lane, state, builder, span)
+        }
+        Operator::I64x2ReplaceLane { lane } => {
+            simd::translate_replace_lane(Lanes::I64x2, *lane, state, builder, span)
+        }
+        Operator::I8x16Add => simd::translate_add(Lanes::I8x16, state, builder, span),
+        Operator::I16x8Add => simd::translate_add(Lanes::I16x8, state, builder, span),
+        Operator::I32x4Add => simd::translate_add(Lanes::I32x4, state, builder, span),
+        Operator::I64x2Add => simd::translate_add(Lanes::I64x2, state, builder, span),
+        Operator::I8x16Sub => simd::translate_sub(Lanes::I8x16, state, builder, span),
+        Operator::I16x8Sub => simd::translate_sub(Lanes::I16x8, state, builder, span),
+        Operator::I32x4Sub => simd::translate_sub(Lanes::I32x4, state, builder, span),
+        Operator::I64x2Sub => simd::translate_sub(Lanes::I64x2, state, builder, span),
+        Operator::I32x4Mul => simd::translate_mul(Lanes::I32x4, state, builder, span),
+        Operator::I64x2Mul => simd::translate_mul(Lanes::I64x2, state, builder, span),
+        Operator::I32x4Eq => simd::translate_i32x4_compare(true, state, builder, span),
+        Operator::I32x4Ne => simd::translate_i32x4_compare(false, state, builder, span),
+        Operator::I32x4Shl => simd::translate_i32x4_shift(Shift::Left, state, builder, span),
+        Operator::I32x4ShrS => {
+            simd::translate_i32x4_shift(Shift::RightSigned, state, builder, span)
+        }
+        Operator::I32x4ShrU => {
+            simd::translate_i32x4_shift(Shift::RightUnsigned, state, builder, span)
+        }
+        Operator::I8x16Shuffle { lanes } => simd::translate_shuffle(lanes, state, builder, span),
+        /*************************** Floating-point Operators ***********************************/
+        op if soft_float::is_f64_operator(op) => {
+            if !module_state.soft_float {
+                unsupported_diag!(
+                    diagnostics,
+                    "Wasm op {:?} is not supported: f64 operations require software floating \
+                     point, which can be enabled with `-C soft-float`",
+                    op
+                );
+            }
+            soft_float::translate_operator(op, state, builder, span);
+        }
         op => {
             unsupported_diag!(diagnostics, "Wasm op {:?} is not supported", op);
         }
@@ -554,9 +711,21 @@ fn translate_load(
     builder: &mut FunctionBuilderExt,
     span: SourceSpan,
 ) {
+    let val = load(ptr_ty, memarg, state, builder, span);
+    state.push1(val);
+}
+
+/// Pop an address off the stack, and load a value of type `ptr_ty` from it
+fn load(
+    ptr_ty: Type,
+    memarg: &MemArg,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) -> Value {
     let addr_int = state.pop1();
     let addr = prepare_addr(addr_int, &ptr_ty, Some(memarg), builder, span);
-    state.push1(builder.ins().load(addr, span));
+    builder.ins().load(addr, span)
 }
 
 fn translate_load_sext(
@@ -699,6 +868,25 @@ fn translate_call(
         );
         func_state.popn(num_wasm_args);
         func_state.pushn(&results);
+    } else if wasm_sig.params().iter().any(|param| param.ty.is_pointer()) {
+        // The signature of a function imported from another component types the values passed
+        // through the linear memory of the caller as pointers, see `canonical_abi_import_signature`
+        let args = args
+            .iter()
+            .copied()
+            .zip(wasm_sig.params())
+            .map(|(arg, param)| {
+                if param.ty.is_pointer() {
+                    builder.ins().inttoptr(arg, param.ty.clone(), span)
+                } else {
+                    arg
+                }
+            })
+            .collect::<Vec<_>>();
+        let call = builder.ins().call(func_id, &args, span);
+        let results = builder.inst_results(call);
+        func_state.popn(num_wasm_args);
+        func_state.pushn(results);
     } else {
         // no transformation needed
         let call = builder.ins().call(func_id, args, span);
@@ -813,7 +1001,6 @@ fn translate_br_table(
 
         targets.push(depth);
     }
-    targets.sort();
 
     let default_depth = br_targets.default();
     let min_depth =
@@ -879,8 +1066,11 @@ fn translate_block(
 fn translate_end(
     state: &mut FuncTranslationState,
     builder: &mut FunctionBuilderExt,
+    module: &Module,
+    mod_types: &ModuleTypes,
+    diagnostics: &DiagnosticsHandler,
     span: SourceSpan,
-) {
+) -> WasmResult<()> {
     // The `End` instruction pops the last control frame from the control stack, seals
     // the destination block (since `br` instructions targeting it only appear inside the
     // block and have already been translated) and modify the value stack to use the
@@ -899,6 +1089,10 @@ fn translate_end(
     // since we truncate the stack back to the original height
     // below.
 
+    // If we just finished a `try_table`, now that all of the code which can unwind to it has been
+    // translated, we can emit its landing pad.
+    exceptions::emit_landing_pad(&frame, builder, state, module, mod_types, diagnostics, span)?;
+
     builder.switch_to_block(next_block);
     builder.seal_block(next_block);
 
@@ -909,6 +1103,7 @@ fn translate_end(
 
     frame.truncate_value_stack_to_original_size(&mut state.stack);
     state.stack.extend_from_slice(builder.block_params(next_block));
+    Ok(())
 }
 
 fn translate_else(
@@ -1066,6 +1261,7 @@ fn translate_unreachable_operator(
     op: &Operator,
     builder: &mut FunctionBuilderExt,
     state: &mut FuncTranslationState,
+    module: &Module,
     mod_types: &ModuleTypes,
     diagnostics: &DiagnosticsHandler,
     span: SourceSpan,
@@ -1087,7 +1283,9 @@ fn translate_unreachable_operator(
                 blockty,
             );
         }
-        Operator::Loop { blockty: _ } | Operator::Block { blockty: _ } => {
+        Operator::Loop { blockty: _ }
+        | Operator::Block { blockty: _ }
+        | Operator::TryTable { try_table: _ } => {
             state.push_block(Block::reserved_value(), 0, 0);
         }
         Operator::Else => {
@@ -1145,12 +1343,22 @@ fn translate_unreachable_operator(
             }
         }
         Operator::End => {
-            let stack = &mut state.stack;
-            let control_stack = &mut state.control_stack;
-            let frame = control_stack.pop().unwrap();
+            let frame = state.control_stack.pop().unwrap();
 
             // Pop unused parameters from stack.
-            frame.truncate_value_stack_to_original_size(stack);
+            frame.truncate_value_stack_to_original_size(&mut state.stack);
+
+            // The body of a `try_table` may have unwound before becoming unreachable
+            exceptions::emit_landing_pad(
+                &frame,
+                builder,
+                state,
+                module,
+                mod_types,
+                diagnostics,
+                span,
+            )?;
+            let stack = &mut state.stack;
 
             let reachable_anyway = match frame {
                 // If it is a loop we also have to seal the body loop block
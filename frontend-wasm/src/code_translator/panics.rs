@@ -0,0 +1,164 @@
+//! Recognition of calls to the Rust panic machinery.
+//!
+//! A panic in Rust code compiled to Wasm ends in a call to one of a handful of functions in
+//! `core`, whose arguments are, in the common case, constant pointers into the read-only data of
+//! the module: the panic message, or the `fmt::Arguments` it is formatted from, and the `Location`
+//! of the panic in the source code. Those calls
+//! never return, so when we can recover the message at compile-time, we replace the call with an
+//! assertion which always fails, with an error code assigned to the message in the module's
+//! [midenc_hir::ErrorCodeTable]. This allows the message to be shown when the program panics,
+//! rather than just the fact that it trapped.
+//!
+//! Calls whose arguments cannot be recovered are translated as ordinary calls.
+
+use midenc_hir::{
+    diagnostics::SourceSpan, Immediate, InstBuilder, Instruction, Opcode, Type, UnaryOpImm, Value,
+    ValueData,
+};
+
+use crate::module::{
+    func_translation_state::FuncTranslationState, function_builder_ext::FunctionBuilderExt,
+    module_translation_state::ModuleTranslationState, types::FuncIndex, Module,
+};
+
+/// The functions which raise `panic_const` panics, and the messages they panic with
+const PANIC_CONST_MESSAGES: &[(&str, &str)] = &[
+    ("panic_const_add_overflow", "attempt to add with overflow"),
+    ("panic_const_sub_overflow", "attempt to subtract with overflow"),
+    ("panic_const_mul_overflow", "attempt to multiply with overflow"),
+    ("panic_const_div_overflow", "attempt to divide with overflow"),
+    ("panic_const_rem_overflow", "attempt to calculate the remainder with overflow"),
+    ("panic_const_neg_overflow", "attempt to negate with overflow"),
+    ("panic_const_shr_overflow", "attempt to shift right with overflow"),
+    ("panic_const_shl_overflow", "attempt to shift left with overflow"),
+    ("panic_const_div_by_zero", "attempt to divide by zero"),
+    (
+        "panic_const_rem_by_zero",
+        "attempt to calculate the remainder with a divisor of zero",
+    ),
+];
+
+/// Translate a call to `function_index` as a failed assertion, if it is a call to a known panic
+/// function whose message can be recovered from the read-only data of the module.
+///
+/// Returns false, without modifying the function, if the call was not recognized.
+pub fn translate_panic_call(
+    function_index: FuncIndex,
+    state: &mut FuncTranslationState,
+    module_state: &mut ModuleTranslationState,
+    module: &Module,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) -> bool {
+    let num_args = module_state.signature(function_index).params().len();
+    let args = const_args(state.peekn(num_args), builder);
+    let name = module.func_name(function_index);
+    let Some(message) = panic_message(name.as_str(), &args, module_state) else {
+        return false;
+    };
+    // In the unlikely event that the code derived from the message is already taken, we keep the
+    // call, rather than lose track of which message a code refers to
+    let Ok(code) = module_state.error_codes.intern(&message) else {
+        return false;
+    };
+    let zero = builder.ins().u32(0, span);
+    builder.ins().assert_with_error(zero, code, span);
+    builder.ins().unreachable(span);
+    state.popn(num_args);
+    state.reachable = false;
+    true
+}
+
+/// Get the message a call to the panic function `name` with `args` panics with
+fn panic_message(
+    name: &str,
+    args: &[Option<u32>],
+    module_state: &ModuleTranslationState,
+) -> Option<String> {
+    let (message, location) = match (name, args) {
+        ("core::panicking::panic", &[Some(msg_ptr), Some(msg_len), Some(location)])
+        | ("core::option::expect_failed", &[Some(msg_ptr), Some(msg_len), Some(location)])
+        | ("core::result::unwrap_failed", &[Some(msg_ptr), Some(msg_len), _, _, Some(location)]) => {
+            (Some(read_str(msg_ptr, msg_len, module_state)?.to_string()), location)
+        }
+        ("core::panicking::panic_fmt", &[args, Some(location)]) => {
+            (args.and_then(|addr| read_fmt_arguments(addr, module_state)), location)
+        }
+        ("core::panicking::panic_bounds_check", &[_, _, Some(location)]) => {
+            (Some("index out of bounds".to_string()), location)
+        }
+        ("core::option::unwrap_failed", &[Some(location)]) => {
+            (Some("called `Option::unwrap()` on a `None` value".to_string()), location)
+        }
+        (name, &[Some(location)]) => {
+            let name = name.strip_prefix("core::panicking::panic_const::")?;
+            let (_, message) = PANIC_CONST_MESSAGES.iter().find(|(f, _)| *f == name)?;
+            (Some(message.to_string()), location)
+        }
+        _ => return None,
+    };
+    match (read_location(location, module_state), message) {
+        (Some(location), Some(message)) => Some(format!("panicked at {location}: {message}")),
+        (Some(location), None) => Some(format!("panicked at {location}")),
+        (None, Some(message)) => Some(format!("panicked: {message}")),
+        (None, None) => None,
+    }
+}
+
+/// Read a `core::panic::Location` at `addr`, formatted as `file:line:col`
+fn read_location(addr: u32, module_state: &ModuleTranslationState) -> Option<String> {
+    let bytes = module_state.read_rodata(addr, 16)?;
+    let field = |i: usize| u32::from_le_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+    let file = read_str(field(0), field(1), module_state)?;
+    Some(format!("{file}:{}:{}", field(2), field(3)))
+}
+
+/// Read the message of a `core::fmt::Arguments` at `addr`, if it has no arguments to format
+///
+/// The `Arguments` is laid out as `{ pieces: &[&str], fmt: Option<&[Placeholder]>, args:
+/// &[Argument] }`, and when there is nothing to format at runtime, the message is just the
+/// concatenation of its pieces.
+fn read_fmt_arguments(addr: u32, module_state: &ModuleTranslationState) -> Option<String> {
+    let bytes = module_state.read_rodata(addr, 24)?;
+    let field = |i: usize| u32::from_le_bytes(bytes[i * 4..(i + 1) * 4].try_into().unwrap());
+    let (pieces_ptr, pieces_len, fmt_ptr, args_len) = (field(0), field(1), field(2), field(5));
+    if fmt_ptr != 0 || args_len != 0 {
+        return None;
+    }
+    let pieces = module_state.read_rodata(pieces_ptr, pieces_len.checked_mul(8)?)?;
+    pieces
+        .chunks_exact(8)
+        .map(|piece| {
+            let ptr = u32::from_le_bytes(piece[..4].try_into().unwrap());
+            let len = u32::from_le_bytes(piece[4..].try_into().unwrap());
+            read_str(ptr, len, module_state)
+        })
+        .collect()
+}
+
+/// Read a UTF-8 string of `len` bytes at `addr`
+fn read_str(addr: u32, len: u32, module_state: &ModuleTranslationState) -> Option<&str> {
+    core::str::from_utf8(module_state.read_rodata(addr, len)?).ok()
+}
+
+/// Get the values of those of `args` which are `i32` constants
+fn const_args(args: &[Value], builder: &FunctionBuilderExt) -> Vec<Option<u32>> {
+    let dfg = builder.data_flow_graph();
+    args.iter()
+        .map(|arg| match dfg.value_data(*arg) {
+            ValueData::Inst {
+                ty: Type::I32,
+                inst,
+                ..
+            } => match &dfg[*inst] {
+                Instruction::UnaryOpImm(UnaryOpImm {
+                    op: Opcode::ImmI32,
+                    imm: Immediate::I32(value),
+                    ..
+                }) => Some(*value as u32),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,581 @@
+//! Software lowering of the integer subset of the WebAssembly `simd128` proposal.
+//!
+//! Miden has no vector instructions, so a `v128` value is represented as a [Type::U128], and
+//! vector operations are lowered to sequences of scalar operations on its four 32-bit lanes.
+//!
+//! The lanes are laid out in the same order as in linear memory, i.e. 32-bit lane `0` is the most
+//! significant limb of the `u128`, and is found at the lowest address when the value is stored,
+//! so `i32.load` of the `n`th 32-bit chunk of a stored vector observes 32-bit lane `n`. Vectors
+//! are loaded and stored one 32-bit lane at a time.
+//!
+//! Narrower and wider lanes are defined in terms of the 32-bit lanes, following the little-endian
+//! lane order of the specification: 8-bit lane `n` is byte `n % 4` (counting from the least
+//! significant byte) of 32-bit lane `n / 4`, and 64-bit lane `n` is formed from 32-bit lanes
+//! `2n` (the low half) and `2n + 1` (the high half).
+//!
+//! Floating-point vector operations are not supported, as floats themselves are not.
+
+use midenc_hir::{diagnostics::SourceSpan, Immediate, InstBuilder, Type, Value};
+use wasmparser::{MemArg, V128};
+
+use super::prepare_addr;
+use crate::module::{
+    func_translation_state::FuncTranslationState, function_builder_ext::FunctionBuilderExt,
+};
+
+/// The shape of the lanes a `v128` is interpreted as by an operator
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lanes {
+    I8x16,
+    I16x8,
+    I32x4,
+    I64x2,
+}
+
+impl Lanes {
+    /// The width of each lane, in bits
+    fn lane_bits(self) -> u32 {
+        match self {
+            Self::I8x16 => 8,
+            Self::I16x8 => 16,
+            Self::I32x4 => 32,
+            Self::I64x2 => 64,
+        }
+    }
+
+    /// For lanes narrower than 32 bits, the mask selecting the low lane of a 32-bit lane
+    fn lane_mask(self) -> u32 {
+        debug_assert!(self.lane_bits() < 32);
+        (1 << self.lane_bits()) - 1
+    }
+
+    /// For lanes narrower than 32 bits, the mask selecting the sign bit of every lane packed in a
+    /// 32-bit lane
+    fn sign_bits(self) -> u32 {
+        match self {
+            Self::I8x16 => 0x8080_8080,
+            Self::I16x8 => 0x8000_8000,
+            Self::I32x4 | Self::I64x2 => unreachable!(),
+        }
+    }
+}
+
+/// Split a `v128` value into its four 32-bit lanes, lane `0` first
+fn split(vector: Value, builder: &mut FunctionBuilderExt, span: SourceSpan) -> [Value; 4] {
+    [0, 1, 2, 3].map(|lane| {
+        let shifted = match lane {
+            3 => vector,
+            lane => builder.ins().shr_imm(vector, 32 * (3 - lane), span),
+        };
+        builder.ins().trunc(shifted, Type::U32, span)
+    })
+}
+
+/// Build a `v128` value from its four 32-bit lanes, lane `0` first
+fn join(lanes: [Value; 4], builder: &mut FunctionBuilderExt, span: SourceSpan) -> Value {
+    let mut vector = builder.ins().zext(lanes[3], Type::U128, span);
+    for (lane, value) in lanes.into_iter().enumerate().take(3) {
+        let value = builder.ins().zext(value, Type::U128, span);
+        let value = builder.ins().shl_imm(value, 32 * (3 - lane as u32), span);
+        vector = builder.ins().bor(vector, value, span);
+    }
+    vector
+}
+
+/// Combine a pair of 32-bit lanes into a 64-bit lane
+fn join_u64(lo: Value, hi: Value, builder: &mut FunctionBuilderExt, span: SourceSpan) -> Value {
+    let lo = builder.ins().zext(lo, Type::U64, span);
+    let hi = builder.ins().zext(hi, Type::U64, span);
+    let hi = builder.ins().shl_imm(hi, 32, span);
+    builder.ins().bor(hi, lo, span)
+}
+
+/// Split a 64-bit lane into a pair of 32-bit lanes, the low half first
+fn split_u64(value: Value, builder: &mut FunctionBuilderExt, span: SourceSpan) -> (Value, Value) {
+    let lo = builder.ins().trunc(value, Type::U32, span);
+    let hi = builder.ins().shr_imm(value, 32, span);
+    let hi = builder.ins().trunc(hi, Type::U32, span);
+    (lo, hi)
+}
+
+/// Pop the operands of a lane-wise binary operator, and push the result of applying `op` to each
+/// pair of 32-bit lanes.
+fn lanewise<F>(
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+    mut op: F,
+) where
+    F: FnMut(&mut FunctionBuilderExt, Value, Value) -> Value,
+{
+    let (lhs, rhs) = state.pop2();
+    let lhs = split(lhs, builder, span);
+    let rhs = split(rhs, builder, span);
+    let mut result = lhs;
+    for (lane, value) in result.iter_mut().enumerate() {
+        *value = op(builder, lhs[lane], rhs[lane]);
+    }
+    state.push1(join(result, builder, span));
+}
+
+/// Like [lanewise], but applies `op` to each pair of 64-bit lanes.
+fn lanewise_u64<F>(
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+    mut op: F,
+) where
+    F: FnMut(&mut FunctionBuilderExt, Value, Value) -> Value,
+{
+    let (lhs, rhs) = state.pop2();
+    let lhs = split(lhs, builder, span);
+    let rhs = split(rhs, builder, span);
+    let mut result = lhs;
+    for lane in [0, 2] {
+        let a = join_u64(lhs[lane], lhs[lane + 1], builder, span);
+        let b = join_u64(rhs[lane], rhs[lane + 1], builder, span);
+        let value = op(builder, a, b);
+        (result[lane], result[lane + 1]) = split_u64(value, builder, span);
+    }
+    state.push1(join(result, builder, span));
+}
+
+/// Translate `v128.const`, whose lanes are encoded in little-endian order
+pub fn translate_const(
+    value: &V128,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let bytes = value.bytes();
+    let lanes: [u32; 4] = core::array::from_fn(|lane| {
+        u32::from_le_bytes([
+            bytes[lane * 4],
+            bytes[lane * 4 + 1],
+            bytes[lane * 4 + 2],
+            bytes[lane * 4 + 3],
+        ])
+    });
+    let value = lanes.into_iter().fold(0u128, |acc, lane| (acc << 32) | lane as u128);
+    state.push1(builder.ins().u128(value, span));
+}
+
+/// Translate `v128.load`
+pub fn translate_load(
+    memarg: &MemArg,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let addr = state.pop1();
+    let lanes = [0, 1, 2, 3].map(|lane| {
+        let ptr = prepare_addr(addr, &Type::U32, Some(&lane_memarg(memarg, lane)), builder, span);
+        builder.ins().load(ptr, span)
+    });
+    state.push1(join(lanes, builder, span));
+}
+
+/// Translate `v128.store`
+pub fn translate_store(
+    memarg: &MemArg,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (addr, vector) = state.pop2();
+    let lanes = split(vector, builder, span);
+    for (lane, value) in lanes.into_iter().enumerate() {
+        let memarg = lane_memarg(memarg, lane as u64);
+        let ptr = prepare_addr(addr, &Type::U32, Some(&memarg), builder, span);
+        builder.ins().store(ptr, value, span);
+    }
+}
+
+/// Get the memory argument used to access the 32-bit `lane` of a vector accessed with `memarg`
+fn lane_memarg(memarg: &MemArg, lane: u64) -> MemArg {
+    MemArg {
+        // Lanes are at most 4-byte aligned, regardless of the alignment of the vector
+        align: memarg.align.min(2),
+        offset: memarg.offset + 4 * lane,
+        ..*memarg
+    }
+}
+
+/// Translate `v128.andnot`, i.e. `lhs & !rhs`
+pub fn translate_andnot(
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (lhs, rhs) = state.pop2();
+    let rhs = builder.ins().bnot(rhs, span);
+    state.push1(builder.ins().band(lhs, rhs, span));
+}
+
+/// Translate `v128.bitselect`, which selects each bit from `lhs` if the corresponding bit of the
+/// mask is set, and from `rhs` otherwise
+pub fn translate_bitselect(
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (lhs, rhs, mask) = state.pop3();
+    let lhs = builder.ins().band(lhs, mask, span);
+    let mask = builder.ins().bnot(mask, span);
+    let rhs = builder.ins().band(rhs, mask, span);
+    state.push1(builder.ins().bor(lhs, rhs, span));
+}
+
+/// Translate `v128.any_true`
+pub fn translate_any_true(
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let vector = state.pop1();
+    let is_true = builder.ins().neq_imm(vector, Immediate::U128(0), span);
+    state.push1(builder.ins().zext(is_true, Type::I32, span));
+}
+
+/// Translate `i32x4.all_true`
+pub fn translate_all_true(
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let vector = state.pop1();
+    let lanes = split(vector, builder, span);
+    let mut all_true = builder.ins().neq_imm(lanes[0], Immediate::U32(0), span);
+    for lane in lanes.into_iter().skip(1) {
+        let is_true = builder.ins().neq_imm(lane, Immediate::U32(0), span);
+        all_true = builder.ins().and(all_true, is_true, span);
+    }
+    state.push1(builder.ins().zext(all_true, Type::I32, span));
+}
+
+/// Translate `*.splat`, i.e. build a vector with every lane set to the scalar operand
+pub fn translate_splat(
+    lanes: Lanes,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let value = state.pop1();
+    let value = match lanes {
+        Lanes::I64x2 => builder.ins().bitcast(value, Type::U64, span),
+        _ => builder.ins().bitcast(value, Type::U32, span),
+    };
+    state.push1(splat(lanes, value, builder, span));
+}
+
+/// Translate the `v128.loadN_splat` operators, given the value loaded from memory
+pub fn translate_load_splat(
+    lanes: Lanes,
+    value: Value,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let value = match lanes {
+        Lanes::I8x16 | Lanes::I16x8 => builder.ins().zext(value, Type::U32, span),
+        Lanes::I32x4 | Lanes::I64x2 => value,
+    };
+    state.push1(splat(lanes, value, builder, span));
+}
+
+/// Build a vector with every lane set to `value`, which is a `u64` for 64-bit lanes, and a `u32`
+/// otherwise
+fn splat(lanes: Lanes, value: Value, builder: &mut FunctionBuilderExt, span: SourceSpan) -> Value {
+    let lane = match lanes {
+        Lanes::I64x2 => {
+            let (lo, hi) = split_u64(value, builder, span);
+            return join([lo, hi, lo, hi], builder, span);
+        }
+        Lanes::I32x4 => value,
+        Lanes::I16x8 | Lanes::I8x16 => {
+            let value = builder.ins().band_imm(value, Immediate::U32(lanes.lane_mask()), span);
+            // Replicate the lane across the 32-bit lane, i.e. multiply by 0x01010101 for 8-bit
+            // lanes
+            let ones = u32::MAX / lanes.lane_mask();
+            builder.ins().mul_imm_wrapping(value, Immediate::U32(ones), span)
+        }
+    };
+    join([lane; 4], builder, span)
+}
+
+/// Translate the `v128.loadN_zero` operators, given the value loaded from memory
+pub fn translate_load_zero(
+    value: Value,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let zero = builder.ins().u32(0, span);
+    let vector = if builder.data_flow_graph().value_type(value) == &Type::U64 {
+        let (lo, hi) = split_u64(value, builder, span);
+        join([lo, hi, zero, zero], builder, span)
+    } else {
+        join([value, zero, zero, zero], builder, span)
+    };
+    state.push1(vector);
+}
+
+/// Translate `*.extract_lane`, `*.extract_lane_s` and `*.extract_lane_u`
+pub fn translate_extract_lane(
+    lanes: Lanes,
+    lane: u8,
+    signed: bool,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let vector = state.pop1();
+    let words = split(vector, builder, span);
+    let lane = lane as u32;
+    let value = match lanes {
+        Lanes::I64x2 => {
+            let lane = 2 * lane as usize;
+            let value = join_u64(words[lane], words[lane + 1], builder, span);
+            builder.ins().bitcast(value, Type::I64, span)
+        }
+        Lanes::I32x4 => builder.ins().bitcast(words[lane as usize], Type::I32, span),
+        Lanes::I16x8 | Lanes::I8x16 => {
+            let per_word = 32 / lanes.lane_bits();
+            let word = words[(lane / per_word) as usize];
+            let shift = (lane % per_word) * lanes.lane_bits();
+            let value = match shift {
+                0 => word,
+                shift => builder.ins().shr_imm(word, shift, span),
+            };
+            let mut value = builder.ins().band_imm(value, Immediate::U32(lanes.lane_mask()), span);
+            if signed {
+                // Sign-extend the lane by flipping the sign bit, and subtracting it back out, i.e.
+                // `(x ^ 0x80) - 0x80` for 8-bit lanes
+                let sign_bit = Immediate::U32(1 << (lanes.lane_bits() - 1));
+                value = builder.ins().bxor_imm(value, sign_bit, span);
+                value = builder.ins().sub_imm_wrapping(value, sign_bit, span);
+            }
+            builder.ins().bitcast(value, Type::I32, span)
+        }
+    };
+    state.push1(value);
+}
+
+/// Translate `*.replace_lane`
+pub fn translate_replace_lane(
+    lanes: Lanes,
+    lane: u8,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (vector, value) = state.pop2();
+    let mut words = split(vector, builder, span);
+    let lane = lane as u32;
+    match lanes {
+        Lanes::I64x2 => {
+            let lane = 2 * lane as usize;
+            let value = builder.ins().bitcast(value, Type::U64, span);
+            (words[lane], words[lane + 1]) = split_u64(value, builder, span);
+        }
+        Lanes::I32x4 => {
+            words[lane as usize] = builder.ins().bitcast(value, Type::U32, span);
+        }
+        Lanes::I16x8 | Lanes::I8x16 => {
+            let per_word = 32 / lanes.lane_bits();
+            let word = &mut words[(lane / per_word) as usize];
+            let shift = (lane % per_word) * lanes.lane_bits();
+            let value = builder.ins().bitcast(value, Type::U32, span);
+            let value = builder.ins().band_imm(value, Immediate::U32(lanes.lane_mask()), span);
+            let value = builder.ins().shl_imm(value, shift, span);
+            let cleared =
+                builder
+                    .ins()
+                    .band_imm(*word, Immediate::U32(!(lanes.lane_mask() << shift)), span);
+            *word = builder.ins().bor(cleared, value, span);
+        }
+    }
+    state.push1(join(words, builder, span));
+}
+
+/// Translate `*.add`, with wrapping semantics for each lane
+pub fn translate_add(
+    lanes: Lanes,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    match lanes {
+        Lanes::I64x2 => lanewise_u64(state, builder, span, |builder, a, b| {
+            builder.ins().add_wrapping(a, b, span)
+        }),
+        Lanes::I32x4 => {
+            lanewise(state, builder, span, |builder, a, b| builder.ins().add_wrapping(a, b, span))
+        }
+        Lanes::I16x8 | Lanes::I8x16 => {
+            // Add the lanes packed in each 32-bit lane at once, without carrying across lanes by
+            // masking off the sign bits first, and then computing the sign bits separately:
+            //
+            //     ((a & !H) + (b & !H)) ^ ((a ^ b) & H)
+            let sign_bits = lanes.sign_bits();
+            lanewise(state, builder, span, |builder, a, b| {
+                let a_low = builder.ins().band_imm(a, Immediate::U32(!sign_bits), span);
+                let b_low = builder.ins().band_imm(b, Immediate::U32(!sign_bits), span);
+                let sum = builder.ins().add_wrapping(a_low, b_low, span);
+                let signs = builder.ins().bxor(a, b, span);
+                let signs = builder.ins().band_imm(signs, Immediate::U32(sign_bits), span);
+                builder.ins().bxor(sum, signs, span)
+            })
+        }
+    }
+}
+
+/// Translate `*.sub`, with wrapping semantics for each lane
+pub fn translate_sub(
+    lanes: Lanes,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    match lanes {
+        Lanes::I64x2 => lanewise_u64(state, builder, span, |builder, a, b| {
+            builder.ins().sub_wrapping(a, b, span)
+        }),
+        Lanes::I32x4 => {
+            lanewise(state, builder, span, |builder, a, b| builder.ins().sub_wrapping(a, b, span))
+        }
+        Lanes::I16x8 | Lanes::I8x16 => {
+            // Like `add`, setting the sign bits of the minuend ensures no lane borrows from the
+            // next one, and the sign bits are then computed separately:
+            //
+            //     ((a | H) - (b & !H)) ^ ((a ^ !b) & H)
+            let sign_bits = lanes.sign_bits();
+            lanewise(state, builder, span, |builder, a, b| {
+                let a_high = builder.ins().bor_imm(a, Immediate::U32(sign_bits), span);
+                let b_low = builder.ins().band_imm(b, Immediate::U32(!sign_bits), span);
+                let difference = builder.ins().sub_wrapping(a_high, b_low, span);
+                let not_b = builder.ins().bnot(b, span);
+                let signs = builder.ins().bxor(a, not_b, span);
+                let signs = builder.ins().band_imm(signs, Immediate::U32(sign_bits), span);
+                builder.ins().bxor(difference, signs, span)
+            })
+        }
+    }
+}
+
+/// Translate `i32x4.mul` and `i64x2.mul`, with wrapping semantics for each lane
+pub fn translate_mul(
+    lanes: Lanes,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    match lanes {
+        Lanes::I64x2 => lanewise_u64(state, builder, span, |builder, a, b| {
+            builder.ins().mul_wrapping(a, b, span)
+        }),
+        Lanes::I32x4 => {
+            lanewise(state, builder, span, |builder, a, b| builder.ins().mul_wrapping(a, b, span))
+        }
+        Lanes::I16x8 | Lanes::I8x16 => unreachable!("there is no {lanes:?}.mul operator"),
+    }
+}
+
+/// Translate `i32x4.eq` and `i32x4.ne`, which set each lane to all ones if the comparison holds,
+/// and zero otherwise
+pub fn translate_i32x4_compare(
+    equal: bool,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    lanewise(state, builder, span, |builder, a, b| {
+        let is_true = if equal {
+            builder.ins().eq(a, b, span)
+        } else {
+            builder.ins().neq(a, b, span)
+        };
+        let is_true = builder.ins().zext(is_true, Type::U32, span);
+        let zero = builder.ins().u32(0, span);
+        builder.ins().sub_wrapping(zero, is_true, span)
+    });
+}
+
+/// The kind of shift performed by `i32x4.shl`, `i32x4.shr_s` and `i32x4.shr_u`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shift {
+    Left,
+    RightSigned,
+    RightUnsigned,
+}
+
+/// Translate the `i32x4` shifts, which shift every lane by the same scalar amount
+pub fn translate_i32x4_shift(
+    shift: Shift,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (vector, amount) = state.pop2();
+    // The shift amount is taken modulo the lane width
+    let amount = builder.ins().bitcast(amount, Type::U32, span);
+    let amount = builder.ins().band_imm(amount, Immediate::U32(31), span);
+    let mut lanes = split(vector, builder, span);
+    for lane in lanes.iter_mut() {
+        *lane = match shift {
+            Shift::Left => builder.ins().shl(*lane, amount, span),
+            Shift::RightUnsigned => builder.ins().shr(*lane, amount, span),
+            Shift::RightSigned => {
+                let signed = builder.ins().bitcast(*lane, Type::I32, span);
+                let shifted = builder.ins().shr(signed, amount, span);
+                builder.ins().bitcast(shifted, Type::U32, span)
+            }
+        };
+    }
+    state.push1(join(lanes, builder, span));
+}
+
+/// Translate `i8x16.shuffle`, which selects each byte of the result from the 32 bytes of its
+/// two operands
+pub fn translate_shuffle(
+    indices: &[u8; 16],
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (lhs, rhs) = state.pop2();
+    let lhs = split(lhs, builder, span);
+    let rhs = split(rhs, builder, span);
+    let words = [lhs, rhs].concat();
+
+    let mut result = lhs;
+    for (lane, indices) in indices.chunks_exact(4).enumerate() {
+        // Shuffles of whole 32-bit lanes are common, and are just a move of the lane
+        let first = indices[0];
+        if first % 4 == 0 && indices.iter().zip(first..).all(|(index, expected)| *index == expected)
+        {
+            result[lane] = words[first as usize / 4];
+            continue;
+        }
+
+        let mut word = None;
+        for (position, index) in indices.iter().enumerate() {
+            let source = words[*index as usize / 4];
+            let byte = match (*index % 4) as u32 * 8 {
+                0 => source,
+                shift => builder.ins().shr_imm(source, shift, span),
+            };
+            let byte = builder.ins().band_imm(byte, Immediate::U32(0xff), span);
+            let byte = match position as u32 * 8 {
+                0 => byte,
+                shift => builder.ins().shl_imm(byte, shift, span),
+            };
+            word = Some(match word {
+                None => byte,
+                Some(word) => builder.ins().bor(word, byte, span),
+            });
+        }
+        result[lane] = word.unwrap();
+    }
+    state.push1(join(result, builder, span));
+}
@@ -0,0 +1,249 @@
+//! Software lowering of the WebAssembly `f64` operators.
+//!
+//! Miden has no floating-point instructions, so an `f64` is represented by its IEEE-754 bit
+//! pattern, as a [Type::U64], and arithmetic on it is lowered to calls to the `intrinsics::f64`
+//! module, which is only linked in when software floating point is enabled with `-C soft-float`.
+//!
+//! Operators which only manipulate the bit pattern, i.e. `abs`, `neg`, `copysign` and the
+//! reinterpretations, are lowered to bitwise operations inline.
+//!
+//! `f32` is not supported, as the Miden SDK uses it to represent a field element, and neither is
+//! `f64.sqrt`.
+
+use midenc_hir::{
+    diagnostics::SourceSpan, AbiParam, FunctionIdent, Ident, Immediate, InstBuilder, Signature,
+    Symbol, Type, Value,
+};
+use wasmparser::Operator;
+
+use super::{translate_load, translate_store};
+use crate::module::{
+    func_translation_state::FuncTranslationState, function_builder_ext::FunctionBuilderExt,
+};
+
+/// The name of the module containing the soft-float intrinsics
+pub(crate) const INTRINSICS_MODULE: &str = "intrinsics::f64";
+
+const SIGN_BIT: u64 = 1 << 63;
+
+/// Returns true if `op` is an `f64` operator which is lowered by [translate_operator]
+pub fn is_f64_operator(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::F64Load { .. }
+            | Operator::F64Store { .. }
+            | Operator::F64Const { .. }
+            | Operator::F64Abs
+            | Operator::F64Neg
+            | Operator::F64Copysign
+            | Operator::F64Ceil
+            | Operator::F64Floor
+            | Operator::F64Trunc
+            | Operator::F64Nearest
+            | Operator::F64Add
+            | Operator::F64Sub
+            | Operator::F64Mul
+            | Operator::F64Div
+            | Operator::F64Min
+            | Operator::F64Max
+            | Operator::F64Eq
+            | Operator::F64Ne
+            | Operator::F64Lt
+            | Operator::F64Gt
+            | Operator::F64Le
+            | Operator::F64Ge
+            | Operator::F64ConvertI32S
+            | Operator::F64ConvertI32U
+            | Operator::F64ConvertI64S
+            | Operator::F64ConvertI64U
+            | Operator::I32TruncF64S
+            | Operator::I32TruncF64U
+            | Operator::I64TruncF64S
+            | Operator::I64TruncF64U
+            | Operator::I32TruncSatF64S
+            | Operator::I32TruncSatF64U
+            | Operator::I64TruncSatF64S
+            | Operator::I64TruncSatF64U
+            | Operator::F64ReinterpretI64
+            | Operator::I64ReinterpretF64
+    )
+}
+
+/// Translate an operator for which [is_f64_operator] returns true
+pub fn translate_operator(
+    op: &Operator,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    use Type::*;
+
+    match op {
+        Operator::F64Load { memarg } => translate_load(U64, memarg, state, builder, span),
+        Operator::F64Store { memarg } => translate_store(U64, memarg, state, builder, span),
+        Operator::F64Const { value } => state.push1(builder.ins().u64(value.bits(), span)),
+        Operator::F64Abs => {
+            let arg = state.pop1_bitcasted(U64, builder, span);
+            state.push1(builder.ins().band_imm(arg, Immediate::U64(!SIGN_BIT), span));
+        }
+        Operator::F64Neg => {
+            let arg = state.pop1_bitcasted(U64, builder, span);
+            state.push1(builder.ins().bxor_imm(arg, Immediate::U64(SIGN_BIT), span));
+        }
+        Operator::F64Copysign => {
+            let (magnitude, sign) = state.pop2_bitcasted(U64, builder, span);
+            let magnitude = builder.ins().band_imm(magnitude, Immediate::U64(!SIGN_BIT), span);
+            let sign = builder.ins().band_imm(sign, Immediate::U64(SIGN_BIT), span);
+            state.push1(builder.ins().bor(magnitude, sign, span));
+        }
+        Operator::F64Ceil => translate_unary("ceil", state, builder, span),
+        Operator::F64Floor => translate_unary("floor", state, builder, span),
+        Operator::F64Trunc => translate_unary("trunc", state, builder, span),
+        Operator::F64Nearest => translate_unary("nearest", state, builder, span),
+        Operator::F64Add => translate_binary("add", state, builder, span),
+        Operator::F64Sub => translate_binary("sub", state, builder, span),
+        Operator::F64Mul => translate_binary("mul", state, builder, span),
+        Operator::F64Div => translate_binary("div", state, builder, span),
+        Operator::F64Min => translate_binary("min", state, builder, span),
+        Operator::F64Max => translate_binary("max", state, builder, span),
+        Operator::F64Eq => translate_compare("eq", false, false, state, builder, span),
+        Operator::F64Ne => translate_compare("eq", false, true, state, builder, span),
+        Operator::F64Lt => translate_compare("lt", false, false, state, builder, span),
+        Operator::F64Gt => translate_compare("lt", true, false, state, builder, span),
+        Operator::F64Le => translate_compare("le", false, false, state, builder, span),
+        Operator::F64Ge => translate_compare("le", true, false, state, builder, span),
+        Operator::F64ConvertI32S => translate_from_int("from_i32", I32, state, builder, span),
+        Operator::F64ConvertI32U => translate_from_int("from_u32", U32, state, builder, span),
+        Operator::F64ConvertI64S => translate_from_int("from_i64", I64, state, builder, span),
+        Operator::F64ConvertI64U => translate_from_int("from_u64", U64, state, builder, span),
+        Operator::I32TruncF64S => translate_to_int("trunc_i32", I32, state, builder, span),
+        Operator::I32TruncF64U => translate_to_int("trunc_u32", U32, state, builder, span),
+        Operator::I64TruncF64S => translate_to_int("trunc_i64", I64, state, builder, span),
+        Operator::I64TruncF64U => translate_to_int("trunc_u64", U64, state, builder, span),
+        Operator::I32TruncSatF64S => translate_to_int("trunc_sat_i32", I32, state, builder, span),
+        Operator::I32TruncSatF64U => translate_to_int("trunc_sat_u32", U32, state, builder, span),
+        Operator::I64TruncSatF64S => translate_to_int("trunc_sat_i64", I64, state, builder, span),
+        Operator::I64TruncSatF64U => translate_to_int("trunc_sat_u64", U64, state, builder, span),
+        Operator::F64ReinterpretI64 => {
+            let arg = state.pop1_bitcasted(U64, builder, span);
+            state.push1(arg);
+        }
+        Operator::I64ReinterpretF64 => {
+            let arg = state.pop1_bitcasted(I64, builder, span);
+            state.push1(arg);
+        }
+        op => unreachable!("{op:?} is not an f64 operator"),
+    }
+}
+
+/// Translate a unary operator on an `f64` to a call to `intrinsics::f64::<name>`
+fn translate_unary(
+    name: &str,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let arg = state.pop1_bitcasted(Type::U64, builder, span);
+    let result = call_intrinsic(name, &[arg], Type::U64, builder, span);
+    state.push1(result);
+}
+
+/// Translate a binary operator on two `f64`s to a call to `intrinsics::f64::<name>`
+fn translate_binary(
+    name: &str,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (arg1, arg2) = state.pop2_bitcasted(Type::U64, builder, span);
+    let result = call_intrinsic(name, &[arg1, arg2], Type::U64, builder, span);
+    state.push1(result);
+}
+
+/// Translate a comparison of two `f64`s to a call to `intrinsics::f64::<name>`, optionally
+/// swapping the operands, and/or negating the result, to derive the comparisons which have no
+/// intrinsic of their own.
+fn translate_compare(
+    name: &str,
+    swap: bool,
+    negate: bool,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let (arg1, arg2) = state.pop2_bitcasted(Type::U64, builder, span);
+    let args = if swap { [arg2, arg1] } else { [arg1, arg2] };
+    let result = call_intrinsic(name, &args, Type::I1, builder, span);
+    let result = if negate {
+        builder.ins().not(result, span)
+    } else {
+        result
+    };
+    state.push1(builder.ins().zext(result, Type::I32, span));
+}
+
+/// Translate a conversion of an integer of type `ty` to an `f64`, to a call to
+/// `intrinsics::f64::<name>`
+fn translate_from_int(
+    name: &str,
+    ty: Type,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let arg = state.pop1_bitcasted(ty, builder, span);
+    let result = call_intrinsic(name, &[arg], Type::U64, builder, span);
+    state.push1(result);
+}
+
+/// Translate a conversion of an `f64` to an integer of type `ty`, to a call to
+/// `intrinsics::f64::<name>`
+fn translate_to_int(
+    name: &str,
+    ty: Type,
+    state: &mut FuncTranslationState,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) {
+    let arg = state.pop1_bitcasted(Type::U64, builder, span);
+    let result = call_intrinsic(name, &[arg], ty.clone(), builder, span);
+    // Wasm has no unsigned integer types
+    let result = match ty {
+        Type::U32 => builder.ins().bitcast(result, Type::I32, span),
+        Type::U64 => builder.ins().bitcast(result, Type::I64, span),
+        _ => result,
+    };
+    state.push1(result);
+}
+
+/// Emit a call to `intrinsics::f64::<name>`, importing it first if necessary
+fn call_intrinsic(
+    name: &str,
+    args: &[Value],
+    result: Type,
+    builder: &mut FunctionBuilderExt,
+    span: SourceSpan,
+) -> Value {
+    let func_id = FunctionIdent {
+        module: Ident::with_empty_span(Symbol::intern(INTRINSICS_MODULE)),
+        function: Ident::with_empty_span(Symbol::intern(name)),
+    };
+    if builder
+        .data_flow_graph()
+        .get_import_by_name(func_id.module, func_id.function)
+        .is_none()
+    {
+        let params = args
+            .iter()
+            .map(|arg| AbiParam::new(builder.data_flow_graph().value_type(*arg).clone()))
+            .collect::<Vec<_>>();
+        let signature = Signature::new(params, [AbiParam::new(result)]);
+        let _ = builder.data_flow_graph_mut().import_function(
+            func_id.module,
+            func_id.function,
+            signature,
+        );
+    }
+    let call = builder.ins().call(func_id, args, span);
+    builder.data_flow_graph().first_result(call)
+}
@@ -1,15 +1,24 @@
 use core::fmt::Write;
 
 use expect_test::expect;
-use midenc_hir::Ident;
+use midenc_hir::{testing::TestContext, ErrorCodeTable, Ident};
 
 use crate::{test_utils::test_context, translate, WasmTranslationConfig};
 
 /// Check IR generated for a Wasm op(s).
 /// Wrap Wasm ops in a function and check the IR generated for the entry block of that function.
 fn check_op(wat_op: &str, expected_ir: expect_test::Expect) {
-    let context = test_context();
+    check_op_with_context(test_context(), wat_op, expected_ir)
+}
 
+/// Same as [check_op], but with software floating point enabled
+fn check_soft_float_op(wat_op: &str, expected_ir: expect_test::Expect) {
+    let mut context = test_context();
+    context.session.options.soft_float = true;
+    check_op_with_context(context, wat_op, expected_ir)
+}
+
+fn check_op_with_context(context: TestContext, wat_op: &str, expected_ir: expect_test::Expect) {
     let wat = format!(
         r#"
         (module
@@ -19,6 +28,16 @@ fn check_op(wat_op: &str, expected_ir: expect_test::Expect) {
             )
         )"#,
     );
+    check_module(context, &wat, expected_ir);
+}
+
+/// Translate the module in `wat`, and check the IR generated for the entry block of its
+/// `test_wrapper` function, returning the translated module.
+fn check_module(
+    context: TestContext,
+    wat: &str,
+    expected_ir: expect_test::Expect,
+) -> midenc_hir::Module {
     let wasm = wat::parse_str(wat).unwrap();
     let module = translate(&wasm, &WasmTranslationConfig::default(), &context.session)
         .unwrap()
@@ -43,6 +62,7 @@ fn check_op(wat_op: &str, expected_ir: expect_test::Expect) {
         writeln!(&mut w, "{inst_printer}").unwrap();
     }
     expected_ir.assert_eq(&w);
+    *module
 }
 
 #[test]
@@ -1546,3 +1566,890 @@ fn select_i32() {
         "#]],
     )
 }
+
+/// Check the IR generated for the function `$test_wrapper` of the given Wasm module.
+fn check_module_func(wat: &str, expected_ir: expect_test::Expect) {
+    let context = test_context();
+    let wasm = wat::parse_str(wat).unwrap();
+    let module = translate(&wasm, &WasmTranslationConfig::default(), &context.session)
+        .unwrap()
+        .unwrap_one_module();
+    let func = module.function(Ident::from("test_wrapper")).unwrap();
+    expected_ir.assert_eq(&func.to_string());
+}
+
+#[test]
+fn try_table_catch() {
+    check_module_func(
+        r#"
+        (module
+            (tag $e (param i32))
+            (func $may_throw (param i32)
+                local.get 0
+                throw $e
+            )
+            (func $test_wrapper (param i32) (result i32)
+                block $handler (result i32)
+                    try_table (catch $e $handler)
+                        local.get 0
+                        call $may_throw
+                    end
+                    i32.const 0
+                    return
+                end
+            )
+        )"#,
+        expect![[r#"
+            (func (export #test_wrapper) (param i32) (result i32)
+                (block 0 (param v0 i32)
+                    (call #may_throw v0)
+                    (let (v3 u32) (global.load u32 (global.symbol #__miden_exn_tag)))
+                    (let (v4 i1) (neq v3 0))
+                    (condbr v4 (block 4) (block 5)))
+
+                (block 1 (param v1 i32)
+                    (ret v1))
+
+                (block 2 (param v2 i32)
+                    (br (block 1 v2)))
+
+                (block 3
+                    (let (v10 i32) (const.i32 0))
+                    (ret v10))
+
+                (block 4
+                    (let (v5 u32) (global.load u32 (global.symbol #__miden_exn_tag)))
+                    (let (v6 i1) (eq v5 1))
+                    (condbr v6 (block 6) (block 7)))
+
+                (block 5
+                    (br (block 3)))
+
+                (block 6
+                    (let (v7 (ptr u32)) (global.symbol #__miden_exn_tag))
+                    (let (v8 u32) (const.u32 0))
+                    (store v7 v8)
+                    (let (v9 i32) (global.load i32 (global.symbol #__miden_exn_payload0_0)))
+                    (br (block 2 v9)))
+
+                (block 7
+                    (br (block 8)))
+
+                (block 8
+                    (let (v11 i32) (const.i32 0))
+                    (ret v11))
+            )"#]],
+    )
+}
+
+#[test]
+fn try_table_uncaught_rethrow() {
+    check_module_func(
+        r#"
+        (module
+            (tag $e (param i32))
+            (func $test_wrapper (param i32)
+                block $cleanup (result i32 exnref)
+                    try_table (catch_ref $e $cleanup)
+                        local.get 0
+                        throw $e
+                    end
+                    return
+                end
+                throw_ref
+            )
+        )"#,
+        expect![[r#"
+            (func (export #test_wrapper) (param i32)
+                (block 0 (param v0 i32)
+                    (let (v3 (ptr i32)) (global.symbol #__miden_exn_payload0_0))
+                    (store v3 v0)
+                    (let (v4 u32) (const.u32 1))
+                    (let (v5 (ptr u32)) (global.symbol #__miden_exn_tag))
+                    (store v5 v4)
+                    (br (block 4)))
+
+                (block 1)
+
+                (block 2 (param v1 i32) (param v2 u32)
+                    (let (v11 i1) (neq v2 0))
+                    (assert v11)
+                    (let (v12 (ptr u32)) (global.symbol #__miden_exn_tag))
+                    (store v12 v2)
+                    (br (block 7)))
+
+                (block 3)
+
+                (block 4
+                    (let (v6 u32) (global.load u32 (global.symbol #__miden_exn_tag)))
+                    (let (v7 i1) (eq v6 1))
+                    (condbr v7 (block 5) (block 6)))
+
+                (block 5
+                    (let (v8 (ptr u32)) (global.symbol #__miden_exn_tag))
+                    (let (v9 u32) (const.u32 0))
+                    (store v8 v9)
+                    (let (v10 i32) (global.load i32 (global.symbol #__miden_exn_payload0_0)))
+                    (br (block 2 v10 v6)))
+
+                (block 6
+                    (br (block 7)))
+
+                (block 7
+                    (ret))
+            )"#]],
+    )
+}
+
+/// Any function may be the callee of an indirect call, so an exception raised by it must be
+/// caught by the enclosing `try_table`, just like for a direct call.
+///
+/// NOTE: The indirect call itself is not lowered yet, only the check which follows it.
+#[test]
+fn try_table_call_indirect() {
+    check_module_func(
+        r#"
+        (module
+            (type $callback (func))
+            (tag $e (param i32))
+            (table 1 funcref)
+            (func $test_wrapper (param i32) (result i32)
+                block $handler (result i32)
+                    try_table (catch $e $handler)
+                        local.get 0
+                        call_indirect (type $callback)
+                    end
+                    i32.const 0
+                    return
+                end
+            )
+        )"#,
+        expect![[r#"
+            (func (export #test_wrapper) (param i32) (result i32)
+                (block 0 (param v0 i32)
+                    (let (v3 u32) (global.load u32 (global.symbol #__miden_exn_tag)))
+                    (let (v4 i1) (neq v3 0))
+                    (condbr v4 (block 4) (block 5)))
+
+                (block 1 (param v1 i32)
+                    (ret v1))
+
+                (block 2 (param v2 i32)
+                    (br (block 1 v2)))
+
+                (block 3
+                    (let (v10 i32) (const.i32 0))
+                    (ret v10))
+
+                (block 4
+                    (let (v5 u32) (global.load u32 (global.symbol #__miden_exn_tag)))
+                    (let (v6 i1) (eq v5 1))
+                    (condbr v6 (block 6) (block 7)))
+
+                (block 5
+                    (br (block 3)))
+
+                (block 6
+                    (let (v7 (ptr u32)) (global.symbol #__miden_exn_tag))
+                    (let (v8 u32) (const.u32 0))
+                    (store v7 v8)
+                    (let (v9 i32) (global.load i32 (global.symbol #__miden_exn_payload0_0)))
+                    (br (block 2 v9)))
+
+                (block 7
+                    (br (block 8)))
+
+                (block 8
+                    (let (v11 i32) (const.i32 0))
+                    (ret v11))
+            )"#]],
+    )
+}
+
+/// An exception which is not caught by any function of the module must not escape an exported
+/// function as an ordinary return, as its callers are not aware of the pending exception.
+#[test]
+fn exported_function_uncaught_exception() {
+    check_module_func(
+        r#"
+        (module
+            (tag $e (param i32))
+            (func $test_wrapper (export "test_wrapper") (param i32) (result i32)
+                local.get 0
+                throw $e
+            )
+        )"#,
+        expect![[r#"
+            (func (export #test_wrapper) (param i32) (result i32)
+                (block 0 (param v0 i32)
+                    (let (v2 (ptr i32)) (global.symbol #__miden_exn_payload0_0))
+                    (store v2 v0)
+                    (let (v3 u32) (const.u32 1))
+                    (let (v4 (ptr u32)) (global.symbol #__miden_exn_tag))
+                    (store v4 v3)
+                    (br (block 2)))
+
+                (block 1 (param v1 i32))
+
+                (block 2
+                    (let (v5 u32) (const.u32 0))
+                    (assert 251 v5)
+                    (unreachable))
+            )"#]],
+    )
+}
+
+/// Executes `test_wrapper` of the module in `wat` in the emulator, with `arg` as its argument
+fn execute_module_func(wat: &str, arg: u32) -> u32 {
+    use midenc_codegen_masm::{Emulator, MasmCompiler};
+    use midenc_hir::{FunctionIdent, ProgramBuilder, Stack};
+
+    let mut context = test_context();
+    context
+        .session
+        .options
+        .output_types
+        .insert(midenc_session::OutputType::Masm, None);
+    context
+        .session
+        .options
+        .output_types
+        .insert(midenc_session::OutputType::Mast, None);
+    let wasm = wat::parse_str(wat).unwrap();
+    let module = translate(&wasm, &WasmTranslationConfig::default(), &context.session)
+        .unwrap()
+        .unwrap_one_module();
+    let entrypoint = FunctionIdent {
+        module: module.name,
+        function: Ident::from("test_wrapper"),
+    };
+    let program = ProgramBuilder::new(&context.session.diagnostics)
+        .with_module(module)
+        .unwrap()
+        .with_entrypoint(entrypoint)
+        .link()
+        .expect("failed to link program");
+    let program = MasmCompiler::new(&context.session)
+        .compile(program)
+        .expect("compilation failed")
+        .unwrap_executable();
+
+    let mut emulator = Emulator::default();
+    emulator.load_program(program.freeze()).expect("failed to load program");
+    emulator.stack_mut().push(midenc_hir::Felt::new(arg as u64));
+    let mut stack = emulator.start().expect("execution failed");
+    stack.pop().expect("expected a result").as_int() as u32
+}
+
+#[test]
+#[should_panic(expected = "assertion failed with error code 251")]
+fn exported_function_uncaught_exception_traps() {
+    let wat = r#"
+        (module
+            (tag $e (param i32))
+            (func $may_throw (param i32)
+                local.get 0
+                if
+                    local.get 0
+                    throw $e
+                end
+            )
+            (func $test_wrapper (export "test_wrapper") (param i32) (result i32)
+                local.get 0
+                call $may_throw
+                i32.const 1
+            )
+        )"#;
+    assert_eq!(execute_module_func(wat, 0), 1);
+    execute_module_func(wat, 2);
+}
+
+#[test]
+fn imported_tag_is_unsupported() {
+    let context = test_context();
+    let wasm = wat::parse_str(
+        r#"
+        (module
+            (import "env" "exn" (tag $e (param i32)))
+        )"#,
+    )
+    .unwrap();
+    let result = translate(&wasm, &WasmTranslationConfig::default(), &context.session);
+    let Err(err) = result else {
+        panic!("expected importing an exception tag to fail");
+    };
+    let message = err.to_string();
+    assert!(
+        message.contains("importing exception tag 'env::exn' is not supported"),
+        "{message}"
+    );
+}
+
+#[test]
+fn v128_load_store() {
+    check_op(
+        r#"
+            i32.const 16
+            i32.const 32
+            v128.load
+            v128.store
+        "#,
+        expect![[r#"
+            (let (v0 i32) (const.i32 16))
+            (let (v1 i32) (const.i32 32))
+            (let (v2 u32) (bitcast v1))
+            (let (v3 u32) (mod.unchecked v2 4))
+            (assertz 250 v3)
+            (let (v4 (ptr u32)) (inttoptr v2))
+            (let (v5 u32) (load v4))
+            (let (v6 u32) (bitcast v1))
+            (let (v7 u32) (add.checked v6 4))
+            (let (v8 u32) (mod.unchecked v7 4))
+            (assertz 250 v8)
+            (let (v9 (ptr u32)) (inttoptr v7))
+            (let (v10 u32) (load v9))
+            (let (v11 u32) (bitcast v1))
+            (let (v12 u32) (add.checked v11 8))
+            (let (v13 u32) (mod.unchecked v12 4))
+            (assertz 250 v13)
+            (let (v14 (ptr u32)) (inttoptr v12))
+            (let (v15 u32) (load v14))
+            (let (v16 u32) (bitcast v1))
+            (let (v17 u32) (add.checked v16 12))
+            (let (v18 u32) (mod.unchecked v17 4))
+            (assertz 250 v18)
+            (let (v19 (ptr u32)) (inttoptr v17))
+            (let (v20 u32) (load v19))
+            (let (v21 u128) (zext v20))
+            (let (v22 u128) (zext v5))
+            (let (v23 u128) (shl.wrapping v22 96))
+            (let (v24 u128) (bor v21 v23))
+            (let (v25 u128) (zext v10))
+            (let (v26 u128) (shl.wrapping v25 64))
+            (let (v27 u128) (bor v24 v26))
+            (let (v28 u128) (zext v15))
+            (let (v29 u128) (shl.wrapping v28 32))
+            (let (v30 u128) (bor v27 v29))
+            (let (v31 u128) (shr.wrapping v30 96))
+            (let (v32 u32) (trunc v31))
+            (let (v33 u128) (shr.wrapping v30 64))
+            (let (v34 u32) (trunc v33))
+            (let (v35 u128) (shr.wrapping v30 32))
+            (let (v36 u32) (trunc v35))
+            (let (v37 u32) (trunc v30))
+            (let (v38 u32) (bitcast v0))
+            (let (v39 u32) (mod.unchecked v38 4))
+            (assertz 250 v39)
+            (let (v40 (ptr u32)) (inttoptr v38))
+            (store v40 v32)
+            (let (v41 u32) (bitcast v0))
+            (let (v42 u32) (add.checked v41 4))
+            (let (v43 u32) (mod.unchecked v42 4))
+            (assertz 250 v43)
+            (let (v44 (ptr u32)) (inttoptr v42))
+            (store v44 v34)
+            (let (v45 u32) (bitcast v0))
+            (let (v46 u32) (add.checked v45 8))
+            (let (v47 u32) (mod.unchecked v46 4))
+            (assertz 250 v47)
+            (let (v48 (ptr u32)) (inttoptr v46))
+            (store v48 v36)
+            (let (v49 u32) (bitcast v0))
+            (let (v50 u32) (add.checked v49 12))
+            (let (v51 u32) (mod.unchecked v50 4))
+            (assertz 250 v51)
+            (let (v52 (ptr u32)) (inttoptr v50))
+            (store v52 v37)
+        "#]],
+    )
+}
+
+#[test]
+fn i32x4_add() {
+    check_op(
+        r#"
+            v128.const i32x4 1 2 3 4
+            v128.const i32x4 5 6 7 8
+            i32x4.add
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u128) (const.u128 79228162551157825753847955460))
+            (let (v1 u128) (const.u128 396140812682002152440041832456))
+            (let (v2 u128) (shr.wrapping v0 96))
+            (let (v3 u32) (trunc v2))
+            (let (v4 u128) (shr.wrapping v0 64))
+            (let (v5 u32) (trunc v4))
+            (let (v6 u128) (shr.wrapping v0 32))
+            (let (v7 u32) (trunc v6))
+            (let (v8 u32) (trunc v0))
+            (let (v9 u128) (shr.wrapping v1 96))
+            (let (v10 u32) (trunc v9))
+            (let (v11 u128) (shr.wrapping v1 64))
+            (let (v12 u32) (trunc v11))
+            (let (v13 u128) (shr.wrapping v1 32))
+            (let (v14 u32) (trunc v13))
+            (let (v15 u32) (trunc v1))
+            (let (v16 u32) (add.wrapping v3 v10))
+            (let (v17 u32) (add.wrapping v5 v12))
+            (let (v18 u32) (add.wrapping v7 v14))
+            (let (v19 u32) (add.wrapping v8 v15))
+            (let (v20 u128) (zext v19))
+            (let (v21 u128) (zext v16))
+            (let (v22 u128) (shl.wrapping v21 96))
+            (let (v23 u128) (bor v20 v22))
+            (let (v24 u128) (zext v17))
+            (let (v25 u128) (shl.wrapping v24 64))
+            (let (v26 u128) (bor v23 v25))
+            (let (v27 u128) (zext v18))
+            (let (v28 u128) (shl.wrapping v27 32))
+            (let (v29 u128) (bor v26 v28))
+        "#]],
+    )
+}
+
+#[test]
+fn i8x16_extract_lane_s() {
+    check_op(
+        r#"
+            v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+            i8x16.extract_lane_s 5
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u128) (const.u128 3998088865655093235720858234338020620))
+            (let (v1 u128) (shr.wrapping v0 96))
+            (let (v2 u32) (trunc v1))
+            (let (v3 u128) (shr.wrapping v0 64))
+            (let (v4 u32) (trunc v3))
+            (let (v5 u128) (shr.wrapping v0 32))
+            (let (v6 u32) (trunc v5))
+            (let (v7 u32) (trunc v0))
+            (let (v8 u32) (shr.wrapping v4 8))
+            (let (v9 u32) (band v8 255))
+            (let (v10 u32) (bxor v9 128))
+            (let (v11 u32) (sub.wrapping v10 128))
+            (let (v12 i32) (bitcast v11))
+        "#]],
+    )
+}
+
+#[test]
+fn i8x16_shuffle_whole_lanes() {
+    check_op(
+        r#"
+            v128.const i32x4 1 2 3 4
+            v128.const i32x4 5 6 7 8
+            i8x16.shuffle 12 13 14 15 16 17 18 19 0 1 2 3 28 29 30 31
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u128) (const.u128 79228162551157825753847955460))
+            (let (v1 u128) (const.u128 396140812682002152440041832456))
+            (let (v2 u128) (shr.wrapping v0 96))
+            (let (v3 u32) (trunc v2))
+            (let (v4 u128) (shr.wrapping v0 64))
+            (let (v5 u32) (trunc v4))
+            (let (v6 u128) (shr.wrapping v0 32))
+            (let (v7 u32) (trunc v6))
+            (let (v8 u32) (trunc v0))
+            (let (v9 u128) (shr.wrapping v1 96))
+            (let (v10 u32) (trunc v9))
+            (let (v11 u128) (shr.wrapping v1 64))
+            (let (v12 u32) (trunc v11))
+            (let (v13 u128) (shr.wrapping v1 32))
+            (let (v14 u32) (trunc v13))
+            (let (v15 u32) (trunc v1))
+            (let (v16 u128) (zext v15))
+            (let (v17 u128) (zext v8))
+            (let (v18 u128) (shl.wrapping v17 96))
+            (let (v19 u128) (bor v16 v18))
+            (let (v20 u128) (zext v10))
+            (let (v21 u128) (shl.wrapping v20 64))
+            (let (v22 u128) (bor v19 v21))
+            (let (v23 u128) (zext v3))
+            (let (v24 u128) (shl.wrapping v23 32))
+            (let (v25 u128) (bor v22 v24))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_const() {
+    check_soft_float_op(
+        r#"
+            f64.const 1.5
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u64) (const.u64 4609434218613702656))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_add() {
+    check_soft_float_op(
+        r#"
+            f64.const 1.5
+            f64.const -2
+            f64.add
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u64) (const.u64 4609434218613702656))
+            (let (v1 u64) (const.u64 13835058055282163712))
+            (let (v2 u64) (call (#intrinsics::f64 #add) v0 v1))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_gt() {
+    check_soft_float_op(
+        r#"
+            f64.const 1.5
+            f64.const -2
+            f64.gt
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u64) (const.u64 4609434218613702656))
+            (let (v1 u64) (const.u64 13835058055282163712))
+            (let (v2 i1) (call (#intrinsics::f64 #lt) v1 v0))
+            (let (v3 i32) (zext v2))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_ne() {
+    check_soft_float_op(
+        r#"
+            f64.const 1.5
+            f64.const -2
+            f64.ne
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u64) (const.u64 4609434218613702656))
+            (let (v1 u64) (const.u64 13835058055282163712))
+            (let (v2 i1) (call (#intrinsics::f64 #eq) v0 v1))
+            (let (v3 i1) (not v2))
+            (let (v4 i32) (zext v3))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_copysign() {
+    check_soft_float_op(
+        r#"
+            f64.const 1.5
+            f64.const -2
+            f64.copysign
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u64) (const.u64 4609434218613702656))
+            (let (v1 u64) (const.u64 13835058055282163712))
+            (let (v2 u64) (band v0 9223372036854775807))
+            (let (v3 u64) (band v1 9223372036854775808))
+            (let (v4 u64) (bor v2 v3))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_convert_i32_u() {
+    check_soft_float_op(
+        r#"
+            i32.const 1
+            f64.convert_i32_u
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 i32) (const.i32 1))
+            (let (v1 u32) (bitcast v0))
+            (let (v2 u64) (call (#intrinsics::f64 #from_u32) v1))
+        "#]],
+    )
+}
+
+#[test]
+fn i64_trunc_f64_u() {
+    check_soft_float_op(
+        r#"
+            f64.const 1.5
+            i64.trunc_f64_u
+            drop
+        "#,
+        expect![[r#"
+            (let (v0 u64) (const.u64 4609434218613702656))
+            (let (v1 u64) (call (#intrinsics::f64 #trunc_u64) v0))
+            (let (v2 i64) (bitcast v1))
+        "#]],
+    )
+}
+
+#[test]
+fn f64_load_store() {
+    check_soft_float_op(
+        r#"
+            i32.const 8
+            i32.const 16
+            f64.load
+            f64.store
+        "#,
+        expect![[r#"
+            (let (v0 i32) (const.i32 8))
+            (let (v1 i32) (const.i32 16))
+            (let (v2 u32) (bitcast v1))
+            (let (v3 u32) (mod.unchecked v2 8))
+            (assertz 250 v3)
+            (let (v4 (ptr u64)) (inttoptr v2))
+            (let (v5 u64) (load v4))
+            (let (v6 u32) (bitcast v0))
+            (let (v7 u32) (mod.unchecked v6 8))
+            (assertz 250 v7)
+            (let (v8 (ptr u64)) (inttoptr v6))
+            (store v8 v5)
+        "#]],
+    )
+}
+
+#[test]
+fn f64_signature_requires_soft_float() {
+    let wat = r#"
+        (module
+            (func $test_wrapper (param f64) (result f64)
+                local.get 0
+            )
+        )
+    "#;
+    let wasm = wat::parse_str(wat).unwrap();
+    let context = test_context();
+    let err = translate(&wasm, &WasmTranslationConfig::default(), &context.session)
+        .err()
+        .expect("expected f64 signature to be rejected");
+    assert_eq!(
+        err.to_string(),
+        "Function 'test_wrapper' is not supported: f64 parameters and results require software \
+         floating point, which can be enabled with `-C soft-float`"
+    );
+
+    let mut context = test_context();
+    context.session.options.soft_float = true;
+    let module = translate(&wasm, &WasmTranslationConfig::default(), &context.session)
+        .unwrap()
+        .unwrap_one_module();
+    let func = module.function(Ident::from("test_wrapper")).unwrap();
+    assert_eq!(func.signature.params[0].ty, midenc_hir::Type::U64);
+}
+
+#[test]
+fn panic_with_message() {
+    let module = check_module(
+        test_context(),
+        r#"
+        (module
+            (memory (;0;) 17)
+            (func $test_wrapper
+                i32.const 1048588
+                i32.const 25
+                i32.const 1048616
+                call $core::panicking::panic
+                unreachable
+            )
+            (func $core::panicking::panic (param i32 i32 i32)
+                unreachable
+            )
+            (data $.rodata (i32.const 1048576)
+                "src/lib.rs\00\00"
+                "attempt to divide by zero\00\00\00"
+                "\00\00\10\00" "\0a\00\00\00" "\0b\00\00\00" "\43\00\00\00"
+            )
+        )"#,
+        expect![[r#"
+            (let (v0 i32) (const.i32 1048588))
+            (let (v1 i32) (const.i32 25))
+            (let (v2 i32) (const.i32 1048616))
+            (let (v3 u32) (const.u32 0))
+            (assert 2850000914 v3)
+            (unreachable)
+        "#]],
+    );
+    let error_codes = module.error_codes().iter().collect::<Vec<_>>();
+    assert_eq!(
+        error_codes.as_slice(),
+        &[(
+            ErrorCodeTable::code("panicked at src/lib.rs:11:67: attempt to divide by zero"),
+            "panicked at src/lib.rs:11:67: attempt to divide by zero"
+        )]
+    );
+}
+
+#[test]
+fn panic_fmt_with_custom_message() {
+    // `panic!("custom message")`, whose `fmt::Arguments` is promoted to the read-only data
+    let module = check_module(
+        test_context(),
+        r#"
+        (module
+            (memory (;0;) 17)
+            (func $test_wrapper
+                i32.const 1048612
+                i32.const 1048636
+                call $core::panicking::panic_fmt
+                unreachable
+            )
+            (func $core::panicking::panic_fmt (param i32 i32)
+                unreachable
+            )
+            (data $.rodata (i32.const 1048576)
+                "src/lib.rs\00\00"
+                "custom message\00\00"
+                "\0c\00\10\00" "\0e\00\00\00"
+                "\1c\00\10\00" "\01\00\00\00" "\00\00\00\00" "\00\00\00\00" "\04\00\00\00" "\00\00\00\00"
+                "\00\00\10\00" "\0a\00\00\00" "\0b\00\00\00" "\43\00\00\00"
+            )
+        )"#,
+        expect![[r#"
+            (let (v0 i32) (const.i32 1048612))
+            (let (v1 i32) (const.i32 1048636))
+            (let (v2 u32) (const.u32 0))
+            (assert 2621084714 v2)
+            (unreachable)
+        "#]],
+    );
+    let error_codes = module.error_codes().iter().collect::<Vec<_>>();
+    assert_eq!(
+        error_codes.as_slice(),
+        &[(
+            ErrorCodeTable::code("panicked at src/lib.rs:11:67: custom message"),
+            "panicked at src/lib.rs:11:67: custom message"
+        )]
+    );
+}
+
+#[test]
+fn panic_fmt_with_runtime_arguments() {
+    // `panic!("custom {}", x)`, whose `fmt::Arguments` is built on the stack, so only the location
+    // of the panic can be recovered
+    let module = check_module(
+        test_context(),
+        r#"
+        (module
+            (memory (;0;) 17)
+            (func $test_wrapper (param i32)
+                local.get 0
+                i32.const 1048588
+                call $core::panicking::panic_fmt
+                unreachable
+            )
+            (func $core::panicking::panic_fmt (param i32 i32)
+                unreachable
+            )
+            (data $.rodata (i32.const 1048576)
+                "src/lib.rs\00\00"
+                "\00\00\10\00" "\0a\00\00\00" "\0b\00\00\00" "\43\00\00\00"
+            )
+        )"#,
+        expect![[r#"
+            (let (v1 i32) (const.i32 1048588))
+            (let (v2 u32) (const.u32 0))
+            (assert 2215712907 v2)
+            (unreachable)
+        "#]],
+    );
+    let error_codes = module.error_codes().iter().collect::<Vec<_>>();
+    assert_eq!(
+        error_codes.as_slice(),
+        &[(
+            ErrorCodeTable::code("panicked at src/lib.rs:11:67"),
+            "panicked at src/lib.rs:11:67"
+        )]
+    );
+}
+
+#[test]
+fn panic_with_unknown_message_is_a_call() {
+    // The message is in writable memory, so it cannot be known at compile-time
+    let module = check_module(
+        test_context(),
+        r#"
+        (module
+            (memory (;0;) 17)
+            (func $test_wrapper
+                i32.const 1048576
+                i32.const 4
+                i32.const 1048580
+                call $core::panicking::panic
+                unreachable
+            )
+            (func $core::panicking::panic (param i32 i32 i32)
+                unreachable
+            )
+            (data $.data (i32.const 1048576) "oops")
+        )"#,
+        expect![[r#"
+            (let (v0 i32) (const.i32 1048576))
+            (let (v1 i32) (const.i32 4))
+            (let (v2 i32) (const.i32 1048580))
+            (call #core::panicking::panic v0 v1 v2)
+            (unreachable)
+        "#]],
+    );
+    assert!(module.error_codes().is_empty());
+}
+
+#[test]
+fn br_table_targets_in_table_order() {
+    // The cases of the switch must branch to the targets in the order they appear in the table,
+    // not in order of their depth
+    check_module_func(
+        r#"
+        (module
+            (func $test_wrapper (param i32) (result i32)
+                block $b0
+                    block $b1
+                        block $b2
+                            local.get 0
+                            br_table $b0 $b2 $b1 $b2
+                        end
+                        i32.const 2
+                        return
+                    end
+                    i32.const 1
+                    return
+                end
+                i32.const 0
+            )
+        )"#,
+        expect![[r#"
+            (func (export #test_wrapper) (param i32) (result i32)
+                (block 0 (param v0 i32)
+                    (let (v2 u32) (cast v0))
+                    (switchv2
+                        (0 . (block 2))
+                        (1 . (block 4))
+                        (2 . (block 3))
+                        (_ . (block 4))))
+
+                (block 1 (param v1 i32)
+                    (ret v1))
+
+                (block 2
+                    (let (v5 i32) (const.i32 0))
+                    (br (block 1 v5)))
+
+                (block 3
+                    (let (v4 i32) (const.i32 1))
+                    (ret v4))
+
+                (block 4
+                    (let (v3 i32) (const.i32 2))
+                    (ret v3))
+            )"#]],
+    )
+}
@@ -12,7 +12,8 @@ use crate::{
     test_utils::test_context,
 };
 
-fn check_unsupported(op: &Operator) {
+/// Translate `op` in an empty function, returning the error message if translation fails
+fn translate_error(op: &Operator, soft_float: bool) -> Option<String> {
     let context = test_context();
     let mod_name = "noname";
     let module_info = Module::default();
@@ -30,6 +31,7 @@ fn check_unsupported(op: &Operator) {
     let mut builder_ext = FunctionBuilderExt::new(&mut module_func_builder, &mut fb_ctx);
     let mut module_state =
         ModuleTranslationState::new(&module_info, &mod_types, vec![], &context.session.diagnostics);
+    module_state.soft_float = soft_float;
     let result = translate_operator(
         op,
         &mut builder_ext,
@@ -40,84 +42,52 @@ fn check_unsupported(op: &Operator) {
         &context.session.diagnostics,
         SourceSpan::default(),
     );
-    assert!(result.is_err(), "Expected unsupported op error for {:?}", op);
-    assert_eq!(result.unwrap_err().to_string(), format!("Wasm op {:?} is not supported", op));
+    result.err().map(|err| err.to_string())
+}
+
+fn check_unsupported(op: &Operator) {
+    assert_eq!(
+        translate_error(op, true),
+        Some(format!("Wasm op {:?} is not supported", op)),
+        "Expected unsupported op error for {:?}",
+        op
+    );
 }
 
 // Wasm Spec v1.0
 const UNSUPPORTED_WASM_V1_OPS: &[Operator] = &[
-    /****************************** Memory Operators *********************************** */
-    F64Load {
-        memarg: MemArg {
-            align: 0,
-            max_align: 0,
-            offset: 0,
-            memory: 0,
-        },
-    },
-    F64Store {
-        memarg: MemArg {
-            align: 0,
-            max_align: 0,
-            offset: 0,
-            memory: 0,
-        },
-    },
     /****************************** Nullary Operators ************************************/
 
     // Cannot construct since Ieee32 fields are private
     // F32Const {
     //     value: Ieee32(0),
     // },
-    // F64Const {
-    //     value: Ieee32(0),
-    // },
 
     /****************************** Unary Operators ************************************/
     F32Sqrt,
     F64Sqrt,
     F32Ceil,
-    F64Ceil,
     F32Floor,
-    F64Floor,
     F32Trunc,
-    F64Trunc,
     F32Nearest,
-    F64Nearest,
     F32Abs,
-    F64Abs,
     F32Neg,
-    F64Neg,
-    F64ConvertI64U,
-    F64ConvertI32U,
-    F64ConvertI64S,
-    F64ConvertI32S,
     F32ConvertI64S,
     F32ConvertI32S,
     F32ConvertI64U,
     F32ConvertI32U,
     F64PromoteF32,
     F32DemoteF64,
-    I64TruncF64S,
     I64TruncF32S,
-    I32TruncF64S,
     I32TruncF32S,
-    I64TruncF64U,
     I64TruncF32U,
-    I32TruncF64U,
     I32TruncF32U,
-    I64TruncSatF64S,
     I64TruncSatF32S,
-    I32TruncSatF64S,
     I32TruncSatF32S,
-    I64TruncSatF64U,
     I64TruncSatF32U,
-    I32TruncSatF64U,
     I32TruncSatF32U,
     F32ReinterpretI32,
-    F64ReinterpretI64,
     I32ReinterpretF32,
-    I64ReinterpretF64,
     /****************************** Binary Operators *********************************** */
     F32Add,
     F32Sub,
@@ -126,13 +96,6 @@ const UNSUPPORTED_WASM_V1_OPS: &[Operator] = &[
     F32Min,
     F32Max,
     F32Copysign,
-    F64Copysign,
-    F64Add,
-    F64Sub,
-    F64Mul,
-    F64Div,
-    F64Min,
-    F64Max,
     /**************************** Comparison Operators ********************************* */
     F32Eq,
     F32Ne,
@@ -140,6 +103,60 @@ const UNSUPPORTED_WASM_V1_OPS: &[Operator] = &[
     F32Ge,
     F32Le,
     F32Lt,
+];
+
+#[test]
+fn error_for_unsupported_wasm_v1_ops() {
+    for op in UNSUPPORTED_WASM_V1_OPS.iter() {
+        check_unsupported(op);
+    }
+}
+
+// Wasm Spec v1.0 operators which are only supported with software floating point
+const SOFT_FLOAT_WASM_V1_OPS: &[Operator] = &[
+    F64Load {
+        memarg: MemArg {
+            align: 0,
+            max_align: 0,
+            offset: 0,
+            memory: 0,
+        },
+    },
+    F64Store {
+        memarg: MemArg {
+            align: 0,
+            max_align: 0,
+            offset: 0,
+            memory: 0,
+        },
+    },
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Nearest,
+    F64Abs,
+    F64Neg,
+    F64ConvertI64U,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI32S,
+    I64TruncF64S,
+    I32TruncF64S,
+    I64TruncF64U,
+    I32TruncF64U,
+    I64TruncSatF64S,
+    I32TruncSatF64S,
+    I64TruncSatF64U,
+    I32TruncSatF64U,
+    F64ReinterpretI64,
+    I64ReinterpretF64,
+    F64Copysign,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
     F64Eq,
     F64Ne,
     F64Gt,
@@ -149,8 +166,34 @@ const UNSUPPORTED_WASM_V1_OPS: &[Operator] = &[
 ];
 
 #[test]
-fn error_for_unsupported_wasm_v1_ops() {
-    for op in UNSUPPORTED_WASM_V1_OPS.iter() {
+fn error_for_f64_ops_without_soft_float() {
+    for op in SOFT_FLOAT_WASM_V1_OPS.iter() {
+        assert_eq!(
+            translate_error(op, false),
+            Some(format!(
+                "Wasm op {:?} is not supported: f64 operations require software floating point, \
+                 which can be enabled with `-C soft-float`",
+                op
+            ))
+        );
+    }
+}
+
+// Fixed-width SIMD operators outside of the supported integer subset
+const UNSUPPORTED_SIMD_OPS: &[Operator] = &[
+    I8x16Swizzle,
+    I8x16Popcnt,
+    I8x16Eq,
+    I16x8Shl,
+    I16x8Mul,
+    F32x4Add,
+    F32x4Splat,
+    F64x2Mul,
+];
+
+#[test]
+fn error_for_unsupported_simd_ops() {
+    for op in UNSUPPORTED_SIMD_OPS.iter() {
         check_unsupported(op);
     }
 }
@@ -65,7 +65,9 @@ fn inline(
 #[cfg(test)]
 mod tests {
     use miden_core::crypto::hash::RpoDigest;
-    use midenc_hir::{FunctionType, Ident, InterfaceFunctionIdent, InterfaceIdent, Symbol};
+    use midenc_hir::{
+        AbiParam, CallConv, FunctionType, Ident, InterfaceFunctionIdent, InterfaceIdent, Symbol,
+    };
     use midenc_hir_type::Type;
 
     use super::*;
@@ -132,6 +134,13 @@ mod tests {
         assert_eq!(export.function.function.as_symbol(), export_name_sym);
         let expected_export_func_ty = FunctionType::new_wasm([Type::U32, Type::U32], [Type::U32]);
         assert_eq!(export.function_ty, expected_export_func_ty);
+
+        // Callers of the lifted function see the component-level types of the export
+        let module = &ir.modules()[&export.function.module];
+        let signature = module.export_signature(export.function.function).unwrap();
+        assert_eq!(signature.params, [AbiParam::new(Type::U32), AbiParam::new(Type::U32)]);
+        assert_eq!(signature.results, [AbiParam::new(Type::U32)]);
+        assert_eq!(signature.cc, CallConv::Wasm);
     }
 
     #[test]
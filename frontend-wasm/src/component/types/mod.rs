@@ -9,6 +9,7 @@ pub mod resources;
 use core::{hash::Hash, ops::Index};
 
 use anyhow::{bail, Result};
+use indexmap::IndexMap;
 use midenc_hir::cranelift_entity::{EntityRef, PrimaryMap};
 use rustc_hash::FxHashMap;
 use wasmparser::{collections::IndexSet, names::KebabString, types};
@@ -597,7 +598,7 @@ impl ComponentTypesBuilder {
             types::EntityType::Table(ty) => EntityType::Table(convert_table_type(ty)),
             types::EntityType::Memory(ty) => EntityType::Memory((*ty).into()),
             types::EntityType::Global(ty) => EntityType::Global(convert_global_type(ty)),
-            types::EntityType::Tag(_) => bail!("exceptions proposal not implemented"),
+            types::EntityType::Tag(_) => bail!("exception tags cannot be shared across components"),
         })
     }
 
@@ -946,22 +947,22 @@ pub struct TypeModule {
     /// two-level namespace of core WebAssembly, but unlike core wasm all import
     /// names are required to be unique to describe a module in the component
     /// model.
-    pub imports: FxHashMap<(String, String), EntityType>,
+    pub imports: IndexMap<(String, String), EntityType>,
 
     /// The values that this module exports.
     ///
     /// Note that the value of this map is the core wasm `EntityType` to
     /// represent that core wasm items are being exported.
-    pub exports: FxHashMap<String, EntityType>,
+    pub exports: IndexMap<String, EntityType>,
 }
 
 /// The type of a component in the component model.
 #[derive(Default)]
 pub struct TypeComponent {
     /// The named values that this component imports.
-    pub imports: FxHashMap<String, TypeDef>,
+    pub imports: IndexMap<String, TypeDef>,
     /// The named values that this component exports.
-    pub exports: FxHashMap<String, TypeDef>,
+    pub exports: IndexMap<String, TypeDef>,
 }
 
 /// The type of a component instance in the component model, or an instantiated
@@ -971,7 +972,7 @@ pub struct TypeComponent {
 #[derive(Default)]
 pub struct TypeComponentInstance {
     /// The list of exports that this component has along with their types.
-    pub exports: FxHashMap<String, TypeDef>,
+    pub exports: IndexMap<String, TypeDef>,
 }
 
 /// A component function type in the component model.
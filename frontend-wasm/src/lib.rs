@@ -47,12 +47,16 @@ pub fn translate(
 /// The set of core WebAssembly features which we need to or wish to support
 pub(crate) fn supported_features() -> WasmFeatures {
     WasmFeatures::BULK_MEMORY
+        | WasmFeatures::EXCEPTIONS
         | WasmFeatures::FLOATS
         | WasmFeatures::FUNCTION_REFERENCES
         | WasmFeatures::MULTI_VALUE
         | WasmFeatures::MUTABLE_GLOBAL
+        // Required by the exception-handling proposal for `exnref`
+        | WasmFeatures::REFERENCE_TYPES
         | WasmFeatures::SATURATING_FLOAT_TO_INT
         | WasmFeatures::SIGN_EXTENSION
+        | WasmFeatures::SIMD
         | WasmFeatures::TAIL_CALL
 }
 
@@ -2,22 +2,23 @@ use core::mem;
 
 use midenc_hir::{
     diagnostics::{DiagnosticsHandler, IntoDiagnostic, Severity, SourceSpan},
-    CallConv, ConstantData, Linkage, MidenAbiImport, ModuleBuilder, Symbol,
+    CallConv, ConstantData, Linkage, MidenAbiImport, ModuleBuilder, Symbol, Type,
 };
 use midenc_session::Session;
 use wasmparser::Validator;
 
 use super::{module_translation_state::ModuleTranslationState, MemoryIndex, Module};
 use crate::{
+    code_translator::{exceptions, soft_float},
     error::WasmResult,
     intrinsics::is_miden_intrinsics_module,
     miden_abi::miden_abi_function_type,
     module::{
         func_translator::FuncTranslator,
         module_env::{FunctionBodyData, ModuleEnvironment, ParsedModule},
-        types::{ir_func_sig, ir_func_type, ir_type, ModuleTypes},
+        types::{ir_func_sig, ir_func_type, ir_type, ModuleTypes, WasmType},
     },
-    WasmTranslationConfig,
+    unsupported_diag, WasmTranslationConfig,
 };
 
 /// Translate a valid Wasm core module binary into Miden IR component building
@@ -59,7 +60,9 @@ pub fn translate_module_as_component(
     for import_module_id in module_imports.iter_module_names() {
         if let Some(imports) = module_imports.imported(import_module_id) {
             for ext_func in imports {
-                if is_miden_intrinsics_module(ext_func.module.as_symbol()) {
+                if is_miden_intrinsics_module(ext_func.module.as_symbol())
+                    || ext_func.module.as_str() == soft_float::INTRINSICS_MODULE
+                {
                     // ignore intrinsics imports
                     continue;
                 }
@@ -90,6 +93,7 @@ pub fn build_ir_module(
     _config: &WasmTranslationConfig,
     session: &Session,
 ) -> WasmResult<midenc_hir::Module> {
+    module_state.soft_float = session.options.soft_float;
     let name = parsed_module.module.name();
     let memory_size = parsed_module
         .module
@@ -100,8 +104,8 @@ pub fn build_ir_module(
     if let Some(memory_size) = memory_size {
         module_builder.with_reserved_memory_pages(memory_size);
     }
-    build_globals(&parsed_module.module, &mut module_builder, &session.diagnostics)?;
-    build_data_segments(parsed_module, &mut module_builder, &session.diagnostics)?;
+    build_globals(&parsed_module.module, module_types, &mut module_builder, &session.diagnostics)?;
+    build_data_segments(parsed_module, &mut module_builder, module_state, &session.diagnostics)?;
     let addr2line = addr2line::Context::from_dwarf(gimli::Dwarf {
         debug_abbrev: parsed_module.debuginfo.dwarf.debug_abbrev,
         debug_addr: parsed_module.debuginfo.dwarf.debug_addr,
@@ -129,12 +133,28 @@ pub fn build_ir_module(
         let func_type = &parsed_module.module.functions[*func_index];
         let func_name = &parsed_module.module.func_name(*func_index);
         let wasm_func_type = module_types[func_type.signature].clone();
+        // f64 values are only representable via the soft-float intrinsics, so a signature that
+        // carries them cannot be called without `-C soft-float`
+        if !module_state.soft_float
+            && wasm_func_type
+                .params()
+                .iter()
+                .chain(wasm_func_type.returns())
+                .any(|ty| *ty == WasmType::F64)
+        {
+            unsupported_diag!(
+                &session.diagnostics,
+                "Function '{func_name}' is not supported: f64 parameters and results require \
+                 software floating point, which can be enabled with `-C soft-float`"
+            );
+        }
         let ir_func_type = ir_func_type(&wasm_func_type, &session.diagnostics)?;
         let sig = ir_func_sig(&ir_func_type, CallConv::SystemV, Linkage::External);
         let mut module_func_builder = module_builder.function(func_name.as_str(), sig.clone())?;
         let FunctionBodyData { validator, body } = body_data;
         let mut func_validator = validator.into_validator(Default::default());
         func_translator.translate_body(
+            *func_index,
             &body,
             &mut module_func_builder,
             module_state,
@@ -146,12 +166,17 @@ pub fn build_ir_module(
         )?;
         module_func_builder.build(&session.diagnostics)?;
     }
+    module_builder.with_error_codes(mem::take(&mut module_state.error_codes));
+    if let Some(metadata) = parsed_module.account_component.take() {
+        module_builder.with_account_component(metadata);
+    }
     let module = module_builder.build();
     Ok(*module)
 }
 
 fn build_globals(
     wasm_module: &Module,
+    module_types: &ModuleTypes,
     module_builder: &mut ModuleBuilder,
     diagnostics: &DiagnosticsHandler,
 ) -> WasmResult<()> {
@@ -182,12 +207,49 @@ fn build_globals(
                 .into_report());
         }
     }
+    if wasm_module.uses_exceptions() {
+        build_exception_globals(wasm_module, module_types, module_builder, diagnostics)?;
+    }
+    Ok(())
+}
+
+/// Declares the module-local globals used to propagate exceptions, see `code_translator::exceptions`
+fn build_exception_globals(
+    wasm_module: &Module,
+    module_types: &ModuleTypes,
+    module_builder: &mut ModuleBuilder,
+    diagnostics: &DiagnosticsHandler,
+) -> WasmResult<()> {
+    let mut globals = vec![(exceptions::EXCEPTION_TAG_GLOBAL.to_string(), Type::U32)];
+    for tag in wasm_module.tags.keys() {
+        let payload_types =
+            exceptions::tag_payload_types(tag, wasm_module, module_types, diagnostics)?;
+        for (index, ty) in payload_types.into_iter().enumerate() {
+            globals.push((exceptions::exception_payload_global(tag, index), ty));
+        }
+    }
+    for (name, ty) in globals {
+        let init = ConstantData::from(vec![0u8; ty.size_in_bytes()]);
+        if let Err(e) = module_builder.declare_global_variable(
+            &name,
+            ty,
+            Linkage::Internal,
+            Some(init),
+            SourceSpan::default(),
+        ) {
+            return Err(diagnostics
+                .diagnostic(Severity::Error)
+                .with_message(format!("Failed to declare global variable '{name}': {e:?}"))
+                .into_report());
+        }
+    }
     Ok(())
 }
 
 fn build_data_segments(
     translation: &ParsedModule,
     module_builder: &mut ModuleBuilder,
+    module_state: &mut ModuleTranslationState,
     diagnostics: &DiagnosticsHandler,
 ) -> WasmResult<()> {
     for (data_segment_idx, data_segment) in &translation.data_segments {
@@ -197,6 +259,9 @@ fn build_data_segments(
         let init = ConstantData::from(data_segment.data);
         let offset = data_segment.offset.as_i32(&translation.module, diagnostics)? as u32;
         let size = init.len() as u32;
+        if readonly {
+            module_state.declare_rodata(offset, data_segment.data);
+        }
         if let Err(e) = module_builder.declare_data_segment(offset, size, init, readonly) {
             let message = format!(
                 "Failed to declare data segment '{data_segment_name}' with size '{size}' at \
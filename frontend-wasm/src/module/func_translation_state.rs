@@ -41,8 +41,8 @@ pub enum ElseData {
     },
 }
 
-/// A control stack frame can be an `if`, a `block` or a `loop`, each one having the following
-/// fields:
+/// A control stack frame can be an `if`, a `block`, a `loop` or a `try_table`, each one having
+/// the following fields:
 ///
 /// - `destination`: reference to the `Block` that will hold the code after the control block;
 /// - `num_return_values`: number of values returned by the control block;
@@ -50,6 +50,10 @@ pub enum ElseData {
 ///
 /// The `loop` frame has a `header` field that references the `Block` that contains the beginning
 /// of the body of the loop.
+///
+/// The `try_table` frame behaves like a `block`, but additionally records its catch clauses, and
+/// the landing pad `Block` to which any exception raised in its body is routed. The landing pad
+/// is only allocated once something in the body can actually unwind.
 #[derive(Debug)]
 pub enum ControlStackFrame {
     If {
@@ -86,6 +90,15 @@ pub enum ControlStackFrame {
         num_return_values: usize,
         original_stack_size: usize,
     },
+    TryTable {
+        destination: Block,
+        catches: Vec<wasmparser::Catch>,
+        landing_pad: Option<Block>,
+        num_param_values: usize,
+        num_return_values: usize,
+        original_stack_size: usize,
+        exit_is_branched_to: bool,
+    },
 }
 
 /// Helper methods for the control stack objects.
@@ -100,6 +113,9 @@ impl ControlStackFrame {
             }
             | Self::Loop {
                 num_return_values, ..
+            }
+            | Self::TryTable {
+                num_return_values, ..
             } => num_return_values,
         }
     }
@@ -114,6 +130,9 @@ impl ControlStackFrame {
             }
             | Self::Loop {
                 num_param_values, ..
+            }
+            | Self::TryTable {
+                num_param_values, ..
             } => num_param_values,
         }
     }
@@ -122,13 +141,16 @@ impl ControlStackFrame {
         match *self {
             Self::If { destination, .. }
             | Self::Block { destination, .. }
-            | Self::Loop { destination, .. } => destination,
+            | Self::Loop { destination, .. }
+            | Self::TryTable { destination, .. } => destination,
         }
     }
 
     pub fn br_destination(&self) -> Block {
         match *self {
-            Self::If { destination, .. } | Self::Block { destination, .. } => destination,
+            Self::If { destination, .. }
+            | Self::Block { destination, .. }
+            | Self::TryTable { destination, .. } => destination,
             Self::Loop { header, .. } => header,
         }
     }
@@ -148,13 +170,17 @@ impl ControlStackFrame {
             | Self::Loop {
                 original_stack_size,
                 ..
+            }
+            | Self::TryTable {
+                original_stack_size,
+                ..
             } => original_stack_size,
         }
     }
 
     pub fn is_loop(&self) -> bool {
         match *self {
-            Self::If { .. } | Self::Block { .. } => false,
+            Self::If { .. } | Self::Block { .. } | Self::TryTable { .. } => false,
             Self::Loop { .. } => true,
         }
     }
@@ -168,6 +194,10 @@ impl ControlStackFrame {
             | Self::Block {
                 exit_is_branched_to,
                 ..
+            }
+            | Self::TryTable {
+                exit_is_branched_to,
+                ..
             } => exit_is_branched_to,
             Self::Loop { .. } => false,
         }
@@ -182,6 +212,10 @@ impl ControlStackFrame {
             | Self::Block {
                 ref mut exit_is_branched_to,
                 ..
+            }
+            | Self::TryTable {
+                ref mut exit_is_branched_to,
+                ..
             } => *exit_is_branched_to = true,
             Self::Loop { .. } => {}
         }
@@ -229,6 +263,9 @@ pub struct FuncTranslationState {
     /// Is the current translation state still reachable? This is false when translating operators
     /// like End, Return, or Unreachable.
     pub(crate) reachable: bool,
+    /// The block which returns from the function when an exception is not caught by any
+    /// `try_table` in this function. It is allocated lazily, the first time something unwinds.
+    pub(crate) unwind_block: Option<Block>,
 }
 
 impl FuncTranslationState {
@@ -238,6 +275,7 @@ impl FuncTranslationState {
             stack: Vec::new(),
             control_stack: Vec::new(),
             reachable: true,
+            unwind_block: None,
         }
     }
 
@@ -245,6 +283,7 @@ impl FuncTranslationState {
         debug_assert!(self.stack.is_empty());
         debug_assert!(self.control_stack.is_empty());
         self.reachable = true;
+        self.unwind_block = None;
     }
 
     /// Initialize the state for compiling a function with the given signature.
@@ -435,6 +474,40 @@ impl FuncTranslationState {
         });
     }
 
+    /// Push a try_table on the control stack.
+    pub(crate) fn push_try_table(
+        &mut self,
+        following_code: Block,
+        catches: Vec<wasmparser::Catch>,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
+        debug_assert!(num_param_types <= self.stack.len());
+        self.control_stack.push(ControlStackFrame::TryTable {
+            destination: following_code,
+            catches,
+            landing_pad: None,
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
+            num_return_values: num_result_types,
+            exit_is_branched_to: false,
+        });
+    }
+
+    /// Get the block to which an exception raised at the current point should be routed,
+    /// allocating it if this is the first time something unwinds to it.
+    ///
+    /// This is the landing pad of the innermost enclosing `try_table`, or if there is none, the
+    /// block which returns from the function with the exception still pending.
+    pub(crate) fn unwind_destination(&mut self, builder: &mut FunctionBuilderExt) -> Block {
+        let landing_pad = self.control_stack.iter_mut().rev().find_map(|frame| match frame {
+            ControlStackFrame::TryTable { landing_pad, .. } => Some(landing_pad),
+            _ => None,
+        });
+        let slot = landing_pad.unwrap_or(&mut self.unwind_block);
+        *slot.get_or_insert_with(|| builder.create_block())
+    }
+
     /// Push an if on the control stack.
     pub(crate) fn push_if(
         &mut self,
@@ -16,13 +16,13 @@ use wasmparser::{FuncValidator, FunctionBody, WasmModuleResources};
 
 use super::{module_env::ParsedModule, module_translation_state::ModuleTranslationState};
 use crate::{
-    code_translator::translate_operator,
+    code_translator::{exceptions, translate_operator},
     error::WasmResult,
     module::{
         func_translation_state::FuncTranslationState,
         function_builder_ext::{FunctionBuilderContext, FunctionBuilderExt},
         module_env::DwarfReader,
-        types::{convert_valtype, ir_type, ModuleTypes},
+        types::{convert_valtype, ir_type, FuncIndex, ModuleTypes},
     },
     ssa::Variable,
     translation_utils::emit_zero,
@@ -47,10 +47,11 @@ impl FuncTranslator {
         }
     }
 
-    /// Translate a binary WebAssembly function from a `FunctionBody`.
+    /// Translate a binary WebAssembly function, `func_index`, from a `FunctionBody`.
     #[allow(clippy::too_many_arguments)]
     pub fn translate_body(
         &mut self,
+        func_index: FuncIndex,
         body: &FunctionBody<'_>,
         mod_func_builder: &mut ModuleFunctionBuilder,
         module_state: &mut ModuleTranslationState,
@@ -84,6 +85,7 @@ impl FuncTranslator {
 
         let mut reader = body.get_operators_reader().into_diagnostic()?;
         parse_function_body(
+            func_index,
             &mut reader,
             &mut builder,
             &mut self.state,
@@ -169,6 +171,7 @@ fn declare_locals(
 /// arguments and locals are declared in the builder.
 #[allow(clippy::too_many_arguments)]
 fn parse_function_body(
+    func_index: FuncIndex,
     reader: &mut wasmparser::OperatorsReader<'_>,
     builder: &mut FunctionBuilderExt,
     state: &mut FuncTranslationState,
@@ -244,6 +247,12 @@ fn parse_function_body(
         builder.ins().ret(state.stack.first().cloned(), end_span);
     }
 
+    // If an exception raised in this function may not be caught by it, we need a block which
+    // returns to the caller with the exception still pending, or traps if there is no caller in
+    // this module which could catch it.
+    let is_exported = module.module.is_exported_function(func_index);
+    exceptions::emit_unwind_return(builder, state, is_exported, &session.diagnostics, end_span)?;
+
     // Discard any remaining values on the stack. Either we just returned them,
     // or the end of the function is unreachable.
     state.stack.clear();
@@ -152,6 +152,9 @@ pub struct Module {
     /// WebAssembly module memories.
     pub memories: PrimaryMap<MemoryIndex, Memory>,
 
+    /// WebAssembly exception tags defined in the module.
+    pub tags: PrimaryMap<TagIndex, Tag>,
+
     /// Parsed names section.
     name_section: NameSection,
 
@@ -193,6 +196,23 @@ impl Module {
         }
     }
 
+    /// Returns true if this module defines any exception tags, in which case code generated for
+    /// it must follow the unwinding protocol described in `code_translator::exceptions`.
+    #[inline]
+    pub fn uses_exceptions(&self) -> bool {
+        !self.tags.is_empty()
+    }
+
+    /// Returns true if the function at `index` can be called from outside of this module, i.e. it
+    /// is exported, or is the start function of the module.
+    pub fn is_exported_function(&self, index: FuncIndex) -> bool {
+        self.start_func == Some(index)
+            || self
+                .exports
+                .values()
+                .any(|entity| matches!(entity, EntityIndex::Function(i) if *i == index))
+    }
+
     /// Test whether the given function index is for an imported function.
     #[inline]
     pub fn is_imported_function(&self, index: FuncIndex) -> bool {
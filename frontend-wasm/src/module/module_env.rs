@@ -30,6 +30,10 @@ use crate::{
     unsupported_diag, WasmTranslationConfig,
 };
 
+/// The name of the custom section in which the account-component metadata of a module is stored,
+/// as UTF-8 encoded TOML.
+pub const ACCOUNT_COMPONENT_SECTION: &str = "miden:account-component";
+
 /// Object containing the standalone environment information.
 pub struct ModuleEnvironment<'a, 'data> {
     /// The current module being translated
@@ -73,6 +77,10 @@ pub struct ParsedModule<'data> {
     /// List of data segments found in this module
     pub data_segments: PrimaryMap<DataSegmentIndex, DataSegment<'data>>,
 
+    /// The account-component metadata of this module, as TOML, if it has a
+    /// [ACCOUNT_COMPONENT_SECTION] custom section.
+    pub account_component: Option<String>,
+
     /// When we're parsing the code section this will be incremented so we know
     /// which function is currently being defined.
     code_index: u32,
@@ -172,18 +180,13 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
             }
             Payload::End(offset) => self.payload_end(offset)?,
             Payload::TypeSection(types) => self.type_section(types)?,
-            Payload::ImportSection(imports) => self.import_section(imports)?,
+            Payload::ImportSection(imports) => self.import_section(imports, diagnostics)?,
             Payload::FunctionSection(functions) => self.function_section(functions)?,
             Payload::TableSection(tables) => self.table_section(tables, diagnostics)?,
             Payload::MemorySection(memories) => self.memory_section(memories)?,
-            Payload::TagSection(tags) => {
-                self.validator.tag_section(&tags).into_diagnostic()?;
-                // This feature isn't enabled at this time, so we should
-                // never get here.
-                unreachable!();
-            }
+            Payload::TagSection(tags) => self.tag_section(tags)?,
             Payload::GlobalSection(globals) => self.global_section(globals, diagnostics)?,
-            Payload::ExportSection(exports) => self.export_section(exports)?,
+            Payload::ExportSection(exports) => self.export_section(exports, diagnostics)?,
             Payload::StartSection { func, range } => self.start_section(func, range)?,
             Payload::ElementSection(elements) => self.element_section(elements, diagnostics)?,
             Payload::CodeSectionStart { count, range, .. } => {
@@ -210,6 +213,9 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
                     log::warn!("failed to parse name section {:?}", e);
                 }
             }
+            Payload::CustomSection(s) if s.name() == ACCOUNT_COMPONENT_SECTION => {
+                self.account_component_section(&s, diagnostics)?
+            }
             Payload::CustomSection(s) => self.dwarf_section(&s),
             // It's expected that validation will probably reject other
             // payloads such as `UnknownSection` or those related to the
@@ -258,6 +264,7 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
     fn import_section(
         &mut self,
         imports: wasmparser::ImportSectionReader<'data>,
+        diagnostics: &DiagnosticsHandler,
     ) -> Result<(), Report> {
         self.validator.import_section(&imports).into_diagnostic()?;
         let cnt = usize::try_from(imports.count()).unwrap();
@@ -288,15 +295,34 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
                     self.result.module.num_imported_tables += 1;
                     EntityType::Table(convert_table_type(&ty))
                 }
-
-                // doesn't get past validation
-                TypeRef::Tag(_) => unreachable!(),
+                TypeRef::Tag(_) => {
+                    // Exceptions are lowered to module-local unwinding state, so a tag shared
+                    // with another module cannot be given a consistent identity.
+                    unsupported_diag!(
+                        diagnostics,
+                        "wasm error: importing exception tag '{}::{}' is not supported, only tags \
+                         defined in the module itself can be thrown or caught",
+                        import.module,
+                        import.name
+                    );
+                }
             };
             self.declare_import(import.module, import.name, ty);
         }
         Ok(())
     }
 
+    fn tag_section(&mut self, tags: wasmparser::TagSectionReader<'data>) -> Result<(), Report> {
+        self.validator.tag_section(&tags).into_diagnostic()?;
+        let cnt = usize::try_from(tags.count()).unwrap();
+        self.result.module.tags.reserve_exact(cnt);
+        for entry in tags {
+            let tag = entry.into_diagnostic()?;
+            self.result.module.tags.push(tag.into());
+        }
+        Ok(())
+    }
+
     fn function_section(
         &mut self,
         functions: wasmparser::FunctionSectionReader<'data>,
@@ -409,6 +435,7 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
     fn export_section(
         &mut self,
         exports: wasmparser::ExportSectionReader<'data>,
+        diagnostics: &DiagnosticsHandler,
     ) -> Result<(), Report> {
         self.validator.export_section(&exports).into_diagnostic()?;
         let cnt = usize::try_from(exports.count()).unwrap();
@@ -424,9 +451,14 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
                 ExternalKind::Table => EntityIndex::Table(TableIndex::from_u32(index)),
                 ExternalKind::Memory => EntityIndex::Memory(MemoryIndex::from_u32(index)),
                 ExternalKind::Global => EntityIndex::Global(GlobalIndex::from_u32(index)),
-
-                // this never gets past validation
-                ExternalKind::Tag => unreachable!(),
+                ExternalKind::Tag => {
+                    // Tags never escape the module they are defined in, see `import_section`
+                    diagnostics.warn(format!(
+                        "ignoring export of exception tag '{name}': exception tags cannot be \
+                         shared with other modules"
+                    ));
+                    continue;
+                }
             };
             self.result.module.exports.insert(String::from(name), entity);
         }
@@ -716,6 +748,25 @@ impl<'a, 'data> ModuleEnvironment<'a, 'data> {
         Ok(())
     }
 
+    fn account_component_section(
+        &mut self,
+        section: &CustomSectionReader<'data>,
+        diagnostics: &DiagnosticsHandler,
+    ) -> WasmResult<()> {
+        let Ok(metadata) = core::str::from_utf8(section.data()) else {
+            return Err(diagnostics
+                .diagnostic(Severity::Error)
+                .with_message(format!(
+                    "invalid '{ACCOUNT_COMPONENT_SECTION}' section: expected UTF-8 encoded TOML"
+                ))
+                .into_report());
+        };
+        // Sections with the same name are concatenated by the linker, so multiple declarations
+        // of the metadata end up as a single section, which is rejected when it is parsed.
+        self.result.account_component.get_or_insert_with(String::new).push_str(metadata);
+        Ok(())
+    }
+
     fn dwarf_section(&mut self, section: &CustomSectionReader<'data>) {
         let name = section.name();
         if !name.starts_with(".debug_") {
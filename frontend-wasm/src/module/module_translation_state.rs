@@ -1,7 +1,11 @@
+use std::collections::BTreeMap;
+
 use miden_core::crypto::hash::RpoDigest;
 use midenc_hir::{
+    canonical_abi_flat_count, canonical_abi_lowered_type, canonical_abi_record_type,
     diagnostics::{DiagnosticsHandler, Severity},
-    AbiParam, CallConv, DataFlowGraph, FunctionIdent, Ident, Linkage, Signature,
+    AbiParam, CallConv, ComponentImport, DataFlowGraph, ErrorCodeTable, FunctionIdent,
+    FunctionType, Ident, Linkage, Signature, Type, MAX_FLAT_PARAMS, MAX_FLAT_RESULTS,
 };
 use rustc_hash::FxHashMap;
 
@@ -21,6 +25,12 @@ pub struct ModuleTranslationState {
     digests: FxHashMap<FunctionIdent, RpoDigest>,
     /// Number of imported or aliased functions in the module.
     pub num_imported_funcs: usize,
+    /// Whether f64 operators are lowered to calls to the software floating-point intrinsics
+    pub soft_float: bool,
+    /// The contents of the read-only data segments of the module, keyed by offset
+    rodata: BTreeMap<u32, Box<[u8]>>,
+    /// The error codes assigned to the panic messages recognized during translation
+    pub error_codes: ErrorCodeTable,
     // stable_imported_miden_abi_functions: FxHashMap<FunctionIdent, String>,
 }
 
@@ -32,6 +42,7 @@ impl ModuleTranslationState {
         diagnostics: &DiagnosticsHandler,
     ) -> Self {
         let mut function_import_subst = FxHashMap::default();
+        let mut component_imports = FxHashMap::default();
         if module.imports.len() == module_args.len() {
             for (import, arg) in module.imports.iter().zip(module_args) {
                 match (import.index, arg) {
@@ -40,8 +51,14 @@ impl ModuleTranslationState {
                         // another module
                         function_import_subst.insert(func_idx, func_id);
                     }
-                    (EntityIndex::Function(_), ModuleArgument::ComponentImport(_)) => {
-                        // Do nothing, the local function id will be used
+                    (EntityIndex::Function(func_idx), ModuleArgument::ComponentImport(import)) => {
+                        // The local function id will be used, but the function belongs to
+                        // another component, so it must be called using the canonical ABI
+                        let function_ty = match import {
+                            ComponentImport::CanonAbiImport(import) => Some(import.function_ty),
+                            ComponentImport::MidenAbiImport(_) => None,
+                        };
+                        component_imports.insert(func_idx, function_ty);
                     }
                     (EntityIndex::Function(_), module_arg) => {
                         panic!(
@@ -58,7 +75,16 @@ impl ModuleTranslationState {
         for (index, func_type) in &module.functions {
             let wasm_func_type = mod_types[func_type.signature].clone();
             let ir_func_type = ir_func_type(&wasm_func_type, diagnostics).unwrap();
-            let sig = sig_from_func_type(&ir_func_type, CallConv::SystemV, Linkage::External);
+            let sig = match component_imports.get(&index) {
+                Some(function_ty) => {
+                    let sig = sig_from_func_type(&ir_func_type, CallConv::Wasm, Linkage::External);
+                    match function_ty {
+                        Some(function_ty) => canonical_abi_import_signature(sig, function_ty),
+                        None => sig,
+                    }
+                }
+                None => sig_from_func_type(&ir_func_type, CallConv::SystemV, Linkage::External),
+            };
             if let Some(subst) = function_import_subst.get(&index) {
                 functions.insert(index, (*subst, sig));
             } else if module.is_imported_function(index) {
@@ -92,9 +118,28 @@ impl ModuleTranslationState {
             functions,
             digests,
             num_imported_funcs: module.num_imported_funcs,
+            soft_float: false,
+            rodata: Default::default(),
+            error_codes: Default::default(),
         }
     }
 
+    /// Record the contents of a read-only data segment placed at `offset`
+    pub fn declare_rodata(&mut self, offset: u32, data: &[u8]) {
+        self.rodata.insert(offset, data.into());
+    }
+
+    /// Read `len` bytes of read-only data starting at `addr`
+    ///
+    /// Returns `None` if the requested bytes are not entirely contained in a single read-only data
+    /// segment, as the contents of writable memory cannot be known at compile-time.
+    pub fn read_rodata(&self, addr: u32, len: u32) -> Option<&[u8]> {
+        let (offset, data) = self.rodata.range(..=addr).next_back()?;
+        let start = (addr - offset) as usize;
+        let end = start.checked_add(len as usize)?;
+        data.get(start..end)
+    }
+
     /// Returns an IR function signature converted from Wasm function signature
     /// for the given function index.
     pub fn signature(&self, index: FuncIndex) -> &Signature {
@@ -147,3 +192,53 @@ impl ModuleTranslationState {
         Ok(func_id)
     }
 }
+
+/// Refine the signature of a function imported from another component, as derived from its core
+/// Wasm type, with the component-level type of the function, so that the values passed through the
+/// linear memory of the caller are typed as pointers.
+///
+/// When the flattened arguments of the function do not fit in [MAX_FLAT_PARAMS] values, they are
+/// passed via a pointer to a record containing them, and when its flattened results do not fit in
+/// [MAX_FLAT_RESULTS] values, the caller passes a pointer to which the results are written as the
+/// last argument. Otherwise, the only pointers are those to the elements of lists.
+fn canonical_abi_import_signature(mut sig: Signature, function_ty: &FunctionType) -> Signature {
+    let num_params = function_ty.params.iter().map(canonical_abi_flat_count).sum::<usize>();
+    let num_results = function_ty.results.iter().map(canonical_abi_flat_count).sum::<usize>();
+    if num_params > MAX_FLAT_PARAMS {
+        let params_ty = canonical_abi_record_type(&function_ty.params);
+        sig.params[0] = AbiParam::new(Type::Ptr(Box::new(params_ty)));
+    } else {
+        let mut params = sig.params.iter_mut();
+        for ty in function_ty.params.iter() {
+            type_list_pointers(&canonical_abi_lowered_type(ty), &mut params);
+        }
+    }
+    if num_results > MAX_FLAT_RESULTS {
+        let results_ty = canonical_abi_record_type(&function_ty.results);
+        let retptr = sig.params.last_mut().expect("expected a return pointer parameter");
+        *retptr = AbiParam::sret(Type::Ptr(Box::new(results_ty)));
+    }
+    sig
+}
+
+/// Type the flattened parameters corresponding to a value of type `ty`, as represented in linear
+/// memory by the canonical ABI, such that the pointers to the elements of lists are pointer-typed
+fn type_list_pointers<'a, I>(ty: &Type, params: &mut I)
+where
+    I: Iterator<Item = &'a mut AbiParam>,
+{
+    match ty {
+        Type::Struct(struct_ty) => {
+            for field in struct_ty.fields() {
+                type_list_pointers(&field.ty, params);
+            }
+        }
+        Type::Ptr(_) => {
+            let param = params.next().expect("expected a parameter for the list pointer");
+            *param = AbiParam::new(ty.clone());
+        }
+        _ => {
+            params.next();
+        }
+    }
+}
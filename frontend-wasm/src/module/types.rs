@@ -70,6 +70,9 @@ pub struct TypeIndex(u32);
 /// Index type of a data segment inside the WebAssembly module.
 pub struct DataSegmentIndex(u32);
 
+/// Index type of an exception tag inside the WebAssembly module.
+pub struct TagIndex(u32);
+
 }
 
 /// WebAssembly value type -- equivalent of `wasmparser`'s Type.
@@ -147,6 +150,10 @@ pub enum WasmHeapType {
     ///
     /// Introduced in the references-types proposal.
     Extern,
+    /// The abstract, exception heap type.
+    ///
+    /// Introduced in the exception-handling proposal.
+    Exn,
 }
 
 impl fmt::Display for WasmHeapType {
@@ -154,6 +161,7 @@ impl fmt::Display for WasmHeapType {
         match self {
             Self::Func => write!(f, "func"),
             Self::Extern => write!(f, "extern"),
+            Self::Exn => write!(f, "exn"),
         }
     }
 }
@@ -584,7 +592,18 @@ pub fn ir_type(ty: WasmType, diagnostics: &DiagnosticsHandler) -> WasmResult<hir
         WasmType::I32 => hir::Type::I32,
         WasmType::I64 => hir::Type::I64,
         WasmType::F32 => hir::Type::Felt,
-        ty @ (WasmType::F64 | WasmType::V128 | WasmType::Ref(_)) => {
+        // An `exnref` is represented by the (non-zero) identifier of the exception tag it refers
+        // to, see `code_translator::exceptions` for details.
+        WasmType::Ref(WasmRefType {
+            heap_type: WasmHeapType::Exn,
+            ..
+        }) => hir::Type::U32,
+        // See `code_translator::simd` for how vector lanes are represented
+        WasmType::V128 => hir::Type::U128,
+        // An `f64` is represented by its bit pattern, and operations on it are only supported
+        // using software floating point, see `code_translator::soft_float`
+        WasmType::F64 => hir::Type::U64,
+        ty @ WasmType::Ref(_) => {
             unsupported_diag!(diagnostics, "wasm error: unsupported type '{}'", ty)
         }
     })
@@ -657,6 +676,7 @@ pub fn convert_heap_type(ty: wasmparser::HeapType) -> WasmHeapType {
         wasmparser::HeapType::Abstract { ty, shared: _ } => match ty {
             AbstractHeapType::Func => WasmHeapType::Func,
             AbstractHeapType::Extern => WasmHeapType::Extern,
+            AbstractHeapType::Exn => WasmHeapType::Exn,
             ty => unimplemented!("unsupported abstract heap type {ty:?}"),
         },
         wasmparser::HeapType::Concrete(_) => {
@@ -120,10 +120,10 @@ pub fn emit_zero(
         Type::U16 => builder.ins().u16(0, SourceSpan::default()),
         Type::U32 => builder.ins().u32(0, SourceSpan::default()),
         Type::U64 => builder.ins().u64(0, SourceSpan::default()),
+        Type::U128 => builder.ins().u128(0, SourceSpan::default()),
         Type::F64 => builder.ins().f64(0.0, SourceSpan::default()),
         Type::Felt => builder.ins().felt(Felt::ZERO, SourceSpan::default()),
         Type::I128
-        | Type::U128
         | Type::U256
         | Type::Ptr(_)
         | Type::NativePtr(..)
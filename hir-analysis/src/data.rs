@@ -34,10 +34,7 @@ impl Analysis for GlobalVariableAnalysis<Program> {
         _session: &Session,
     ) -> AnalysisResult<Self> {
         let mut layout = GlobalVariableLayout {
-            global_table_offset: core::cmp::max(
-                program.reserved_memory_bytes().next_multiple_of(32),
-                program.segments().next_available_offset(),
-            ),
+            global_table_offset: program.global_table_offset(),
             ..GlobalVariableLayout::default()
         };
 
@@ -6,6 +6,7 @@ mod def_use;
 pub mod dependency_graph;
 mod dominance;
 mod liveness;
+mod locals;
 mod loops;
 pub mod spill;
 mod treegraph;
@@ -18,8 +19,9 @@ pub use self::{
     dependency_graph::DependencyGraph,
     dominance::{DominanceFrontier, DominatorTree, DominatorTreePreorder},
     liveness::LivenessAnalysis,
+    locals::{LocalSlot, LocalSlotAnalysis},
     loops::{Loop, LoopAnalysis, LoopLevel},
     spill::{Reload, ReloadInfo, Spill, SpillAnalysis, SpillInfo},
     treegraph::{OrderedTreeGraph, TreeGraph},
-    validation::{ModuleValidationAnalysis, Rule},
+    validation::{CanonicalAbiCalls, ModuleValidationAnalysis, Rule},
 };
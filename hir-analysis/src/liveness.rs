@@ -1,6 +1,6 @@
 use std::{
     cmp,
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, BTreeSet, VecDeque},
 };
 
 use midenc_hir::{
@@ -67,6 +67,10 @@ pub struct LivenessAnalysis {
     live_out: FxHashMap<ProgramPoint, NextUseSet>,
     // Maximum pressures for each block
     per_block_info: FxHashMap<BlockId, BlockInfo>,
+    // Locals live at entry to a given block
+    live_locals_in: FxHashMap<BlockId, BTreeSet<LocalId>>,
+    // Locals live at exit from a given block
+    live_locals_out: FxHashMap<BlockId, BTreeSet<LocalId>>,
 }
 impl Analysis for LivenessAnalysis {
     type Entity = Function;
@@ -110,7 +114,8 @@ impl LivenessAnalysis {
         loops: &LoopAnalysis,
     ) {
         self.clear();
-        compute_liveness(self, function, cfg, domtree, loops)
+        compute_liveness(self, function, cfg, domtree, loops);
+        compute_local_liveness(self, function, cfg, domtree);
     }
 
     /// Clear all computed liveness information, without releasing the memory we allocated
@@ -118,6 +123,8 @@ impl LivenessAnalysis {
         self.live_in.clear();
         self.live_out.clear();
         self.per_block_info.clear();
+        self.live_locals_in.clear();
+        self.live_locals_out.clear();
     }
 
     /// Returns true if `value` is live at the given program point.
@@ -168,6 +175,18 @@ impl LivenessAnalysis {
         self.live_out[&pp].live()
     }
 
+    /// Returns the set of locals which are live at entry to `block`.
+    ///
+    /// A local is live if it may be loaded before it is next stored to.
+    pub fn live_locals_at(&self, block: &BlockId) -> &BTreeSet<LocalId> {
+        &self.live_locals_in[block]
+    }
+
+    /// Returns the set of locals which are live at exit from `block`
+    pub fn live_locals_after(&self, block: &BlockId) -> &BTreeSet<LocalId> {
+        &self.live_locals_out[block]
+    }
+
     /// Returns the maximum register pressure in the given block
     pub fn max_register_pressure(&self, block: &BlockId) -> usize {
         self.per_block_info[block].max_register_pressure as usize
@@ -873,6 +892,78 @@ fn compute_liveness(
     }
 }
 
+/// Returns the local accessed by `inst`, and whether the access is a store, if it is a local
+/// variable access.
+pub(crate) fn local_access(function: &Function, inst: InstId) -> Option<(LocalId, bool)> {
+    match function.dfg.inst(inst) {
+        Instruction::LocalVar(LocalVarOp { op, local, .. }) => {
+            Some((*local, matches!(op, Opcode::Store)))
+        }
+        _ => None,
+    }
+}
+
+/// This function computes the sets of locals live at entry to, and exit from, each block of
+/// `function`, until a fixpoint is reached.
+///
+/// Unlike SSA values, locals may be defined any number of times, so this is the classic liveness
+/// analysis: a `load` of a local is a use, and a `store` to it is a definition which kills it.
+fn compute_local_liveness(
+    liveness: &mut LivenessAnalysis,
+    function: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+) {
+    let postorder = domtree.cfg_postorder();
+    if function.dfg.locals().next().is_none() {
+        for block in postorder.iter().copied() {
+            liveness.live_locals_in.insert(block, BTreeSet::default());
+            liveness.live_locals_out.insert(block, BTreeSet::default());
+        }
+        return;
+    }
+
+    // Compute the upward-exposed uses, and definitions, of each block
+    let mut uses = FxHashMap::<BlockId, BTreeSet<LocalId>>::default();
+    let mut defs = FxHashMap::<BlockId, BTreeSet<LocalId>>::default();
+    for block in postorder.iter().copied() {
+        let block_uses = uses.entry(block).or_default();
+        let block_defs = defs.entry(block).or_default();
+        for inst in function.dfg.block_insts(block) {
+            match local_access(function, inst) {
+                Some((local, true)) => {
+                    block_defs.insert(local);
+                }
+                Some((local, false)) if !block_defs.contains(&local) => {
+                    block_uses.insert(local);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Visit blocks in postorder until the results stop changing
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in postorder.iter().copied() {
+            let mut out = BTreeSet::default();
+            for succ in cfg.succ_iter(block) {
+                if let Some(succ_in) = liveness.live_locals_in.get(&succ) {
+                    out.extend(succ_in.iter().copied());
+                }
+            }
+            let mut in_ = uses[&block].clone();
+            in_.extend(out.difference(&defs[&block]).copied());
+            liveness.live_locals_out.insert(block, out);
+            if liveness.live_locals_in.get(&block) != Some(&in_) {
+                liveness.live_locals_in.insert(block, in_);
+                changed = true;
+            }
+        }
+    }
+}
+
 impl hir::Decorator for &LivenessAnalysis {
     type Display<'a> = DisplayLiveness<'a> where Self: 'a;
 
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use midenc_hir::{
+    pass::{Analysis, AnalysisManager, AnalysisResult, PreservedAnalyses},
+    Function, LocalId, Type,
+};
+use midenc_session::Session;
+use smallvec::SmallVec;
+
+use super::{
+    liveness::local_access, ControlFlowGraph, DominatorTree, LivenessAnalysis, LoopAnalysis,
+};
+
+/// A slot of procedure-local memory, shared by one or more locals whose lifetimes do not overlap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalSlot {
+    /// The type to allocate for this slot, i.e. the largest type of any local assigned to it
+    pub ty: Type,
+    /// The locals assigned to this slot, in ascending order
+    pub locals: SmallVec<[LocalId; 2]>,
+}
+
+/// This analysis assigns the local variables of a function to slots of procedure-local memory,
+/// such that locals whose lifetimes do not overlap share the same slot.
+///
+/// The locals live at entry to, and exit from, each block are provided by [LivenessAnalysis], from
+/// which we derive the locals live at each point within a block by walking it backwards. Two
+/// locals interfere if one is stored to while the other is live, or if both are live
+/// at entry to the function. Locals are then greedily colored in order of their identifiers, with
+/// each local assigned to the first slot with which it does not interfere.
+///
+/// Each slot is allocated as a single procedure local, and so is word-aligned, and large enough to
+/// hold any of the locals assigned to it.
+#[derive(Debug, Default)]
+pub struct LocalSlotAnalysis {
+    slots: Vec<LocalSlot>,
+    assignments: BTreeMap<LocalId, usize>,
+}
+impl Analysis for LocalSlotAnalysis {
+    type Entity = Function;
+
+    fn analyze(
+        function: &Self::Entity,
+        analyses: &mut AnalysisManager,
+        session: &Session,
+    ) -> AnalysisResult<Self> {
+        let domtree = analyses.get_or_compute(function, session)?;
+        let liveness = analyses.get_or_compute(function, session)?;
+        Ok(LocalSlotAnalysis::compute(function, &domtree, &liveness))
+    }
+
+    fn is_invalidated(&self, preserved: &PreservedAnalyses) -> bool {
+        !preserved.is_preserved::<ControlFlowGraph>()
+            || !preserved.is_preserved::<DominatorTree>()
+            || !preserved.is_preserved::<LoopAnalysis>()
+    }
+}
+impl LocalSlotAnalysis {
+    /// Assigns the locals of `function` to slots, using the provided analyses
+    pub fn compute(
+        function: &Function,
+        domtree: &DominatorTree,
+        liveness: &LivenessAnalysis,
+    ) -> Self {
+        let interference = compute_interference(function, domtree, liveness);
+
+        let mut slots = Vec::<LocalSlot>::new();
+        let mut assignments = BTreeMap::default();
+        for local in function.dfg.locals() {
+            let available = slots.iter().position(|slot| {
+                slot.locals
+                    .iter()
+                    .all(|other| !interference.contains(&ordered(local.id, *other)))
+            });
+            let index = match available {
+                Some(index) => {
+                    let slot = &mut slots[index];
+                    if local.ty.size_in_bytes() > slot.ty.size_in_bytes() {
+                        slot.ty = local.ty.clone();
+                    }
+                    slot.locals.push(local.id);
+                    index
+                }
+                None => {
+                    slots.push(LocalSlot {
+                        ty: local.ty.clone(),
+                        locals: SmallVec::from_slice(&[local.id]),
+                    });
+                    slots.len() - 1
+                }
+            };
+            assignments.insert(local.id, index);
+        }
+
+        Self { slots, assignments }
+    }
+
+    /// Returns the slots allocated for the locals of the function, in order of their first local
+    pub fn slots(&self) -> &[LocalSlot] {
+        self.slots.as_slice()
+    }
+
+    /// Returns the index of the slot in [Self::slots] to which `local` is assigned
+    ///
+    /// NOTE: This function will panic if `local` is not a local of the analyzed function
+    pub fn slot(&self, local: LocalId) -> usize {
+        self.assignments[&local]
+    }
+}
+
+#[inline]
+fn ordered(a: LocalId, b: LocalId) -> (LocalId, LocalId) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Computes the set of pairs of locals which interfere with each other in `function`
+fn compute_interference(
+    function: &Function,
+    domtree: &DominatorTree,
+    liveness: &LivenessAnalysis,
+) -> BTreeSet<(LocalId, LocalId)> {
+    let mut interference = BTreeSet::default();
+    if function.dfg.locals().next().is_none() {
+        return interference;
+    }
+
+    let postorder = domtree.cfg_postorder();
+
+    // Locals which are live at entry are read before they are ever written, so we must presume
+    // their contents are all observed
+    let entry = liveness.live_locals_at(&function.dfg.entry_block());
+    for (i, a) in entry.iter().copied().enumerate() {
+        for b in entry.iter().copied().skip(i + 1) {
+            interference.insert(ordered(a, b));
+        }
+    }
+
+    // A store to a local clobbers its slot, so it interferes with every other local live after
+    // the store, whether or not the stored value is ever loaded
+    for block in postorder.iter().copied() {
+        let mut live = liveness.live_locals_after(&block).clone();
+        let insts = function.dfg.block_insts(block).collect::<SmallVec<[_; 16]>>();
+        for inst in insts.into_iter().rev() {
+            match local_access(function, inst) {
+                Some((local, true)) => {
+                    live.remove(&local);
+                    interference.extend(live.iter().map(|other| ordered(local, *other)));
+                }
+                Some((local, false)) => {
+                    live.insert(local);
+                }
+                None => (),
+            }
+        }
+    }
+
+    interference
+}
@@ -3,6 +3,7 @@ use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use cranelift_entity::{entity_impl, EntityRef};
 use midenc_hir::{
     adt::{SmallMap, SmallSet},
+    diagnostics::{DiagnosticsHandler, Report, Severity, Spanned},
     pass::{Analysis, AnalysisManager, AnalysisResult},
     Block, BranchInfo, Function, InsertionPoint, Inst, ProgramPoint, SourceSpan, Type, Value,
 };
@@ -357,7 +358,7 @@ impl Analysis for SpillAnalysis {
         let domtree = analyses.get_or_compute(function, session)?;
         let loops = analyses.get_or_compute(function, session)?;
         let liveness = analyses.get_or_compute(function, session)?;
-        SpillAnalysis::compute(function, &cfg, &domtree, &loops, &liveness)
+        SpillAnalysis::compute(function, &cfg, &domtree, &loops, &liveness, &session.diagnostics)
     }
 }
 
@@ -483,6 +484,7 @@ impl SpillAnalysis {
         domtree: &DominatorTree,
         loops: &LoopAnalysis,
         liveness: &LivenessAnalysis,
+        diagnostics: &DiagnosticsHandler,
     ) -> AnalysisResult<Self> {
         let mut analysis = Self::default();
 
@@ -503,7 +505,7 @@ impl SpillAnalysis {
             inst_q.clear();
 
             // Compute W^entry(B)
-            compute_w_entry(block_id, &mut analysis, function, cfg, loops, liveness);
+            compute_w_entry(block_id, &mut analysis, function, cfg, loops, liveness, diagnostics)?;
             let w_entry = analysis.w_entries[&block_id].clone();
 
             // Compute S^entry(B)
@@ -583,7 +585,7 @@ impl SpillAnalysis {
             let mut s = block_info.s_entry;
             inst_q.extend(function.dfg.block_insts(block_id));
             while let Some(current_inst) = inst_q.pop_front() {
-                min(current_inst, &mut w, &mut s, &mut analysis, function, liveness);
+                min(current_inst, &mut w, &mut s, &mut analysis, function, liveness, diagnostics)?;
             }
 
             analysis.w_exits.insert(block_id, w);
@@ -703,12 +705,40 @@ fn compute_w_entry(
     cfg: &ControlFlowGraph,
     loops: &LoopAnalysis,
     liveness: &LivenessAnalysis,
-) {
+    diagnostics: &DiagnosticsHandler,
+) -> Result<(), Report> {
+    // Block arguments are always in w_entry by definition, so they must fit on the operand stack
+    let params = function
+        .dfg
+        .block_params(block_id)
+        .iter()
+        .map(|v| function.dfg.value_type(*v).size_in_felts())
+        .sum::<usize>();
+    if params > K {
+        let span = match function.dfg.block_insts(block_id).next() {
+            Some(inst) if block_id != function.dfg.entry_block() => function.dfg.inst_span(inst),
+            _ => function.span(),
+        };
+        return Err(diagnostics
+            .diagnostic(Severity::Error)
+            .with_message("operand stack depth exceeded")
+            .with_primary_label(
+                span,
+                format!(
+                    "the parameters of {block_id} require {params} elements of the operand stack, \
+                     but at most {K} are addressable"
+                ),
+            )
+            .into_report());
+    }
+
     if let Some(loop_id) = loops.is_loop_header(block_id) {
         compute_w_entry_loop(block_id, loop_id, analysis, function, cfg, loops, liveness);
     } else {
         compute_w_entry_normal(block_id, analysis, function, cfg, liveness);
     }
+
+    Ok(())
 }
 
 fn compute_w_entry_normal(
@@ -735,11 +765,9 @@ fn compute_w_entry_normal(
     // signature as part of the function prologue. Thus, the S set is preloaded with those values
     // which were spilled in this manner.
     //
-    // NOTE: It should never be the case that the set of block arguments consumes more than K
-    assert!(
-        take.iter().map(|o| o.size as usize).sum::<usize>() <= K,
-        "unhandled spills implied by function/block parameter list"
-    );
+    // NOTE: It should never be the case that the set of block arguments consumes more than K, as
+    // that is rejected by `compute_w_entry`
+    debug_assert!(take.iter().map(|o| o.size as usize).sum::<usize>() <= K);
 
     // If this is the entry block, the operands in w_entry are guaranteed to be equal to the set of
     // function arguments, so we're done.
@@ -1066,6 +1094,10 @@ fn compute_control_flow_edge_spills_and_reloads(
 /// of which predecessor edge was used to reach the block. This is handled earlier during analysis
 /// by computing the necessary spills and reloads to be inserted along each control flow edge, as
 /// required.
+///
+/// An error is returned if the operands and results of an instruction cannot fit on the operand
+/// stack at the same time, no matter what is spilled, e.g. a call with more than 16 elements worth
+/// of arguments.
 fn min(
     current_inst: Inst,
     w: &mut SmallSet<Operand, 4>,
@@ -1073,7 +1105,8 @@ fn min(
     analysis: &mut SpillAnalysis,
     function: &Function,
     liveness: &LivenessAnalysis,
-) {
+    diagnostics: &DiagnosticsHandler,
+) -> Result<(), Report> {
     let current_pp = ProgramPoint::Inst(current_inst);
     let ip = InsertionPoint::before(current_pp);
     let place = Placement::At(ip);
@@ -1176,12 +1209,9 @@ fn min(
                 });
                 // Spill until we have made enough room
                 while must_spill > 0 {
-                    let candidate = candidates.pop().unwrap_or_else(|| {
-                        panic!(
-                            "unable to spill sufficient capacity to hold all operands on stack at \
-                             one time at {current_inst}"
-                        )
-                    });
+                    let Some(candidate) = candidates.pop() else {
+                        return Err(stack_overflow(current_inst, function, diagnostics));
+                    };
                     must_spill = must_spill.saturating_sub(candidate.size as usize);
                     to_spill.insert(candidate);
                 }
@@ -1222,12 +1252,9 @@ fn min(
                     a_dist.cmp(&b_dist).then(a.size.cmp(&b.size))
                 });
                 while must_spill > 0 {
-                    let candidate = candidates.pop().unwrap_or_else(|| {
-                        panic!(
-                            "unable to spill sufficient capacity to hold all operands on stack at \
-                             one time at {current_inst}"
-                        )
-                    });
+                    let Some(candidate) = candidates.pop() else {
+                        return Err(stack_overflow(current_inst, function, diagnostics));
+                    };
                     // If we're spilling an operand of I, we can multiple the amount of space
                     // freed by the spill by the number of uses of the spilled value in I
                     let num_uses =
@@ -1288,6 +1315,32 @@ fn min(
             }
         }
     }
+
+    Ok(())
+}
+
+/// Construct the error raised when the operands and results of `inst` require more than [K]
+/// elements of the operand stack, making it impossible to find a valid set of spills.
+fn stack_overflow(inst: Inst, function: &Function, diagnostics: &DiagnosticsHandler) -> Report {
+    let size_of = |v: &Value| function.dfg.value_type(*v).size_in_felts();
+    let operands = function.dfg.inst_args(inst).iter().map(size_of).sum::<usize>();
+    let results = function.dfg.inst_results(inst).iter().map(size_of).sum::<usize>();
+    diagnostics
+        .diagnostic(Severity::Error)
+        .with_message("operand stack depth exceeded")
+        .with_primary_label(
+            function.dfg.inst_span(inst),
+            format!(
+                "this instruction requires {operands} elements of the operand stack for its \
+                 operands, and {results} for its results, but at most {K} are addressable"
+            ),
+        )
+        .with_help(
+            "Calls to functions whose parameters are more than 16 elements in total can only be \
+             lowered when the callee uses a calling convention which permits passing arguments in \
+             memory.",
+        )
+        .into_report()
 }
 
 #[cfg(test)]
@@ -2,6 +2,7 @@ use midenc_hir::{
     diagnostics::{DiagnosticsHandler, Report, Severity, Spanned},
     *,
 };
+use rustc_hash::FxHashSet;
 
 use super::{BlockValidator, DefsDominateUses, NamingConventions, Rule, TypeCheck};
 use crate::{ControlFlowGraph, DominatorTree};
@@ -52,6 +53,155 @@ impl Rule<Function> for FunctionValidator {
     }
 }
 
+/// This validation rule ensures that the calls made by a function which use the canonical ABI
+/// are ones we are able to lower:
+///
+/// * A function exported using the canonical ABI may not be called from within its own module
+/// * The flattened arguments and results, where they are not passed through memory, must each fit
+///   in 16 elements on the operand stack
+/// * Values which refer to other memory, i.e. lists and strings, may not be passed
+///
+/// Functions using the [CallConv::Wasm] calling convention are invoked with `call`, and execute in
+/// a fresh memory context. A call from within the same module shares the memory of the caller,
+/// and so would be lowered to `exec`, bypassing the context switch the callee relies on. Values
+/// passed through memory, see [canonical_abi_params_area] and [canonical_abi_results_area], are
+/// copied between the memories of the caller and callee, but only as-is, so any data they refer
+/// to would need to be copied too, which is not yet implemented.
+pub struct CanonicalAbiCalls {
+    exports: FxHashSet<FunctionIdent>,
+}
+impl CanonicalAbiCalls {
+    /// Create this rule for the functions of `module`
+    pub fn new(module: &Module) -> Self {
+        let exports = module
+            .functions()
+            .filter(|function| {
+                function.signature.cc == CallConv::Wasm && function.signature.is_public()
+            })
+            .map(|function| function.id)
+            .collect();
+        Self { exports }
+    }
+}
+impl Rule<Function> for CanonicalAbiCalls {
+    fn validate(
+        &mut self,
+        function: &Function,
+        diagnostics: &DiagnosticsHandler,
+    ) -> Result<(), Report> {
+        for (_, block) in function.dfg.blocks() {
+            for node in block.insts.iter() {
+                let Instruction::Call(Call {
+                    op: Opcode::Call,
+                    callee,
+                    ..
+                }) = node.as_ref()
+                else {
+                    continue;
+                };
+                let span = node.span();
+
+                if self.exports.contains(callee) {
+                    return Err(diagnostics
+                        .diagnostic(Severity::Error)
+                        .with_message("invalid call")
+                        .with_primary_label(
+                            span,
+                            format!(
+                                "'{callee}' is exported using the 'wasm' calling convention, and \
+                                 so may not be called from within its own module"
+                            ),
+                        )
+                        .with_help(
+                            "Functions using the canonical ABI are invoked in their own memory \
+                             context, which is not possible for a call from the same module. Move \
+                             the body of the export into a separate function, and call that \
+                             instead",
+                        )
+                        .into_report());
+                }
+
+                let Some(import) = function.dfg.get_import(callee) else {
+                    continue;
+                };
+                let signature = &import.signature;
+                if signature.cc != CallConv::Wasm {
+                    continue;
+                }
+                let params_area = canonical_abi_params_area(signature);
+                let results_area = canonical_abi_results_area(signature);
+                let num_params = signature.params.len();
+                let is_area = |index: usize| {
+                    (index == 0 && params_area.is_some())
+                        || (index + 1 == num_params && results_area.is_some())
+                };
+                let by_reference = params_area.into_iter().chain(results_area).any(|area| {
+                    canonical_abi_has_references(area) || canonical_abi_layout(area).is_none()
+                }) || signature
+                    .params
+                    .iter()
+                    .enumerate()
+                    .any(|(index, param)| param.ty.is_pointer() && !is_area(index))
+                    || signature.results.iter().any(|result| result.ty.is_pointer());
+                if by_reference {
+                    return Err(diagnostics
+                        .diagnostic(Severity::Error)
+                        .with_message("invalid call")
+                        .with_primary_label(
+                            span,
+                            format!(
+                                "the signature of '{callee}' requires lifting or lowering values \
+                                 which refer to other memory, which is not yet supported"
+                            ),
+                        )
+                        .with_help(
+                            "Values passed to or returned from a function in another component \
+                             are copied between the memories of the caller and callee, which is \
+                             not yet possible for lists and strings",
+                        )
+                        .into_report());
+                }
+
+                // Arguments and results passed through memory are represented on the operand
+                // stack by a commitment to the data, and its offset in the first word
+                let num_args = match params_area {
+                    Some(_) => CANONICAL_ABI_AREA_SIZE_IN_FELTS,
+                    None => signature
+                        .params
+                        .iter()
+                        .filter(|param| param.purpose != ArgumentPurpose::StructReturn)
+                        .map(|param| param.ty.size_in_felts())
+                        .sum::<usize>(),
+                };
+                let num_results = match results_area {
+                    Some(_) => CANONICAL_ABI_AREA_SIZE_IN_FELTS,
+                    None => signature.results.iter().map(|r| r.ty.size_in_felts()).sum::<usize>(),
+                };
+                if num_args > 16 || num_results > 16 {
+                    return Err(diagnostics
+                        .diagnostic(Severity::Error)
+                        .with_message("invalid call")
+                        .with_primary_label(
+                            span,
+                            format!(
+                                "the flattened arguments or results of '{callee}' do not fit on \
+                                 the operand stack"
+                            ),
+                        )
+                        .with_help(
+                            "The flattened arguments and results of a function in another \
+                             component must each fit in 16 elements on the operand stack, values \
+                             which are 64 bits wide occupy two elements",
+                        )
+                        .into_report());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// This validation rule ensures that a [Signature] is coherent
 ///
 /// A signature is coherent if:
@@ -146,6 +296,8 @@ impl Rule<Function> for CoherentSignature {
         // 3
         // * sret parameters may not be used with kernel calling convention
         // * pointer-typed parameters/results may not be used with kernel calling convention
+        // * sret parameters may not be used with wasm calling convention
+        // * at most one result may be returned with wasm calling convention
         // * parameters larger than 8 bytes must be passed by reference with fast/C calling
         //   conventions
         // * results larger than 8 bytes require the use of an sret parameter with fast/C calling
@@ -256,6 +408,21 @@ impl Rule<Function> for CoherentSignature {
                     .into_report());
             }
 
+            if is_sret && matches!(cc, CallConv::Wasm) {
+                return Err(diagnostics
+                    .diagnostic(Severity::Error)
+                    .with_message("invalid function signature")
+                    .with_primary_label(
+                        span,
+                        "functions using the 'wasm' calling convention may not use sret parameters",
+                    )
+                    .with_help(
+                        "Functions using the canonical ABI are invoked in a different memory \
+                         context, so they may not return values by reference",
+                    )
+                    .into_report());
+            }
+
             if !is_kernel_function {
                 if is_sret {
                     if sret_count > 1 || !is_first {
@@ -342,6 +509,21 @@ impl Rule<Function> for CoherentSignature {
                 .into_report());
         }
 
+        if matches!(cc, CallConv::Wasm) && function.signature.results.len() > 1 {
+            return Err(diagnostics
+                .diagnostic(Severity::Error)
+                .with_message("invalid function signature")
+                .with_primary_label(
+                    span,
+                    "functions using the 'wasm' calling convention may have at most one result",
+                )
+                .with_help(
+                    "The canonical ABI returns at most one flattened value on the operand stack, \
+                     any other results must be returned via the memory of the callee",
+                )
+                .into_report());
+        }
+
         for (i, result) in function.signature.results.iter().enumerate() {
             if result.purpose == ArgumentPurpose::StructReturn {
                 return Err(diagnostics
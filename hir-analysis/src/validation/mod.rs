@@ -10,6 +10,7 @@ use midenc_hir::{
 };
 use midenc_session::Session;
 
+pub use self::function::CanonicalAbiCalls;
 use self::{
     block::{BlockValidator, DefsDominateUses},
     function::FunctionValidator,
@@ -133,7 +134,8 @@ impl ModuleValidationAnalysis {
         }
 
         // Apply function-scoped rules
-        let mut rules = FunctionValidator::new(module.is_kernel());
+        let mut rules =
+            FunctionValidator::new(module.is_kernel()).chain(CanonicalAbiCalls::new(module));
         for function in module.functions() {
             rules.validate(function, &session.diagnostics)?;
         }
@@ -166,4 +168,33 @@ mod tests {
         let analysis = ModuleValidationAnalysis::validate(&module, &context.session);
         analysis.expect("module was expected to be valid")
     }
+
+    /// A call to a function in another component, whose flattened results do not fit on the
+    /// operand stack, and which are not returned through memory, is rejected
+    #[test]
+    fn canonical_abi_call_results_overflow_test() {
+        let context = TestContext::default();
+        let span = context.current_span();
+
+        // Each 64-bit result occupies two elements of the operand stack
+        let mut wide_sig = Signature::new([], (0..9).map(|_| AbiParam::new(Type::U64)));
+        wide_sig.cc = CallConv::Wasm;
+
+        let mut builder = ModuleBuilder::new("test");
+        builder.with_span(span);
+        let mut fb = builder
+            .function("main", Signature::new([], [AbiParam::new(Type::U64)]))
+            .expect("unexpected symbol conflict");
+        let wide = fb.import_function("other", "wide", wide_sig).unwrap();
+        let call = fb.ins().call(wide, &[], span);
+        let result = fb.first_result(call);
+        fb.ins().ret(Some(result), span);
+        fb.build(&context.session.diagnostics)
+            .expect("unexpected error building function");
+        let module = builder.build();
+
+        let err = ModuleValidationAnalysis::validate(&module, &context.session)
+            .expect_err("module was expected to be invalid");
+        assert_eq!(err.to_string(), "invalid call");
+    }
 }
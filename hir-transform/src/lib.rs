@@ -1,12 +1,16 @@
 pub(crate) mod adt;
 mod inline_blocks;
+mod lower_switch;
 mod spill;
 mod split_critical_edges;
 mod treeify;
+mod wide_signatures;
 
 pub use self::{
     inline_blocks::InlineBlocks,
+    lower_switch::LowerSwitch,
     spill::{ApplySpills, InsertSpills, RewriteSpills},
     split_critical_edges::SplitCriticalEdges,
     treeify::Treeify,
+    wide_signatures::LowerWideSignatures,
 };
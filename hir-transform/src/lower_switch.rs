@@ -0,0 +1,210 @@
+use midenc_hir::{
+    self as hir,
+    pass::{AnalysisManager, RewritePass, RewriteResult},
+    Block as BlockId, Value as ValueId, *,
+};
+use midenc_session::{diagnostics::IntoDiagnostic, Session};
+use smallvec::SmallVec;
+
+/// This pass rewrites `switch` instructions into a balanced binary decision tree of `cond_br`
+/// instructions, as Miden Assembly has no equivalent of a multi-way branch.
+///
+/// Before building the tree, the arms of each `switch` are simplified:
+///
+/// * Arms which transfer control to the same destination, with the same arguments, as the
+///   default successor are removed, as they are indistinguishable from the default.
+/// * Arms for consecutive values which share a successor are folded into a single case covering
+///   a contiguous range of values, which is tested with at most one range check, rather than one
+///   equality test per value.
+///
+/// The remaining cases are then split around their median at each level of the tree, so a
+/// `switch` with `N` cases is lowered to `O(log N)` comparisons along any path through the tree,
+/// rather than the `O(N)` comparisons required by a linear chain of equality tests. The bounds
+/// established by the comparisons on the path to a leaf are used to omit redundant checks at the
+/// leaf, e.g. a case which extends up to the upper bound of its subtree only requires a check of
+/// its lower bound.
+///
+/// Every leaf of the tree which does not fully cover its subtree branches to the default successor,
+/// so the default successor of a lowered `switch` usually has multiple predecessors. The resulting
+/// critical edges are expected to be split by [crate::SplitCriticalEdges], which must run after
+/// this pass.
+#[derive(Default, PassInfo, ModuleRewritePassAdapter)]
+pub struct LowerSwitch;
+impl RewritePass for LowerSwitch {
+    type Entity = hir::Function;
+
+    fn apply(
+        &mut self,
+        function: &mut Self::Entity,
+        analyses: &mut AnalysisManager,
+        session: &Session,
+    ) -> RewriteResult {
+        let switches = function
+            .dfg
+            .blocks()
+            .filter_map(|(block, _)| {
+                let terminator = function.dfg.last_inst(block)?;
+                match function.dfg.inst(terminator) {
+                    Instruction::Switch(_) => Some((block, terminator)),
+                    _ => None,
+                }
+            })
+            .collect::<SmallVec<[(BlockId, Inst); 2]>>();
+
+        if switches.is_empty() {
+            analyses.mark_all_preserved::<Function>(&function.id);
+            return Ok(());
+        }
+
+        for (block, terminator) in switches {
+            lower_switch(function, block, terminator);
+        }
+
+        session.print(&*function, Self::FLAG).into_diagnostic()?;
+        if session.should_print_cfg(Self::FLAG) {
+            use std::io::Write;
+            let cfg = function.cfg_printer();
+            let mut stdout = std::io::stdout().lock();
+            write!(&mut stdout, "{cfg}").into_diagnostic()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A control flow edge out of a `switch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    destination: BlockId,
+    args: SmallVec<[ValueId; 2]>,
+}
+impl Target {
+    fn new(successor: &hir::Successor, pool: &ValueListPool) -> Self {
+        Self {
+            destination: successor.destination,
+            args: SmallVec::from_slice(successor.args.as_slice(pool)),
+        }
+    }
+}
+
+/// A contiguous range of selector values, `lo..=hi`, which transfer control to `target`
+#[derive(Debug)]
+struct Case {
+    lo: u32,
+    hi: u32,
+    target: Target,
+}
+
+/// Replace the `switch` terminator of `block` with a decision tree
+fn lower_switch(function: &mut hir::Function, block: BlockId, terminator: Inst) {
+    let span = function.dfg.inst_span(terminator);
+    let (selector, mut arms, default) = match function.dfg.inst(terminator) {
+        Instruction::Switch(hir::Switch {
+            arg, arms, default, ..
+        }) => {
+            let pool = &function.dfg.value_lists;
+            let arms = arms
+                .iter()
+                .map(|arm| (arm.value, Target::new(&arm.successor, pool)))
+                .collect::<Vec<_>>();
+            (*arg, arms, Target::new(default, pool))
+        }
+        _ => unreachable!(),
+    };
+    function.dfg.block_mut(block).cursor_mut_at_inst(terminator).remove();
+
+    arms.sort_by_key(|(value, _)| *value);
+
+    let mut cases = Vec::<Case>::with_capacity(arms.len());
+    for (value, target) in arms.into_iter().filter(|(_, target)| target != &default) {
+        match cases.last_mut() {
+            Some(case) if case.hi.checked_add(1) == Some(value) && case.target == target => {
+                case.hi = value;
+            }
+            _ => cases.push(Case {
+                lo: value,
+                hi: value,
+                target,
+            }),
+        }
+    }
+
+    let mut builder = DecisionTreeBuilder {
+        function,
+        selector,
+        default,
+        span,
+    };
+    builder.build(block, &cases, 0, u32::MAX);
+}
+
+struct DecisionTreeBuilder<'a> {
+    function: &'a mut hir::Function,
+    selector: ValueId,
+    default: Target,
+    span: SourceSpan,
+}
+impl<'a> DecisionTreeBuilder<'a> {
+    /// Terminate `block` with the decision tree for `cases`, given that the selector is known to
+    /// be in the range `lo..=hi` when `block` is reached.
+    fn build(&mut self, block: BlockId, cases: &[Case], lo: u32, hi: u32) {
+        let selector = self.selector;
+        let span = self.span;
+        match cases {
+            [] => {
+                let default = self.default.clone();
+                self.ins(block).br(default.destination, &default.args, span);
+            }
+            [case] => self.build_leaf(block, case, lo, hi),
+            cases => {
+                let mid = cases.len() / 2;
+                let pivot = cases[mid].lo;
+                let is_lt = self.ins(block).lt_imm(selector, Immediate::U32(pivot), span);
+                let lt_block = self.function.dfg.create_block_after(block);
+                let gte_block = self.function.dfg.create_block_after(lt_block);
+                self.ins(block).cond_br(is_lt, lt_block, &[], gte_block, &[], span);
+                self.build(lt_block, &cases[..mid], lo, pivot - 1);
+                self.build(gte_block, &cases[mid..], pivot, hi);
+            }
+        }
+    }
+
+    /// Terminate `block` with a test of whether the selector is in the range covered by `case`
+    fn build_leaf(&mut self, block: BlockId, case: &Case, lo: u32, hi: u32) {
+        let selector = self.selector;
+        let span = self.span;
+        let is_match = match (case.lo > lo, case.hi < hi) {
+            // The case covers every value the selector can have here
+            (false, false) => {
+                self.ins(block).br(case.target.destination, &case.target.args, span);
+                return;
+            }
+            (true, true) if case.lo == case.hi => {
+                self.ins(block).eq_imm(selector, Immediate::U32(case.lo), span)
+            }
+            (true, true) => {
+                let offset =
+                    self.ins(block).sub_imm_wrapping(selector, Immediate::U32(case.lo), span);
+                self.ins(block).lte_imm(offset, Immediate::U32(case.hi - case.lo), span)
+            }
+            (true, false) => self.ins(block).gte_imm(selector, Immediate::U32(case.lo), span),
+            (false, true) => self.ins(block).lte_imm(selector, Immediate::U32(case.hi), span),
+        };
+        let default = self.default.clone();
+        self.ins(block).cond_br(
+            is_match,
+            case.target.destination,
+            &case.target.args,
+            default.destination,
+            &default.args,
+            span,
+        );
+    }
+
+    fn ins(&mut self, block: BlockId) -> DefaultInstBuilder<'_> {
+        DefaultInstBuilder::at(
+            &mut self.function.dfg,
+            InsertionPoint::after(ProgramPoint::Block(block)),
+        )
+    }
+}
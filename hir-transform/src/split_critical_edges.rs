@@ -83,17 +83,28 @@ impl RewritePass for SplitCriticalEdges {
                 // * Insert an unconditional branch to the successor with the block
                 // arguments of the original terminator
                 // * Recompute the control flow graph for affected blocks
-                let split = function.dfg.create_block_after(p);
+                //
+                // A `switch` may have multiple edges to the same successor, each with different
+                // block arguments, so each of those edges gets its own block.
                 let terminator = function.dfg.last_inst(p).unwrap();
                 let span = function.dfg.inst_span(terminator);
+                let num_edges = match function.dfg.analyze_branch(terminator) {
+                    BranchInfo::MultiDest(successors) => {
+                        successors.iter().filter(|succ| succ.destination == b).count()
+                    }
+                    _ => 1,
+                };
+                let splits = (0..num_edges)
+                    .map(|_| function.dfg.create_block_after(p))
+                    .collect::<SmallVec<[BlockId; 2]>>();
                 let ix = function.dfg.inst_mut(terminator);
-                let args: ValueList;
+                let mut edges = SmallVec::<[(BlockId, ValueList); 2]>::default();
                 match ix {
                     Instruction::Br(hir::Br {
                         ref mut successor, ..
                     }) => {
-                        args = successor.args.take();
-                        successor.destination = split;
+                        edges.push((splits[0], successor.args.take()));
+                        successor.destination = splits[0];
                     }
                     Instruction::CondBr(hir::CondBr {
                         ref mut then_dest,
@@ -101,33 +112,49 @@ impl RewritePass for SplitCriticalEdges {
                         ..
                     }) => {
                         if then_dest.destination == b {
-                            then_dest.destination = split;
-                            args = then_dest.args.take();
+                            then_dest.destination = splits[0];
+                            edges.push((splits[0], then_dest.args.take()));
                         } else {
-                            else_dest.destination = split;
-                            args = else_dest.args.take();
+                            else_dest.destination = splits[0];
+                            edges.push((splits[0], else_dest.args.take()));
+                        }
+                    }
+                    Instruction::Switch(hir::Switch {
+                        ref mut arms,
+                        ref mut default,
+                        ..
+                    }) => {
+                        let successors = arms
+                            .iter_mut()
+                            .map(|arm| &mut arm.successor)
+                            .chain(core::iter::once(default))
+                            .filter(|succ| succ.destination == b);
+                        for (successor, split) in successors.zip(splits.iter().copied()) {
+                            successor.destination = split;
+                            edges.push((split, successor.args.take()));
                         }
                     }
-                    Instruction::Switch(_) => unimplemented!(),
                     _ => unreachable!(),
                 }
-                function.dfg.insert_inst(
-                    InsertionPoint {
-                        at: ProgramPoint::Block(split),
-                        action: Insert::After,
-                    },
-                    Instruction::Br(hir::Br {
-                        op: hir::Opcode::Br,
-                        successor: hir::Successor {
-                            destination: b,
-                            args,
+                for (split, args) in edges {
+                    function.dfg.insert_inst(
+                        InsertionPoint {
+                            at: ProgramPoint::Block(split),
+                            action: Insert::After,
                         },
-                    }),
-                    Type::Unknown,
-                    span,
-                );
-
-                cfg.recompute_block(&function.dfg, split);
+                        Instruction::Br(hir::Br {
+                            op: hir::Opcode::Br,
+                            successor: hir::Successor {
+                                destination: b,
+                                args,
+                            },
+                        }),
+                        Type::Unknown,
+                        span,
+                    );
+
+                    cfg.recompute_block(&function.dfg, split);
+                }
             }
 
             cfg.recompute_block(&function.dfg, p);
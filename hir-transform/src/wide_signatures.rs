@@ -0,0 +1,448 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use midenc_hir::{
+    self as hir,
+    diagnostics::{Severity, Spanned},
+    pass::{AnalysisManager, RewritePass, RewriteResult},
+    *,
+};
+use midenc_session::Session;
+
+/// The maximum number of operand stack elements which can be used to pass arguments to a function
+const MAX_STACK_PARAMS: usize = 16;
+
+/// The size in bytes of a Miden word
+const WORD_SIZE: usize = 16;
+
+/// This pass lowers functions whose parameters require more than 16 elements of the operand stack,
+/// the maximum addressable depth, so that the excess parameters are passed through memory.
+///
+/// The leading parameters which fit on the operand stack are passed as usual. The remaining
+/// parameters are written by the caller to a static buffer associated with the callee, just prior
+/// to the call, and the callee loads each of them from that buffer where it is used. Since Miden
+/// does not permit recursion, the buffer cannot be overwritten while the callee is still using it.
+///
+/// The buffer is a global variable with `odr` linkage, declared in the module containing the
+/// callee, whose name is derived from the name of the callee.
+///
+/// Only functions with `internal` linkage are lowered in this way, as the signature of an exported
+/// function is part of the interface of its module, which callers outside of it rely on. Exported
+/// functions whose parameters do not fit on the operand stack are rejected with a diagnostic, as
+/// there is no ABI by which callers in other modules could pass the excess parameters. This is a
+/// documented limitation, see `docs/appendix/known-limitations.md`.
+///
+/// Likewise, only functions using the `fast` or `C` calling conventions are lowered, as the other
+/// conventions are either fixed by external requirements, or cross a context boundary, and thus
+/// cannot share memory with the caller. Such functions, as well as those with parameters of a type
+/// that cannot be stored in memory, are left unchanged, and will be rejected with a diagnostic
+/// during code generation.
+#[derive(Default, PassInfo, RewritePassRegistration)]
+pub struct LowerWideSignatures;
+impl RewritePass for LowerWideSignatures {
+    type Entity = hir::Module;
+
+    fn apply(
+        &mut self,
+        module: &mut Self::Entity,
+        analyses: &mut AnalysisManager,
+        session: &Session,
+    ) -> RewriteResult {
+        let mut buffers = BTreeMap::<Ident, usize>::default();
+        let mut changed = false;
+
+        // Only functions private to this module may have their signatures changed
+        let mut internal = BTreeSet::<FunctionIdent>::default();
+        for function in module.functions() {
+            let Some(wide) = WideSignature::new(function.id, &function.signature) else {
+                continue;
+            };
+            if function.signature.is_public() {
+                return Err(session
+                    .diagnostics
+                    .diagnostic(Severity::Error)
+                    .with_message("invalid function signature")
+                    .with_primary_label(
+                        function.span(),
+                        format!(
+                            "the parameters of this function require more than {MAX_STACK_PARAMS} \
+                             elements of the operand stack, but it is exported"
+                        ),
+                    )
+                    .with_help(format!(
+                        "Only the first {} parameters fit on the operand stack. Parameters beyond \
+                         that can only be passed in memory for functions with internal linkage, \
+                         so you must either pass some of them by reference, or stop exporting \
+                         this function",
+                        wide.num_stack_params
+                    ))
+                    .into_report());
+            }
+            internal.insert(function.id);
+        }
+
+        let mut cursor = module.cursor_mut();
+        while let Some(mut function) = cursor.remove() {
+            let mut dirty = false;
+            if internal.contains(&function.id) {
+                let wide = WideSignature::new(function.id, &function.signature).unwrap();
+                lower_definition(&mut function, &wide);
+                buffers.insert(wide.buffer, wide.size_in_bytes);
+                dirty = true;
+            }
+            for wide in lower_calls(&mut function, &internal) {
+                buffers.insert(wide.buffer, wide.size_in_bytes);
+                dirty = true;
+            }
+            if dirty {
+                analyses.invalidate::<hir::Function>(&function.id);
+                changed = true;
+            }
+            cursor.insert_before(function);
+        }
+
+        for (name, size) in buffers {
+            let ty = Type::Array(Box::new(Type::U128), size / WORD_SIZE);
+            module.declare_global_variable(name, ty, Linkage::Odr, None)?;
+        }
+
+        if !changed {
+            analyses.mark_all_preserved::<hir::Module>(&module.name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes how the parameters of a function with a wide signature are passed
+struct WideSignature {
+    /// The number of leading parameters which are passed on the operand stack
+    num_stack_params: usize,
+    /// The global variable through which the remaining parameters are passed
+    buffer: Ident,
+    /// The offset, in bytes, of each parameter passed through `buffer`.
+    ///
+    /// Each parameter is placed on a word boundary.
+    offsets: Vec<i32>,
+    /// The size of `buffer` in bytes
+    size_in_bytes: usize,
+}
+impl WideSignature {
+    /// Determine how to pass the parameters of the function `id`, with `signature`.
+    ///
+    /// Returns `None` if all of the parameters can be passed on the operand stack, or if the
+    /// function cannot be lowered.
+    fn new(id: FunctionIdent, signature: &Signature) -> Option<Self> {
+        if !matches!(signature.cc, CallConv::Fast | CallConv::SystemV) {
+            return None;
+        }
+
+        let mut used = 0;
+        let num_stack_params = signature
+            .params
+            .iter()
+            .take_while(|param| {
+                used += param.ty.size_in_felts();
+                used <= MAX_STACK_PARAMS
+            })
+            .count();
+        let overflow = &signature.params[num_stack_params..];
+        if overflow.is_empty() {
+            return None;
+        }
+        let is_supported = |param: &AbiParam| {
+            matches!(param.purpose, ArgumentPurpose::Default) && is_storable(&param.ty)
+        };
+        if !overflow.iter().all(is_supported) {
+            return None;
+        }
+
+        let mut offsets = Vec::with_capacity(overflow.len());
+        let mut size_in_bytes = 0;
+        for param in overflow {
+            offsets.push(size_in_bytes as i32);
+            size_in_bytes += param.ty.size_in_words() * WORD_SIZE;
+        }
+
+        Some(Self {
+            num_stack_params,
+            buffer: Ident::new(
+                Symbol::intern(format!("{}::{}.args", id.module.as_str(), id.function.as_str())),
+                id.function.span,
+            ),
+            offsets,
+            size_in_bytes,
+        })
+    }
+}
+
+/// Returns true if values of type `ty` can be passed through memory
+fn is_storable(ty: &Type) -> bool {
+    match ty {
+        Type::I1
+        | Type::I8
+        | Type::U8
+        | Type::I16
+        | Type::U16
+        | Type::I32
+        | Type::U32
+        | Type::I64
+        | Type::U64
+        | Type::I128
+        | Type::Felt
+        | Type::Ptr(_) => true,
+        Type::Array(elem, 4) => elem.as_ref() == &Type::Felt,
+        _ => false,
+    }
+}
+
+/// Rewrite the definition of `function` to load the parameters passed in memory from the buffer
+/// described by `wide`.
+///
+/// A load is inserted before each use of such a parameter, rather than once on entry, so that the
+/// values do not occupy the operand stack until they are needed.
+fn lower_definition(function: &mut hir::Function, wide: &WideSignature) {
+    let entry = function.dfg.entry_block();
+    let params = function.dfg.block_params(entry)[wide.num_stack_params..].to_vec();
+    let insts = function.dfg.blocks().flat_map(|(_, block)| block.insts()).collect::<Vec<_>>();
+
+    for (param, offset) in params.iter().copied().zip(wide.offsets.iter().copied()) {
+        let ty = function.dfg.value_type(param).clone();
+        for inst in insts.iter().copied() {
+            if !uses_value(&function.dfg, inst, param) {
+                continue;
+            }
+            let span = function.dfg.inst_span(inst);
+            let ip = InsertionPoint::before(ProgramPoint::Inst(inst));
+            let value = DefaultInstBuilder::at(&mut function.dfg, ip).load_symbol_relative(
+                wide.buffer.as_str(),
+                ty.clone(),
+                offset,
+                span,
+            );
+            function.dfg.replace_uses(inst, param, value);
+        }
+    }
+
+    for param in params.into_iter().rev() {
+        function.dfg.remove_block_param(param);
+    }
+    function.signature.params.truncate(wide.num_stack_params);
+}
+
+/// Rewrite all calls in `function` to the functions in `internal`, which have wide signatures, so
+/// that the arguments which are passed in memory are stored to the buffer of the callee before the
+/// call.
+///
+/// Returns the description of each such callee.
+fn lower_calls(
+    function: &mut hir::Function,
+    internal: &BTreeSet<FunctionIdent>,
+) -> Vec<WideSignature> {
+    let callees = function
+        .dfg
+        .imports()
+        .filter(|import| internal.contains(&import.id))
+        .filter_map(|import| {
+            WideSignature::new(import.id, &import.signature).map(|wide| (import.id, wide))
+        })
+        .collect::<BTreeMap<_, _>>();
+    if callees.is_empty() {
+        return vec![];
+    }
+
+    let calls = function
+        .dfg
+        .blocks()
+        .flat_map(|(_, block)| block.insts())
+        .filter(|inst| {
+            matches!(function.dfg.inst(*inst), Instruction::Call(Call { callee, .. }) if callees.contains_key(callee))
+        })
+        .collect::<Vec<_>>();
+
+    for call in calls {
+        let Instruction::Call(Call { callee, .. }) = function.dfg.inst(call) else {
+            unreachable!()
+        };
+        let wide = &callees[callee];
+        let span = function.dfg.inst_span(call);
+        let args = function.dfg.inst_args(call)[wide.num_stack_params..].to_vec();
+        for (arg, offset) in args.into_iter().zip(wide.offsets.iter().copied()) {
+            let ty = Type::Ptr(Box::new(function.dfg.value_type(arg).clone()));
+            let ip = InsertionPoint::before(ProgramPoint::Inst(call));
+            let ptr = DefaultInstBuilder::at(&mut function.dfg, ip).symbol_relative_addr(
+                wide.buffer.as_str(),
+                offset,
+                ty,
+                span,
+            );
+            DefaultInstBuilder::at(&mut function.dfg, ip).store(ptr, arg, span);
+        }
+        let dfg = &mut function.dfg;
+        let Instruction::Call(Call { ref mut args, .. }) = &mut *dfg.insts[call].data else {
+            unreachable!()
+        };
+        args.truncate(wide.num_stack_params, &mut dfg.value_lists);
+    }
+
+    for (id, wide) in callees.iter() {
+        let import = function.dfg.imports.get_mut(id).unwrap();
+        import.signature.params.truncate(wide.num_stack_params);
+    }
+
+    callees.into_values().collect()
+}
+
+/// Returns true if `value` is used as an argument by `inst`, including as a successor argument
+fn uses_value(dfg: &DataFlowGraph, inst: Inst, value: Value) -> bool {
+    if dfg.inst_args(inst).contains(&value) {
+        return true;
+    }
+    match dfg.analyze_branch(inst) {
+        BranchInfo::NotABranch => false,
+        BranchInfo::SingleDest(info) => info.args.contains(&value),
+        BranchInfo::MultiDest(infos) => infos.iter().any(|info| info.args.contains(&value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midenc_hir::{
+        pass::{AnalysisManager, RewritePass},
+        testing::TestContext,
+        AbiParam, CallConv, InstBuilder, Linkage, ModuleBuilder, Signature, SourceSpan, Type,
+    };
+    use pretty_assertions::assert_eq;
+
+    use crate::LowerWideSignatures;
+
+    /// Lower a module containing an internal function with 20 integer parameters, and a call to it.
+    ///
+    /// We expect the last 4 parameters to be removed from both the definition and the call, and
+    /// passed instead through a buffer declared in the module.
+    #[test]
+    fn lower_wide_signatures_test() {
+        let context = TestContext::default();
+        let span = SourceSpan::UNKNOWN;
+        let sig = Signature {
+            linkage: Linkage::Internal,
+            ..Signature::new((0..20).map(|_| AbiParam::new(Type::U32)), [AbiParam::new(Type::U32)])
+        };
+
+        let mut mb = ModuleBuilder::new("test");
+        let callee = {
+            let mut fb = mb.function("wide", sig.clone()).expect("unexpected symbol conflict");
+            let entry = fb.current_block();
+            let params = fb.block_params(entry).to_vec();
+            let result = fb.ins().add_checked(params[0], params[19], span);
+            fb.ins().ret(Some(result), span);
+            fb.build(&context.session.diagnostics)
+                .expect("unexpected error building function")
+        };
+        {
+            let mut fb = mb
+                .function("caller", Signature::new([], [AbiParam::new(Type::U32)]))
+                .expect("unexpected symbol conflict");
+            let callee = fb.import_function(callee.module, callee.function, sig).unwrap();
+            let args = (0..20).map(|n| fb.ins().u32(n, span)).collect::<Vec<_>>();
+            let call = fb.ins().call(callee, &args, span);
+            let result = fb.first_result(call);
+            fb.ins().ret(Some(result), span);
+            fb.build(&context.session.diagnostics)
+                .expect("unexpected error building function");
+        }
+        let mut module = mb.build();
+
+        let mut analyses = AnalysisManager::default();
+        let mut rewrite = LowerWideSignatures;
+        rewrite
+            .apply(&mut module, &mut analyses, &context.session)
+            .expect("rewrite failed");
+
+        let buffer = module.globals().find("test::wide.args".into()).expect("undefined buffer");
+        let buffer = module.globals().get(buffer);
+        assert_eq!(buffer.linkage, Linkage::Odr);
+        assert_eq!(buffer.ty, Type::Array(Box::new(Type::U128), 4));
+
+        let wide = module.function("wide".into()).unwrap();
+        assert_eq!(wide.signature.params.len(), 16);
+        assert_eq!(wide.signature.cc, CallConv::SystemV);
+        assert_eq!(wide.dfg.block_params(wide.dfg.entry_block()).len(), 16);
+
+        let caller = module.function("caller".into()).unwrap();
+        let import = caller.dfg.get_import(&callee).unwrap();
+        assert_eq!(import.signature.params.len(), 16);
+        let call = caller
+            .dfg
+            .block_insts(caller.dfg.entry_block())
+            .find(|inst| matches!(caller.dfg.inst(*inst), midenc_hir::Instruction::Call(_)))
+            .unwrap();
+        assert_eq!(caller.dfg.inst_args(call).len(), 16);
+    }
+
+    /// Lower a module which exports a function with 20 integer parameters.
+    ///
+    /// We expect this to be rejected, as callers outside of the module rely on its signature.
+    #[test]
+    fn lower_wide_signatures_exported_test() {
+        let context = TestContext::default();
+        let span = SourceSpan::UNKNOWN;
+        let sig =
+            Signature::new((0..20).map(|_| AbiParam::new(Type::U32)), [AbiParam::new(Type::U32)]);
+
+        let mut mb = ModuleBuilder::new("test");
+        {
+            let mut fb = mb.function("wide", sig).expect("unexpected symbol conflict");
+            let entry = fb.current_block();
+            let params = fb.block_params(entry).to_vec();
+            let result = fb.ins().add_checked(params[0], params[19], span);
+            fb.ins().ret(Some(result), span);
+            fb.build(&context.session.diagnostics)
+                .expect("unexpected error building function");
+        }
+        let mut module = mb.build();
+
+        let mut analyses = AnalysisManager::default();
+        let mut rewrite = LowerWideSignatures;
+        let err = rewrite
+            .apply(&mut module, &mut analyses, &context.session)
+            .expect_err("expected rewrite to fail");
+        assert_eq!(err.to_string(), "invalid function signature");
+
+        let wide = module.function("wide".into()).unwrap();
+        assert_eq!(wide.signature.params.len(), 20);
+    }
+
+    /// Lower a module which exports a function with 20 integer parameters using the canonical ABI.
+    ///
+    /// We expect this to be left unchanged, as the canonical ABI passes such arguments in memory.
+    #[test]
+    fn lower_wide_signatures_exported_canonical_abi_test() {
+        let context = TestContext::default();
+        let span = SourceSpan::UNKNOWN;
+        let mut sig =
+            Signature::new((0..20).map(|_| AbiParam::new(Type::U32)), [AbiParam::new(Type::U32)]);
+        sig.cc = CallConv::Wasm;
+
+        let mut mb = ModuleBuilder::new("test");
+        {
+            let mut fb = mb.function("wide", sig).expect("unexpected symbol conflict");
+            let entry = fb.current_block();
+            let params = fb.block_params(entry).to_vec();
+            let result = fb.ins().add_checked(params[0], params[19], span);
+            fb.ins().ret(Some(result), span);
+            fb.build(&context.session.diagnostics)
+                .expect("unexpected error building function");
+        }
+        let mut module = mb.build();
+
+        let mut analyses = AnalysisManager::default();
+        let mut rewrite = LowerWideSignatures;
+        rewrite
+            .apply(&mut module, &mut analyses, &context.session)
+            .expect("expected rewrite to succeed");
+
+        let wide = module.function("wide".into()).unwrap();
+        assert_eq!(wide.signature.params.len(), 20);
+        assert!(module.globals().is_empty());
+    }
+}
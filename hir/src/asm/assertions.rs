@@ -2,3 +2,7 @@
 
 /// This assertion fails when a pointer address does not meet minimum alignment for the type
 pub const ASSERT_FAILED_ALIGNMENT: u32 = 0xfa;
+
+/// This assertion fails when an exception propagates out of a function exported by the module
+/// which raised it, as nothing outside of the module can catch it
+pub const ASSERT_FAILED_UNCAUGHT_EXCEPTION: u32 = 0xfb;
@@ -623,7 +623,7 @@ impl<'a> MasmOpBuilder<'a> {
         self.build(self.ip, MasmOp::EqImm(imm), span);
     }
 
-    /// Pops two words off the stack, and pushes 1 on the stack if they are equal, else 0.
+    /// Pushes 1 on the stack if the top two words are equal, else 0, leaving both words in place.
     pub fn eqw(mut self, span: SourceSpan) {
         self.build(self.ip, MasmOp::Eqw, span);
     }
@@ -871,7 +871,16 @@ impl<'a> MasmOpBuilder<'a> {
                     ],
                 );
             }
-            Overflow::Overflowing => MasmOp::U32OverflowingMul,
+            Overflow::Overflowing => {
+                // The overflow is given as the high 32 bits of the product, not a boolean
+                return self.build_many(
+                    self.ip,
+                    [
+                        Span::new(span, MasmOp::U32OverflowingMul),
+                        Span::new(span, MasmOp::NeqImm(Felt::new(0))),
+                    ],
+                );
+            }
             Overflow::Wrapping => MasmOp::U32WrappingMul,
         };
         self.build(self.ip, op, span);
@@ -891,7 +900,15 @@ impl<'a> MasmOpBuilder<'a> {
                     ],
                 );
             }
-            Overflow::Overflowing => MasmOp::U32OverflowingMulImm(imm),
+            Overflow::Overflowing => {
+                return self.build_many(
+                    self.ip,
+                    [
+                        Span::new(span, MasmOp::U32OverflowingMulImm(imm)),
+                        Span::new(span, MasmOp::NeqImm(Felt::new(0))),
+                    ],
+                );
+            }
             Overflow::Wrapping => MasmOp::U32WrappingMulImm(imm),
         };
         self.build(self.ip, op, span);
@@ -2027,7 +2044,7 @@ fn apply_op_stack_effects(
             stack.push(Type::I1);
         }
         MasmOp::Eqw => {
-            stack.dropn(8);
+            assert!(stack.len() > 7, "expected at least 8 elements on the operand stack for eqw");
             stack.push(Type::I1);
         }
         op @ (MasmOp::Ext2add | MasmOp::Ext2sub | MasmOp::Ext2mul | MasmOp::Ext2div) => {
@@ -2125,21 +2142,32 @@ fn apply_op_stack_effects(
             assert_compatible_u32_operand!(ty, op);
             stack.push(ty);
         }
-        MasmOp::U32OverflowingAdd | MasmOp::U32OverflowingSub | MasmOp::U32OverflowingMul => {
+        MasmOp::U32OverflowingAdd | MasmOp::U32OverflowingSub => {
             let rty = stack.pop().expect("operand stack is empty");
             let lty = stack.pop().expect("operand stack is empty");
             assert_compatible_u32_operands!(lty, rty, op);
             stack.push(lty);
             stack.push(Type::I1);
         }
-        MasmOp::U32OverflowingAddImm(_)
-        | MasmOp::U32OverflowingSubImm(_)
-        | MasmOp::U32OverflowingMulImm(_) => {
+        MasmOp::U32OverflowingMul => {
+            let rty = stack.pop().expect("operand stack is empty");
+            let lty = stack.pop().expect("operand stack is empty");
+            assert_compatible_u32_operands!(lty, rty, op);
+            stack.push(lty);
+            stack.push(Type::U32);
+        }
+        MasmOp::U32OverflowingAddImm(_) | MasmOp::U32OverflowingSubImm(_) => {
             let ty = stack.pop().expect("operand stack is empty");
             assert_compatible_u32_operand!(ty, op);
             stack.push(ty);
             stack.push(Type::I1);
         }
+        MasmOp::U32OverflowingMulImm(_) => {
+            let ty = stack.pop().expect("operand stack is empty");
+            assert_compatible_u32_operand!(ty, op);
+            stack.push(ty);
+            stack.push(Type::U32);
+        }
         MasmOp::U32OverflowingAdd3 => {
             let cty = stack.pop().expect("operand stack is empty");
             let bty = stack.pop().expect("operand stack is empty");
@@ -2211,7 +2239,10 @@ fn execute_call(
     }
     match import.signature.cc {
         // For now, we're treating all calling conventions the same as SystemV
-        CallConv::Fast | CallConv::SystemV | CallConv::Kernel => {
+        //
+        // NOTE: Functions using the canonical ABI are invoked in their own context, but the
+        // effect on the operand stack as observed by the caller is the same
+        CallConv::Fast | CallConv::SystemV | CallConv::Kernel | CallConv::Wasm => {
             // Visit the argument list in reverse (so that the top of the stack on entry
             // is the first argument), and allocate elements based on the argument types.
             let mut elements_needed = 0;
@@ -2236,7 +2267,6 @@ fn execute_call(
                 push_type_on_stack(result.ty.clone(), stack);
             }
         }
-        CallConv::Wasm => unimplemented!("canonical abi support is not yet implemented"),
     }
 }
 
@@ -1,3 +1,4 @@
+use alloc::collections::{BTreeMap, BTreeSet};
 use core::{
     fmt::Write,
     hash::{Hash, Hasher},
@@ -5,7 +6,6 @@ use core::{
 };
 
 use anyhow::bail;
-use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
     diagnostics::{SourceSpan, Spanned},
@@ -15,11 +15,11 @@ use crate::{
 #[derive(Default, Debug, Clone)]
 pub struct ModuleImportInfo {
     /// This maps original, fully-qualified module names to their corresponding import
-    modules: FxHashMap<Ident, MasmImport>,
+    modules: BTreeMap<Ident, MasmImport>,
     /// This maps known aliases to their fully-qualified identifiers
-    aliases: FxHashMap<Ident, Ident>,
+    aliases: BTreeMap<Ident, Ident>,
     /// This maps short-form/aliased module names to the functions imported from that module
-    functions: FxHashMap<Ident, FxHashSet<FunctionIdent>>,
+    functions: BTreeMap<Ident, BTreeSet<FunctionIdent>>,
 }
 impl ModuleImportInfo {
     /// Inserts a new import in the table
@@ -32,7 +32,7 @@ impl ModuleImportInfo {
     ///
     /// NOTE: It is assumed that the caller is adding imports using fully-qualified names.
     pub fn add(&mut self, id: FunctionIdent) {
-        use std::collections::hash_map::Entry;
+        use alloc::collections::btree_map::Entry;
 
         let module_id = id.module;
         match self.modules.entry(module_id) {
@@ -85,7 +85,7 @@ impl ModuleImportInfo {
     pub fn get<Q>(&self, module: &Q) -> Option<&MasmImport>
     where
         Ident: core::borrow::Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Ord + ?Sized,
     {
         self.modules.get(module)
     }
@@ -94,7 +94,7 @@ impl ModuleImportInfo {
     pub fn alias<Q>(&self, module: &Q) -> Option<Ident>
     where
         Ident: core::borrow::Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Ord + ?Sized,
     {
         self.modules.get(module).map(|i| Ident::new(i.alias, i.span))
     }
@@ -103,7 +103,7 @@ impl ModuleImportInfo {
     pub fn unalias<Q>(&self, alias: &Q) -> Option<Ident>
     where
         Ident: core::borrow::Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Ord + ?Sized,
     {
         self.aliases.get(alias).copied()
     }
@@ -112,16 +112,16 @@ impl ModuleImportInfo {
     pub fn is_import<Q>(&self, module: &Q) -> bool
     where
         Ident: core::borrow::Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Ord + ?Sized,
     {
         self.modules.contains_key(module)
     }
 
     /// Given a module alias, get the set of functions imported from that module
-    pub fn imported<Q>(&self, alias: &Q) -> Option<&FxHashSet<FunctionIdent>>
+    pub fn imported<Q>(&self, alias: &Q) -> Option<&BTreeSet<FunctionIdent>>
     where
         Ident: core::borrow::Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Ord + ?Sized,
     {
         self.functions.get(alias)
     }
@@ -102,6 +102,8 @@ pub enum MasmOp {
     /// Pushes a pair of field elements on top of the stack
     Push2([Felt; 2]),
     /// Pushes the given word constant on top of the stack
+    ///
+    /// The elements are pushed in order, so the last element of the word ends up on top
     Pushw([Felt; 4]),
     /// Pushes the given 8-bit constant on top of the stack
     PushU8(u8),
@@ -599,8 +601,8 @@ pub enum MasmOp {
     LteImm(Felt),
     /// Pops `a` off the stack, and places the 1 on the stack if `a` is odd, else 0
     IsOdd,
-    /// Pops `B, A` off the stack, and places the result of `A == B` on the stack,
-    /// where the uppercase variables here represent words, rather than field elements.
+    /// Places the result of `A == B` on top of the stack, where `B` is the word on top of the
+    /// stack, and `A` is the word below it. Both words are left on the stack.
     ///
     /// The comparison works by comparing pairs of elements from each word
     Eqw,
@@ -900,18 +902,38 @@ macro_rules! unwrap_u8 {
     }};
 }
 
+/// The cost of pushing `value` on the operand stack: `0` and `1` are pushed with `pad` and
+/// `pad incr` respectively, anything else with a single `push`.
+fn push_cost(value: u64) -> usize {
+    match value {
+        1 => 2,
+        _ => 1,
+    }
+}
+
 impl MasmOp {
     pub fn has_regions(&self) -> bool {
         matches!(self, Self::If(_, _) | Self::While(_) | Self::Repeat(_, _))
     }
 
     /// The cost of this instruction in cycles
+    ///
+    /// For instructions which are not control flow, this is the number of VM operations the
+    /// instruction is assembled to, each of which takes a single cycle to execute. The costs of
+    /// control flow instructions are measured overheads, and do not include the cost of the code
+    /// they execute, e.g. the body of a loop, or the callee of an `exec`.
+    ///
+    /// The cost does not include the `noop` padding the assembler may insert when grouping
+    /// operations into batches, which adds up to roughly 1 cycle for every 9 operations.
     pub fn cost(&self) -> usize {
         match self {
             Self::Padw => 4,
-            Self::Push(_) | Self::PushU8(_) | Self::PushU16(_) | Self::PushU32(_) => 1,
-            Self::Push2(_) => 2,
-            Self::Pushw(_) => 4,
+            Self::Push(imm) => push_cost(imm.as_int()),
+            Self::PushU8(imm) => push_cost(*imm as u64),
+            Self::PushU16(imm) => push_cost(*imm as u64),
+            Self::PushU32(imm) => push_cost(*imm as u64),
+            Self::Push2(imms) => imms.iter().map(|imm| push_cost(imm.as_int())).sum(),
+            Self::Pushw(imms) => imms.iter().map(|imm| push_cost(imm.as_int())).sum(),
             Self::Drop => 1,
             Self::Dropw => 4,
             Self::Dup(8) | Self::Dup(10) | Self::Dup(12) | Self::Dup(14) => 3,
@@ -919,6 +941,7 @@ impl MasmOp {
             Self::Dupw(_) => 4,
             Self::Swap(1) => 1,
             Self::Swap(2..=8) => 2,
+            Self::Swap(9) => 5,
             Self::Swap(_) => 6,
             Self::Swapw(_) | Self::Swapdw => 1,
             Self::Movup(2..=8) => 1,
@@ -937,21 +960,18 @@ impl MasmOp {
             Self::Assertz | Self::AssertzWithError(_) => 2,
             Self::AssertEq | Self::AssertEqWithError(_) => 2,
             Self::AssertEqw | Self::AssertEqwWithError(_) => 11,
+            // The cost of accessing a local depends on its offset from the frame pointer, which
+            // depends on the number of locals in the procedure, so these are typical costs
             Self::LocAddr(_) => 2,
-            Self::LocStore(id) if id.as_usize() == 1 => 5,
             Self::LocStore(_) => 4,
-            Self::LocStorew(id) if id.as_usize() == 1 => 4,
             Self::LocStorew(_) => 3,
-            Self::LocLoad(id) | Self::LocLoadw(id) if id.as_usize() == 1 => 4,
             Self::LocLoad(_) | Self::LocLoadw(_) => 3,
             Self::MemLoad | Self::MemLoadw => 1,
-            Self::MemLoadImm(_) | Self::MemLoadwImm(_) => 2,
+            Self::MemLoadImm(addr) | Self::MemLoadwImm(addr) => 1 + push_cost(*addr as u64),
             Self::MemStore => 2,
-            Self::MemStoreImm(1) => 4,
-            Self::MemStoreImm(_) => 3,
+            Self::MemStoreImm(addr) => 2 + push_cost(*addr as u64),
             Self::MemStorew => 1,
-            Self::MemStorewImm(1) => 3,
-            Self::MemStorewImm(_) => 2,
+            Self::MemStorewImm(addr) => 1 + push_cost(*addr as u64),
             Self::MemStream => 1,
             Self::AdvPipe => 1,
             Self::AdvPush(n) => *n as usize,
@@ -979,16 +999,21 @@ impl MasmOp {
             Self::DynExec => 8,
             // A `dyncall` requires an additional 8 cycles compared to `dynexec`
             Self::DynCall => 16,
-            Self::Add | Self::Sub | Self::Mul => 1,
+            Self::Add | Self::Mul => 1,
+            Self::Sub => 2,
             Self::AddImm(imm) => match imm.as_int() {
                 0 => 0,
                 1 => 1,
                 _ => 2,
             },
-            Self::SubImm(imm) | Self::MulImm(imm) => match imm.as_int() {
+            Self::SubImm(imm) => match imm.as_int() {
                 0 => 0,
                 _ => 2,
             },
+            Self::MulImm(imm) => match imm.as_int() {
+                1 => 0,
+                _ => 2,
+            },
             Self::Div => 2,
             Self::DivImm(imm) => match imm.as_int() {
                 1 => 0,
@@ -997,24 +1022,24 @@ impl MasmOp {
             Self::Neg | Self::Inv | Self::Incr => 1,
             Self::Ilog2 => 44,
             Self::Pow2 => 16,
-            // The cost of this instruction is 9 + log2(b), but we don't know `b`, so we use a value
-            // of 32 to estimate average cost
-            Self::Exp => 9 + 32usize.ilog2() as usize,
+            // An exponentiation with an exponent of `b` bits costs 9 + b cycles, and `exp` assumes
+            // the exponent may use all 64 bits
+            Self::Exp => 9 + 64,
+            Self::ExpBitLength(bits) => 9 + *bits as usize,
+            // Small exponents are computed by repeated multiplication
             Self::ExpImm(0) => 3,
-            Self::ExpImm(1) => 1,
+            Self::ExpImm(1) => 0,
             Self::ExpImm(2) => 2,
             Self::ExpImm(3) => 4,
             Self::ExpImm(4) => 6,
             Self::ExpImm(5) => 8,
             Self::ExpImm(6) => 10,
             Self::ExpImm(7) => 12,
-            Self::ExpImm(imm) | Self::ExpBitLength(imm) => {
-                9 + unsafe { f64::from(*imm).log2().ceil().to_int_unchecked::<usize>() }
-            }
+            Self::ExpImm(imm) => 11 + imm.ilog2() as usize,
             Self::Not | Self::And | Self::Or => 1,
-            Self::AndImm(_) | Self::OrImm(_) => 2,
+            Self::AndImm(imm) | Self::OrImm(imm) => 1 + push_cost(*imm as u64),
             Self::Xor => 7,
-            Self::XorImm(_) => 8,
+            Self::XorImm(imm) => 7 + push_cost(*imm as u64),
             Self::Eq => 1,
             Self::EqImm(imm) => match imm.as_int() {
                 0 => 1,
@@ -1022,17 +1047,17 @@ impl MasmOp {
             },
             Self::Neq => 2,
             Self::NeqImm(imm) => match imm.as_int() {
-                0 => 1,
+                0 => 2,
                 _ => 3,
             },
             Self::Gt => 15,
-            Self::GtImm(_) => 16,
+            Self::GtImm(imm) => 15 + push_cost(imm.as_int()),
             Self::Gte => 16,
-            Self::GteImm(_) => 17,
+            Self::GteImm(imm) => 16 + push_cost(imm.as_int()),
             Self::Lt => 14,
-            Self::LtImm(_) => 15,
+            Self::LtImm(imm) => 14 + push_cost(imm.as_int()),
             Self::Lte => 15,
-            Self::LteImm(_) => 16,
+            Self::LteImm(imm) => 15 + push_cost(imm.as_int()),
             Self::IsOdd => 5,
             Self::Eqw => 15,
             Self::Hash => 20,
@@ -1042,8 +1067,7 @@ impl MasmOp {
             Self::MtreeSet => 29,
             Self::MtreeMerge => 16,
             Self::MtreeVerify | Self::MtreeVerifyWithError(_) => 1,
-            // This hasn't been measured, just a random guess due to the complexity
-            Self::FriExt2Fold4 | Self::RCombBase => 50,
+            Self::FriExt2Fold4 | Self::RCombBase => 1,
             Self::Ext2add => 5,
             Self::Ext2sub => 7,
             Self::Ext2mul => 3,
@@ -1059,27 +1083,27 @@ impl MasmOp {
             Self::U32Cast => 2,
             Self::U32Split => 1,
             Self::U32OverflowingAdd => 1,
-            Self::U32OverflowingAddImm(_) => 2,
+            Self::U32OverflowingAddImm(imm) => 1 + push_cost(*imm as u64),
             Self::U32WrappingAdd => 2,
-            Self::U32WrappingAddImm(_) => 3,
+            Self::U32WrappingAddImm(imm) => 2 + push_cost(*imm as u64),
             Self::U32OverflowingAdd3 => 1,
             Self::U32WrappingAdd3 => 2,
             Self::U32OverflowingSub => 1,
-            Self::U32OverflowingSubImm(_) => 2,
+            Self::U32OverflowingSubImm(imm) => 1 + push_cost(*imm as u64),
             Self::U32WrappingSub => 2,
-            Self::U32WrappingSubImm(_) => 3,
+            Self::U32WrappingSubImm(imm) => 2 + push_cost(*imm as u64),
             Self::U32OverflowingMul => 1,
-            Self::U32OverflowingMulImm(_) => 2,
+            Self::U32OverflowingMulImm(imm) => 1 + push_cost(*imm as u64),
             Self::U32WrappingMul => 2,
-            Self::U32WrappingMulImm(_) => 3,
+            Self::U32WrappingMulImm(imm) => 2 + push_cost(*imm as u64),
             Self::U32OverflowingMadd => 1,
             Self::U32WrappingMadd => 2,
             Self::U32Div => 2,
-            Self::U32DivImm(_) => 3,
+            Self::U32DivImm(imm) => 2 + push_cost(*imm as u64),
             Self::U32Mod => 3,
-            Self::U32ModImm(_) => 4,
+            Self::U32ModImm(imm) => 3 + push_cost(*imm as u64),
             Self::U32DivMod => 1,
-            Self::U32DivModImm(_) => 2,
+            Self::U32DivModImm(imm) => 1 + push_cost(*imm as u64),
             Self::U32And => 1,
             Self::U32Or => 6,
             Self::U32Xor => 1,
@@ -1102,17 +1126,17 @@ impl MasmOp {
             Self::U32Clo => 36,
             Self::U32Cto => 33,
             Self::U32Lt => 3,
-            Self::U32LtImm(_) => 4,
+            Self::U32LtImm(imm) => 3 + push_cost(*imm as u64),
             Self::U32Lte => 5,
-            Self::U32LteImm(_) => 6,
+            Self::U32LteImm(imm) => 5 + push_cost(*imm as u64),
             Self::U32Gt => 4,
-            Self::U32GtImm(_) => 5,
+            Self::U32GtImm(imm) => 4 + push_cost(*imm as u64),
             Self::U32Gte => 4,
-            Self::U32GteImm(_) => 5,
+            Self::U32GteImm(imm) => 4 + push_cost(*imm as u64),
             Self::U32Min => 8,
-            Self::U32MinImm(_) => 9,
+            Self::U32MinImm(imm) => 8 + push_cost(*imm as u64),
             Self::U32Max => 9,
-            Self::U32MaxImm(_) => 10,
+            Self::U32MaxImm(imm) => 9 + push_cost(*imm as u64),
             // These instructions do not modify the VM state, so we place set their cost at 0 for
             // now
             Self::Emit(_)
@@ -1136,8 +1160,8 @@ impl MasmOp {
             | Self::DebugFrame
             | Self::DebugFrameAt(_)
             | Self::DebugFrameRange(..)
-            | Self::Breakpoint
-            | Self::Nop => 0,
+            | Self::Breakpoint => 0,
+            Self::Nop => 1,
         }
     }
 
@@ -204,9 +204,9 @@ pub trait Stack: IndexMut<usize, Output = <Self as Stack>::Element> {
         let len = stack.len();
         let index = n * 4;
         assert!(
-            index < len,
+            index + 4 <= len,
             "invalid operand stack index ({}), only {} elements are available",
-            index,
+            index + 3,
             len
         );
         let end = len - 1;
@@ -222,7 +222,9 @@ pub trait Stack: IndexMut<usize, Output = <Self as Stack>::Element> {
     /// Swaps the top two and bottom two words on the stack.
     fn swapdw(&mut self) {
         let stack = self.stack_mut();
-        stack.rotate_left(8);
+        let len = stack.len();
+        assert!(len >= 16, "invalid operand stack index (15), only {len} elements are available");
+        stack[(len - 16)..].rotate_left(8);
     }
 
     /// Moves the `n`th value to the top of the stack
@@ -259,9 +261,9 @@ pub trait Stack: IndexMut<usize, Output = <Self as Stack>::Element> {
         let len = stack.len();
         let index = (n * 4) + 4;
         assert!(
-            index < len,
+            index <= len,
             "invalid operand stack index ({}), only {} elements are available",
-            index,
+            index - 1,
             len
         );
         // Pick the midpoint index by counting backwards from the end
@@ -306,9 +308,9 @@ pub trait Stack: IndexMut<usize, Output = <Self as Stack>::Element> {
         let len = stack.len();
         let index = (n * 4) + 4;
         assert!(
-            index < len,
+            index <= len,
             "invalid operand stack index ({}), only {} elements are available",
-            index,
+            index - 1,
             len
         );
         // Split the stack so that the desired position is in the top half
@@ -508,6 +510,55 @@ impl<'a, E: StackElement + fmt::Debug, T: ?Sized + Stack<Element = E>> fmt::Disp
 mod tests {
     use super::*;
 
+    #[test]
+    fn operand_stack_word_ops_bounds_test() {
+        #[inline(always)]
+        fn as_int(word: [Felt; 4]) -> [u64; 4] {
+            [word[0].as_int(), word[1].as_int(), word[2].as_int(), word[3].as_int()]
+        }
+
+        // The deepest word can be moved when the stack holds exactly that many elements
+        let mut stack = OperandStack::<Felt>::default();
+        for i in 0..12 {
+            stack.push(Felt::new(i));
+        }
+        stack.movupw(2);
+        assert_eq!(stack.peekw().map(as_int), Some([3, 2, 1, 0]));
+        stack.movdnw(2);
+        assert_eq!(stack.peekw().map(as_int), Some([11, 10, 9, 8]));
+        assert_eq!(stack[11].as_int(), 0);
+        stack.swapw(2);
+        assert_eq!(stack.peekw().map(as_int), Some([3, 2, 1, 0]));
+        assert_eq!(stack[8].as_int(), 11);
+        stack.swapw(2);
+
+        // swapdw only affects the top 16 elements of the stack
+        for i in 12..20 {
+            stack.push(Felt::new(i));
+        }
+        stack.swapdw();
+        assert_eq!(stack.len(), 20);
+        assert_eq!(stack.peekw().map(as_int), Some([11, 10, 9, 8]));
+        assert_eq!(stack[4].as_int(), 7);
+        assert_eq!(stack[8].as_int(), 19);
+        assert_eq!(stack[12].as_int(), 15);
+        assert_eq!(stack[15].as_int(), 12);
+        for _ in 0..4 {
+            stack.dropw();
+        }
+        assert_eq!(stack.peekw().map(as_int), Some([3, 2, 1, 0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid operand stack index (7), only 5 elements are available")]
+    fn operand_stack_swapw_out_of_bounds_test() {
+        let mut stack = OperandStack::<Felt>::default();
+        for i in 0..5 {
+            stack.push(Felt::new(i));
+        }
+        stack.swapw(1);
+    }
+
     #[test]
     fn operand_stack_primitive_ops_test() {
         let mut stack = OperandStack::<Felt>::default();
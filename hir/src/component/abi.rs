@@ -0,0 +1,131 @@
+use core::alloc::Layout;
+
+use crate::{ArgumentPurpose, Signature, StructType, Type};
+
+/// The maximum number of flattened values which are passed as arguments to a function using the
+/// canonical ABI, beyond which the arguments are passed in linear memory instead
+pub const MAX_FLAT_PARAMS: usize = 16;
+
+/// The maximum number of flattened values which are returned from a function using the canonical
+/// ABI, beyond which the results are returned in linear memory instead
+pub const MAX_FLAT_RESULTS: usize = 1;
+
+/// The number of elements on the operand stack by which arguments or results passed through linear
+/// memory are represented, when calling a function in another memory context, i.e. a commitment to
+/// the words of memory containing them, and the offset of the data in the first of those words
+pub const CANONICAL_ABI_AREA_SIZE_IN_FELTS: usize = 5;
+
+/// Returns the number of core Wasm values that a value of type `ty` is flattened to by the
+/// canonical ABI
+pub fn canonical_abi_flat_count(ty: &Type) -> usize {
+    match ty {
+        Type::Struct(struct_ty) => {
+            struct_ty.fields().iter().map(|field| canonical_abi_flat_count(&field.ty)).sum()
+        }
+        // A pointer to the elements, and the number of elements
+        Type::List(_) => 2,
+        _ => 1,
+    }
+}
+
+/// Returns true if a value of type `ty` refers to data elsewhere in linear memory, and so cannot
+/// be copied into another memory context as-is
+pub fn canonical_abi_has_references(ty: &Type) -> bool {
+    match ty {
+        Type::Struct(struct_ty) => {
+            struct_ty.fields().iter().any(|field| canonical_abi_has_references(&field.ty))
+        }
+        Type::List(_) | Type::Ptr(_) | Type::NativePtr(..) => true,
+        _ => false,
+    }
+}
+
+/// Returns the layout of a value of type `ty` in linear memory, as defined by the canonical ABI.
+///
+/// This differs from [Type::layout], as the canonical ABI requires 64-bit integers to be naturally
+/// aligned. Returns `None` if `ty` has no representation in the canonical ABI, or if the value
+/// refers to data elsewhere in linear memory, e.g. a list.
+pub fn canonical_abi_layout(ty: &Type) -> Option<Layout> {
+    let (size, align) = match ty {
+        Type::I1 | Type::I8 | Type::U8 => (1, 1),
+        Type::I16 | Type::U16 => (2, 2),
+        Type::I32 | Type::U32 => (4, 4),
+        Type::I64 | Type::U64 | Type::F64 => (8, 8),
+        Type::Struct(struct_ty) => {
+            return canonical_abi_record_layout(struct_ty.fields().iter().map(|field| &field.ty))
+        }
+        _ => return None,
+    };
+    Layout::from_size_align(size, align).ok()
+}
+
+/// Returns the layout of a record with fields of the given types in linear memory, as defined by
+/// the canonical ABI. See [canonical_abi_layout].
+///
+/// This is the layout of the memory through which the parameters or results of a function using
+/// the canonical ABI are passed, when they cannot be flattened.
+pub fn canonical_abi_record_layout<'a, I>(fields: I) -> Option<Layout>
+where
+    I: IntoIterator<Item = &'a Type>,
+{
+    let mut layout = Layout::from_size_align(0, 1).unwrap();
+    for field in fields {
+        let (extended, _offset) = layout.extend(canonical_abi_layout(field)?).ok()?;
+        layout = extended;
+    }
+    Some(layout.pad_to_align())
+}
+
+/// Returns the type of the record through which the arguments of `signature` are passed in linear
+/// memory, if it is the signature of a function imported using the canonical ABI, which takes more
+/// than [MAX_FLAT_PARAMS] flattened arguments.
+///
+/// In that case, the caller passes a pointer to this record as the first and only argument, other
+/// than the pointer to which the results are written, see [canonical_abi_results_area].
+pub fn canonical_abi_params_area(signature: &Signature) -> Option<&Type> {
+    signature
+        .params
+        .first()
+        .filter(|param| param.purpose == ArgumentPurpose::Default)
+        .and_then(|param| param.ty.pointee())
+        .filter(|pointee| matches!(pointee, Type::Struct(_)))
+}
+
+/// Returns the type of the record through which the results of `signature` are returned in linear
+/// memory, if it is the signature of a function imported using the canonical ABI, which returns
+/// more than [MAX_FLAT_RESULTS] flattened results.
+///
+/// In that case, the caller passes a pointer to memory to which the results are written, as the
+/// last argument, with the `sret` attribute.
+pub fn canonical_abi_results_area(signature: &Signature) -> Option<&Type> {
+    signature
+        .params
+        .last()
+        .filter(|param| param.purpose == ArgumentPurpose::StructReturn)
+        .and_then(|param| param.ty.pointee())
+}
+
+/// Returns the type of a value of the component-level type `ty`, as it is represented in linear
+/// memory by the canonical ABI, i.e. with each list represented by a pointer to its elements,
+/// followed by the number of elements
+pub fn canonical_abi_lowered_type(ty: &Type) -> Type {
+    match ty {
+        Type::Struct(struct_ty) => {
+            canonical_abi_record_type(struct_ty.fields().iter().map(|field| &field.ty))
+        }
+        Type::List(element_ty) => Type::Struct(StructType::new([
+            Type::Ptr(Box::new(canonical_abi_lowered_type(element_ty))),
+            Type::U32,
+        ])),
+        ty => ty.clone(),
+    }
+}
+
+/// Returns the type of a record with fields of the given component-level types, as it is
+/// represented in linear memory by the canonical ABI, see [canonical_abi_lowered_type]
+pub fn canonical_abi_record_type<'a, I>(fields: I) -> Type
+where
+    I: IntoIterator<Item = &'a Type>,
+{
+    Type::Struct(StructType::new(fields.into_iter().map(canonical_abi_lowered_type)))
+}
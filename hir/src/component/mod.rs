@@ -10,9 +10,10 @@ use crate::{
     *,
 };
 
+mod abi;
 mod interface;
 
-pub use interface::*;
+pub use self::{abi::*, interface::*};
 
 /// Canonical ABI options associated with a lifted or lowered function.
 #[derive(Debug, Clone)]
@@ -124,6 +125,18 @@ pub struct ComponentExport {
     pub options: CanonicalOptions,
 }
 
+impl ComponentExport {
+    /// Get the signature of the exported function in terms of its component(lifted) type
+    pub fn lifted_signature(&self) -> Signature {
+        Signature {
+            params: self.function_ty.params.iter().cloned().map(AbiParam::new).collect(),
+            results: self.function_ty.results.iter().cloned().map(AbiParam::new).collect(),
+            cc: CallConv::Wasm,
+            linkage: Linkage::External,
+        }
+    }
+}
+
 /// A [Component] is a collection of [Module]s that are being compiled together as a package and
 /// have exports/imports.
 #[derive(Default)]
@@ -326,8 +339,24 @@ impl<'a> ComponentBuilder<'a> {
         self.exports.insert(name, export);
     }
 
-    pub fn build(self) -> Component {
+    pub fn build(mut self) -> Component {
         assert!(!self.modules.is_empty(), "Cannot build a component with no modules");
+
+        // The functions lifted by the exports of this component are invoked by other components
+        // using `call`, so they must use the canonical ABI
+        for export in self.exports.values() {
+            let Some(module) = self.modules.get_mut(&export.function.module) else {
+                continue;
+            };
+            // Callers see the lifted function in terms of the component-level types of the export
+            module.set_export_signature(export.function.function, export.lifted_signature());
+            let mut cursor = module.cursor_mut_at(export.function.function);
+            if let Some(mut function) = cursor.remove() {
+                function.signature.cc = CallConv::Wasm;
+                cursor.insert_before(function);
+            }
+        }
+
         Component {
             modules: self.modules,
             imports: self.imports,
@@ -1,7 +1,7 @@
+use alloc::collections::BTreeMap;
 use core::ops::{Deref, DerefMut, Index, IndexMut};
 
 use cranelift_entity::{PrimaryMap, SecondaryMap};
-use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
 
 use crate::{
@@ -17,7 +17,7 @@ pub struct DataFlowGraph {
     pub results: SecondaryMap<Inst, ValueList>,
     pub values: PrimaryMap<Value, ValueData>,
     pub value_lists: ValueListPool,
-    pub imports: FxHashMap<FunctionIdent, ExternalFunction>,
+    pub imports: BTreeMap<FunctionIdent, ExternalFunction>,
     pub globals: PrimaryMap<GlobalValue, GlobalValueData>,
     pub locals: PrimaryMap<LocalId, Local>,
     pub constants: ConstantPool,
@@ -114,7 +114,7 @@ impl DataFlowGraph {
         name: Ident,
         signature: Signature,
     ) -> Result<FunctionIdent, SymbolConflictError> {
-        use std::collections::hash_map::Entry;
+        use alloc::collections::btree_map::Entry;
 
         let id = FunctionIdent {
             module,
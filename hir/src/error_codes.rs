@@ -0,0 +1,109 @@
+use alloc::{collections::BTreeMap, sync::Arc};
+
+use miden_core::crypto::hash::Blake3_256;
+
+use crate::diagnostics::{miette, Diagnostic};
+
+/// This error is raised when merging two [ErrorCodeTable] which assign the same error code to
+/// different messages.
+#[derive(Debug, thiserror::Error, Diagnostic)]
+#[error(
+    "invalid error code table: error code {code:#x} is assigned to both '{existing}' and \
+     '{conflict}'"
+)]
+#[diagnostic()]
+pub struct ErrorCodeError {
+    pub code: u32,
+    pub existing: Arc<str>,
+    pub conflict: Arc<str>,
+}
+
+/// An [ErrorCodeTable] associates the error codes of failing assertions, i.e. `assert.err=<code>`
+/// in Miden Assembly, with a human-readable message describing the failure, e.g. the message and
+/// source location of a Rust panic.
+///
+/// Error codes are derived from a hash of the message they describe, so that the same message is
+/// assigned the same code in every module, and tables built for separate modules can be merged at
+/// link-time without renumbering the assertions which refer to them. Derived codes always have the
+/// most significant bit set, so they never overlap with the small error codes used by the compiler
+/// itself. Because codes are never renumbered, two messages whose derived codes collide cannot be
+/// assigned codes in the same table, and this is reported as an error by
+/// [ErrorCodeTable::intern] and [ErrorCodeTable::merge].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorCodeTable {
+    codes: BTreeMap<u32, Arc<str>>,
+}
+impl ErrorCodeTable {
+    /// The bit which is set in every error code assigned by an [ErrorCodeTable]
+    pub const ERROR_CODE_BIT: u32 = 1 << 31;
+
+    /// Returns true if this table is empty
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Returns the number of error codes in this table
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Get the error code derived from `message`
+    ///
+    /// The code depends only on the contents of `message`, not on the table it is interned in.
+    pub fn code(message: &str) -> u32 {
+        let digest = Blake3_256::hash(message.as_bytes());
+        u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]]) | Self::ERROR_CODE_BIT
+    }
+
+    /// Get the error code assigned to `message`, adding it to this table if this is the first time
+    /// it has been seen.
+    ///
+    /// Returns `Err` if the code derived from `message` is already assigned to a different message.
+    pub fn intern(&mut self, message: &str) -> Result<u32, ErrorCodeError> {
+        let code = Self::code(message);
+        match self.codes.get(&code) {
+            Some(existing) if existing.as_ref() != message => Err(ErrorCodeError {
+                code,
+                existing: existing.clone(),
+                conflict: Arc::from(message),
+            }),
+            Some(_) => Ok(code),
+            None => {
+                self.codes.insert(code, Arc::from(message));
+                Ok(code)
+            }
+        }
+    }
+
+    /// Get the message associated with `code`, if known
+    pub fn get(&self, code: u32) -> Option<&str> {
+        self.codes.get(&code).map(|message| message.as_ref())
+    }
+
+    /// Get an iterator over the error codes in this table, and their messages, in code order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> + '_ {
+        self.codes.iter().map(|(code, message)| (*code, message.as_ref()))
+    }
+
+    /// Merge the error codes of `other` into this table
+    ///
+    /// Returns `Err` if `other` assigns a code already in this table to a different message.
+    pub fn merge(&mut self, other: &Self) -> Result<(), ErrorCodeError> {
+        for (code, message) in other.codes.iter() {
+            match self.codes.get(code) {
+                Some(existing) if existing != message => {
+                    return Err(ErrorCodeError {
+                        code: *code,
+                        existing: existing.clone(),
+                        conflict: message.clone(),
+                    });
+                }
+                Some(_) => (),
+                None => {
+                    self.codes.insert(*code, message.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
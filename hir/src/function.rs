@@ -50,6 +50,12 @@ pub enum CallConv {
     #[default]
     SystemV,
     /// A function which is using the WebAssembly Component Model "Canonical ABI".
+    ///
+    /// Such functions are exported by a component, and are invoked from other components via
+    /// the `call` instruction, i.e. they execute in their own memory context. The parameters and
+    /// results are the flattened core types of the component-level function signature, and so
+    /// must be passed entirely on the operand stack: the parameters can use at most 16 elements,
+    /// and there can be at most one result.
     Wasm,
     /// A function with this calling convention must be called using
     /// the `syscall` instruction. Attempts to call it with any other
@@ -70,9 +70,9 @@ pub enum Linkage {
 impl fmt::Display for Linkage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Internal => f.write_str("internal"),
-            Self::Odr => f.write_str("odr"),
-            Self::External => f.write_str("external"),
+            Self::Internal => f.pad("internal"),
+            Self::Odr => f.pad("odr"),
+            Self::External => f.pad("external"),
         }
     }
 }
@@ -212,10 +212,10 @@ impl GlobalVariableTable {
         //
         // At the end, the effective address of the pointer is the total
         // size in bytes of the allocation
-        let mut size = 0;
+        let mut size = 0usize;
         for gv in self.layout.iter() {
             let layout = gv.layout();
-            size += layout.size().align_up(layout.align());
+            size = size.align_up(layout.align()) + layout.size();
         }
         size
     }
@@ -240,8 +240,7 @@ impl GlobalVariableTable {
         let mut size = 0usize;
         for gv in self.layout.iter() {
             let layout = gv.layout();
-            let align_offset = layout.size().align_offset(layout.align());
-            size += align_offset;
+            size = size.align_up(layout.align());
 
             // If the current variable is the one we're after,
             // the aligned address is the offset to the start
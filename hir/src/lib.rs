@@ -132,6 +132,7 @@ mod component;
 mod constants;
 mod dataflow;
 mod display;
+mod error_codes;
 pub mod formatter;
 mod function;
 mod globals;
@@ -164,6 +165,7 @@ pub use self::{
     constants::{Constant, ConstantData, ConstantPool, IntoBytes},
     dataflow::DataFlowGraph,
     display::{Decorator, DisplayValues},
+    error_codes::{ErrorCodeError, ErrorCodeTable},
     function::*,
     globals::*,
     ident::{demangle, FunctionIdent, Ident},
@@ -177,7 +179,10 @@ pub use self::{
         AnalysisKey, ConversionPassRegistration, ModuleRewritePassAdapter, PassInfo,
         RewritePassRegistration,
     },
-    program::{Linker, Program, ProgramAnalysisKey, ProgramBuilder},
+    program::{
+        Linker, LinkerMap, MappedFunction, MappedGlobal, MappedSegment, Program,
+        ProgramAnalysisKey, ProgramBuilder,
+    },
     segments::{DataSegment, DataSegmentAdapter, DataSegmentError, DataSegmentTable},
     value::{Value, ValueData, ValueList, ValueListPool},
 };
@@ -82,6 +82,19 @@ pub struct Module {
     pub(crate) segments: DataSegmentTable,
     /// The set of global variables declared in this module
     pub(crate) globals: GlobalVariableTable,
+    /// The messages associated with the error codes of assertions in this module
+    pub(crate) error_codes: ErrorCodeTable,
+    /// The signatures of exported functions as seen by their callers, where that differs from the
+    /// signature of the function itself.
+    ///
+    /// For example, a function lifted by a component export is called with the flattened core
+    /// types of its parameters, but is described by the component-level types of the interface.
+    pub(crate) export_signatures: BTreeMap<Ident, Signature>,
+    /// The account-component metadata declared by this module, as TOML, if any.
+    ///
+    /// This is opaque to the IR: it is carried through to the package produced from the program,
+    /// where it is parsed and validated.
+    pub(crate) account_component: Option<String>,
     /// The set of functions which belong to this module, in the order
     /// in which they were defined.
     pub(crate) functions: LinkedList<FunctionListAdapter>,
@@ -202,6 +215,9 @@ impl fmt::Debug for Module {
             .field("docs", &self.docs)
             .field("segments", &self.segments)
             .field("globals", &self.globals)
+            .field("error_codes", &self.error_codes)
+            .field("export_signatures", &self.export_signatures)
+            .field("account_component", &self.account_component)
             .field("functions", &self.functions)
             .finish()
     }
@@ -238,6 +254,9 @@ impl PartialEq for Module {
             && self.page_size == other.page_size
             && self.docs == other.docs
             && self.segments.iter().eq(other.segments.iter())
+            && self.error_codes == other.error_codes
+            && self.export_signatures == other.export_signatures
+            && self.account_component == other.account_component
             && self.globals.len() == other.globals.len()
             && self.functions.iter().count() == other.functions.iter().count();
         if !is_eq {
@@ -325,6 +344,9 @@ impl Module {
             page_size: 64 * 1024,
             segments: Default::default(),
             globals: GlobalVariableTable::new(ConflictResolutionStrategy::None),
+            error_codes: Default::default(),
+            export_signatures: Default::default(),
+            account_component: None,
             functions: Default::default(),
             is_kernel,
         }
@@ -392,6 +414,37 @@ impl Module {
         self.segments.declare(offset, size, init, readonly)
     }
 
+    /// Return the table of error code messages for this module
+    pub fn error_codes(&self) -> &ErrorCodeTable {
+        &self.error_codes
+    }
+
+    /// Return a mutable reference to the table of error code messages for this module
+    pub fn error_codes_mut(&mut self) -> &mut ErrorCodeTable {
+        &mut self.error_codes
+    }
+
+    /// Return the account-component metadata declared by this module, as TOML, if any
+    pub fn account_component(&self) -> Option<&str> {
+        self.account_component.as_deref()
+    }
+
+    /// Return the signature of the exported function `name` as seen by its callers, if it differs
+    /// from the signature of the function itself
+    pub fn export_signature(&self, name: Ident) -> Option<&Signature> {
+        self.export_signatures.get(&name)
+    }
+
+    /// Return the table of export signatures for this module, see [Module::export_signature]
+    pub fn export_signatures(&self) -> &BTreeMap<Ident, Signature> {
+        &self.export_signatures
+    }
+
+    /// Set the signature of the exported function `name` as seen by its callers
+    pub fn set_export_signature(&mut self, name: Ident, signature: Signature) {
+        self.export_signatures.insert(name, signature);
+    }
+
     /// Return the table of global variables for this module
     pub fn globals(&self) -> &GlobalVariableTable {
         &self.globals
@@ -731,6 +784,16 @@ impl ModuleBuilder {
         self
     }
 
+    pub fn with_error_codes(&mut self, error_codes: ErrorCodeTable) -> &mut Self {
+        self.module.error_codes = error_codes;
+        self
+    }
+
+    pub fn with_account_component<S: Into<String>>(&mut self, metadata: S) -> &mut Self {
+        self.module.account_component = Some(metadata.into());
+        self
+    }
+
     pub fn with_reserved_memory_pages(&mut self, num_pages: u32) -> &mut Self {
         self.module.reserved_memory_pages = num_pages;
         self
@@ -795,6 +858,17 @@ impl ModuleBuilder {
         })
     }
 
+    /// Set the type signature of the function `name` exported from this module, as seen by callers
+    /// in other components, see [Module::set_export_signature]
+    pub fn with_export_signature<S: Into<Ident>>(
+        &mut self,
+        name: S,
+        signature: Signature,
+    ) -> &mut Self {
+        self.module.set_export_signature(name.into(), signature);
+        self
+    }
+
     pub fn build(self) -> Box<Module> {
         self.module
     }
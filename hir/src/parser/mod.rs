@@ -28,7 +28,7 @@ use self::{
     ast::ConvertAstToHir,
     lexer::{Lexed, Lexer},
 };
-use crate::diagnostics::{Report, SourceFile, SourceManagerExt};
+use crate::diagnostics::{Report, SourceFile, SourceId, SourceManagerExt};
 
 pub type ParseResult<T> = Result<T, Report>;
 
@@ -143,3 +143,26 @@ impl Parse for crate::Module {
         }
     }
 }
+impl core::str::FromStr for crate::Signature {
+    type Err = Report;
+
+    /// Parse a standalone function type, e.g. `(func (param i32) (result i32))`
+    ///
+    /// The resulting signature has external linkage.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = Arc::new(SourceFile::new(SourceId::UNKNOWN, "signature", s));
+        let lexer = Lexer::new(source.id(), source.as_str());
+        let mut next_var = 0;
+        let result = grammar::SignatureParser::new().parse(source.id(), &mut next_var, lexer);
+        match result {
+            Ok(signature) => Ok(signature),
+            Err(lalrpop_util::ParseError::User { error }) => {
+                Err(Report::from(error).with_source_code(source))
+            }
+            Err(err) => {
+                let error = ParseError::from(err);
+                Err(Report::from(error).with_source_code(source))
+            }
+        }
+    }
+}
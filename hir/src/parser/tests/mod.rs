@@ -2,6 +2,7 @@ use pretty_assertions::assert_eq;
 
 use crate::{
     diagnostics::{SourceSpan, Span},
+    formatter::PrettyPrint,
     parser::ast::*,
     AbiParam, ArgumentExtension, ArgumentPurpose, CallConv, ExternalFunction, FunctionIdent, Ident,
     Linkage, Opcode, Overflow, Signature, StructType, Type,
@@ -167,6 +168,37 @@ fn parser_integration_test_roundtrip() {
     assert_eq!(formatted, expected);
 }
 
+#[test]
+fn parser_signature_test() {
+    let signature: Signature = "(func (cc kernel) (param (zext) u8) (param felt) (result u32) \
+                                (result i1))"
+        .parse()
+        .expect("parsing failed");
+    let expected = Signature {
+        params: vec![
+            AbiParam {
+                ty: Type::U8,
+                purpose: ArgumentPurpose::Default,
+                extension: ArgumentExtension::Zext,
+            },
+            AbiParam::new(Type::Felt),
+        ],
+        results: vec![AbiParam::new(Type::U32), AbiParam::new(Type::I1)],
+        cc: CallConv::Kernel,
+        linkage: Linkage::External,
+    };
+    assert_eq!(signature, expected);
+
+    // Signatures must round-trip through their textual representation
+    let printed = format!("(func {})", signature.to_pretty_string());
+    assert_eq!(printed.parse::<Signature>().expect("parsing failed"), expected);
+
+    let empty: Signature = "(func)".parse().expect("parsing failed");
+    assert_eq!(empty, Signature::new([], []));
+
+    assert!("(func (result i32) (param i32))".parse::<Signature>().is_err());
+}
+
 /// Round-trip an IR module through the textual format and assert that we get back the same module
 #[allow(unused)]
 fn roundtrip(module: &crate::Module) {
@@ -25,7 +25,12 @@ pub enum Object {
     /// The object is an HIR module
     Hir(Box<Module>),
     /// The object is a compiled Miden Assembly module
-    Masm { name: Ident, exports: Vec<Ident> },
+    Masm {
+        name: Ident,
+        exports: Vec<Ident>,
+        /// The signatures of the exports of this module, for those that are known
+        signatures: BTreeMap<Ident, Signature>,
+    },
 }
 impl Object {
     /// Return the identifier associated with this object
@@ -40,7 +45,9 @@ impl Object {
     pub fn exports(&self) -> Box<(dyn Iterator<Item = FunctionIdent> + '_)> {
         match self {
             Self::Hir(module) => Box::new(module.functions().map(|f| f.id)),
-            Self::Masm { name, ref exports } => {
+            Self::Masm {
+                name, ref exports, ..
+            } => {
                 let name = *name;
                 Box::new(exports.iter().copied().map(move |function| FunctionIdent {
                     module: name,
@@ -60,6 +67,7 @@ impl From<(Ident, Vec<Ident>)> for Object {
         Self::Masm {
             name: module.0,
             exports: module.1,
+            signatures: Default::default(),
         }
     }
 }
@@ -244,6 +252,21 @@ impl<'a> Linker<'a> {
         }
     }
 
+    /// Add a data segment to the program being linked, which was laid out by a separately compiled
+    /// object, e.g. the rodata of a package being linked against.
+    ///
+    /// Returns a [Report] if the segment conflicts with one that was previously added.
+    pub fn add_data_segment(
+        &mut self,
+        offset: Offset,
+        size: u32,
+        init: ConstantData,
+        readonly: bool,
+    ) -> Result<(), Report> {
+        self.program.segments.declare(offset, size, init, readonly)?;
+        Ok(())
+    }
+
     /// Add an object to link as part of the resulting [Program].
     ///
     /// There are different types of objects, see [Object] for details.
@@ -297,6 +320,8 @@ impl<'a> Linker<'a> {
     /// * Multiple modules with the same name
     /// * Conflicting data segment declarations
     /// * Conflicting global variable declarations
+    /// * Conflicting error codes
+    /// * Account-component metadata declared by more than one module
     /// * Recursion in the local call graph of the module (global analysis comes later)
     ///
     /// If any of the above errors occurs, a [Report] is returned.
@@ -325,6 +350,26 @@ impl<'a> Linker<'a> {
             self.program.segments.insert(segment)?;
         }
 
+        // Import all error codes
+        self.program.error_codes.merge(&module.error_codes)?;
+
+        // Import the account-component metadata, of which a program may only have one
+        if let Some(metadata) = module.account_component.take() {
+            if self.program.account_component.is_some() {
+                return Err(self
+                    .diagnostics
+                    .diagnostic(Severity::Error)
+                    .with_message("linker error")
+                    .with_primary_label(
+                        id.span,
+                        "this module declares account-component metadata, but another module in \
+                         the program already did",
+                    )
+                    .into_report());
+            }
+            self.program.account_component = Some(metadata);
+        }
+
         // Import all globals, and in the process:
         //
         // * Record all global variable definitions in the dependency graph
@@ -522,7 +567,11 @@ impl<'a> Linker<'a> {
                             .into_report())
                     }
                 },
-                Object::Masm { ref exports, .. } => {
+                Object::Masm {
+                    ref exports,
+                    ref signatures,
+                    ..
+                } => {
                     if !exports.contains(&node.function) && !is_allowed_missing {
                         return Err(self
                             .diagnostics
@@ -530,7 +579,7 @@ impl<'a> Linker<'a> {
                             .with_message(format!("linker error: undefined function '{}'", &node))
                             .into_report());
                     }
-                    (true, None)
+                    (true, signatures.get(&node.function))
                 }
             };
 
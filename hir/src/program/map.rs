@@ -0,0 +1,208 @@
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use miden_core::crypto::hash::RpoDigest;
+
+use crate::{formatter::DisplayHex, *};
+
+/// A [LinkerMap] describes where the linker placed each item of a [Program]: the address of every
+/// global variable, the range of every data segment, the base addresses of the heap and stack, and
+/// every function, along with its linkage and MAST root.
+///
+/// It is produced from a linked [Program], and is emitted for [midenc_session::OutputType::Map].
+/// The MAST roots of functions are not known until the program is lowered to MAST, so they must
+/// be provided separately, see [LinkerMap::set_digests].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkerMap {
+    /// The base address of the dynamic heap
+    pub heap_base: u32,
+    /// The address at which the shadow stack starts, i.e. the initial value of the
+    /// `__stack_pointer` global, if the program has one
+    pub stack_base: Option<u32>,
+    /// The data segments of the program, in address order
+    pub segments: Vec<MappedSegment>,
+    /// The global variables of the program, in address order
+    pub globals: Vec<MappedGlobal>,
+    /// The functions defined in the program, ordered by module, then by name
+    pub functions: Vec<MappedFunction>,
+}
+
+/// A data segment of a [LinkerMap]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedSegment {
+    /// The address at which the segment starts
+    pub start: u32,
+    /// The size of the segment, in bytes
+    pub size: u32,
+    /// Whether the segment is read-only
+    pub readonly: bool,
+}
+impl MappedSegment {
+    /// The address following the last byte of the segment
+    pub fn end(&self) -> u32 {
+        self.start + self.size
+    }
+}
+
+/// A global variable of a [LinkerMap]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedGlobal {
+    pub name: Ident,
+    pub ty: Type,
+    pub linkage: Linkage,
+    /// The address at which the global variable is allocated
+    pub addr: u32,
+    /// The size of the global variable, in bytes
+    pub size: u32,
+}
+
+/// A function of a [LinkerMap]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedFunction {
+    pub id: FunctionIdent,
+    pub linkage: Linkage,
+    /// The MAST root of the function, if known
+    pub digest: Option<RpoDigest>,
+}
+
+impl LinkerMap {
+    /// Describe the layout of the linked `program`
+    pub fn new(program: &Program) -> Self {
+        let segments = program
+            .segments()
+            .iter()
+            .map(|segment| MappedSegment {
+                start: segment.offset(),
+                size: segment.size(),
+                readonly: segment.is_readonly(),
+            })
+            .collect();
+
+        let table = program.globals();
+        let global_table_offset = program.global_table_offset();
+        let globals = table
+            .iter()
+            .map(|global| MappedGlobal {
+                name: global.name,
+                ty: global.ty.clone(),
+                linkage: global.linkage,
+                // SAFETY: The program has been linked, so the layout of the table is final
+                addr: global_table_offset + unsafe { table.offset_of(global.id()) },
+                size: global.layout().size() as u32,
+            })
+            .collect();
+
+        let stack_base = table
+            .iter()
+            .find(|global| global.name.as_str() == "__stack_pointer")
+            .and_then(|global| global.initializer())
+            .and_then(|init| <[u8; 4]>::try_from(table.get_constant(init).as_slice()).ok())
+            .map(u32::from_le_bytes);
+
+        let mut functions = program
+            .modules()
+            .iter()
+            .flat_map(|module| module.functions())
+            .map(|function| MappedFunction {
+                id: function.id,
+                linkage: function.signature.linkage,
+                digest: None,
+            })
+            .collect::<Vec<_>>();
+        functions.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Self {
+            heap_base: program.heap_base(),
+            stack_base,
+            segments,
+            globals,
+            functions,
+        }
+    }
+
+    /// Record the MAST roots of the functions in this map, as given by `digests`
+    pub fn set_digests(&mut self, digests: &BTreeMap<FunctionIdent, RpoDigest>) {
+        for function in self.functions.iter_mut() {
+            function.digest = digests.get(&function.id).copied();
+        }
+    }
+}
+
+impl fmt::Display for LinkerMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "heap base:  {:#010x}", self.heap_base)?;
+        if let Some(stack_base) = self.stack_base {
+            writeln!(f, "stack base: {stack_base:#010x}")?;
+        }
+
+        if !self.segments.is_empty() {
+            writeln!(f, "\ndata segments:")?;
+            for segment in self.segments.iter() {
+                write!(
+                    f,
+                    "  {:#010x}..{:#010x}  {:>8} bytes",
+                    segment.start,
+                    segment.end(),
+                    segment.size
+                )?;
+                if segment.readonly {
+                    writeln!(f, "  readonly")?;
+                } else {
+                    writeln!(f)?;
+                }
+            }
+        }
+
+        if !self.globals.is_empty() {
+            writeln!(f, "\nglobals:")?;
+            for global in self.globals.iter() {
+                writeln!(
+                    f,
+                    "  {:#010x}  {:>8} bytes  {:<8}  {} : {}",
+                    global.addr,
+                    global.size,
+                    global.linkage,
+                    global.name.as_str(),
+                    &global.ty
+                )?;
+            }
+        }
+
+        if !self.functions.is_empty() {
+            writeln!(f, "\nfunctions:")?;
+            for function in self.functions.iter() {
+                let digest = match function.digest {
+                    Some(digest) => format!("{:#x}", DisplayHex::new(&digest.as_bytes())),
+                    None => "<not lowered>".to_string(),
+                };
+                writeln!(f, "  {:<8}  {digest}  {}", function.linkage, function.id.display())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl midenc_session::Emit for LinkerMap {
+    fn name(&self) -> Option<Symbol> {
+        None
+    }
+
+    fn output_type(&self, _mode: midenc_session::OutputMode) -> midenc_session::OutputType {
+        midenc_session::OutputType::Map
+    }
+
+    fn write_to<W: std::io::Write>(
+        &self,
+        mut writer: W,
+        mode: midenc_session::OutputMode,
+        _session: &midenc_session::Session,
+    ) -> std::io::Result<()> {
+        assert_eq!(
+            mode,
+            midenc_session::OutputMode::Text,
+            "binary mode is not supported for linker maps"
+        );
+        write!(writer, "{self}")
+    }
+}
@@ -1,4 +1,5 @@
 mod linker;
+mod map;
 
 use alloc::collections::BTreeMap;
 use core::ops::{Deref, DerefMut};
@@ -7,7 +8,11 @@ use intrusive_collections::RBTree;
 use miden_assembly::Library as CompiledLibrary;
 use miden_core::crypto::hash::RpoDigest;
 
-pub use self::linker::Linker;
+use self::linker::Object;
+pub use self::{
+    linker::Linker,
+    map::{LinkerMap, MappedFunction, MappedGlobal, MappedSegment},
+};
 use crate::{
     diagnostics::{DiagnosticsHandler, Report},
     *,
@@ -56,6 +61,11 @@ pub struct Program {
     /// modules in this program. The layout of this table corresponds to the layout of
     /// global variables in the linear memory heap at runtime.
     globals: GlobalVariableTable,
+    /// The error code table produced by merging the error code tables of all modules in this
+    /// program.
+    error_codes: ErrorCodeTable,
+    /// The account-component metadata declared by one of the modules in this program, as TOML.
+    account_component: Option<String>,
 }
 
 impl Default for Program {
@@ -68,6 +78,8 @@ impl Default for Program {
             entrypoint: Default::default(),
             segments: Default::default(),
             globals: Default::default(),
+            error_codes: Default::default(),
+            account_component: None,
         }
     }
 }
@@ -97,6 +109,23 @@ impl Program {
         self.reserved_memory_pages * self.page_size
     }
 
+    /// Get the address at which the global variable table of this program is allocated, i.e.
+    /// following both the reserved linear memory region, and the data segments of the program
+    pub fn global_table_offset(&self) -> u32 {
+        core::cmp::max(
+            self.reserved_memory_bytes().next_multiple_of(32),
+            self.segments.next_available_offset(),
+        )
+    }
+
+    /// Get the base address of the dynamic heap of this program, i.e. the next page boundary
+    /// following both the reserved linear memory region, and the global variables of the program
+    pub fn heap_base(&self) -> u32 {
+        self.reserved_memory_bytes()
+            + u32::try_from(self.globals.size_in_bytes().next_multiple_of(self.page_size as usize))
+                .expect("unable to allocate dynamic heap: global table too large")
+    }
+
     /// Add to the set of libraries this [Program] will be assembled with
     pub fn add_library(&mut self, lib: CompiledLibrary) {
         self.libraries.insert(*lib.digest(), lib);
@@ -155,6 +184,16 @@ impl Program {
         &mut self.globals
     }
 
+    /// Get a reference to the error code table for this program
+    pub fn error_codes(&self) -> &ErrorCodeTable {
+        &self.error_codes
+    }
+
+    /// Get the account-component metadata declared by this program, as TOML, if any
+    pub fn account_component(&self) -> Option<&str> {
+        self.account_component.as_deref()
+    }
+
     /// Returns true if `name` is defined in this program.
     pub fn contains(&self, name: Ident) -> bool {
         !self.modules.find(&name).is_null()
@@ -186,7 +225,9 @@ pub struct ProgramBuilder<'a> {
     /// The set of HIR modules to link into the program
     modules: BTreeMap<Ident, Box<Module>>,
     /// The set of modules defined externally, which will be linked during assembly
-    extern_modules: BTreeMap<Ident, Vec<Ident>>,
+    extern_modules: BTreeMap<Ident, BTreeMap<Ident, Option<Signature>>>,
+    /// The set of data segments laid out by externally defined modules
+    extern_segments: Vec<(Offset, u32, ConstantData, bool)>,
     /// The set of libraries we're linking against
     libraries: BTreeMap<RpoDigest, CompiledLibrary>,
     entry: Option<FunctionIdent>,
@@ -199,6 +240,7 @@ impl<'a> ProgramBuilder<'a> {
         Self {
             modules: Default::default(),
             extern_modules: Default::default(),
+            extern_segments: Default::default(),
             libraries: Default::default(),
             entry: None,
             page_size: 64 * 1024,
@@ -253,6 +295,24 @@ impl<'a> ProgramBuilder<'a> {
     ) -> Result<(), ModuleConflictError>
     where
         E: IntoIterator<Item = Ident>,
+    {
+        self.add_extern_module_with_signatures(
+            module,
+            exports.into_iter().map(|export| (export, None)),
+        )
+    }
+
+    /// Like `add_extern_module`, but also makes the linker aware of the signatures of the exports
+    /// of `module`, where known, so that references to them can be type checked.
+    ///
+    /// Returns `Err` if a module with the same name already exists
+    pub fn add_extern_module_with_signatures<E>(
+        &mut self,
+        module: Ident,
+        exports: E,
+    ) -> Result<(), ModuleConflictError>
+    where
+        E: IntoIterator<Item = (Ident, Option<Signature>)>,
     {
         if self.modules.contains_key(&module) || self.extern_modules.contains_key(&module) {
             return Err(ModuleConflictError::new(module));
@@ -263,6 +323,19 @@ impl<'a> ProgramBuilder<'a> {
         Ok(())
     }
 
+    /// Make the linker aware of a data segment laid out by externally defined modules, e.g. the
+    /// rodata of a package being linked against, so that it is initialized by the [Program], and
+    /// is not overlapped by the data of any other module.
+    pub fn add_extern_data_segment(
+        &mut self,
+        offset: Offset,
+        size: u32,
+        init: ConstantData,
+        readonly: bool,
+    ) {
+        self.extern_segments.push((offset, size, init, readonly));
+    }
+
     /// Make the linker aware of the objects contained in the given library.
     ///
     /// Duplicate libraries/objects are ignored.
@@ -299,7 +372,21 @@ impl<'a> ProgramBuilder<'a> {
 
         linker.add_libraries(self.libraries.into_values());
 
-        self.extern_modules.into_iter().try_for_each(|obj| linker.add_object(obj))?;
+        for (offset, size, init, readonly) in self.extern_segments {
+            linker.add_data_segment(offset, size, init, readonly)?;
+        }
+
+        for (name, exports) in self.extern_modules {
+            let signatures = exports
+                .iter()
+                .filter_map(|(export, signature)| Some((*export, signature.clone()?)))
+                .collect();
+            linker.add_object(Object::Masm {
+                name,
+                exports: exports.into_keys().collect(),
+                signatures,
+            })?;
+        }
         self.modules.into_values().try_for_each(|obj| linker.add_object(obj))?;
 
         linker.link()
@@ -1,3 +1,7 @@
+use alloc::collections::BTreeMap;
+
+use miden_core::crypto::hash::RpoDigest;
+
 use super::{testing::TestContext, *};
 
 /// Test that we can construct a basic module and function and validate it
@@ -128,6 +132,37 @@ fn inline_asm_builders_test() {
     builder.build();
 }
 
+/// Test that `eqw` leaves both of the words it compares on the operand stack
+#[test]
+fn inline_asm_eqw_test() {
+    let context = TestContext::default();
+
+    let mut builder = ModuleBuilder::new("test");
+    let sig = Signature {
+        params: vec![],
+        results: vec![AbiParam::new(Type::I1)],
+        cc: CallConv::SystemV,
+        linkage: Linkage::External,
+    };
+    let mut fb = builder.function("words_equal", sig).expect("unexpected symbol conflict");
+
+    let span = SourceSpan::UNKNOWN;
+    let mut asm_builder = fb.ins().inline_asm(&[], [Type::I1], span);
+    asm_builder.ins().pushw([Felt::ONE; 4], span); // [A]
+    asm_builder.ins().pushw([Felt::ONE; 4], span); // [B, A]
+    asm_builder.ins().eqw(span); // [A == B, B, A]
+    asm_builder.ins().movdn(8, span); // [B, A, A == B]
+    asm_builder.ins().dropw(span); // [A, A == B]
+    asm_builder.ins().dropw(span); // [A == B]
+    let asm = asm_builder.build();
+    let equal = fb.data_flow_graph().first_result(asm);
+    fb.ins().ret(Some(equal), span);
+
+    fb.build(&context.session.diagnostics)
+        .expect("unexpected validation error, see diagnostics output");
+    builder.build();
+}
+
 /// Test that we can construct and link a set of modules correctly
 #[test]
 fn linker_test() {
@@ -142,3 +177,173 @@ fn linker_test() {
         .link()
         .expect("failed to link program");
 }
+
+/// Test that global variables are laid out at addresses aligned for their type
+#[test]
+fn global_variable_layout_test() {
+    let mut globals = GlobalVariableTable::default();
+    let mut declare = |name: &str, ty: Type| {
+        globals
+            .declare(Ident::with_empty_span(Symbol::intern(name)), ty, Linkage::Internal, None)
+            .expect("unexpected global variable error")
+    };
+    let a = declare("a", Type::U8);
+    let b = declare("b", Type::U32);
+    let c = declare("c", Type::U8);
+    let d = declare("d", Type::U16);
+
+    // SAFETY: The layout of the table is not modified after this point
+    let offsets = unsafe {
+        [
+            globals.offset_of(a),
+            globals.offset_of(b),
+            globals.offset_of(c),
+            globals.offset_of(d),
+        ]
+    };
+    assert_eq!(offsets, [0, 4, 8, 10]);
+    assert_eq!(globals.size_in_bytes(), 12);
+}
+
+/// Test that the linker map describes where each item of a linked program was placed
+#[test]
+fn linker_map_test() {
+    let context = TestContext::default();
+    let span = SourceSpan::UNKNOWN;
+
+    let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+    {
+        let mut mb = builder.module("test");
+        mb.declare_data_segment(0x100000, 8, vec![1; 8], true)
+            .expect("unexpected data segment error");
+        let stack_base = ConstantData::from(0x100000u32.to_le_bytes());
+        mb.declare_global_variable(
+            "__stack_pointer",
+            Type::I32,
+            Linkage::External,
+            Some(stack_base),
+            span,
+        )
+        .expect("unexpected global variable error");
+        mb.declare_global_variable("COUNTER", Type::U64, Linkage::External, None, span)
+            .expect("unexpected global variable error");
+        let mut fb =
+            mb.function("main", Signature::new([], [])).expect("unexpected symbol conflict");
+        fb.ins().load_symbol_relative("__stack_pointer", Type::I32, 0, span);
+        fb.ins().load_symbol_relative("COUNTER", Type::U64, 0, span);
+        fb.ins().ret(None, span);
+        fb.build().expect("unexpected error building function");
+        mb.build().expect("unexpected error building module");
+    }
+    let program = builder
+        .with_entrypoint("test::main".parse().unwrap())
+        .link()
+        .expect("failed to link program");
+
+    let mut map = LinkerMap::new(&program);
+    assert_eq!(
+        map.segments,
+        [MappedSegment {
+            start: 0x100000,
+            size: 8,
+            readonly: true,
+        }]
+    );
+    assert_eq!(map.heap_base, program.heap_base());
+    assert_eq!(map.stack_base, Some(0x100000));
+
+    // Global variables are allocated following the data segments, in declaration order
+    assert_eq!(program.global_table_offset(), 0x100020);
+    let globals = map
+        .globals
+        .iter()
+        .map(|global| (global.name.as_str(), global.addr, global.size))
+        .collect::<Vec<_>>();
+    assert_eq!(globals, [("__stack_pointer", 0x100020, 4), ("COUNTER", 0x100024, 8)]);
+
+    // Functions are listed with their MAST roots, once known
+    let main: FunctionIdent = "test::main".parse().unwrap();
+    assert_eq!(map.functions.len(), 1);
+    assert_eq!(map.functions[0].id, main);
+    assert_eq!(map.functions[0].linkage, Linkage::External);
+    assert_eq!(map.functions[0].digest, None);
+    map.set_digests(&BTreeMap::from([(main, RpoDigest::default())]));
+    assert_eq!(map.functions[0].digest, Some(RpoDigest::default()));
+
+    let rendered = map.to_string();
+    assert!(
+        rendered.contains("0x00100000..0x00100008         8 bytes  readonly"),
+        "{rendered}"
+    );
+    assert!(
+        rendered.contains("0x00100024         8 bytes  external  COUNTER : u64"),
+        "{rendered}"
+    );
+}
+
+/// Test that references to an externally-defined module are checked against the signatures of its
+/// exports, when they are known
+#[test]
+fn linker_extern_signatures_test() {
+    let context = TestContext::default();
+    let span = SourceSpan::UNKNOWN;
+    let sig = Signature::new(
+        [AbiParam::new(Type::U32), AbiParam::new(Type::U32)],
+        [AbiParam::new(Type::U32)],
+    );
+
+    let link_with_import = |import: Signature| {
+        let mut builder = ProgramBuilder::new(&context.session.diagnostics);
+        builder
+            .add_extern_module_with_signatures("dep".into(), [("add".into(), Some(sig.clone()))])
+            .expect("unexpected module conflict");
+        {
+            let mut mb = builder.module("test");
+            let mut fb = mb
+                .function("main", Signature::new([], [AbiParam::new(Type::U32)]))
+                .expect("unexpected symbol conflict");
+            let params = import.params.iter().map(|_| fb.ins().u32(1, span)).collect::<Vec<_>>();
+            let add = fb.import_function("dep", "add", import).expect("unexpected import conflict");
+            let call = fb.ins().call(add, &params, span);
+            let result = fb.first_result(call);
+            fb.ins().ret(Some(result), span);
+            fb.build().expect("unexpected error building function");
+            mb.build().expect("unexpected error building module");
+        }
+        builder.link()
+    };
+
+    assert!(link_with_import(sig.clone()).is_ok(), "failed to link program");
+    assert!(
+        link_with_import(Signature::new([AbiParam::new(Type::U32)], [AbiParam::new(Type::U32)]))
+            .is_err(),
+        "expected signature mismatch to be rejected"
+    );
+}
+
+#[test]
+fn error_code_table_test() {
+    let mut a = ErrorCodeTable::default();
+    let divide = a.intern("panicked: attempt to divide by zero").unwrap();
+    assert_eq!(divide & ErrorCodeTable::ERROR_CODE_BIT, ErrorCodeTable::ERROR_CODE_BIT);
+    assert_eq!(a.intern("panicked: attempt to divide by zero").unwrap(), divide);
+    assert_eq!(a.get(divide), Some("panicked: attempt to divide by zero"));
+
+    // Codes are independent of the table they were assigned in, so tables can be merged
+    let mut b = ErrorCodeTable::default();
+    let overflow = b.intern("panicked: attempt to add with overflow").unwrap();
+    assert_eq!(b.intern("panicked: attempt to divide by zero").unwrap(), divide);
+    a.merge(&b).expect("unexpected conflict");
+    assert_eq!(a.len(), 2);
+    assert_eq!(a.get(overflow), Some("panicked: attempt to add with overflow"));
+
+    // These two messages hash to the same code, which must be reported rather than renumbered
+    assert_eq!(ErrorCodeTable::code("message 43223"), ErrorCodeTable::code("message 119605"));
+    let mut c = ErrorCodeTable::default();
+    let code = c.intern("message 43223").unwrap();
+    assert!(c.intern("message 119605").is_err());
+    let mut d = ErrorCodeTable::default();
+    d.intern("message 119605").unwrap();
+    let err = a.merge(&c).and_then(|_| a.merge(&d)).expect_err("expected conflict");
+    assert_eq!(err.code, code);
+}
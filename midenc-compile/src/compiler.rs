@@ -37,6 +37,18 @@ pub struct Compiler {
         help_heading = "Compiler"
     )]
     pub sysroot: Option<PathBuf>,
+    /// The path to the root directory of the local package registry
+    ///
+    /// Packages requested by version, e.g. `-l foo@^1.2`, are resolved using this registry.
+    ///
+    /// By default this is assumed to be ~/.miden/registry
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "MIDENC_REGISTRY",
+        help_heading = "Linker"
+    )]
+    pub registry: Option<PathBuf>,
     /// Write compiled output to compiler-chosen filename in `<dir>`
     #[arg(
         long,
@@ -64,6 +76,12 @@ pub struct Compiler {
         help_heading = "Diagnostics"
     )]
     pub name: Option<String>,
+    /// Specify the version of the project being compiled
+    ///
+    /// This is recorded in the package produced by the compiler, and in turn, in the manifest of
+    /// any package which is linked against it.
+    #[arg(long, value_name = "VERSION", help_heading = "Output")]
+    pub package_version: Option<semver::Version>,
     /// Specify what type and level of informational output to emit
     #[arg(
         long = "verbose",
@@ -153,6 +171,9 @@ pub struct Compiler {
     ///
     /// The optional KIND can be provided to indicate what type of library it is.
     ///
+    /// The optional VERSION is a requirement, e.g. `^1.2`, used to link against the latest
+    /// matching version of a package published to the local package registry.
+    ///
     /// NAME must either be an absolute path (with extension when applicable), or
     /// a library namespace (no extension). The former will be used as the path
     /// to load the library, without looking for it in the library search paths,
@@ -162,7 +183,7 @@ pub struct Compiler {
     #[arg(
         long = "link-library",
         short = 'l',
-        value_name = "[KIND=]NAME",
+        value_name = "[KIND=]NAME[@VERSION]",
         value_delimiter = ',',
         default_value_ifs([
             ("target", "base", "std"),
@@ -212,6 +233,13 @@ pub struct Compiler {
         help_heading = "Output"
     )]
     pub opt_level: OptLevel,
+    /// Compile the inputs twice, and verify that both builds produce an identical package
+    ///
+    /// The second build is done by running midenc again with the same arguments, after which the
+    /// serialized packages of both builds are compared. This requires that midenc was invoked
+    /// directly, rather than as a library.
+    #[arg(long, default_value_t = false, help_heading = "Output")]
+    pub verify_reproducible: bool,
     /// Set a codegen option
     ///
     /// Use `-C help` to print available options
@@ -261,6 +289,11 @@ pub struct CodegenOptions {
     /// Tell the compiler to generate Miden Assembly from the inputs without linking them
     #[arg(long, default_value_t = false)]
     pub no_link: bool,
+    /// Lower f64 operations to calls into a software floating-point library
+    ///
+    /// Without this option, any use of f64 in the inputs is rejected
+    #[arg(long, default_value_t = false)]
+    pub soft_float: bool,
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -430,13 +463,19 @@ impl Compiler {
             .with_debug_info(self.debug)
             .with_optimization(self.opt_level)
             .with_output_types(output_types);
+        options.version = self.package_version;
         options.search_paths = self.search_path;
+        if let Some(registry) = self.registry {
+            options.registry = Some(registry);
+        }
         options.link_libraries = self.link_libraries;
         options.entrypoint = self.entrypoint;
         options.parse_only = codegen.parse_only;
         options.analyze_only = codegen.analyze_only;
         options.link_only = codegen.link_only;
         options.no_link = codegen.no_link;
+        options.soft_float = codegen.soft_float;
+        options.verify_reproducible = self.verify_reproducible;
         options.print_cfg_after_all = unstable.print_cfg_after_all;
         options.print_cfg_after_pass = unstable.print_cfg_after_pass;
         options.print_ir_after_all = unstable.print_ir_after_all;
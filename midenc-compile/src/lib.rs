@@ -8,9 +8,10 @@ use either::Either::{self, Left, Right};
 use midenc_codegen_masm::{self as masm, MasmArtifact};
 use midenc_hir::{
     diagnostics::{miette, Diagnostic, IntoDiagnostic, Report, WrapErr},
+    formatter::DisplayHex,
     pass::AnalysisManager,
 };
-use midenc_session::{OutputMode, Session};
+use midenc_session::{Emit, OutputMode, Session};
 
 pub use self::compiler::Compiler;
 use self::{stage::Stage, stages::*};
@@ -25,7 +26,6 @@ pub struct CompilerStopped;
 
 /// Run the compiler using the provided [Session]
 pub fn compile(session: Rc<Session>) -> CompilerResult<()> {
-    use midenc_hir::formatter::DisplayHex;
     let mut analyses = AnalysisManager::new();
     log::info!("starting compilation session");
     match compile_inputs(session.inputs.clone(), &mut analyses, &session)? {
@@ -35,6 +35,9 @@ pub fn compile(session: Rc<Session>) -> CompilerResult<()> {
                 mast.name,
                 DisplayHex::new(&mast.digest.as_bytes())
             );
+            if session.options.verify_reproducible {
+                verify_reproducible(mast, &session)?;
+            }
             session
                 .emit(OutputMode::Text, mast)
                 .into_diagnostic()
@@ -101,6 +104,179 @@ where
     stages.run(session.inputs.clone(), &mut analyses, &session)
 }
 
+/// Compile the inputs of `session` a second time, in a separate `midenc` process, and verify that
+/// the serialized package is identical to that of `package`, the result of the first compilation.
+///
+/// The second build is run with the same command-line arguments as this one, except for those
+/// which control where outputs are placed, so that it writes nothing but the package to compare.
+fn verify_reproducible(package: &masm::Package, session: &Session) -> CompilerResult<()> {
+    use std::{
+        ffi::OsString,
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    use midenc_session::InputType;
+
+    log::info!("verifying that package '{}' is reproducible", package.name);
+
+    let midenc = std::env::current_exe()
+        .into_diagnostic()
+        .wrap_err("failed to locate the compiler executable")?;
+    if midenc.file_stem() != Some(std::ffi::OsStr::new("midenc")) {
+        return Err(Report::msg(format!(
+            "cannot verify reproducibility: the compiler was not invoked via midenc, but via '{}'",
+            midenc.display()
+        )));
+    }
+
+    let out_dir = session.output_files.tmp_dir.join("verify-reproducible");
+    std::fs::create_dir_all(&out_dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to create output directory '{}'", out_dir.display()))?;
+    let rebuilt_path = out_dir.join(package.name.as_str()).with_extension("masp");
+    if rebuilt_path.exists() {
+        std::fs::remove_file(&rebuilt_path).into_diagnostic()?;
+    }
+
+    let mut args = rebuild_args(std::env::args_os().skip(1));
+    args.push(OsString::from("--name"));
+    args.push(OsString::from(session.name.as_str()));
+    args.push(OsString::from("--emit"));
+    let mut emit = OsString::from("masp=");
+    emit.push(&out_dir);
+    args.push(emit);
+
+    let stdin = session.inputs.iter().find_map(|input| match &input.file {
+        InputType::Stdin { input, .. } => Some(input.as_slice()),
+        InputType::Real(_) => None,
+    });
+    let mut child = Command::new(&midenc)
+        .args(&args)
+        .env_remove("MIDENC_OUT_DIR")
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .into_diagnostic()
+        .wrap_err("failed to start the compiler to rebuild the package")?;
+    if let Some(bytes) = stdin {
+        let mut child_stdin = child.stdin.take().unwrap();
+        child_stdin
+            .write_all(bytes)
+            .into_diagnostic()
+            .wrap_err("failed to pass input to the compiler rebuilding the package")?;
+    }
+    let output = child.wait_with_output().into_diagnostic()?;
+    if !output.status.success() {
+        return Err(Report::msg(format!(
+            "failed to rebuild package while verifying reproducibility ({}):\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    let rebuilt_bytes = std::fs::read(&rebuilt_path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read rebuilt package '{}'", rebuilt_path.display()))?;
+
+    let mut bytes = vec![];
+    package.write_to(&mut bytes, OutputMode::Binary, session).into_diagnostic()?;
+    if bytes == rebuilt_bytes {
+        log::info!("package '{}' is reproducible", package.name);
+        return Ok(());
+    }
+
+    // Describe how the packages differ, where we can
+    let rebuilt = masm::Package::read_from_bytes(rebuilt_bytes)
+        .wrap_err("failed to read rebuilt package while verifying reproducibility")?;
+    let mut differences = vec![];
+    if package.digest != rebuilt.digest {
+        differences.push(format!(
+            "MAST digest differs: {} vs {}",
+            DisplayHex::new(&package.digest.as_bytes()),
+            DisplayHex::new(&rebuilt.digest.as_bytes())
+        ));
+    }
+    let exports = package.manifest.exports.iter().map(|export| (export.id, export.digest));
+    let rebuilt_exports = rebuilt.manifest.exports.iter().map(|export| (export.id, export.digest));
+    if !exports.eq(rebuilt_exports) {
+        differences.push("exported procedures differ".to_string());
+    }
+    if package.rodata.len() != rebuilt.rodata.len() {
+        differences.push(format!(
+            "number of rodata segments differs: {} vs {}",
+            package.rodata.len(),
+            rebuilt.rodata.len()
+        ));
+    }
+    for (rodata, rebuilt_rodata) in package.rodata.iter().zip(rebuilt.rodata.iter()) {
+        if rodata != rebuilt_rodata {
+            differences.push(format!(
+                "rodata segment at {:#x} differs from the rebuilt segment at {:#x}",
+                rodata.start.as_ptr(),
+                rebuilt_rodata.start.as_ptr()
+            ));
+        }
+    }
+    if differences.is_empty() {
+        // Everything compared above matches, so the difference is in e.g. debug info
+        differences.push("serialized packages differ".to_string());
+    }
+
+    Err(Report::msg(format!(
+        "package '{}' is not reproducible (the rebuilt package is at '{}'):\n - {}",
+        package.name,
+        rebuilt_path.display(),
+        differences.join("\n - ")
+    )))
+}
+
+/// Derive the arguments for the `midenc` process which rebuilds a package in
+/// [verify_reproducible] from `args`, the arguments of this process, excluding the program name.
+///
+/// All arguments which control where outputs are placed, what outputs are emitted, or how they are
+/// named, are removed, as is `--verify-reproducible` itself.
+fn rebuild_args(args: impl IntoIterator<Item = std::ffi::OsString>) -> Vec<std::ffi::OsString> {
+    const FLAGS: &[&str] = &["--verify-reproducible", "--stdout"];
+    const LONG_OPTIONS: &[&str] = &["--output-file", "--output-dir", "--emit", "--name"];
+    const SHORT_OPTIONS: &[&str] = &["-o", "-O", "-n"];
+
+    let mut rebuild_args = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(arg_str) = arg.to_str() else {
+            rebuild_args.push(arg);
+            continue;
+        };
+        if arg_str == "--" {
+            rebuild_args.push(arg);
+            rebuild_args.extend(args);
+            break;
+        }
+        if FLAGS.contains(&arg_str) {
+            continue;
+        }
+        if LONG_OPTIONS.contains(&arg_str) || SHORT_OPTIONS.contains(&arg_str) {
+            // The value is the next argument
+            args.next();
+            continue;
+        }
+        let is_long_with_value = LONG_OPTIONS
+            .iter()
+            .any(|option| arg_str.strip_prefix(option).is_some_and(|value| value.starts_with('=')));
+        let is_short_with_value = SHORT_OPTIONS.iter().any(|option| arg_str.starts_with(option));
+        if is_long_with_value || is_short_with_value {
+            continue;
+        }
+        rebuild_args.push(arg);
+    }
+    rebuild_args
+}
+
 fn compile_inputs(
     inputs: Vec<midenc_session::InputFile>,
     analyses: &mut AnalysisManager,
@@ -46,7 +46,7 @@ impl Stage for AssembleStage {
                 );
                 session.emit(OutputMode::Text, &mast).into_diagnostic()?;
                 session.emit(OutputMode::Binary, &mast).into_diagnostic()?;
-                Ok(Artifact::Assembled(masm::Package::new(mast, &masm_artifact, session)))
+                masm::Package::new(mast, &masm_artifact, session).map(Artifact::Assembled)
             }
             Left(masm_artifact) => {
                 log::debug!(
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use midenc_session::OutputType;
 
 use super::*;
@@ -21,6 +23,7 @@ impl Stage for CodegenStage {
         let LinkerOutput {
             linked,
             masm: mut masm_modules,
+            map,
         } = linker_output;
         match linked {
             Left(program) => {
@@ -34,6 +37,11 @@ impl Stage for CodegenStage {
                     }
                 }
 
+                // Intrinsics are only linked in to resolve calls, so are not reported on
+                let mut reported =
+                    artifact.modules().map(|module| module.id).collect::<BTreeSet<_>>();
+                reported.extend(masm_modules.iter().map(|module| module.id));
+
                 // Ensure intrinsics modules are linked
                 for intrinsics_module in required_intrinsics_modules(session) {
                     log::debug!(
@@ -48,6 +56,20 @@ impl Stage for CodegenStage {
                     artifact.insert(module);
                 }
 
+                emit_cycle_report(
+                    artifact.modules(),
+                    |module| reported.contains(&module.id),
+                    session,
+                )?;
+
+                if let Some(mut map) = map {
+                    map.set_digests(&artifact.procedure_digests(session)?);
+                    session
+                        .emit(OutputMode::Text, &map)
+                        .into_diagnostic()
+                        .wrap_err("failed to emit 'map' output")?;
+                }
+
                 Ok(Left(artifact))
             }
             Right(ir) => {
@@ -64,19 +86,49 @@ impl Stage for CodegenStage {
                     masm_modules.insert(masm_module);
                 }
 
+                emit_cycle_report(masm_modules.iter(), |_| true, session)?;
+
                 Ok(Right(masm_modules))
             }
         }
     }
 }
 
+/// Emit the estimated cycle counts of the exported procedures in `modules`, if requested, as text,
+/// JSON, or both.
+fn emit_cycle_report<'a>(
+    modules: impl IntoIterator<Item = &'a masm::Module>,
+    report: impl Fn(&masm::Module) -> bool,
+    session: &Session,
+) -> CompilerResult<()> {
+    if !session.should_emit(OutputType::Cycles) && !session.should_emit(OutputType::CyclesJson) {
+        return Ok(());
+    }
+    let report = masm::CycleReport::new(modules, report);
+    session
+        .emit(OutputMode::Text, &report)
+        .into_diagnostic()
+        .wrap_err("failed to emit 'cycles' output")?;
+    session
+        .emit(OutputMode::Text, &report.as_json())
+        .into_diagnostic()
+        .wrap_err("failed to emit 'cycles-json' output")
+}
+
 fn required_intrinsics_modules(session: &Session) -> Vec<masm::Module> {
-    vec![
+    let mut modules = vec![
         masm::intrinsics::load("intrinsics::mem", &session.source_manager)
             .expect("undefined intrinsics module"),
         masm::intrinsics::load("intrinsics::i32", &session.source_manager)
             .expect("undefined intrinsics module"),
         masm::intrinsics::load("intrinsics::i64", &session.source_manager)
             .expect("undefined intrinsics module"),
-    ]
+    ];
+    if session.options.soft_float {
+        modules.push(
+            masm::intrinsics::load("intrinsics::f64", &session.source_manager)
+                .expect("undefined intrinsics module"),
+        );
+    }
+    modules
 }
@@ -1,3 +1,7 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use midenc_session::{LibraryKind, LinkLibrary, OutputType};
+
 use super::*;
 
 pub enum LinkerInput {
@@ -10,6 +14,8 @@ pub struct LinkerOutput {
     pub linked: Either<Box<hir::Program>, hir::ModuleList>,
     /// The set of MASM inputs to the linker
     pub masm: masm::ModuleTree,
+    /// The linker map of the linked program, if requested
+    pub map: Option<hir::LinkerMap>,
 }
 
 /// Link together one or more HIR modules into an HIR program
@@ -65,30 +71,143 @@ impl Stage for LinkerStage {
             }
 
             // Load link libraries now
+            let mut packages = BTreeSet::default();
             for link_lib in session.options.link_libraries.iter() {
-                log::debug!(
-                    "registering link library '{}' ({}, from {:#?}) with linker",
-                    link_lib.name,
-                    link_lib.kind,
-                    link_lib.path.as_ref()
-                );
-                builder.add_library(link_lib.load(session)?);
+                add_link_library(&mut builder, link_lib, &mut packages, session)?;
             }
 
-            let linked = Left(builder.link()?);
+            let program = builder.link()?;
+            // The MAST roots of the functions in the map are filled in once they are known, so it
+            // is not emitted until code generation, unless the program will not be lowered at all
+            let mut map =
+                session.should_emit(OutputType::Map).then(|| hir::LinkerMap::new(&program));
+            if !session.should_codegen() {
+                if let Some(map) = map.take() {
+                    session
+                        .emit(OutputMode::Text, &map)
+                        .into_diagnostic()
+                        .wrap_err("failed to emit 'map' output")?;
+                }
+            }
+            let linked = Left(program);
 
             if session.options.link_only {
                 log::debug!("stopping compiler early (link-only=true)");
                 Err(Report::from(CompilerStopped))
             } else {
-                Ok(LinkerOutput { linked, masm })
+                Ok(LinkerOutput { linked, masm, map })
             }
         } else {
             log::debug!("skipping hir linker (should-link=false)");
             Ok(LinkerOutput {
                 linked: Right(ir),
                 masm,
+                map: None,
             })
         }
     }
 }
+
+/// Register `link_lib` with the linker.
+///
+/// Packages are registered as externally-defined modules, using the exports in their manifest, so
+/// that references to them can be checked against their signatures. The rodata of the package, and
+/// the libraries it was linked against, are brought along with it.
+fn add_link_library(
+    builder: &mut hir::ProgramBuilder<'_>,
+    link_lib: &LinkLibrary,
+    packages: &mut BTreeSet<[u8; 32]>,
+    session: &Session,
+) -> Result<(), Report> {
+    log::debug!(
+        "registering link library '{}' ({}, from {:#?}) with linker",
+        link_lib.name,
+        link_lib.kind,
+        link_lib.path.as_ref()
+    );
+
+    match link_lib.kind {
+        LibraryKind::Package => {
+            let package = masm::Package::load(link_lib, session)?;
+            add_package(builder, &package, packages, session)
+        }
+        LibraryKind::Mast | LibraryKind::Masm => {
+            builder.add_library(link_lib.load(session)?);
+            Ok(())
+        }
+    }
+}
+
+/// Register `package` with the linker, along with its dependencies, which are verified to be the
+/// builds that `package` was linked against.
+///
+/// The digests of the packages registered so far are tracked in `packages`, so that a package
+/// which is depended upon more than once is only registered the first time.
+fn add_package(
+    builder: &mut hir::ProgramBuilder<'_>,
+    package: &masm::Package,
+    packages: &mut BTreeSet<[u8; 32]>,
+    session: &Session,
+) -> Result<(), Report> {
+    if !package.is_library() {
+        return Err(Report::msg(format!(
+            "unable to link against package '{}': it is an executable, not a library",
+            &package.name
+        )));
+    }
+    if !packages.insert(package.digest.as_bytes()) {
+        return Ok(());
+    }
+
+    builder.add_library(package.unwrap_library().as_ref().clone());
+
+    let mut modules = BTreeMap::<hir::Ident, Vec<_>>::default();
+    for export in package.manifest.exports.iter() {
+        // Every package includes the compiler intrinsics it uses, but the program being linked
+        // always gets its own, so registering them would conflict with those of other packages
+        if export.id.module.as_str().starts_with("intrinsics::") {
+            continue;
+        }
+        modules
+            .entry(export.id.module)
+            .or_default()
+            .push((export.id.function, export.signature.clone()));
+    }
+    for (module, exports) in modules {
+        log::debug!("adding external module '{module}' from package '{}'", &package.name);
+        builder.add_extern_module_with_signatures(module, exports)?;
+    }
+
+    // The rodata of a package includes the initial values of its global variables, which may be
+    // modified at runtime, so we cannot treat these segments as read-only
+    for rodata in package.rodata.iter() {
+        builder.add_extern_data_segment(
+            rodata.start.as_ptr(),
+            rodata.size_in_bytes() as u32,
+            rodata.data.as_ref().clone(),
+            false,
+        );
+    }
+
+    for dependency in package.manifest.dependencies.iter() {
+        log::debug!(
+            "registering dependency '{}' of package '{}' with linker",
+            dependency.name(),
+            &package.name
+        );
+        let invalid_dependency = || format!("invalid dependency of package '{}'", &package.name);
+        match dependency.library.kind {
+            LibraryKind::Package => {
+                let dependency =
+                    dependency.load_package(session).wrap_err_with(invalid_dependency)?;
+                add_package(builder, &dependency, packages, session)?;
+            }
+            LibraryKind::Mast | LibraryKind::Masm => {
+                let library = dependency.load_library(session).wrap_err_with(invalid_dependency)?;
+                builder.add_library(library);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -193,7 +193,8 @@ impl ParseStage {
         let span = ast.span();
 
         // Convert to MASM IR representation
-        Ok(ParseOutput::Masm(Box::new(masm::Module::from_ast(&ast, span))))
+        let module = masm::Module::from_ast(&ast, span)?;
+        Ok(ParseOutput::Masm(Box::new(module)))
     }
 
     fn parse_masm_from_bytes(
@@ -221,6 +222,7 @@ impl ParseStage {
         let span = ast.span();
 
         // Convert to MASM IR representation
-        Ok(ParseOutput::Masm(Box::new(masm::Module::from_ast(&ast, span))))
+        let module = masm::Module::from_ast(&ast, span)?;
+        Ok(ParseOutput::Masm(Box::new(module)))
     }
 }
@@ -25,6 +25,16 @@ pub struct Debugger {
         help_heading = "Compiler"
     )]
     pub sysroot: Option<PathBuf>,
+    /// The path to the root directory of the local package registry
+    ///
+    /// By default this is assumed to be ~/.miden/registry
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "MIDENC_REGISTRY",
+        help_heading = "Linker"
+    )]
+    pub registry: Option<PathBuf>,
     /// Whether, and how, to color terminal output
     #[arg(
         long,
@@ -123,6 +133,9 @@ impl Debugger {
         let mut options = Options::new(None, self.target, ProjectType::Program, cwd, self.sysroot)
             .with_color(color);
         options.search_paths = self.search_path;
+        if let Some(registry) = self.registry {
+            options.registry = Some(registry);
+        }
         options.link_libraries = self.link_libraries;
         options.entrypoint = self.entrypoint;
 
@@ -30,6 +30,7 @@ pub struct Executor {
     stack: StackInputs,
     advice: AdviceInputs,
     libraries: Vec<MastForest>,
+    error_codes: BTreeMap<u32, String>,
 }
 impl Executor {
     /// Construct an executor with the given arguments on the operand stack
@@ -38,6 +39,7 @@ impl Executor {
             stack: StackInputs::new(args).expect("invalid stack inputs"),
             advice: AdviceInputs::default(),
             libraries: Default::default(),
+            error_codes: Default::default(),
         }
     }
 
@@ -55,16 +57,18 @@ impl Executor {
 
         let mut exec = Self::new(args);
 
-        for link_library in package.manifest.link_libraries.iter() {
+        for dependency in package.manifest.dependencies.iter() {
+            let link_library = &dependency.library;
             log::debug!(
                 "loading link library from package manifest: {} (kind = {}, from = {:#?})",
                 link_library.name.as_ref(),
                 link_library.kind,
                 link_library.path.as_ref().map(|p| p.display())
             );
-            let library = link_library.load(session)?;
+            for library in dependency.load(session)? {
+                exec.with_library(&library);
+            }
             log::debug!("library loaded succesfully");
-            exec.with_library(&library);
         }
 
         for rodata in package.rodata.iter() {
@@ -78,6 +82,8 @@ impl Executor {
                 .extend_map([(rodata.digest, rodata.to_elements().map_err(Report::msg)?)]);
         }
 
+        exec.with_error_codes(package.manifest.error_codes.clone());
+
         log::debug!("executor created");
 
         Ok(exec)
@@ -95,6 +101,15 @@ impl Executor {
         self
     }
 
+    /// Add messages for the error codes of failed assertions, used to explain execution failures
+    pub fn with_error_codes<I>(&mut self, error_codes: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (u32, String)>,
+    {
+        self.error_codes.extend(error_codes);
+        self
+    }
+
     /// Convert this [Executor] into a [DebugExecutor], which captures much more information
     /// about the program being executed, and must be stepped manually.
     pub fn into_debug(mut self, program: &Program, session: &Session) -> DebugExecutor {
@@ -105,6 +120,7 @@ impl Executor {
         for lib in core::mem::take(&mut self.libraries) {
             host.load_mast_forest(lib);
         }
+        host.register_error_codes(core::mem::take(&mut self.error_codes));
 
         let trace_events: Rc<RefCell<BTreeMap<RowIndex, TraceEvent>>> = Rc::new(Default::default());
         let frame_start_events = Rc::clone(&trace_events);
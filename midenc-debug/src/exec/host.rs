@@ -17,6 +17,7 @@ pub struct DebuggerHost {
     store: MemMastForestStore,
     tracing_callbacks: BTreeMap<u32, Vec<Box<TraceHandler>>>,
     on_assert_failed: Option<Box<TraceHandler>>,
+    error_codes: BTreeMap<u32, String>,
 }
 impl DebuggerHost {
     /// Construct a new instance of [DebuggerHost] with the given advice provider.
@@ -26,6 +27,7 @@ impl DebuggerHost {
             store: Default::default(),
             tracing_callbacks: Default::default(),
             on_assert_failed: None,
+            error_codes: Default::default(),
         }
     }
 
@@ -49,6 +51,14 @@ impl DebuggerHost {
         self.on_assert_failed = Some(Box::new(callback));
     }
 
+    /// Register the messages associated with the error codes of failed assertions
+    pub fn register_error_codes<I>(&mut self, error_codes: I)
+    where
+        I: IntoIterator<Item = (u32, String)>,
+    {
+        self.error_codes.extend(error_codes);
+    }
+
     /// Load `forest` into the MAST store for this host
     pub fn load_mast_forest(&mut self, forest: MastForest) {
         self.store.insert(forest);
@@ -102,7 +112,10 @@ impl Host for DebuggerHost {
                  for that use"
                     .to_string(),
             ),
-            _ => None,
+            midenc_hir::ASSERT_FAILED_UNCAUGHT_EXCEPTION => {
+                Some("uncaught exception: an exception was not caught by any function".to_string())
+            }
+            code => self.error_codes.get(&code).cloned(),
         };
         ExecutionError::FailedAssertion {
             clk,
@@ -53,6 +53,10 @@ pub fn run_noninteractively(
 
     let state = ui::State::from_inputs(inputs, args, session)?;
 
+    if let Err(err) = state.executor.result.as_ref() {
+        return Err(Report::msg(format!("program execution failed: {err}")));
+    }
+
     println!(
         "Executed program with hash {} in {}",
         state.package.digest.to_hex(),
@@ -3,7 +3,7 @@ use std::{rc::Rc, sync::Arc};
 use miden_assembly::Library;
 use miden_core::{utils::Deserializable, FieldElement};
 use miden_processor::{Felt, Program, StackInputs};
-use midenc_codegen_masm::Package;
+use midenc_codegen_masm::{load_link_library, Package};
 use midenc_session::{
     diagnostics::{IntoDiagnostic, Report, SourceSpan, Span, WrapErr},
     InputType, Session,
@@ -53,8 +53,9 @@ impl State {
         let mut executor = crate::Executor::for_package(&package, args.clone(), &session)?;
         executor.with_advice_inputs(inputs.advice_inputs.clone());
         for link_library in session.options.link_libraries.iter() {
-            let lib = link_library.load(&session)?;
-            executor.with_library(&lib);
+            for lib in load_link_library(link_library, &session)? {
+                executor.with_library(&lib);
+            }
         }
 
         let program = package.unwrap_program();
@@ -64,8 +65,9 @@ impl State {
         let mut trace_executor = crate::Executor::for_package(&package, args, &session)?;
         trace_executor.with_advice_inputs(inputs.advice_inputs.clone());
         for link_library in session.options.link_libraries.iter() {
-            let lib = link_library.load(&session)?;
-            trace_executor.with_library(&lib);
+            for lib in load_link_library(link_library, &session)? {
+                trace_executor.with_library(&lib);
+            }
         }
 
         let now = std::time::Instant::now();
@@ -96,8 +98,9 @@ impl State {
         let mut executor = crate::Executor::for_package(&package, args.clone(), &self.session)?;
         executor.with_advice_inputs(self.inputs.advice_inputs.clone());
         for link_library in self.session.options.link_libraries.iter() {
-            let lib = link_library.load(&self.session)?;
-            executor.with_library(&lib);
+            for lib in load_link_library(link_library, &self.session)? {
+                executor.with_library(&lib);
+            }
         }
         let program = package.unwrap_program();
         let executor = executor.into_debug(&program, &self.session);
@@ -106,8 +109,9 @@ impl State {
         let mut trace_executor = crate::Executor::for_package(&package, args, &self.session)?;
         trace_executor.with_advice_inputs(self.inputs.advice_inputs.clone());
         for link_library in self.session.options.link_libraries.iter() {
-            let lib = link_library.load(&self.session)?;
-            trace_executor.with_library(&lib);
+            for lib in load_link_library(link_library, &self.session)? {
+                trace_executor.with_library(&lib);
+            }
         }
         let execution_trace = trace_executor.capture_trace(&program, &self.session);
 
@@ -0,0 +1,703 @@
+use std::{collections::BTreeMap, fmt};
+
+use miden_core::{
+    crypto::hash::RpoDigest,
+    mast::{MastForest, MastNode, MastNodeId},
+    Decorator,
+};
+use midenc_codegen_masm::Package;
+use midenc_hir::formatter::DisplayHex;
+use midenc_session::{
+    diagnostics::{IntoDiagnostic, Report},
+    FileType, InputFile,
+};
+use serde::Serialize;
+
+use crate::inspect::{read_input, Inspection, Procedure, Segment};
+
+/// The differences between two builds of a package
+#[derive(Debug, Serialize)]
+pub struct PackageDiff {
+    /// The summary of the package being compared against, e.g. the previous build
+    pub old: Inspection,
+    /// The summary of the package being compared, e.g. the new build
+    pub new: Inspection,
+    /// The procedures exported by `new`, but not by `old`
+    pub added: Vec<Procedure>,
+    /// The procedures exported by `old`, but not by `new`
+    pub removed: Vec<Procedure>,
+    /// The procedures exported by both packages, whose MAST digest has changed
+    pub changed: Vec<ChangedProcedure>,
+    /// The rodata segments which differ between both packages
+    pub rodata: Vec<SegmentChange>,
+}
+
+/// An exported procedure whose MAST digest differs between two builds of a package
+#[derive(Debug, Serialize)]
+pub struct ChangedProcedure {
+    /// The fully-qualified name of the procedure
+    pub name: String,
+    /// The MAST root of the procedure in the old package
+    pub old_digest: String,
+    /// The MAST root of the procedure in the new package
+    pub new_digest: String,
+    /// A line-by-line comparison of the Miden Assembly of both versions of the procedure.
+    ///
+    /// This is only available when both packages were compiled with debug info, as that is what
+    /// records the instructions from which the MAST of the procedure was assembled.
+    pub masm: Option<Vec<DiffLine>>,
+}
+
+/// A line of a side-by-side diff.
+///
+/// A line present on only one side was removed (`new` is `None`) or added (`old` is `None`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// A change to the rodata segments of a package, identified by their address
+#[derive(Debug, Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum SegmentChange {
+    Added { segment: Segment },
+    Removed { segment: Segment },
+    Changed { old: Segment, new: Segment },
+}
+
+impl PackageDiff {
+    /// Compare the packages in `old` and `new`
+    pub fn new(old: &InputFile, new: &InputFile) -> Result<Self, Report> {
+        let old = read_package(old)?;
+        let new = read_package(new)?;
+        Ok(Self::from_packages(&old, &new))
+    }
+
+    /// Compare the exports and rodata of `old` and `new`
+    pub fn from_packages(old: &Package, new: &Package) -> Self {
+        let old_inspection = Inspection::from_package(old);
+        let new_inspection = Inspection::from_package(new);
+
+        let old_exports = exports_by_name(&old_inspection);
+        let new_exports = exports_by_name(&new_inspection);
+        let added = new_exports
+            .iter()
+            .filter(|(name, _)| !old_exports.contains_key(*name))
+            .map(|(_, export)| (*export).clone())
+            .collect();
+        let removed = old_exports
+            .iter()
+            .filter(|(name, _)| !new_exports.contains_key(*name))
+            .map(|(_, export)| (*export).clone())
+            .collect();
+        let old_listings = Listings::new(old);
+        let new_listings = Listings::new(new);
+        let changed = old_exports
+            .iter()
+            .filter_map(|(name, old_export)| {
+                let new_export = new_exports.get(name)?;
+                if old_export.digest == new_export.digest {
+                    return None;
+                }
+                let masm = old_listings
+                    .listing_of(name)
+                    .zip(new_listings.listing_of(name))
+                    .map(|(old_lines, new_lines)| diff_lines(&old_lines, &new_lines));
+                Some(ChangedProcedure {
+                    name: name.to_string(),
+                    old_digest: old_export.digest.clone(),
+                    new_digest: new_export.digest.clone(),
+                    masm,
+                })
+            })
+            .collect();
+
+        let old_rodata = segments_by_address(&old_inspection);
+        let new_rodata = segments_by_address(&new_inspection);
+        let mut rodata = vec![];
+        for (address, segment) in old_rodata.iter() {
+            match new_rodata.get(address) {
+                None => rodata.push(SegmentChange::Removed {
+                    segment: (*segment).clone(),
+                }),
+                Some(new_segment) if new_segment != segment => {
+                    rodata.push(SegmentChange::Changed {
+                        old: (*segment).clone(),
+                        new: (*new_segment).clone(),
+                    })
+                }
+                Some(_) => (),
+            }
+        }
+        for (address, segment) in new_rodata.iter() {
+            if !old_rodata.contains_key(address) {
+                rodata.push(SegmentChange::Added {
+                    segment: (*segment).clone(),
+                });
+            }
+        }
+        rodata.sort_by_key(|change| match change {
+            SegmentChange::Added { segment } | SegmentChange::Removed { segment } => {
+                segment.address
+            }
+            SegmentChange::Changed { old, .. } => old.address,
+        });
+
+        Self {
+            old: old_inspection,
+            new: new_inspection,
+            added,
+            removed,
+            changed,
+            rodata,
+        }
+    }
+
+    /// Returns true if neither the exports nor the rodata of the packages differ
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.rodata.is_empty()
+    }
+
+    /// Render this diff as JSON
+    pub fn to_json(&self) -> Result<String, Report> {
+        serde_json::to_string_pretty(self).into_diagnostic()
+    }
+}
+
+impl fmt::Display for PackageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "--- {}  {}", &self.old.name, &self.old.digest)?;
+        writeln!(f, "+++ {}  {}", &self.new.name, &self.new.digest)?;
+        if self.is_empty() {
+            if self.old.digest == self.new.digest {
+                writeln!(f, "\npackages are identical")?;
+            } else {
+                writeln!(f, "\nexported procedures and rodata are identical")?;
+            }
+            return Ok(());
+        }
+
+        if !self.added.is_empty() {
+            writeln!(f, "\nadded:")?;
+            for export in self.added.iter() {
+                writeln!(
+                    f,
+                    "  + {}  {}",
+                    export.name.as_deref().unwrap_or("<anonymous>"),
+                    &export.digest
+                )?;
+            }
+        }
+
+        if !self.removed.is_empty() {
+            writeln!(f, "\nremoved:")?;
+            for export in self.removed.iter() {
+                writeln!(
+                    f,
+                    "  - {}  {}",
+                    export.name.as_deref().unwrap_or("<anonymous>"),
+                    &export.digest
+                )?;
+            }
+        }
+
+        if !self.changed.is_empty() {
+            writeln!(f, "\nchanged:")?;
+            for (i, procedure) in self.changed.iter().enumerate() {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "{procedure}")?;
+            }
+        }
+
+        if !self.rodata.is_empty() {
+            writeln!(f, "\nrodata:")?;
+            for change in self.rodata.iter() {
+                match change {
+                    SegmentChange::Added { segment } => writeln!(
+                        f,
+                        "  + {:#010x}  {} bytes  {}",
+                        segment.address, segment.size, &segment.digest
+                    )?,
+                    SegmentChange::Removed { segment } => writeln!(
+                        f,
+                        "  - {:#010x}  {} bytes  {}",
+                        segment.address, segment.size, &segment.digest
+                    )?,
+                    SegmentChange::Changed { old, new } => {
+                        writeln!(f, "  ~ {:#010x}", old.address)?;
+                        writeln!(f, "      old: {} bytes  {}", old.size, &old.digest)?;
+                        writeln!(f, "      new: {} bytes  {}", new.size, &new.digest)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ChangedProcedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// The maximum width of the left-hand column of the side-by-side diff
+        const MAX_WIDTH: usize = 48;
+        /// The number of unchanged lines shown before and after each change
+        const CONTEXT: usize = 3;
+
+        writeln!(f, "  ~ {}", &self.name)?;
+        writeln!(f, "      old: {}", &self.old_digest)?;
+        writeln!(f, "      new: {}", &self.new_digest)?;
+        let Some(lines) = self.masm.as_deref() else {
+            return writeln!(f, "      (no debug info available to compare the assembly)");
+        };
+
+        let is_changed = |line: &DiffLine| line.old != line.new;
+        let is_visible = |index: usize| {
+            let start = index.saturating_sub(CONTEXT);
+            let end = (index + CONTEXT + 1).min(lines.len());
+            lines[start..end].iter().any(is_changed)
+        };
+        let width = lines
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| is_visible(*index))
+            .filter_map(|(_, line)| line.old.as_deref())
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(MAX_WIDTH);
+        writeln!(f)?;
+        let mut elided = false;
+        for (index, line) in lines.iter().enumerate() {
+            if !is_visible(index) {
+                if !elided {
+                    writeln!(f, "      ...")?;
+                    elided = true;
+                }
+                continue;
+            }
+            elided = false;
+            // Same convention as `diff --side-by-side`
+            let marker = match (line.old.as_deref(), line.new.as_deref()) {
+                (Some(old), Some(new)) if old == new => ' ',
+                (Some(_), Some(_)) => '|',
+                (Some(_), None) => '<',
+                (None, _) => '>',
+            };
+            let old = line.old.as_deref().unwrap_or("");
+            let new = line.new.as_deref().unwrap_or("");
+            let line = format!("      {old:<width$} {marker} {new}");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the package in `input`
+fn read_package(input: &InputFile) -> Result<Package, Report> {
+    match input.file_type() {
+        FileType::Masp => Package::read_from_bytes(read_input(input)?),
+        ty => Err(Report::msg(format!(
+            "unable to diff '{}': expected a package, got a '{ty}' file",
+            input.file_name()
+        ))),
+    }
+}
+
+fn exports_by_name(inspection: &Inspection) -> BTreeMap<&str, &Procedure> {
+    inspection
+        .exports
+        .iter()
+        .filter_map(|export| export.name.as_deref().map(|name| (name, export)))
+        .collect()
+}
+
+fn segments_by_address(inspection: &Inspection) -> BTreeMap<u32, &Segment> {
+    inspection.rodata.iter().map(|segment| (segment.address, segment)).collect()
+}
+
+/// Renders the procedures in the MAST of a package as Miden Assembly, using the instructions
+/// recorded in its debug info.
+struct Listings<'a> {
+    forest: &'a MastForest,
+    /// The names of the procedures exported by the package, used to render calls to them
+    names: BTreeMap<RpoDigest, String>,
+    /// The MAST roots of the procedures exported by the package, by name
+    roots: BTreeMap<String, RpoDigest>,
+}
+impl<'a> Listings<'a> {
+    fn new(package: &'a Package) -> Self {
+        let exports = package
+            .manifest
+            .exports
+            .iter()
+            .map(|export| (export.id.display().to_string(), export.digest));
+        let roots = exports.collect::<BTreeMap<_, _>>();
+        let names = roots.iter().map(|(name, digest)| (*digest, name.clone())).collect();
+        Self {
+            forest: package.mast.mast_forest(),
+            names,
+            roots,
+        }
+    }
+
+    /// Render the exported procedure called `name`, see [Self::listing]
+    fn listing_of(&self, name: &str) -> Option<Vec<String>> {
+        self.roots.get(name).and_then(|digest| self.listing(*digest))
+    }
+
+    /// Render the procedure with MAST root `digest`, one instruction per line.
+    ///
+    /// Returns `None` if the procedure is not in the MAST forest, or has no debug info.
+    fn listing(&self, digest: RpoDigest) -> Option<Vec<String>> {
+        let root = self.forest.find_procedure_root(digest)?;
+        let mut lines = vec![];
+        let mut has_debug_info = false;
+        self.render(root, 0, &mut lines, &mut has_debug_info);
+        has_debug_info.then_some(lines)
+    }
+
+    fn render(
+        &self,
+        id: MastNodeId,
+        depth: usize,
+        lines: &mut Vec<String>,
+        has_debug_info: &mut bool,
+    ) {
+        let indent = "    ".repeat(depth);
+        match &self.forest[id] {
+            MastNode::Block(block) => {
+                for (_, decorator) in block.decorators() {
+                    if let Decorator::AsmOp(op) = decorator {
+                        *has_debug_info = true;
+                        lines.push(format!("{indent}{}", op.op()));
+                    }
+                }
+            }
+            MastNode::Join(node) => {
+                self.render(node.first(), depth, lines, has_debug_info);
+                self.render(node.second(), depth, lines, has_debug_info);
+            }
+            MastNode::Split(node) => {
+                lines.push(format!("{indent}if.true"));
+                self.render(node.on_true(), depth + 1, lines, has_debug_info);
+                lines.push(format!("{indent}else"));
+                self.render(node.on_false(), depth + 1, lines, has_debug_info);
+                lines.push(format!("{indent}end"));
+            }
+            MastNode::Loop(node) => {
+                lines.push(format!("{indent}while.true"));
+                self.render(node.body(), depth + 1, lines, has_debug_info);
+                lines.push(format!("{indent}end"));
+            }
+            MastNode::Call(node) => {
+                let callee = self.name(self.forest[node.callee()].digest());
+                let kind = if node.is_syscall() { "syscall" } else { "call" };
+                lines.push(format!("{indent}{kind}.{callee}"));
+            }
+            MastNode::Dyn => lines.push(format!("{indent}dynexec")),
+            MastNode::External(node) => {
+                lines.push(format!("{indent}exec.{}", self.name(node.digest())));
+            }
+        }
+    }
+
+    fn name(&self, digest: RpoDigest) -> String {
+        match self.names.get(&digest) {
+            Some(name) => name.clone(),
+            None => format!("{:#x}", DisplayHex::new(&digest.as_bytes())),
+        }
+    }
+}
+
+/// The maximum number of entries in the table used to compute the longest common subsequence of
+/// the lines which differ between two versions of a procedure, i.e. about 1000 lines on each side
+const MAX_LCS_TABLE_SIZE: usize = 1 << 20;
+
+/// Compute a side-by-side diff of `old` and `new`, based on their longest common subsequence.
+///
+/// Runs of removed lines followed by added lines are paired up, so that a changed line is shown
+/// next to the line that replaced it.
+///
+/// Lines common to the start and end of both versions are matched directly. If what remains is
+/// too large to compute the longest common subsequence of, it is shown as a single changed run.
+fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    let unchanged = |line: &String| DiffLine {
+        old: Some(line.clone()),
+        new: Some(line.clone()),
+    };
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..(old.len() - suffix)];
+    let new_changed = &new[prefix..(new.len() - suffix)];
+
+    let mut lines = old[..prefix].iter().map(unchanged).collect::<Vec<_>>();
+    if (old_changed.len() + 1) * (new_changed.len() + 1) <= MAX_LCS_TABLE_SIZE {
+        diff_lines_by_lcs(old_changed, new_changed, &mut lines);
+    } else {
+        pair_changed_lines(&mut lines, old_changed.to_vec(), new_changed.to_vec());
+    }
+    lines.extend(old[(old.len() - suffix)..].iter().map(unchanged));
+
+    lines
+}
+
+/// Append the side-by-side diff of `old` and `new` to `lines`, see [diff_lines]
+fn diff_lines_by_lcs(old: &[String], new: &[String], lines: &mut Vec<DiffLine>) {
+    // lcs[i * width + j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut removed = vec![];
+    let mut added = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            pair_changed_lines(lines, core::mem::take(&mut removed), core::mem::take(&mut added));
+            lines.push(DiffLine {
+                old: Some(old[i].clone()),
+                new: Some(new[j].clone()),
+            });
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            removed.push(old[i].clone());
+            i += 1;
+        } else {
+            added.push(new[j].clone());
+            j += 1;
+        }
+    }
+    pair_changed_lines(lines, removed, added);
+}
+
+/// Append a run of `removed` lines, followed by a run of `added` lines, to `lines`, pairing them up
+/// side-by-side
+fn pair_changed_lines(lines: &mut Vec<DiffLine>, removed: Vec<String>, added: Vec<String>) {
+    let len = removed.len().max(added.len());
+    let mut removed = removed.into_iter();
+    let mut added = added.into_iter();
+    lines.extend((0..len).map(|_| DiffLine {
+        old: removed.next(),
+        new: added.next(),
+    }));
+}
+
+/// Print the differences between the packages in `old` and `new` to stdout
+pub fn diff(old: &InputFile, new: &InputFile, json: bool) -> Result<(), Report> {
+    use std::io::Write;
+
+    let diff = PackageDiff::new(old, new)?;
+    let mut stdout = std::io::stdout().lock();
+    let result = if json {
+        writeln!(stdout, "{}", diff.to_json()?)
+    } else {
+        write!(stdout, "{diff}")
+    };
+    match result {
+        // The reader has gone away, e.g. when piping the output into `head`, which isn't an error
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.into_diagnostic(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inspect::tests::package;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn line(old: Option<&str>, new: Option<&str>) -> DiffLine {
+        DiffLine {
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }
+    }
+
+    const OLD: &str = "
+export.unchanged
+    push.1 add
+end
+
+export.changed
+    push.2 mul push.3 add
+end
+
+export.removed
+    push.4 mul
+end
+";
+
+    const NEW: &str = "
+export.unchanged
+    push.1 add
+end
+
+export.changed
+    push.2 mul push.7 add
+end
+
+export.added
+    push.5 mul
+end
+";
+
+    #[test]
+    fn diff_exports() {
+        let diff = PackageDiff::from_packages(&package(OLD, &[]), &package(NEW, &[]));
+
+        let names = |procedures: &[Procedure]| {
+            procedures.iter().map(|p| p.name.clone().unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&diff.added), ["test::added"]);
+        assert_eq!(names(&diff.removed), ["test::removed"]);
+        assert_eq!(diff.changed.len(), 1);
+        let changed = &diff.changed[0];
+        assert_eq!(changed.name, "test::changed");
+        assert_ne!(changed.old_digest, changed.new_digest);
+        assert_eq!(
+            changed.masm.as_deref(),
+            Some(
+                [
+                    line(Some("push.2"), Some("push.2")),
+                    line(Some("mul"), Some("mul")),
+                    line(Some("push.3"), Some("push.7")),
+                    line(Some("add"), Some("add")),
+                ]
+                .as_slice()
+            )
+        );
+        assert!(diff.rodata.is_empty());
+        assert!(!diff.is_empty());
+
+        let diff = PackageDiff::from_packages(&package(OLD, &[]), &package(OLD, &[]));
+        assert!(diff.is_empty());
+        assert!(diff.to_string().ends_with("\npackages are identical\n"));
+    }
+
+    #[test]
+    fn diff_rodata() {
+        let old = package(OLD, &[(0x1000, &[1, 2, 3, 4]), (0x2000, &[5])]);
+        let new = package(OLD, &[(0x1000, &[1, 2, 3, 5]), (0x3000, &[6, 7])]);
+        let diff = PackageDiff::from_packages(&old, &new);
+
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+        let changes = diff
+            .rodata
+            .iter()
+            .map(|change| match change {
+                SegmentChange::Added { segment } => ('+', segment.address, segment.size),
+                SegmentChange::Removed { segment } => ('-', segment.address, segment.size),
+                SegmentChange::Changed { old, new } => {
+                    assert_eq!(old.address, new.address);
+                    assert_ne!(old.digest, new.digest);
+                    ('~', old.address, new.size)
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(changes, [('~', 0x1000, 4), ('-', 0x2000, 1), ('+', 0x3000, 2)]);
+        assert!(diff.to_string().contains("\nrodata:\n  ~ 0x00001000\n"));
+    }
+
+    #[test]
+    fn diff_lines_side_by_side() {
+        let old = lines(&["a", "b", "c", "d", "e"]);
+        let new = lines(&["a", "x", "y", "c", "e", "f"]);
+        assert_eq!(
+            diff_lines(&old, &new),
+            [
+                line(Some("a"), Some("a")),
+                // A removed line followed by added lines is paired with the first of them
+                line(Some("b"), Some("x")),
+                line(None, Some("y")),
+                line(Some("c"), Some("c")),
+                line(Some("d"), None),
+                line(Some("e"), Some("e")),
+                line(None, Some("f")),
+            ]
+        );
+
+        let procedure = ChangedProcedure {
+            name: "test::changed".to_string(),
+            old_digest: "0x01".to_string(),
+            new_digest: "0x02".to_string(),
+            masm: Some(diff_lines(&old, &new)),
+        };
+        let rendered = procedure.to_string();
+        let rendered = rendered.lines().skip(4).collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            [
+                "      a   a",
+                "      b | x",
+                "        > y",
+                "      c   c",
+                "      d <",
+                "      e   e",
+                "        > f",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_lines_large() {
+        // The lines common to both ends are matched, and the rest is too large to align, so it is
+        // paired up as a single change
+        let n = 2048;
+        let mut old = lines(&["begin"]);
+        old.extend((0..n).map(|i| format!("old.{i}")));
+        old.push("end".to_string());
+        let mut new = lines(&["begin"]);
+        new.extend((0..n).map(|i| format!("new.{}", n - i)));
+        new.push("end".to_string());
+
+        let diff = diff_lines(&old, &new);
+        assert_eq!(diff.len(), n + 2);
+        assert_eq!(diff[0], line(Some("begin"), Some("begin")));
+        assert_eq!(diff[1], line(Some("old.0"), Some(&format!("new.{n}"))));
+        assert_eq!(diff[n + 1], line(Some("end"), Some("end")));
+    }
+
+    #[test]
+    fn diff_json() {
+        let old = package(OLD, &[(0x1000, &[1, 2, 3, 4])]);
+        let new = package(NEW, &[(0x1000, &[1, 2, 3, 5])]);
+        let diff = PackageDiff::from_packages(&old, &new);
+        let json: serde_json::Value =
+            serde_json::from_str(&diff.to_json().unwrap()).expect("invalid json");
+
+        assert_eq!(json["old"]["name"], "test");
+        assert_eq!(json["added"][0]["name"], "test::added");
+        assert_eq!(json["removed"][0]["name"], "test::removed");
+        assert_eq!(json["changed"][0]["name"], "test::changed");
+        assert_eq!(json["changed"][0]["masm"][2]["old"], "push.3");
+        assert_eq!(json["changed"][0]["masm"][2]["new"], "push.7");
+        assert_eq!(json["rodata"][0]["change"], "changed");
+        assert_eq!(json["rodata"][0]["old"]["address"], 0x1000);
+        assert_eq!(json["rodata"][0]["new"]["size"], 4);
+    }
+}
@@ -0,0 +1,519 @@
+use std::{collections::BTreeSet, fmt};
+
+use miden_assembly::Library as CompiledLibrary;
+use miden_core::{
+    mast::{MastForest, MastNode, MastNodeId},
+    utils::Deserializable,
+};
+use midenc_codegen_masm::{MastArtifact, Package, StorageSlot};
+use midenc_hir::{
+    formatter::{DisplayHex, PrettyPrint},
+    Signature,
+};
+use midenc_session::{
+    diagnostics::{IntoDiagnostic, Report},
+    FileType, InputFile, InputType,
+};
+use serde::Serialize;
+
+/// A summary of the contents of a package (`.masp`) or compiled library (`.masl`)
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    /// The name of the package, or for libraries, the name of the file it was loaded from
+    pub name: String,
+    /// The version of the package, if known
+    pub version: Option<String>,
+    /// Whether the MAST is an executable program or a library
+    pub kind: ArtifactKind,
+    /// The MAST digest of the program or library
+    pub digest: String,
+    /// The entrypoint of the program, if executable
+    pub entrypoint: Option<Procedure>,
+    /// The procedures exported by the library, or for packages, listed in its manifest
+    pub exports: Vec<Procedure>,
+    /// The rodata segments which are loaded into memory when the program starts
+    pub rodata: Vec<Segment>,
+    /// The libraries linked against
+    pub dependencies: Vec<Dependency>,
+    /// The account-component metadata of the package, if it implements an account component
+    pub account_component: Option<AccountComponent>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    Program,
+    Library,
+}
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Program => f.write_str("program"),
+            Self::Library => f.write_str("library"),
+        }
+    }
+}
+
+/// A procedure in the MAST of a program or library
+#[derive(Debug, Clone, Serialize)]
+pub struct Procedure {
+    /// The fully-qualified name of the procedure, if known
+    pub name: Option<String>,
+    /// The MAST root of the procedure
+    pub digest: String,
+    /// The type signature of the procedure, if known
+    pub signature: Option<String>,
+    /// The number of MAST nodes reachable from the root of the procedure, not counting those of
+    /// other procedures it calls, or `None` if the procedure is not in the MAST forest
+    pub num_nodes: Option<usize>,
+}
+
+/// A rodata segment of a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Segment {
+    /// The address in linear memory, in bytes, at which the segment starts
+    pub address: u32,
+    /// The size of the segment, in bytes
+    pub size: usize,
+    /// The content digest of the segment
+    pub digest: String,
+}
+
+/// A library linked against by a package
+#[derive(Debug, Serialize)]
+pub struct Dependency {
+    pub name: String,
+    pub kind: String,
+    pub path: Option<String>,
+    pub version: Option<String>,
+    pub digest: String,
+}
+
+/// The account-component metadata of a package
+#[derive(Debug, Serialize)]
+pub struct AccountComponent {
+    pub name: String,
+    pub description: Option<String>,
+    /// The storage slots used by the component
+    pub storage: Vec<StorageSlot>,
+    /// The exports of the package which are account interface methods
+    pub interface: Vec<String>,
+    /// The exports of the package which are internal to the component
+    pub internal: Vec<String>,
+}
+
+impl Inspection {
+    /// Inspect the package or library in `input`
+    pub fn new(input: &InputFile) -> Result<Self, Report> {
+        let bytes = read_input(input)?;
+        match input.file_type() {
+            FileType::Masp => Ok(Self::from_package(&Package::read_from_bytes(bytes)?)),
+            FileType::Mast => {
+                let library = CompiledLibrary::read_from_bytes(&bytes)
+                    .map_err(|err| Report::msg(format!("failed to deserialize library: {err}")))?;
+                Ok(Self::from_library(input.filestem(), &library))
+            }
+            ty => Err(Report::msg(format!(
+                "unable to inspect '{}': expected a package or library, got a '{ty}' file",
+                input.file_name()
+            ))),
+        }
+    }
+
+    /// Inspect `package`
+    pub fn from_package(package: &Package) -> Self {
+        let forest = package.mast.mast_forest();
+        let entrypoint = match package.mast {
+            MastArtifact::Executable(ref program) => Some(Procedure {
+                name: None,
+                digest: format!("{:#x}", DisplayHex::new(&program.hash().as_bytes())),
+                signature: None,
+                num_nodes: Some(count_nodes(forest, program.entrypoint())),
+            }),
+            MastArtifact::Library(_) => None,
+        };
+        let exports = package
+            .manifest
+            .exports
+            .iter()
+            .map(|export| Procedure {
+                name: Some(export.id.display().to_string()),
+                digest: format!("{:#x}", DisplayHex::new(&export.digest.as_bytes())),
+                signature: export.signature.as_ref().map(display_signature),
+                num_nodes: forest
+                    .find_procedure_root(export.digest)
+                    .map(|root| count_nodes(forest, root)),
+            })
+            .collect();
+        let rodata = package
+            .rodata
+            .iter()
+            .map(|rodata| Segment {
+                address: rodata.start.as_ptr(),
+                size: rodata.size_in_bytes(),
+                digest: format!("{:#x}", DisplayHex::new(&rodata.digest.as_bytes())),
+            })
+            .collect();
+        let dependencies = package
+            .manifest
+            .dependencies
+            .iter()
+            .map(|dependency| Dependency {
+                name: dependency.name().to_string(),
+                kind: dependency.library.kind.to_string(),
+                path: dependency.library.path.as_ref().map(|path| path.display().to_string()),
+                version: dependency.version.as_ref().map(|version| version.to_string()),
+                digest: format!("{:#x}", DisplayHex::new(&dependency.digest.as_bytes())),
+            })
+            .collect();
+        let account_component = package.manifest.account_component.as_ref().map(|metadata| {
+            let mut storage = metadata.storage.clone();
+            storage.sort_by_key(|slot| slot.slot);
+            let (interface, internal): (Vec<_>, Vec<_>) = package
+                .manifest
+                .exports
+                .iter()
+                .map(|export| {
+                    (export.id.display().to_string(), metadata.is_interface_method(export))
+                })
+                .partition(|(_, is_interface)| *is_interface);
+            let names =
+                |exports: Vec<(String, bool)>| exports.into_iter().map(|(name, _)| name).collect();
+            AccountComponent {
+                name: metadata.name.clone(),
+                description: metadata.description.clone(),
+                storage,
+                interface: names(interface),
+                internal: names(internal),
+            }
+        });
+
+        Self {
+            name: package.name.to_string(),
+            version: package.version.as_ref().map(|version| version.to_string()),
+            kind: if package.is_program() {
+                ArtifactKind::Program
+            } else {
+                ArtifactKind::Library
+            },
+            digest: format!("{:#x}", DisplayHex::new(&package.digest.as_bytes())),
+            entrypoint,
+            exports,
+            rodata,
+            dependencies,
+            account_component,
+        }
+    }
+
+    /// Inspect `library`, which was loaded from a file called `name`
+    pub fn from_library(name: &str, library: &CompiledLibrary) -> Self {
+        let forest = library.mast_forest();
+        let mut exports = vec![];
+        for module in library.module_infos() {
+            for (_, procedure) in module.procedures() {
+                exports.push(Procedure {
+                    name: Some(format!("{}::{}", module.path(), &procedure.name)),
+                    digest: format!("{:#x}", DisplayHex::new(&procedure.digest.as_bytes())),
+                    signature: None,
+                    num_nodes: forest
+                        .find_procedure_root(procedure.digest)
+                        .map(|root| count_nodes(forest, root)),
+                });
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            version: None,
+            kind: ArtifactKind::Library,
+            digest: format!("{:#x}", DisplayHex::new(&library.digest().as_bytes())),
+            entrypoint: None,
+            exports,
+            rodata: vec![],
+            dependencies: vec![],
+            account_component: None,
+        }
+    }
+
+    /// Render this inspection as JSON
+    pub fn to_json(&self) -> Result<String, Report> {
+        serde_json::to_string_pretty(self).into_diagnostic()
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name:    {}", &self.name)?;
+        if let Some(version) = self.version.as_deref() {
+            writeln!(f, "version: {version}")?;
+        }
+        writeln!(f, "kind:    {}", self.kind)?;
+        writeln!(f, "digest:  {}", &self.digest)?;
+
+        if let Some(entrypoint) = self.entrypoint.as_ref() {
+            writeln!(f, "\nentrypoint:")?;
+            write!(f, "{entrypoint}")?;
+        }
+
+        if !self.exports.is_empty() {
+            writeln!(f, "\nexports:")?;
+            for export in self.exports.iter() {
+                write!(f, "{export}")?;
+            }
+        }
+
+        if !self.rodata.is_empty() {
+            writeln!(f, "\nrodata:")?;
+            for segment in self.rodata.iter() {
+                writeln!(
+                    f,
+                    "  {:#010x}  {} bytes  {}",
+                    segment.address, segment.size, &segment.digest
+                )?;
+            }
+        }
+
+        if !self.dependencies.is_empty() {
+            writeln!(f, "\ndependencies:")?;
+            for dependency in self.dependencies.iter() {
+                write!(f, "  {} ({})", &dependency.name, &dependency.kind)?;
+                if let Some(version) = dependency.version.as_deref() {
+                    write!(f, " {version}")?;
+                }
+                writeln!(f, "  {}", &dependency.digest)?;
+                if let Some(path) = dependency.path.as_deref() {
+                    writeln!(f, "    path: {path}")?;
+                }
+            }
+        }
+
+        if let Some(component) = self.account_component.as_ref() {
+            write!(f, "\n{component}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for AccountComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "account component: {}", &self.name)?;
+        if let Some(description) = self.description.as_deref() {
+            writeln!(f, "  {description}")?;
+        }
+
+        if !self.storage.is_empty() {
+            writeln!(f, "  storage:")?;
+            for slot in self.storage.iter() {
+                let indices = match slot.size {
+                    1 => slot.slot.to_string(),
+                    _ => format!("{}..{}", slot.indices().start, slot.indices().end),
+                };
+                write!(f, "    {indices:<8} {:<5}  {}", slot.kind, &slot.name)?;
+                match slot.description.as_deref() {
+                    Some(description) => writeln!(f, "  ({description})")?,
+                    None => writeln!(f)?,
+                }
+            }
+        }
+
+        for (heading, procedures) in [("interface", &self.interface), ("internal", &self.internal)]
+        {
+            if !procedures.is_empty() {
+                writeln!(f, "  {heading}:")?;
+                for procedure in procedures.iter() {
+                    writeln!(f, "    {procedure}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  {}", self.name.as_deref().unwrap_or("<anonymous>"))?;
+        writeln!(f, "    digest:    {}", &self.digest)?;
+        if let Some(signature) = self.signature.as_deref() {
+            writeln!(f, "    signature: {signature}")?;
+        }
+        match self.num_nodes {
+            Some(num_nodes) => writeln!(f, "    nodes:     {num_nodes}"),
+            None => writeln!(f, "    nodes:     <external>"),
+        }
+    }
+}
+
+/// Read the raw contents of `input`
+pub(crate) fn read_input(input: &InputFile) -> Result<Vec<u8>, Report> {
+    match &input.file {
+        InputType::Real(ref path) => std::fs::read(path).into_diagnostic(),
+        InputType::Stdin { input, .. } => Ok(input.clone()),
+    }
+}
+
+/// Count the number of MAST nodes in `forest` reachable from `root`.
+///
+/// The callee of a `call` or `syscall` is the root of another procedure, so its nodes are not
+/// counted. Nodes which are shared within the procedure are counted once.
+fn count_nodes(forest: &MastForest, root: MastNodeId) -> usize {
+    let mut visited = BTreeSet::default();
+    let mut worklist = vec![root];
+    while let Some(id) = worklist.pop() {
+        if !visited.insert(id.as_u32()) {
+            continue;
+        }
+        match &forest[id] {
+            MastNode::Join(node) => worklist.extend([node.first(), node.second()]),
+            MastNode::Split(node) => worklist.extend([node.on_true(), node.on_false()]),
+            MastNode::Loop(node) => worklist.push(node.body()),
+            MastNode::Block(_) | MastNode::Call(_) | MastNode::Dyn | MastNode::External(_) => (),
+        }
+    }
+    visited.len()
+}
+
+/// Render `signature` in the same form used for function declarations in HIR
+fn display_signature(signature: &Signature) -> String {
+    let rendered = signature.to_pretty_string();
+    if rendered.is_empty() {
+        "(func)".to_string()
+    } else {
+        format!("(func {rendered})")
+    }
+}
+
+/// Print the inspection of `input` to stdout
+pub fn inspect(input: &InputFile, json: bool) -> Result<(), Report> {
+    use std::io::Write;
+
+    let inspection = Inspection::new(input)?;
+    let mut stdout = std::io::stdout().lock();
+    let result = if json {
+        writeln!(stdout, "{}", inspection.to_json()?)
+    } else {
+        write!(stdout, "{inspection}")
+    };
+    match result {
+        // The reader has gone away, e.g. when piping the output into `head`, which isn't an error
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.into_diagnostic(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use miden_assembly::{
+        ast::{Module, ModuleKind},
+        Assembler, LibraryPath,
+    };
+    use midenc_codegen_masm::{NativePtr, PackageExport, PackageManifest, Rodata};
+    use midenc_hir::{ConstantData, FunctionIdent, Ident, Symbol};
+
+    use super::*;
+
+    /// Assemble `source` as the library module `test`, with debug info, and package it with the
+    /// given rodata segments
+    pub(crate) fn package(source: &str, rodata: &[(u32, &[u8])]) -> Package {
+        let assembler = Assembler::default().with_debug_mode(true);
+        let module = Module::parser(ModuleKind::Library)
+            .parse_str(LibraryPath::new("test").unwrap(), source, &assembler.source_manager())
+            .expect("failed to parse module");
+        let library = assembler.assemble_library([module]).expect("failed to assemble library");
+
+        let mut manifest = PackageManifest::default();
+        for module_info in library.module_infos() {
+            let module = Ident::with_empty_span(Symbol::intern(module_info.path().path()));
+            for (_, procedure) in module_info.procedures() {
+                manifest.exports.insert(PackageExport {
+                    id: FunctionIdent {
+                        module,
+                        function: Ident::with_empty_span(Symbol::intern(procedure.name.as_str())),
+                    },
+                    digest: procedure.digest,
+                    signature: None,
+                });
+            }
+        }
+        let rodata = rodata
+            .iter()
+            .map(|(address, data)| {
+                Rodata::new(
+                    NativePtr::from_ptr(*address),
+                    Arc::new(ConstantData::from(data.to_vec())),
+                )
+            })
+            .collect();
+
+        Package {
+            name: Symbol::intern("test"),
+            version: None,
+            digest: *library.digest(),
+            mast: MastArtifact::Library(Arc::new(library)),
+            rodata,
+            manifest,
+        }
+    }
+
+    #[test]
+    fn inspect_package() {
+        let package = package(
+            "
+export.foo
+    push.1 add
+end
+
+export.bar
+    if.true push.2 else push.3 end mul
+end
+",
+            &[(0x1000, &[1, 2, 3, 4, 5])],
+        );
+        let inspection = Inspection::from_package(&package);
+
+        assert_eq!(inspection.name, "test");
+        assert_eq!(inspection.kind, ArtifactKind::Library);
+        assert!(inspection.entrypoint.is_none());
+        let exports = inspection
+            .exports
+            .iter()
+            .map(|export| (export.name.as_deref().unwrap(), export.num_nodes))
+            .collect::<Vec<_>>();
+        // `bar` is a join of the split on the condition, and the block containing `mul`
+        assert_eq!(exports, [("test::bar", Some(5)), ("test::foo", Some(1))]);
+        assert_eq!(
+            inspection.rodata,
+            [Segment {
+                address: 0x1000,
+                size: 5,
+                digest: format!("{:#x}", DisplayHex::new(&package.rodata[0].digest.as_bytes())),
+            }]
+        );
+
+        // A library has the same exports, but nothing else
+        let MastArtifact::Library(ref library) = package.mast else {
+            unreachable!()
+        };
+        let from_library = Inspection::from_library("test", library);
+        assert_eq!(from_library.digest, inspection.digest);
+        let names = |inspection: &Inspection| {
+            inspection.exports.iter().map(|export| export.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&from_library), names(&inspection));
+        assert!(from_library.rodata.is_empty());
+
+        let rendered = inspection.to_string();
+        assert!(rendered.starts_with("name:    test\nkind:    library\n"));
+        assert!(rendered.contains("\nrodata:\n  0x00001000  5 bytes  "));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&inspection.to_json().unwrap()).expect("invalid json");
+        assert_eq!(json["kind"], "library");
+        assert_eq!(json["exports"][1]["name"], "test::foo");
+        assert_eq!(json["exports"][1]["num_nodes"], 1);
+        assert_eq!(json["rodata"][0]["size"], 5);
+    }
+}
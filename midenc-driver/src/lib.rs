@@ -1,11 +1,19 @@
+mod diff;
+mod inspect;
 mod midenc;
+mod publish;
 
 pub use clap::Error as ClapError;
 use log::Log;
 pub use midenc_session::diagnostics;
 use midenc_session::diagnostics::{miette, Diagnostic, Report};
 
-pub use self::midenc::Midenc;
+pub use self::{
+    diff::{diff, PackageDiff},
+    inspect::{inspect, Inspection},
+    midenc::Midenc,
+    publish::publish,
+};
 
 /// A convenience alias for `Result<T, Report>`
 pub type DriverResult<T> = Result<T, Report>;
@@ -32,6 +32,39 @@ enum Commands {
         #[command(flatten)]
         options: compile::Compiler,
     },
+    /// Print a summary of the contents of a package or library
+    ///
+    /// This includes its exports, with their signatures and MAST roots, its rodata segments, and
+    /// the libraries it was linked against.
+    Inspect {
+        /// Specify the path to the package (`.masp`) or library (`.masl`) to inspect.
+        ///
+        /// You may use `-` as a file name to read a file from stdin.
+        #[arg(required(true), value_name = "FILE")]
+        input: InputFile,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare two builds of a package, e.g. before and after upgrading the compiler
+    ///
+    /// This reports the exported procedures which were added, removed, or whose MAST digest
+    /// changed, as well as any differences in rodata. For changed procedures, the Miden Assembly
+    /// of both builds is shown side-by-side, if both packages were compiled with debug info.
+    Diff {
+        /// Specify the path to the package (`.masp`) to compare against, e.g. the previous build
+        #[arg(required(true), value_name = "OLD")]
+        old: InputFile,
+        /// Specify the path to the package (`.masp`) to compare, e.g. the new build
+        #[arg(required(true), value_name = "NEW")]
+        new: InputFile,
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Manage compiled packages
+    #[command(subcommand)]
+    Package(PackageCommands),
     /// Execute a compiled program or library, using the Miden VM.
     #[cfg(feature = "debug")]
     Run {
@@ -101,6 +134,31 @@ enum Commands {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum PackageCommands {
+    /// Publish a package, so that it can be linked against by name and version
+    ///
+    /// The package must have been compiled with `--package-version`. Once published, it can be
+    /// linked against with e.g. `-l NAME@^1.2`.
+    Publish {
+        /// Specify the path to the package (`.masp`) to publish.
+        ///
+        /// You may use `-` as a file name to read a file from stdin.
+        #[arg(required(true), value_name = "FILE")]
+        input: InputFile,
+        /// Publish the package to the local package registry
+        ///
+        /// This is currently the only kind of registry which is supported, and so is required.
+        #[arg(long, required(true))]
+        local: bool,
+        /// The path to the root directory of the local package registry
+        ///
+        /// By default this is assumed to be ~/.miden/registry
+        #[arg(long, value_name = "DIR", env = "MIDENC_REGISTRY")]
+        registry: Option<PathBuf>,
+    },
+}
+
 impl Midenc {
     pub fn run<P, A>(
         cwd: P,
@@ -158,6 +216,11 @@ impl Midenc {
                     options.into_session(vec![input], emitter).with_extra_flags(matches.into());
                 compile::compile(Rc::new(session))
             }
+            Commands::Inspect { input, json } => crate::inspect(&input, json),
+            Commands::Diff { old, new, json } => crate::diff(&old, &new, json),
+            Commands::Package(PackageCommands::Publish {
+                input, registry, ..
+            }) => crate::publish(&input, registry).map(|_| ()),
             #[cfg(feature = "debug")]
             Commands::Run {
                 input,
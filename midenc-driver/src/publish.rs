@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use midenc_codegen_masm::Package;
+use midenc_session::{
+    diagnostics::{IntoDiagnostic, Report},
+    FileType, InputFile, Registry,
+};
+
+use crate::inspect::read_input;
+
+/// Publish the package in `input` to the local package registry at `registry`, or the default
+/// registry if not specified.
+///
+/// Returns the path at which the package was installed in the registry.
+pub fn publish(input: &InputFile, registry: Option<PathBuf>) -> Result<PathBuf, Report> {
+    use std::io::Write;
+
+    if input.file_type() != FileType::Masp {
+        return Err(Report::msg(format!(
+            "unable to publish '{}': expected a package, got a '{}' file",
+            input.file_name(),
+            input.file_type()
+        )));
+    }
+    let bytes = read_input(input)?;
+    let package = Package::read_from_bytes(bytes.clone())?;
+    let Some(version) = package.version.as_ref() else {
+        return Err(Report::msg(format!(
+            "unable to publish '{}': the package has no version, recompile it with \
+             `--package-version`",
+            &package.name
+        )));
+    };
+
+    let registry =
+        registry.or_else(Registry::default_root).map(Registry::new).ok_or_else(|| {
+            Report::msg(
+                "unable to locate the local package registry: use --registry to specify one",
+            )
+        })?;
+    let path = registry.publish(package.name.as_str(), version, &package.digest, &bytes)?;
+
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "published {} {version} to '{}'", &package.name, path.display())
+        .into_diagnostic()?;
+
+    Ok(path)
+}
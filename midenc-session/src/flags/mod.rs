@@ -7,12 +7,14 @@ pub use self::flag::{CompileFlag, FlagAction};
 use crate::diagnostics::{IntoDiagnostic, Report};
 
 #[cfg(feature = "std")]
+#[derive(Clone)]
 pub struct CompileFlags {
     flags: Vec<CompileFlag>,
     arg_matches: clap::ArgMatches,
 }
 
 #[cfg(not(feature = "std"))]
+#[derive(Clone)]
 pub struct CompileFlags {
     flags: Vec<CompileFlag>,
     args: alloc::collections::BTreeMap<String, MatchedArg>,
@@ -22,6 +22,8 @@ mod libs;
 mod options;
 mod outputs;
 #[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
 mod statistics;
 
 use alloc::{fmt, sync::Arc};
@@ -46,6 +48,7 @@ pub use self::{
     libs::{LibraryKind, LinkLibrary},
     options::*,
     outputs::{OutputFile, OutputFiles, OutputMode, OutputType, OutputTypeSpec, OutputTypes},
+    registry::Registry,
     statistics::Statistics,
 };
 
@@ -355,6 +358,48 @@ impl Session {
             || self.options.print_cfg_after_pass.iter().any(|p| p == pass)
     }
 
+    /// Get the local package registry configured for this session
+    pub fn registry(&self) -> Result<Registry, diagnostics::Report> {
+        self.options.registry.as_deref().map(Registry::new).ok_or_else(|| {
+            diagnostics::Report::msg(
+                "no package registry is configured: use --registry to specify one",
+            )
+        })
+    }
+
+    /// Get `path` in the form in which it should be recorded in compiled artifacts, e.g. as the
+    /// location of an instruction in debug info.
+    ///
+    /// Paths under the current working directory are made relative to it, so that the artifacts
+    /// produced by a build do not depend on the directory in which it was performed.
+    pub fn artifact_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.options.current_dir).unwrap_or(path)
+    }
+
+    /// Get a [SourceManager] for this session, in which the source files have been renamed
+    /// using [Self::artifact_path].
+    ///
+    /// This is used when assembling Miden Assembly, as the assembler records the paths of the
+    /// source files it was given in the debug info of the artifacts it produces. The returned
+    /// source manager assigns the same [diagnostics::SourceId] to each file as the session's
+    /// source manager, so spans remain valid in both. If that cannot be preserved, e.g. because
+    /// two files are given the same name, the session's source manager is returned as-is.
+    pub fn artifact_source_manager(&self) -> Arc<dyn SourceManager> {
+        let source_manager = diagnostics::DefaultSourceManager::default();
+        for id in 0.. {
+            let Ok(file) = self.source_manager.get(diagnostics::SourceId::new(id)) else {
+                break;
+            };
+            let name: Arc<str> = self.artifact_path(file.path()).to_string_lossy().into();
+            let content =
+                diagnostics::SourceContent::new(name.clone(), file.as_str().to_string().into());
+            if source_manager.load_from_raw_parts(name, content).id() != file.id() {
+                return self.source_manager.clone();
+            }
+        }
+        Arc::new(source_manager)
+    }
+
     /// Print the given emittable IR to stdout, as produced by a pass with name `pass`
     pub fn print(&self, ir: impl Emit, pass: &str) -> std::io::Result<()> {
         if self.should_print_ir(pass) {
@@ -31,12 +31,15 @@ pub enum LibraryKind {
     Mast,
     /// A source-form MASM library, using the standard project layout
     Masm,
+    /// A Miden package (`.masp`), as produced by the compiler
+    Package,
 }
 impl fmt::Display for LibraryKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Mast => f.write_str("mast"),
             Self::Masm => f.write_str("masm"),
+            Self::Package => f.write_str("package"),
         }
     }
 }
@@ -47,6 +50,7 @@ impl FromStr for LibraryKind {
         match s {
             "mast" | "masl" => Ok(Self::Mast),
             "masm" => Ok(Self::Masm),
+            "package" | "masp" => Ok(Self::Package),
             _ => Err(()),
         }
     }
@@ -71,8 +75,18 @@ pub struct LinkLibrary {
     /// By default this is assumed to be a `.masl` library, but the kind will be detected based on
     /// how it is requested by the user. It may also be specified explicitly by the user.
     pub kind: LibraryKind,
+    /// If specified, the versions of the package which may be linked against, e.g. `-l foo@^1.2`.
+    ///
+    /// A package requested with a version requirement is resolved using the local package
+    /// registry, rather than the search paths, see [crate::Registry].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub version: Option<semver::VersionReq>,
 }
 impl LinkLibrary {
+    /// Load this library as a [CompiledLibrary]
+    ///
+    /// Packages cannot be loaded here, as their format is defined by the code generator, which
+    /// provides its own means of loading them. Use [LinkLibrary::locate] to find a package.
     pub fn load(&self, session: &Session) -> Result<CompiledLibrary, Report> {
         if let Some(path) = self.path.as_deref() {
             return self.load_from_path(path, session);
@@ -107,12 +121,29 @@ impl LinkLibrary {
                     path.display()
                 ))
             }),
+            LibraryKind::Package => Err(Report::msg(format!(
+                "unable to load '{}': packages must be loaded by the code generator",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Get the path from which this library should be loaded, searching for it among the
+    /// search paths of `session`, if no path was given.
+    pub fn locate(&self, session: &Session) -> Result<PathBuf, Report> {
+        match self.path.as_deref() {
+            Some(path) => Ok(path.to_path_buf()),
+            None => self.find(session),
         }
     }
 
     fn find(&self, session: &Session) -> Result<PathBuf, Report> {
         use std::fs;
 
+        if let Some(requirement) = self.version.as_ref() {
+            return session.registry()?.resolve(&self.name, requirement);
+        }
+
         for search_path in session.options.search_paths.iter() {
             let reader = fs::read_dir(search_path).map_err(|err| {
                 Report::msg(format!(
@@ -131,6 +162,11 @@ impl LinkLibrary {
                 if stem != self.name.as_ref() {
                     continue;
                 }
+                // A package may be located alongside the MAST library it was produced with
+                if self.kind == LibraryKind::Package && path.extension() != Some(OsStr::new("masp"))
+                {
+                    continue;
+                }
 
                 match self.kind {
                     LibraryKind::Mast => {
@@ -141,6 +177,14 @@ impl LinkLibrary {
                             )));
                         }
                     }
+                    LibraryKind::Package => {
+                        if !path.is_file() {
+                            return Err(Report::msg(format!(
+                                "unable to load package from '{}': not a file",
+                                path.display()
+                            )));
+                        }
+                    }
                     LibraryKind::Masm => {
                         if !path.is_dir() {
                             return Err(Report::msg(format!(
@@ -154,6 +198,17 @@ impl LinkLibrary {
             }
         }
 
+        // Packages which are not found in the search paths may have been published to the local
+        // package registry, in which case we use the latest version
+        if self.kind == LibraryKind::Package {
+            if let Ok(path) = session
+                .registry()
+                .and_then(|registry| registry.resolve(&self.name, &semver::VersionReq::STAR))
+            {
+                return Ok(path);
+            }
+        }
+
         Err(Report::msg(format!(
             "unable to locate library '{}' using any of the provided search paths",
             &self.name
@@ -184,6 +239,7 @@ impl clap::builder::TypedValueParser for LinkLibraryParser {
             [
                 PossibleValue::new("masm").help("A Miden Assembly project directory"),
                 PossibleValue::new("masl").help("A compiled MAST library file"),
+                PossibleValue::new("masp").help("A Miden package file"),
             ]
             .into_iter(),
         ))
@@ -191,10 +247,13 @@ impl clap::builder::TypedValueParser for LinkLibraryParser {
 
     /// Parses the `-l` flag using the following format:
     ///
-    /// `-l[KIND=]NAME`
+    /// `-l[KIND=]NAME[@VERSION]`
     ///
-    /// * `KIND` is one of: `masl`, `masm`; defaults to `masl`
+    /// * `KIND` is one of: `masl`, `masm`, `masp`; defaults to `masl`, or `masp` if `VERSION` is
+    ///   given
     /// * `NAME` is either an absolute path, or a name (without extension)
+    /// * `VERSION` is a semantic version requirement, e.g. `^1.2`, for a package to be resolved
+    ///   using the local package registry
     fn parse_ref(
         &self,
         _cmd: &clap::Command,
@@ -216,6 +275,48 @@ impl clap::builder::TypedValueParser for LinkLibraryParser {
             ));
         }
 
+        // A version requirement can only be given for a package requested by name
+        if let Some((name, version)) = name.split_once('@') {
+            if name.is_empty() || Path::new(name).components().count() > 1 {
+                return Err(Error::raw(
+                    ErrorKind::ValueValidation,
+                    "invalid link library: a version requirement can only be given for a library \
+                     requested by name",
+                ));
+            }
+            let version = version.parse::<semver::VersionReq>().map_err(|err| {
+                Error::raw(
+                    ErrorKind::ValueValidation,
+                    format!("invalid link library: invalid version requirement '{version}': {err}"),
+                )
+            })?;
+            let kind = match kind {
+                Some(kind) if !kind.is_empty() => kind.parse::<LibraryKind>().map_err(|_| {
+                    Error::raw(
+                        ErrorKind::InvalidValue,
+                        format!("'{kind}' is not a valid library kind"),
+                    )
+                })?,
+                Some(_) | None => LibraryKind::Package,
+            };
+            if kind != LibraryKind::Package {
+                return Err(Error::raw(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "invalid link library: a version requirement was given for '{name}', but \
+                         only packages can be requested by version"
+                    ),
+                ));
+            }
+
+            return Ok(LinkLibrary {
+                name: name.to_string().into(),
+                path: None,
+                kind,
+                version: Some(version),
+            });
+        }
+
         let maybe_path = Path::new(name);
         let extension = maybe_path.extension().map(|ext| ext.to_str().unwrap());
         let kind = match kind {
@@ -245,7 +346,7 @@ impl clap::builder::TypedValueParser for LinkLibraryParser {
             })?;
 
             match kind {
-                LibraryKind::Mast if !meta.is_file() => {
+                LibraryKind::Mast | LibraryKind::Package if !meta.is_file() => {
                     return Err(Error::raw(
                         ErrorKind::ValueValidation,
                         format!("invalid link library: '{}' is not a file", maybe_path.display()),
@@ -270,6 +371,7 @@ impl clap::builder::TypedValueParser for LinkLibraryParser {
                 name: name.into(),
                 path: Some(maybe_path.to_path_buf()),
                 kind,
+                version: None,
             })
         } else if extension.is_some() {
             let name = name.strip_suffix(unsafe { extension.unwrap_unchecked() }).unwrap();
@@ -280,13 +382,87 @@ impl clap::builder::TypedValueParser for LinkLibraryParser {
                 name: name.into(),
                 path: None,
                 kind,
+                version: None,
             })
         } else {
             Ok(LinkLibrary {
                 name: name.to_string().into(),
                 path: None,
                 kind,
+                version: None,
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::{builder::TypedValueParser, error::ErrorKind};
+
+    use super::*;
+
+    fn parse(value: &str) -> Result<LinkLibrary, clap::error::Error> {
+        LinkLibraryParser.parse_ref(&clap::Command::new("midenc"), None, OsStr::new(value))
+    }
+
+    #[test]
+    fn link_library_with_version() {
+        let version = semver::VersionReq::parse("^1.2").unwrap();
+        for value in ["foo@^1.2", "masp=foo@^1.2", "package=foo@^1.2", "=foo@^1.2"] {
+            let lib = parse(value).unwrap_or_else(|err| panic!("failed to parse '{value}': {err}"));
+            assert_eq!(
+                lib,
+                LinkLibrary {
+                    name: "foo".into(),
+                    path: None,
+                    kind: LibraryKind::Package,
+                    version: Some(version.clone()),
+                },
+                "unexpected result for '{value}'"
+            );
+        }
+
+        let lib = parse("foo@1.0.0").unwrap();
+        assert_eq!(lib.version, Some(semver::VersionReq::parse("^1.0.0").unwrap()));
+    }
+
+    #[test]
+    fn link_library_with_invalid_version() {
+        for value in ["foo@", "foo@latest", "foo@1.x.y", "foo@^1.2@3"] {
+            let err = parse(value).expect_err(value);
+            assert_eq!(err.kind(), ErrorKind::ValueValidation, "unexpected error for '{value}'");
+            assert!(
+                err.to_string().contains("invalid version requirement"),
+                "unexpected error for '{value}': {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn link_library_path_with_version() {
+        for value in ["@1.0", "./libs/foo@1.0", "/libs/foo@1.0", "masp=libs/foo@1.0"] {
+            let err = parse(value).expect_err(value);
+            assert_eq!(err.kind(), ErrorKind::ValueValidation, "unexpected error for '{value}'");
+            assert!(
+                err.to_string().contains("can only be given for a library requested by name"),
+                "unexpected error for '{value}': {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn link_library_kind_with_version() {
+        for value in ["masl=foo@1.0", "mast=foo@1.0", "masm=foo@1.0"] {
+            let err = parse(value).expect_err(value);
+            assert_eq!(err.kind(), ErrorKind::ValueValidation, "unexpected error for '{value}'");
+            assert!(
+                err.to_string().contains("only packages can be requested by version"),
+                "unexpected error for '{value}': {err}"
+            );
+        }
+
+        let err = parse("dylib=foo@1.0").expect_err("dylib=foo@1.0");
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        assert!(err.to_string().contains("'dylib' is not a valid library kind"), "{err}");
+    }
+}
@@ -7,10 +7,12 @@ use crate::{
 };
 
 /// This struct contains all of the configuration options for the compiler
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Options {
     /// The name of the program being compiled
     pub name: Option<String>,
+    /// The version of the program being compiled, if known
+    pub version: Option<semver::Version>,
     /// The type of project we're compiling this session
     pub project_type: ProjectType,
     /// The name of the function to call as the entrypoint
@@ -29,6 +31,8 @@ pub struct Options {
     pub link_libraries: Vec<LinkLibrary>,
     /// The location of the libraries which are shipped with the compiler
     pub sysroot: Option<PathBuf>,
+    /// The location of the local package registry, see [crate::Registry]
+    pub registry: Option<PathBuf>,
     /// Whether, and how, to color terminal output
     pub color: ColorChoice,
     /// The current diagnostics configuration
@@ -43,6 +47,8 @@ pub struct Options {
     pub link_only: bool,
     /// Generate Miden Assembly from the inputs without the linker
     pub no_link: bool,
+    /// Lower f64 operations to calls into the software floating-point intrinsics
+    pub soft_float: bool,
     /// Print CFG to stdout after each pass
     pub print_cfg_after_all: bool,
     /// Print CFG to stdout each time the named passes are applied
@@ -53,6 +59,8 @@ pub struct Options {
     pub print_ir_after_pass: Vec<String>,
     /// Save intermediate artifacts in memory during compilation
     pub save_temps: bool,
+    /// Compile the inputs a second time, and verify that the result is identical to the first
+    pub verify_reproducible: bool,
     /// We store any leftover argument matches in the session options for use
     /// by any downstream crates that register custom flags
     pub flags: CompileFlags,
@@ -86,6 +94,7 @@ impl Options {
 
         Self {
             name,
+            version: None,
             target,
             project_type,
             entrypoint: None,
@@ -95,6 +104,7 @@ impl Options {
             search_paths: vec![],
             link_libraries: vec![],
             sysroot,
+            registry: crate::Registry::default_root(),
             color: Default::default(),
             diagnostics: Default::default(),
             current_dir,
@@ -102,7 +112,9 @@ impl Options {
             analyze_only: false,
             link_only: false,
             no_link: false,
+            soft_float: false,
             save_temps: false,
+            verify_reproducible: false,
             print_cfg_after_all: false,
             print_cfg_after_pass: vec![],
             print_ir_after_all: false,
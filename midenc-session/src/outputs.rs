@@ -32,11 +32,21 @@ pub enum OutputType {
     /// The compiler will emit a MAST package in binary form
     #[default]
     Masp,
+    /// The compiler will emit a report of the estimated cycle counts of exported procedures
+    Cycles,
+    /// The compiler will emit the same report as [OutputType::Cycles], as JSON
+    CyclesJson,
+    /// The compiler will emit a linker map, describing where each global variable, data segment
+    /// and function of the linked program ended up
+    Map,
 }
 impl OutputType {
     /// Returns true if this output type is an intermediate artifact produced during compilation
     pub fn is_intermediate(&self) -> bool {
-        !matches!(self, Self::Mast | Self::Masl | Self::Masp)
+        !matches!(
+            self,
+            Self::Mast | Self::Masl | Self::Masp | Self::Cycles | Self::CyclesJson | Self::Map
+        )
     }
 
     pub fn extension(&self) -> &'static str {
@@ -47,22 +57,28 @@ impl OutputType {
             Self::Mast => "mast",
             Self::Masl => "masl",
             Self::Masp => "masp",
+            Self::Cycles => "cycles",
+            Self::CyclesJson => "cycles.json",
+            Self::Map => "map",
         }
     }
 
     pub fn shorthand_display() -> String {
         format!(
-            "`{}`, `{}`, `{}`, `{}`, `{}`, `{}`",
+            "`{}`, `{}`, `{}`, `{}`, `{}`, `{}`, `{}`, `{}`, `{}`",
             Self::Ast,
             Self::Hir,
             Self::Masm,
             Self::Mast,
             Self::Masl,
             Self::Masp,
+            Self::Cycles,
+            Self::CyclesJson,
+            Self::Map,
         )
     }
 
-    pub fn all() -> [OutputType; 6] {
+    pub fn all() -> [OutputType; 9] {
         [
             OutputType::Ast,
             OutputType::Hir,
@@ -70,6 +86,9 @@ impl OutputType {
             OutputType::Mast,
             OutputType::Masl,
             OutputType::Masp,
+            OutputType::Cycles,
+            OutputType::CyclesJson,
+            OutputType::Map,
         ]
     }
 }
@@ -82,6 +101,9 @@ impl fmt::Display for OutputType {
             Self::Mast => f.write_str("mast"),
             Self::Masl => f.write_str("masl"),
             Self::Masp => f.write_str("masp"),
+            Self::Cycles => f.write_str("cycles"),
+            Self::CyclesJson => f.write_str("cycles-json"),
+            Self::Map => f.write_str("map"),
         }
     }
 }
@@ -96,6 +118,9 @@ impl FromStr for OutputType {
             "mast" => Ok(Self::Mast),
             "masl" => Ok(Self::Masl),
             "masp" => Ok(Self::Masp),
+            "cycles" => Ok(Self::Cycles),
+            "cycles-json" => Ok(Self::CyclesJson),
+            "map" => Ok(Self::Map),
             _ => Err(()),
         }
     }
@@ -394,13 +419,25 @@ impl OutputTypes {
                     | OutputType::Mast
                     | OutputType::Masl
                     | OutputType::Masp
+                    | OutputType::Cycles
+                    | OutputType::CyclesJson
+                    | OutputType::Map
             )
         })
     }
 
     pub fn should_codegen(&self) -> bool {
         self.0.keys().any(|k| {
-            matches!(k, OutputType::Masm | OutputType::Mast | OutputType::Masl | OutputType::Masp)
+            matches!(
+                k,
+                OutputType::Masm
+                    | OutputType::Mast
+                    | OutputType::Masl
+                    | OutputType::Masp
+                    | OutputType::Cycles
+                    | OutputType::CyclesJson
+                    | OutputType::Map
+            )
         })
     }
 
@@ -448,6 +485,11 @@ impl clap::builder::TypedValueParser for OutputTypeParser {
                 PossibleValue::new("mast").help("Merkelized Abstract Syntax Tree (text)"),
                 PossibleValue::new("masl").help("Merkelized Abstract Syntax Tree (binary)"),
                 PossibleValue::new("masp").help("Miden Assembly Package Format (binary)"),
+                PossibleValue::new("cycles")
+                    .help("Estimated cycle counts of exported procedures (text)"),
+                PossibleValue::new("cycles-json")
+                    .help("Estimated cycle counts of exported procedures (JSON)"),
+                PossibleValue::new("map").help("Linker map of the program (text)"),
                 PossibleValue::new("all").help("All of the above"),
             ]
             .into_iter(),
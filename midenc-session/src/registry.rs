@@ -0,0 +1,158 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miden_core::crypto::hash::RpoDigest;
+use semver::{Version, VersionReq};
+
+use crate::diagnostics::{IntoDiagnostic, Report};
+
+/// A local, on-disk registry of compiled packages.
+///
+/// Packages are stored using the layout `<root>/<name>/<version>/<digest>.masp`, where `digest` is
+/// the hex-encoded MAST digest of the package. A published version of a package is immutable, so
+/// each version directory contains exactly one package.
+///
+/// Packages in the registry can be linked against by name and version requirement, e.g.
+/// `-l name@^1.2`, which resolves to the latest published version matching the requirement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registry {
+    root: PathBuf,
+}
+
+impl Registry {
+    /// The extension of the package files stored in the registry
+    const EXTENSION: &'static str = "masp";
+
+    /// Open the registry rooted at `root`, which need not exist yet
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The default location of the local registry, i.e. `~/.miden/registry`
+    pub fn default_root() -> Option<PathBuf> {
+        std::env::var("HOME")
+            .ok()
+            .map(|home| Path::new(&home).join(".miden").join("registry"))
+    }
+
+    /// The directory in which this registry is stored
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the path at which the build of version `version` of package `name`, with MAST digest
+    /// `digest`, is stored in this registry.
+    pub fn package_path(&self, name: &str, version: &Version, digest: &RpoDigest) -> PathBuf {
+        let digest = digest.to_hex();
+        let digest = digest.strip_prefix("0x").unwrap_or(&digest);
+        self.root
+            .join(name)
+            .join(version.to_string())
+            .join(digest)
+            .with_extension(Self::EXTENSION)
+    }
+
+    /// Get the versions of package `name` published to this registry, from oldest to newest
+    pub fn versions(&self, name: &str) -> Vec<Version> {
+        let Ok(reader) = fs::read_dir(self.root.join(name)) else {
+            return Vec::new();
+        };
+        let mut versions = reader
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<Version>().ok())
+            .collect::<Vec<_>>();
+        versions.sort();
+        versions
+    }
+
+    /// Find the newest version of package `name` which satisfies `requirement`, and return the
+    /// path to it.
+    pub fn resolve(&self, name: &str, requirement: &VersionReq) -> Result<PathBuf, Report> {
+        let versions = self.versions(name);
+        let Some(version) = versions.iter().rev().find(|version| requirement.matches(version))
+        else {
+            let available = if versions.is_empty() {
+                String::from("no versions have been published")
+            } else {
+                let versions = versions.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                format!("available versions are: {}", versions.join(", "))
+            };
+            return Err(Report::msg(format!(
+                "unable to find a version of '{name}' matching '{requirement}' in the package \
+                 registry at '{}': {available}",
+                self.root.display()
+            )));
+        };
+
+        self.find_package(&self.root.join(name).join(version.to_string()))
+    }
+
+    /// Get the path of the package stored in the version directory `dir`
+    fn find_package(&self, dir: &Path) -> Result<PathBuf, Report> {
+        let mut packages = fs::read_dir(dir)
+            .into_diagnostic()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path.extension().is_some_and(|extension| extension == Self::EXTENSION)
+            });
+        match (packages.next(), packages.next()) {
+            (Some(path), None) => Ok(path),
+            (None, _) => Err(Report::msg(format!(
+                "invalid package registry: '{}' does not contain a package",
+                dir.display()
+            ))),
+            (Some(_), Some(_)) => Err(Report::msg(format!(
+                "invalid package registry: '{}' contains more than one package",
+                dir.display()
+            ))),
+        }
+    }
+
+    /// Install the serialized package `bytes`, which is version `version` of package `name`, with
+    /// MAST digest `digest`, into this registry.
+    ///
+    /// Publishing the same build of a version again has no effect, but a different build of a
+    /// version that has already been published is rejected, as doing so would silently change
+    /// what packages which depend on that version are linked against.
+    ///
+    /// Returns the path at which the package was stored.
+    pub fn publish(
+        &self,
+        name: &str,
+        version: &Version,
+        digest: &RpoDigest,
+        bytes: &[u8],
+    ) -> Result<PathBuf, Report> {
+        let path = self.package_path(name, version, digest);
+        if path.is_file() {
+            return Ok(path);
+        }
+        let dir = path.parent().unwrap();
+        if let Ok(existing) = self.find_package(dir) {
+            return Err(Report::msg(format!(
+                "version {version} of '{name}' has already been published as '{}', with a \
+                 different digest: published versions cannot be changed, so you must increase the \
+                 version of the package to publish it",
+                existing.display()
+            )));
+        }
+
+        fs::create_dir_all(dir).into_diagnostic()?;
+        // Write to a temporary file first, so that a package in the registry is never incomplete
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes).into_diagnostic()?;
+        fs::rename(&tmp, &path).into_diagnostic()?;
+
+        Ok(path)
+    }
+}
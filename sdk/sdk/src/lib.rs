@@ -3,3 +3,21 @@
 pub use miden_base_sys::bindings::tx::*;
 pub use miden_sdk_alloc::BumpAlloc;
 pub use miden_stdlib_sys::*;
+
+/// Declare the account-component metadata of this crate, i.e. the storage slots it uses, and
+/// which of its exported procedures are account interface methods, given as TOML bytes:
+///
+/// ```ignore
+/// miden_sdk::account_component!(include_bytes!("../account-component.toml"));
+/// ```
+///
+/// The metadata is embedded in the `miden:account-component` custom section of the Wasm module,
+/// from which the compiler validates it and stores it in the manifest of the package.
+#[macro_export]
+macro_rules! account_component {
+    ($metadata:expr) => {
+        #[link_section = "miden:account-component"]
+        #[used]
+        static __MIDEN_ACCOUNT_COMPONENT: [u8; $metadata.len()] = *$metadata;
+    };
+}
@@ -1,4 +1,5 @@
 use std::{
+    ffi::OsStr,
     path::{Path, PathBuf},
     rc::Rc,
 };
@@ -9,10 +10,19 @@ use midenc_session::{
     InputFile, OutputType,
 };
 
+/// Compile the Wasm module at `wasm_file_path` to a Miden package in `output_folder`.
+///
+/// The package is linked against `dependencies`, which are packages in the local package
+/// registry, each given as `name@version-requirement`, e.g. `foo@^1.2`.
+///
+/// If `account_component` is given, it is the path to a TOML file containing the account-component
+/// metadata of the package.
 pub fn build_masm(
     wasm_file_path: &Path,
     output_folder: &Path,
     is_bin: bool,
+    dependencies: &[String],
+    account_component: Option<&Path>,
 ) -> Result<PathBuf, Report> {
     if !output_folder.exists() {
         return Err(Report::msg(format!(
@@ -32,7 +42,7 @@ pub fn build_masm(
         .join(wasm_file_path.file_stem().expect("invalid wasm file path: no file stem"))
         .with_extension(OutputType::Masp.extension());
     let project_type = if is_bin { "--exe" } else { "--lib" };
-    let args: Vec<&std::ffi::OsStr> = vec![
+    let mut args: Vec<&OsStr> = vec![
         "--output-dir".as_ref(),
         output_folder.as_os_str(),
         "-o".as_ref(),
@@ -42,6 +52,17 @@ pub fn build_masm(
         "--target".as_ref(),
         "rollup".as_ref(),
     ];
+    // Requesting any library replaces the default ones for the target, so they must be requested
+    // explicitly along with the dependencies
+    if !dependencies.is_empty() {
+        args.extend([OsStr::new("-l"), OsStr::new("std,base")]);
+        for dependency in dependencies {
+            args.extend([OsStr::new("-l"), OsStr::new(dependency)]);
+        }
+    }
+    if let Some(account_component) = account_component {
+        args.extend([OsStr::new("--account-component"), account_component.as_os_str()]);
+    }
     let session = Rc::new(Compiler::new_session([input], None, args));
     midenc_compile::compile(session.clone())?;
     Ok(output_file)
@@ -9,10 +9,49 @@ use crate::{
     target::{install_wasm32_wasi, WASM32_WASI_TARGET},
 };
 
+/// The name of the file, next to `Cargo.toml`, which describes a package as an account component
+const ACCOUNT_COMPONENT_FILE: &str = "account-component.toml";
+
 fn is_wasm_target(target: &str) -> bool {
     target == WASM32_WASI_TARGET
 }
 
+/// Get the Miden packages that `package` depends on, from the local package registry.
+///
+/// These are specified in the `[package.metadata.miden.dependencies]` table of its manifest,
+/// mapping the name of each package to a version requirement, e.g. `foo = "^1.2"`.
+fn miden_dependencies(package: &cargo_metadata::Package) -> Result<Vec<String>, Report> {
+    let Some(dependencies) =
+        package.metadata.get("miden").and_then(|miden| miden.get("dependencies"))
+    else {
+        return Ok(vec![]);
+    };
+    let dependencies = dependencies.as_object().ok_or_else(|| {
+        Report::msg(format!(
+            "invalid `package.metadata.miden.dependencies` in package `{}`: expected a table",
+            package.name
+        ))
+    })?;
+    dependencies
+        .iter()
+        .map(|(name, version)| match version.as_str() {
+            Some(version) => Ok(format!("{name}@{version}")),
+            None => Err(Report::msg(format!(
+                "invalid version requirement for Miden dependency `{name}` of package `{}`: \
+                 expected a string",
+                package.name
+            ))),
+        })
+        .collect()
+}
+
+/// Get the account-component metadata of `package`, i.e. the `account-component.toml` file next
+/// to its manifest, if there is one.
+fn account_component(package: &cargo_metadata::Package) -> Option<PathBuf> {
+    let path = package.manifest_path.parent()?.join(ACCOUNT_COMPONENT_FILE);
+    path.is_file().then(|| path.into_std_path_buf())
+}
+
 /// Runs the cargo command as specified in the configuration.
 ///
 /// Returns any relevant output artifacts.
@@ -110,18 +149,30 @@ pub fn run_cargo_command(
 
             for package in &metadata.packages {
                 let is_bin = package.targets.iter().any(|t| t.is_bin());
+                let dependencies = miden_dependencies(package)?;
+                let account_component = account_component(package);
 
                 // First try for <name>.wasm
                 let path = out_dir.join(&package.name).with_extension("wasm");
                 if path.exists() {
-                    let output =
-                        build_masm(path.as_std_path(), miden_out_dir.as_std_path(), is_bin)?;
+                    let output = build_masm(
+                        path.as_std_path(),
+                        miden_out_dir.as_std_path(),
+                        is_bin,
+                        &dependencies,
+                        account_component.as_deref(),
+                    )?;
                     outputs.push(output);
                 } else {
                     let path = out_dir.join(package.name.replace('-', "_")).with_extension("wasm");
                     if path.exists() {
-                        let output =
-                            build_masm(path.as_std_path(), miden_out_dir.as_std_path(), is_bin)?;
+                        let output = build_masm(
+                            path.as_std_path(),
+                            miden_out_dir.as_std_path(),
+                            is_bin,
+                            &dependencies,
+                            account_component.as_deref(),
+                        )?;
                         outputs.push(output);
                     } else {
                         log::debug!("no output found for package `{name}`", name = package.name);